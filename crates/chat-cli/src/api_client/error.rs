@@ -93,6 +93,11 @@ pub enum ApiClientError {
     // Credential errors
     #[error("failed to load credentials: {}", .0)]
     Credentials(CredentialsError),
+
+    /// A failure injected through `Q_FAULT_INJECT`
+    #[cfg(feature = "fault-injection")]
+    #[error("{0}")]
+    InjectedFault(String),
 }
 
 impl ApiClientError {
@@ -116,8 +121,20 @@ impl ApiClientError {
             Self::ModelOverloadedError { status_code, .. } => *status_code,
             Self::MonthlyLimitReached { status_code } => *status_code,
             Self::Credentials(_e) => None,
+            #[cfg(feature = "fault-injection")]
+            Self::InjectedFault(_) => None,
         }
     }
+
+    /// Whether this is an event of a chat response stream that couldn't be decoded. The events
+    /// after it can still be received, so it can be skipped rather than ending the response.
+    pub fn is_malformed_frame(&self) -> bool {
+        matches!(
+            self,
+            Self::CodewhispererChatResponseStream(SdkError::ResponseError(_))
+                | Self::QDeveloperChatResponseStream(SdkError::ResponseError(_))
+        )
+    }
 }
 
 impl ReasonCode for ApiClientError {
@@ -141,6 +158,8 @@ impl ReasonCode for ApiClientError {
             Self::ModelOverloadedError { .. } => "ModelOverloadedError".to_string(),
             Self::MonthlyLimitReached { .. } => "MonthlyLimitReached".to_string(),
             Self::Credentials(_) => "CredentialsError".to_string(),
+            #[cfg(feature = "fault-injection")]
+            Self::InjectedFault(_) => "InjectedFault".to_string(),
         }
     }
 }
@@ -223,4 +242,13 @@ mod tests {
             println!("{error} {error:?}");
         }
     }
+
+    #[test]
+    fn test_is_malformed_frame() {
+        assert!(all_errors().iter().all(|error| !error.is_malformed_frame()));
+        assert!(
+            ApiClientError::QDeveloperChatResponseStream(SdkError::response_error("<unmarshall>", raw_message()))
+                .is_malformed_frame()
+        );
+    }
 }
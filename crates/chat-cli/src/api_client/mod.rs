@@ -2,10 +2,13 @@ mod credentials;
 pub mod customization;
 mod endpoints;
 mod error;
+pub mod http_dump;
 pub mod model;
 mod opt_out;
 pub mod profile;
+pub mod scheduler;
 pub mod send_message_output;
+pub mod stream_parser;
 
 use std::sync::Arc;
 use std::time::Duration;
@@ -13,6 +16,8 @@ use std::time::Duration;
 use amzn_codewhisperer_client::Client as CodewhispererClient;
 use amzn_codewhisperer_client::operation::create_subscription_token::CreateSubscriptionTokenOutput;
 use amzn_codewhisperer_client::types::{
+    Completion,
+    FileContext,
     OptOutPreference,
     SubscriptionStatus,
     TelemetryEvent,
@@ -38,11 +43,16 @@ use tracing::{
 };
 
 use crate::api_client::credentials::CredentialsChain;
+use crate::api_client::http_dump::HttpDumpInterceptor;
 use crate::api_client::model::{
     ChatResponseStream,
     ConversationState,
 };
 use crate::api_client::opt_out::OptOutInterceptor;
+use crate::api_client::scheduler::{
+    RequestPriority,
+    Scheduler,
+};
 use crate::api_client::send_message_output::SendMessageOutput;
 use crate::auth::builder_id::BearerResolver;
 use crate::aws_common::{
@@ -73,6 +83,7 @@ pub struct ApiClient {
     sigv4_streaming_client: Option<QDeveloperStreamingClient>,
     mock_client: Option<Arc<Mutex<std::vec::IntoIter<Vec<ChatResponseStream>>>>>,
     profile: Option<AuthProfile>,
+    scheduler: Scheduler,
 }
 
 impl ApiClient {
@@ -99,6 +110,7 @@ impl ApiClient {
                 .http_client(crate::aws_common::http_client::client())
                 .interceptor(OptOutInterceptor::new(database))
                 .interceptor(UserAgentOverrideInterceptor::new())
+                .interceptor(HttpDumpInterceptor::new())
                 .bearer_token_resolver(BearerResolver)
                 .app_name(app_name())
                 .endpoint_url(endpoint.url())
@@ -112,6 +124,7 @@ impl ApiClient {
                 sigv4_streaming_client: None,
                 mock_client: None,
                 profile: None,
+                scheduler: Scheduler::default(),
             };
 
             if let Ok(json) = env.get("Q_MOCK_CHAT_RESPONSE") {
@@ -144,6 +157,7 @@ impl ApiClient {
                     .http_client(crate::aws_common::http_client::client())
                     .interceptor(OptOutInterceptor::new(database))
                     .interceptor(UserAgentOverrideInterceptor::new())
+                    .interceptor(HttpDumpInterceptor::new())
                     .app_name(app_name())
                     .endpoint_url(endpoint.url())
                     .stalled_stream_protection(stalled_stream_protection_config())
@@ -156,6 +170,7 @@ impl ApiClient {
                         .http_client(crate::aws_common::http_client::client())
                         .interceptor(OptOutInterceptor::new(database))
                         .interceptor(UserAgentOverrideInterceptor::new())
+                        .interceptor(HttpDumpInterceptor::new())
                         .bearer_token_resolver(BearerResolver)
                         .app_name(app_name())
                         .endpoint_url(endpoint.url())
@@ -179,6 +194,7 @@ impl ApiClient {
             sigv4_streaming_client,
             mock_client: None,
             profile,
+            scheduler: Scheduler::new(&database.settings),
         })
     }
 
@@ -248,9 +264,59 @@ impl ApiClient {
             .map_err(ApiClientError::CreateSubscriptionToken)
     }
 
+    /// Requests inline completions at the cursor described by `file_context`, best first.
+    pub async fn generate_completions(
+        &self,
+        file_context: FileContext,
+        max_results: i32,
+    ) -> Result<Vec<Completion>, ApiClientError> {
+        if cfg!(test) {
+            return Ok(vec![
+                Completion::builder()
+                    .content(format!("// completion for {}", file_context.filename()))
+                    .build()?,
+            ]);
+        }
+
+        let output = self
+            .client
+            .generate_completions()
+            .file_context(file_context)
+            .max_results(max_results)
+            .set_profile_arn(self.profile.as_ref().map(|p| p.arn.clone()))
+            .send()
+            .await?;
+
+        Ok(output.completions.unwrap_or_default())
+    }
+
+    /// Sets whether requests are made for someone at a prompt, which are sent ahead of background
+    /// ones when `api.maxConcurrentRequests` are being streamed already.
+    pub fn set_request_priority(&mut self, priority: RequestPriority) {
+        self.scheduler.set_priority(priority);
+    }
+
     pub async fn send_message(&self, conversation: ConversationState) -> Result<SendMessageOutput, ApiClientError> {
+        let permit = self.scheduler.acquire().await;
+        let output = self.send_message_unscheduled(conversation).await?;
+        Ok(match permit {
+            Some(permit) => SendMessageOutput::Scheduled {
+                output: Box::new(output),
+                _permit: permit,
+            },
+            None => output,
+        })
+    }
+
+    async fn send_message_unscheduled(
+        &self,
+        conversation: ConversationState,
+    ) -> Result<SendMessageOutput, ApiClientError> {
         debug!("Sending conversation: {:#?}", conversation);
 
+        #[cfg(feature = "fault-injection")]
+        crate::util::fault_injection::injector().send_message().await?;
+
         let ConversationState {
             conversation_id,
             user_input_message,
@@ -423,6 +489,15 @@ impl ApiClient {
         }
     }
 
+    /// Answers each request with the next of `responses`, in the format of `Q_MOCK_CHAT_RESPONSE`,
+    /// instead of sending it to the service. Used by `q internal e2e` to smoke test builds
+    /// without credentials.
+    pub fn use_mock_backend(&mut self, responses: serde_json::Value) {
+        self.streaming_client = None;
+        self.sigv4_streaming_client = None;
+        self.set_mock_output(responses);
+    }
+
     /// Only meant for testing. Do not use outside of testing responses.
     pub fn set_mock_output(&mut self, json: serde_json::Value) {
         let mut mock = Vec::new();
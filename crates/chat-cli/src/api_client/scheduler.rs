@@ -0,0 +1,206 @@
+//! Limits how many responses are streamed from the service at once across every running session,
+//! to `api.maxConcurrentRequests`. Each request takes one of that many slots, which are lock files
+//! in the runtime directory, and holds it until its response is dropped. Locks are released by the
+//! OS when a session exits, so a crashed session can't keep a slot.
+//!
+//! Interactive requests take any free slot and check often. Background requests, such as those of
+//! `q chat --no-interactive`, leave the first slot to interactive sessions and don't take a slot
+//! while an interactive request is waiting for one, so a batch job can't hold up a chat.
+
+use tracing::warn;
+
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+/// How many requests are streamed at once unless `api.maxConcurrentRequests` says otherwise.
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// Who is waiting for the response of a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RequestPriority {
+    /// Someone at a prompt
+    #[default]
+    Interactive,
+    /// A session running on its own, such as `q chat --no-interactive`
+    Background,
+}
+
+/// Returns the number of requests streamed at once, or [None] if `api.maxConcurrentRequests` is 0.
+fn max_concurrent_requests(settings: &Settings) -> Option<usize> {
+    match settings.get_int(Setting::ApiMaxConcurrentRequests) {
+        Some(limit) if limit <= 0 => None,
+        Some(limit) => usize::try_from(limit).ok(),
+        None => Some(DEFAULT_MAX_CONCURRENT_REQUESTS),
+    }
+}
+
+/// The slots a request of `priority` can take when there are `limit` of them.
+fn slots(limit: usize, priority: RequestPriority) -> std::ops::Range<usize> {
+    match priority {
+        RequestPriority::Background if limit > 1 => 1..limit,
+        _ => 0..limit,
+    }
+}
+
+/// Hands out the slots of requests. The default doesn't limit them, e.g. for tests.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    limit: Option<usize>,
+    priority: RequestPriority,
+}
+
+impl Scheduler {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            limit: max_concurrent_requests(settings),
+            priority: RequestPriority::default(),
+        }
+    }
+
+    pub fn set_priority(&mut self, priority: RequestPriority) {
+        self.priority = priority;
+    }
+
+    /// Waits for a free slot. Requests are sent without one if the slots can't be used, e.g. when
+    /// the runtime directory isn't writable, rather than not at all.
+    pub async fn acquire(&self) -> Option<RequestPermit> {
+        let limit = self.limit?;
+        match imp::acquire(limit, self.priority).await {
+            Ok(permit) => Some(permit),
+            Err(err) => {
+                warn!(?err, "Failed to take a request slot, sending the request without one");
+                None
+            },
+        }
+    }
+}
+
+pub use imp::RequestPermit;
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::{
+        File,
+        OpenOptions,
+    };
+    use std::os::fd::AsRawFd;
+    use std::path::{
+        Path,
+        PathBuf,
+    };
+    use std::time::Duration;
+
+    use super::*;
+
+    const INTERACTIVE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+    const BACKGROUND_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// A slot held until the response it was taken for is dropped.
+    #[derive(Debug)]
+    pub struct RequestPermit {
+        _slot: File,
+    }
+
+    fn slots_dir() -> std::io::Result<PathBuf> {
+        crate::util::directories::runtime_dir()
+            .map(|dir| dir.join("qrequests"))
+            .map_err(std::io::Error::other)
+    }
+
+    fn open(path: &Path) -> std::io::Result<File> {
+        OpenOptions::new().create(true).truncate(false).write(true).open(path)
+    }
+
+    /// Locks `file` without waiting, returning whether it was locked.
+    fn try_lock(file: &File, operation: libc::c_int) -> std::io::Result<bool> {
+        // SAFETY: flock only operates on the descriptor, which stays open for the call
+        if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+            return Ok(true);
+        }
+        let err = std::io::Error::last_os_error();
+        match err.kind() {
+            std::io::ErrorKind::WouldBlock => Ok(false),
+            _ => Err(err),
+        }
+    }
+
+    pub async fn acquire(limit: usize, priority: RequestPriority) -> std::io::Result<RequestPermit> {
+        let dir = slots_dir()?;
+        std::fs::create_dir_all(&dir)?;
+        // Interactive requests hold a shared lock on this file while they wait, which background
+        // requests check for with an exclusive one.
+        let waiting_path = dir.join("waiting.lock");
+        let mut waiting = None;
+
+        loop {
+            let yield_to_interactive = priority == RequestPriority::Background && {
+                let waiting = open(&waiting_path)?;
+                !try_lock(&waiting, libc::LOCK_EX)?
+            };
+            if !yield_to_interactive {
+                for i in slots(limit, priority) {
+                    let slot = open(&dir.join(format!("slot-{i}.lock")))?;
+                    if try_lock(&slot, libc::LOCK_EX)? {
+                        return Ok(RequestPermit { _slot: slot });
+                    }
+                }
+            }
+
+            let interval = match priority {
+                RequestPriority::Interactive => {
+                    if waiting.is_none() {
+                        let file = open(&waiting_path)?;
+                        if try_lock(&file, libc::LOCK_SH)? {
+                            waiting = Some(file);
+                        }
+                    }
+                    INTERACTIVE_POLL_INTERVAL
+                },
+                RequestPriority::Background => BACKGROUND_POLL_INTERVAL,
+            };
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::*;
+
+    /// Slots are only shared between sessions on macOS and Linux, elsewhere requests aren't
+    /// limited.
+    #[derive(Debug)]
+    pub struct RequestPermit;
+
+    pub async fn acquire(_limit: usize, _priority: RequestPriority) -> std::io::Result<RequestPermit> {
+        Ok(RequestPermit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_max_concurrent_requests() {
+        let mut settings = Settings::new().await.unwrap();
+        assert_eq!(
+            max_concurrent_requests(&settings),
+            Some(DEFAULT_MAX_CONCURRENT_REQUESTS)
+        );
+        settings.set(Setting::ApiMaxConcurrentRequests, 2).await.unwrap();
+        assert_eq!(max_concurrent_requests(&settings), Some(2));
+        settings.set(Setting::ApiMaxConcurrentRequests, 0).await.unwrap();
+        assert_eq!(max_concurrent_requests(&settings), None);
+    }
+
+    #[test]
+    fn test_slots() {
+        assert_eq!(slots(4, RequestPriority::Interactive), 0..4);
+        assert_eq!(slots(4, RequestPriority::Background), 1..4);
+        // With a single slot, background requests still get to run
+        assert_eq!(slots(1, RequestPriority::Background), 0..1);
+    }
+}
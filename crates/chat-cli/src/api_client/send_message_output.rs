@@ -2,6 +2,7 @@ use aws_types::request_id::RequestId;
 
 use crate::api_client::ApiClientError;
 use crate::api_client::model::ChatResponseStream;
+use crate::api_client::scheduler::RequestPermit;
 
 #[derive(Debug)]
 pub enum SendMessageOutput {
@@ -10,6 +11,12 @@ pub enum SendMessageOutput {
     ),
     QDeveloper(amzn_qdeveloper_streaming_client::operation::send_message::SendMessageOutput),
     Mock(Vec<ChatResponseStream>),
+    /// A response holding a slot of the [scheduler](crate::api_client::scheduler) until it is
+    /// dropped
+    Scheduled {
+        output: Box<SendMessageOutput>,
+        _permit: RequestPermit,
+    },
 }
 
 impl SendMessageOutput {
@@ -18,6 +25,7 @@ impl SendMessageOutput {
             SendMessageOutput::Codewhisperer(output) => output.request_id(),
             SendMessageOutput::QDeveloper(output) => output.request_id(),
             SendMessageOutput::Mock(_) => None,
+            SendMessageOutput::Scheduled { output, .. } => output.request_id(),
         }
     }
 
@@ -30,6 +38,7 @@ impl SendMessageOutput {
                 .map(|s| s.into())),
             SendMessageOutput::QDeveloper(output) => Ok(output.send_message_response.recv().await?.map(|s| s.into())),
             SendMessageOutput::Mock(vec) => Ok(vec.pop()),
+            SendMessageOutput::Scheduled { output, .. } => Box::pin(output.recv()).await,
         }
     }
 }
@@ -40,6 +49,7 @@ impl RequestId for SendMessageOutput {
             SendMessageOutput::Codewhisperer(output) => output.request_id(),
             SendMessageOutput::QDeveloper(output) => output.request_id(),
             SendMessageOutput::Mock(_) => Some("<mock-request-id>"),
+            SendMessageOutput::Scheduled { output, .. } => RequestId::request_id(output.as_ref()),
         }
     }
 }
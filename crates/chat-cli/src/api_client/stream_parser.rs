@@ -0,0 +1,331 @@
+//! Parsing of the event stream of a chat response into text and tool uses, independently of how
+//! the events are received. [StreamParser] is fed one [Frame] at a time and never blocks, which
+//! keeps it usable outside of the chat session and lets it be fuzzed.
+//!
+//! Frames that couldn't be decoded are skipped with a warning rather than ending the response,
+//! unless too many of them arrive in a row, which means the stream itself is broken.
+
+use std::collections::VecDeque;
+
+use thiserror::Error;
+use tracing::{
+    error,
+    warn,
+};
+
+use super::model::ChatResponseStream;
+
+/// How many malformed frames in a row are skipped before the stream is considered broken.
+pub const MAX_CONSECUTIVE_MALFORMED_FRAMES: usize = 5;
+
+/// A frame of the event stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Event(ChatResponseStream),
+    /// A frame that couldn't be decoded, with the reason why.
+    Malformed(String),
+}
+
+/// What a chat response is made of, in the order it is received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// Text of the response.
+    Text(String),
+    /// The start of a tool use, before its input is received.
+    ToolUseStart { id: String, name: String },
+    /// A tool use with its whole input, which is supposed to be JSON but isn't checked to be.
+    ToolUse { id: String, name: String, input: String },
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum StreamError {
+    #[error("received {count} malformed events in a row, the last one: {last}")]
+    MalformedFrames { count: usize, last: String },
+}
+
+#[derive(Debug)]
+struct PartialToolUse {
+    id: String,
+    name: String,
+    input: String,
+}
+
+#[derive(Debug, Default)]
+pub struct StreamParser {
+    /// Text held back until the next event shows whether it is followed by a code reference, in
+    /// which case it is dropped.
+    pending_text: Option<String>,
+    tool_use: Option<PartialToolUse>,
+    malformed_frames: usize,
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the next frame, adding the events it completes to `events`.
+    pub fn push(&mut self, frame: Frame, events: &mut VecDeque<StreamEvent>) -> Result<(), StreamError> {
+        let event = match frame {
+            Frame::Event(event) => event,
+            Frame::Malformed(reason) => {
+                self.malformed_frames += 1;
+                warn!(%reason, count = self.malformed_frames, "Skipping a malformed event of the response");
+                if self.malformed_frames >= MAX_CONSECUTIVE_MALFORMED_FRAMES {
+                    return Err(StreamError::MalformedFrames {
+                        count: self.malformed_frames,
+                        last: reason,
+                    });
+                }
+                return Ok(());
+            },
+        };
+        self.malformed_frames = 0;
+
+        match event {
+            ChatResponseStream::CodeReferenceEvent(_) => {
+                self.pending_text = None;
+            },
+            ChatResponseStream::ToolUseEvent {
+                tool_use_id,
+                name,
+                input,
+                stop,
+            } => {
+                // Tool use events continue the current tool use unless they are for another one
+                if self
+                    .tool_use
+                    .as_ref()
+                    .is_some_and(|tool_use| tool_use.id != tool_use_id)
+                {
+                    events.extend(self.finish_tool_use());
+                }
+                events.extend(self.pending_text.take().map(StreamEvent::Text));
+                let tool_use = self.tool_use.get_or_insert_with(|| {
+                    events.push_back(StreamEvent::ToolUseStart {
+                        id: tool_use_id.clone(),
+                        name: name.clone(),
+                    });
+                    PartialToolUse {
+                        id: tool_use_id,
+                        name,
+                        input: String::new(),
+                    }
+                });
+                tool_use.input.push_str(input.as_deref().unwrap_or_default());
+                if stop == Some(true) {
+                    events.extend(self.finish_tool_use());
+                }
+            },
+            event => {
+                events.extend(self.finish_tool_use());
+                events.extend(self.pending_text.take().map(StreamEvent::Text));
+                match event {
+                    ChatResponseStream::AssistantResponseEvent { content } => self.pending_text = Some(content),
+                    ChatResponseStream::InvalidStateEvent { reason, message } => {
+                        error!(%reason, %message, "invalid state event");
+                    },
+                    _ => (),
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Adds the events left once the stream has ended to `events`. A tool use still being
+    /// received is completed with the input received so far.
+    pub fn finish(&mut self, events: &mut VecDeque<StreamEvent>) {
+        events.extend(self.finish_tool_use());
+        events.extend(self.pending_text.take().map(StreamEvent::Text));
+    }
+
+    fn finish_tool_use(&mut self) -> Option<StreamEvent> {
+        self.tool_use.take().map(|tool_use| StreamEvent::ToolUse {
+            id: tool_use.id,
+            name: tool_use.name,
+            input: tool_use.input,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{
+        Rng,
+        SeedableRng,
+    };
+
+    use super::*;
+
+    fn text(content: &str) -> Frame {
+        Frame::Event(ChatResponseStream::AssistantResponseEvent {
+            content: content.to_string(),
+        })
+    }
+
+    fn tool(id: &str, input: Option<&str>, stop: Option<bool>) -> Frame {
+        Frame::Event(ChatResponseStream::ToolUseEvent {
+            tool_use_id: id.to_string(),
+            name: format!("tool_{id}"),
+            input: input.map(str::to_string),
+            stop,
+        })
+    }
+
+    fn parse(frames: impl IntoIterator<Item = Frame>) -> Result<Vec<StreamEvent>, StreamError> {
+        let mut parser = StreamParser::new();
+        let mut events = VecDeque::new();
+        for frame in frames {
+            parser.push(frame, &mut events)?;
+        }
+        parser.finish(&mut events);
+        Ok(events.into())
+    }
+
+    #[test]
+    fn test_parse() {
+        let frames = [
+            text("hi"),
+            text(" there"),
+            text("IGNORE ME PLEASE"),
+            Frame::Event(ChatResponseStream::CodeReferenceEvent(())),
+            tool("1", None, None),
+            tool("1", Some(r#"{"command""#), None),
+            tool("1", Some(r#": "echo hello"}"#), None),
+            tool("1", None, Some(true)),
+            tool("2", Some("{}"), None),
+        ];
+        assert_eq!(parse(frames).unwrap(), [
+            StreamEvent::Text("hi".to_string()),
+            StreamEvent::Text(" there".to_string()),
+            StreamEvent::ToolUseStart {
+                id: "1".to_string(),
+                name: "tool_1".to_string(),
+            },
+            StreamEvent::ToolUse {
+                id: "1".to_string(),
+                name: "tool_1".to_string(),
+                input: r#"{"command": "echo hello"}"#.to_string(),
+            },
+            StreamEvent::ToolUseStart {
+                id: "2".to_string(),
+                name: "tool_2".to_string(),
+            },
+            StreamEvent::ToolUse {
+                id: "2".to_string(),
+                name: "tool_2".to_string(),
+                input: "{}".to_string(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_malformed_frames() {
+        let malformed = || Frame::Malformed("<unmarshall>".to_string());
+        let frames = [text("a"), malformed(), text("b"), malformed(), malformed(), text("c")];
+        assert_eq!(
+            parse(frames).unwrap(),
+            parse([text("a"), text("b"), text("c")]).unwrap()
+        );
+
+        let frames = std::iter::once(text("a")).chain(std::iter::repeat_with(malformed).take(10));
+        assert_eq!(
+            parse(frames),
+            Err(StreamError::MalformedFrames {
+                count: MAX_CONSECUTIVE_MALFORMED_FRAMES,
+                last: "<unmarshall>".to_string(),
+            })
+        );
+    }
+
+    fn random_frame(rng: &mut StdRng) -> Frame {
+        let random_string = |rng: &mut StdRng| {
+            let len = rng.random_range(0..8);
+            (0..len).map(|_| rng.random_range(' '..='~')).collect::<String>()
+        };
+        let event = match rng.random_range(0..9) {
+            0..=2 => ChatResponseStream::AssistantResponseEvent {
+                content: random_string(rng),
+            },
+            3 => ChatResponseStream::CodeReferenceEvent(()),
+            4..=6 => ChatResponseStream::ToolUseEvent {
+                tool_use_id: rng.random_range(0..3).to_string(),
+                name: "tool".to_string(),
+                input: rng.random_bool(0.7).then(|| random_string(rng)),
+                stop: rng.random_bool(0.5).then(|| rng.random_bool(0.5)),
+            },
+            7 => ChatResponseStream::InvalidStateEvent {
+                reason: random_string(rng),
+                message: random_string(rng),
+            },
+            _ => ChatResponseStream::MessageMetadataEvent {
+                conversation_id: None,
+                utterance_id: None,
+            },
+        };
+        Frame::Event(event)
+    }
+
+    /// Parses random streams, checking that every tool use is started once and completed once,
+    /// that no text or tool input is lost or invented, and that malformed frames change nothing.
+    #[test]
+    fn test_fuzz_parse() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..2000 {
+            let len = rng.random_range(0..40);
+            let frames = (0..len).map(|_| random_frame(&mut rng)).collect::<Vec<_>>();
+            let events = parse(frames.clone()).unwrap();
+
+            let mut expected_text = String::new();
+            let mut expected_input = String::new();
+            for (i, frame) in frames.iter().enumerate() {
+                match frame {
+                    Frame::Event(ChatResponseStream::AssistantResponseEvent { content })
+                        if frames.get(i + 1) != Some(&Frame::Event(ChatResponseStream::CodeReferenceEvent(()))) =>
+                    {
+                        expected_text.push_str(content);
+                    },
+                    Frame::Event(ChatResponseStream::ToolUseEvent { input: Some(input), .. }) => {
+                        expected_input.push_str(input);
+                    },
+                    _ => (),
+                }
+            }
+
+            let mut text = String::new();
+            let mut input = String::new();
+            let mut started = None;
+            for event in &events {
+                match event {
+                    StreamEvent::Text(content) => {
+                        assert!(started.is_none(), "text in the middle of a tool use: {frames:?}");
+                        text.push_str(content);
+                    },
+                    StreamEvent::ToolUseStart { id, .. } => {
+                        assert!(started.is_none(), "tool use started twice: {frames:?}");
+                        started = Some(id.clone());
+                    },
+                    StreamEvent::ToolUse {
+                        id, input: tool_input, ..
+                    } => {
+                        assert_eq!(started.take().as_ref(), Some(id), "tool use not started: {frames:?}");
+                        input.push_str(tool_input);
+                    },
+                }
+            }
+            assert!(started.is_none(), "tool use never completed: {frames:?}");
+            assert_eq!(text, expected_text, "{frames:?}");
+            assert_eq!(input, expected_input, "{frames:?}");
+
+            // Fewer malformed frames in a row than the limit are skipped without a trace
+            let mut with_malformed = Vec::new();
+            for frame in frames {
+                let count = rng.random_range(0..MAX_CONSECUTIVE_MALFORMED_FRAMES);
+                with_malformed.extend(std::iter::repeat_n(Frame::Malformed("<malformed>".to_string()), count));
+                with_malformed.push(frame);
+            }
+            assert_eq!(parse(with_malformed).unwrap(), events);
+        }
+    }
+}
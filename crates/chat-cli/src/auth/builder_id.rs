@@ -111,7 +111,7 @@ pub struct DeviceRegistration {
 }
 
 impl DeviceRegistration {
-    const SECRET_KEY: &'static str = "codewhisperer:odic:device-registration";
+    pub(super) const SECRET_KEY: &'static str = "codewhisperer:odic:device-registration";
 
     pub fn from_output(
         output: RegisterClientOutput,
@@ -286,7 +286,7 @@ pub struct BuilderIdToken {
 }
 
 impl BuilderIdToken {
-    const SECRET_KEY: &'static str = "codewhisperer:odic:token";
+    pub(super) const SECRET_KEY: &'static str = "codewhisperer:odic:token";
 
     #[cfg(test)]
     fn test() -> Self {
@@ -464,6 +464,11 @@ impl BuilderIdToken {
         }
     }
 
+    /// The region of the OIDC client the token was issued to.
+    pub fn oidc_region(&self) -> Region {
+        self.region.clone().map_or(OIDC_BUILDER_ID_REGION, Region::new)
+    }
+
     /// Check if the token is for the internal amzn start URL (`https://amzn.awsapps.com/start`),
     /// this implies the user will use midway for private specs
     pub fn is_amzn_user(&self) -> bool {
@@ -0,0 +1,156 @@
+//! Logins saved under an alias, so that people working with several identities, like a personal
+//! Builder ID and the IAM Identity Center of a client, can switch between them without going
+//! through the browser again.
+//!
+//! The active login is kept where it always is. Switching saves it under its alias and restores the
+//! saved login in its place, whose token is refreshed as usual once it expired.
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::AuthError;
+use super::builder_id::{
+    BuilderIdToken,
+    DeviceRegistration,
+};
+use crate::database::{
+    AuthProfile,
+    Database,
+};
+
+/// The alias a login that was active before it was given one is saved under.
+pub const DEFAULT_ALIAS: &str = "default";
+
+const SECRET_KEY_PREFIX: &str = "codewhisperer:odic:identity:";
+
+/// A login as saved in the secret store.
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedIdentity {
+    /// The token, as stored for the active login
+    token: String,
+    /// The client the token was issued to, needed to refresh it
+    registration: Option<String>,
+    profile: Option<AuthProfile>,
+}
+
+fn secret_key(alias: &str) -> String {
+    format!("{SECRET_KEY_PREFIX}{alias}")
+}
+
+/// Saves the active login under its alias, or under [DEFAULT_ALIAS] if it has none, returning the
+/// alias. Does nothing when logged out.
+pub async fn save_active(database: &Database) -> Result<Option<String>, AuthError> {
+    let Some(token) = database.get_secret(BuilderIdToken::SECRET_KEY).await? else {
+        return Ok(None);
+    };
+    let alias = database
+        .get_active_identity()?
+        .unwrap_or_else(|| DEFAULT_ALIAS.to_string());
+    let saved = SavedIdentity {
+        token: token.0,
+        registration: database
+            .get_secret(DeviceRegistration::SECRET_KEY)
+            .await?
+            .map(|registration| registration.0),
+        profile: database.get_auth_profile()?,
+    };
+    database
+        .set_secret(&secret_key(&alias), &serde_json::to_string(&saved)?)
+        .await?;
+
+    let mut aliases = database.get_identity_aliases()?;
+    if !aliases.contains(&alias) {
+        aliases.push(alias.clone());
+        database.set_identity_aliases(&aliases)?;
+    }
+    database.set_active_identity(Some(&alias))?;
+    Ok(Some(alias))
+}
+
+/// Makes the login saved as `alias` the active one, after saving the active login. Returns false
+/// if no login is saved as `alias`.
+pub async fn switch(database: &mut Database, alias: &str) -> Result<bool, AuthError> {
+    let Some(saved) = database.get_secret(&secret_key(alias)).await? else {
+        return Ok(false);
+    };
+    let saved: SavedIdentity = serde_json::from_str(&saved.0)?;
+    save_active(database).await?;
+
+    database.set_secret(BuilderIdToken::SECRET_KEY, &saved.token).await?;
+    match &saved.registration {
+        Some(registration) => {
+            database
+                .set_secret(DeviceRegistration::SECRET_KEY, registration)
+                .await?;
+        },
+        None => database.delete_secret(DeviceRegistration::SECRET_KEY).await?,
+    }
+    match &saved.profile {
+        Some(profile) => database.set_auth_profile(profile)?,
+        None => database.unset_auth_profile()?,
+    }
+    database.set_active_identity(Some(alias))?;
+    Ok(true)
+}
+
+/// Forgets the saved copy of the active login, when logging out of it.
+pub async fn forget_active(database: &Database) -> Result<(), AuthError> {
+    let Some(alias) = database.get_active_identity()? else {
+        return Ok(());
+    };
+    database.delete_secret(&secret_key(&alias)).await?;
+    let mut aliases = database.get_identity_aliases()?;
+    aliases.retain(|saved| *saved != alias);
+    database.set_identity_aliases(&aliases)?;
+    database.set_active_identity(None)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_switch() {
+        let mut database = Database::new().await.unwrap();
+        assert_eq!(save_active(&database).await.unwrap(), None);
+        assert!(!switch(&mut database, "work").await.unwrap());
+
+        let work = AuthProfile {
+            arn: "arn:aws:codewhisperer:us-east-1:123456789012:profile/WORK".to_string(),
+            profile_name: "work".to_string(),
+        };
+        database
+            .set_secret(BuilderIdToken::SECRET_KEY, "work token")
+            .await
+            .unwrap();
+        database.set_auth_profile(&work).unwrap();
+        database.set_active_identity(Some("work")).unwrap();
+        assert_eq!(save_active(&database).await.unwrap().as_deref(), Some("work"));
+
+        // Logging in again without an alias, the login is saved as the default one when switching
+        database.set_active_identity(None).unwrap();
+        database.unset_auth_profile().unwrap();
+        database
+            .set_secret(BuilderIdToken::SECRET_KEY, "personal token")
+            .await
+            .unwrap();
+        assert!(switch(&mut database, "work").await.unwrap());
+        let token = database.get_secret(BuilderIdToken::SECRET_KEY).await.unwrap().unwrap();
+        assert_eq!(token.0, "work token");
+        assert_eq!(database.get_auth_profile().unwrap().unwrap().arn, work.arn);
+        assert_eq!(database.get_active_identity().unwrap().as_deref(), Some("work"));
+        assert_eq!(database.get_identity_aliases().unwrap(), ["work", DEFAULT_ALIAS]);
+
+        assert!(switch(&mut database, DEFAULT_ALIAS).await.unwrap());
+        let token = database.get_secret(BuilderIdToken::SECRET_KEY).await.unwrap().unwrap();
+        assert_eq!(token.0, "personal token");
+        assert!(database.get_auth_profile().unwrap().is_none());
+
+        forget_active(&database).await.unwrap();
+        assert_eq!(database.get_active_identity().unwrap(), None);
+        assert_eq!(database.get_identity_aliases().unwrap(), ["work"]);
+    }
+}
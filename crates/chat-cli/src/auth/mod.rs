@@ -1,6 +1,8 @@
 pub mod builder_id;
 mod consts;
+pub mod identities;
 pub mod pkce;
+pub mod refresher;
 mod scope;
 
 use aws_sdk_ssooidc::error::SdkError;
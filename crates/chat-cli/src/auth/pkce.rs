@@ -18,6 +18,10 @@
 //!
 //! Once access/refresh tokens are received, there is no difference between PKCE
 //! and device code (as already implemented in [crate::builder_id]).
+//!
+//! Where the browser can't reach a local server, like over SSH, [PkceRegistration::register_manual]
+//! skips step 2: the user pastes the URL the browser was redirected to, or the code in it, to
+//! [PkceRegistration::finish_manual].
 
 use std::future::Future;
 use std::pin::Pin;
@@ -41,6 +45,7 @@ use hyper::{
 use hyper_util::rt::TokioIo;
 use percent_encoding::{
     NON_ALPHANUMERIC,
+    percent_decode_str,
     utf8_percent_encode,
 };
 use rand::Rng;
@@ -60,6 +65,10 @@ use crate::database::Database;
 
 const DEFAULT_AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(60 * 3);
 
+/// The redirect URI of a registration without a local server. The browser fails to load it, but
+/// shows the code in the address bar.
+const MANUAL_REDIRECT_URI: &str = "http://127.0.0.1/oauth/callback";
+
 /// Starts the PKCE authorization flow, using [`START_URL`] and [`OIDC_BUILDER_ID_REGION`] as the
 /// default issuer URL and region. Returns the [`PkceClient`] to use to finish the flow.
 pub async fn start_pkce_authorization(
@@ -73,6 +82,18 @@ pub async fn start_pkce_authorization(
     Ok((client, registration))
 }
 
+/// Like [`start_pkce_authorization`], for a flow finished with [`PkceRegistration::finish_manual`].
+pub async fn start_manual_pkce_authorization(
+    start_url: Option<String>,
+    region: Option<String>,
+) -> Result<(Client, PkceRegistration), AuthError> {
+    let issuer_url = start_url.as_deref().unwrap_or(START_URL);
+    let region = region.clone().map_or(OIDC_BUILDER_ID_REGION, Region::new);
+    let client = client(region.clone());
+    let registration = PkceRegistration::register_manual(&client, region, issuer_url.to_string()).await?;
+    Ok((client, registration))
+}
+
 /// Represents a client used for registering with AWS IAM OIDC.
 #[async_trait::async_trait]
 pub trait PkceClient {
@@ -175,8 +196,8 @@ pub struct PkceRegistration {
     ///
     /// <https://stackoverflow.com/questions/26132066/what-is-the-purpose-of-the-state-parameter-in-oauth-authorization-request>
     pub state: String,
-    /// Listener for hosting the local HTTP server.
-    listener: TcpListener,
+    /// Listener for hosting the local HTTP server, if the code isn't pasted instead.
+    listener: Option<TcpListener>,
     region: Region,
     /// Interchangeable with the "start URL" concept in the device code flow.
     issuer_url: String,
@@ -193,6 +214,27 @@ impl PkceRegistration {
     ) -> Result<Self, AuthError> {
         let listener = TcpListener::bind("127.0.0.1:0").await?;
         let redirect_uri = format!("http://{}/oauth/callback", listener.local_addr()?);
+        Self::register_with(client, region, issuer_url, timeout, Some(listener), redirect_uri).await
+    }
+
+    /// Registers a client without hosting a local server, for flows finished with
+    /// [`Self::finish_manual`].
+    pub async fn register_manual(
+        client: &impl PkceClient,
+        region: Region,
+        issuer_url: String,
+    ) -> Result<Self, AuthError> {
+        Self::register_with(client, region, issuer_url, None, None, MANUAL_REDIRECT_URI.to_string()).await
+    }
+
+    async fn register_with(
+        client: &impl PkceClient,
+        region: Region,
+        issuer_url: String,
+        timeout: Option<Duration>,
+        listener: Option<TcpListener>,
+        redirect_uri: String,
+    ) -> Result<Self, AuthError> {
         let code_verifier = generate_code_verifier();
         let code_challenge = generate_code_challenge(&code_verifier);
         let state = rand::rng()
@@ -231,16 +273,41 @@ impl PkceRegistration {
     /// then the access and refresh tokens will be saved.
     ///
     /// Only the first connection will be served.
-    pub async fn finish<C: PkceClient>(self, client: &C, database: Option<&mut Database>) -> Result<(), AuthError> {
+    pub async fn finish<C: PkceClient>(mut self, client: &C, database: Option<&mut Database>) -> Result<(), AuthError> {
+        let Some(listener) = self.listener.take() else {
+            return Err(AuthError::OAuthCustomError(
+                "no local server was registered to handle the redirect".into(),
+            ));
+        };
         let code = tokio::select! {
-            code = Self::recv_code(self.listener, self.state) => {
+            code = Self::recv_code(listener, self.state.clone()) => {
                 code?
             },
             _ = tokio::time::sleep(self.timeout) => {
                 return Err(AuthError::OAuthTimeout);
             }
         };
+        self.create_token(client, code, database).await
+    }
+
+    /// Trades the code in `pasted`, the URL the browser was redirected to or only the code, for an
+    /// access token. If a [`Database`] is passed, then the access and refresh tokens will be saved.
+    pub async fn finish_manual<C: PkceClient>(
+        self,
+        client: &C,
+        pasted: &str,
+        database: Option<&mut Database>,
+    ) -> Result<(), AuthError> {
+        let code = parse_pasted_code(pasted, &self.state)?;
+        self.create_token(client, code, database).await
+    }
 
+    async fn create_token<C: PkceClient>(
+        self,
+        client: &C,
+        code: String,
+        database: Option<&mut Database>,
+    ) -> Result<(), AuthError> {
         let response = client
             .create_token(CreateTokenArgs {
                 client_id: self.registered_client.client_id().to_string(),
@@ -423,6 +490,41 @@ impl Service<Request<Incoming>> for PkceHttpService {
     }
 }
 
+/// The code in `pasted`, either the URL the browser was redirected to or only the code.
+fn parse_pasted_code(pasted: &str, expected_state: &str) -> Result<String, AuthError> {
+    let pasted = pasted.trim();
+    let Some((_, query)) = pasted.split_once('?') else {
+        return match pasted.is_empty() || pasted.contains(char::is_whitespace) {
+            true => Err(AuthError::OAuthMissingCode),
+            false => Ok(pasted.to_string()),
+        };
+    };
+
+    let query = query.split('#').next().unwrap_or_default();
+    let params = query
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(key, value)| (key, percent_decode_str(value).decode_utf8_lossy().into_owned()))
+        .collect::<std::collections::HashMap<_, _>>();
+    if let Some(error) = params.get("error") {
+        return Err(AuthError::OAuthCustomError(format!(
+            "error occurred during authorization: {:?}, {:?}",
+            error,
+            params.get("error_description").map(String::as_str).unwrap_or_default()
+        )));
+    }
+    // A pasted redirect URL must come from the authorization started here, so unlike a bare code
+    // it is rejected without a matching state
+    let state = params.get("state").map(String::as_str).unwrap_or_default();
+    if state != expected_state {
+        return Err(AuthError::OAuthStateMismatch {
+            actual: state.to_string(),
+            expected: expected_state.to_string(),
+        });
+    }
+    params.get("code").cloned().ok_or(AuthError::OAuthMissingCode)
+}
+
 /// Query params for the initial GET request that starts the PKCE flow. Use
 /// [`PkceQueryParams::as_query_params`] to get a URL-safe string.
 #[derive(Debug, Clone, serde::Serialize)]
@@ -616,6 +718,44 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_pkce_flow_with_pasted_code() {
+        let region = Region::new("us-east-1");
+        let client = TestPkceClient {};
+        let registration = PkceRegistration::register_manual(&client, region, START_URL.into())
+            .await
+            .unwrap();
+        assert_eq!(registration.redirect_uri, MANUAL_REDIRECT_URI);
+
+        let pasted = format!("{MANUAL_REDIRECT_URI}?code=code&state={}", registration.state);
+        registration.finish_manual(&client, &pasted, None).await.unwrap();
+    }
+
+    #[test]
+    fn test_parse_pasted_code() {
+        assert_eq!(parse_pasted_code(" code \n", "state").unwrap(), "code");
+        assert_eq!(
+            parse_pasted_code(&format!("{MANUAL_REDIRECT_URI}?code=a%2Bb&state=state"), "state").unwrap(),
+            "a+b"
+        );
+        assert!(matches!(
+            parse_pasted_code(&format!("{MANUAL_REDIRECT_URI}?code=code&state=other"), "state"),
+            Err(AuthError::OAuthStateMismatch { .. })
+        ));
+        assert!(matches!(
+            parse_pasted_code(&format!("{MANUAL_REDIRECT_URI}?code=code"), "state"),
+            Err(AuthError::OAuthStateMismatch { .. })
+        ));
+        assert!(matches!(
+            parse_pasted_code(&format!("{MANUAL_REDIRECT_URI}?error=access_denied"), "state"),
+            Err(AuthError::OAuthCustomError(_))
+        ));
+        assert!(matches!(
+            parse_pasted_code("", "state"),
+            Err(AuthError::OAuthMissingCode)
+        ));
+    }
+
     #[tokio::test]
     async fn verify_gen_code_challenge() {
         let code_verifier = generate_code_verifier();
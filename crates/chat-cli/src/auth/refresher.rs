@@ -0,0 +1,144 @@
+//! Refreshing the token of the active login ahead of its expiry while a chat session is open, when
+//! `auth.backgroundRefresh` is enabled. Otherwise it is only refreshed once a request finds it
+//! expired, which fails the request if the refresh does.
+//!
+//! Failures are kept for the session to show, since requests keep working until the token expires.
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::time::Duration;
+
+use aws_types::region::Region;
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+use tracing::{
+    debug,
+    warn,
+};
+
+use crate::auth::builder_id::{
+    BuilderIdToken,
+    client,
+};
+use crate::auth::consts::OIDC_BUILDER_ID_REGION;
+use crate::database::Database;
+use crate::telemetry::{
+    TelemetryResult,
+    TelemetryThread,
+};
+
+/// How long before its expiry the token is refreshed.
+const REFRESH_AHEAD: Duration = Duration::from_secs(5 * 60);
+/// How long to wait before trying again after a refresh failed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub struct TokenRefresher {
+    /// The error of the latest refresh, if it failed
+    failure: Arc<Mutex<Option<String>>>,
+    handle: JoinHandle<()>,
+}
+
+impl TokenRefresher {
+    pub fn spawn(telemetry: TelemetryThread) -> Self {
+        let failure = Arc::new(Mutex::new(None));
+        let handle = tokio::spawn(refresh_loop(telemetry, Arc::clone(&failure)));
+        Self { failure, handle }
+    }
+
+    /// The error of the latest refresh if it failed, taken so that it is only shown once.
+    pub fn take_failure(&self) -> Option<String> {
+        self.failure.lock().ok()?.take()
+    }
+}
+
+impl Drop for TokenRefresher {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn refresh_loop(telemetry: TelemetryThread, failure: Arc<Mutex<Option<String>>>) {
+    let set_failure = |message: Option<String>| {
+        if let Ok(mut failure) = failure.lock() {
+            *failure = message;
+        }
+    };
+
+    loop {
+        // The database is opened again each time, like for requests, to follow logins made in the
+        // meantime
+        let token = match Database::new().await {
+            Ok(database) => BuilderIdToken::load(&database).await.map(|token| (database, token)),
+            Err(err) => Err(err.into()),
+        };
+        let (database, token) = match token {
+            Ok((database, Some(token))) => (database, token),
+            // Logged out, or with a token that can't be refreshed anymore
+            Ok((_, None)) => return,
+            Err(err) => {
+                warn!(?err, "failed to load the token to refresh");
+                tokio::time::sleep(RETRY_INTERVAL).await;
+                continue;
+            },
+        };
+
+        let delay = refresh_delay(token.expires_at, OffsetDateTime::now_utc());
+        if !delay.is_zero() {
+            debug!(?delay, "waiting to refresh the token");
+            tokio::time::sleep(delay).await;
+            continue;
+        }
+
+        let region = token.region.clone().map_or(OIDC_BUILDER_ID_REGION, Region::new);
+        let oauth_flow = format!("{:?}", token.oauth_flow);
+        match token.refresh_token(&client(region.clone()), &database, &region).await {
+            Ok(Some(_)) => {
+                debug!("refreshed the token ahead of its expiry");
+                set_failure(None);
+                telemetry
+                    .send_refresh_credentials(TelemetryResult::Succeeded, None, oauth_flow)
+                    .ok();
+            },
+            Ok(None) => {
+                set_failure(Some("the login can't be refreshed".to_string()));
+                telemetry
+                    .send_refresh_credentials(TelemetryResult::Failed, Some("not refreshable".to_string()), oauth_flow)
+                    .ok();
+                return;
+            },
+            Err(err) => {
+                warn!(?err, "failed to refresh the token");
+                set_failure(Some(err.to_string()));
+                telemetry
+                    .send_refresh_credentials(TelemetryResult::Failed, Some(err.to_string()), oauth_flow)
+                    .ok();
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            },
+        }
+    }
+}
+
+/// How long to wait before refreshing a token expiring at `expires_at`.
+fn refresh_delay(expires_at: OffsetDateTime, now: OffsetDateTime) -> Duration {
+    let refresh_at = expires_at - REFRESH_AHEAD;
+    (refresh_at - now).try_into().unwrap_or(Duration::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_delay() {
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(
+            refresh_delay(now + time::Duration::hours(1), now),
+            Duration::from_secs(55 * 60)
+        );
+        assert_eq!(refresh_delay(now + time::Duration::minutes(2), now), Duration::ZERO);
+        assert_eq!(refresh_delay(now - time::Duration::minutes(2), now), Duration::ZERO);
+    }
+}
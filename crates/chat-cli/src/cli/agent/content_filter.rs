@@ -0,0 +1,181 @@
+use std::path::Path;
+
+use globset::{
+    Glob,
+    GlobSet,
+    GlobSetBuilder,
+};
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tracing::warn;
+
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+/// Rules that prevent files from ever being sent to the model, whether they are attached as
+/// context or read by a tool.
+///
+/// The rules configured in the agent are combined with the ones from the `chat.contentFilter.*`
+/// settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentFilter {
+    /// Glob patterns of paths that are blocked, e.g. "**/secrets/**"
+    #[serde(default)]
+    pub denied_paths: Vec<String>,
+    /// File extensions or file names that are blocked. "pem" blocks "cert.pem", "env" blocks both
+    /// ".env" and "prod.env" and "id_rsa" blocks files named "id_rsa"
+    #[serde(default)]
+    pub denied_extensions: Vec<String>,
+    /// Files larger than this many bytes are blocked
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+}
+
+impl ContentFilter {
+    /// Combines the filter configured in the agent with the one from the user's settings.
+    pub fn resolve(settings: &Settings, agent_filter: Option<&ContentFilter>) -> Self {
+        let mut filter = Self {
+            denied_paths: settings.get_list(Setting::ChatContentFilterDeniedPaths),
+            denied_extensions: settings.get_list(Setting::ChatContentFilterDeniedExtensions),
+            max_file_size: settings
+                .get_int(Setting::ChatContentFilterMaxFileSize)
+                .and_then(|size| u64::try_from(size).ok()),
+        };
+        if let Some(agent_filter) = agent_filter {
+            filter.denied_paths.extend(agent_filter.denied_paths.iter().cloned());
+            filter
+                .denied_extensions
+                .extend(agent_filter.denied_extensions.iter().cloned());
+            filter.max_file_size = match (filter.max_file_size, agent_filter.max_file_size) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+        filter
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.denied_paths.is_empty() && self.denied_extensions.is_empty() && self.max_file_size.is_none()
+    }
+
+    /// Returns why the file at `path` must not be sent to the model, if it is blocked. `size` is
+    /// the size of the file in bytes if known.
+    pub fn blocked_reason(&self, path: &Path, size: Option<u64>) -> Option<String> {
+        if let Some(file_name) = path.file_name().map(|name| name.to_string_lossy().to_lowercase()) {
+            let denied_extension = self.denied_extensions.iter().find(|ext| {
+                let ext = ext.trim_start_matches('.').to_lowercase();
+                file_name == ext || file_name.ends_with(&format!(".{ext}"))
+            });
+            if let Some(ext) = denied_extension {
+                return Some(format!("files matching '{ext}' are denied"));
+            }
+        }
+
+        if !self.denied_paths.is_empty() && self.denied_path_set().is_match(path) {
+            return Some("the path is denied".to_string());
+        }
+
+        match (size, self.max_file_size) {
+            (Some(size), Some(max)) if size > max => Some(format!("the file is larger than {max} bytes")),
+            _ => None,
+        }
+    }
+
+    /// The message returned to the model when it attempts to access a blocked file.
+    pub fn refusal(path: &Path, reason: &str) -> String {
+        format!(
+            "Access to {} is blocked by a content filter configured by the user because {reason}. Do not attempt to access this file by any other means.",
+            path.display()
+        )
+    }
+
+    fn denied_path_set(&self) -> GlobSet {
+        let mut builder = GlobSetBuilder::new();
+        for path in &self.denied_paths {
+            match Glob::new(path) {
+                Ok(glob) => {
+                    builder.add(glob);
+                },
+                Err(err) => warn!(?err, "Invalid denied path glob: {path}. Ignoring."),
+            }
+        }
+        builder.build().unwrap_or_else(|_| GlobSet::empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_blocked_reason() {
+        let filter = ContentFilter {
+            denied_paths: vec!["**/secrets/**".to_string()],
+            denied_extensions: vec!["pem".to_string(), ".env".to_string(), "id_rsa".to_string()],
+            max_file_size: Some(10),
+        };
+
+        assert!(filter.blocked_reason(&PathBuf::from("/repo/cert.PEM"), None).is_some());
+        assert!(filter.blocked_reason(&PathBuf::from("/repo/.env"), None).is_some());
+        assert!(filter.blocked_reason(&PathBuf::from("/repo/prod.env"), None).is_some());
+        assert!(
+            filter
+                .blocked_reason(&PathBuf::from("/home/user/.ssh/id_rsa"), None)
+                .is_some()
+        );
+        assert!(
+            filter
+                .blocked_reason(&PathBuf::from("/repo/secrets/token.txt"), None)
+                .is_some()
+        );
+        assert!(
+            filter
+                .blocked_reason(&PathBuf::from("/repo/big.txt"), Some(11))
+                .is_some()
+        );
+
+        assert!(
+            filter
+                .blocked_reason(&PathBuf::from("/repo/id_rsa.pub"), None)
+                .is_none()
+        );
+        assert!(
+            filter
+                .blocked_reason(&PathBuf::from("/repo/environment.rs"), None)
+                .is_none()
+        );
+        assert!(
+            filter
+                .blocked_reason(&PathBuf::from("/repo/small.txt"), Some(10))
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let mut settings = Settings::new().await.unwrap();
+        settings
+            .set(Setting::ChatContentFilterDeniedExtensions, "pem, key")
+            .await
+            .unwrap();
+        settings.set(Setting::ChatContentFilterMaxFileSize, 100).await.unwrap();
+
+        let agent_filter = ContentFilter {
+            denied_extensions: vec!["env".to_string()],
+            max_file_size: Some(50),
+            ..Default::default()
+        };
+        let filter = ContentFilter::resolve(&settings, Some(&agent_filter));
+        assert_eq!(filter.denied_extensions, vec!["pem", "key", "env"]);
+        assert_eq!(filter.max_file_size, Some(50));
+        assert!(ContentFilter::resolve(&Settings::new().await.unwrap(), None).is_empty());
+    }
+}
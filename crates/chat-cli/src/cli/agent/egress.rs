@@ -0,0 +1,414 @@
+use std::net::{
+    IpAddr,
+    SocketAddr,
+};
+use std::sync::Arc;
+
+use reqwest::dns::{
+    Addrs,
+    Name,
+    Resolve,
+    Resolving,
+};
+use reqwest::redirect::Policy;
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use url::{
+    Host,
+    Url,
+};
+
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+/// Destinations that tools may connect to over the network. Without any allowed domains or CIDRs
+/// everything that is not denied can be reached.
+///
+/// The policy configured in the agent is enforced along with the one from the `chat.egress.*`
+/// settings, so a destination has to pass both.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EgressPolicy {
+    /// Domains that can be reached. "example.com" covers the domain and its subdomains,
+    /// "*.example.com" only its subdomains
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// Domains that are blocked, matched like the allowed domains
+    #[serde(default)]
+    pub denied_domains: Vec<String>,
+    /// IP ranges in CIDR notation that can be reached, e.g. "10.0.0.0/8". Single addresses are
+    /// accepted as well
+    #[serde(default)]
+    pub allowed_cidrs: Vec<String>,
+    /// IP ranges in CIDR notation that are blocked
+    #[serde(default)]
+    pub denied_cidrs: Vec<String>,
+}
+
+impl EgressPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.allowed_domains.is_empty()
+            && self.denied_domains.is_empty()
+            && self.allowed_cidrs.is_empty()
+            && self.denied_cidrs.is_empty()
+    }
+
+    fn is_restricted(&self) -> bool {
+        !self.allowed_domains.is_empty() || !self.allowed_cidrs.is_empty()
+    }
+
+    /// Returns why `host` can't be reached, if it can't. Addresses are only matched against the
+    /// CIDR rules, so a policy that allows some domains does not let tools around it by
+    /// connecting to an address directly. Domains are matched against the domain rules here, and
+    /// against the CIDR rules by [Self::resolved_violation] once they are resolved.
+    fn violation(&self, host: &Host<&str>) -> Option<&'static str> {
+        let (denied, allowed) = match host {
+            // A domain that isn't allowed by name may still resolve to allowed addresses
+            Host::Domain(domain) => (
+                self.domain_denied(domain),
+                self.domain_allowed(domain) || !self.allowed_cidrs.is_empty(),
+            ),
+            Host::Ipv4(addr) => self.cidr_rules(IpAddr::V4(*addr)),
+            Host::Ipv6(addr) => self.cidr_rules(IpAddr::V6(*addr)),
+        };
+
+        if denied {
+            Some("it is denied")
+        } else if self.is_restricted() && !allowed {
+            Some("it is not allowed")
+        } else {
+            None
+        }
+    }
+
+    /// Returns why `domain` can't be reached at `addrs`, the addresses it resolved to, if it
+    /// can't. It is denied if any of them is, and a domain that isn't allowed by name is only
+    /// allowed if all of them are.
+    fn resolved_violation(&self, domain: &str, addrs: &[IpAddr]) -> Option<&'static str> {
+        let rules = addrs.iter().map(|addr| self.cidr_rules(*addr)).collect::<Vec<_>>();
+        let allowed_by_addrs =
+            !self.allowed_cidrs.is_empty() && !rules.is_empty() && rules.iter().all(|(_, allowed)| *allowed);
+        if self.domain_denied(domain) || rules.iter().any(|(denied, _)| *denied) {
+            Some("it is denied")
+        } else if self.is_restricted() && !self.domain_allowed(domain) && !allowed_by_addrs {
+            Some("it is not allowed")
+        } else {
+            None
+        }
+    }
+
+    fn domain_denied(&self, domain: &str) -> bool {
+        self.denied_domains
+            .iter()
+            .any(|pattern| domain_matches(pattern, domain))
+    }
+
+    fn domain_allowed(&self, domain: &str) -> bool {
+        self.allowed_domains
+            .iter()
+            .any(|pattern| domain_matches(pattern, domain))
+    }
+
+    fn cidr_rules(&self, addr: IpAddr) -> (bool, bool) {
+        let matches = |cidrs: &[String]| cidrs.iter().any(|cidr| cidr_contains(cidr, addr));
+        (matches(&self.denied_cidrs), matches(&self.allowed_cidrs))
+    }
+}
+
+/// The egress policies in effect for a session.
+#[derive(Debug, Clone, Default)]
+pub struct Egress(Vec<EgressPolicy>);
+
+impl Egress {
+    /// Combines the policy configured in the agent with the one from the user's settings.
+    pub fn resolve(settings: &Settings, agent_policy: Option<&EgressPolicy>) -> Self {
+        let settings_policy = EgressPolicy {
+            allowed_domains: settings.get_list(Setting::ChatEgressAllowedDomains),
+            denied_domains: settings.get_list(Setting::ChatEgressDeniedDomains),
+            allowed_cidrs: settings.get_list(Setting::ChatEgressAllowedCidrs),
+            denied_cidrs: settings.get_list(Setting::ChatEgressDeniedCidrs),
+        };
+        Self(
+            [Some(settings_policy), agent_policy.cloned()]
+                .into_iter()
+                .flatten()
+                .filter(|policy| !policy.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Checks that the host of `url` can be reached. Urls that can't be parsed are left to fail
+    /// wherever they are used.
+    pub fn check_url(&self, url: &str) -> Result<(), EgressViolation> {
+        let Ok(url) = Url::parse(url) else {
+            return Ok(());
+        };
+        let Some(host) = url.host() else {
+            return Ok(());
+        };
+        match self.0.iter().find_map(|policy| policy.violation(&host)) {
+            Some(reason) => Err(EgressViolation {
+                destination: host.to_string(),
+                reason,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that `addr` can be reached, for connections that are only known by their address.
+    /// Only the CIDR rules apply, so a policy that only allows domains blocks every address.
+    #[cfg(feature = "wasm-plugins")]
+    pub fn check_addr(&self, addr: IpAddr) -> Result<(), EgressViolation> {
+        let host = match addr {
+            IpAddr::V4(addr) => Host::Ipv4(addr),
+            IpAddr::V6(addr) => Host::Ipv6(addr),
+        };
+        match self.0.iter().find_map(|policy| policy.violation(&host)) {
+            Some(reason) => Err(EgressViolation {
+                destination: addr.to_string(),
+                reason,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that `domain` can be reached at `addrs`, the addresses it resolved to.
+    pub fn check_resolved(&self, domain: &str, addrs: &[IpAddr]) -> Result<(), EgressViolation> {
+        match self
+            .0
+            .iter()
+            .find_map(|policy| policy.resolved_violation(domain, addrs))
+        {
+            Some(reason) => Err(EgressViolation {
+                destination: domain.to_string(),
+                reason,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// A resolver for clients that connect within the policies, see [EgressResolver].
+    pub fn resolver(&self) -> Arc<EgressResolver> {
+        Arc::new(EgressResolver(self.clone()))
+    }
+
+    /// A redirect policy that follows up to `max_redirects` redirects the policies allow.
+    pub fn redirect_policy(&self, max_redirects: usize) -> Policy {
+        let egress = self.clone();
+        Policy::custom(move |attempt| {
+            if attempt.previous().len() >= max_redirects {
+                return attempt.error("too many redirects");
+            }
+            match egress.check_url(attempt.url().as_str()) {
+                Ok(()) => attempt.follow(),
+                Err(violation) => attempt.error(violation),
+            }
+        })
+    }
+}
+
+/// Resolves domains for a client and checks the addresses they resolve to against the CIDR rules
+/// of the policies. The client connects to the addresses that were checked rather than resolving
+/// the domain again, so a DNS record that changes in between can't lead it elsewhere.
+#[derive(Debug, Clone)]
+pub struct EgressResolver(Egress);
+
+impl Resolve for EgressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let egress = self.0.clone();
+        Box::pin(async move {
+            let domain = name.as_str();
+            let addrs = tokio::net::lookup_host((domain, 0)).await?.collect::<Vec<SocketAddr>>();
+            let ips = addrs.iter().map(SocketAddr::ip).collect::<Vec<_>>();
+            egress.check_resolved(domain, &ips)?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Connecting to {destination} is blocked by the egress policy because {reason}")]
+pub struct EgressViolation {
+    pub destination: String,
+    pub reason: &'static str,
+}
+
+fn domain_matches(pattern: &str, domain: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_lowercase();
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(parent) => domain.ends_with(&format!(".{parent}")),
+        None => domain == pattern || domain.ends_with(&format!(".{pattern}")),
+    }
+}
+
+fn cidr_contains(cidr: &str, addr: IpAddr) -> bool {
+    let (network, prefix_len) = match cidr.trim().split_once('/') {
+        Some((network, prefix_len)) => (network, prefix_len.parse::<u32>().ok()),
+        None => (cidr.trim(), None),
+    };
+    let Ok(network) = network.parse::<IpAddr>() else {
+        return false;
+    };
+    match (network.to_canonical(), addr.to_canonical()) {
+        (IpAddr::V4(network), IpAddr::V4(addr)) => {
+            let Some(prefix_len) = prefix_len.map_or(Some(32), |len| (len <= 32).then_some(len)) else {
+                return false;
+            };
+            let mask = u32::MAX.checked_shl(32 - prefix_len).unwrap_or(0);
+            u32::from(network) & mask == u32::from(addr) & mask
+        },
+        (IpAddr::V6(network), IpAddr::V6(addr)) => {
+            let Some(prefix_len) = prefix_len.map_or(Some(128), |len| (len <= 128).then_some(len)) else {
+                return false;
+            };
+            let mask = u128::MAX.checked_shl(128 - prefix_len).unwrap_or(0);
+            u128::from(network) & mask == u128::from(addr) & mask
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn egress(policy: serde_json::Value) -> Egress {
+        Egress(vec![serde_json::from_value(policy).unwrap()])
+    }
+
+    #[test]
+    fn test_check_url() {
+        let egress = egress(serde_json::json!({
+            "allowedDomains": ["example.com", "*.internal.dev"],
+            "deniedDomains": ["secrets.example.com"],
+            "allowedCidrs": ["10.0.0.0/8"],
+            "deniedCidrs": ["10.0.0.1"],
+        }));
+
+        assert!(egress.check_url("https://example.com/mcp").is_ok());
+        assert!(egress.check_url("https://api.EXAMPLE.com/mcp").is_ok());
+        assert!(egress.check_url("https://mcp.internal.dev").is_ok());
+        assert!(egress.check_url("http://10.1.2.3:8080/sse").is_ok());
+
+        let violation = egress.check_url("https://secrets.example.com").unwrap_err();
+        assert_eq!(violation.destination, "secrets.example.com");
+        assert_eq!(violation.reason, "it is denied");
+        // Domains that aren't allowed by name are left to the addresses they resolve to
+        let public = [IpAddr::from([93, 184, 215, 14])];
+        assert!(egress.check_url("https://example.org").is_ok());
+        assert_eq!(
+            egress.check_resolved("internal.dev", &public).unwrap_err().reason,
+            "it is not allowed"
+        );
+        assert_eq!(
+            egress.check_resolved("example.org", &public).unwrap_err().reason,
+            "it is not allowed"
+        );
+        assert_eq!(egress.check_url("http://10.0.0.1").unwrap_err().reason, "it is denied");
+        assert_eq!(
+            egress.check_url("http://192.168.0.1").unwrap_err().reason,
+            "it is not allowed"
+        );
+        assert!(egress.check_url("http://[::1]:3000").is_err());
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    #[test]
+    fn test_check_addr() {
+        let cidrs = egress(serde_json::json!({
+            "allowedCidrs": ["10.0.0.0/8"],
+            "deniedCidrs": ["10.0.0.1"],
+        }));
+        assert!(cidrs.check_addr(IpAddr::from([10, 1, 2, 3])).is_ok());
+        assert_eq!(
+            cidrs.check_addr(IpAddr::from([10, 0, 0, 1])).unwrap_err().reason,
+            "it is denied"
+        );
+        assert_eq!(
+            cidrs.check_addr(IpAddr::from([192, 168, 0, 1])).unwrap_err().reason,
+            "it is not allowed"
+        );
+
+        let domains_only = egress(serde_json::json!({ "allowedDomains": ["example.com"] }));
+        assert!(domains_only.check_addr(IpAddr::from([93, 184, 215, 14])).is_err());
+        assert!(Egress::default().check_addr(IpAddr::from([93, 184, 215, 14])).is_ok());
+    }
+
+    #[test]
+    fn test_denied_only() {
+        let egress = egress(serde_json::json!({ "deniedCidrs": ["169.254.0.0/16", "fd00::/8"] }));
+        assert!(egress.check_url("https://example.com").is_ok());
+        assert!(egress.check_url("http://169.254.169.254/latest").is_err());
+        assert!(egress.check_url("http://[fd12::1]").is_err());
+        assert!(egress.check_url("http://[::ffff:169.254.169.254]").is_err());
+    }
+
+    #[test]
+    fn test_check_resolved() {
+        let metadata = [IpAddr::from([169, 254, 169, 254])];
+        let private = [IpAddr::from([10, 0, 0, 5]), IpAddr::from([10, 0, 0, 6])];
+
+        // Hosts given by name are denied by the addresses they resolve to
+        let denied = egress(serde_json::json!({ "deniedCidrs": ["169.254.0.0/16"] }));
+        assert!(denied.check_url("http://metadata.example.com").is_ok());
+        assert_eq!(
+            denied
+                .check_resolved("metadata.example.com", &metadata)
+                .unwrap_err()
+                .reason,
+            "it is denied"
+        );
+        assert!(denied.check_resolved("example.com", &private).is_ok());
+
+        // And allowed by them when all of them are allowed
+        let allowed = egress(serde_json::json!({ "allowedDomains": ["example.com"], "allowedCidrs": ["10.0.0.0/8"] }));
+        assert!(allowed.check_resolved("git.corp", &private).is_ok());
+        assert!(allowed.check_resolved("example.com", &metadata).is_ok());
+        assert!(allowed.check_resolved("git.corp", &[private[0], metadata[0]]).is_err());
+        assert!(allowed.check_url("https://example.org").is_ok());
+        assert!(allowed.check_resolved("example.org", &metadata).is_err());
+        let domains_only = egress(serde_json::json!({ "allowedDomains": ["example.com"] }));
+        assert!(domains_only.check_url("https://example.org").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolver() {
+        let egress = egress(serde_json::json!({ "deniedCidrs": ["127.0.0.0/8", "::1"] }));
+        let name = "localhost".parse::<Name>().unwrap();
+        let err = egress.resolver().resolve(name).await.err().unwrap();
+        assert!(err.to_string().contains("blocked by the egress policy"), "{err}");
+
+        let name = "localhost".parse::<Name>().unwrap();
+        let addrs = Egress::default().resolver().resolve(name).await.unwrap();
+        assert!(addrs.map(|addr| addr.ip()).all(|ip| ip.is_loopback()));
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let mut settings = Settings::new().await.unwrap();
+        settings
+            .set(Setting::ChatEgressAllowedDomains, "example.com, example.org")
+            .await
+            .unwrap();
+        let agent_policy = EgressPolicy {
+            allowed_domains: vec!["example.com".to_string()],
+            ..Default::default()
+        };
+
+        // Both policies have to allow a destination
+        let egress = Egress::resolve(&settings, Some(&agent_policy));
+        assert!(egress.check_url("https://example.com").is_ok());
+        assert!(egress.check_url("https://example.org").is_err());
+        assert!(
+            Egress::resolve(&settings, None)
+                .check_url("https://example.org")
+                .is_ok()
+        );
+    }
+}
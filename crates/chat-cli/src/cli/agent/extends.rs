@@ -0,0 +1,178 @@
+//! Inheritance between agent configs. An agent with `"extends": "base"` starts from the config of
+//! the agent `base` in the global agent directory, or from the config at a path if the value is
+//! one, and overrides the fields it sets itself. This lets a workspace agent share the tools,
+//! MCP servers and permissions of a team wide agent while changing only a few of them.
+//!
+//! Objects such as `toolAliases`, `toolsSettings` and `mcpServers` are merged key by key, while
+//! everything else is replaced as a whole. An agent that sets `tools` or `allowedTools` thus
+//! replaces the lists of its base rather than adding to them.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use serde_json::Value;
+
+use super::Agent;
+use crate::os::Os;
+use crate::util::directories;
+
+/// How many configs an agent can extend through, in case they form a very long chain.
+const MAX_DEPTH: usize = 8;
+
+/// Parses the config at `path`, whose content is `content`, on top of the configs it extends.
+pub async fn parse_agent_config(os: &Os, path: &Path, content: &[u8]) -> Result<Agent> {
+    Ok(serde_json::from_value(resolve_agent_config(os, path, content).await?)?)
+}
+
+/// Merges the config at `path`, whose content is `content`, on top of the configs it extends.
+pub async fn resolve_agent_config(os: &Os, path: &Path, content: &[u8]) -> Result<Value> {
+    let mut chain = vec![serde_json::from_slice::<Value>(content)?];
+    let mut visited = vec![path.to_path_buf()];
+    while let Some(extends) = chain.last().and_then(|config| config.get("extends")?.as_str()) {
+        if chain.len() > MAX_DEPTH {
+            bail!("Agent config {} extends more than {MAX_DEPTH} configs", path.display());
+        }
+        let Some(dir) = visited.last().and_then(|path| path.parent()) else {
+            bail!("Agent config {} has no parent directory", path.display());
+        };
+        let base_path = base_path(os, dir, extends)?;
+        if visited.contains(&base_path) {
+            bail!(
+                "Agent config {} extends itself through {}",
+                path.display(),
+                base_path.display()
+            );
+        }
+
+        let base = os.fs.read(&base_path).await.wrap_err_with(|| {
+            format!(
+                "Failed to read {}, which {} extends",
+                base_path.display(),
+                path.display()
+            )
+        })?;
+        chain.push(
+            serde_json::from_slice::<Value>(&base)
+                .wrap_err_with(|| format!("Agent config {} is not valid JSON", base_path.display()))?,
+        );
+        visited.push(base_path);
+    }
+
+    let mut config = chain.pop().unwrap_or_default();
+    while let Some(overrides) = chain.pop() {
+        merge(&mut config, overrides);
+    }
+    Ok(config)
+}
+
+/// The config file `extends` refers to: the path it is, relative to `dir`, or the agent it names
+/// in the global agent directory.
+fn base_path(os: &Os, dir: &Path, extends: &str) -> Result<PathBuf> {
+    if extends.ends_with(".json") || extends.contains(['/', std::path::MAIN_SEPARATOR]) {
+        return Ok(dir.join(shellexpand::tilde(extends).as_ref()));
+    }
+    Ok(directories::chat_global_agent_path(os)?.join(format!("{extends}.json")))
+}
+
+/// Applies `overrides` on top of `base`, merging objects key by key and replacing other values.
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    },
+                }
+            }
+        },
+        (base, overrides) => *base = overrides,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut config = json!({
+            "tools": ["*"],
+            "allowedTools": ["fs_read"],
+            "toolAliases": { "@git/git_status": "status" },
+            "prompt": "base prompt",
+        });
+        merge(
+            &mut config,
+            json!({
+                "tools": ["fs_read", "@git"],
+                "toolAliases": { "@git/git_log": "log" },
+            }),
+        );
+        assert_eq!(
+            config,
+            json!({
+                "tools": ["fs_read", "@git"],
+                "allowedTools": ["fs_read"],
+                "toolAliases": { "@git/git_status": "status", "@git/git_log": "log" },
+                "prompt": "base prompt",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_agent_config() {
+        let os = Os::new().await.unwrap();
+        let global_dir = directories::chat_global_agent_path(&os).unwrap();
+        os.fs.create_dir_all(&global_dir).await.unwrap();
+        os.fs
+            .write(
+                global_dir.join("team.json"),
+                json!({
+                    "description": "Team agent",
+                    "tools": ["*"],
+                    "allowedTools": ["fs_read"],
+                    "resources": ["file://CONTRIBUTING.md"],
+                })
+                .to_string(),
+            )
+            .await
+            .unwrap();
+
+        let child = json!({ "extends": "team", "allowedTools": ["fs_read", "fs_write"] }).to_string();
+        let path = PathBuf::from("/repo/.amazonq/cli-agents/project.json");
+        let agent = parse_agent_config(&os, &path, child.as_bytes()).await.unwrap();
+        assert_eq!(agent.extends.as_deref(), Some("team"));
+        assert_eq!(agent.description.as_deref(), Some("Team agent"));
+        assert_eq!(agent.tools, ["*"]);
+        assert_eq!(agent.resources, ["file://CONTRIBUTING.md"]);
+        assert_eq!(agent.allowed_tools.len(), 2);
+
+        // Agents can't extend themselves, even through other agents
+        os.fs
+            .write(global_dir.join("a.json"), r#"{ "extends": "b" }"#)
+            .await
+            .unwrap();
+        os.fs
+            .write(global_dir.join("b.json"), r#"{ "extends": "a" }"#)
+            .await
+            .unwrap();
+        let path = global_dir.join("a.json");
+        assert!(parse_agent_config(&os, &path, br#"{ "extends": "b" }"#).await.is_err());
+        assert!(
+            parse_agent_config(&os, &path, br#"{ "extends": "missing" }"#)
+                .await
+                .is_err()
+        );
+    }
+}
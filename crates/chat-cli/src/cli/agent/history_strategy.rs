@@ -0,0 +1,60 @@
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// How many of the latest turns the summarizing strategies keep as they are, unless
+/// `recentTurns` says otherwise.
+pub const DEFAULT_RECENT_TURNS: usize = 10;
+/// How many of the first turns the hybrid strategy keeps as they are, unless `anchorTurns` says
+/// otherwise.
+pub const DEFAULT_ANCHOR_TURNS: usize = 1;
+
+/// Which part of the conversation history is sent with each request. A turn is a prompt of the
+/// user along with the tool uses and responses that follow it.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryStrategy {
+    /// sliding sends only the latest turns, summarize replaces older turns with a summary written
+    /// by the model, and hybrid does the same while also keeping the first turns as they are
+    pub kind: HistoryStrategyKind,
+    /// How many of the latest turns are sent as they are. Without it, the sliding strategy sends
+    /// as much of the history as fits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recent_turns: Option<usize>,
+    /// How many of the first turns the hybrid strategy keeps as they are, e.g. the one describing
+    /// the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor_turns: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryStrategyKind {
+    #[default]
+    Sliding,
+    Summarize,
+    Hybrid,
+}
+
+impl HistoryStrategyKind {
+    /// Parses the value of `chat.historyStrategy`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "sliding" => Some(Self::Sliding),
+            "summarize" => Some(Self::Summarize),
+            "hybrid" => Some(Self::Hybrid),
+            _ => None,
+        }
+    }
+}
+
+impl From<HistoryStrategyKind> for HistoryStrategy {
+    fn from(kind: HistoryStrategyKind) -> Self {
+        Self {
+            kind,
+            ..Default::default()
+        }
+    }
+}
@@ -21,6 +21,20 @@ pub enum HookTrigger {
     AgentSpawn,
     /// Triggered per user message submission
     UserPromptSubmit,
+    /// Triggered before each tool use, with the tool name and arguments on stdin
+    PreToolUse,
+    /// Triggered after each tool use, with the tool name, arguments and outcome on stdin
+    PostToolUse,
+    /// Triggered when the chat session ends
+    SessionEnd,
+}
+
+impl HookTrigger {
+    /// Whether the hooks of this trigger run when a prompt is sent, with their output added to
+    /// its context.
+    pub fn runs_with_prompt(&self) -> bool {
+        matches!(self, HookTrigger::AgentSpawn | HookTrigger::UserPromptSubmit)
+    }
 }
 
 impl Display for HookTrigger {
@@ -28,6 +42,9 @@ impl Display for HookTrigger {
         match self {
             HookTrigger::AgentSpawn => write!(f, "agentSpawn"),
             HookTrigger::UserPromptSubmit => write!(f, "userPromptSubmit"),
+            HookTrigger::PreToolUse => write!(f, "preToolUse"),
+            HookTrigger::PostToolUse => write!(f, "postToolUse"),
+            HookTrigger::SessionEnd => write!(f, "sessionEnd"),
         }
     }
 }
@@ -48,6 +65,11 @@ pub struct Hook {
     /// How long the hook output is cached before it will be executed again
     #[serde(default = "Hook::default_cache_ttl_seconds")]
     pub cache_ttl_seconds: u64,
+
+    /// Whether the output of a preToolUse or postToolUse hook is added to the context along with
+    /// the result of the tool. The output of agentSpawn and userPromptSubmit hooks always is
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub inject_output: bool,
 }
 
 impl Hook {
@@ -57,6 +79,7 @@ impl Hook {
             timeout_ms: Self::default_timeout_ms(),
             max_output_size: Self::default_max_output_size(),
             cache_ttl_seconds: Self::default_cache_ttl_seconds(),
+            inject_output: false,
         }
     }
 
@@ -72,3 +95,7 @@ impl Hook {
         DEFAULT_CACHE_TTL_SECONDS
     }
 }
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
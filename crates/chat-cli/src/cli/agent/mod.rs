@@ -1,7 +1,14 @@
+pub mod content_filter;
+pub mod egress;
+mod extends;
+pub mod history_strategy;
 pub mod hook;
 mod legacy;
 mod mcp_config;
 mod root_command_args;
+pub mod sandbox;
+mod schema;
+mod transfer;
 mod wrapper_types;
 
 use std::borrow::Borrow;
@@ -27,9 +34,11 @@ use crossterm::{
     queue,
     style,
 };
+pub use extends::resolve_agent_config;
 use eyre::bail;
 pub use mcp_config::McpServerConfig;
 pub use root_command_args::*;
+pub use schema::validate_agent_config;
 use schemars::JsonSchema;
 use serde::{
     Deserialize,
@@ -40,6 +49,7 @@ use tracing::{
     error,
     warn,
 };
+pub use transfer::grants;
 pub use wrapper_types::{
     OriginalToolName,
     ToolSettingTarget,
@@ -47,15 +57,22 @@ pub use wrapper_types::{
     tool_settings_schema,
 };
 
+use super::chat::tool_manager::workspace_mcp_config_path;
+use super::chat::tools::formatter::ToolFormatter;
+use super::chat::tools::wasm_tool::WasmToolConfig;
 use super::chat::tools::{
     DEFAULT_APPROVE,
     NATIVE_TOOLS,
     ToolOrigin,
 };
+use crate::cli::agent::content_filter::ContentFilter;
+use crate::cli::agent::egress::EgressPolicy;
+use crate::cli::agent::history_strategy::HistoryStrategy;
 use crate::cli::agent::hook::{
     Hook,
     HookTrigger,
 };
+use crate::cli::agent::sandbox::SandboxConfig;
 use crate::database::settings::Setting;
 use crate::os::Os;
 use crate::util::{
@@ -98,6 +115,11 @@ pub struct Agent {
     /// This field is not model facing and is mostly here for users to discern between agents
     #[serde(default)]
     pub description: Option<String>,
+    /// The agent this one inherits its config from, either the name of an agent in the global
+    /// agent directory or the path of its config. The fields set here override those of the base,
+    /// with objects such as toolAliases merged key by key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
     /// (NOT YET IMPLEMENTED) The intention for this field is to provide high level context to the
     /// agent. This should be seen as the same category of context as a system prompt.
     #[serde(default)]
@@ -132,6 +154,36 @@ pub struct Agent {
     /// you configure in the mcpServers field in this config
     #[serde(default)]
     pub use_legacy_mcp_json: bool,
+    /// Tools implemented as WebAssembly (WASI) modules, keyed by tool name. These run in process
+    /// with only the filesystem, network and environment access they are granted
+    #[serde(default)]
+    pub wasm_tools: HashMap<String, WasmToolConfig>,
+    /// Formatters applied to the output of tools before it is displayed, keyed by tool name (or
+    /// "@{MCP_SERVER_NAME}" for all tools from a server). The output of these tools is only shown
+    /// formatted, while the model always receives the raw output
+    #[serde(default)]
+    pub tool_formatters: HashMap<String, ToolFormatter>,
+    /// Paths, file extensions and file sizes that must never be attached as context or read by
+    /// the file system, grep_search and git tools. These are combined with the
+    /// chat.contentFilter.* settings
+    #[serde(default)]
+    pub content_filter: ContentFilter,
+    /// Domains and IP ranges that tools may connect to. These are enforced along with the
+    /// chat.egress.* settings
+    #[serde(default)]
+    pub egress: EgressPolicy,
+    /// Runs the commands of execute_bash in a sandbox that restricts what they can write to and
+    /// whether they can use the network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxConfig>,
+    /// The model used while this agent is active, by name or id as listed by /model. --model
+    /// takes precedence, and this takes precedence over the chat.defaultModel setting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Which part of the conversation history is sent with each request, overriding the
+    /// chat.historyStrategy setting. Trades recall of earlier turns for smaller requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_strategy: Option<HistoryStrategy>,
     #[serde(skip)]
     pub path: Option<PathBuf>,
 }
@@ -141,6 +193,7 @@ impl Default for Agent {
         Self {
             name: "default".to_string(),
             description: Some("Default agent".to_string()),
+            extends: None,
             prompt: Default::default(),
             mcp_servers: Default::default(),
             tools: vec!["*".to_string()],
@@ -158,6 +211,13 @@ impl Default for Agent {
             hooks: Default::default(),
             tools_settings: Default::default(),
             use_legacy_mcp_json: true,
+            wasm_tools: Default::default(),
+            tool_formatters: Default::default(),
+            content_filter: Default::default(),
+            egress: Default::default(),
+            sandbox: None,
+            model: None,
+            history_strategy: None,
             path: None,
         }
     }
@@ -220,6 +280,38 @@ impl Agent {
         Ok(())
     }
 
+    /// Reads the MCP servers, tools and tool aliases of this agent from its config file (and the
+    /// legacy mcp.json, if it is used) again. Everything else about the agent is left as it is, so
+    /// that tools trusted during the session stay trusted.
+    pub async fn reload_mcp_servers(&mut self, os: &Os) -> eyre::Result<()> {
+        let Some(path) = self.path.clone() else {
+            bail!("Agent {} has no config file to reload MCP servers from", self.name);
+        };
+        let content = os.fs.read(&path).await?;
+        let mut agent = extends::parse_agent_config(os, &path, &content).await?;
+        let global_mcp_config = load_legacy_mcp_config(os).await;
+        agent.thaw(&path, global_mcp_config.as_ref())?;
+
+        self.mcp_servers = agent.mcp_servers;
+        self.tools = agent.tools;
+        self.tool_aliases = agent.tool_aliases;
+        self.use_legacy_mcp_json = agent.use_legacy_mcp_json;
+        Ok(())
+    }
+
+    /// The files the MCP servers of this agent are configured in.
+    pub fn mcp_config_paths(&self, os: &Os) -> Vec<PathBuf> {
+        let Some(path) = &self.path else {
+            return Vec::new();
+        };
+        let mut paths = vec![path.clone()];
+        if self.use_legacy_mcp_json {
+            paths.extend(directories::chat_legacy_mcp_config(os).ok());
+            paths.extend(workspace_mcp_config_path(os).ok());
+        }
+        paths
+    }
+
     pub fn to_str_pretty(&self) -> eyre::Result<String> {
         let mut agent_clone = self.clone();
         agent_clone.freeze()?;
@@ -228,6 +320,9 @@ impl Agent {
 
     /// Retrieves an agent by name. It does so via first seeking the given agent under local dir,
     /// and falling back to global dir if it does not exist in local.
+    ///
+    /// The agent is read as its config is written, without the configs it extends, so that it can
+    /// be written back.
     pub async fn get_agent_by_name(os: &Os, agent_name: &str) -> eyre::Result<(Agent, PathBuf)> {
         let config_path: Result<PathBuf, PathBuf> = 'config: {
             // local first, and then fall back to looking at global
@@ -236,6 +331,11 @@ impl Agent {
                 break 'config Ok(local_config_dir);
             }
 
+            let workspace_config_dir = directories::chat_workspace_agent_dir(os)?.join(format!("{agent_name}.json"));
+            if os.fs.exists(&workspace_config_dir) {
+                break 'config Ok(workspace_config_dir);
+            }
+
             let global_config_dir = directories::chat_global_agent_path(os)?.join(format!("{agent_name}.json"));
             if os.fs.exists(&global_config_dir) {
                 break 'config Ok(global_config_dir);
@@ -249,20 +349,28 @@ impl Agent {
                 let content = os.fs.read(&config_path).await?;
                 let mut agent = serde_json::from_slice::<Agent>(&content)?;
 
-                let global_mcp_path = directories::chat_legacy_mcp_config(os)?;
-                let global_mcp_config = match McpServerConfig::load_from_file(os, global_mcp_path).await {
-                    Ok(config) => Some(config),
-                    Err(e) => {
-                        tracing::error!("Error loading global mcp json path: {e}.");
-                        None
-                    },
-                };
+                let global_mcp_config = load_legacy_mcp_config(os).await;
                 agent.thaw(&config_path, global_mcp_config.as_ref())?;
                 Ok((agent, config_path))
             },
             _ => bail!("Agent {agent_name} does not exist"),
         }
     }
+
+    /// Loads the agent whose config at `path` is `content`, on top of the configs it extends.
+    pub async fn load_from_path(os: &Os, path: &Path, content: &[u8]) -> eyre::Result<Agent> {
+        let config = resolve_agent_config(os, path, content).await?;
+        Self::load_from_config(os, path, config).await
+    }
+
+    /// Loads the agent at `path` from its `config` as merged by [resolve_agent_config].
+    pub async fn load_from_config(os: &Os, path: &Path, config: serde_json::Value) -> eyre::Result<Agent> {
+        let mut agent = serde_json::from_value::<Agent>(config)?;
+        agent.path = Some(path.to_path_buf());
+        let global_mcp_config = load_legacy_mcp_config(os).await;
+        agent.thaw(path, global_mcp_config.as_ref())?;
+        Ok(agent)
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -406,13 +514,22 @@ impl Agents {
                 },
             }
 
-            let Ok(path) = directories::chat_local_agent_dir() else {
-                break 'local Vec::<Agent>::new();
-            };
-            let Ok(files) = os.fs.read_dir(path).await else {
-                break 'local Vec::<Agent>::new();
-            };
-            load_agents_from_entries(files, os, &mut global_mcp_config).await
+            // Workspace agents can live under either cwd/.aws/amazonq/agents or the .amazonq
+            // directory scaffolded by `q init`
+            let mut agents = Vec::<Agent>::new();
+            for path in [
+                directories::chat_local_agent_dir(),
+                directories::chat_workspace_agent_dir(os),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                let Ok(files) = os.fs.read_dir(path).await else {
+                    continue;
+                };
+                agents.append(&mut load_agents_from_entries(files, os, &mut global_mcp_config).await);
+            }
+            agents
         };
 
         let mut global_agents = 'global: {
@@ -602,6 +719,10 @@ impl Agents {
             "use_aws" => "trust read-only commands".dark_grey(),
             "report_issue" => "trusted".dark_green().bold(),
             "thinking" => "trusted (prerelease)".dark_green().bold(),
+            "manage_todo" => "trusted".dark_green().bold(),
+            "web_fetch" => "not trusted".dark_grey(),
+            "grep_search" => "trusted".dark_green().bold(),
+            "git" => "trust read-only commands".dark_grey(),
             _ if self.trust_all_tools => "trusted".dark_grey().bold(),
             _ => "not trusted".dark_grey(),
         };
@@ -633,7 +754,7 @@ async fn load_agents_from_entries(
                 },
             };
 
-            let mut agent = match serde_json::from_slice::<Agent>(&content) {
+            let mut agent = match extends::parse_agent_config(os, file_path, &content).await {
                 Ok(mut agent) => {
                     agent.path = Some(file_path.clone());
                     agent
@@ -647,21 +768,8 @@ async fn load_agents_from_entries(
 
             // The agent config could have use_legacy_mcp_json set to true but not have a valid
             // global mcp.json. We would still need to carry on loading the config.
-            'load_legacy_mcp_json: {
-                if agent.use_legacy_mcp_json && global_mcp_config.is_none() {
-                    let Ok(global_mcp_path) = directories::chat_legacy_mcp_config(os) else {
-                        tracing::error!("Error obtaining legacy mcp json path. Skipping");
-                        break 'load_legacy_mcp_json;
-                    };
-                    let legacy_mcp_config = match McpServerConfig::load_from_file(os, global_mcp_path).await {
-                        Ok(config) => config,
-                        Err(e) => {
-                            tracing::error!("Error loading global mcp json path: {e}. Skipping");
-                            break 'load_legacy_mcp_json;
-                        },
-                    };
-                    global_mcp_config.replace(legacy_mcp_config);
-                }
+            if agent.use_legacy_mcp_json && global_mcp_config.is_none() {
+                *global_mcp_config = load_legacy_mcp_config(os).await;
             }
 
             if let Err(e) = agent.thaw(file_path, global_mcp_config.as_ref()) {
@@ -677,8 +785,36 @@ async fn load_agents_from_entries(
     res
 }
 
-#[cfg(test)]
-fn validate_agent_name(name: &str) -> eyre::Result<()> {
+/// Loads the servers from the legacy global mcp.json, overlaid with the servers from the workspace
+/// .amazonq/mcp.json (which is what `q init` scaffolds). Workspace servers take precedence on
+/// name conflicts. Returns [None] if neither config could be loaded.
+async fn load_legacy_mcp_config(os: &Os) -> Option<McpServerConfig> {
+    let mut config = None::<McpServerConfig>;
+
+    for path in [
+        directories::chat_legacy_mcp_config(os).map_err(eyre::Report::from),
+        workspace_mcp_config_path(os),
+    ] {
+        let path = match path {
+            Ok(path) => path,
+            Err(e) => {
+                tracing::error!("Error obtaining legacy mcp json path: {e}. Skipping");
+                continue;
+            },
+        };
+        if !os.fs.exists(&path) {
+            continue;
+        }
+        match McpServerConfig::load_from_file(os, &path).await {
+            Ok(loaded) => config.get_or_insert_default().mcp_servers.extend(loaded.mcp_servers),
+            Err(e) => tracing::error!("Error loading mcp json at {}: {e}. Skipping", path.display()),
+        }
+    }
+
+    config
+}
+
+pub fn validate_agent_name(name: &str) -> eyre::Result<()> {
     // Check if name is empty
     if name.is_empty() {
         eyre::bail!("Agent name cannot be empty");
@@ -905,6 +1041,44 @@ mod tests {
         assert_eq!(result.unwrap_err().to_string(), "Agent 'nonexistent' does not exist");
     }
 
+    #[tokio::test]
+    async fn test_reload_mcp_servers() {
+        let os = Os::new().await.unwrap();
+        let path = PathBuf::from("/agents/dev.json");
+        os.fs.create_dir_all("/agents").await.unwrap();
+        os.fs
+            .write(
+                &path,
+                r#"{"mcpServers": {"git": {"command": "git-mcp"}}, "tools": ["@git"], "useLegacyMcpJson": false}"#,
+            )
+            .await
+            .unwrap();
+
+        let mut agent = Agent {
+            name: "dev".to_string(),
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        agent.allowed_tools.insert("@git".to_string());
+        agent.reload_mcp_servers(&os).await.unwrap();
+        assert!(agent.mcp_servers.mcp_servers.contains_key("git"));
+        assert_eq!(agent.tools, vec!["@git".to_string()]);
+        assert_eq!(agent.mcp_config_paths(&os), vec![path.clone()]);
+
+        os.fs
+            .write(
+                &path,
+                r#"{"mcpServers": {"git": {"command": "git-mcp"}, "fetch": {"command": "fetch"}}, "useLegacyMcpJson": false}"#,
+            )
+            .await
+            .unwrap();
+        agent.reload_mcp_servers(&os).await.unwrap();
+        assert_eq!(agent.mcp_servers.mcp_servers.len(), 2);
+        assert!(agent.allowed_tools.contains("@git"));
+
+        assert!(Agent::default().reload_mcp_servers(&os).await.is_err());
+    }
+
     #[test]
     fn test_validate_agent_name() {
         // Valid names
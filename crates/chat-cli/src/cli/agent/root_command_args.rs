@@ -1,5 +1,8 @@
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::process::ExitCode;
 
 use clap::{
@@ -10,11 +13,21 @@ use eyre::{
     Result,
     bail,
 };
+use serde::Serialize;
 
+use super::transfer::{
+    checksum,
+    export_agent,
+    grants,
+    read_import,
+    save_import,
+};
 use super::{
     Agent,
     Agents,
+    validate_agent_config,
 };
+use crate::cli::OutputFormat;
 use crate::database::settings::Setting;
 use crate::os::Os;
 use crate::util::directories::{
@@ -50,6 +63,55 @@ pub enum AgentSubcommands {
         #[arg(long, short)]
         from: Option<String>,
     },
+    /// Open the config of an agent in $EDITOR, validating it once the editor exits
+    Edit {
+        /// Name of the agent, or the path of its config
+        name: String,
+    },
+    /// Check the config of an agent against the agent schema
+    Validate {
+        /// Name of the agent, or the path of its config
+        name: String,
+    },
+    /// Delete the config of an agent
+    Delete {
+        /// Name of the agent, or the path of its config
+        name: String,
+    },
+    /// Export the config of an agent, with the configs it extends merged in, to share it
+    Export {
+        /// Name of the agent, or the path of its config
+        name: String,
+        /// The file to write the config to, or stdout
+        #[arg(long, short, default_value = "stdout")]
+        output: String,
+    },
+    /// Import an agent config from a file or an https url into the global agent directory, after
+    /// listing what the agent is allowed to do
+    Import {
+        /// The path or https url of the config
+        source: String,
+        /// The name to save the agent under, the name of the config file by default
+        #[arg(long, short)]
+        name: Option<String>,
+        /// The expected SHA-256 checksum of the config, as printed by `agent export`
+        #[arg(long)]
+        sha256: Option<String>,
+        /// Import without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+        /// Replace an existing agent with the same name
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// An agent as listed by `agent list` in the JSON formats.
+#[derive(Debug, Serialize)]
+struct AgentListEntry {
+    name: String,
+    /// The config file of the agent, [None] for the built in default agent
+    path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Args)]
@@ -59,10 +121,22 @@ pub struct AgentArgs {
 }
 
 impl AgentArgs {
-    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
         let mut stderr = std::io::stderr();
         let mut agents = Agents::load(os, None, true, &mut stderr).await;
         match self.cmd {
+            Some(AgentSubcommands::List) | None if format.is_json() => {
+                let mut entries = agents
+                    .agents
+                    .into_values()
+                    .map(|agent| AgentListEntry {
+                        name: agent.name,
+                        path: agent.path,
+                    })
+                    .collect::<Vec<_>>();
+                entries.sort_by(|a, b| a.name.cmp(&b.name));
+                format.print(String::new, || entries);
+            },
             Some(AgentSubcommands::List) | None => {
                 let agent_with_path =
                     agents
@@ -89,26 +163,10 @@ impl AgentArgs {
             },
             Some(AgentSubcommands::Create { name, directory, from }) => {
                 let path_with_file_name = create_agent(os, &mut agents, name.clone(), directory, from).await?;
-                let editor_cmd = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
-                let mut cmd = std::process::Command::new(editor_cmd);
-
-                let status = cmd.arg(&path_with_file_name).status()?;
-                if !status.success() {
-                    bail!("Editor process did not exit with success");
-                }
-
-                let Ok(content) = os.fs.read(&path_with_file_name).await else {
-                    bail!(
-                        "Post write validation failed. Error opening {}. Aborting",
-                        path_with_file_name.display()
-                    );
-                };
-                if let Err(e) = serde_json::from_slice::<Agent>(&content) {
-                    bail!(
-                        "Post write validation failed for agent '{name}' at path: {}. Malformed config detected: {e}",
-                        path_with_file_name.display()
-                    );
-                }
+                open_in_editor(&path_with_file_name)?;
+                validate_agent_file(os, &path_with_file_name)
+                    .await
+                    .map_err(|e| eyre::eyre!("Post write validation failed for agent '{name}': {e:#}"))?;
 
                 writeln!(
                     stderr,
@@ -121,6 +179,71 @@ impl AgentArgs {
                 rename_agent(os, &mut agents, agent.clone(), new_name.clone()).await?;
                 writeln!(stderr, "\n✓ Renamed agent '{}' to '{}'\n", agent, new_name)?;
             },
+            Some(AgentSubcommands::Edit { name }) => {
+                let path = agent_config_path(os, &agents, &name)?;
+                open_in_editor(&path)?;
+                validate_agent_file(os, &path).await?;
+                writeln!(stderr, "\n✓ Saved agent '{}' at {}\n", name, path.display())?;
+            },
+            Some(AgentSubcommands::Validate { name }) => {
+                let path = agent_config_path(os, &agents, &name)?;
+                validate_agent_file(os, &path).await?;
+                writeln!(stderr, "\n✓ Agent '{}' at {} is valid\n", name, path.display())?;
+            },
+            Some(AgentSubcommands::Delete { name }) => {
+                let path = delete_agent(os, &agents, &name).await?;
+                writeln!(stderr, "\n✓ Deleted agent '{}' at {}\n", name, path.display())?;
+            },
+            Some(AgentSubcommands::Export { name, output }) => {
+                let agent = match agents.agents.get(&name) {
+                    Some(agent) => agent.clone(),
+                    None => {
+                        let path = agent_config_path(os, &agents, &name)?;
+                        let content = os.fs.read(&path).await?;
+                        Agent::load_from_path(os, &path, &content).await?
+                    },
+                };
+                let content = export_agent(&agent)?;
+                match output.as_str() {
+                    "stdout" | "-" => println!("{content}"),
+                    path => {
+                        os.fs.write(path, &content).await?;
+                        writeln!(stderr, "\n✓ Exported agent '{}' to {}", name, path)?;
+                    },
+                }
+                writeln!(stderr, "SHA-256: {}", checksum(content.as_bytes()))?;
+            },
+            Some(AgentSubcommands::Import {
+                source,
+                name,
+                sha256,
+                yes,
+                force,
+            }) => {
+                let imported = read_import(os, &source, name, sha256.as_deref()).await?;
+                writeln!(stderr, "\nAgent '{}' from {}", imported.name, source)?;
+                if let Some(description) = &imported.agent.description {
+                    writeln!(stderr, "  {description}")?;
+                }
+                for grant in grants(&imported.agent) {
+                    writeln!(stderr, "  {grant}")?;
+                }
+                writeln!(stderr, "  SHA-256: {}", checksum(imported.content.as_bytes()))?;
+
+                if !yes {
+                    write!(stderr, "\nImport this agent and grant it the above? [y/N]: ")?;
+                    stderr.flush()?;
+                    let mut input = String::new();
+                    std::io::stdin().read_line(&mut input)?;
+                    if !matches!(input.trim(), "y" | "Y") {
+                        bail!("The agent was not imported");
+                    }
+                }
+
+                let dir = directories::chat_global_agent_path(os)?;
+                let path = save_import(os, &dir, &imported, force).await?;
+                writeln!(stderr, "\n✓ Imported agent '{}' to {}\n", imported.name, path.display())?;
+            },
         }
         Ok(ExitCode::SUCCESS)
     }
@@ -178,6 +301,54 @@ pub async fn create_agent(
     Ok(path_with_file_name)
 }
 
+/// Returns the config file of the agent called `name`, or `name` itself if it is the path of a
+/// config.
+fn agent_config_path(os: &Os, agents: &Agents, name: &str) -> Result<PathBuf> {
+    if let Some(agent) = agents.agents.get(name) {
+        return match &agent.path {
+            Some(path) => Ok(path.clone()),
+            None => bail!("Agent '{name}' is built in and has no config file"),
+        };
+    }
+
+    let path = PathBuf::from(name);
+    if path.extension().is_some_and(|ext| ext == "json") && os.fs.exists(&path) {
+        return Ok(path);
+    }
+    bail!("No agent named '{name}' was found");
+}
+
+fn open_in_editor(path: &Path) -> Result<()> {
+    let editor_cmd = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let status = std::process::Command::new(editor_cmd).arg(path).status()?;
+    if !status.success() {
+        bail!("Editor process did not exit with success");
+    }
+    Ok(())
+}
+
+async fn validate_agent_file(os: &Os, path: &Path) -> Result<()> {
+    let Ok(content) = os.fs.read_to_string(path).await else {
+        bail!("Error opening {}", path.display());
+    };
+    if let Err(e) = validate_agent_config(&content) {
+        bail!("Invalid agent config at {}. {e:#}", path.display());
+    }
+    Ok(())
+}
+
+/// Deletes the config of the agent called `name`, and clears the default agent if it was the one.
+pub async fn delete_agent(os: &mut Os, agents: &Agents, name: &str) -> Result<PathBuf> {
+    let path = agent_config_path(os, agents, name)?;
+    os.fs.remove_file(&path).await?;
+
+    let agent_name = path.file_stem().map(|stem| stem.to_string_lossy().to_string());
+    if os.database.settings.get_string(Setting::ChatDefaultAgent) == agent_name {
+        os.database.settings.remove(Setting::ChatDefaultAgent).await?;
+    }
+    Ok(path)
+}
+
 pub async fn rename_agent(os: &mut Os, agents: &mut Agents, agent: String, new_name: String) -> Result<()> {
     if agents.agents.iter().any(|(name, _)| name == &new_name) {
         bail!("New name {new_name} already exists in the current scope. Aborting");
@@ -257,4 +428,72 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_agent_subcommand_edit_validate_delete() {
+        assert_parse!(
+            ["agent", "edit", "my_agent"],
+            RootSubcommand::Agent(AgentArgs {
+                cmd: Some(AgentSubcommands::Edit {
+                    name: "my_agent".to_string(),
+                })
+            })
+        );
+        assert_parse!(
+            ["agent", "validate", "my_agent"],
+            RootSubcommand::Agent(AgentArgs {
+                cmd: Some(AgentSubcommands::Validate {
+                    name: "my_agent".to_string(),
+                })
+            })
+        );
+        assert_parse!(
+            ["agent", "delete", "my_agent"],
+            RootSubcommand::Agent(AgentArgs {
+                cmd: Some(AgentSubcommands::Delete {
+                    name: "my_agent".to_string(),
+                })
+            })
+        );
+    }
+
+    #[test]
+    fn test_agent_subcommand_export_import() {
+        assert_parse!(
+            ["agent", "export", "my_agent", "--output", "my_agent.json"],
+            RootSubcommand::Agent(AgentArgs {
+                cmd: Some(AgentSubcommands::Export {
+                    name: "my_agent".to_string(),
+                    output: "my_agent.json".to_string(),
+                })
+            })
+        );
+        assert_parse!(
+            ["agent", "export", "my_agent"],
+            RootSubcommand::Agent(AgentArgs {
+                cmd: Some(AgentSubcommands::Export {
+                    name: "my_agent".to_string(),
+                    output: "stdout".to_string(),
+                })
+            })
+        );
+        assert_parse!(
+            [
+                "agent",
+                "import",
+                "https://artifacts.example.com/agents/reviewer.json",
+                "--sha256",
+                "abc123"
+            ],
+            RootSubcommand::Agent(AgentArgs {
+                cmd: Some(AgentSubcommands::Import {
+                    source: "https://artifacts.example.com/agents/reviewer.json".to_string(),
+                    name: None,
+                    sha256: Some("abc123".to_string()),
+                    yes: false,
+                    force: false,
+                })
+            })
+        );
+    }
 }
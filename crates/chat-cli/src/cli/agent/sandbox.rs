@@ -0,0 +1,253 @@
+use std::fmt;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use eyre::{
+    Result,
+    bail,
+};
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Directories of the system that are mounted read-only in the bubblewrap sandbox, when they
+/// exist, so that the usual programs and libraries can be run.
+const SYSTEM_DIRS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc", "/opt", "/nix",
+];
+
+/// Isolation for the commands run by `execute_bash`. Commands can write to the working directory
+/// and the writable mounts only, and can't use the network unless it is enabled.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxConfig {
+    /// How commands are isolated. Defaults to bubblewrap on Linux and sandbox-exec on macOS
+    #[serde(default)]
+    pub backend: SandboxBackend,
+    /// Whether commands can use the network
+    #[serde(default)]
+    pub network: bool,
+    /// Paths made available to commands besides the working directory, e.g. "~/.cargo".
+    /// Relative paths are relative to the working directory
+    #[serde(default)]
+    pub mounts: Vec<SandboxMount>,
+    /// The image commands are run in with the docker backend
+    #[serde(default)]
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum SandboxBackend {
+    #[default]
+    Auto,
+    /// Linux namespaces with bwrap
+    Bubblewrap,
+    /// The seatbelt profiles of macOS
+    SandboxExec,
+    /// A container that is removed after each command
+    Docker,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxMount {
+    pub path: String,
+    /// Whether commands can write to the path, rather than only read it
+    #[serde(default)]
+    pub writable: bool,
+}
+
+impl SandboxConfig {
+    /// The backend used on this platform.
+    pub fn backend(&self) -> Result<SandboxBackend> {
+        let backend = match self.backend {
+            SandboxBackend::Auto if cfg!(target_os = "linux") => SandboxBackend::Bubblewrap,
+            SandboxBackend::Auto if cfg!(target_os = "macos") => SandboxBackend::SandboxExec,
+            SandboxBackend::Auto => bail!("Commands can't be sandboxed on this platform"),
+            backend => backend,
+        };
+        match backend {
+            SandboxBackend::Bubblewrap if !cfg!(target_os = "linux") => bail!("bubblewrap is only available on Linux"),
+            SandboxBackend::SandboxExec if !cfg!(target_os = "macos") => {
+                bail!("sandbox-exec is only available on macOS")
+            },
+            SandboxBackend::Docker if self.image.is_none() => bail!("The docker sandbox needs an image"),
+            backend => Ok(backend),
+        }
+    }
+
+    /// The program that runs the sandbox, which has to be installed.
+    pub fn program(&self) -> Result<&'static str> {
+        Ok(match self.backend()? {
+            SandboxBackend::Bubblewrap => "bwrap",
+            SandboxBackend::SandboxExec => "sandbox-exec",
+            SandboxBackend::Docker | SandboxBackend::Auto => "docker",
+        })
+    }
+
+    /// The program and arguments that run `command` with `shell` in the sandbox, in `cwd`.
+    pub fn command(&self, shell: &str, command: &str, cwd: &Path) -> Result<Vec<String>> {
+        let mounts = self
+            .mounts
+            .iter()
+            .map(|mount| (expand(&mount.path, cwd), mount.writable))
+            .collect::<Vec<_>>();
+        let cwd = cwd.to_string_lossy().into_owned();
+        let mut args = vec![self.program()?.to_string()];
+        match self.backend()? {
+            SandboxBackend::Bubblewrap => {
+                args.extend(["--die-with-parent", "--new-session", "--unshare-all"].map(String::from));
+                if self.network {
+                    args.push("--share-net".to_string());
+                }
+                for dir in SYSTEM_DIRS {
+                    args.extend(["--ro-bind-try", dir, dir].map(String::from));
+                }
+                args.extend(["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"].map(String::from));
+                for (path, writable) in &mounts {
+                    let path = path.to_string_lossy().into_owned();
+                    let flag = if *writable { "--bind-try" } else { "--ro-bind-try" };
+                    args.extend([flag.to_string(), path.clone(), path]);
+                }
+                args.extend([
+                    "--bind".to_string(),
+                    cwd.clone(),
+                    cwd.clone(),
+                    "--chdir".to_string(),
+                    cwd,
+                ]);
+                args.push("--".to_string());
+            },
+            SandboxBackend::SandboxExec => {
+                args.extend(["-p".to_string(), self.seatbelt_profile(&cwd, &mounts)]);
+            },
+            SandboxBackend::Docker | SandboxBackend::Auto => {
+                args.extend(["run", "--rm", "--interactive", "--network"].map(String::from));
+                args.push(if self.network { "bridge" } else { "none" }.to_string());
+                // Files written to the mounts are owned by the user rather than root
+                #[cfg(unix)]
+                args.extend([
+                    "--user".to_string(),
+                    format!("{}:{}", nix::unistd::getuid(), nix::unistd::getgid()),
+                ]);
+                for (path, writable) in &mounts {
+                    let path = path.to_string_lossy();
+                    let suffix = if *writable { "" } else { ":ro" };
+                    args.extend(["--volume".to_string(), format!("{path}:{path}{suffix}")]);
+                }
+                args.extend([
+                    "--volume".to_string(),
+                    format!("{cwd}:{cwd}"),
+                    "--workdir".to_string(),
+                    cwd,
+                    self.image.clone().unwrap_or_default(),
+                ]);
+            },
+        }
+        args.extend([shell.to_string(), "-c".to_string(), command.to_string()]);
+        Ok(args)
+    }
+
+    /// The profile for sandbox-exec. The last rule that matches an operation decides it, so the
+    /// exceptions follow the rules they make exceptions to.
+    fn seatbelt_profile(&self, cwd: &str, mounts: &[(PathBuf, bool)]) -> String {
+        let subpath = |path: &str| format!("(subpath \"{}\")", path.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut readable = vec![subpath(cwd)];
+        let mut writable = vec![
+            subpath(cwd),
+            subpath("/private/tmp"),
+            subpath("/private/var/folders"),
+            "(literal \"/dev/null\")".to_string(),
+            "(literal \"/dev/tty\")".to_string(),
+        ];
+        for (path, is_writable) in mounts {
+            let path = subpath(&path.to_string_lossy());
+            if *is_writable {
+                writable.push(path.clone());
+            }
+            readable.push(path);
+        }
+
+        let mut profile = vec!["(version 1)".to_string(), "(allow default)".to_string()];
+        if let Some(home) = dirs::home_dir() {
+            profile.push(format!("(deny file-read* {})", subpath(&home.to_string_lossy())));
+            profile.push(format!("(allow file-read* {})", readable.join(" ")));
+        }
+        profile.push("(deny file-write*)".to_string());
+        profile.push(format!("(allow file-write* {})", writable.join(" ")));
+        if !self.network {
+            profile.push("(deny network*)".to_string());
+        }
+        profile.join("\n")
+    }
+}
+
+impl fmt::Display for SandboxConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let backend = match self.backend() {
+            Ok(SandboxBackend::Bubblewrap) => "bubblewrap".to_string(),
+            Ok(SandboxBackend::SandboxExec) => "sandbox-exec".to_string(),
+            Ok(_) => format!("docker ({})", self.image.as_deref().unwrap_or_default()),
+            Err(_) => "unavailable".to_string(),
+        };
+        let network = if self.network { "with" } else { "without" };
+        write!(f, "{backend}, {network} network access")
+    }
+}
+
+/// Expands `~` and resolves relative paths against `cwd`.
+fn expand(path: &str, cwd: &Path) -> PathBuf {
+    let path = PathBuf::from(shellexpand::tilde(path).into_owned());
+    if path.is_absolute() { path } else { cwd.join(path) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(value: serde_json::Value) -> SandboxConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_bubblewrap_command() {
+        let sandbox = config(serde_json::json!({
+            "mounts": [{ "path": "/data" }, { "path": "build", "writable": true }],
+        }));
+        let args = sandbox.command("bash", "ls", Path::new("/work")).unwrap();
+        assert_eq!(args[0], "bwrap");
+        assert!(args.contains(&"--unshare-all".to_string()));
+        assert!(!args.contains(&"--share-net".to_string()));
+        let joined = args.join(" ");
+        assert!(joined.contains("--ro-bind-try /data /data"));
+        assert!(joined.contains("--bind-try /work/build /work/build"));
+        assert!(joined.ends_with("--bind /work /work --chdir /work -- bash -c ls"));
+    }
+
+    #[test]
+    fn test_docker_command() {
+        let sandbox = config(serde_json::json!({ "backend": "docker", "network": true }));
+        assert!(sandbox.command("bash", "ls", Path::new("/work")).is_err());
+
+        let sandbox = config(serde_json::json!({ "backend": "docker", "network": true, "image": "rust:1" }));
+        let args = sandbox.command("bash", "cargo test", Path::new("/work")).unwrap();
+        let joined = args.join(" ");
+        assert!(joined.starts_with("docker run --rm --interactive --network bridge"));
+        assert!(joined.ends_with("--volume /work:/work --workdir /work rust:1 bash -c cargo test"));
+    }
+
+    #[test]
+    fn test_seatbelt_profile() {
+        let sandbox = config(serde_json::json!({ "backend": "sandboxExec" }));
+        let profile = sandbox.seatbelt_profile("/work/my \"app\"", &[(PathBuf::from("/cache"), true)]);
+        assert!(profile.contains("(allow file-write* (subpath \"/work/my \\\"app\\\"\")"));
+        assert!(profile.contains("(subpath \"/cache\")"));
+        assert!(profile.ends_with("(deny network*)"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Validation of agent configs against the JSON schema generated for [Agent]. Only the keywords
+//! schemars generates for it are checked: `$ref`, `type`, `enum`, `const`, `anyOf`, `oneOf`,
+//! `allOf`, `properties`, `additionalProperties`, `required` and `items`.
+//!
+//! Fields the schema doesn't know about are reported as well, even though serde ignores them,
+//! since they are almost always misspelled fields whose settings would silently not apply.
+
+use std::fmt::Display;
+
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use serde_json::Value;
+
+use super::Agent;
+
+/// A part of an agent config that doesn't match the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    /// JSON pointer to the value, empty for the whole config
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "{}", self.message),
+            path => write!(f, "{path}: {}", self.message),
+        }
+    }
+}
+
+/// Checks `content` against the schema of [Agent], reporting every mismatch at once, and then
+/// makes sure it deserializes.
+pub fn validate_agent_config(content: &str) -> Result<()> {
+    let value = serde_json::from_str::<Value>(content).wrap_err("The agent config is not valid JSON")?;
+    let schema = serde_json::to_value(schemars::schema_for!(Agent))?;
+    let errors = schema_errors(&schema, &value);
+    if !errors.is_empty() {
+        bail!(
+            "The agent config doesn't match the agent schema:\n{}",
+            errors
+                .iter()
+                .map(|error| format!("  - {error}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    serde_json::from_value::<Agent>(value)?;
+    Ok(())
+}
+
+/// Returns the parts of `value` that don't match `schema`.
+pub fn schema_errors(schema: &Value, value: &Value) -> Vec<SchemaError> {
+    let mut errors = Vec::new();
+    check(schema, schema, value, "", &mut errors);
+    errors
+}
+
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<SchemaError>) {
+    let mut error = |message: String| {
+        errors.push(SchemaError {
+            path: path.to_string(),
+            message,
+        });
+    };
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return error("is not allowed".to_string()),
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(target) = schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| root.pointer(reference.trim_start_matches('#')))
+    {
+        check(root, target, value, path, errors);
+    }
+
+    for alternatives in ["anyOf", "oneOf"].iter().filter_map(|key| schema.get(*key)?.as_array()) {
+        // The alternative that matches best is the one the value was most likely meant to be
+        let closest = alternatives
+            .iter()
+            .map(|alternative| {
+                let mut alternative_errors = Vec::new();
+                check(root, alternative, value, path, &mut alternative_errors);
+                alternative_errors
+            })
+            .min_by_key(Vec::len);
+        errors.extend(closest.unwrap_or_default());
+    }
+    for required in schema.get("allOf").and_then(Value::as_array).into_iter().flatten() {
+        check(root, required, value, path, errors);
+    }
+
+    let mut error = |message: String| {
+        errors.push(SchemaError {
+            path: path.to_string(),
+            message,
+        });
+    };
+    if let Some(types) = schema.get("type") {
+        let types = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect::<Vec<_>>(),
+            Value::String(ty) => vec![ty.as_str()],
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|ty| has_type(value, ty)) {
+            return error(format!("expected {}, found {}", types.join(" or "), type_name(value)));
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed = allowed.iter().map(Value::to_string).collect::<Vec<_>>();
+            error(format!("must be one of {}", allowed.join(", ")));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            error(format!("must be {expected}"));
+        }
+    }
+
+    match value {
+        Value::Object(fields) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for name in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+                if let Some(name) = name.as_str().filter(|name| !fields.contains_key(*name)) {
+                    error(format!("missing field \"{name}\""));
+                }
+            }
+            for (name, field) in fields {
+                let field_path = format!("{path}/{name}");
+                match (
+                    properties.and_then(|properties| properties.get(name)),
+                    schema.get("additionalProperties"),
+                ) {
+                    (Some(field_schema), _) | (None, Some(field_schema @ Value::Object(_))) => {
+                        check(root, field_schema, field, &field_path, errors);
+                    },
+                    (None, Some(Value::Bool(true))) => (),
+                    // Editors add the schema of the file, which isn't a field
+                    (None, _) if path.is_empty() && name == "$schema" => (),
+                    (None, Some(Value::Bool(false)) | None) if properties.is_some() => {
+                        errors.push(SchemaError {
+                            path: field_path,
+                            message: "is not a known field".to_string(),
+                        });
+                    },
+                    (None, _) => (),
+                }
+            }
+        },
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(root, item_schema, item, &format!("{path}/{i}"), errors);
+                }
+            }
+        },
+        _ => (),
+    }
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_validate_agent_config() {
+        assert!(validate_agent_config(&Agent::default().to_str_pretty().unwrap()).is_ok());
+        assert!(validate_agent_config(r#"{ "$schema": "agent.json", "tools": ["*"] }"#).is_ok());
+        assert!(validate_agent_config("{ not json").is_err());
+
+        let schema = serde_json::to_value(schemars::schema_for!(Agent)).unwrap();
+        let config = json!({
+            "tools": "fs_read",
+            "resource": ["file://README.md"],
+            "allowedTools": ["fs_read", 3],
+            "useLegacyMcpJson": true,
+        });
+        let errors = schema_errors(&schema, &config)
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>();
+        // Fields are checked in the order of the map, which sorts them
+        assert_eq!(errors, [
+            "/allowedTools/1: expected string, found number",
+            "/resource: is not a known field",
+            "/tools: expected array, found string",
+        ]);
+    }
+}
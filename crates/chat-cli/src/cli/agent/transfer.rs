@@ -0,0 +1,240 @@
+//! Exporting agents as a single config and importing them from a file or an https url, so that
+//! teams can distribute vetted agents through an artifact store. An exported config has the
+//! configs it extends merged in, so that it doesn't depend on agents the importer may not have.
+//!
+//! Importing checks the config against the agent schema and, when a checksum is given, that the
+//! config is the one that was vetted. What the agent is allowed to do is listed before it is
+//! saved, since its allowed tools, MCP servers and hooks run without asking.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+
+use super::{
+    Agent,
+    validate_agent_config,
+};
+use crate::os::Os;
+
+/// The config of `agent` as exported, with the configs it extends merged in.
+pub fn export_agent(agent: &Agent) -> Result<String> {
+    let mut agent = agent.clone();
+    agent.extends = None;
+    agent.to_str_pretty()
+}
+
+/// The SHA-256 checksum of a config, in hex.
+pub fn checksum(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+/// An agent config to be imported.
+#[derive(Debug)]
+pub struct ImportedAgent {
+    /// The name the agent is saved under, derived from the source unless given
+    pub name: String,
+    pub content: String,
+    pub agent: Agent,
+}
+
+/// Reads the config at `source`, a path or an https url, and checks it against `sha256` and the
+/// agent schema.
+pub async fn read_import(os: &Os, source: &str, name: Option<String>, sha256: Option<&str>) -> Result<ImportedAgent> {
+    let content = if source.starts_with("https://") {
+        crate::request::new_client()?
+            .get(source)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec()
+    } else if source.contains("://") {
+        bail!("Agents can only be imported from a path or an https url");
+    } else {
+        os.fs
+            .read(source)
+            .await
+            .wrap_err_with(|| format!("Failed to read {source}"))?
+    };
+
+    let actual = checksum(&content);
+    if let Some(expected) = sha256 {
+        if !expected.trim().eq_ignore_ascii_case(&actual) {
+            bail!("The checksum of {source} is {actual}, not the expected {expected}");
+        }
+    }
+
+    let Ok(content) = String::from_utf8(content) else {
+        bail!("The agent config at {source} is not valid UTF-8");
+    };
+    validate_agent_config(&content).wrap_err_with(|| format!("Invalid agent config at {source}"))?;
+    let agent = serde_json::from_str::<Agent>(&content)?;
+
+    let name = match name {
+        Some(name) => name,
+        None => source_name(source)?,
+    };
+    Ok(ImportedAgent { name, content, agent })
+}
+
+/// The name of the agent at `source`: the name of its file, without the `.json` extension.
+fn source_name(source: &str) -> Result<String> {
+    let file_name = source
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit(['/', std::path::MAIN_SEPARATOR]).next())
+        .unwrap_or_default();
+    let name = file_name.strip_suffix(".json").unwrap_or(file_name);
+    if name.is_empty() {
+        bail!("Can't tell the name of the agent at {source}, pass one with --name");
+    }
+    Ok(name.to_string())
+}
+
+/// Saves an imported agent in `dir`, refusing to replace an existing agent unless `force` is set.
+pub async fn save_import(os: &Os, dir: &Path, imported: &ImportedAgent, force: bool) -> Result<PathBuf> {
+    let path = dir.join(format!("{}.json", imported.name));
+    if !force && os.fs.exists(&path) {
+        bail!(
+            "Agent '{}' already exists at {}, pass --force to replace it",
+            imported.name,
+            path.display()
+        );
+    }
+    os.fs.create_dir_all(dir).await?;
+    os.fs.write(&path, &imported.content).await?;
+    Ok(path)
+}
+
+/// What `agent` is allowed to do, one line each, for users to review before trusting it.
+pub fn grants(agent: &Agent) -> Vec<String> {
+    let sorted = |mut values: Vec<&str>| {
+        values.sort_unstable();
+        values.join(", ")
+    };
+
+    let mut lines = Vec::new();
+    if let Some(extends) = &agent.extends {
+        lines.push(format!("Extends: {extends}"));
+    }
+    if !agent.tools.is_empty() {
+        lines.push(format!("Tools: {}", agent.tools.join(", ")));
+    }
+    if !agent.allowed_tools.is_empty() {
+        lines.push(format!(
+            "Runs without asking: {}",
+            sorted(agent.allowed_tools.iter().map(String::as_str).collect())
+        ));
+    }
+    let mut servers = agent.mcp_servers.mcp_servers.iter().collect::<Vec<_>>();
+    servers.sort_by_key(|(name, _)| *name);
+    for (name, server) in servers {
+        let target = match &server.url {
+            Some(url) => url.clone(),
+            None => std::iter::once(&server.command)
+                .chain(&server.args)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        lines.push(format!("MCP server {name}: {target}"));
+    }
+    if agent.use_legacy_mcp_json {
+        lines.push("MCP servers: also those of the global and workspace mcp.json".to_string());
+    }
+    let mut hooks = agent.hooks.iter().collect::<Vec<_>>();
+    hooks.sort_by_key(|(trigger, _)| trigger.to_string());
+    for (trigger, hooks) in hooks {
+        for hook in hooks {
+            lines.push(format!("Hook {trigger}: {}", hook.command));
+        }
+    }
+    if !agent.tools_settings.is_empty() {
+        lines.push(format!(
+            "Tool settings: {}",
+            sorted(agent.tools_settings.keys().map(|target| target.as_str()).collect())
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_source_name() {
+        assert_eq!(source_name("agents/reviewer.json").unwrap(), "reviewer");
+        assert_eq!(
+            source_name("https://artifacts.example.com/agents/reviewer.json?version=3").unwrap(),
+            "reviewer"
+        );
+        assert!(source_name("https://artifacts.example.com/").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import() {
+        let os = Os::new().await.unwrap();
+        let content = json!({
+            "description": "Reviews code",
+            "tools": ["fs_read", "@git"],
+            "allowedTools": ["fs_read"],
+            "mcpServers": { "git": { "command": "git-mcp", "args": ["--read-only"] } },
+            "useLegacyMcpJson": false,
+        })
+        .to_string();
+        os.fs.write("/reviewer.json", &content).await.unwrap();
+
+        let imported = read_import(&os, "/reviewer.json", None, None).await.unwrap();
+        assert_eq!(imported.name, "reviewer");
+        assert_eq!(grants(&imported.agent), [
+            "Tools: fs_read, @git",
+            "Runs without asking: fs_read",
+            "MCP server git: git-mcp --read-only",
+        ]);
+
+        let sha256 = checksum(content.as_bytes());
+        assert!(read_import(&os, "/reviewer.json", None, Some(&sha256)).await.is_ok());
+        assert!(
+            read_import(&os, "/reviewer.json", None, Some(&checksum(b"{}")))
+                .await
+                .is_err()
+        );
+        assert!(
+            read_import(&os, "http://example.com/reviewer.json", None, None)
+                .await
+                .is_err()
+        );
+
+        let dir = PathBuf::from("/agents");
+        let path = save_import(&os, &dir, &imported, false).await.unwrap();
+        assert_eq!(os.fs.read_to_string(&path).await.unwrap(), content);
+        assert!(save_import(&os, &dir, &imported, false).await.is_err());
+        assert!(save_import(&os, &dir, &imported, true).await.is_ok());
+    }
+
+    #[test]
+    fn test_export_agent() {
+        let agent = Agent {
+            extends: Some("base".to_string()),
+            ..Default::default()
+        };
+        let exported = export_agent(&agent).unwrap();
+        assert!(validate_agent_config(&exported).is_ok());
+        assert!(!exported.contains("extends"));
+    }
+}
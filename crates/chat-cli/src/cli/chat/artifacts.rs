@@ -0,0 +1,167 @@
+//! Files left behind by tools and commands in chat sessions, such as temporary files and backups
+//! of edited files. Each one is recorded along with the conversation that created it, so that it
+//! can be removed once it is no longer useful, see [collect_garbage].
+
+use std::path::PathBuf;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use time::OffsetDateTime;
+use tracing::{
+    debug,
+    warn,
+};
+
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// Days artifacts are kept for when `chat.artifactRetentionDays` is not set.
+const DEFAULT_RETENTION_DAYS: i64 = 7;
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ArtifactKind {
+    /// Only needed while the session that created it is running
+    TempFile,
+    /// Copy of a file taken before a tool changed it
+    Backup,
+}
+
+impl ArtifactKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TempFile => "temp file",
+            Self::Backup => "backup",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Artifact {
+    pub conversation_id: String,
+    pub kind: ArtifactKind,
+    pub path: PathBuf,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+}
+
+impl Artifact {
+    /// Whether the artifact is no longer needed, either because it is older than the retention
+    /// period or because it is a temporary file of the conversation whose session ended.
+    fn is_expired(&self, now: i64, retention_days: i64, ended_conversation: Option<&str>) -> bool {
+        let ended = ended_conversation.is_some_and(|id| id == self.conversation_id);
+        (ended && self.kind == ArtifactKind::TempFile) || now - self.created_at >= retention_days * SECONDS_PER_DAY
+    }
+}
+
+/// Records `path` as an artifact created by `conversation_id`.
+pub fn track(os: &Os, conversation_id: &str, kind: ArtifactKind, path: impl Into<PathBuf>) {
+    let artifact = Artifact {
+        conversation_id: conversation_id.to_string(),
+        kind,
+        path: path.into(),
+        created_at: OffsetDateTime::now_utc().unix_timestamp(),
+    };
+    let result = os.database.get_artifacts().and_then(|mut artifacts| {
+        artifacts.push(artifact);
+        os.database.set_artifacts(&artifacts)
+    });
+    if let Err(err) = result {
+        warn!(?err, "failed to track an artifact");
+    }
+}
+
+/// Removes the artifacts that are older than `chat.artifactRetentionDays`, along with the
+/// temporary files of `ended_conversation`. Artifacts that were already removed by other means
+/// are forgotten. Returns the number of files removed.
+pub async fn collect_garbage(os: &Os, ended_conversation: Option<&str>) -> usize {
+    let artifacts = match os.database.get_artifacts() {
+        Ok(artifacts) if artifacts.is_empty() => return 0,
+        Ok(artifacts) => artifacts,
+        Err(err) => {
+            warn!(?err, "failed to read the tracked artifacts");
+            return 0;
+        },
+    };
+    let retention_days = os
+        .database
+        .settings
+        .get_int(Setting::ChatArtifactRetentionDays)
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+        .max(0);
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+
+    let mut removed = 0;
+    let mut kept = Vec::new();
+    for artifact in artifacts {
+        if !os.fs.exists(&artifact.path) {
+            continue;
+        }
+        if !artifact.is_expired(now, retention_days, ended_conversation) {
+            kept.push(artifact);
+            continue;
+        }
+        match os.fs.remove_file(&artifact.path).await {
+            Ok(()) => removed += 1,
+            Err(err) => {
+                warn!(?err, path = %artifact.path.display(), "failed to remove an artifact");
+                kept.push(artifact);
+            },
+        }
+    }
+
+    if let Err(err) = os.database.set_artifacts(&kept) {
+        warn!(?err, "failed to update the tracked artifacts");
+    }
+    debug!(removed, kept = kept.len(), "collected artifacts");
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collect_garbage() {
+        let mut os = Os::new().await.unwrap();
+        os.fs.write("/prompt.md", "draft").await.unwrap();
+        os.fs.write("/main.rs.bak", "fn main() {}").await.unwrap();
+        track(&os, "conv", ArtifactKind::TempFile, "/prompt.md");
+        track(&os, "conv", ArtifactKind::Backup, "/main.rs.bak");
+        track(&os, "conv", ArtifactKind::TempFile, "/already-removed.md");
+
+        // Another conversation ending leaves them alone, but forgets the missing file.
+        assert_eq!(collect_garbage(&os, Some("other")).await, 0);
+        assert_eq!(os.database.get_artifacts().unwrap().len(), 2);
+
+        // Backups outlive the session, temporary files do not.
+        assert_eq!(collect_garbage(&os, Some("conv")).await, 1);
+        assert!(!os.fs.exists("/prompt.md"));
+        assert!(os.fs.exists("/main.rs.bak"));
+
+        os.database
+            .settings
+            .set(Setting::ChatArtifactRetentionDays, 0)
+            .await
+            .unwrap();
+        assert_eq!(collect_garbage(&os, None).await, 1);
+        assert!(!os.fs.exists("/main.rs.bak"));
+        assert!(os.database.get_artifacts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let artifact = Artifact {
+            conversation_id: "conv".to_string(),
+            kind: ArtifactKind::Backup,
+            path: PathBuf::from("/backup"),
+            created_at: 0,
+        };
+        assert!(!artifact.is_expired(SECONDS_PER_DAY, 7, Some("conv")));
+        assert!(artifact.is_expired(7 * SECONDS_PER_DAY, 7, None));
+    }
+}
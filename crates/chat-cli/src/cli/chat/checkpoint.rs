@@ -0,0 +1,127 @@
+//! Checkpoints of the files changed by tools, undone with `/undo` and `/checkpoint restore`. One
+//! is recorded for every write of fs_write, pointing at the backup the tool takes of the file
+//! before changing it (see [ArtifactKind::Backup](super::artifacts::ArtifactKind::Backup)), or at
+//! nothing when the file was created. Changes made by commands run with execute_bash are not
+//! tracked.
+
+use std::path::PathBuf;
+
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+
+use crate::os::Os;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Numbered from 1 in the order the changes were made
+    pub id: usize,
+    pub path: PathBuf,
+    /// Copy of the file from before the change, or None if the change created it
+    pub backup: Option<PathBuf>,
+}
+
+impl Checkpoint {
+    /// Puts the file back the way it was before the change.
+    async fn revert(&self, os: &Os) -> Result<()> {
+        match &self.backup {
+            Some(backup) if !os.fs.exists(backup) => {
+                bail!("its backup {} no longer exists", backup.display())
+            },
+            Some(backup) => {
+                os.fs.copy(backup, &self.path).await?;
+            },
+            None if os.fs.exists(&self.path) => os.fs.remove_file(&self.path).await?,
+            None => (),
+        }
+        Ok(())
+    }
+}
+
+/// The changes made in a session that can still be undone, oldest first.
+#[derive(Debug, Default)]
+pub struct Checkpoints {
+    checkpoints: Vec<Checkpoint>,
+    last_id: usize,
+}
+
+impl Checkpoints {
+    /// Records a change to `path`, taking `backup` as its contents before the change.
+    pub fn record(&mut self, path: PathBuf, backup: Option<PathBuf>) {
+        self.last_id += 1;
+        self.checkpoints.push(Checkpoint {
+            id: self.last_id,
+            path,
+            backup,
+        });
+    }
+
+    pub fn list(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    pub fn last(&self) -> Option<&Checkpoint> {
+        self.checkpoints.last()
+    }
+
+    /// Undoes the change of checkpoint `id` along with every later one, latest first, and returns
+    /// the checkpoints that were undone. If a file can't be restored, the changes that were undone
+    /// until then stay undone.
+    pub async fn restore(&mut self, os: &Os, id: usize) -> Result<Vec<Checkpoint>> {
+        let Some(index) = self.checkpoints.iter().position(|checkpoint| checkpoint.id == id) else {
+            bail!("There is no checkpoint {id}, see /checkpoint list");
+        };
+
+        let mut restored = Vec::new();
+        while self.checkpoints.len() > index {
+            let Some(checkpoint) = self.checkpoints.last() else {
+                break;
+            };
+            checkpoint
+                .revert(os)
+                .await
+                .map_err(|err| eyre!("Failed to restore {}: {err}", checkpoint.path.display()))?;
+            restored.extend(self.checkpoints.pop());
+        }
+        Ok(restored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_restore() {
+        let os = Os::new().await.unwrap();
+        os.fs.write("/main.rs", "v2").await.unwrap();
+        os.fs.write("/main.rs.bak1", "v1").await.unwrap();
+        os.fs.write("/main.rs.bak2", "v2").await.unwrap();
+        let mut checkpoints = Checkpoints::default();
+        checkpoints.record("/main.rs".into(), Some("/main.rs.bak1".into()));
+        checkpoints.record("/new.rs".into(), None);
+        checkpoints.record("/main.rs".into(), Some("/main.rs.bak2".into()));
+        os.fs.write("/main.rs", "v3").await.unwrap();
+        os.fs.write("/new.rs", "new").await.unwrap();
+
+        assert!(checkpoints.restore(&os, 4).await.is_err());
+        let restored = checkpoints.restore(&os, 3).await.unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(os.fs.read_to_string("/main.rs").await.unwrap(), "v2");
+
+        // Later checkpoints are undone first
+        let restored = checkpoints.restore(&os, 1).await.unwrap();
+        assert_eq!(restored.iter().map(|c| c.id).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(os.fs.read_to_string("/main.rs").await.unwrap(), "v1");
+        assert!(!os.fs.exists("/new.rs"));
+        assert!(checkpoints.list().is_empty());
+
+        // Ids are not reused
+        checkpoints.record("/main.rs".into(), Some("/missing.bak".into()));
+        assert_eq!(checkpoints.last().map(|c| c.id), Some(4));
+        assert!(checkpoints.restore(&os, 4).await.is_err());
+        assert_eq!(checkpoints.list().len(), 1);
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+use std::io::Write;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use clap::Args;
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use crate::cli::chat::notify::Completion;
+use crate::cli::chat::token_counter::TokenCounter;
+use crate::cli::chat::util::truncate_safe;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::util::datetime::format_duration;
+use crate::util::theme::theme;
+
+/// Line the model replies with once the goal has been achieved.
+const DONE_MARKER: &str = "[AUTO_GOAL_COMPLETE]";
+/// Maximum length of the model's final message included in the progress report.
+const SUMMARY_MAX_LEN: usize = 1000;
+
+/// Arguments for the `/auto` command, which works toward a goal without prompting the user for
+/// every turn.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "/auto keeps the conversation going on its own until the goal is achieved or
+one of the limits is reached. Tools are still subject to the agent's permissions, so tools that
+are not trusted will prompt for approval as usual.
+
+Press ctrl+c at any time to stop. A progress report is printed when the run ends, and sent to the
+chat.completionCommand and chat.completionWebhook settings when they are set.
+
+Examples
+• /auto 10m fix the failing tests in crates/parser
+• /auto 1h --max-turns 50 migrate the remaining handlers to the new router"
+)]
+pub struct AutoArgs {
+    /// How long to run for, e.g. 30s, 10m or 1h
+    #[arg(value_parser = parse_duration)]
+    duration: Duration,
+    /// Stop after the model has responded this many times
+    #[arg(long)]
+    max_turns: Option<usize>,
+    /// Stop after the model has generated approximately this many tokens
+    #[arg(long)]
+    max_tokens: Option<usize>,
+    /// The goal to work toward
+    #[arg(required = true, trailing_var_arg = true)]
+    goal: Vec<String>,
+}
+
+impl AutoArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let auto_mode = AutoMode::new(self.goal.join(" "), self.duration, self.max_turns, self.max_tokens);
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(theme().success),
+            style::Print(format!(
+                "\nRunning autonomously for up to {}. Press ctrl+c to stop.\n\n",
+                format_duration(self.duration)
+            )),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+
+        let input = auto_mode.initial_prompt();
+        session.auto_mode = Some(auto_mode);
+        Ok(ChatState::HandleInput { input })
+    }
+}
+
+/// Why an autonomous run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    GoalComplete,
+    TimeLimit,
+    TurnLimit,
+    TokenLimit,
+    Interrupted,
+    Error,
+    /// Control was returned to the user for any other reason, e.g. the request was not sent
+    Stopped,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            StopReason::GoalComplete => "goal complete",
+            StopReason::TimeLimit => "time limit reached",
+            StopReason::TurnLimit => "turn limit reached",
+            StopReason::TokenLimit => "token limit reached",
+            StopReason::Interrupted => "interrupted",
+            StopReason::Error => "stopped due to an error",
+            StopReason::Stopped => "stopped",
+        })
+    }
+}
+
+/// State of an autonomous run started with `/auto`.
+#[derive(Debug)]
+pub struct AutoMode {
+    goal: String,
+    started: Instant,
+    duration: Duration,
+    max_turns: Option<usize>,
+    max_tokens: Option<usize>,
+    turns: usize,
+    tokens: usize,
+    tool_uses: BTreeMap<String, usize>,
+    /// Files written during the run
+    artifacts: BTreeSet<String>,
+    last_response: String,
+}
+
+impl AutoMode {
+    pub fn new(goal: String, duration: Duration, max_turns: Option<usize>, max_tokens: Option<usize>) -> Self {
+        Self {
+            goal,
+            started: Instant::now(),
+            duration,
+            max_turns,
+            max_tokens,
+            turns: 0,
+            tokens: 0,
+            tool_uses: BTreeMap::new(),
+            artifacts: BTreeSet::new(),
+            last_response: String::new(),
+        }
+    }
+
+    pub fn initial_prompt(&self) -> String {
+        format!(
+            "You are working autonomously toward the goal below and the user is not available to answer \
+            questions. Keep making progress with the tools available to you and make reasonable assumptions \
+            instead of asking for input. Once the goal is fully achieved, reply with a short summary of what \
+            was done followed by {DONE_MARKER} on its own line.\n\nGoal: {}",
+            self.goal
+        )
+    }
+
+    /// The message sent on the user's behalf whenever the model ends its turn before the goal is
+    /// complete.
+    pub fn continuation_prompt(&self) -> String {
+        format!(
+            "Continue working toward the goal: {}\n\nYou have about {} left. If the goal is fully achieved, \
+            reply with a short summary followed by {DONE_MARKER} on its own line.",
+            self.goal,
+            format_duration(self.remaining())
+        )
+    }
+
+    /// Records a response from the model along with the names of the tools it used.
+    pub fn record_response(&mut self, content: &str, tool_names: impl IntoIterator<Item = String>) {
+        self.turns += 1;
+        self.tokens += TokenCounter::count_tokens(content);
+        for name in tool_names {
+            *self.tool_uses.entry(name).or_default() += 1;
+        }
+        if !content.trim().is_empty() {
+            self.last_response = content.to_string();
+        }
+    }
+
+    pub fn record_artifact(&mut self, path: &str) {
+        self.artifacts.insert(path.to_string());
+    }
+
+    /// Returns why the run should stop after the latest response, if it should.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        if self.last_response.contains(DONE_MARKER) {
+            Some(StopReason::GoalComplete)
+        } else if self.remaining().is_zero() {
+            Some(StopReason::TimeLimit)
+        } else if self.max_turns.is_some_and(|max| self.turns >= max) {
+            Some(StopReason::TurnLimit)
+        } else if self.max_tokens.is_some_and(|max| self.tokens >= max) {
+            Some(StopReason::TokenLimit)
+        } else {
+            None
+        }
+    }
+
+    fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.started.elapsed())
+    }
+
+    /// The completion notification for a run that stopped for `reason`.
+    pub fn completion(&self, reason: StopReason, conversation_id: &str) -> Completion {
+        Completion {
+            source: "auto",
+            status: reason.to_string(),
+            summary: self.summary(),
+            artifacts: self.artifacts.iter().cloned().collect(),
+            conversation_id: conversation_id.to_string(),
+        }
+    }
+
+    /// The model's final message without the marker it ends with.
+    fn summary(&self) -> String {
+        self.last_response.replace(DONE_MARKER, "").trim().to_string()
+    }
+
+    pub fn print_report(&self, output: &mut impl Write, reason: StopReason) -> std::io::Result<()> {
+        let color = match reason {
+            StopReason::GoalComplete => theme().success,
+            StopReason::Interrupted | StopReason::Error => theme().error,
+            _ => theme().warning,
+        };
+        queue!(
+            output,
+            style::SetAttribute(Attribute::Bold),
+            style::Print("\nAutonomous run ended: "),
+            style::SetForegroundColor(color),
+            style::Print(format!("{reason}\n")),
+            style::SetForegroundColor(Color::Reset),
+            style::SetAttribute(Attribute::Reset),
+            style::Print(format!("  Goal:     {}\n", self.goal)),
+            style::Print(format!(
+                "  Elapsed:  {} of {}\n",
+                format_duration(self.started.elapsed()),
+                format_duration(self.duration)
+            )),
+            style::Print(format!("  Turns:    {}{}\n", self.turns, limit_suffix(self.max_turns))),
+            style::Print(format!(
+                "  Tokens:   ~{}{}\n",
+                self.tokens,
+                limit_suffix(self.max_tokens)
+            )),
+        )?;
+
+        let tool_uses = match self.tool_uses.is_empty() {
+            true => "none".to_string(),
+            false => self
+                .tool_uses
+                .iter()
+                .map(|(name, count)| format!("{name} ({count})"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        queue!(output, style::Print(format!("  Tools:    {tool_uses}\n")))?;
+        if !self.artifacts.is_empty() {
+            let artifacts = self.artifacts.iter().cloned().collect::<Vec<_>>().join(", ");
+            queue!(output, style::Print(format!("  Files:    {artifacts}\n")))?;
+        }
+
+        let summary = self.summary();
+        if !summary.is_empty() {
+            let truncated = truncate_safe(&summary, SUMMARY_MAX_LEN);
+            queue!(
+                output,
+                style::Print("  Last response:\n"),
+                style::SetForegroundColor(theme().secondary),
+            )?;
+            for line in truncated.lines() {
+                queue!(output, style::Print(format!("    {line}\n")))?;
+            }
+            if truncated.len() < summary.len() {
+                queue!(output, style::Print("    ...\n"))?;
+            }
+            queue!(output, style::SetForegroundColor(Color::Reset))?;
+        }
+        execute!(output, style::Print("\n"))
+    }
+}
+
+fn limit_suffix(limit: Option<usize>) -> String {
+    limit.map(|max| format!(" of {max}")).unwrap_or_default()
+}
+
+/// Parses durations such as "90s", "10m", "1h" or "1h30m". A number without a unit is taken to
+/// be minutes.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    if let Ok(minutes) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(minutes * 60));
+    }
+
+    let invalid = || format!("invalid duration '{value}', expected e.g. 30s, 10m or 1h");
+    let mut secs = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let multiplier = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let Ok(n) = number.parse::<u64>() else {
+            return Err(invalid());
+        };
+        secs += n * multiplier;
+        number.clear();
+    }
+    if !number.is_empty() || secs == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("10").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("1h5").is_err());
+    }
+
+    #[test]
+    fn test_stop_reason() {
+        let mut auto_mode = AutoMode::new("goal".to_string(), Duration::from_secs(600), Some(3), Some(10_000));
+        auto_mode.record_response("working on it", ["fs_read".to_string()]);
+        assert_eq!(auto_mode.stop_reason(), None);
+
+        auto_mode.record_response("", ["fs_write".to_string(), "fs_read".to_string()]);
+        auto_mode.record_response("", []);
+        assert_eq!(auto_mode.stop_reason(), Some(StopReason::TurnLimit));
+        assert_eq!(auto_mode.tool_uses["fs_read"], 2);
+        assert_eq!(auto_mode.last_response, "working on it");
+
+        auto_mode.record_response(&format!("All done\n{DONE_MARKER}"), []);
+        assert_eq!(auto_mode.stop_reason(), Some(StopReason::GoalComplete));
+
+        let auto_mode = AutoMode::new("goal".to_string(), Duration::ZERO, None, None);
+        assert_eq!(auto_mode.stop_reason(), Some(StopReason::TimeLimit));
+    }
+
+    #[test]
+    fn test_completion() {
+        let mut auto_mode = AutoMode::new("goal".to_string(), Duration::from_secs(600), None, None);
+        auto_mode.record_artifact("src/main.rs");
+        auto_mode.record_artifact("src/lib.rs");
+        auto_mode.record_artifact("src/main.rs");
+        auto_mode.record_response(&format!("Fixed the tests.\n{DONE_MARKER}"), []);
+
+        let completion = auto_mode.completion(StopReason::GoalComplete, "conv");
+        assert_eq!(completion.status, "goal complete");
+        assert_eq!(completion.summary, "Fixed the tests.");
+        assert_eq!(completion.artifacts, vec!["src/lib.rs", "src/main.rs"]);
+        assert_eq!(completion.conversation_id, "conv");
+    }
+
+    #[test]
+    fn test_auto_args() {
+        use clap::Parser;
+
+        use crate::cli::chat::cli::SlashCommand;
+
+        let command =
+            SlashCommand::try_parse_from(["", "auto", "10m", "--max-turns", "5", "fix", "the", "tests"]).unwrap();
+        assert_eq!(
+            command,
+            SlashCommand::Auto(AutoArgs {
+                duration: Duration::from_secs(600),
+                max_turns: Some(5),
+                max_tokens: None,
+                goal: vec!["fix".to_string(), "the".to_string(), "tests".to_string()],
+            })
+        );
+    }
+}
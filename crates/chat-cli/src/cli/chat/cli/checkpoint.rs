@@ -0,0 +1,198 @@
+use std::path::PathBuf;
+
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::{
+    self,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use crate::cli::chat::checkpoint::Checkpoint;
+use crate::cli::chat::tools::{
+    format_path,
+    sanitize_path_tool_arg,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::cli::snapshots::{
+    self,
+    RestoreOutcome,
+};
+use crate::os::Os;
+use crate::util::theme::theme;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct UndoArgs;
+
+impl UndoArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match session.checkpoints.last().map(|checkpoint| checkpoint.id) {
+            Some(id) => restore(os, session, id, "undo").await,
+            None => {
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(theme().secondary),
+                    style::Print("\nNo changes made by tools in this session can be undone.\n\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+                Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                })
+            },
+        }
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct RevertArgs {
+    /// Only revert these files
+    paths: Vec<PathBuf>,
+}
+
+impl RevertArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let cwd = os.env.current_dir()?;
+        let paths = self
+            .paths
+            .iter()
+            .map(|path| cwd.join(sanitize_path_tool_arg(os, path)))
+            .collect::<Vec<_>>();
+        let outcomes = snapshots::restore(os, session.conversation.conversation_id(), &paths)
+            .await
+            .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+
+        let describe = |outcome: &RestoreOutcome| {
+            let path = format_path(&cwd, outcome.path());
+            match outcome {
+                RestoreOutcome::Restored { .. } => format!("\n- restored {path}"),
+                RestoreOutcome::Removed { .. } => format!("\n- removed {path}"),
+                RestoreOutcome::Failed { reason, .. } => format!("\n- could not restore {path}: {reason}"),
+            }
+        };
+        let reverted = outcomes
+            .iter()
+            .filter(|outcome| !matches!(outcome, RestoreOutcome::Failed { .. }))
+            .map(describe)
+            .collect::<String>();
+        if !reverted.is_empty() {
+            session.pending_context.push(format!(
+                "[/revert]\nThe user reverted the changes you made to these files in this session, they are back to how they were before it:{reverted}"
+            ));
+        }
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(theme().success),
+            style::Print(format!("\n✔ Reverted {} file(s):", outcomes.len())),
+            style::SetForegroundColor(Color::Reset),
+            style::Print(outcomes.iter().map(describe).collect::<String>()),
+            style::Print("\n\n"),
+        )?;
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "A checkpoint is recorded every time fs_write changes a file, so that the change can be undone.
+Changes made by commands run with execute_bash are not tracked. /undo restores the latest checkpoint."
+)]
+pub enum CheckpointSubcommand {
+    /// List the changes that can be undone
+    #[command(alias = "ls")]
+    List,
+    /// Undo the change of a checkpoint along with every later one
+    Restore {
+        /// Id of the checkpoint, as shown by /checkpoint list
+        id: usize,
+    },
+}
+
+impl CheckpointSubcommand {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self {
+            Self::List => {
+                let cwd = os.env.current_dir()?;
+                if session.checkpoints.list().is_empty() {
+                    queue!(
+                        session.stderr,
+                        style::SetForegroundColor(theme().secondary),
+                        style::Print("\nNo checkpoints yet, tools have not changed any files.\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                }
+                for checkpoint in session.checkpoints.list().iter().rev() {
+                    queue!(
+                        session.stderr,
+                        style::SetForegroundColor(theme().info),
+                        style::Print(format!("\n{:>4}  ", checkpoint.id)),
+                        style::SetForegroundColor(Color::Reset),
+                        style::Print(format!("{:<9}", describe(checkpoint))),
+                        style::Print(format_path(&cwd, &checkpoint.path)),
+                    )?;
+                }
+                execute!(session.stderr, style::Print("\n\n"))?;
+                Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                })
+            },
+            Self::Restore { id } => restore(os, session, id, "checkpoint restore").await,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::List => "list",
+            Self::Restore { .. } => "restore",
+        }
+    }
+}
+
+fn describe(checkpoint: &Checkpoint) -> &'static str {
+    match checkpoint.backup {
+        Some(_) => "modified",
+        None => "created",
+    }
+}
+
+/// Restores checkpoint `id` and lets the model know about the files that changed under it.
+async fn restore(os: &Os, session: &mut ChatSession, id: usize, command: &str) -> Result<ChatState, ChatError> {
+    let restored = session
+        .checkpoints
+        .restore(os, id)
+        .await
+        .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+
+    let cwd = os.env.current_dir()?;
+    let undone = restored
+        .iter()
+        .map(|checkpoint| format!("\n- {} {}", describe(checkpoint), format_path(&cwd, &checkpoint.path)))
+        .collect::<String>();
+    session.pending_context.push(format!(
+        "[/{command}]\nThe user undid these changes you made, the files are back to how they were before them:{undone}"
+    ));
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(theme().success),
+        style::Print(format!("\n✔ Undid {} change(s):", restored.len())),
+        style::SetForegroundColor(Color::Reset),
+        style::Print(undone),
+        style::Print("\n\n"),
+    )?;
+    Ok(ChatState::PromptUser {
+        skip_printing_tools: true,
+    })
+}
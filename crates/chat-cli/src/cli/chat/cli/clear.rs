@@ -14,6 +14,7 @@ use crate::cli::chat::{
     ChatSession,
     ChatState,
 };
+use crate::util::theme::theme;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
@@ -23,18 +24,18 @@ impl ClearArgs {
     pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         execute!(
             session.stderr,
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print(
                 "\nAre you sure? This will erase the conversation history and context from hooks for the current session. "
             ),
             style::Print("["),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print("y"),
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print("/"),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print("n"),
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print("]:\n\n"),
             style::SetForegroundColor(Color::Reset),
             cursor::Show,
@@ -53,7 +54,7 @@ impl ClearArgs {
             }
             execute!(
                 session.stderr,
-                style::SetForegroundColor(Color::Green),
+                style::SetForegroundColor(theme().success),
                 style::Print("\nConversation history cleared.\n\n"),
                 style::SetForegroundColor(Color::Reset)
             )?;
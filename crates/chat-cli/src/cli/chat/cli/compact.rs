@@ -7,8 +7,19 @@ use crate::cli::chat::{
     ChatSession,
     ChatState,
 };
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
 use crate::os::Os;
 
+/// How full the context window gets before the history is compacted ahead of a request, unless
+/// `chat.autoCompactPercent` says otherwise.
+const DEFAULT_AUTO_COMPACT_PERCENT: usize = 90;
+/// How many of the latest user and assistant message pairs are kept as they are when the history
+/// is compacted ahead of a request.
+pub const AUTO_COMPACT_KEEP_RECENT: usize = 4;
+
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
 #[command(
@@ -28,7 +39,9 @@ How it works
 • Clears the conversation history to free up space
 • The assistant will reference the summary context in future responses
 
-Compaction will be automatically performed whenever the context window overflows.
+Compaction is automatically performed before a request once the conversation takes up 90% of the
+context window, keeping the latest messages as they are, or whenever the context window overflows.
+To change the threshold, run: `q settings chat.autoCompactPercent 80`, or 0 to wait for overflows
 To disable this behavior, run: `q settings chat.disableAutoCompaction true`"
 )]
 pub struct CompactArgs {
@@ -60,6 +73,10 @@ impl CompactArgs {
         session
             .compact_history(os, prompt, self.show_summary, CompactStrategy {
                 messages_to_exclude: self.messages_to_exclude.unwrap_or(default.messages_to_exclude),
+                anchor_messages: session
+                    .conversation
+                    .context_strategy(os)
+                    .anchor_len(session.conversation.history()),
                 truncate_large_messages: self.truncate_large_messages.unwrap_or(default.truncate_large_messages),
                 max_message_length: self.max_message_length.map_or(default.max_message_length, |v| {
                     v.clamp(UserMessageContent::TRUNCATED_SUFFIX.len(), MAX_USER_MESSAGE_SIZE)
@@ -69,11 +86,27 @@ impl CompactArgs {
     }
 }
 
+/// Returns the share of the context window at which the history is compacted before a request is
+/// sent, or [None] if that is disabled.
+pub fn auto_compact_percent(settings: &Settings) -> Option<usize> {
+    if settings.get_bool(Setting::ChatDisableAutoCompaction).unwrap_or(false) {
+        return None;
+    }
+    match settings.get_int(Setting::ChatAutoCompactPercent) {
+        Some(percent) if percent <= 0 => None,
+        Some(percent) => usize::try_from(percent.min(100)).ok(),
+        None => Some(DEFAULT_AUTO_COMPACT_PERCENT),
+    }
+}
+
 /// Parameters for performing the history compaction request.
 #[derive(Debug, Copy, Clone)]
 pub struct CompactStrategy {
     /// Number of user/assistant pairs to exclude from the history as part of compaction.
     pub messages_to_exclude: usize,
+    /// Number of user/assistant pairs at the start of the history to keep out of the summary, for
+    /// history strategies that pin the first turns.
+    pub anchor_messages: usize,
     /// Whether or not to truncate large messages in the history.
     pub truncate_large_messages: bool,
     /// Maximum allowed size of messages in the conversation history.
@@ -84,8 +117,31 @@ impl Default for CompactStrategy {
     fn default() -> Self {
         Self {
             messages_to_exclude: Default::default(),
+            anchor_messages: Default::default(),
             truncate_large_messages: Default::default(),
             max_message_length: MAX_USER_MESSAGE_SIZE,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_auto_compact_percent() {
+        let mut settings = Settings::new().await.unwrap();
+        assert_eq!(auto_compact_percent(&settings), Some(DEFAULT_AUTO_COMPACT_PERCENT));
+
+        settings.set(Setting::ChatAutoCompactPercent, 75).await.unwrap();
+        assert_eq!(auto_compact_percent(&settings), Some(75));
+        settings.set(Setting::ChatAutoCompactPercent, 150).await.unwrap();
+        assert_eq!(auto_compact_percent(&settings), Some(100));
+        settings.set(Setting::ChatAutoCompactPercent, 0).await.unwrap();
+        assert_eq!(auto_compact_percent(&settings), None);
+
+        settings.set(Setting::ChatAutoCompactPercent, 75).await.unwrap();
+        settings.set(Setting::ChatDisableAutoCompaction, true).await.unwrap();
+        assert_eq!(auto_compact_percent(&settings), None);
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::sync::Arc;
 
 use clap::Subcommand;
 use crossterm::style::{
@@ -10,15 +10,24 @@ use crossterm::{
     style,
 };
 
-use crate::cli::chat::consts::CONTEXT_FILES_MAX_SIZE;
+use crate::cli::chat::context::{
+    ContextResource,
+    MAX_FILES_PER_PATH,
+};
 use crate::cli::chat::token_counter::TokenCounter;
-use crate::cli::chat::util::drop_matched_context_files;
+use crate::cli::chat::tool_manager::ToolManager;
+use crate::cli::chat::tools::custom_tool::CustomToolClient;
+use crate::cli::chat::util::{
+    BudgetOutcome,
+    MIN_TRUNCATED_TOKENS,
+};
 use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
 };
 use crate::os::Os;
+use crate::util::theme::theme;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Subcommand)]
@@ -31,7 +40,11 @@ more accurate and helpful responses.
 
 Notes:
 • You can add specific files or use glob patterns (e.g., \"*.py\", \"src/**/*.js\")
+• Directories add the files in them, and those of their subdirectories too with --recursive. Files ignored by .gitignore are left out, and a pattern or directory adds 250 files at most
+• Web pages are added by url, e.g. /context add https://docs.rs/tokio, and refreshed every hour, or as often as `q settings chat.contextUrlRefreshMinutes` says
+• Resources of MCP servers are added with /context add-resource <server>/<uri>
 • Agent rules apply only to the current agent 
+• Context files share a budget of 150000 tokens, set with `q settings chat.contextBudgetTokens`. /context show lists the files that are truncated or dropped to fit it
 • Context changes are NOT preserved between chat sessions. To make these changes permanent, edit the agent config file."
 )]
 pub enum ContextSubcommand {
@@ -42,15 +55,24 @@ pub enum ContextSubcommand {
         #[arg(long)]
         expand: bool,
     },
-    /// Add context rules (filenames or glob patterns)
+    /// Add context rules (filenames, directories, glob patterns such as src/**/*.rs or urls)
     Add {
         /// Include even if matched files exceed size limits
         #[arg(short, long)]
         force: bool,
+        /// Include the files in subdirectories of directories as well
+        #[arg(short, long)]
+        recursive: bool,
         #[arg(required = true)]
         paths: Vec<String>,
     },
-    /// Remove specified rules from current profile
+    /// Add a resource of an MCP server as <server>/<uri>, or list the resources of a server by
+    /// giving only its name
+    AddResource {
+        #[arg(required = true)]
+        resource: String,
+    },
+    /// Remove specified rules or resources from current profile
     #[command(alias = "rm")]
     Remove {
         #[arg(required = true)]
@@ -67,7 +89,7 @@ impl ContextSubcommand {
         let Some(context_manager) = &mut session.conversation.context_manager else {
             execute!(
                 session.stderr,
-                style::SetForegroundColor(Color::Red),
+                style::SetForegroundColor(theme().error),
                 style::Print("\nContext management is not available.\n\n"),
                 style::SetForegroundColor(Color::Reset)
             )?;
@@ -79,11 +101,10 @@ impl ContextSubcommand {
 
         match self {
             Self::Show { expand } => {
-                let profile_context_files = HashSet::<(String, String)>::new();
                 execute!(
                     session.stderr,
                     style::SetAttribute(Attribute::Bold),
-                    style::SetForegroundColor(Color::Magenta),
+                    style::SetForegroundColor(theme().tool),
                     style::Print(format!("\n👤 Agent ({}):\n", context_manager.current_profile)),
                     style::SetAttribute(Attribute::Reset),
                 )?;
@@ -91,7 +112,7 @@ impl ContextSubcommand {
                 if context_manager.paths.is_empty() {
                     execute!(
                         session.stderr,
-                        style::SetForegroundColor(Color::DarkGrey),
+                        style::SetForegroundColor(theme().secondary),
                         style::Print("    <none>\n\n"),
                         style::SetForegroundColor(Color::Reset)
                     )?;
@@ -101,7 +122,7 @@ impl ContextSubcommand {
                         if let Ok(context_files) = context_manager.get_context_files_by_path(os, path).await {
                             execute!(
                                 session.stderr,
-                                style::SetForegroundColor(Color::Green),
+                                style::SetForegroundColor(theme().success),
                                 style::Print(format!(
                                     "({} match{})",
                                     context_files.len(),
@@ -115,22 +136,44 @@ impl ContextSubcommand {
                     execute!(session.stderr, style::Print("\n"))?;
                 }
 
-                if profile_context_files.is_empty() {
+                if !context_manager.resources.is_empty() {
                     execute!(
                         session.stderr,
-                        style::SetForegroundColor(Color::DarkGrey),
+                        style::SetAttribute(Attribute::Bold),
+                        style::SetForegroundColor(theme().tool),
+                        style::Print("🔌 MCP resources:\n"),
+                        style::SetAttribute(Attribute::Reset),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    for resource in &context_manager.resources {
+                        execute!(
+                            session.stderr,
+                            style::Print(format!("    {} ", resource.name())),
+                            style::SetForegroundColor(theme().secondary),
+                            style::Print(format!("(~{} tkns)\n", TokenCounter::count_tokens(&resource.content))),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    }
+                    execute!(session.stderr, style::Print("\n"))?;
+                }
+
+                let files = context_manager
+                    .collect_budgeted_context_files(os)
+                    .await
+                    .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+                if files.is_empty() {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(theme().secondary),
                         style::Print("No files in the current directory matched the rules above.\n\n"),
                         style::SetForegroundColor(Color::Reset)
                     )?;
                 } else {
-                    let total = profile_context_files.len();
-                    let total_tokens = profile_context_files
-                        .iter()
-                        .map(|(_, content)| TokenCounter::count_tokens(content))
-                        .sum::<usize>();
+                    let budget = context_manager.token_budget(os);
+                    let total = files.len();
                     execute!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::SetAttribute(Attribute::Bold),
                         style::Print(format!(
                             "{} matched file{} in use:\n",
@@ -141,20 +184,33 @@ impl ContextSubcommand {
                         style::SetAttribute(Attribute::Reset)
                     )?;
 
-                    for (filename, content) in &profile_context_files {
-                        let est_tokens = TokenCounter::count_tokens(content);
+                    let mut used_tokens = 0;
+                    for file in &files {
+                        execute!(
+                            session.stderr,
+                            style::Print(format!("👤 {} ", file.name)),
+                            style::SetForegroundColor(theme().secondary),
+                            style::Print(format!("(~{} tkns)", file.tokens)),
+                            style::SetForegroundColor(theme().warning),
+                        )?;
+                        match file.outcome {
+                            BudgetOutcome::Included => used_tokens += file.tokens,
+                            BudgetOutcome::Truncated(kept) => {
+                                used_tokens += kept;
+                                execute!(session.stderr, style::Print(format!(" truncated to ~{kept} tkns")))?;
+                            },
+                            BudgetOutcome::Dropped => execute!(session.stderr, style::Print(" dropped"))?,
+                        }
                         execute!(
                             session.stderr,
-                            style::Print(format!("👤 {} ", filename)),
-                            style::SetForegroundColor(Color::DarkGrey),
-                            style::Print(format!("(~{} tkns)\n", est_tokens)),
                             style::SetForegroundColor(Color::Reset),
+                            style::Print("\n")
                         )?;
-                        if expand {
+                        if expand && !file.content.is_empty() {
                             execute!(
                                 session.stderr,
-                                style::SetForegroundColor(Color::DarkGrey),
-                                style::Print(format!("{}\n\n", content)),
+                                style::SetForegroundColor(theme().secondary),
+                                style::Print(format!("{}\n\n", file.content)),
                                 style::SetForegroundColor(Color::Reset)
                             )?;
                         }
@@ -164,50 +220,21 @@ impl ContextSubcommand {
                         execute!(session.stderr, style::Print(format!("{}\n\n", "▔".repeat(3))),)?;
                     }
 
-                    let mut files_as_vec = profile_context_files.iter().cloned().collect::<Vec<_>>();
-                    let dropped_files = drop_matched_context_files(&mut files_as_vec, CONTEXT_FILES_MAX_SIZE).ok();
-
                     execute!(
                         session.stderr,
-                        style::Print(format!("\nTotal: ~{} tokens\n\n", total_tokens))
+                        style::Print(format!("\nTotal: ~{used_tokens} of {budget} tokens\n\n"))
                     )?;
 
-                    if let Some(dropped_files) = dropped_files {
-                        if !dropped_files.is_empty() {
-                            execute!(
-                                session.stderr,
-                                style::SetForegroundColor(Color::DarkYellow),
-                                style::Print(format!(
-                                    "Total token count exceeds limit: {}. The following files will be automatically dropped when interacting with Q. Consider removing them. \n\n",
-                                    CONTEXT_FILES_MAX_SIZE
-                                )),
-                                style::SetForegroundColor(Color::Reset)
-                            )?;
-                            let total_files = dropped_files.len();
-
-                            let truncated_dropped_files = &dropped_files[..10];
-
-                            for (filename, content) in truncated_dropped_files {
-                                let est_tokens = TokenCounter::count_tokens(content);
-                                execute!(
-                                    session.stderr,
-                                    style::Print(format!("{} ", filename)),
-                                    style::SetForegroundColor(Color::DarkGrey),
-                                    style::Print(format!("(~{} tkns)\n", est_tokens)),
-                                    style::SetForegroundColor(Color::Reset),
-                                )?;
-                            }
-
-                            if total_files > 10 {
-                                execute!(
-                                    session.stderr,
-                                    style::Print(format!("({} more files)\n", total_files - 10))
-                                )?;
-                            }
-                        }
+                    if files.iter().any(|file| file.outcome != BudgetOutcome::Included) {
+                        execute!(
+                            session.stderr,
+                            style::SetForegroundColor(Color::DarkYellow),
+                            style::Print(format!(
+                                "The context files exceed their budget of {budget} tokens. Files are included from the smallest to the largest, the first one that doesn't fit is truncated to what is left if that is at least {MIN_TRUNCATED_TOKENS} tokens, and the rest are dropped. Remove files with /context rm, or change the budget with `q settings chat.contextBudgetTokens <tokens>`.\n\n"
+                            )),
+                            style::SetForegroundColor(Color::Reset)
+                        )?;
                     }
-
-                    execute!(session.stderr, style::Print("\n"))?;
                 }
 
                 // Show last cached session.conversation summary if available, otherwise regenerate it
@@ -217,7 +244,7 @@ impl ContextSubcommand {
                         execute!(
                             session.stderr,
                             style::Print("\n"),
-                            style::SetForegroundColor(Color::Cyan),
+                            style::SetForegroundColor(theme().info),
                             style::Print(&border),
                             style::Print("\n"),
                             style::SetAttribute(Attribute::Bold),
@@ -232,29 +259,87 @@ impl ContextSubcommand {
                     }
                 }
             },
-            Self::Add { force, paths } => match context_manager.add_paths(os, paths.clone(), force).await {
-                Ok(_) => {
+            Self::Add {
+                force,
+                recursive,
+                paths,
+            } => match context_manager.add_paths(os, paths, force, recursive).await {
+                Ok(added) => {
+                    let matched = added.iter().filter_map(|(_, matched)| *matched).sum::<usize>();
+                    let message = if added.iter().all(|(_, matched)| matched.is_some()) {
+                        format!(
+                            "\nAdded {} path(s) to context, matching {matched} file(s).\n",
+                            added.len()
+                        )
+                    } else {
+                        format!("\nAdded {} path(s) to context.\n", added.len())
+                    };
                     execute!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Green),
-                        style::Print(format!("\nAdded {} path(s) to context.\n\n", paths.len())),
-                        style::SetForegroundColor(Color::Reset)
+                        style::SetForegroundColor(theme().success),
+                        style::Print(message),
+                        style::SetForegroundColor(theme().warning),
+                    )?;
+                    for (path, _) in added.iter().filter(|(_, matched)| *matched == Some(MAX_FILES_PER_PATH)) {
+                        execute!(
+                            session.stderr,
+                            style::Print(format!(
+                                "'{path}' may match more files, only the first {MAX_FILES_PER_PATH} are included.\n"
+                            )),
+                        )?;
+                    }
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(Color::Reset),
+                        style::Print("\n")
                     )?;
                 },
                 Err(e) => {
                     execute!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Red),
+                        style::SetForegroundColor(theme().error),
                         style::Print(format!("\nError: {}\n\n", e)),
                         style::SetForegroundColor(Color::Reset)
                     )?;
                 },
             },
+            Self::AddResource { resource } => {
+                let tool_manager = &session.conversation.tool_manager;
+                let result = match resource.split_once('/') {
+                    Some((server, uri)) => match read_resource(tool_manager, server, uri).await {
+                        Ok(resource) => {
+                            let message = format!(
+                                "\nAdded {} to context (~{} tkns).\n\n",
+                                resource.name(),
+                                TokenCounter::count_tokens(&resource.content)
+                            );
+                            context_manager.add_resource(resource);
+                            Ok(message)
+                        },
+                        Err(err) => Err(err),
+                    },
+                    None => list_resources(tool_manager, &resource).await,
+                };
+                match result {
+                    Ok(message) => execute!(
+                        session.stderr,
+                        style::SetForegroundColor(theme().success),
+                        style::Print(message),
+                        style::SetForegroundColor(Color::Reset)
+                    )?,
+                    Err(e) => execute!(
+                        session.stderr,
+                        style::SetForegroundColor(theme().error),
+                        style::Print(format!("\nError: {}\n\n", e)),
+                        style::SetForegroundColor(Color::Reset)
+                    )?,
+                }
+            },
             Self::Remove { paths } => match context_manager.remove_paths(paths.clone()) {
                 Ok(_) => {
                     execute!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::Print(format!("\nRemoved {} path(s) from context.\n\n", paths.len(),)),
                         style::SetForegroundColor(Color::Reset)
                     )?;
@@ -262,7 +347,7 @@ impl ContextSubcommand {
                 Err(e) => {
                     execute!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Red),
+                        style::SetForegroundColor(theme().error),
                         style::Print(format!("\nError: {}\n\n", e)),
                         style::SetForegroundColor(Color::Reset)
                     )?;
@@ -272,7 +357,7 @@ impl ContextSubcommand {
                 context_manager.clear();
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print("\nCleared context\n\n"),
                     style::SetForegroundColor(Color::Reset)
                 )?;
@@ -280,11 +365,11 @@ impl ContextSubcommand {
             Self::Hooks => {
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print("The /context hooks command is deprecated. Use "),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print("/hooks"),
-                    style::SetForegroundColor(Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print(" instead.\n\n"),
                     style::SetForegroundColor(Color::Reset)
                 )?;
@@ -300,9 +385,58 @@ impl ContextSubcommand {
         match self {
             ContextSubcommand::Show { .. } => "show",
             ContextSubcommand::Add { .. } => "add",
+            ContextSubcommand::AddResource { .. } => "add-resource",
             ContextSubcommand::Remove { .. } => "remove",
             ContextSubcommand::Clear => "clear",
             ContextSubcommand::Hooks => "hooks",
         }
     }
 }
+
+/// The client of the MCP server named `server`, if it offers resources.
+async fn resource_client(tool_manager: &ToolManager, server: &str) -> eyre::Result<Arc<CustomToolClient>> {
+    let Some(client) = tool_manager.clients.get(server) else {
+        eyre::bail!("No MCP server named '{server}' is running");
+    };
+    if !client.supports_resources().await {
+        eyre::bail!("{server} does not offer resources");
+    }
+    Ok(client.clone())
+}
+
+/// Reads `uri` from `server`. Only text contents are kept, binary ones are skipped.
+async fn read_resource(tool_manager: &ToolManager, server: &str, uri: &str) -> eyre::Result<ContextResource> {
+    let result = resource_client(tool_manager, server).await?.read_resource(uri).await?;
+    let content = result
+        .contents
+        .into_iter()
+        .filter_map(|contents| contents.text)
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if content.is_empty() {
+        eyre::bail!("{server}/{uri} has no text content");
+    }
+    Ok(ContextResource {
+        server: server.to_string(),
+        uri: uri.to_string(),
+        content,
+    })
+}
+
+/// Lists the resources of `server` in the form they are added with.
+async fn list_resources(tool_manager: &ToolManager, server: &str) -> eyre::Result<String> {
+    let resources = resource_client(tool_manager, server).await?.list_resources().await?;
+    if resources.is_empty() {
+        return Ok(format!("\n{server} has no resources.\n\n"));
+    }
+    let mut message = format!("\nResources of {server}:\n");
+    for resource in resources {
+        message.push_str(&format!("    {server}/{} ({})", resource.uri, resource.name));
+        if let Some(description) = resource.description {
+            message.push_str(&format!(" - {description}"));
+        }
+        message.push('\n');
+    }
+    message.push('\n');
+    Ok(message)
+}
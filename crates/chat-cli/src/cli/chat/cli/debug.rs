@@ -0,0 +1,251 @@
+use std::borrow::Borrow;
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Color,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::api_client::model::Tool as FigTool;
+use crate::cli::chat::token_counter::{
+    CharCount,
+    CharCounter,
+    TokenCount,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::util::theme::theme;
+
+/// Developer commands for inspecting the chat session, e.g. when filing a bug report.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum DebugSubcommand {
+    /// Dump the conversation state as json, along with what changed since the start of the
+    /// previous turn
+    State {
+        /// Write the json to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+impl DebugSubcommand {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self {
+            Self::State { output } => {
+                let current = StateSnapshot::capture(session);
+                let diff = match &session.turn_snapshot {
+                    Some(previous) => diff_snapshots(previous, &current),
+                    None => Vec::new(),
+                };
+                let json = serde_json::to_string_pretty(&serde_json::json!({
+                    "state": current,
+                    "diffSincePreviousTurn": diff,
+                }))
+                .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+
+                match output {
+                    Some(path) => {
+                        os.fs.write(&path, &json).await?;
+                        execute!(
+                            session.stderr,
+                            style::SetForegroundColor(theme().success),
+                            style::Print(format!("\nWrote the conversation state to {}\n\n", path.display())),
+                            style::SetForegroundColor(Color::Reset),
+                        )?;
+                    },
+                    None => {
+                        execute!(session.stdout, style::Print(format!("\n{json}\n\n")))?;
+                    },
+                }
+            },
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::State { .. } => "state",
+        }
+    }
+}
+
+/// Token estimates for the parts of the conversation that are sent with each request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenEstimates {
+    /// Context files and hooks, as of the last request
+    pub context: usize,
+    pub user_messages: usize,
+    pub assistant_messages: usize,
+    pub tool_specs: usize,
+}
+
+/// A summary of the conversation state that can be compared across turns. Message contents are
+/// left out so that the snapshot can be shared in bug reports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSnapshot {
+    pub conversation_id: String,
+    pub agent: Option<String>,
+    pub model: Option<String>,
+    pub message_count: usize,
+    pub transcript_len: usize,
+    pub has_summary: bool,
+    pub tokens: TokenEstimates,
+    /// Paths and globs of the context files
+    pub context: Vec<String>,
+    /// Tool names keyed by where the tool comes from, i.e. "native" or the MCP server name
+    pub tools: BTreeMap<String, BTreeSet<String>>,
+    /// Tools that are trusted by the agent or for the session with `/tools trust`
+    pub trusted_tools: BTreeSet<String>,
+    pub trust_all_tools: bool,
+    pub pending_tool_uses: Vec<String>,
+    /// Number of tasks done and in total in the task list
+    pub todo_progress: (usize, usize),
+}
+
+impl StateSnapshot {
+    pub fn capture(session: &ChatSession) -> Self {
+        let conversation = &session.conversation;
+        let (user_chars, assistant_chars) = conversation.history().iter().fold(
+            (CharCount::from(0), CharCount::from(0)),
+            |(user, assistant), (user_msg, assistant_msg)| {
+                (user + user_msg.char_count(), assistant + assistant_msg.char_count())
+            },
+        );
+        let tool_spec_chars = conversation
+            .tools
+            .values()
+            .flatten()
+            .filter_map(|tool| serde_json::to_string(tool).ok())
+            .map(|spec| spec.len())
+            .sum::<usize>();
+        let tokens = |chars: CharCount| TokenCount::from(chars).value();
+
+        let tools = conversation
+            .tools
+            .iter()
+            .map(|(origin, tools)| {
+                let names = tools
+                    .iter()
+                    .map(|tool| {
+                        let FigTool::ToolSpecification(spec) = tool;
+                        spec.name.clone()
+                    })
+                    .collect();
+                (Borrow::<str>::borrow(origin).to_string(), names)
+            })
+            .collect();
+
+        let agent = conversation.agents.get_active();
+        Self {
+            conversation_id: conversation.conversation_id().to_string(),
+            agent: agent.map(|a| a.name.clone()),
+            model: conversation.model.clone(),
+            message_count: conversation.history().len() * 2,
+            transcript_len: conversation.transcript.len(),
+            has_summary: conversation.latest_summary().is_some(),
+            tokens: TokenEstimates {
+                context: tokens(conversation.context_message_length().unwrap_or_default().into()),
+                user_messages: tokens(user_chars),
+                assistant_messages: tokens(assistant_chars),
+                tool_specs: tokens(tool_spec_chars.into()),
+            },
+            context: conversation
+                .context_manager
+                .as_ref()
+                .map(|cm| cm.paths.clone())
+                .unwrap_or_default(),
+            tools,
+            trusted_tools: agent
+                .map(|a| a.allowed_tools.iter().cloned().collect())
+                .unwrap_or_default(),
+            trust_all_tools: conversation.agents.trust_all_tools,
+            pending_tool_uses: session.tool_uses.iter().map(|t| t.name.clone()).collect(),
+            todo_progress: conversation.todo_list.progress(),
+        }
+    }
+}
+
+/// A value that differs between two snapshots, identified by its JSON pointer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub path: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+pub fn diff_snapshots(before: &StateSnapshot, after: &StateSnapshot) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if let (Ok(before), Ok(after)) = (serde_json::to_value(before), serde_json::to_value(after)) {
+        diff_values(String::new(), &before, &after, &mut changes);
+    }
+    changes
+}
+
+fn diff_values(path: String, before: &Value, after: &Value, changes: &mut Vec<Change>) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            let keys = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                let path = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+                diff_values(
+                    path,
+                    before.get(key).unwrap_or(&Value::Null),
+                    after.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        },
+        (before, after) if before != after => changes.push(Change {
+            path,
+            before: before.clone(),
+            after: after.clone(),
+        }),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_values() {
+        let before = json!({ "a": 1, "tools": { "native": ["fs_read"] }, "same": true });
+        let after = json!({ "a": 2, "tools": { "native": ["fs_read"], "git": ["status"] }, "same": true });
+        let mut changes = Vec::new();
+        diff_values(String::new(), &before, &after, &mut changes);
+
+        assert_eq!(changes, vec![
+            Change {
+                path: "/a".to_string(),
+                before: json!(1),
+                after: json!(2),
+            },
+            Change {
+                path: "/tools/git".to_string(),
+                before: Value::Null,
+                after: json!(["status"]),
+            },
+        ]);
+    }
+}
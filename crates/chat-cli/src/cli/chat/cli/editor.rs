@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use clap::Args;
 use crossterm::execute;
 use crossterm::style::{
@@ -7,11 +9,17 @@ use crossterm::style::{
 };
 use uuid::Uuid;
 
+use crate::cli::chat::artifacts::{
+    self,
+    ArtifactKind,
+};
 use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
 };
+use crate::os::Os;
+use crate::util::theme::theme;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
@@ -20,19 +28,29 @@ pub struct EditorArgs {
 }
 
 impl EditorArgs {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
         let initial_text = if self.initial_text.is_empty() {
             None
         } else {
             Some(self.initial_text.join(" "))
         };
 
-        let content = match open_editor(initial_text) {
+        // Create a temporary file with a unique name. It is tracked in case the editor fails and
+        // it is left behind.
+        let temp_file_path = std::env::temp_dir().join(format!("q_prompt_{}.md", Uuid::new_v4()));
+        artifacts::track(
+            os,
+            session.conversation.conversation_id(),
+            ArtifactKind::TempFile,
+            &temp_file_path,
+        );
+
+        let content = match open_editor(initial_text, &temp_file_path) {
             Ok(content) => content,
             Err(err) => {
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Red),
+                    style::SetForegroundColor(theme().error),
                     style::Print(format!("\nError opening editor: {}\n\n", err)),
                     style::SetForegroundColor(Color::Reset)
                 )?;
@@ -47,7 +65,7 @@ impl EditorArgs {
             true => {
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print("\nEmpty content from editor, not submitting.\n\n"),
                     style::SetForegroundColor(Color::Reset)
                 )?;
@@ -59,7 +77,7 @@ impl EditorArgs {
             false => {
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print("\nContent loaded from editor. Submitting prompt...\n\n"),
                     style::SetForegroundColor(Color::Reset)
                 )?;
@@ -68,7 +86,7 @@ impl EditorArgs {
                 execute!(
                     session.stderr,
                     style::SetAttribute(Attribute::Reset),
-                    style::SetForegroundColor(Color::Magenta),
+                    style::SetForegroundColor(theme().tool),
                     style::Print("> "),
                     style::SetAttribute(Attribute::Reset),
                     style::Print(&content),
@@ -83,12 +101,7 @@ impl EditorArgs {
 }
 
 /// Opens the user's preferred editor to compose a prompt
-fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
-    // Create a temporary file with a unique name
-    let temp_dir = std::env::temp_dir();
-    let file_name = format!("q_prompt_{}.md", Uuid::new_v4());
-    let temp_file_path = temp_dir.join(file_name);
-
+fn open_editor(initial_text: Option<String>, temp_file_path: &Path) -> Result<String, ChatError> {
     // Get the editor from environment variable or use a default
     let editor_cmd = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
 
@@ -104,7 +117,7 @@ fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
 
     // Write initial content to the file if provided
     let initial_content = initial_text.unwrap_or_default();
-    std::fs::write(&temp_file_path, &initial_content)
+    std::fs::write(temp_file_path, &initial_content)
         .map_err(|e| ChatError::Custom(format!("Failed to create temporary file: {}", e).into()))?;
 
     // Open the editor with the parsed command and arguments
@@ -115,7 +128,7 @@ fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
     }
     // Add the file path as the last argument
     let status = cmd
-        .arg(&temp_file_path)
+        .arg(temp_file_path)
         .status()
         .map_err(|e| ChatError::Custom(format!("Failed to open editor: {}", e).into()))?;
 
@@ -124,11 +137,11 @@ fn open_editor(initial_text: Option<String>) -> Result<String, ChatError> {
     }
 
     // Read the content back
-    let content = std::fs::read_to_string(&temp_file_path)
+    let content = std::fs::read_to_string(temp_file_path)
         .map_err(|e| ChatError::Custom(format!("Failed to read temporary file: {}", e).into()))?;
 
     // Clean up the temporary file
-    let _ = std::fs::remove_file(&temp_file_path);
+    let _ = std::fs::remove_file(temp_file_path);
 
     Ok(content.trim().to_string())
 }
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use clap::{
+    Args,
+    ValueEnum,
+};
+use crossterm::execute;
+use crossterm::style::{
+    self,
+    Attribute,
+};
+use pulldown_cmark::{
+    Event,
+    Options,
+    Parser,
+};
+
+use crate::api_client::model::ToolResultStatus;
+use crate::cli::ConversationState;
+use crate::cli::chat::message::{
+    AssistantMessage,
+    ToolUseResult,
+    ToolUseResultBlock,
+    UserMessageContent,
+};
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::util::datetime::DateTimeFormat;
+use crate::util::theme::theme;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+}
+
+impl ExportFormat {
+    /// The format for a file, which is HTML for `.html` and `.htm` files and Markdown otherwise.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => Self::Html,
+            _ => Self::Markdown,
+        }
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct ExportArgs {
+    /// Path of the transcript to write
+    pub path: String,
+    /// Format of the transcript, inferred from the extension of the path by default
+    #[arg(long, value_enum)]
+    pub format: Option<ExportFormat>,
+    /// Overwrite the file if it already exists
+    #[arg(short, long)]
+    pub force: bool,
+}
+
+impl ExportArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if os.fs.exists(&self.path) && !self.force {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(theme().error),
+                style::Print(format!(
+                    "\nFile at {} already exists. To overwrite, use -f or --force\n\n",
+                    &self.path
+                )),
+                style::SetAttribute(Attribute::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        let format = self
+            .format
+            .unwrap_or_else(|| ExportFormat::for_path(Path::new(&self.path)));
+        let dates = DateTimeFormat::new(&os.database.settings);
+        let transcript = render(&session.conversation, format, &dates.date_time(dates.now()));
+        if let Err(err) = os.fs.write(&self.path, transcript).await {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(theme().error),
+                style::Print(format!("\nFailed to export to {}: {}\n\n", &self.path, err)),
+                style::SetAttribute(Attribute::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(theme().success),
+            style::Print(format!("\n✔ Exported the transcript to {}\n\n", &self.path)),
+            style::SetAttribute(Attribute::Reset)
+        )?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+/// Renders the transcript of a conversation, including the tools used and their results.
+/// `exported_at` is shown in the user's locale and timezone under the title.
+pub fn render(conversation: &ConversationState, format: ExportFormat, exported_at: &str) -> String {
+    let markdown = render_markdown(conversation, exported_at);
+    match format {
+        ExportFormat::Markdown => markdown,
+        ExportFormat::Html => render_html(conversation.conversation_id(), &markdown),
+    }
+}
+
+fn render_markdown(conversation: &ConversationState, exported_at: &str) -> String {
+    let mut out = format!(
+        "# Conversation {}\n\n_Exported {exported_at}_\n",
+        conversation.conversation_id()
+    );
+    // Tool results only carry the id of the tool use they answer
+    let mut tool_names = HashMap::new();
+
+    for (user, assistant) in conversation.history() {
+        match user.content() {
+            UserMessageContent::Prompt { prompt } => {
+                out.push_str(&format!("\n## User\n\n{}\n", prompt.trim_end()));
+            },
+            UserMessageContent::CancelledToolUses {
+                prompt,
+                tool_use_results,
+            } => {
+                render_tool_results(&mut out, tool_use_results, &tool_names);
+                if let Some(prompt) = prompt {
+                    out.push_str(&format!("\n## User\n\n{}\n", prompt.trim_end()));
+                }
+            },
+            UserMessageContent::ToolUseResults { tool_use_results } => {
+                render_tool_results(&mut out, tool_use_results, &tool_names);
+            },
+        }
+
+        if !assistant.content().trim().is_empty() {
+            out.push_str(&format!("\n## Amazon Q\n\n{}\n", assistant.content().trim_end()));
+        }
+        if let AssistantMessage::ToolUse { tool_uses, .. } = assistant {
+            for tool_use in tool_uses {
+                tool_names.insert(tool_use.id.as_str(), tool_use.name.as_str());
+                let args = serde_json::to_string_pretty(&tool_use.args).unwrap_or_default();
+                out.push_str(&format!(
+                    "\n### Tool use: `{}`\n\n{}",
+                    tool_use.name,
+                    fenced(&args, "json")
+                ));
+            }
+        }
+    }
+
+    out
+}
+
+fn render_tool_results(out: &mut String, results: &[ToolUseResult], tool_names: &HashMap<&str, &str>) {
+    for result in results {
+        let name = tool_names.get(result.tool_use_id.as_str()).unwrap_or(&"tool");
+        let status = match result.status {
+            ToolResultStatus::Success => "",
+            ToolResultStatus::Error => " (failed)",
+        };
+        out.push_str(&format!("\n### Result of `{name}`{status}\n\n"));
+        for block in &result.content {
+            match block {
+                ToolUseResultBlock::Text(text) => out.push_str(&fenced(text, "")),
+                ToolUseResultBlock::Json(value) => {
+                    out.push_str(&fenced(
+                        &serde_json::to_string_pretty(value).unwrap_or_default(),
+                        "json",
+                    ));
+                },
+            }
+        }
+    }
+}
+
+/// Wraps `content` in a code fence longer than any run of backticks inside it.
+fn fenced(content: &str, language: &str) -> String {
+    let longest_run = content.split(|c| c != '`').map(str::len).max().unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{fence}{language}\n{}\n{fence}\n", content.trim_end())
+}
+
+fn render_html(conversation_id: &str, markdown: &str) -> String {
+    // The transcript is untrusted, so raw HTML in it is shown as text rather than rendered
+    let parser =
+        Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH).map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        });
+    let mut body = String::new();
+    pulldown_cmark::html::push_html(&mut body, parser);
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Conversation {conversation_id}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; line-height: 1.5; max-width: 60rem; margin: 2rem auto; padding: 0 1rem; }}
+h2 {{ border-bottom: 1px solid #ddd; padding-bottom: 0.25rem; }}
+h3 {{ font-size: 1rem; color: #555; }}
+pre {{ background: #f6f8fa; padding: 0.75rem; overflow-x: auto; }}
+code {{ font-family: ui-monospace, monospace; }}
+</style>
+</head>
+<body>
+{body}</body>
+</html>
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::agent::Agents;
+    use crate::cli::chat::message::AssistantToolUse;
+    use crate::cli::chat::tool_manager::ToolManager;
+
+    #[tokio::test]
+    async fn test_render_markdown() {
+        let mut os = Os::new().await.unwrap();
+        let mut conversation =
+            ConversationState::new("conv", Agents::default(), HashMap::new(), ToolManager::default(), None).await;
+        conversation
+            .set_next_user_message("what is in the readme?".to_string())
+            .await;
+        conversation.push_assistant_message(
+            &mut os,
+            AssistantMessage::new_tool_use(None, "Let me read it.".to_string(), vec![AssistantToolUse {
+                id: "t1".to_string(),
+                name: "fs_read".to_string(),
+                args: serde_json::json!({ "path": "README.md" }),
+                ..Default::default()
+            }]),
+        );
+        conversation.add_tool_results(vec![ToolUseResult {
+            tool_use_id: "t1".to_string(),
+            content: vec![ToolUseResultBlock::Text("# Readme".to_string())],
+            status: ToolResultStatus::Success,
+        }]);
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "A title.".to_string()));
+
+        assert_eq!(
+            render_markdown(&conversation, "03/07/2026 6:05 PM"),
+            "# Conversation conv\n\n_Exported 03/07/2026 6:05 PM_\n\n## User\n\nwhat is in the readme?\n\n## Amazon Q\n\nLet me read it.\n\n### Tool use: `fs_read`\n\n```json\n{\n  \"path\": \"README.md\"\n}\n```\n\n### Result of `fs_read`\n\n```\n# Readme\n```\n\n## Amazon Q\n\nA title.\n"
+        );
+    }
+
+    #[test]
+    fn test_fenced() {
+        assert_eq!(fenced("let x = 1;\n", "rust"), "```rust\nlet x = 1;\n```\n");
+        assert_eq!(fenced("a ```b``` c", ""), "````\na ```b``` c\n````\n");
+    }
+
+    #[test]
+    fn test_for_path() {
+        assert_eq!(ExportFormat::for_path(Path::new("chat.html")), ExportFormat::Html);
+        assert_eq!(ExportFormat::for_path(Path::new("chat.HTM")), ExportFormat::Html);
+        assert_eq!(ExportFormat::for_path(Path::new("chat.md")), ExportFormat::Markdown);
+        assert_eq!(ExportFormat::for_path(Path::new("chat")), ExportFormat::Markdown);
+    }
+
+    #[test]
+    fn test_render_html_escapes_raw_html() {
+        let html = render_html(
+            "abc",
+            "## User\n\n<script>alert(1)</script>\n\nsee `code` and <b>bold</b>\n",
+        );
+        assert!(html.contains("<title>Conversation abc</title>"));
+        assert!(html.contains("<h2>User</h2>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("<code>code</code>"));
+        assert!(html.contains("&lt;b&gt;bold&lt;/b&gt;"));
+    }
+}
@@ -10,7 +10,6 @@ use bstr::ByteSlice;
 use clap::Args;
 use crossterm::style::{
     self,
-    Color,
     Stylize,
 };
 use crossterm::{
@@ -31,6 +30,7 @@ use spinners::{
     Spinner,
     Spinners,
 };
+use tokio::io::AsyncWriteExt;
 
 use crate::cli::agent::hook::{
     Hook,
@@ -42,6 +42,7 @@ use crate::cli::chat::{
     ChatSession,
     ChatState,
 };
+use crate::util::theme::theme;
 
 #[derive(Debug, Clone)]
 pub struct CachedHook {
@@ -84,7 +85,7 @@ impl HookExecutor {
                 cached.push((hook.clone(), cache.clone()));
                 continue;
             }
-            futures.push(self.run_hook(hook, prompt));
+            futures.push(self.run_hook(hook, prompt, None));
         }
 
         let mut complete = 0;
@@ -120,19 +121,7 @@ impl HookExecutor {
             }
 
             if let Err(err) = &result {
-                queue!(
-                    output,
-                    style::SetForegroundColor(style::Color::Red),
-                    style::Print("✗ "),
-                    style::SetForegroundColor(style::Color::Blue),
-                    style::Print(&hook.1.command),
-                    style::ResetColor,
-                    style::Print(" failed after "),
-                    style::SetForegroundColor(style::Color::Yellow),
-                    style::Print(format!("{:.2} s", duration.as_secs_f32())),
-                    style::ResetColor,
-                    style::Print(format!(": {}\n", err)),
-                )?;
+                queue_failure(output, &hook.1, duration, err)?;
             }
 
             // Process results regardless of output enabled
@@ -152,9 +141,9 @@ impl HookExecutor {
 
                 queue!(
                     output,
-                    style::SetForegroundColor(Color::Blue),
+                    style::SetForegroundColor(theme().label),
                     style::Print(format!("{symbol} {} in ", spinner_text(complete, total))),
-                    style::SetForegroundColor(style::Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print(format!("{:.2} s\n", start_time.elapsed().as_secs_f32())),
                     style::ResetColor,
                 )?;
@@ -170,7 +159,7 @@ impl HookExecutor {
                 output: output.clone(),
                 expiry: match trigger {
                     HookTrigger::AgentSpawn => None,
-                    HookTrigger::UserPromptSubmit => Some(Instant::now() + Duration::from_secs(hook.cache_ttl_seconds)),
+                    _ => Some(Instant::now() + Duration::from_secs(hook.cache_ttl_seconds)),
                 },
             });
         }
@@ -178,10 +167,44 @@ impl HookExecutor {
         Ok(results)
     }
 
+    /// Runs the `hooks` of an event such as a tool use, with `input` written to their stdin as
+    /// JSON. Their output is never cached. Hooks that fail are reported to `output` without
+    /// failing the event, so that a broken audit hook doesn't get in the way of the session.
+    ///
+    /// Returns the output of the hooks that ask for it to be injected into the context, in the
+    /// order of `hooks`.
+    pub async fn run_event_hooks(
+        &self,
+        trigger: HookTrigger,
+        hooks: &[Hook],
+        input: &serde_json::Value,
+        output: &mut impl Write,
+    ) -> Result<Vec<String>, ChatError> {
+        let input = input.to_string();
+        let results = futures::future::join_all(
+            hooks
+                .iter()
+                .map(|hook| self.run_hook((trigger, hook.clone()), None, Some(input.clone()))),
+        )
+        .await;
+
+        let mut injected = vec![];
+        for ((_, hook), result, duration) in results {
+            match result {
+                Ok(stdout) if hook.inject_output => injected.push(stdout),
+                Ok(_) => (),
+                Err(err) => queue_failure(output, &hook, duration, &err)?,
+            }
+        }
+        output.flush()?;
+        Ok(injected)
+    }
+
     async fn run_hook(
         &self,
         hook: (HookTrigger, Hook),
         prompt: Option<&str>,
+        input: Option<String>,
     ) -> ((HookTrigger, Hook), Result<String>, Duration) {
         let start_time = Instant::now();
 
@@ -216,7 +239,18 @@ impl HookExecutor {
             cmd.env("USER_PROMPT", sanitized_prompt);
         }
 
-        let command_future = cmd.output();
+        let command_future = async {
+            let mut child = cmd.spawn()?;
+            // Stdin is closed once the input is written, or right away without input. It is written
+            // while the output is read, so that a hook writing a lot before reading can't block.
+            if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+                tokio::spawn(async move {
+                    // A hook is free to exit without reading its input
+                    let _ = stdin.write_all(input.as_bytes()).await;
+                });
+            }
+            child.wait_with_output().await
+        };
 
         // Run with timeout
         let result = match tokio::time::timeout(timeout, command_future).await {
@@ -260,6 +294,22 @@ impl HookExecutor {
     }
 }
 
+fn queue_failure(output: &mut impl Write, hook: &Hook, duration: Duration, err: &eyre::Report) -> std::io::Result<()> {
+    queue!(
+        output,
+        style::SetForegroundColor(theme().error),
+        style::Print("✗ "),
+        style::SetForegroundColor(theme().label),
+        style::Print(&hook.command),
+        style::ResetColor,
+        style::Print(" failed after "),
+        style::SetForegroundColor(theme().warning),
+        style::Print(format!("{:.2} s", duration.as_secs_f32())),
+        style::ResetColor,
+        style::Print(format!(": {}\n", err)),
+    )
+}
+
 /// Sanitizes a string value to be used as an environment variable
 fn sanitize_user_prompt(input: &str) -> String {
     // Limit the size of input to first 4096 characters
@@ -307,3 +357,34 @@ impl HooksArgs {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_event_hooks() {
+        let hooks = serde_json::from_value::<HashMap<HookTrigger, Vec<Hook>>>(json!({
+            "preToolUse": [
+                { "command": "cat", "inject_output": true },
+                { "command": "cat > /dev/null; echo logged" },
+                { "command": "exit 1", "inject_output": true },
+            ],
+        }))
+        .unwrap();
+        let hooks = &hooks[&HookTrigger::PreToolUse];
+        assert!(!hooks[1].inject_output);
+
+        let input = json!({ "hookEvent": "preToolUse", "toolName": "fs_read" });
+        let mut output = vec![];
+        let injected = HookExecutor::new()
+            .run_event_hooks(HookTrigger::PreToolUse, hooks, &input, &mut output)
+            .await
+            .unwrap();
+        assert_eq!(injected, vec![input.to_string()]);
+        assert!(String::from_utf8_lossy(&output).contains("exit 1"));
+    }
+}
@@ -0,0 +1,100 @@
+use clap::Args;
+use crossterm::style::{
+    self,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use super::tools::Toggle;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::os::Os;
+use crate::util::theme::theme;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "In incognito mode the conversation isn't saved for --resume, turns cut off by an exit aren't
+journaled, and neither usage statistics nor telemetry are recorded, apart from errors. The turns
+made in incognito mode are never saved, even once it is turned off."
+)]
+pub struct IncognitoArgs {
+    /// Turn incognito mode on or off, toggling it if omitted
+    state: Option<Toggle>,
+}
+
+impl IncognitoArgs {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let incognito = match self.state {
+            Some(Toggle::On) => true,
+            Some(Toggle::Off) => false,
+            None => !session.conversation.incognito,
+        };
+        session.set_incognito(os, incognito);
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(theme().success),
+            style::Print(if incognito {
+                "\nIncognito mode is on. Nothing from here on is saved, and telemetry is limited to errors. Use /ephemeral to also leave out earlier turns.\n\n"
+            } else {
+                "\nIncognito mode is off. The turns made while it was on won't be saved.\n\n"
+            }),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct EphemeralArgs {
+    /// How many of the latest turns to leave out of the saved conversation
+    #[arg(default_value_t = 1)]
+    turns: usize,
+}
+
+impl EphemeralArgs {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let marked = session.conversation.mark_ephemeral(self.turns);
+        session.conversation.save(os);
+        if marked == 0 {
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(theme().secondary),
+                style::Print("\nThere are no turns to leave out yet.\n"),
+            )?;
+        } else {
+            let turns = if marked == 1 { "turn" } else { "turns" };
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(theme().success),
+                style::Print(format!(
+                    "\nThe last {marked} {turns} won't be saved. They stay in the context of this session.\n"
+                )),
+            )?;
+            if session.conversation.incognito {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(theme().secondary),
+                    style::Print("The saved conversation is updated once incognito mode is turned off.\n"),
+                )?;
+            }
+        }
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n")
+        )?;
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
@@ -21,7 +21,12 @@ use crate::cli::chat::{
 };
 use crate::database::settings::Setting;
 use crate::os::Os;
+use crate::util::datetime::{
+    DateTimeFormat,
+    format_duration,
+};
 use crate::util::knowledge_store::KnowledgeStore;
+use crate::util::theme::theme;
 
 /// Knowledge base management commands
 #[derive(Clone, Debug, PartialEq, Eq, Subcommand)]
@@ -78,7 +83,7 @@ impl KnowledgeSubcommand {
     fn write_feature_disabled_message(session: &mut ChatSession) -> Result<(), std::io::Error> {
         queue!(
             session.stderr,
-            style::SetForegroundColor(Color::Red),
+            style::SetForegroundColor(theme().error),
             style::Print("\nKnowledge tool is disabled. Enable it with: q settings chat.enableKnowledge true\n\n"),
             style::SetForegroundColor(Color::Reset)
         )
@@ -93,7 +98,7 @@ impl KnowledgeSubcommand {
     async fn execute_operation(&self, os: &Os, session: &mut ChatSession) -> OperationResult {
         match self {
             KnowledgeSubcommand::Show => {
-                match Self::handle_show(session).await {
+                match Self::handle_show(os, session).await {
                     Ok(_) => OperationResult::Info("".to_string()), // Empty Info, formatting already done
                     Err(e) => OperationResult::Error(format!("Failed to show contexts: {}", e)),
                 }
@@ -107,7 +112,7 @@ impl KnowledgeSubcommand {
         }
     }
 
-    async fn handle_show(session: &mut ChatSession) -> Result<(), std::io::Error> {
+    async fn handle_show(os: &Os, session: &mut ChatSession) -> Result<(), std::io::Error> {
         let async_knowledge_store = KnowledgeStore::get_async_instance().await;
         let store = async_knowledge_store.lock().await;
 
@@ -116,17 +121,21 @@ impl KnowledgeSubcommand {
             // Write error to output using queue system
             let _ = queue!(
                 session.stderr,
-                style::SetForegroundColor(Color::Red),
+                style::SetForegroundColor(theme().error),
                 style::Print(&format!("Error getting contexts: {}\n", e)),
                 style::ResetColor
             );
             Vec::new()
         });
 
-        Self::format_contexts(session, &contexts)
+        Self::format_contexts(session, &contexts, &DateTimeFormat::new(&os.database.settings))
     }
 
-    fn format_contexts(session: &mut ChatSession, contexts: &[KnowledgeContext]) -> Result<(), std::io::Error> {
+    fn format_contexts(
+        session: &mut ChatSession,
+        contexts: &[KnowledgeContext],
+        dates: &DateTimeFormat,
+    ) -> Result<(), std::io::Error> {
         if contexts.is_empty() {
             queue!(
                 session.stderr,
@@ -142,7 +151,7 @@ impl KnowledgeSubcommand {
             )?;
 
             for context in contexts {
-                Self::format_single_context(session, &context)?;
+                Self::format_single_context(session, &context, dates)?;
                 queue!(session.stderr, style::Print(format!("{}\n", "━".repeat(80))))?;
             }
             // Add final newline to match original formatting exactly
@@ -151,13 +160,17 @@ impl KnowledgeSubcommand {
         Ok(())
     }
 
-    fn format_single_context(session: &mut ChatSession, context: &&KnowledgeContext) -> Result<(), std::io::Error> {
+    fn format_single_context(
+        session: &mut ChatSession,
+        context: &&KnowledgeContext,
+        dates: &DateTimeFormat,
+    ) -> Result<(), std::io::Error> {
         queue!(
             session.stderr,
             style::SetAttribute(style::Attribute::Bold),
-            style::SetForegroundColor(Color::Cyan),
+            style::SetForegroundColor(theme().info),
             style::Print(format!("📂 {}: ", context.id)),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print(&context.name),
             style::SetAttribute(style::Attribute::Reset),
             style::Print("\n")
@@ -168,11 +181,11 @@ impl KnowledgeSubcommand {
             style::Print(format!("   Description: {}\n", context.description)),
             style::Print(format!(
                 "   Created: {}\n",
-                context.created_at.format("%Y-%m-%d %H:%M:%S")
+                dates.timestamp(context.created_at.timestamp())
             )),
             style::Print(format!(
                 "   Updated: {}\n",
-                context.updated_at.format("%Y-%m-%d %H:%M:%S")
+                dates.timestamp(context.updated_at.timestamp())
             ))
         )?;
 
@@ -183,7 +196,7 @@ impl KnowledgeSubcommand {
         queue!(
             session.stderr,
             style::Print("   Items: "),
-            style::SetForegroundColor(Color::Yellow),
+            style::SetForegroundColor(theme().warning),
             style::Print(format!("{}", context.item_count)),
             style::SetForegroundColor(Color::Reset),
             style::Print(" | Persistent: ")
@@ -192,7 +205,7 @@ impl KnowledgeSubcommand {
         if context.persistent {
             queue!(
                 session.stderr,
-                style::SetForegroundColor(Color::Green),
+                style::SetForegroundColor(theme().success),
                 style::Print("Yes"),
                 style::SetForegroundColor(Color::Reset),
                 style::Print("\n")
@@ -200,7 +213,7 @@ impl KnowledgeSubcommand {
         } else {
             queue!(
                 session.stderr,
-                style::SetForegroundColor(Color::Yellow),
+                style::SetForegroundColor(theme().warning),
                 style::Print("No"),
                 style::SetForegroundColor(Color::Reset),
                 style::Print("\n")
@@ -375,10 +388,10 @@ impl KnowledgeSubcommand {
                 status_icon, op.short_id, operation_desc, status_info
             )
         } else {
-            let mut time_info = format!("Elapsed: {}s", elapsed.as_secs());
+            let mut time_info = format!("Elapsed: {}", format_duration(elapsed));
 
             if let Some(eta) = op.eta {
-                time_info.push_str(&format!(" | ETA: {}s", eta.as_secs()));
+                time_info.push_str(&format!(" | ETA: {}", format_duration(eta)));
             }
 
             format!(
@@ -445,7 +458,7 @@ impl KnowledgeSubcommand {
             OperationResult::Success(msg) => {
                 queue!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(format!("\n{}\n\n", msg)),
                     style::SetForegroundColor(Color::Reset)
                 )
@@ -463,7 +476,7 @@ impl KnowledgeSubcommand {
             OperationResult::Warning(msg) => {
                 queue!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print(format!("\n{}\n\n", msg)),
                     style::SetForegroundColor(Color::Reset)
                 )
@@ -471,7 +484,7 @@ impl KnowledgeSubcommand {
             OperationResult::Error(msg) => {
                 queue!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Red),
+                    style::SetForegroundColor(theme().error),
                     style::Print(format!("\nError: {}\n\n", msg)),
                     style::SetForegroundColor(Color::Reset)
                 )
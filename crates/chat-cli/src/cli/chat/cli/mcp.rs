@@ -1,7 +1,12 @@
 use std::io::Write;
 
-use clap::Args;
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::Color;
 use crossterm::{
+    execute,
     queue,
     style,
 };
@@ -12,13 +17,86 @@ use crate::cli::chat::{
     ChatSession,
     ChatState,
 };
+use crate::os::Os;
+use crate::util::theme::theme;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
-pub struct McpArgs;
+pub struct McpArgs {
+    #[command(subcommand)]
+    subcommand: Option<McpSubcommand>,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum McpSubcommand {
+    /// Show the recent stderr output of a server
+    Logs {
+        /// Name of the server
+        server: String,
+        /// Number of lines to show
+        #[arg(long, short = 'n', default_value_t = 50)]
+        lines: usize,
+    },
+    /// Read the MCP server config again and restart the servers
+    Reload,
+}
 
 impl McpArgs {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        if let Some(McpSubcommand::Reload) = self.subcommand {
+            session.reload_mcp_servers(os).await?;
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(theme().success),
+                style::Print("Reloaded MCP servers. Run /mcp to see how they loaded.\n\n"),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        if let Some(McpSubcommand::Logs { server, lines }) = self.subcommand {
+            let terminal_width = session.terminal_width();
+            let Some(client) = session.conversation.tool_manager.clients.get(&server) else {
+                return Err(ChatError::Custom(format!("No MCP server named {server}").into()));
+            };
+            let stderr_log = client.stderr_log();
+            let recent = stderr_log.recent(lines);
+            queue!(
+                session.stderr,
+                style::Print(format!("\n{server}\n")),
+                style::Print(format!("{}\n", "▔".repeat(terminal_width))),
+            )?;
+            if recent.is_empty() {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(theme().secondary),
+                    style::Print("The server has not written anything to stderr.\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            for line in recent {
+                queue!(session.stderr, style::Print(format!("{line}\n")))?;
+            }
+            if stderr_log.is_closed() {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(theme().warning),
+                    style::Print("The server process has exited.\n"),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            queue!(session.stderr, style::Print("\n"))?;
+            session.stderr.flush()?;
+
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
         let terminal_width = session.terminal_width();
         let still_loading = session
             .conversation
@@ -49,6 +127,28 @@ impl McpArgs {
                 style::Print(msg),
                 style::Print("\n")
             )?;
+            let client = session.conversation.tool_manager.clients.get(server_name);
+            let attempts = client.map_or(0, |client| client.init_attempts());
+            if attempts > 1 {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(theme().secondary),
+                    style::Print(format!("Initialization took {attempts} attempts.\n")),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            }
+            let has_exited = client.is_some_and(|client| client.stderr_log().is_closed());
+            if has_exited {
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(theme().warning),
+                    style::Print(format!(
+                        "The server process has exited. Run /mcp logs {server_name} for its recent stderr output.\n"
+                    )),
+                    style::SetForegroundColor(Color::Reset),
+                    style::Print("\n")
+                )?;
+            }
         }
 
         if !still_loading.is_empty() {
@@ -67,4 +167,11 @@ impl McpArgs {
             skip_printing_tools: true,
         })
     }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|s| match s {
+            McpSubcommand::Logs { .. } => "logs",
+            McpSubcommand::Reload => "reload",
+        })
+    }
 }
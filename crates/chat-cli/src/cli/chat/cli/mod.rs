@@ -1,30 +1,53 @@
+pub mod auto;
+pub mod checkpoint;
 pub mod clear;
 pub mod compact;
 pub mod context;
+pub mod debug;
 pub mod editor;
+pub mod export;
 pub mod hooks;
+pub mod incognito;
 pub mod knowledge;
 pub mod mcp;
 pub mod model;
 pub mod persist;
+pub mod plugins;
 pub mod profile;
 pub mod prompts;
+pub mod roots;
 pub mod subscribe;
+pub mod todo;
 pub mod tools;
 pub mod usage;
 
+use auto::AutoArgs;
+use checkpoint::{
+    CheckpointSubcommand,
+    RevertArgs,
+    UndoArgs,
+};
 use clap::Parser;
 use clear::ClearArgs;
 use compact::CompactArgs;
 use context::ContextSubcommand;
+use debug::DebugSubcommand;
 use editor::EditorArgs;
+use export::ExportArgs;
 use hooks::HooksArgs;
+use incognito::{
+    EphemeralArgs,
+    IncognitoArgs,
+};
 use knowledge::KnowledgeSubcommand;
 use mcp::McpArgs;
 use model::ModelArgs;
 use persist::PersistSubcommand;
+use plugins::PluginsArgs;
 use profile::AgentSubcommand;
 use prompts::PromptsArgs;
+use roots::RootsArgs;
+use todo::TodoArgs;
 use tools::ToolsArgs;
 
 use crate::cli::chat::cli::subscribe::SubscribeArgs;
@@ -35,7 +58,10 @@ use crate::cli::chat::{
     ChatState,
     EXTRA_HELP,
 };
-use crate::cli::issue;
+use crate::cli::{
+    OutputFormat,
+    issue,
+};
 use crate::os::Os;
 
 /// q (Amazon Q Chat)
@@ -62,6 +88,9 @@ pub enum SlashCommand {
     PromptEditor(EditorArgs),
     /// Summarize the conversation to free up context space
     Compact(CompactArgs),
+    /// Export a transcript of the conversation, with the tools used and their results, to
+    /// Markdown or HTML
+    Export(ExportArgs),
     /// View and manage tools and permissions
     Tools(ToolsArgs),
     /// Create a new Github issue or make a feature request
@@ -70,14 +99,36 @@ pub enum SlashCommand {
     Prompts(PromptsArgs),
     /// View and manage context hooks
     Hooks(HooksArgs),
-    /// Show current session's context window usage
+    /// Show the context window usage and the tokens used by the session
     Usage(UsageArgs),
-    /// See mcp server loaded
+    /// See mcp server loaded and their recent output
     Mcp(McpArgs),
-    /// Select a model for the current conversation session
+    /// List, select or switch the model of the conversation
     Model(ModelArgs),
     /// Upgrade to a Q Developer Pro subscription for increased query limits
     Subscribe(SubscribeArgs),
+    /// List the slash commands provided by executables in ~/.aws/amazonq/commands
+    Plugins(PluginsArgs),
+    /// Work toward a goal autonomously until it is achieved or a time limit is reached
+    Auto(AutoArgs),
+    /// View and edit the task list Amazon Q keeps for multi-step work
+    Todo(TodoArgs),
+    /// Undo the latest change a tool made to a file
+    Undo(UndoArgs),
+    /// List and restore the changes tools made to files
+    #[command(subcommand)]
+    Checkpoint(CheckpointSubcommand),
+    /// Put the files tools changed in this session back the way they were before it
+    Revert(RevertArgs),
+    /// Manage the roots of a multi-root workspace
+    Roots(RootsArgs),
+    /// Stop saving the conversation and recording usage until turned off
+    Incognito(IncognitoArgs),
+    /// Leave the latest turns out of the saved conversation
+    Ephemeral(EphemeralArgs),
+    /// Developer commands for debugging the chat session
+    #[command(subcommand, hide = true)]
+    Debug(DebugSubcommand),
     #[command(flatten)]
     Persist(PersistSubcommand),
     // #[command(flatten)]
@@ -92,11 +143,12 @@ impl SlashCommand {
             Self::Agent(subcommand) => subcommand.execute(os, session).await,
             Self::Context(args) => args.execute(os, session).await,
             Self::Knowledge(subcommand) => subcommand.execute(os, session).await,
-            Self::PromptEditor(args) => args.execute(session).await,
+            Self::PromptEditor(args) => args.execute(os, session).await,
             Self::Compact(args) => args.execute(os, session).await,
+            Self::Export(args) => args.execute(os, session).await,
             Self::Tools(args) => args.execute(session).await,
             Self::Issue(args) => {
-                if let Err(err) = args.execute(os).await {
+                if let Err(err) = args.execute(os, OutputFormat::Plain).await {
                     return Err(ChatError::Custom(err.to_string().into()));
                 }
 
@@ -107,9 +159,19 @@ impl SlashCommand {
             Self::Prompts(args) => args.execute(session).await,
             Self::Hooks(args) => args.execute(session).await,
             Self::Usage(args) => args.execute(os, session).await,
-            Self::Mcp(args) => args.execute(session).await,
-            Self::Model(args) => args.execute(session).await,
+            Self::Mcp(args) => args.execute(os, session).await,
+            Self::Model(args) => args.execute(os, session).await,
             Self::Subscribe(args) => args.execute(os, session).await,
+            Self::Plugins(args) => args.execute(os, session).await,
+            Self::Auto(args) => args.execute(session).await,
+            Self::Todo(args) => args.execute(session).await,
+            Self::Undo(args) => args.execute(os, session).await,
+            Self::Checkpoint(subcommand) => subcommand.execute(os, session).await,
+            Self::Revert(args) => args.execute(os, session).await,
+            Self::Roots(args) => args.execute(os, session).await,
+            Self::Incognito(args) => args.execute(os, session).await,
+            Self::Ephemeral(args) => args.execute(os, session).await,
+            Self::Debug(subcommand) => subcommand.execute(os, session).await,
             Self::Persist(subcommand) => subcommand.execute(os, session).await,
             // Self::Root(subcommand) => {
             //     if let Err(err) = subcommand.execute(os, database, telemetry).await {
@@ -132,6 +194,7 @@ impl SlashCommand {
             Self::Knowledge(_) => "knowledge",
             Self::PromptEditor(_) => "editor",
             Self::Compact(_) => "compact",
+            Self::Export(_) => "export",
             Self::Tools(_) => "tools",
             Self::Issue(_) => "issue",
             Self::Prompts(_) => "prompts",
@@ -140,9 +203,20 @@ impl SlashCommand {
             Self::Mcp(_) => "mcp",
             Self::Model(_) => "model",
             Self::Subscribe(_) => "subscribe",
+            Self::Plugins(_) => "plugins",
+            Self::Auto(_) => "auto",
+            Self::Todo(_) => "todo",
+            Self::Undo(_) => "undo",
+            Self::Checkpoint(_) => "checkpoint",
+            Self::Revert(_) => "revert",
+            Self::Roots(_) => "roots",
+            Self::Incognito(_) => "incognito",
+            Self::Ephemeral(_) => "ephemeral",
+            Self::Debug(_) => "debug",
             Self::Persist(sub) => match sub {
                 PersistSubcommand::Save { .. } => "save",
                 PersistSubcommand::Load { .. } => "load",
+                PersistSubcommand::Resume { .. } => "resume",
             },
         }
     }
@@ -154,6 +228,12 @@ impl SlashCommand {
             SlashCommand::Knowledge(sub) => Some(sub.name()),
             SlashCommand::Tools(arg) => arg.subcommand_name(),
             SlashCommand::Prompts(arg) => arg.subcommand_name(),
+            SlashCommand::Todo(arg) => arg.subcommand_name(),
+            SlashCommand::Checkpoint(sub) => Some(sub.name()),
+            SlashCommand::Roots(arg) => arg.subcommand_name(),
+            SlashCommand::Mcp(arg) => arg.subcommand_name(),
+            SlashCommand::Model(arg) => arg.subcommand_name(),
+            SlashCommand::Debug(sub) => Some(sub.name()),
             _ => None,
         }
     }
@@ -1,4 +1,7 @@
-use clap::Args;
+use clap::{
+    Args,
+    Subcommand,
+};
 use crossterm::style::{
     self,
     Color,
@@ -8,96 +11,179 @@ use crossterm::{
     queue,
 };
 use dialoguer::Select;
+use tracing::warn;
 
 use crate::auth::builder_id::{
     BuilderIdToken,
     TokenType,
 };
+use crate::cli::chat::model_registry::{
+    ModelInfo,
+    models,
+};
 use crate::cli::chat::{
     ChatError,
     ChatSession,
     ChatState,
 };
+use crate::database::settings::Setting;
 use crate::os::Os;
+use crate::util::theme::theme;
 
-pub struct ModelOption {
-    pub name: &'static str,
-    pub model_id: &'static str,
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "Without a subcommand, /model asks which model to use. The model selected with /model or /model
+set is used for the rest of the conversation and saved as chat.defaultModel for the sessions that
+follow. q chat --model only selects a model for one session."
+)]
+pub struct ModelArgs {
+    #[command(subcommand)]
+    subcommand: Option<ModelSubcommand>,
 }
 
-pub const MODEL_OPTIONS: [ModelOption; 2] = [
-    ModelOption {
-        name: "claude-4-sonnet",
-        model_id: "CLAUDE_SONNET_4_20250514_V1_0",
-    },
-    ModelOption {
-        name: "claude-3.7-sonnet",
-        model_id: "CLAUDE_3_7_SONNET_20250219_V1_0",
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum ModelSubcommand {
+    /// List the models that can be used
+    List,
+    /// Switch to a model, by name or id
+    Set {
+        /// The name or id of the model
+        model: String,
     },
-];
+}
 
-#[deny(missing_docs)]
-#[derive(Debug, PartialEq, Args)]
-pub struct ModelArgs;
+impl ModelSubcommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::List => "list",
+            Self::Set { .. } => "set",
+        }
+    }
+}
 
 impl ModelArgs {
-    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
-        queue!(session.stderr, style::Print("\n"))?;
-        let active_model_id = session.conversation.model.as_deref();
-        let labels: Vec<String> = MODEL_OPTIONS
-            .iter()
-            .map(|opt| {
-                if (opt.model_id.is_empty() && active_model_id.is_none()) || Some(opt.model_id) == active_model_id {
-                    format!("{} (active)", opt.name)
-                } else {
-                    opt.name.to_owned()
-                }
-            })
-            .collect();
-
-        let selection: Option<_> = match Select::with_theme(&crate::util::dialoguer_theme())
-            .with_prompt("Select a model for this chat session")
-            .items(&labels)
-            .default(0)
-            .interact_on_opt(&dialoguer::console::Term::stdout())
-        {
-            Ok(sel) => {
-                let _ = crossterm::execute!(
-                    std::io::stdout(),
-                    crossterm::style::SetForegroundColor(crossterm::style::Color::Magenta)
-                );
-                sel
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(ModelSubcommand::name)
+    }
+
+    pub async fn execute(self, os: &mut Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        match self.subcommand {
+            None => select(os, session).await?,
+            Some(ModelSubcommand::List) => list(session)?,
+            Some(ModelSubcommand::Set { model }) => {
+                let Some(selected) = models().find(&model) else {
+                    let names = models()
+                        .models
+                        .iter()
+                        .map(|model| model.name.as_str())
+                        .collect::<Vec<_>>();
+                    return Err(ChatError::Custom(
+                        format!("Model '{model}' does not exist. Available models: {}", names.join(", ")).into(),
+                    ));
+                };
+                switch(os, session, selected).await?;
             },
-            // Ctrl‑C -> Err(Interrupted)
-            Err(dialoguer::Error::IO(ref e)) if e.kind() == std::io::ErrorKind::Interrupted => None,
-            Err(e) => return Err(ChatError::Custom(format!("Failed to choose model: {e}").into())),
-        };
-
-        queue!(session.stderr, style::ResetColor)?;
-
-        if let Some(index) = selection {
-            let selected = &MODEL_OPTIONS[index];
-            let model_id_str = selected.model_id.to_string();
-            session.conversation.model = Some(model_id_str);
-
-            queue!(
-                session.stderr,
-                style::Print("\n"),
-                style::Print(format!(" Using {}\n\n", selected.name)),
-                style::ResetColor,
-                style::SetForegroundColor(Color::Reset),
-                style::SetBackgroundColor(Color::Reset),
-            )?;
         }
 
-        execute!(session.stderr, style::ResetColor)?;
-
         Ok(ChatState::PromptUser {
             skip_printing_tools: false,
         })
     }
 }
 
+async fn select(os: &mut Os, session: &mut ChatSession) -> Result<(), ChatError> {
+    queue!(session.stderr, style::Print("\n"))?;
+    let active_model_id = session.conversation.model.as_deref();
+    let options = &models().models;
+    let name_width = options.iter().map(|model| model.name.len()).max().unwrap_or_default();
+    let labels: Vec<String> = options
+        .iter()
+        .map(|model| {
+            let label = format!("{:<name_width$}  {}", model.name, model.description());
+            if Some(model.model_id.as_str()) == active_model_id {
+                format!("{label} (active)")
+            } else {
+                label
+            }
+        })
+        .collect();
+
+    let selection: Option<_> = match Select::with_theme(&crate::util::dialoguer_theme())
+        .with_prompt("Select a model for this chat session")
+        .items(&labels)
+        .default(0)
+        .interact_on_opt(&dialoguer::console::Term::stdout())
+    {
+        Ok(sel) => {
+            let _ = crossterm::execute!(std::io::stdout(), crossterm::style::SetForegroundColor(theme().tool));
+            sel
+        },
+        // Ctrl‑C -> Err(Interrupted)
+        Err(dialoguer::Error::IO(ref e)) if e.kind() == std::io::ErrorKind::Interrupted => None,
+        Err(e) => return Err(ChatError::Custom(format!("Failed to choose model: {e}").into())),
+    };
+
+    queue!(session.stderr, style::ResetColor)?;
+
+    if let Some(index) = selection {
+        switch(os, session, &options[index]).await?;
+    }
+
+    execute!(session.stderr, style::ResetColor)?;
+    Ok(())
+}
+
+fn list(session: &mut ChatSession) -> Result<(), ChatError> {
+    let active_model_id = session.conversation.model.as_deref();
+    let options = &models().models;
+    let name_width = options.iter().map(|model| model.name.len()).max().unwrap_or_default();
+    let id_width = options
+        .iter()
+        .map(|model| model.model_id.len())
+        .max()
+        .unwrap_or_default();
+    queue!(session.stderr, style::Print("\n"))?;
+    for model in options {
+        let active = Some(model.model_id.as_str()) == active_model_id;
+        queue!(
+            session.stderr,
+            style::Print(if active { "* " } else { "  " }),
+            style::SetForegroundColor(if active { theme().success } else { Color::Reset }),
+            style::Print(format!("{:<name_width$}", model.name)),
+            style::SetForegroundColor(theme().secondary),
+            style::Print(format!("  {:<id_width$}  {}\n", model.model_id, model.description())),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+    }
+    execute!(session.stderr, style::Print("\n"))?;
+    Ok(())
+}
+
+/// Uses `model` for the rest of the conversation and saves it as the default for new sessions.
+async fn switch(os: &mut Os, session: &mut ChatSession, model: &ModelInfo) -> Result<(), ChatError> {
+    session.conversation.model = Some(model.model_id.clone());
+    if let Err(err) = os
+        .database
+        .settings
+        .set(Setting::ChatDefaultModel, model.name.clone())
+        .await
+    {
+        warn!(?err, "failed to save the default model");
+    }
+
+    queue!(
+        session.stderr,
+        style::Print("\n"),
+        style::Print(format!(" Using {}\n\n", model.name)),
+        style::ResetColor,
+        style::SetForegroundColor(Color::Reset),
+        style::SetBackgroundColor(Color::Reset),
+    )?;
+    Ok(())
+}
+
 /// Returns Claude 3.7 for: Amazon IDC users, FRA region users
 /// Returns Claude 4.0 for: Builder ID users, other regions
 pub async fn default_model_id(os: &Os) -> &'static str {
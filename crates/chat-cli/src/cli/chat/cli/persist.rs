@@ -13,6 +13,7 @@ use crate::cli::chat::{
     ChatState,
 };
 use crate::os::Os;
+use crate::util::theme::theme;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Subcommand)]
@@ -25,6 +26,11 @@ pub enum PersistSubcommand {
     },
     /// Load a previous conversation
     Load { path: String },
+    /// Resume a conversation saved automatically in any directory, or list them if no id is given
+    Resume {
+        /// Id of the conversation, or the directory it was saved in
+        id: Option<String>,
+    },
 }
 
 impl PersistSubcommand {
@@ -36,7 +42,7 @@ impl PersistSubcommand {
                     Err(err) => {
                         execute!(
                             session.stderr,
-                            style::SetForegroundColor(Color::Red),
+                            style::SetForegroundColor(theme().error),
                             style::Print(format!("\nFailed to {} {}: {}\n\n", $name, $path, &err)),
                             style::SetAttribute(Attribute::Reset)
                         )?;
@@ -55,7 +61,7 @@ impl PersistSubcommand {
                 if os.fs.exists(&path) && !force {
                     execute!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Red),
+                        style::SetForegroundColor(theme().error),
                         style::Print(format!(
                             "\nFile at {} already exists. To overwrite, use -f or --force\n\n",
                             &path
@@ -70,7 +76,7 @@ impl PersistSubcommand {
 
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(format!("\n✔ Exported conversation state to {}\n\n", &path)),
                     style::SetAttribute(Attribute::Reset)
                 )?;
@@ -100,15 +106,77 @@ impl PersistSubcommand {
                     &mut session.conversation.context_manager,
                 );
                 std::mem::swap(&mut new_state.agents, &mut session.conversation.agents);
+                new_state.incognito = session.conversation.incognito;
                 session.conversation = new_state;
 
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(format!("\n✔ Imported conversation state from {}\n\n", &path)),
                     style::SetAttribute(Attribute::Reset)
                 )?;
             },
+            Self::Resume { id: None } => {
+                let conversations = tri!(os.database.get_all_conversations(), "list", "saved conversations");
+                if conversations.is_empty() {
+                    execute!(session.stderr, style::Print("\nNo saved conversations\n\n"))?;
+                }
+                for (path, conversation) in &conversations {
+                    let current = conversation.conversation_id() == session.conversation.conversation_id();
+                    execute!(
+                        session.stderr,
+                        style::Print(match current {
+                            true => "\n* ",
+                            false => "\n  ",
+                        }),
+                        style::SetForegroundColor(theme().secondary),
+                        style::Print(conversation.conversation_id()),
+                        style::SetForegroundColor(Color::Reset),
+                        style::Print(format!("  {path}  ({} messages)", conversation.history().len())),
+                    )?;
+                }
+                if !conversations.is_empty() {
+                    execute!(session.stderr, style::Print("\n\n"))?;
+                }
+            },
+            Self::Resume { id: Some(id) } => {
+                let conversations = tri!(os.database.get_all_conversations(), "resume", &id);
+                let Some((path, mut new_state)) = conversations
+                    .into_iter()
+                    .find(|(path, conversation)| conversation.conversation_id() == id || *path == id)
+                else {
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(theme().error),
+                        style::Print(format!("\nNo saved conversation found with id {id}\n\n")),
+                        style::SetAttribute(Attribute::Reset)
+                    )?;
+                    return Ok(ChatState::PromptUser {
+                        skip_printing_tools: true,
+                    });
+                };
+
+                std::mem::swap(&mut new_state.tool_manager, &mut session.conversation.tool_manager);
+                std::mem::swap(
+                    &mut new_state.context_manager,
+                    &mut session.conversation.context_manager,
+                );
+                std::mem::swap(&mut new_state.agents, &mut session.conversation.agents);
+                new_state.incognito = session.conversation.incognito;
+                new_state.enforce_tool_use_history_invariants();
+                session.conversation = new_state;
+
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(theme().success),
+                    style::Print(format!(
+                        "\n✔ Resumed conversation {} from {path} ({} messages)\n\n",
+                        session.conversation.conversation_id(),
+                        session.conversation.history().len()
+                    )),
+                    style::SetAttribute(Attribute::Reset)
+                )?;
+            },
         }
 
         Ok(ChatState::PromptUser {
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::Stdio;
+use std::time::Duration;
+
+use bstr::ByteSlice;
+use clap::Args;
+use crossterm::style::{
+    self,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tokio::io::AsyncWriteExt;
+
+use crate::cli::chat::util::truncate_safe;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::util::directories;
+use crate::util::theme::theme;
+
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_OUTPUT_SIZE: usize = 64 * 1024;
+/// Number of trailing transcript entries passed to a plugin as part of its metadata
+const TRANSCRIPT_TAIL_LEN: usize = 10;
+/// Environment variables passed through to plugins when `chat.commandPluginCleanEnv` is set
+const CLEAN_ENV_ALLOWLIST: [&str; 6] = ["PATH", "HOME", "USER", "LANG", "TERM", "TMPDIR"];
+
+/// How the output of a command plugin is added to the conversation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PluginOutput {
+    /// Sent as a user message
+    #[default]
+    Message,
+    /// Attached as a context block to the next user message
+    Context,
+}
+
+/// What a plugin prints when invoked with `--describe`. Plugins that do not print valid json have
+/// the first line of their output used as the description instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginDescription {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub usage: Option<String>,
+    #[serde(default)]
+    pub output: PluginOutput,
+}
+
+/// An executable under ~/.aws/amazonq/commands that is exposed as a slash command named after the
+/// file (without extension).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPlugin {
+    pub name: String,
+    pub path: PathBuf,
+    pub description: PluginDescription,
+}
+
+/// Conversation metadata written to the plugin's stdin as json.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PluginInput<'a> {
+    command: &'a str,
+    args: &'a [String],
+    conversation_id: &'a str,
+    cwd: PathBuf,
+    agent: Option<&'a str>,
+    model: Option<&'a str>,
+    transcript: Vec<&'a str>,
+}
+
+impl CommandPlugin {
+    /// Returns every command plugin that is available, sorted by name.
+    ///
+    /// Plugins are only described the first time they are discovered, after which their
+    /// description is taken from `descriptions`.
+    pub async fn discover(os: &Os, descriptions: &mut HashMap<PathBuf, PluginDescription>) -> Vec<Self> {
+        let Ok(dir) = directories::chat_commands_dir(os) else {
+            return vec![];
+        };
+        let Ok(mut entries) = os.fs.read_dir(&dir).await else {
+            return vec![];
+        };
+
+        let mut plugins = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if !is_valid_name(name) || !is_executable(os, &path).await {
+                continue;
+            }
+            plugins.push((name.to_string(), path));
+        }
+        plugins.sort();
+        plugins.dedup_by(|a, b| a.0 == b.0);
+
+        let mut discovered = Vec::with_capacity(plugins.len());
+        for (name, path) in plugins {
+            let description = match descriptions.get(&path) {
+                Some(description) => description.clone(),
+                None => {
+                    let description = describe(os, &path).await;
+                    descriptions.insert(path.clone(), description.clone());
+                    description
+                },
+            };
+            discovered.push(Self {
+                name,
+                path,
+                description,
+            });
+        }
+        discovered
+    }
+
+    pub async fn find(os: &Os, name: &str, descriptions: &mut HashMap<PathBuf, PluginDescription>) -> Option<Self> {
+        Self::discover(os, descriptions)
+            .await
+            .into_iter()
+            .find(|p| p.name == name)
+    }
+
+    pub async fn execute(&self, os: &Os, session: &mut ChatSession, args: Vec<String>) -> Result<ChatState, ChatError> {
+        let output = self
+            .run(os, session, &args)
+            .await
+            .map_err(|e| ChatError::Custom(format!("/{}: {e}", self.name).into()))?;
+
+        if output.trim().is_empty() {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(theme().secondary),
+                style::Print(format!("/{} produced no output\n\n", self.name)),
+                style::SetForegroundColor(Color::Reset)
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        match self.description.output {
+            // Sent as is, so output starting with e.g. `/` or `!` is not run as a command
+            PluginOutput::Message => Ok(ChatState::SendMessage { input: output }),
+            PluginOutput::Context => {
+                session
+                    .pending_context
+                    .push(format!("[/{}]\n{}", self.name, output.trim_end()));
+                execute!(
+                    session.stderr,
+                    style::SetForegroundColor(theme().success),
+                    style::Print(format!(
+                        "✔ Output of /{} ({} bytes) will be included as context with your next message\n\n",
+                        self.name,
+                        output.len()
+                    )),
+                    style::SetForegroundColor(Color::Reset)
+                )?;
+                Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                })
+            },
+        }
+    }
+
+    async fn run(&self, os: &Os, session: &ChatSession, args: &[String]) -> Result<String> {
+        let conversation = &session.conversation;
+        let input = PluginInput {
+            command: &self.name,
+            args,
+            conversation_id: conversation.conversation_id(),
+            cwd: os.env.current_dir()?,
+            agent: conversation.current_profile(),
+            model: conversation.model.as_deref(),
+            transcript: conversation
+                .transcript
+                .iter()
+                .rev()
+                .take(TRANSCRIPT_TAIL_LEN)
+                .rev()
+                .map(String::as_str)
+                .collect(),
+        };
+
+        let mut cmd = command(os, &self.path);
+        cmd.args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let input = serde_json::to_vec(&input)?;
+        let mut child = cmd.spawn()?;
+        let stdin = child.stdin.take();
+        // The input is written while the output is read, so that a plugin that doesn't read all of
+        // its input before writing can't block, and both count towards the timeout
+        let write = async move {
+            if let Some(mut stdin) = stdin {
+                // Plugins are free to ignore their input, so a closed pipe is not an error
+                let _ = stdin.write_all(&input).await;
+            }
+        };
+        let run = async move {
+            let ((), output) = tokio::join!(write, child.wait_with_output());
+            output
+        };
+
+        let timeout = Duration::from_millis(
+            os.database
+                .settings
+                .get_int(Setting::ChatCommandPluginTimeout)
+                .and_then(|t| u64::try_from(t).ok())
+                .unwrap_or(DEFAULT_TIMEOUT_MS),
+        );
+        let output = match tokio::time::timeout(timeout, run).await {
+            Ok(output) => output?,
+            Err(_) => bail!("timed out after {} ms", timeout.as_millis()),
+        };
+
+        if !output.status.success() {
+            let stderr = output.stderr.to_str_lossy();
+            return Err(eyre!("exited with {}: {}", output.status, stderr.trim()));
+        }
+
+        let stdout = output.stdout.to_str_lossy();
+        Ok(truncate_safe(&stdout, MAX_OUTPUT_SIZE).to_string())
+    }
+}
+
+/// Runs the plugin with `--describe`. Any failure results in an empty description rather than an
+/// error so that a single broken plugin does not prevent the others from being listed.
+async fn describe(os: &Os, path: &Path) -> PluginDescription {
+    let mut cmd = command(os, path);
+    cmd.arg("--describe").stdin(Stdio::null());
+    let Ok(Ok(output)) = tokio::time::timeout(DESCRIBE_TIMEOUT, cmd.output()).await else {
+        return PluginDescription::default();
+    };
+    if !output.status.success() {
+        return PluginDescription::default();
+    }
+
+    let stdout = output.stdout.to_str_lossy();
+    serde_json::from_str::<PluginDescription>(&stdout).unwrap_or_else(|_| PluginDescription {
+        description: stdout.lines().next().map(str::to_string).filter(|s| !s.is_empty()),
+        ..Default::default()
+    })
+}
+
+fn command(os: &Os, path: &Path) -> tokio::process::Command {
+    let mut cmd = tokio::process::Command::new(path);
+    if let Ok(cwd) = os.env.current_dir() {
+        cmd.current_dir(cwd);
+    }
+    if os
+        .database
+        .settings
+        .get_bool(Setting::ChatCommandPluginCleanEnv)
+        .unwrap_or(false)
+    {
+        cmd.env_clear();
+        for key in CLEAN_ENV_ALLOWLIST {
+            if let Ok(value) = os.env.get(key) {
+                cmd.env(key, value);
+            }
+        }
+    }
+    cmd
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(unix)]
+async fn is_executable(os: &Os, path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    os.fs
+        .metadata(path)
+        .await
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(windows)]
+async fn is_executable(os: &Os, path: &Path) -> bool {
+    os.fs.metadata(path).await.is_ok_and(|m| m.is_file())
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| ["exe", "bat", "cmd"].contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Arguments for the `/plugins` command, which lists the available command plugins.
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct PluginsArgs;
+
+impl PluginsArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let plugins = CommandPlugin::discover(os, &mut session.plugin_descriptions).await;
+        if plugins.is_empty() {
+            let dir = directories::chat_commands_dir(os)
+                .map(|d| d.display().to_string())
+                .unwrap_or_default();
+            execute!(
+                session.stderr,
+                style::Print(format!(
+                    "\nNo command plugins found. Add executables to {dir} to make them available as slash commands\n\n"
+                ))
+            )?;
+            return Ok(ChatState::PromptUser {
+                skip_printing_tools: true,
+            });
+        }
+
+        queue!(session.stderr, style::Print("\n"))?;
+        for plugin in plugins {
+            let description = plugin.description;
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(theme().success),
+                style::Print(format!("/{}", plugin.name)),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+            if let Some(usage) = description.usage {
+                queue!(session.stderr, style::Print(format!(" {usage}")))?;
+            }
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(theme().secondary),
+                style::Print(format!(
+                    "  {}\n",
+                    description.description.as_deref().unwrap_or("No description")
+                )),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        execute!(session.stderr, style::Print("\n"))?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("deploy"));
+        assert!(is_valid_name("run-tests_2"));
+        assert!(!is_valid_name("2fast"));
+        assert!(!is_valid_name("has space"));
+        assert!(!is_valid_name(""));
+    }
+
+    #[test]
+    fn test_plugin_description_deser() {
+        let description: PluginDescription =
+            serde_json::from_str(r#"{ "description": "Runs the tests", "output": "context" }"#).unwrap();
+        assert_eq!(description.description.as_deref(), Some("Runs the tests"));
+        assert_eq!(description.output, PluginOutput::Context);
+
+        let description: PluginDescription = serde_json::from_str("{}").unwrap();
+        assert_eq!(description.output, PluginOutput::Message);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_discover() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let os = Os::new().await.unwrap();
+        let dir = directories::chat_commands_dir(&os).unwrap();
+        os.fs.create_dir_all(&dir).await.unwrap();
+        os.fs.write(dir.join("hello.sh"), "#!/bin/sh\necho hi\n").await.unwrap();
+        os.fs.write(dir.join("not_executable"), "").await.unwrap();
+        os.fs
+            .set_permissions(dir.join("hello.sh"), std::fs::Permissions::from_mode(0o755))
+            .await
+            .unwrap();
+
+        let mut descriptions = HashMap::new();
+        let plugins = CommandPlugin::discover(&os, &mut descriptions).await;
+        assert_eq!(plugins.len(), 1);
+        assert_eq!(plugins[0].name, "hello");
+        assert_eq!(plugins[0].description.description.as_deref(), Some("hi"));
+        assert_eq!(descriptions.get(&plugins[0].path), Some(&plugins[0].description));
+    }
+}
@@ -35,6 +35,7 @@ use crate::cli::chat::{
 };
 use crate::os::Os;
 use crate::util::directories::chat_global_agent_path;
+use crate::util::theme::theme;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Subcommand)]
@@ -43,7 +44,8 @@ use crate::util::directories::chat_global_agent_path;
 
 Notes
 • Launch q chat with a specific agent with --agent
-• Construct an agent under ~/.aws/amazonq/agents/ (accessible globally) or cwd/.aws/amazonq/agents (accessible in workspace)
+• Construct an agent under ~/.aws/amazonq/agents/ (accessible globally) or cwd/.aws/amazonq/agents or cwd/.amazonq/agents (accessible in workspace)
+• Run \"q init\" to scaffold a workspace .amazonq directory with a starter agent
 • See example config under global directory
 • Set default agent to assume with settings by running \"q settings chat.defaultAgent agent_name\"
 • Each agent maintains its own set of context and customizations"
@@ -92,7 +94,7 @@ impl AgentSubcommand {
             ($err:expr) => {
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Red),
+                    style::SetForegroundColor(theme().error),
                     style::Print(format!("\nError: {}\n\n", $err)),
                     style::SetForegroundColor(Color::Reset)
                 )?
@@ -108,7 +110,7 @@ impl AgentSubcommand {
                     if active_profile.is_some_and(|p| p == *profile) {
                         queue!(
                             session.stderr,
-                            style::SetForegroundColor(Color::Green),
+                            style::SetForegroundColor(theme().success),
                             style::Print("* "),
                             style::Print(&profile.name),
                             style::SetForegroundColor(Color::Reset),
@@ -163,15 +165,15 @@ impl AgentSubcommand {
 
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print("Agent "),
-                    style::SetForegroundColor(Color::Cyan),
+                    style::SetForegroundColor(theme().info),
                     style::Print(name),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(" has been created successfully"),
                     style::SetForegroundColor(Color::Reset),
                     style::Print("\n"),
-                    style::SetForegroundColor(Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print("Changes take effect on next launch"),
                     style::SetForegroundColor(Color::Reset)
                 )?;
@@ -184,17 +186,17 @@ impl AgentSubcommand {
 
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print("Agent "),
-                    style::SetForegroundColor(Color::Cyan),
+                    style::SetForegroundColor(theme().info),
                     style::Print(agent),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(" has been renamed to "),
-                    style::SetForegroundColor(Color::Cyan),
+                    style::SetForegroundColor(theme().info),
                     style::Print(new_name),
                     style::SetForegroundColor(Color::Reset),
                     style::Print("\n"),
-                    style::SetForegroundColor(Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print("Changes take effect on next launch"),
                     style::SetForegroundColor(Color::Reset)
                 )?;
@@ -211,7 +213,7 @@ impl AgentSubcommand {
                 };
                 execute!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print(format!(
                         "To make changes or create agents, please do so via create the corresponding config in {}, where you would also find an example config for your reference.\nTo switch agent, launch another instance of q chat with --agent.\n\n",
                         global_path
@@ -27,6 +27,7 @@ use crate::cli::chat::{
     ChatState,
 };
 use crate::mcp_client::PromptGetResult;
+use crate::util::theme::theme;
 
 #[derive(Debug, Error)]
 pub enum GetPromptError {
@@ -55,7 +56,10 @@ These templates are provided by the mcp servers you have installed and configure
 To actually retrieve a prompt, directly start with the following command (without prepending /prompt get):
   <em>@<<prompt name>> [arg]</em>                             <black!>Retrieve prompt specified</black!>
 Or if you prefer the long way:
-  <em>/prompts get <<prompt name>> [arg]</em>                 <black!>Retrieve prompt specified</black!>"
+  <em>/prompts get <<prompt name>> [arg]</em>                 <black!>Retrieve prompt specified</black!>
+To retrieve a prompt of a specific server:
+  <em>/prompts <<server>> <<prompt name>> [arg]</em>          <black!>Retrieve prompt specified from server</black!>
+  <em>/prompts <<server>></em>                                <black!>List the prompts of server</black!>"
 })]
 pub struct PromptsArgs {
     #[command(subcommand)]
@@ -64,13 +68,14 @@ pub struct PromptsArgs {
 
 impl PromptsArgs {
     pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
-        let search_word = match &self.subcommand {
-            Some(PromptsSubcommand::List { search_word }) => search_word.clone(),
-            _ => None,
+        let (search_word, server) = match &self.subcommand {
+            Some(PromptsSubcommand::List { search_word }) => (search_word.clone(), None),
+            Some(PromptsSubcommand::Server(parts)) if parts.len() == 1 => (None, parts.first().cloned()),
+            _ => (None, None),
         };
 
         if let Some(subcommand) = self.subcommand {
-            if matches!(subcommand, PromptsSubcommand::Get { .. }) {
+            if subcommand.is_get() {
                 return subcommand.execute(session).await;
             }
         }
@@ -98,7 +103,7 @@ impl PromptsArgs {
             style::SetAttribute(Attribute::Reset),
             style::Print("You can use a prompt by typing "),
             style::SetAttribute(Attribute::Bold),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print("'@<prompt name> [...args]'"),
             style::SetForegroundColor(Color::Reset),
             style::SetAttribute(Attribute::Reset),
@@ -130,7 +135,10 @@ impl PromptsArgs {
                         if prompt_name.len() > longest_name.len() {
                             longest_name = prompt_name.as_str();
                         }
-                        for bundle in bundles {
+                        for bundle in bundles
+                            .iter()
+                            .filter(|bundle| server.as_ref().is_none_or(|server| *server == bundle.server_name))
+                        {
                             acc.entry(&bundle.server_name)
                                 .and_modify(|b| b.push(bundle))
                                 .or_insert(vec![bundle]);
@@ -183,7 +191,7 @@ impl PromptsArgs {
                     for (i, arg) in args.iter().enumerate() {
                         queue!(
                             session.stderr,
-                            style::SetForegroundColor(Color::DarkGrey),
+                            style::SetForegroundColor(theme().secondary),
                             style::Print(match arg.required {
                                 Some(true) => format!("{}*", arg.name),
                                 _ => arg.name.clone(),
@@ -217,17 +225,29 @@ pub enum PromptsSubcommand {
         name: String,
         arguments: Option<Vec<String>>,
     },
+    /// Retrieve a prompt of the server named by the first value, or list its prompts if no prompt
+    /// name follows
+    #[command(external_subcommand)]
+    Server(Vec<String>),
 }
 
 impl PromptsSubcommand {
     pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
-        let PromptsSubcommand::Get {
-            orig_input,
-            name,
-            arguments,
-        } = self
-        else {
-            unreachable!("List has already been parsed out at this point");
+        let (orig_input, name, arguments) = match self {
+            PromptsSubcommand::Get {
+                orig_input,
+                name,
+                arguments,
+            } => (orig_input, name, arguments),
+            PromptsSubcommand::Server(parts) => match parts.as_slice() {
+                [server, name, args @ ..] => (
+                    None,
+                    format!("{server}/{name}"),
+                    (!args.is_empty()).then(|| args.to_vec()),
+                ),
+                _ => unreachable!("Listing the prompts of a server has already been parsed out at this point"),
+            },
+            PromptsSubcommand::List { .. } => unreachable!("List has already been parsed out at this point"),
         };
 
         let prompts = match session.conversation.tool_manager.get_prompt(name, arguments).await {
@@ -238,13 +258,13 @@ impl PromptsSubcommand {
                         queue!(
                             session.stderr,
                             style::Print("\n"),
-                            style::SetForegroundColor(Color::Yellow),
+                            style::SetForegroundColor(theme().warning),
                             style::Print("Prompt "),
-                            style::SetForegroundColor(Color::Cyan),
+                            style::SetForegroundColor(theme().info),
                             style::Print(prompt_name),
-                            style::SetForegroundColor(Color::Yellow),
+                            style::SetForegroundColor(theme().warning),
                             style::Print(" is ambiguous. Use one of the following "),
-                            style::SetForegroundColor(Color::Cyan),
+                            style::SetForegroundColor(theme().info),
                             style::Print(alt_msg),
                             style::SetForegroundColor(Color::Reset),
                         )?;
@@ -253,15 +273,15 @@ impl PromptsSubcommand {
                         queue!(
                             session.stderr,
                             style::Print("\n"),
-                            style::SetForegroundColor(Color::Yellow),
+                            style::SetForegroundColor(theme().warning),
                             style::Print("Prompt "),
-                            style::SetForegroundColor(Color::Cyan),
+                            style::SetForegroundColor(theme().info),
                             style::Print(prompt_name),
-                            style::SetForegroundColor(Color::Yellow),
+                            style::SetForegroundColor(theme().warning),
                             style::Print(" not found. Use "),
-                            style::SetForegroundColor(Color::Cyan),
+                            style::SetForegroundColor(theme().info),
                             style::Print("/prompts list"),
-                            style::SetForegroundColor(Color::Yellow),
+                            style::SetForegroundColor(theme().warning),
                             style::Print(" to see available prompts.\n"),
                             style::SetForegroundColor(Color::Reset),
                         )?;
@@ -285,7 +305,7 @@ impl PromptsSubcommand {
                 style::Print("Error encountered while retrieving prompt:"),
                 style::SetAttribute(Attribute::Reset),
                 style::Print("\n"),
-                style::SetForegroundColor(Color::Red),
+                style::SetForegroundColor(theme().error),
                 style::Print(format_mcp_error(&to_display)),
                 style::SetForegroundColor(Color::Reset),
                 style::Print("\n"),
@@ -310,10 +330,52 @@ impl PromptsSubcommand {
         })
     }
 
+    /// Whether the subcommand retrieves a prompt rather than listing them.
+    fn is_get(&self) -> bool {
+        match self {
+            PromptsSubcommand::List { .. } => false,
+            PromptsSubcommand::Get { .. } => true,
+            PromptsSubcommand::Server(parts) => parts.len() > 1,
+        }
+    }
+
     pub fn name(&self) -> &'static str {
         match self {
             PromptsSubcommand::List { .. } => "list",
-            PromptsSubcommand::Get { .. } => "get",
+            PromptsSubcommand::Get { .. } | PromptsSubcommand::Server(_) => "get",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::chat::cli::SlashCommand;
+
+    #[test]
+    fn test_server_prompt_args() {
+        let SlashCommand::Prompts(args) =
+            SlashCommand::try_parse_from(["", "prompts", "github", "review-pr", "123"]).unwrap()
+        else {
+            panic!("expected /prompts");
+        };
+        let subcommand = args.subcommand.unwrap();
+        assert!(subcommand.is_get());
+        assert_eq!(
+            subcommand,
+            PromptsSubcommand::Server(vec!["github".to_string(), "review-pr".to_string(), "123".to_string()])
+        );
+
+        let SlashCommand::Prompts(args) = SlashCommand::try_parse_from(["", "prompts", "github"]).unwrap() else {
+            panic!("expected /prompts");
+        };
+        assert!(!args.subcommand.unwrap().is_get());
+
+        let SlashCommand::Prompts(args) = SlashCommand::try_parse_from(["", "prompts", "list"]).unwrap() else {
+            panic!("expected /prompts");
+        };
+        assert_eq!(args.subcommand, Some(PromptsSubcommand::List { search_word: None }));
+    }
+}
@@ -0,0 +1,186 @@
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::{
+    self,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::util::knowledge_store::KnowledgeStore;
+use crate::util::theme::theme;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+#[command(
+    before_long_help = "Roots are the directories of a multi-root workspace, such as the packages of a monorepo or an app
+next to the repo of its infrastructure. They are saved to .amazonq/workspace.json. The context files of the
+agent are looked up in every root, and tool uses show the root they operate on."
+)]
+pub struct RootsArgs {
+    #[command(subcommand)]
+    subcommand: Option<RootsSubcommand>,
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum RootsSubcommand {
+    /// List the roots of the workspace
+    #[command(alias = "ls")]
+    List,
+    /// Add a directory as a root of the workspace
+    Add {
+        /// Path of the directory, relative to the current one
+        path: String,
+        /// Name shown for the root, defaults to the name of the directory
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Remove a root from the workspace
+    #[command(alias = "rm")]
+    Remove {
+        /// Name of the root, as shown by /roots list
+        name: String,
+    },
+    /// Add every root to the knowledge base, or update the roots already in it
+    Index,
+}
+
+impl RootsArgs {
+    pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let cwd = os.env.current_dir()?;
+        let message = match self.subcommand.unwrap_or(RootsSubcommand::List) {
+            RootsSubcommand::List => None,
+            RootsSubcommand::Add { path, name } => {
+                let root = session
+                    .workspace
+                    .add(&cwd, &path, name)
+                    .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+                if !os.fs.exists(&root.path) {
+                    let err = format!("{} does not exist", root.path.display());
+                    let name = root.name.clone();
+                    session.workspace.remove(&name).ok();
+                    return Err(ChatError::Custom(err.into()));
+                }
+                Some(format!("Added {} as root {}", root.path.display(), root.name))
+            },
+            RootsSubcommand::Remove { name } => {
+                let root = session
+                    .workspace
+                    .remove(&name)
+                    .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+                if !session.workspace.is_multi_root() {
+                    session.pending_context.push(format!(
+                        "[/roots]\nThe workspace no longer has roots other than {}",
+                        cwd.display()
+                    ));
+                }
+                Some(format!("Removed root {}", root.name))
+            },
+            RootsSubcommand::Index => {
+                index_roots(os, session).await?;
+                return Ok(ChatState::PromptUser {
+                    skip_printing_tools: true,
+                });
+            },
+        };
+
+        if let Some(message) = message {
+            session
+                .workspace
+                .save(os)
+                .await
+                .map_err(|err| ChatError::Custom(format!("Failed to save the workspace roots: {err}").into()))?;
+            session.apply_workspace();
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(theme().success),
+                style::Print(format!("\n✔ {message}\n")),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+
+        for (index, root) in session.workspace.roots().iter().enumerate() {
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(theme().info),
+                style::Print(format!("\n  {:<16}", root.name)),
+                style::SetForegroundColor(Color::Reset),
+                style::Print(root.path.display()),
+                style::SetForegroundColor(theme().secondary),
+                style::Print(if index == 0 { "  (current directory)" } else { "" }),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        execute!(session.stderr, style::Print("\n\n"))?;
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|subcommand| match subcommand {
+            RootsSubcommand::List => "list",
+            RootsSubcommand::Add { .. } => "add",
+            RootsSubcommand::Remove { .. } => "remove",
+            RootsSubcommand::Index => "index",
+        })
+    }
+}
+
+/// Starts indexing every root in the knowledge base, replacing the roots indexed before.
+async fn index_roots(os: &Os, session: &mut ChatSession) -> Result<(), ChatError> {
+    if !os
+        .database
+        .settings
+        .get_bool(Setting::EnabledKnowledge)
+        .unwrap_or(false)
+    {
+        return Err(ChatError::Custom(
+            "Knowledge tool is disabled. Enable it with: q settings chat.enableKnowledge true".into(),
+        ));
+    }
+
+    let store = KnowledgeStore::get_async_instance().await;
+    let mut store = store.lock().await;
+    let indexed = store
+        .get_all()
+        .await
+        .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+    for root in session.workspace.roots() {
+        let path = root.path.to_string_lossy().into_owned();
+        let result = match indexed
+            .iter()
+            .any(|context| context.source_path.as_deref() == Some(path.as_str()))
+        {
+            true => store.update_by_path(&path).await,
+            false => store.add(&root.name, &path).await,
+        };
+        match result {
+            Ok(message) => queue!(session.stderr, style::Print(format!("\n{message}\n")))?,
+            Err(err) => queue!(
+                session.stderr,
+                style::SetForegroundColor(theme().error),
+                style::Print(format!("\nFailed to index {}: {err}\n", root.name)),
+                style::SetForegroundColor(Color::Reset),
+            )?,
+        }
+    }
+    execute!(
+        session.stderr,
+        style::Print("\nCheck the progress with /knowledge status\n\n")
+    )?;
+    Ok(())
+}
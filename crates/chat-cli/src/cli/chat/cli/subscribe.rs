@@ -21,6 +21,7 @@ use crate::cli::chat::{
 };
 use crate::os::Os;
 use crate::util::system_info::is_remote;
+use crate::util::theme::theme;
 
 const SUBSCRIBE_TITLE_TEXT: &str = color_print::cstr! { "<white!,bold>Subscribe to Q Developer Pro</white!,bold>" };
 
@@ -43,7 +44,7 @@ impl SubscribeArgs {
         {
             execute!(
                 session.stderr,
-                style::SetForegroundColor(Color::Yellow),
+                style::SetForegroundColor(theme().warning),
                 style::Print("\nYour Q Developer Pro subscription is managed through IAM Identity Center.\n\n"),
                 style::SetForegroundColor(Color::Reset),
             )?;
@@ -54,13 +55,13 @@ impl SubscribeArgs {
                     if status != ActualSubscriptionStatus::Active {
                         queue!(
                             session.stderr,
-                            style::SetForegroundColor(Color::Yellow),
+                            style::SetForegroundColor(theme().warning),
                             style::Print("You don't seem to have a Q Developer Pro subscription. "),
-                            style::SetForegroundColor(Color::DarkGrey),
+                            style::SetForegroundColor(theme().secondary),
                             style::Print("Use "),
-                            style::SetForegroundColor(Color::Green),
+                            style::SetForegroundColor(theme().success),
                             style::Print("/subscribe"),
-                            style::SetForegroundColor(Color::DarkGrey),
+                            style::SetForegroundColor(theme().secondary),
                             style::Print(" to upgrade your subscription.\n\n"),
                             style::SetForegroundColor(Color::Reset),
                         )?;
@@ -69,7 +70,7 @@ impl SubscribeArgs {
                 Err(err) => {
                     queue!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Red),
+                        style::SetForegroundColor(theme().error),
                         style::Print(format!("Failed to get subscription status: {}\n\n", err)),
                         style::SetForegroundColor(Color::Reset),
                     )?;
@@ -111,7 +112,7 @@ async fn upgrade_to_pro(os: &mut Os, session: &mut ChatSession) -> Result<(), Ch
             if status == ActualSubscriptionStatus::Active {
                 queue!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print("Your Builder ID already has a Q Developer Pro subscription.\n\n"),
                     style::SetForegroundColor(Color::Reset),
                 )?;
@@ -121,7 +122,7 @@ async fn upgrade_to_pro(os: &mut Os, session: &mut ChatSession) -> Result<(), Ch
         Err(e) => {
             execute!(
                 session.stderr,
-                style::SetForegroundColor(Color::Red),
+                style::SetForegroundColor(theme().error),
                 style::Print(format!("{}\n\n", e)),
                 style::SetForegroundColor(Color::Reset),
             )?;
@@ -158,7 +159,7 @@ async fn upgrade_to_pro(os: &mut Os, session: &mut ChatSession) -> Result<(), Ch
     if !user_input.is_some_and(|i| ["y", "Y"].contains(&i.as_str())) {
         execute!(
             session.stderr,
-            style::SetForegroundColor(Color::Red),
+            style::SetForegroundColor(theme().error),
             style::Print("Upgrade cancelled.\n\n"),
             style::SetForegroundColor(Color::Reset),
         )?;
@@ -178,7 +179,7 @@ async fn upgrade_to_pro(os: &mut Os, session: &mut ChatSession) -> Result<(), Ch
     if is_remote() || crate::util::open::open_url_async(&url).await.is_err() {
         queue!(
             session.stderr,
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print(format!(
                 "{} Having issues opening the AWS console? Try copy and pasting the URL > {}\n\n",
                 "?".magenta(),
@@ -0,0 +1,118 @@
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use crate::cli::chat::tools::manage_todo::TodoStatus;
+use crate::cli::chat::{
+    ChatError,
+    ChatSession,
+    ChatState,
+};
+use crate::util::theme::theme;
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Args)]
+pub struct TodoArgs {
+    #[command(subcommand)]
+    subcommand: Option<TodoSubcommand>,
+}
+
+impl TodoArgs {
+    pub async fn execute(self, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let list = &mut session.conversation.todo_list;
+        let result = match self.subcommand {
+            None => Ok(()),
+            Some(TodoSubcommand::Add { task }) => {
+                list.add([task.join(" ")]);
+                Ok(())
+            },
+            Some(TodoSubcommand::Start { index }) => list.set_status(index, TodoStatus::InProgress),
+            Some(TodoSubcommand::Done { index }) => list.set_status(index, TodoStatus::Done),
+            Some(TodoSubcommand::Pending { index }) => list.set_status(index, TodoStatus::Pending),
+            Some(TodoSubcommand::Remove { index }) => list.remove(index).map(|_| ()),
+            Some(TodoSubcommand::Clear) => {
+                list.clear();
+                Ok(())
+            },
+        };
+        if let Err(err) = result {
+            return Err(ChatError::Custom(err.to_string().into()));
+        }
+
+        if list.is_empty() {
+            execute!(
+                session.stderr,
+                style::SetForegroundColor(theme().secondary),
+                style::Print("\nThe task list is empty.\n\n"),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        } else {
+            let (done, total) = list.progress();
+            queue!(
+                session.stderr,
+                style::SetAttribute(Attribute::Bold),
+                style::Print(format!("\nTasks ({done}/{total} done):\n")),
+                style::SetAttribute(Attribute::Reset),
+            )?;
+            list.queue_checklist(&mut session.stderr)
+                .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+            execute!(session.stderr, style::Print("\n"))?;
+        }
+
+        Ok(ChatState::PromptUser {
+            skip_printing_tools: true,
+        })
+    }
+
+    pub fn subcommand_name(&self) -> Option<&'static str> {
+        self.subcommand.as_ref().map(|s| s.name())
+    }
+}
+
+#[deny(missing_docs)]
+#[derive(Debug, PartialEq, Subcommand)]
+#[command(
+    before_long_help = "The task list is maintained by Amazon Q while it works through multi-step requests and is saved
+with the conversation. Tasks are referred to by their number in the list."
+)]
+pub enum TodoSubcommand {
+    /// Add a task to the end of the list
+    Add {
+        #[arg(required = true)]
+        task: Vec<String>,
+    },
+    /// Mark a task as in progress
+    Start { index: usize },
+    /// Mark a task as done
+    Done { index: usize },
+    /// Mark a task as pending
+    Pending { index: usize },
+    /// Remove a task
+    #[command(alias = "rm")]
+    Remove { index: usize },
+    /// Remove all tasks
+    Clear,
+}
+
+impl TodoSubcommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TodoSubcommand::Add { .. } => "add",
+            TodoSubcommand::Start { .. } => "start",
+            TodoSubcommand::Done { .. } => "done",
+            TodoSubcommand::Pending { .. } => "pending",
+            TodoSubcommand::Remove { .. } => "remove",
+            TodoSubcommand::Clear => "clear",
+        }
+    }
+}
@@ -7,6 +7,7 @@ use std::io::Write;
 use clap::{
     Args,
     Subcommand,
+    ValueEnum,
 };
 use crossterm::style::{
     Attribute,
@@ -28,6 +29,7 @@ use crate::cli::chat::{
     TRUST_ALL_TEXT,
 };
 use crate::util::consts::MCP_SERVER_TOOL_DELIMITER;
+use crate::util::theme::theme;
 
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
@@ -150,16 +152,25 @@ impl ToolsArgs {
             }
         }
 
+        if session.dry_run {
+            queue!(
+                session.stderr,
+                style::SetForegroundColor(theme().warning),
+                style::Print("\nDry run is on, tools that could change something will not run."),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+
         queue!(
             session.stderr,
             style::Print("\nTrusted tools will run without confirmation."),
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print(format!("\n{}\n", "* Default settings")),
             style::Print("\n💡 Use "),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print("/tools help"),
             style::SetForegroundColor(Color::Reset),
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print(" to edit permissions.\n\n"),
             style::SetForegroundColor(Color::Reset),
         )?;
@@ -195,6 +206,17 @@ pub enum ToolsSubcommand {
     TrustAll,
     /// Reset all tools to default permission levels
     Reset,
+    /// Preview what tools would change instead of running them
+    DryRun {
+        /// Turn dry runs on or off, toggling them if omitted
+        state: Option<Toggle>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Toggle {
+    On,
+    Off,
 }
 
 impl ToolsSubcommand {
@@ -240,7 +262,7 @@ impl ToolsSubcommand {
                 if !invalid_tools.is_empty() {
                     queue!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Red),
+                        style::SetForegroundColor(theme().error),
                         style::Print(format!("\nCannot trust '{}', ", invalid_tools.join("', '"))),
                         if invalid_tools.len() > 1 {
                             style::Print("they do not exist.")
@@ -266,7 +288,7 @@ impl ToolsSubcommand {
 
                     queue!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         if tools_to_trust.len() > 1 {
                             style::Print(format!("\nTools '{}' are ", tools_to_trust.join("', '")))
                         } else {
@@ -276,7 +298,7 @@ impl ToolsSubcommand {
                         style::SetAttribute(Attribute::Bold),
                         style::Print("not"),
                         style::SetAttribute(Attribute::Reset),
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::Print(format!(
                             " ask for confirmation before running {}.",
                             if tools_to_trust.len() > 1 {
@@ -301,7 +323,7 @@ impl ToolsSubcommand {
                 if !invalid_tools.is_empty() {
                     queue!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Red),
+                        style::SetForegroundColor(theme().error),
                         style::Print(format!("\nCannot untrust '{}', ", invalid_tools.join("', '"))),
                         if invalid_tools.len() > 1 {
                             style::Print("they do not exist.")
@@ -329,7 +351,7 @@ impl ToolsSubcommand {
 
                     queue!(
                         session.stderr,
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         if tools_to_untrust.len() > 1 {
                             style::Print(format!("\nTools '{}' are ", tools_to_untrust.join("', '")))
                         } else {
@@ -376,11 +398,28 @@ impl ToolsSubcommand {
                 }
                 queue!(
                     session.stderr,
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print("\nReset all tools to the permission levels as defined in agent."),
                     style::SetForegroundColor(Color::Reset),
                 )?;
             },
+            Self::DryRun { state } => {
+                session.dry_run = match state {
+                    Some(Toggle::On) => true,
+                    Some(Toggle::Off) => false,
+                    None => !session.dry_run,
+                };
+                queue!(
+                    session.stderr,
+                    style::SetForegroundColor(theme().success),
+                    style::Print(if session.dry_run {
+                        "\nDry run is on. Writes will return the diff they would apply without touching disk, and other tools that could change something will not run.\n"
+                    } else {
+                        "\nDry run is off. Tools will make their changes again.\n"
+                    }),
+                    style::SetForegroundColor(Color::Reset),
+                )?;
+            },
         };
 
         session.stderr.flush()?;
@@ -397,6 +436,7 @@ impl ToolsSubcommand {
             ToolsSubcommand::Untrust { .. } => "untrust",
             ToolsSubcommand::TrustAll => "trust-all",
             ToolsSubcommand::Reset => "reset",
+            ToolsSubcommand::DryRun { .. } => "dry-run",
         }
     }
 }
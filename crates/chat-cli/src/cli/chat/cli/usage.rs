@@ -9,7 +9,7 @@ use crossterm::{
     style,
 };
 
-use crate::cli::chat::consts::CONTEXT_WINDOW_SIZE;
+use crate::cli::chat::model_registry::models;
 use crate::cli::chat::token_counter::{
     CharCount,
     TokenCount,
@@ -20,12 +20,18 @@ use crate::cli::chat::{
     ChatState,
 };
 use crate::os::Os;
+use crate::util::theme::theme;
+
+/// How many of the latest turns are listed.
+const RECENT_TURNS: usize = 10;
+
 #[deny(missing_docs)]
 #[derive(Debug, PartialEq, Args)]
 pub struct UsageArgs;
 
 impl UsageArgs {
     pub async fn execute(self, os: &Os, session: &mut ChatSession) -> Result<ChatState, ChatError> {
+        let context_window_size = models().context_window(session.conversation.model.as_deref());
         let state = session
             .conversation
             .backend_conversation_state(os, true, &mut session.stderr)
@@ -63,13 +69,13 @@ impl UsageArgs {
         let progress_bar_width = std::cmp::min(window_width, 80);
 
         let context_width =
-            ((context_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
+            ((context_token_count.value() as f64 / context_window_size as f64) * progress_bar_width as f64) as usize;
         let assistant_width =
-            ((assistant_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
+            ((assistant_token_count.value() as f64 / context_window_size as f64) * progress_bar_width as f64) as usize;
         let tools_width =
-            ((tools_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
+            ((tools_token_count.value() as f64 / context_window_size as f64) * progress_bar_width as f64) as usize;
         let user_width =
-            ((user_token_count.value() as f64 / CONTEXT_WINDOW_SIZE as f64) * progress_bar_width as f64) as usize;
+            ((user_token_count.value() as f64 / context_window_size as f64) * progress_bar_width as f64) as usize;
 
         let left_over_width = progress_bar_width
             - std::cmp::min(
@@ -85,7 +91,7 @@ impl UsageArgs {
                 style::Print(format!(
                     "\nCurrent context window ({} of {}k tokens used)\n",
                     total_token_used,
-                    CONTEXT_WINDOW_SIZE / 1000
+                    context_window_size / 1000
                 )),
                 style::SetForegroundColor(Color::DarkRed),
                 style::Print("█".repeat(progress_bar_width)),
@@ -93,7 +99,7 @@ impl UsageArgs {
                 style::Print(" "),
                 style::Print(format!(
                     "{:.2}%",
-                    (total_token_used.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+                    (total_token_used.value() as f32 / context_window_size as f32) * 100.0
                 )),
             )?;
         } else {
@@ -102,7 +108,7 @@ impl UsageArgs {
                 style::Print(format!(
                     "\nCurrent context window ({} of {}k tokens used)\n",
                     total_token_used,
-                    CONTEXT_WINDOW_SIZE / 1000
+                    context_window_size / 1000
                 )),
                 // Context files
                 style::SetForegroundColor(Color::DarkCyan),
@@ -123,7 +129,7 @@ impl UsageArgs {
                 })),
                 style::Print("█".repeat(tools_width)),
                 // Assistant responses
-                style::SetForegroundColor(Color::Blue),
+                style::SetForegroundColor(theme().label),
                 style::Print("|".repeat(if assistant_width == 0 && *assistant_token_count > 0 {
                     1
                 } else {
@@ -131,16 +137,16 @@ impl UsageArgs {
                 })),
                 style::Print("█".repeat(assistant_width)),
                 // User prompts
-                style::SetForegroundColor(Color::Magenta),
+                style::SetForegroundColor(theme().tool),
                 style::Print("|".repeat(if user_width == 0 && *user_token_count > 0 { 1 } else { 0 })),
                 style::Print("█".repeat(user_width)),
-                style::SetForegroundColor(Color::DarkGrey),
+                style::SetForegroundColor(theme().secondary),
                 style::Print("█".repeat(left_over_width)),
                 style::Print(" "),
                 style::SetForegroundColor(Color::Reset),
                 style::Print(format!(
                     "{:.2}%",
-                    (total_token_used.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+                    (total_token_used.value() as f32 / context_window_size as f32) * 100.0
                 )),
             )?;
         }
@@ -155,7 +161,7 @@ impl UsageArgs {
             style::Print(format!(
                 "~{} tokens ({:.2}%)\n",
                 context_token_count,
-                (context_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+                (context_token_count.value() as f32 / context_window_size as f32) * 100.0
             )),
             style::SetForegroundColor(Color::DarkRed),
             style::Print("█ Tools:    "),
@@ -163,46 +169,55 @@ impl UsageArgs {
             style::Print(format!(
                 " ~{} tokens ({:.2}%)\n",
                 tools_token_count,
-                (tools_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+                (tools_token_count.value() as f32 / context_window_size as f32) * 100.0
             )),
-            style::SetForegroundColor(Color::Blue),
+            style::SetForegroundColor(theme().label),
             style::Print("█ Q responses: "),
             style::SetForegroundColor(Color::Reset),
             style::Print(format!(
                 "  ~{} tokens ({:.2}%)\n",
                 assistant_token_count,
-                (assistant_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+                (assistant_token_count.value() as f32 / context_window_size as f32) * 100.0
             )),
-            style::SetForegroundColor(Color::Magenta),
+            style::SetForegroundColor(theme().tool),
             style::Print("█ Your prompts: "),
             style::SetForegroundColor(Color::Reset),
             style::Print(format!(
                 " ~{} tokens ({:.2}%)\n\n",
                 user_token_count,
-                (user_token_count.value() as f32 / CONTEXT_WINDOW_SIZE as f32) * 100.0
+                (user_token_count.value() as f32 / context_window_size as f32) * 100.0
+            )),
+        )?;
+
+        queue!(
+            session.stderr,
+            style::Print(format!(
+                "~{} tokens left in the context window\n\n",
+                context_window_size.saturating_sub(total_token_used.value())
             )),
         )?;
+        print_session_usage(session)?;
 
         queue!(
             session.stderr,
             style::SetAttribute(Attribute::Bold),
             style::Print("\n💡 Pro Tips:\n"),
             style::SetAttribute(Attribute::Reset),
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print("Run "),
             style::SetForegroundColor(Color::DarkGreen),
             style::Print("/compact"),
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print(" to replace the conversation history with its summary\n"),
             style::Print("Run "),
             style::SetForegroundColor(Color::DarkGreen),
             style::Print("/clear"),
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print(" to erase the entire chat history\n"),
             style::Print("Run "),
             style::SetForegroundColor(Color::DarkGreen),
             style::Print("/context show"),
-            style::SetForegroundColor(Color::DarkGrey),
+            style::SetForegroundColor(theme().secondary),
             style::Print(" to see tokens per context file\n\n"),
             style::SetForegroundColor(Color::Reset),
         )?;
@@ -212,3 +227,60 @@ impl UsageArgs {
         })
     }
 }
+
+/// Prints the tokens of the latest turns and of the whole session, as estimated when the requests
+/// were sent and answered.
+fn print_session_usage(session: &mut ChatSession) -> Result<(), ChatError> {
+    let turns = session.token_usage.turns();
+    if turns.is_empty() {
+        return Ok(());
+    }
+
+    queue!(
+        session.stderr,
+        style::SetAttribute(Attribute::Bold),
+        style::Print("Tokens used this session\n"),
+        style::SetAttribute(Attribute::Reset),
+        style::SetForegroundColor(theme().secondary),
+        style::Print(format!(
+            "{:<8}{:>10}{:>12}{:>12}\n",
+            "Turn", "Requests", "Input", "Output"
+        )),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+    let skipped = turns.len().saturating_sub(RECENT_TURNS);
+    if skipped > 0 {
+        queue!(
+            session.stderr,
+            style::SetForegroundColor(theme().secondary),
+            style::Print(format!("… {skipped} earlier turns\n")),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+    }
+    for (i, turn) in turns.iter().enumerate().skip(skipped) {
+        queue!(
+            session.stderr,
+            style::Print(format!(
+                "{:<8}{:>10}{:>12}{:>12}\n",
+                i + 1,
+                turn.requests,
+                format!("~{}", turn.input_tokens),
+                format!("~{}", turn.output_tokens)
+            )),
+        )?;
+    }
+    let total = session.token_usage.total();
+    queue!(
+        session.stderr,
+        style::SetAttribute(Attribute::Bold),
+        style::Print(format!(
+            "{:<8}{:>10}{:>12}{:>12}\n",
+            "Total",
+            total.requests,
+            format!("~{}", total.input_tokens),
+            format!("~{}", total.output_tokens)
+        )),
+        style::SetAttribute(Attribute::Reset),
+    )?;
+    Ok(())
+}
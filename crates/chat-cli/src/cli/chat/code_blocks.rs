@@ -0,0 +1,326 @@
+//! Actions on the code blocks of a response that are meant for existing files. Once a turn ends
+//! without tool uses, each such block can be compared with its file, applied to it, copied or
+//! skipped. The file of a block is taken from its info string, as in ```` ```rust src/main.rs ````,
+//! or from the path in backticks on the line before it, as in "Update `src/main.rs`:".
+//!
+//! Applying a block replaces the file with it, through [FsWrite] and the same permissions, path
+//! guard and checkpoints as the tool, so it can be undone with `/undo`.
+
+use std::io::Write;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use crossterm::style::{
+    Color,
+    Stylize,
+};
+use crossterm::{
+    execute,
+    queue,
+    style,
+};
+
+use super::artifacts::{
+    self,
+    ArtifactKind,
+};
+use super::path_guard::{
+    self,
+    PathGuardMode,
+};
+use super::tools::fs_write::FsWrite;
+use super::tools::{
+    Tool,
+    sanitize_path_tool_arg,
+};
+use super::{
+    ChatError,
+    ChatSession,
+};
+use crate::cli::agent::PermissionEvalResult;
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::util::theme::theme;
+
+/// A fenced code block of a response, with the file it is meant for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    pub path: String,
+    pub content: String,
+}
+
+/// Whether `token` reads like a file path rather than a language or a word.
+fn looks_like_path(token: &str) -> bool {
+    !token.is_empty()
+        && !token.contains(char::is_whitespace)
+        && !token.contains("://")
+        && (token.contains('/') || token.trim_start_matches('.').contains('.'))
+}
+
+/// The path named in the info string of a fence, e.g. `rust src/main.rs`, `rust:src/main.rs` or
+/// `rust title="src/main.rs"`.
+fn path_in_info(info: &str) -> Option<String> {
+    info.split_whitespace()
+        .flat_map(|token| token.split_once(':').map_or([token, ""], |(lang, path)| [lang, path]))
+        .map(|token| {
+            let token = ["title=", "file=", "path="]
+                .iter()
+                .find_map(|prefix| token.strip_prefix(prefix))
+                .unwrap_or(token);
+            token.trim_matches(['"', '\''])
+        })
+        .find(|token| looks_like_path(token))
+        .map(str::to_string)
+}
+
+/// The last path in backticks on `line`, or the line itself if it is only a path followed by a
+/// colon.
+fn path_in_line(line: &str) -> Option<String> {
+    let in_backticks = line
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .filter(|span| looks_like_path(span))
+        .last();
+    let whole_line = line.trim().strip_suffix(':').filter(|path| looks_like_path(path));
+    in_backticks.or(whole_line).map(str::to_string)
+}
+
+/// Returns the fenced code blocks of `text` whose info string or preceding line names a file,
+/// in the order they appear.
+pub fn find_code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut previous_line = "";
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let Some(info) = line.trim_start().strip_prefix("```") else {
+            if !line.trim().is_empty() {
+                previous_line = line;
+            }
+            continue;
+        };
+
+        let mut content = String::new();
+        for line in lines.by_ref() {
+            if line.trim() == "```" {
+                break;
+            }
+            content.push_str(line);
+            content.push('\n');
+        }
+        if let Some(path) = path_in_info(info).or_else(|| path_in_line(previous_line)) {
+            blocks.push(CodeBlock { path, content });
+        }
+        previous_line = "";
+    }
+    blocks
+}
+
+/// Offers the actions on the code blocks of `response` that are meant for existing files.
+pub async fn offer_actions(session: &mut ChatSession, os: &mut Os, response: &str) -> Result<(), ChatError> {
+    if os
+        .database
+        .settings
+        .get_bool(Setting::ChatDisableCodeBlockActions)
+        .unwrap_or(false)
+    {
+        return Ok(());
+    }
+    let blocks = find_code_blocks(response)
+        .into_iter()
+        .filter(|block| os.fs.chroot_path(sanitize_path_tool_arg(os, &block.path)).is_file())
+        .collect::<Vec<_>>();
+    if blocks.is_empty() {
+        return Ok(());
+    }
+
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(theme().secondary),
+        style::Print(format!(
+            "The response has {} code block(s) for existing files. Turn this off with q settings chat.disableCodeBlockActions true\n",
+            blocks.len()
+        )),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+
+    for block in &blocks {
+        execute!(
+            session.stderr,
+            style::Print("\n"),
+            style::SetForegroundColor(theme().tool),
+            style::Print(&block.path),
+            style::SetForegroundColor(theme().secondary),
+            style::Print(format!(" ({} lines)\n", block.content.lines().count())),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        loop {
+            let prompt = "[d]iff, [a]pply, [c]opy or [s]kip: "
+                .with(theme().secondary)
+                .to_string();
+            let Some(input) = session.read_user_input(&prompt, true) else {
+                return Ok(());
+            };
+            match input.trim().to_lowercase().as_str() {
+                "d" | "diff" => {
+                    create_file(block)
+                        .queue_description(os, &mut session.stderr)
+                        .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+                    session.stderr.flush()?;
+                },
+                "a" | "apply" => {
+                    apply(session, os, block).await?;
+                    break;
+                },
+                "c" | "copy" => {
+                    // OSC 52 asks the terminal to set the clipboard, which works over SSH as well. It
+                    // is written past the output mirrored to shares and recordings.
+                    execute!(
+                        std::io::stdout(),
+                        style::Print(format!("\x1b]52;c;{}\x07", STANDARD.encode(&block.content)))
+                    )?;
+                    execute!(
+                        session.stderr,
+                        style::SetForegroundColor(theme().success),
+                        style::Print("Copied to the clipboard, if the terminal allows it.\n"),
+                        style::SetForegroundColor(Color::Reset),
+                    )?;
+                    break;
+                },
+                _ => break,
+            }
+        }
+    }
+    execute!(session.stderr, style::Print("\n"))?;
+    Ok(())
+}
+
+fn create_file(block: &CodeBlock) -> FsWrite {
+    FsWrite::Create {
+        path: block.path.clone(),
+        file_text: Some(block.content.clone()),
+        new_str: None,
+        summary: None,
+    }
+}
+
+/// Replaces the file of `block` with it, if the agent allows fs_write to and, unless the tool is
+/// trusted, the user accepts the diff.
+async fn apply(session: &mut ChatSession, os: &mut Os, block: &CodeBlock) -> Result<(), ChatError> {
+    let tool = Tool::FsWrite(create_file(block));
+    let agent = session.conversation.agents.get_active();
+    let permission = agent.map(|agent| tool.requires_acceptance(os, agent));
+    let mode = agent.map(PathGuardMode::for_agent).unwrap_or_default();
+    let guarded = match mode {
+        PathGuardMode::Off => None,
+        PathGuardMode::Confirm | PathGuardMode::Block => path_guard::check(os, &tool, &session.workspace),
+    };
+
+    let refused = if session.dry_run {
+        Some("nothing is changed during a dry run".to_string())
+    } else if permission == Some(PermissionEvalResult::Deny) {
+        Some("the agent doesn't allow fs_write to change it".to_string())
+    } else {
+        guarded
+            .as_ref()
+            .filter(|_| mode == PathGuardMode::Block)
+            .map(ToString::to_string)
+    };
+    if let Some(reason) = refused {
+        execute!(
+            session.stderr,
+            style::SetForegroundColor(theme().error),
+            style::Print(format!("Not applying the block to {}: {reason}\n", block.path)),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        return Ok(());
+    }
+
+    let Tool::FsWrite(mut fs_write) = tool else {
+        return Ok(());
+    };
+    fs_write
+        .validate(os)
+        .await
+        .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+    fs_write
+        .queue_description(os, &mut session.stderr)
+        .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+    if let Some(guarded) = &guarded {
+        queue!(
+            session.stderr,
+            style::SetForegroundColor(theme().warning),
+            style::Print(format!("\n{guarded}\n")),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+    }
+    session.stderr.flush()?;
+
+    let trusted = (permission == Some(PermissionEvalResult::Allow) || session.conversation.agents.trust_all_tools)
+        && guarded.is_none();
+    if !trusted {
+        let prompt = "Apply this change? [y/n]: ".with(theme().secondary).to_string();
+        match session.read_user_input(&prompt, true) {
+            Some(input) if input.trim().eq_ignore_ascii_case("y") => (),
+            _ => return Ok(()),
+        }
+    }
+
+    let result = fs_write
+        .invoke(os, &mut session.stderr)
+        .await
+        .map_err(|err| ChatError::Custom(err.to_string().into()))?;
+    let backup = result
+        .artifacts
+        .iter()
+        .find(|(kind, _)| *kind == ArtifactKind::Backup)
+        .map(|(_, path)| path.clone());
+    let path = sanitize_path_tool_arg(os, fs_write.path());
+    session.checkpoints.record(os.env.current_dir()?.join(path), backup);
+    for (kind, path) in &result.artifacts {
+        artifacts::track(os, session.conversation.conversation_id(), *kind, path);
+    }
+    execute!(
+        session.stderr,
+        style::SetForegroundColor(theme().success),
+        style::Print(format!("Applied to {}, undo with /undo\n", block.path)),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_code_blocks() {
+        let response = "Here is the fix.\n\n```rust src/main.rs\nfn main() {}\n```\n\nThen update `Cargo.toml`:\n\n```toml\n[package]\n```\n\nRun it with:\n\n```bash\ncargo run\n```\n\n```python:scripts/build.py\nprint()\n```\n";
+        assert_eq!(find_code_blocks(response), [
+            CodeBlock {
+                path: "src/main.rs".to_string(),
+                content: "fn main() {}\n".to_string(),
+            },
+            CodeBlock {
+                path: "Cargo.toml".to_string(),
+                content: "[package]\n".to_string(),
+            },
+            CodeBlock {
+                path: "scripts/build.py".to_string(),
+                content: "print()\n".to_string(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_path_in_info_and_line() {
+        assert_eq!(path_in_info(r#"ts title="web/app.ts""#).as_deref(), Some("web/app.ts"));
+        assert_eq!(path_in_info("rust"), None);
+        assert_eq!(path_in_line("src/lib.rs:").as_deref(), Some("src/lib.rs"));
+        assert_eq!(path_in_line("See https://example.com/a.b for more"), None);
+        assert_eq!(
+            path_in_line("Change `foo` in `src/lib.rs`").as_deref(),
+            Some("src/lib.rs")
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! The command palette, opened with Ctrl+K on an empty prompt. It lists the slash commands with
+//! what they do, the files tools changed in the session, the agents and the models in one fuzzy
+//! searchable list, and runs the selection as if it had been typed. Files can't be run, so they
+//! are inserted into the prompt instead.
+//!
+//! Ctrl+K is also the emacs binding for killing to the end of the line, which does nothing on an
+//! empty prompt, so the key keeps that behavior everywhere else.
+
+use std::path::PathBuf;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use clap::CommandFactory;
+use rustyline::{
+    Cmd,
+    ConditionalEventHandler,
+    Event,
+    EventContext,
+    RepeatCount,
+};
+use tracing::error;
+
+use super::cli::SlashCommand;
+use super::context::ContextManager;
+use super::skim_integration::{
+    add_command_arguments,
+    launch_skim_selector,
+};
+
+/// How many of the files changed by tools are listed, latest first.
+const RECENT_FILES: usize = 20;
+
+/// The line selected in the palette, taken by the input source in place of the empty line the
+/// palette accepts to run it.
+pub type PaletteSelection = Arc<Mutex<Option<String>>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteItem {
+    Command { command: String, description: String },
+    File(PathBuf),
+    Agent(String),
+    Model(String),
+}
+
+impl PaletteItem {
+    /// The line shown in the palette, which the fuzzy search matches against.
+    fn line(&self) -> String {
+        match self {
+            PaletteItem::Command { command, description } => format!("{command:<28}{description}"),
+            PaletteItem::File(path) => format!("{:<28}{}", "file", path.display()),
+            PaletteItem::Agent(name) => format!("{:<28}{name}", "agent"),
+            PaletteItem::Model(name) => format!("{:<28}{name}", "model"),
+        }
+    }
+}
+
+/// What selecting an item does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PaletteAction {
+    Run(String),
+    Insert(String),
+}
+
+/// Returns the about text of the (sub)command a line of [super::prompt::COMMANDS] runs, or of its
+/// parent when the subcommand has none, e.g. for `/context show --expand`.
+fn command_description(command: &clap::Command, line: &str) -> Option<String> {
+    let mut command = command;
+    let mut description = None;
+    for word in line.trim_start_matches('/').split_whitespace() {
+        let Some(subcommand) = command.find_subcommand(word) else {
+            break;
+        };
+        command = subcommand;
+        if let Some(about) = command.get_about() {
+            description = Some(about.to_string());
+        }
+    }
+    description
+}
+
+/// The items of the palette: every slash command, then the files changed by tools in the session,
+/// latest first, then the agents and the models.
+pub fn palette_items(changed_files: &[PathBuf], agents: &[String], models: &[String]) -> Vec<PaletteItem> {
+    // Building adds the generated help subcommand
+    let mut slash_command = SlashCommand::command();
+    slash_command.build();
+    let mut items = super::prompt::COMMANDS
+        .iter()
+        .map(|command| PaletteItem::Command {
+            command: (*command).to_string(),
+            description: command_description(&slash_command, command).unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut files = Vec::new();
+    for path in changed_files.iter().rev() {
+        if !files.contains(path) {
+            files.push(path.clone());
+        }
+    }
+    items.extend(files.into_iter().take(RECENT_FILES).map(PaletteItem::File));
+    items.extend(agents.iter().cloned().map(PaletteItem::Agent));
+    items.extend(models.iter().cloned().map(PaletteItem::Model));
+    items
+}
+
+pub struct CommandPalette {
+    items: Vec<PaletteItem>,
+    selection: PaletteSelection,
+    context_manager: Arc<ContextManager>,
+    tool_names: Vec<String>,
+}
+
+impl CommandPalette {
+    pub fn new(
+        items: Vec<PaletteItem>,
+        selection: PaletteSelection,
+        context_manager: Arc<ContextManager>,
+        tool_names: Vec<String>,
+    ) -> Self {
+        Self {
+            items,
+            selection,
+            context_manager,
+            tool_names,
+        }
+    }
+
+    fn select(&self) -> eyre::Result<Option<PaletteAction>> {
+        let lines = self.items.iter().map(PaletteItem::line).collect::<Vec<_>>();
+        let Some(selected) = launch_skim_selector(&lines, "Command palette: ", false)? else {
+            return Ok(None);
+        };
+        let Some(selected) = selected.first().map(|line| line.trim_end()) else {
+            return Ok(None);
+        };
+        let Some(item) = lines
+            .iter()
+            .position(|line| line.trim_end() == selected)
+            .and_then(|i| self.items.get(i))
+        else {
+            return Ok(None);
+        };
+
+        Ok(match item {
+            PaletteItem::Command { command, .. } => {
+                add_command_arguments(command, &self.context_manager, &self.tool_names)?.map(PaletteAction::Run)
+            },
+            PaletteItem::File(path) => Some(PaletteAction::Insert(format!("{} ", path.display()))),
+            PaletteItem::Agent(name) => Some(PaletteAction::Run(format!("/agent set {name}"))),
+            PaletteItem::Model(name) => Some(PaletteAction::Run(format!("/model set {name}"))),
+        })
+    }
+}
+
+impl ConditionalEventHandler for CommandPalette {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext<'_>) -> Option<Cmd> {
+        if !ctx.line().is_empty() {
+            return None;
+        }
+
+        match self.select() {
+            Ok(Some(PaletteAction::Run(line))) => {
+                if let Ok(mut selection) = self.selection.lock() {
+                    *selection = Some(line);
+                }
+                Some(Cmd::AcceptLine)
+            },
+            Ok(Some(PaletteAction::Insert(text))) => Some(Cmd::Insert(1, text)),
+            Ok(None) => Some(Cmd::Repaint),
+            Err(err) => {
+                error!(?err, "Failed to run the command palette");
+                Some(Cmd::Repaint)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_description() {
+        let mut command = SlashCommand::command();
+        command.build();
+        assert_eq!(
+            command_description(&command, "/compact").as_deref(),
+            Some("Summarize the conversation to free up context space")
+        );
+        assert_eq!(
+            command_description(&command, "/context show --expand"),
+            command_description(&command, "/context show")
+        );
+        assert_ne!(
+            command_description(&command, "/context show"),
+            command_description(&command, "/context")
+        );
+    }
+
+    #[test]
+    fn test_palette_items() {
+        let changed = [PathBuf::from("a.rs"), PathBuf::from("b.rs"), PathBuf::from("a.rs")];
+        let items = palette_items(&changed, &["default".to_string()], &["claude-sonnet-4".to_string()]);
+
+        assert!(items.iter().all(|item| match item {
+            PaletteItem::Command { description, .. } => !description.is_empty(),
+            _ => true,
+        }));
+        assert_eq!(items[super::super::prompt::COMMANDS.len()..], [
+            PaletteItem::File(PathBuf::from("a.rs")),
+            PaletteItem::File(PathBuf::from("b.rs")),
+            PaletteItem::Agent("default".to_string()),
+            PaletteItem::Model("claude-sonnet-4".to_string()),
+        ]);
+    }
+}
@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 use std::io::Write;
-use std::path::Path;
+use std::path::{
+    Path,
+    PathBuf,
+};
 
 use eyre::{
     Result,
@@ -11,18 +14,34 @@ use serde::{
     Deserialize,
     Serialize,
 };
+use tracing::warn;
 
 use super::consts::CONTEXT_FILES_MAX_SIZE;
-use super::util::drop_matched_context_files;
+use super::url_context;
+use super::util::{
+    BudgetOutcome,
+    BudgetedFile,
+    apply_context_budget,
+};
 use crate::cli::agent::Agent;
+use crate::cli::agent::content_filter::ContentFilter;
+use crate::cli::agent::egress::{
+    Egress,
+    EgressPolicy,
+};
 use crate::cli::agent::hook::{
     Hook,
     HookTrigger,
 };
 use crate::cli::chat::ChatError;
 use crate::cli::chat::cli::hooks::HookExecutor;
+use crate::cli::chat::tools::grep_search::gitignore::GitignoreCache;
+use crate::database::settings::Setting;
 use crate::os::Os;
 
+/// How many files a glob pattern or directory adds to the context at most.
+pub const MAX_FILES_PER_PATH: usize = 250;
+
 /// Manager for context files and profiles.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextManager {
@@ -35,6 +54,35 @@ pub struct ContextManager {
     pub hooks: HashMap<HookTrigger, Vec<Hook>>,
     #[serde(skip)]
     pub hook_executor: HookExecutor,
+    /// Content filter configured in the agent. Files it blocks are never added to the context.
+    #[serde(default)]
+    pub content_filter: ContentFilter,
+    /// Resources of MCP servers added with `/context add-resource`.
+    #[serde(default)]
+    pub resources: Vec<ContextResource>,
+    /// Egress policy configured in the agent, which web documents in the context are fetched
+    /// within.
+    #[serde(default)]
+    pub egress: EgressPolicy,
+    /// Roots of a multi-root workspace besides the current directory, where relative paths are
+    /// looked up as well. Set by the session, see [Workspace](super::workspace::Workspace).
+    #[serde(skip)]
+    pub roots: Vec<PathBuf>,
+}
+
+/// A resource read from an MCP server, kept as it was when it was added to the context.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextResource {
+    pub server: String,
+    pub uri: String,
+    pub content: String,
+}
+
+impl ContextResource {
+    /// The `<server>/<uri>` the resource was added as.
+    pub fn name(&self) -> String {
+        format!("{}/{}", self.server, self.uri)
+    }
 }
 
 impl ContextManager {
@@ -42,8 +90,10 @@ impl ContextManager {
         let paths = agent
             .resources
             .iter()
-            .filter(|resource| resource.starts_with("file://"))
-            .map(|s| s.trim_start_matches("file://").to_string())
+            .filter_map(|resource| match resource.strip_prefix("file://") {
+                Some(path) => Some(path.to_string()),
+                None => url_context::is_url(resource).then(|| resource.clone()),
+            })
             .collect::<Vec<_>>();
 
         Ok(Self {
@@ -52,6 +102,10 @@ impl ContextManager {
             paths,
             hooks: agent.hooks.clone(),
             hook_executor: HookExecutor::new(),
+            content_filter: agent.content_filter.clone(),
+            resources: Vec::new(),
+            egress: agent.egress.clone(),
+            roots: Vec::new(),
         })
     }
 
@@ -60,23 +114,55 @@ impl ContextManager {
     /// # Arguments
     /// * `paths` - List of paths to add
     /// * `force` - If true, skip validation that the path exists
+    /// * `recursive` - If true, directories are added with the files of their subdirectories, as
+    ///   `<dir>/**`
     ///
     /// # Returns
-    /// A Result indicating success or an error
-    pub async fn add_paths(&mut self, os: &Os, paths: Vec<String>, force: bool) -> Result<()> {
-        // Validate paths exist before adding them
-        if !force {
-            let mut context_files = Vec::new();
+    /// The rules that were added, along with the number of files each matches, which is None if it
+    /// wasn't validated
+    pub async fn add_paths(
+        &mut self,
+        os: &Os,
+        paths: Vec<String>,
+        force: bool,
+        recursive: bool,
+    ) -> Result<Vec<(String, Option<usize>)>> {
+        let cwd = os.env.current_dir()?;
+        let paths = paths
+            .into_iter()
+            .map(|path| {
+                let is_dir = resolve_path(os, &cwd, &path).is_ok_and(|full_path| Path::new(&full_path).is_dir());
+                // `**` on its own only matches directories
+                if recursive && is_dir {
+                    format!("{}/**/*", path.trim_end_matches('/'))
+                } else {
+                    path
+                }
+            })
+            .collect::<Vec<_>>();
 
-            // Check each path to make sure it exists or matches at least one file
-            for path in &paths {
+        // Validate paths exist before adding them
+        let mut added = Vec::new();
+        for path in &paths {
+            let matched = if force {
+                None
+            } else {
                 // We're using a temporary context_files vector just for validation
                 // Pass is_validation=true to ensure we error if glob patterns don't match any files
-                match process_path(os, path, &mut context_files, true).await {
-                    Ok(_) => {}, // Path is valid
+                let mut context_files = Vec::new();
+                let result = if url_context::is_url(path) {
+                    url_context::read(os, path, self.resolve_egress(os), true)
+                        .await
+                        .map(|_| 1)
+                } else {
+                    process_path(os, &cwd, path, &mut context_files, true).await
+                };
+                match result {
+                    Ok(matched) => Some(matched),
                     Err(e) => return Err(eyre!("Invalid path '{}': {}. Use --force to add anyway.", path, e)),
                 }
-            }
+            };
+            added.push((path.clone(), matched));
         }
 
         // Add each path, checking for duplicates
@@ -87,22 +173,30 @@ impl ContextManager {
             self.paths.push(path);
         }
 
-        Ok(())
+        Ok(added)
+    }
+
+    /// Adds a resource of an MCP server to the context, replacing the one added before under the
+    /// same name.
+    pub fn add_resource(&mut self, resource: ContextResource) {
+        self.resources.retain(|r| r.name() != resource.name());
+        self.resources.push(resource);
     }
 
     /// Remove paths from the context configuration.
     ///
     /// # Arguments
-    /// * `paths` - List of paths to remove
+    /// * `paths` - List of paths or `<server>/<uri>` resource names to remove
     ///
     /// # Returns
     /// A Result indicating success or an error
     pub fn remove_paths(&mut self, paths: Vec<String>) -> Result<()> {
         // Remove each path if it exists
-        let old_path_num = self.paths.len();
+        let old_num = self.paths.len() + self.resources.len();
         self.paths.retain(|p| !paths.contains(p));
+        self.resources.retain(|r| !paths.contains(&r.name()));
 
-        if old_path_num == self.paths.len() {
+        if old_num == self.paths.len() + self.resources.len() {
             return Err(eyre!("None of the specified paths were found in the context"));
         }
 
@@ -112,6 +206,7 @@ impl ContextManager {
     /// Clear all paths from the context configuration.
     pub fn clear(&mut self) {
         self.paths.clear();
+        self.resources.clear();
     }
 
     /// Get all context files (global + profile-specific).
@@ -129,6 +224,11 @@ impl ContextManager {
         let mut context_files = Vec::new();
 
         self.collect_context_files(os, &self.paths, &mut context_files).await?;
+        context_files.extend(
+            self.resources
+                .iter()
+                .map(|resource| (resource.name(), resource.content.clone())),
+        );
 
         context_files.sort_by(|a, b| a.0.cmp(&b.0));
         context_files.dedup_by(|a, b| a.0 == b.0);
@@ -136,25 +236,66 @@ impl ContextManager {
         Ok(context_files)
     }
 
+    /// Returns the context paths that don't match any file. Web documents are not checked.
+    pub async fn unmatched_paths(&self, os: &Os) -> Vec<String> {
+        let mut unmatched = Vec::new();
+        for path in &self.paths {
+            if url_context::is_url(path) {
+                continue;
+            }
+            if !self
+                .get_context_files_by_path(os, path)
+                .await
+                .is_ok_and(|files| !files.is_empty())
+            {
+                unmatched.push(path.clone());
+            }
+        }
+        unmatched
+    }
+
     pub async fn get_context_files_by_path(&self, os: &Os, path: &str) -> Result<Vec<(String, String)>> {
         let mut context_files = Vec::new();
-        process_path(os, path, &mut context_files, true).await?;
+        if url_context::is_url(path) {
+            let content = url_context::read(os, path, self.resolve_egress(os), false).await?;
+            context_files.push((path.to_string(), content));
+        } else {
+            process_path(os, &os.env.current_dir()?, path, &mut context_files, true).await?;
+        }
+        self.remove_blocked_files(os, &mut context_files);
         Ok(context_files)
     }
 
-    /// Collects context files and optionally drops files if the total size exceeds the limit.
+    /// The tokens the context files may take up, which is `chat.contextBudgetTokens` if set.
+    pub fn token_budget(&self, os: &Os) -> usize {
+        os.database
+            .settings
+            .get_int(Setting::ChatContextBudgetTokens)
+            .and_then(|tokens| usize::try_from(tokens).ok())
+            .unwrap_or(self.max_context_files_size)
+    }
+
+    /// Collects the context files and fits them into the [token budget](Self::token_budget), see
+    /// [apply_context_budget].
+    pub async fn collect_budgeted_context_files(&self, os: &Os) -> Result<Vec<BudgetedFile>> {
+        let files = self.get_context_files(os).await?;
+        Ok(apply_context_budget(files, self.token_budget(os)))
+    }
+
+    /// Collects context files, truncating or dropping them if they exceed the token budget.
     /// Returns (files_to_use, dropped_files)
     pub async fn collect_context_files_with_limit(
         &self,
         os: &Os,
     ) -> Result<(Vec<(String, String)>, Vec<(String, String)>)> {
-        let mut files = self.get_context_files(os).await?;
-
-        let dropped_files = drop_matched_context_files(&mut files, self.max_context_files_size).unwrap_or_default();
-
-        // remove dropped files from files
-        files.retain(|file| !dropped_files.iter().any(|dropped| dropped.0 == file.0));
-
+        let mut files = Vec::new();
+        let mut dropped_files = Vec::new();
+        for file in self.collect_budgeted_context_files(os).await? {
+            match file.outcome {
+                BudgetOutcome::Dropped => dropped_files.push((file.name, file.content)),
+                _ => files.push((file.name, file.content)),
+            }
+        }
         Ok((files, dropped_files))
     }
 
@@ -164,13 +305,50 @@ impl ContextManager {
         paths: &[String],
         context_files: &mut Vec<(String, String)>,
     ) -> Result<()> {
+        let cwd = os.env.current_dir()?;
         for path in paths {
+            if url_context::is_url(path) {
+                match url_context::read(os, path, self.resolve_egress(os), false).await {
+                    Ok(content) => context_files.push((path.clone(), content)),
+                    Err(err) => warn!(?err, "Not adding {path} to the context because it can't be fetched"),
+                }
+                continue;
+            }
             // Use is_validation=false to handle non-matching globs gracefully
-            process_path(os, path, context_files, false).await?;
+            process_path(os, &cwd, path, context_files, false).await?;
+            if !path.starts_with(['/', '~']) {
+                for root in &self.roots {
+                    process_path(os, root, path, context_files, false).await?;
+                }
+            }
         }
+        self.remove_blocked_files(os, context_files);
         Ok(())
     }
 
+    /// The egress policies web documents are fetched within, the agent's and the user's.
+    fn resolve_egress(&self, os: &Os) -> Egress {
+        Egress::resolve(&os.database.settings, Some(&self.egress))
+    }
+
+    /// Removes the files that are blocked by the content filter of the agent or the user's
+    /// settings.
+    fn remove_blocked_files(&self, os: &Os, context_files: &mut Vec<(String, String)>) {
+        let filter = ContentFilter::resolve(&os.database.settings, Some(&self.content_filter));
+        if filter.is_empty() {
+            return;
+        }
+        context_files.retain(|(path, content)| {
+            match filter.blocked_reason(Path::new(path), Some(content.len() as u64)) {
+                Some(reason) => {
+                    warn!("Not adding {path} to the context because {reason}");
+                    false
+                },
+                None => true,
+            }
+        });
+    }
+
     /// Run all the currently enabled hooks from both the global and profile contexts.
     /// # Returns
     /// A vector containing pairs of a [`Hook`] definition and its execution output
@@ -179,73 +357,102 @@ impl ContextManager {
         output: &mut impl Write,
         prompt: Option<&str>,
     ) -> Result<Vec<((HookTrigger, Hook), String)>, ChatError> {
-        self.hook_executor.run_hooks(self.hooks.clone(), output, prompt).await
+        let hooks = self
+            .hooks
+            .iter()
+            .filter(|(trigger, _)| trigger.runs_with_prompt())
+            .map(|(trigger, hooks)| (*trigger, hooks.clone()))
+            .collect();
+        self.hook_executor.run_hooks(hooks, output, prompt).await
+    }
+
+    /// Runs the hooks of an event that isn't a prompt, such as a tool use, with `input` on their
+    /// stdin. Returns the output of those that inject it into the context.
+    pub async fn run_event_hooks(
+        &self,
+        trigger: HookTrigger,
+        input: &serde_json::Value,
+        output: &mut impl Write,
+    ) -> Result<Vec<String>, ChatError> {
+        match self.hooks.get(&trigger) {
+            Some(hooks) if !hooks.is_empty() => self.hook_executor.run_event_hooks(trigger, hooks, input, output).await,
+            _ => Ok(vec![]),
+        }
     }
 }
 
+/// Expands `~` to the home directory and resolves `path` against `base` if it is relative,
+/// returning the path on disk.
+pub fn resolve_path(os: &Os, base: &Path, path: &str) -> Result<String> {
+    // Expand ~ to home directory
+    let expanded_path = if path.starts_with('~') {
+        if let Some(home_dir) = os.env.home() {
+            home_dir.join(&path[2..]).to_string_lossy().to_string()
+        } else {
+            return Err(eyre!("Could not determine home directory"));
+        }
+    } else {
+        path.to_string()
+    };
+
+    // Handle absolute, relative paths, and glob patterns
+    let full_path = if expanded_path.starts_with('/') {
+        expanded_path
+    } else {
+        base.join(&expanded_path).to_string_lossy().to_string()
+    };
+
+    // Required in chroot testing scenarios so that we can use `Path::exists`.
+    Ok(os.fs.chroot_path_str(full_path))
+}
+
 /// Process a path, handling glob patterns and file types.
 ///
 /// This method:
-/// 1. Expands the path (handling ~ for home directory)
+/// 1. Expands the path (handling ~ for home directory), or resolves it against `base` if relative
 /// 2. If the path contains glob patterns, expands them
 /// 3. For each resulting path, adds the file to the context collection
 /// 4. Handles directories by including all files in the directory (non-recursive)
 /// 5. With force=true, includes paths that don't exist yet
 ///
+/// Files found by expanding a glob pattern or a directory are left out if they are ignored by
+/// `.gitignore` files or aren't text, and at most [MAX_FILES_PER_PATH] of them are added.
+///
 /// # Arguments
+/// * `base` - The directory relative paths are resolved against
 /// * `path` - The path to process
 /// * `context_files` - The collection to add files to
 /// * `is_validation` - If true, error when glob patterns don't match; if false, silently skip
 ///
 /// # Returns
-/// A Result indicating success or an error
+/// The number of files added, or an error
 async fn process_path(
     os: &Os,
+    base: &Path,
     path: &str,
     context_files: &mut Vec<(String, String)>,
     is_validation: bool,
-) -> Result<()> {
-    // Expand ~ to home directory
-    let expanded_path = if path.starts_with('~') {
-        if let Some(home_dir) = os.env.home() {
-            home_dir.join(&path[2..]).to_string_lossy().to_string()
-        } else {
-            return Err(eyre!("Could not determine home directory"));
-        }
-    } else {
-        path.to_string()
-    };
-
-    // Handle absolute, relative paths, and glob patterns
-    let full_path = if expanded_path.starts_with('/') {
-        expanded_path
-    } else {
-        os.env.current_dir()?.join(&expanded_path).to_string_lossy().to_string()
-    };
-
-    // Required in chroot testing scenarios so that we can use `Path::exists`.
-    let full_path = os.fs.chroot_path_str(full_path);
+) -> Result<usize> {
+    let full_path = resolve_path(os, base, path)?;
+    let mut matches = MatchedFiles::default();
 
     // Check if the path contains glob patterns
     if full_path.contains('*') || full_path.contains('?') || full_path.contains('[') {
         // Expand glob pattern
         match glob(&full_path) {
             Ok(entries) => {
-                let mut found_any = false;
-
                 for entry in entries {
                     match entry {
                         Ok(path) => {
-                            if path.is_file() {
-                                add_file_to_context(os, &path, context_files).await?;
-                                found_any = true;
+                            if path.is_file() && !matches.add(os, path, context_files).await {
+                                break;
                             }
                         },
                         Err(e) => return Err(eyre!("Glob error: {}", e)),
                     }
                 }
 
-                if !found_any && is_validation {
+                if matches.added == 0 && is_validation {
                     // When validating paths (e.g., for /context add), error if no files match
                     return Err(eyre!("No files found matching glob pattern '{}'", full_path));
                 }
@@ -260,13 +467,18 @@ async fn process_path(
         if path.exists() {
             if path.is_file() {
                 add_file_to_context(os, path, context_files).await?;
+                return Ok(1);
             } else if path.is_dir() {
                 // For directories, add all files in the directory (non-recursive)
-                let mut read_dir = os.fs.read_dir(path).await?;
+                let mut entries = Vec::new();
+                let mut read_dir = tokio::fs::read_dir(path).await?;
                 while let Some(entry) = read_dir.next_entry().await? {
-                    let path = entry.path();
-                    if path.is_file() {
-                        add_file_to_context(os, &path, context_files).await?;
+                    entries.push(entry.path());
+                }
+                entries.sort();
+                for path in entries {
+                    if path.is_file() && !matches.add(os, path, context_files).await {
+                        break;
                     }
                 }
             }
@@ -276,7 +488,37 @@ async fn process_path(
         }
     }
 
-    Ok(())
+    if matches.capped {
+        warn!("Only the first {MAX_FILES_PER_PATH} files matching '{path}' are added to the context");
+    }
+    Ok(matches.added)
+}
+
+/// The files a glob pattern or directory expands to.
+#[derive(Debug, Default)]
+struct MatchedFiles {
+    gitignores: GitignoreCache,
+    added: usize,
+    capped: bool,
+}
+
+impl MatchedFiles {
+    /// Adds the file at `path` unless it is ignored or isn't text, returning false once
+    /// [MAX_FILES_PER_PATH] files were added.
+    async fn add(&mut self, os: &Os, path: PathBuf, context_files: &mut Vec<(String, String)>) -> bool {
+        if self.gitignores.is_ignored(&path).await {
+            return true;
+        }
+        if self.added == MAX_FILES_PER_PATH {
+            self.capped = true;
+            return false;
+        }
+        match add_file_to_context(os, &path, context_files).await {
+            Ok(()) => self.added += 1,
+            Err(err) => warn!(?err, "Not adding {} to the context", path.display()),
+        }
+        true
+    }
 }
 
 /// Add a file to the context collection.
@@ -311,7 +553,9 @@ mod tests {
         os.fs.create_dir_all("test").await?;
         os.fs.write("test/to-include.md", "ha").await?;
         os.fs.write("test/to-drop.md", "long content that exceed limit").await?;
-        manager.add_paths(&os, vec!["test/*.md".to_string()], false).await?;
+        manager
+            .add_paths(&os, vec!["test/*.md".to_string()], false, false)
+            .await?;
 
         let (used, dropped) = manager.collect_context_files_with_limit(&os).await.unwrap();
 
@@ -321,6 +565,27 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_content_filter_removes_blocked_files() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        let mut manager = create_test_context_manager(None).expect("Failed to create test context manager");
+        manager.content_filter = ContentFilter {
+            denied_extensions: vec!["pem".to_string(), "env".to_string()],
+            ..Default::default()
+        };
+
+        os.fs.create_dir_all("test").await?;
+        os.fs.write("test/notes.md", "notes").await?;
+        os.fs.write("test/.env", "SECRET=1").await?;
+        os.fs.write("test/cert.pem", "cert").await?;
+        manager.add_paths(&os, vec!["test/*".to_string()], false, false).await?;
+
+        let files = manager.get_context_files(&os).await?;
+        assert_eq!(files.len(), 1);
+        assert!(files[0].0.ends_with("notes.md"));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_path_ops() -> Result<()> {
         let os = Os::new().await.unwrap();
@@ -336,7 +601,9 @@ mod tests {
             "no files should be returned for an empty profile when force is false"
         );
 
-        manager.add_paths(&os, vec!["test/*.md".to_string()], false).await?;
+        manager
+            .add_paths(&os, vec!["test/*.md".to_string()], false, false)
+            .await?;
         let files = manager.get_context_files(&os).await?;
         assert!(files[0].0.ends_with("p1.md"));
         assert_eq!(files[0].1, "p1");
@@ -345,7 +612,7 @@ mod tests {
 
         assert!(
             manager
-                .add_paths(&os, vec!["test/*.txt".to_string()], false)
+                .add_paths(&os, vec!["test/*.txt".to_string()], false, false)
                 .await
                 .is_err(),
             "adding a glob with no matching and without force should fail"
@@ -353,4 +620,77 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_glob_and_directory_paths() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("repo/.git").await?;
+        os.fs.create_dir_all("repo/src/cli").await?;
+        os.fs.create_dir_all("repo/target").await?;
+        os.fs.write("repo/.gitignore", "target/\n").await?;
+        os.fs.write("repo/src/main.rs", "main").await?;
+        os.fs.write("repo/src/cli/mod.rs", "cli").await?;
+        os.fs.write("repo/target/build.rs", "build").await?;
+
+        // Files ignored by .gitignore aren't matched
+        let mut manager = create_test_context_manager(None).expect("Failed to create test context manager");
+        let added = manager
+            .add_paths(&os, vec!["repo/**/*.rs".to_string()], false, false)
+            .await?;
+        assert_eq!(added, [("repo/**/*.rs".to_string(), Some(2))]);
+
+        // Directories are added with their subdirectories as a glob pattern
+        let mut manager = create_test_context_manager(None).expect("Failed to create test context manager");
+        let added = manager
+            .add_paths(&os, vec!["repo/src/".to_string()], false, true)
+            .await?;
+        assert_eq!(added, [("repo/src/**/*".to_string(), Some(2))]);
+        let files = manager.get_context_files(&os).await?;
+        let contents = files.iter().map(|(_, content)| content.as_str()).collect::<Vec<_>>();
+        assert_eq!(contents, ["cli", "main"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_workspace_roots() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        let mut manager = create_test_context_manager(None).expect("Failed to create test context manager");
+        manager.paths = vec!["AmazonQ.md".to_string(), "/shared/style.md".to_string()];
+
+        os.fs.create_dir_all("/infra").await?;
+        os.fs.create_dir_all("/shared").await?;
+        os.fs.write("/AmazonQ.md", "app").await?;
+        os.fs.write("/infra/AmazonQ.md", "infra").await?;
+        os.fs.write("/shared/style.md", "style").await?;
+        assert_eq!(manager.get_context_files(&os).await?.len(), 2);
+
+        // Relative paths are looked up in every root, absolute ones once
+        manager.roots = vec![PathBuf::from("/infra"), PathBuf::from("/shared")];
+        let files = manager.get_context_files(&os).await?;
+        let contents = files.iter().map(|(_, content)| content.as_str()).collect::<Vec<_>>();
+        assert_eq!(contents, ["app", "infra", "style"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resource_ops() -> Result<()> {
+        let os = Os::new().await.unwrap();
+        let mut manager = create_test_context_manager(None).expect("Failed to create test context manager");
+        let resource = |content: &str| ContextResource {
+            server: "docs".to_string(),
+            uri: "file:///guide.md".to_string(),
+            content: content.to_string(),
+        };
+
+        manager.add_resource(resource("old"));
+        manager.add_resource(resource("new"));
+        let files = manager.get_context_files(&os).await?;
+        assert_eq!(files, vec![("docs/file:///guide.md".to_string(), "new".to_string())]);
+
+        manager.remove_paths(vec!["docs/file:///guide.md".to_string()])?;
+        assert!(manager.get_context_files(&os).await?.is_empty());
+        assert!(manager.remove_paths(vec!["docs/file:///guide.md".to_string()]).is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,245 @@
+//! Strategies deciding which part of the conversation history is sent with each request, on top
+//! of the limits in [super::conversation::ConversationState::enforce_conversation_invariants].
+//! They are selected per agent with the `historyStrategy` field, or with `chat.historyStrategy`.
+//!
+//! The strategies count turns rather than messages: a turn is a prompt of the user along with the
+//! tool uses and responses that follow it, so that a tool use is never sent without its results.
+
+use std::collections::VecDeque;
+
+use super::cli::compact::CompactStrategy;
+use super::message::{
+    AssistantMessage,
+    UserMessage,
+};
+use crate::cli::agent::Agent;
+use crate::cli::agent::history_strategy::{
+    DEFAULT_ANCHOR_TURNS,
+    DEFAULT_RECENT_TURNS,
+    HistoryStrategy,
+    HistoryStrategyKind,
+};
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+type History = VecDeque<(UserMessage, AssistantMessage)>;
+
+pub trait ContextStrategy {
+    /// Index of the oldest entry of `history` sent with the next request, given the range of it
+    /// that fits in a request.
+    fn history_start(&self, _history: &History, valid_range: (usize, usize)) -> usize {
+        valid_range.0
+    }
+
+    /// How many entries at the start of `history` are kept out of summaries.
+    fn anchor_len(&self, _history: &History) -> usize {
+        0
+    }
+
+    /// How the history should be summarized before the next request is sent, if it should be.
+    fn compaction(&self, _history: &History) -> Option<CompactStrategy> {
+        None
+    }
+}
+
+/// Sends the latest turns, or as much of the history as fits if their number isn't set.
+#[derive(Debug)]
+pub struct SlidingWindow {
+    recent_turns: Option<usize>,
+}
+
+impl ContextStrategy for SlidingWindow {
+    fn history_start(&self, history: &History, valid_range: (usize, usize)) -> usize {
+        let Some(recent_turns) = self.recent_turns else {
+            return valid_range.0;
+        };
+        let starts = turn_starts(history)
+            .into_iter()
+            .filter(|i| (valid_range.0..valid_range.1).contains(i))
+            .collect::<Vec<_>>();
+        match starts.len().checked_sub(recent_turns) {
+            Some(i) => starts.get(i).copied().unwrap_or(valid_range.1),
+            None => valid_range.0,
+        }
+    }
+}
+
+/// Replaces the turns older than the latest ones with a summary, once as many turns have fallen
+/// out of the window as it holds, so that the history isn't summarized before every request.
+#[derive(Debug)]
+pub struct Summarize {
+    recent_turns: usize,
+}
+
+impl ContextStrategy for Summarize {
+    fn compaction(&self, history: &History) -> Option<CompactStrategy> {
+        summarize_between(history, 0, self.recent_turns)
+    }
+}
+
+/// Like [Summarize], but keeps the first turns as they are since they usually describe the task.
+#[derive(Debug)]
+pub struct Hybrid {
+    anchor_turns: usize,
+    recent_turns: usize,
+}
+
+impl ContextStrategy for Hybrid {
+    fn anchor_len(&self, history: &History) -> usize {
+        turn_starts(history)
+            .get(self.anchor_turns)
+            .copied()
+            .unwrap_or(history.len())
+    }
+
+    fn compaction(&self, history: &History) -> Option<CompactStrategy> {
+        summarize_between(history, self.anchor_turns, self.recent_turns)
+    }
+}
+
+/// The strategy of `agent`, or the one selected with `chat.historyStrategy` if it doesn't set
+/// one.
+pub fn context_strategy(agent: Option<&Agent>, settings: &Settings) -> Box<dyn ContextStrategy> {
+    let config = agent.and_then(|agent| agent.history_strategy).unwrap_or_else(|| {
+        settings
+            .get_string(Setting::ChatHistoryStrategy)
+            .as_deref()
+            .and_then(HistoryStrategyKind::parse)
+            .unwrap_or_default()
+            .into()
+    });
+    from_config(config)
+}
+
+fn from_config(config: HistoryStrategy) -> Box<dyn ContextStrategy> {
+    let recent_turns = config.recent_turns.unwrap_or(DEFAULT_RECENT_TURNS);
+    match config.kind {
+        HistoryStrategyKind::Sliding => Box::new(SlidingWindow {
+            recent_turns: config.recent_turns,
+        }),
+        HistoryStrategyKind::Summarize => Box::new(Summarize { recent_turns }),
+        HistoryStrategyKind::Hybrid => Box::new(Hybrid {
+            anchor_turns: config.anchor_turns.unwrap_or(DEFAULT_ANCHOR_TURNS),
+            recent_turns,
+        }),
+    }
+}
+
+/// Indices of the entries of `history` that start a turn, i.e. user messages without tool
+/// results.
+fn turn_starts(history: &History) -> Vec<usize> {
+    history
+        .iter()
+        .enumerate()
+        .filter(|(_, (user, _))| !user.has_tool_use_results())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Summarizes the turns between the first `anchor_turns` and the latest `recent_turns`, once there
+/// are at least `recent_turns` of them.
+fn summarize_between(history: &History, anchor_turns: usize, recent_turns: usize) -> Option<CompactStrategy> {
+    let starts = turn_starts(history);
+    let older_turns = starts.len().saturating_sub(anchor_turns + recent_turns);
+    if older_turns < recent_turns.max(1) {
+        return None;
+    }
+
+    let anchor_end = starts.get(anchor_turns).copied().unwrap_or(history.len());
+    let recent_start = starts
+        .get(starts.len() - recent_turns)
+        .copied()
+        .unwrap_or(history.len());
+    Some(CompactStrategy {
+        messages_to_exclude: history.len() - recent_start,
+        anchor_messages: anchor_end,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A history of `turns` turns, each a prompt followed by a tool use and its results.
+    fn history(turns: usize) -> History {
+        (0..turns)
+            .flat_map(|i| {
+                [
+                    (
+                        UserMessage::new_prompt(format!("prompt {i}")),
+                        AssistantMessage::new_tool_use(None, String::new(), vec![]),
+                    ),
+                    (
+                        UserMessage::new_tool_use_results(vec![]),
+                        AssistantMessage::new_response(None, format!("response {i}")),
+                    ),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let history = history(6);
+        let unbounded = from_config(HistoryStrategyKind::Sliding.into());
+        assert_eq!(unbounded.history_start(&history, (2, 12)), 2);
+        assert!(unbounded.compaction(&history).is_none());
+
+        let sliding = from_config(HistoryStrategy {
+            kind: HistoryStrategyKind::Sliding,
+            recent_turns: Some(2),
+            anchor_turns: None,
+        });
+        assert_eq!(sliding.history_start(&history, (0, 12)), 8);
+        assert_eq!(sliding.history_start(&history, (10, 12)), 10);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let summarize = from_config(HistoryStrategy {
+            kind: HistoryStrategyKind::Summarize,
+            recent_turns: Some(2),
+            anchor_turns: None,
+        });
+        assert!(summarize.compaction(&history(3)).is_none());
+        let strategy = summarize.compaction(&history(4)).unwrap();
+        assert_eq!(strategy.anchor_messages, 0);
+        assert_eq!(strategy.messages_to_exclude, 4);
+        assert_eq!(summarize.history_start(&history(4), (0, 8)), 0);
+    }
+
+    #[test]
+    fn test_hybrid() {
+        let hybrid = from_config(HistoryStrategy {
+            kind: HistoryStrategyKind::Hybrid,
+            recent_turns: Some(2),
+            anchor_turns: None,
+        });
+        assert_eq!(hybrid.anchor_len(&history(4)), 2);
+        assert_eq!(hybrid.anchor_len(&history(0)), 0);
+        assert!(hybrid.compaction(&history(4)).is_none());
+        let strategy = hybrid.compaction(&history(5)).unwrap();
+        assert_eq!(strategy.anchor_messages, 2);
+        assert_eq!(strategy.messages_to_exclude, 4);
+    }
+
+    #[tokio::test]
+    async fn test_context_strategy() {
+        let mut settings = Settings::new().await.unwrap();
+        settings.set(Setting::ChatHistoryStrategy, "summarize").await.unwrap();
+        assert!(context_strategy(None, &settings).compaction(&history(20)).is_some());
+
+        let agent = Agent {
+            history_strategy: Some(HistoryStrategyKind::Sliding.into()),
+            ..Default::default()
+        };
+        assert!(
+            context_strategy(Some(&agent), &settings)
+                .compaction(&history(20))
+                .is_none()
+        );
+    }
+}
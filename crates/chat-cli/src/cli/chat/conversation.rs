@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::collections::{
     HashMap,
     HashSet,
@@ -23,23 +24,29 @@ use tracing::{
 use super::cli::compact::CompactStrategy;
 use super::consts::{
     DUMMY_TOOL_NAME,
-    MAX_CHARS,
     MAX_CONVERSATION_STATE_HISTORY_LEN,
 };
 use super::context::ContextManager;
+use super::context_strategy::{
+    self,
+    ContextStrategy,
+};
 use super::message::{
     AssistantMessage,
     ToolUseResult,
     UserMessage,
+    UserMessageContent,
 };
+use super::model_registry::models;
 use super::token_counter::{
     CharCount,
     CharCounter,
+    TokenCount,
 };
 use super::tool_manager::ToolManager;
+use super::tools::manage_todo::TodoList;
 use super::tools::{
     InputSchema,
-    QueuedTool,
     ToolOrigin,
     ToolSpec,
 };
@@ -64,6 +71,27 @@ use crate::os::Os;
 
 const CONTEXT_ENTRY_START_HEADER: &str = "--- CONTEXT ENTRY BEGIN ---\n";
 const CONTEXT_ENTRY_END_HEADER: &str = "--- CONTEXT ENTRY END ---\n\n";
+const INTERRUPTED_RESPONSE_NOTE: &str = "[The response was interrupted before it completed]";
+/// How much of the context window a conversation uses before it is warned about.
+const CONTEXT_WARNING_PERCENT: usize = 80;
+
+/// A turn that was still in flight when last journaled, persisted so that it can be recovered if
+/// the session ends before the response completes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterruptedTurn {
+    conversation_id: String,
+    /// Length of the history when the turn started.
+    history_len: usize,
+    user_message: UserMessage,
+    /// Assistant text received so far.
+    partial_response: String,
+}
+
+impl InterruptedTurn {
+    pub fn partial_response(&self) -> &str {
+        &self.partial_response
+    }
+}
 
 /// Tracks state related to an ongoing conversation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -96,6 +124,13 @@ pub struct ConversationState {
     /// Model explicitly selected by the user in this conversation state via `/model`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
+    /// Task list maintained by the model with the `manage_todo` tool and by the user with `/todo`.
+    #[serde(default)]
+    pub todo_list: TodoList,
+    /// Set with `q chat --incognito` or `/incognito`. Nothing is saved while it is, and the turns
+    /// made meanwhile stay ephemeral once it is turned off.
+    #[serde(skip)]
+    pub incognito: bool,
 }
 
 impl ConversationState {
@@ -137,6 +172,8 @@ impl ConversationState {
             latest_summary: None,
             agents,
             model: current_model_id,
+            todo_list: TodoList::default(),
+            incognito: false,
         }
     }
 
@@ -152,6 +189,7 @@ impl ConversationState {
     pub fn clear(&mut self, preserve_summary: bool) {
         self.next_message = None;
         self.history.clear();
+        self.todo_list.clear();
         if !preserve_summary {
             self.latest_summary = None;
         }
@@ -214,14 +252,89 @@ impl ConversationState {
     /// Sets the response message according to the currently set [Self::next_message].
     pub fn push_assistant_message(&mut self, os: &mut Os, message: AssistantMessage) {
         debug_assert!(self.next_message.is_some(), "next_message should exist");
-        let next_user_message = self.next_message.take().expect("next user message should exist");
+        let mut next_user_message = self.next_message.take().expect("next user message should exist");
+        next_user_message.ephemeral |= self.incognito;
 
         self.append_assistant_transcript(&message);
         self.history.push_back((next_user_message, message));
+        self.save(os);
+    }
 
+    /// Saves the conversation so that it can be resumed, leaving out its ephemeral turns. Nothing
+    /// is saved in incognito mode.
+    pub fn save(&self, os: &mut Os) {
+        if self.incognito {
+            return;
+        }
         if let Ok(cwd) = std::env::current_dir() {
-            os.database.set_conversation_by_path(cwd, self).ok();
+            os.database
+                .set_conversation_by_path(cwd, &self.without_ephemeral_turns())
+                .ok();
+        }
+    }
+
+    /// Marks the last `turns` turns, each a prompt along with the tool uses that answered it, as
+    /// ephemeral. Returns how many turns were marked, which is less than `turns` if the history is
+    /// shorter.
+    pub fn mark_ephemeral(&mut self, turns: usize) -> usize {
+        let mut marked = 0;
+        for (user, _) in self.history.iter_mut().rev() {
+            if marked == turns {
+                break;
+            }
+            user.ephemeral = true;
+            if let UserMessageContent::Prompt { .. } = user.content {
+                marked += 1;
+            }
         }
+        marked
+    }
+
+    /// The conversation as it is saved, without the ephemeral turns or their transcript entries.
+    fn without_ephemeral_turns(&self) -> Cow<'_, Self> {
+        if !self.history.iter().any(|(user, _)| user.ephemeral) {
+            return Cow::Borrowed(self);
+        }
+        let ephemeral_entries = self
+            .history
+            .iter()
+            .filter(|(user, _)| user.ephemeral)
+            .map(|(_, assistant)| assistant_transcript_entry(assistant))
+            .collect::<HashSet<_>>();
+        let mut state = self.clone();
+        state.history.retain(|(user, _)| !user.ephemeral);
+        state.transcript.retain(|entry| !ephemeral_entries.contains(entry));
+        state.enforce_conversation_invariants();
+        Cow::Owned(state)
+    }
+
+    /// Snapshot of the turn in flight along with the response received so far. [None] if no user
+    /// message is pending.
+    pub fn interrupted_turn(&self, partial_response: &str) -> Option<InterruptedTurn> {
+        Some(InterruptedTurn {
+            conversation_id: self.conversation_id.clone(),
+            history_len: self.history.len(),
+            user_message: self.next_message.clone()?,
+            partial_response: partial_response.to_string(),
+        })
+    }
+
+    /// Whether `turn` was journaled from this conversation with no turns completed since.
+    pub fn is_interrupted_turn_of(&self, turn: &InterruptedTurn) -> bool {
+        turn.conversation_id == self.conversation_id && turn.history_len == self.history.len()
+    }
+
+    /// Restores a turn that was cut off, keeping the part of the response that was received.
+    pub fn recover_interrupted_turn(&mut self, os: &mut Os, turn: InterruptedTurn) {
+        if let Some(prompt) = turn.user_message.prompt() {
+            self.append_user_transcript(prompt);
+        }
+        self.next_message = Some(turn.user_message);
+        let content = match turn.partial_response.trim().is_empty() {
+            true => INTERRUPTED_RESPONSE_NOTE.to_string(),
+            false => format!("{}\n\n{INTERRUPTED_RESPONSE_NOTE}", turn.partial_response),
+        };
+        self.push_assistant_message(os, AssistantMessage::new_response(None, content));
     }
 
     /// Returns the conversation id.
@@ -275,11 +388,8 @@ impl ConversationState {
     }
 
     /// Sets the next user message with "cancelled" tool results.
-    pub fn abandon_tool_use(&mut self, tools_to_be_abandoned: &[QueuedTool], deny_input: String) {
-        self.next_message = Some(UserMessage::new_cancelled_tool_uses(
-            Some(deny_input),
-            tools_to_be_abandoned.iter().map(|t| t.id.as_str()),
-        ));
+    pub fn abandon_tool_use<'a>(&mut self, tool_use_ids: impl Iterator<Item = &'a str>, deny_input: String) {
+        self.next_message = Some(UserMessage::new_cancelled_tool_uses(Some(deny_input), tool_use_ids));
     }
 
     /// Returns a [FigConversationState] capable of being sent by [api_client::StreamingClient].
@@ -356,6 +466,9 @@ impl ConversationState {
     ) -> Result<BackendConversationState<'_>, ChatError> {
         self.update_state(false).await;
         self.enforce_conversation_invariants();
+        self.valid_history_range.0 = self
+            .context_strategy(os)
+            .history_start(&self.history, self.valid_history_range);
 
         // Run hooks and add to conversation start and next user message.
         let mut conversation_start_context = None;
@@ -387,6 +500,11 @@ impl ConversationState {
         })
     }
 
+    /// The [ContextStrategy] of the active agent, or the one selected with `chat.historyStrategy`.
+    pub fn context_strategy(&self, os: &Os) -> Box<dyn ContextStrategy> {
+        context_strategy::context_strategy(self.agents.get_active(), &os.database.settings)
+    }
+
     /// Returns a [FigConversationState] capable of replacing the history of the current
     /// conversation with a summary generated by the model.
     ///
@@ -456,6 +574,7 @@ impl ConversationState {
         // Create the history according to the passed compact strategy.
         let mut history = conv_state.history.cloned().collect::<VecDeque<_>>();
         history.drain((history.len().saturating_sub(strategy.messages_to_exclude))..);
+        history.drain(..strategy.anchor_messages.min(history.len()));
         if strategy.truncate_large_messages {
             for (user_message, _) in &mut history {
                 user_message.truncate_safe(strategy.max_message_length);
@@ -489,8 +608,8 @@ impl ConversationState {
     /// `strategy` - The [CompactStrategy] used for the corresponding
     /// [ConversationState::create_summary_request].
     pub fn replace_history_with_summary(&mut self, summary: String, strategy: CompactStrategy) {
-        self.history
-            .drain(..(self.history.len().saturating_sub(strategy.messages_to_exclude)));
+        let end = self.history.len().saturating_sub(strategy.messages_to_exclude);
+        self.history.drain(strategy.anchor_messages.min(end)..end);
         self.latest_summary = Some(summary);
     }
 
@@ -575,12 +694,29 @@ impl ConversationState {
             .char_count())
     }
 
-    /// Get the current token warning level
+    /// Estimates the input tokens of a request sent now, with the tool specifications.
+    pub async fn request_tokens(&mut self, os: &Os) -> Result<TokenCount, ChatError> {
+        let state = self.backend_conversation_state(os, false, &mut vec![]).await?;
+        let tools_chars: usize = state
+            .tools
+            .values()
+            .filter_map(|tools| serde_json::to_string(tools).ok())
+            .map(|json| json.len())
+            .sum();
+        Ok((state.char_count() + tools_chars.into()).into())
+    }
+
+    /// Get the current token warning level, based on the context window of the model
     pub async fn get_token_warning_level(&mut self, os: &Os) -> Result<TokenWarningLevel, ChatError> {
-        let total_chars = self.calculate_char_count(os).await?;
+        let tokens = self.request_tokens(os).await?.value();
+        let context_window = models().context_window(self.model.as_deref());
 
-        Ok(if *total_chars >= MAX_CHARS {
+        Ok(if tokens >= context_window {
             TokenWarningLevel::Critical
+        } else if tokens * 100 >= context_window * CONTEXT_WARNING_PERCENT {
+            TokenWarningLevel::High {
+                percent: tokens * 100 / context_window,
+            }
         } else {
             TokenWarningLevel::None
         })
@@ -591,10 +727,7 @@ impl ConversationState {
     }
 
     pub fn append_assistant_transcript(&mut self, message: &AssistantMessage) {
-        let tool_uses = message.tool_uses().map_or("none".to_string(), |tools| {
-            tools.iter().map(|tool| tool.name.clone()).collect::<Vec<_>>().join(",")
-        });
-        self.append_transcript(format!("{}\n[Tool uses: {tool_uses}]", message.content()));
+        self.append_transcript(assistant_transcript_entry(message));
     }
 
     pub fn append_transcript(&mut self, message: String) {
@@ -605,6 +738,13 @@ impl ConversationState {
     }
 }
 
+fn assistant_transcript_entry(message: &AssistantMessage) -> String {
+    let tool_uses = message.tool_uses().map_or("none".to_string(), |tools| {
+        tools.iter().map(|tool| tool.name.clone()).collect::<Vec<_>>().join(",")
+    });
+    format!("{}\n[Tool uses: {tool_uses}]", message.content())
+}
+
 /// Represents a conversation state that can be converted into a [FigConversationState] (the type
 /// used by the API client). Represents borrowed data, and reflects an exact [FigConversationState]
 /// that can be generated from [ConversationState] at any point in time.
@@ -708,7 +848,9 @@ where
 pub enum TokenWarningLevel {
     /// No warning, conversation is within normal limits
     None,
-    /// Critical level - at single warning threshold (600K characters)
+    /// Most of the context window is used
+    High { percent: usize },
+    /// The context window is full
     Critical,
 }
 
@@ -1008,6 +1150,75 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_recover_interrupted_turn() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap(),
+            tool_manager,
+            None,
+        )
+        .await;
+
+        assert!(conversation.interrupted_turn("").is_none());
+        conversation.set_next_user_message("hello".to_string()).await;
+        let turn = conversation.interrupted_turn("Hi, I was about").unwrap();
+        assert!(conversation.is_interrupted_turn_of(&turn));
+
+        // Simulate a fresh session resuming the persisted conversation.
+        conversation.reset_next_user_message();
+        conversation.recover_interrupted_turn(&mut os, turn.clone());
+        assert!(!conversation.is_interrupted_turn_of(&turn));
+        let (user, assistant) = conversation.history().back().unwrap();
+        assert_eq!(user.prompt(), Some("hello"));
+        assert!(assistant.content().starts_with("Hi, I was about"));
+        assert!(assistant.content().ends_with(INTERRUPTED_RESPONSE_NOTE));
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_turns() {
+        let mut os = Os::new().await.unwrap();
+        let mut tool_manager = ToolManager::default();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_manager.load_tools(&mut os, &mut vec![]).await.unwrap(),
+            tool_manager,
+            None,
+        )
+        .await;
+        let saved_prompts = |conversation: &ConversationState| {
+            let saved = conversation.without_ephemeral_turns();
+            saved
+                .history()
+                .iter()
+                .filter_map(|(user, _)| user.prompt().map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+
+        for prompt in ["first", "second", "third"] {
+            conversation.set_next_user_message(prompt.to_string()).await;
+            conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, format!("re: {prompt}")));
+        }
+        assert_eq!(conversation.mark_ephemeral(1), 1);
+        assert_eq!(saved_prompts(&conversation), ["first", "second"]);
+        assert_eq!(conversation.without_ephemeral_turns().transcript.len(), 2);
+        assert_eq!(conversation.history().len(), 3);
+
+        // Turns made in incognito mode stay out of the saved conversation.
+        conversation.incognito = true;
+        conversation.set_next_user_message("fourth".to_string()).await;
+        conversation.push_assistant_message(&mut os, AssistantMessage::new_response(None, "re: fourth".to_string()));
+        conversation.incognito = false;
+        assert_eq!(saved_prompts(&conversation), ["first", "second"]);
+
+        assert_eq!(conversation.mark_ephemeral(10), 4);
+        assert!(saved_prompts(&conversation).is_empty());
+    }
+
     #[tokio::test]
     async fn test_conversation_state_history_handling_with_tool_results() {
         let mut os = Os::new().await.unwrap();
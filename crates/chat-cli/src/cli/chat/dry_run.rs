@@ -0,0 +1,113 @@
+//! Dry runs, toggled with `/tools dry-run`. While one is on, tools that could change something do
+//! not run: writes return the diff they would apply and everything else a note that it was not
+//! run, so that an agent's plan can be reviewed before committing to it.
+
+use eyre::Result;
+use similar::TextDiff;
+
+use super::tools::{
+    InvokeOutput,
+    OutputKind,
+    Tool,
+    sanitize_path_tool_arg,
+};
+use crate::os::Os;
+
+/// Whether the tool is held back during a dry run. Commands and AWS calls that only read run as
+/// usual, while MCP tools, whose effects can't be known, never run.
+pub fn applies_to(tool: &Tool) -> bool {
+    match tool {
+        Tool::FsWrite(_) | Tool::Custom(_) | Tool::Knowledge(_) => true,
+        #[cfg(feature = "wasm-plugins")]
+        Tool::Wasm(_) => true,
+        Tool::ExecuteCommand(execute_command) => execute_command.requires_acceptance(None, true),
+        Tool::UseAws(use_aws) => use_aws.requires_acceptance(),
+        Tool::Git(git) => !git.is_read_only(),
+        Tool::FsRead(_)
+        | Tool::GhIssue(_)
+        | Tool::Thinking(_)
+        | Tool::ManageTodo(_)
+        | Tool::WebFetch(_)
+        | Tool::GrepSearch(_) => false,
+    }
+}
+
+/// The result returned to the model in place of running the tool.
+pub async fn preview(os: &Os, tool: &Tool) -> Result<InvokeOutput> {
+    let text = match tool {
+        Tool::FsWrite(fs_write) => {
+            let path = sanitize_path_tool_arg(os, fs_write.path());
+            let original = match os.fs.exists(&path) {
+                true => os.fs.read_to_string(&path).await?,
+                false => String::new(),
+            };
+            let proposed = fs_write.updated_content(original.clone())?;
+            let path = path.to_string_lossy();
+            let diff = TextDiff::from_lines(&original, &proposed)
+                .unified_diff()
+                .header(&path, &path)
+                .to_string();
+            format!("Dry run: {path} was not changed. The write would have applied this diff:\n\n{diff}")
+        },
+        Tool::ExecuteCommand(execute_command) => {
+            format!("Dry run: the command `{}` was not run.", execute_command.command)
+        },
+        tool => format!("Dry run: {} was not run.", tool.display_name()),
+    };
+
+    Ok(InvokeOutput {
+        output: OutputKind::Text(text),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::util::test::{
+        TEST_FILE_CONTENTS,
+        TEST_FILE_PATH,
+        setup_test_directory,
+    };
+
+    fn fs_write(args: serde_json::Value) -> Tool {
+        Tool::FsWrite(serde_json::from_value(args).unwrap())
+    }
+
+    fn execute_bash(command: &str) -> Tool {
+        Tool::ExecuteCommand(serde_json::from_value(serde_json::json!({ "command": command })).unwrap())
+    }
+
+    #[test]
+    fn test_applies_to() {
+        assert!(applies_to(&fs_write(serde_json::json!({
+            "command": "create",
+            "path": "/file.txt",
+            "file_text": "",
+        }))));
+        assert!(applies_to(&execute_bash("rm -rf target")));
+        assert!(!applies_to(&execute_bash("ls -la")));
+    }
+
+    #[tokio::test]
+    async fn test_preview() {
+        let os = setup_test_directory().await;
+        let replace = fs_write(serde_json::json!({
+            "command": "str_replace",
+            "path": TEST_FILE_PATH,
+            "old_str": "3: asdf",
+            "new_str": "3: qwerty",
+        }));
+        let output = preview(&os, &replace).await.unwrap();
+        assert!(output.as_str().contains("-3: asdf\n+3: qwerty\n"));
+        assert_eq!(os.fs.read_to_string(TEST_FILE_PATH).await.unwrap(), TEST_FILE_CONTENTS);
+
+        let create = fs_write(serde_json::json!({
+            "command": "create",
+            "path": "/new_file.txt",
+            "file_text": "hello",
+        }));
+        assert!(preview(&os, &create).await.unwrap().as_str().contains("+hello\n"));
+        assert!(!os.fs.exists("/new_file.txt"));
+    }
+}
@@ -0,0 +1,492 @@
+//! `q internal e2e`, which runs a suite of conversations through the chat loop against a mock
+//! backend, so that packagers can smoke test a build without credentials. A suite is a YAML file
+//! of cases, each with the responses of the backend and the steps of `q script`, e.g.
+//!
+//! ```yaml
+//! name: packaging
+//! cases:
+//!   - name: calls a tool of an MCP server
+//!     agent: { tools: ["*"] }
+//!     mcp_fixtures: [fixture]
+//!     responses:
+//!       - - tool_use_id: "1"
+//!           name: echo
+//!           args: { text: ping }
+//!       - [The server said ping]
+//!     steps:
+//!       - prompt: Echo ping
+//!         approve: [echo]
+//!         expect:
+//!           tools: [echo]
+//! ```
+//!
+//! Each case runs in an empty temporary directory, and the outcome of every case is written as a
+//! JUnit report.
+
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::ExitCode;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use clap::Args;
+use crossterm::style::Stylize;
+use eyre::{
+    Result,
+    bail,
+};
+use serde::Deserialize;
+use serde_json::{
+    Value,
+    json,
+};
+
+use super::oneshot;
+use super::script::{
+    ScriptStep,
+    check,
+    run_step,
+};
+use crate::cli::agent::{
+    Agent,
+    Agents,
+};
+use crate::mcp_client::{
+    JsonRpcRequest,
+    JsonRpcResponse,
+    PreServerRequestHandler,
+    Response,
+    Server,
+    ServerError,
+    ServerRequestHandler,
+};
+use crate::os::Os;
+
+/// The suite run unless another one is given.
+const BUILTIN_SUITE: &str = include_str!("e2e_suite.yaml");
+
+/// Run the chat loop against a mock backend and write a JUnit report, to smoke test a build
+/// without credentials
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct E2eArgs {
+    /// Path of a suite to run instead of the built-in one
+    suite: Option<PathBuf>,
+    /// Path to write the JUnit report to instead of stdout
+    #[arg(long)]
+    junit: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Suite {
+    #[serde(default = "default_suite_name")]
+    name: String,
+    /// Seconds each step may take, including the tools it runs
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+    cases: Vec<Case>,
+}
+
+fn default_suite_name() -> String {
+    "e2e".to_string()
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Case {
+    name: String,
+    /// Config of the agent the case runs with, as in the JSON file of an agent. Without one, the
+    /// agent is offered every tool
+    agent: Option<Value>,
+    /// Names of MCP servers added to the agent, each serving the tools of [McpFixture]
+    #[serde(default)]
+    mcp_fixtures: Vec<String>,
+    /// What the mock backend answers to each request, in the format of `Q_MOCK_CHAT_RESPONSE`: a
+    /// list of responses, each a list of texts and tool uses
+    responses: Value,
+    steps: Vec<ScriptStep>,
+}
+
+/// What happened while running a case.
+#[derive(Debug, Default)]
+struct CaseReport {
+    name: String,
+    duration: Duration,
+    /// Expectations of the steps that were not met
+    failures: Vec<String>,
+    /// Why the case could not run to the end
+    error: Option<String>,
+}
+
+impl E2eArgs {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        let suite = match &self.suite {
+            Some(path) => parse_suite(&os.fs.read_to_string(path).await?)?,
+            None => parse_suite(BUILTIN_SUITE)?,
+        };
+
+        let mut stderr = std::io::stderr();
+        let original_dir = std::env::current_dir()?;
+        let mut reports = Vec::new();
+        for case in &suite.cases {
+            writeln!(stderr, "{} {}", "▶".cyan(), case.name)?;
+            let workspace = tempfile::tempdir()?;
+            std::env::set_current_dir(workspace.path())?;
+            let start = Instant::now();
+            let mut report = match run_case(os, case, workspace.path(), suite.timeout).await {
+                Ok(failures) => CaseReport {
+                    failures,
+                    ..Default::default()
+                },
+                Err(err) => CaseReport {
+                    error: Some(format!("{err:#}")),
+                    ..Default::default()
+                },
+            };
+            report.name = case.name.clone();
+            report.duration = start.elapsed();
+            std::env::set_current_dir(&original_dir)?;
+
+            match (&report.error, report.failures.is_empty()) {
+                (None, true) => writeln!(stderr, "{} {}", "✔".green(), case.name)?,
+                (error, _) => {
+                    writeln!(stderr, "{} {}", "✘".red(), case.name)?;
+                    for failure in error.iter().chain(&report.failures) {
+                        writeln!(stderr, "    {failure}")?;
+                    }
+                },
+            }
+            reports.push(report);
+        }
+
+        let junit = junit_report(&suite.name, &reports);
+        match &self.junit {
+            Some(path) => os.fs.write(path, junit).await?,
+            None => print!("{junit}"),
+        }
+
+        let failed = reports
+            .iter()
+            .filter(|report| report.error.is_some() || !report.failures.is_empty())
+            .count();
+        if failed > 0 {
+            writeln!(stderr, "\n{failed} of {} case(s) failed", reports.len())?;
+            return Ok(ExitCode::FAILURE);
+        }
+        writeln!(stderr, "\n{} case(s) passed", reports.len())?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn parse_suite(contents: &str) -> Result<Suite> {
+    let suite: Suite = serde_yaml::from_str(contents)?;
+    if suite.cases.is_empty() {
+        bail!("The suite has no cases");
+    }
+    for case in &suite.cases {
+        if case.steps.is_empty() {
+            bail!("The case '{}' has no steps", case.name);
+        }
+        let is_response = |response: &Value| {
+            response
+                .as_array()
+                .is_some_and(|events| events.iter().all(|event| event.is_string() || is_tool_use(event)))
+        };
+        if !case.responses.as_array().is_some_and(|r| r.iter().all(is_response)) {
+            bail!(
+                "The responses of the case '{}' must be a list of responses, each a list of texts and tool uses",
+                case.name
+            );
+        }
+        if case.agent.as_ref().is_some_and(|agent| !agent.is_object()) {
+            bail!("The agent of the case '{}' must be an object", case.name);
+        }
+    }
+    Ok(suite)
+}
+
+fn is_tool_use(event: &Value) -> bool {
+    event.get("tool_use_id").is_some_and(Value::is_string)
+        && event.get("name").is_some_and(Value::is_string)
+        && event.get("args").is_some()
+}
+
+/// Runs the steps of `case` in `workspace`, returning the expectations they did not meet.
+async fn run_case(os: &mut Os, case: &Case, workspace: &Path, timeout: u64) -> Result<Vec<String>> {
+    os.client.use_mock_backend(case.responses.clone());
+
+    let agent = case_agent(os, case, workspace).await?;
+    let agents = Agents {
+        active_idx: agent.name.clone(),
+        agents: [(agent.name.clone(), agent)].into(),
+        trust_all_tools: false,
+    };
+    let mut conversation = oneshot::conversation_with_agents(os, agents, None).await?;
+
+    let mut failures = Vec::new();
+    for (i, step) in case.steps.iter().enumerate() {
+        let name = step.name.clone().unwrap_or_else(|| format!("step {}", i + 1));
+        let outcome = match tokio::time::timeout(
+            Duration::from_secs(timeout),
+            run_step(os, &mut conversation, step, &mut std::io::sink()),
+        )
+        .await
+        {
+            Ok(outcome) => outcome?,
+            Err(_) => bail!("{name}: no response after {timeout}s"),
+        };
+        failures.extend(
+            check(&step.expect, &outcome)?
+                .into_iter()
+                .map(|failure| format!("{name}: {failure}")),
+        );
+    }
+    Ok(failures)
+}
+
+/// The agent of `case`, with its MCP fixtures added. Servers of the legacy mcp.json are left out
+/// unless the config asks for them, so that the case doesn't depend on the machine it runs on.
+async fn case_agent(os: &Os, case: &Case, workspace: &Path) -> Result<Agent> {
+    let mut config = case.agent.clone().unwrap_or_else(|| json!({ "tools": ["*"] }));
+    let Some(object) = config.as_object_mut() else {
+        bail!("The agent of the case '{}' must be an object", case.name);
+    };
+    object.entry("useLegacyMcpJson").or_insert(Value::Bool(false));
+    if !case.mcp_fixtures.is_empty() {
+        let command = std::env::current_exe()?.to_string_lossy().into_owned();
+        let Some(servers) = object.entry("mcpServers").or_insert_with(|| json!({})).as_object_mut() else {
+            bail!(
+                "The mcpServers of the agent of the case '{}' must be an object",
+                case.name
+            );
+        };
+        for name in &case.mcp_fixtures {
+            servers.insert(
+                name.clone(),
+                json!({ "command": command, "args": ["internal", "mcp-fixture"] }),
+            );
+        }
+    }
+
+    Agent::load_from_path(os, &workspace.join("e2e.json"), &serde_json::to_vec(&config)?).await
+}
+
+/// The outcome of each case as a JUnit report.
+fn junit_report(suite: &str, reports: &[CaseReport]) -> String {
+    let failures = reports.iter().filter(|report| !report.failures.is_empty()).count();
+    let errors = reports.iter().filter(|report| report.error.is_some()).count();
+    let time = reports
+        .iter()
+        .map(|report| report.duration)
+        .sum::<Duration>()
+        .as_secs_f64();
+    let suite = escape_xml(suite);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"{suite}\" tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\" time=\"{time:.3}\">\n",
+        reports.len()
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{suite}\" tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\" time=\"{time:.3}\">\n",
+        reports.len()
+    ));
+    for report in reports {
+        xml.push_str(&format!(
+            "    <testcase classname=\"{suite}\" name=\"{}\" time=\"{:.3}\"",
+            escape_xml(&report.name),
+            report.duration.as_secs_f64()
+        ));
+        if report.error.is_none() && report.failures.is_empty() {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        if let Some(error) = &report.error {
+            xml.push_str(&format!(
+                "      <error message=\"{}\"/>\n",
+                escape_xml(error.lines().next().unwrap_or_default())
+            ));
+        }
+        if !report.failures.is_empty() {
+            xml.push_str(&format!(
+                "      <failure message=\"{} expectation(s) not met\">{}</failure>\n",
+                report.failures.len(),
+                escape_xml(&report.failures.join("\n"))
+            ));
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The MCP server that cases list under `mcp_fixtures`, served by `q internal mcp-fixture`. It
+/// has a single tool, `echo`, which returns the text it is given.
+struct McpFixture;
+
+impl PreServerRequestHandler for McpFixture {
+    fn register_pending_request_callback(
+        &mut self,
+        _cb: impl Fn(u64) -> Option<JsonRpcRequest> + Send + Sync + 'static,
+    ) {
+    }
+
+    fn register_send_request_callback(
+        &mut self,
+        _cb: impl Fn(&str, Option<Value>) -> Result<(), ServerError> + Send + Sync + 'static,
+    ) {
+    }
+}
+
+#[async_trait::async_trait]
+impl ServerRequestHandler for McpFixture {
+    async fn handle_initialize(&self, _params: Option<Value>) -> Result<Response, ServerError> {
+        Ok(Some(json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "e2e-fixture", "version": env!("CARGO_PKG_VERSION") },
+        })))
+    }
+
+    async fn handle_incoming(&self, method: &str, params: Option<Value>) -> Result<Response, ServerError> {
+        match method {
+            "notifications/initialized" => Ok(None),
+            "tools/list" => Ok(Some(json!({
+                "tools": [{
+                    "name": "echo",
+                    "description": "Returns the text it is given",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": { "text": { "type": "string", "description": "Text to return" } },
+                        "required": ["text"],
+                    },
+                }],
+            }))),
+            "tools/call" => {
+                let text = params
+                    .as_ref()
+                    .and_then(|params| params.pointer("/arguments/text"))
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                Ok(Some(json!({
+                    "content": [{ "type": "text", "text": text }],
+                    "isError": false,
+                })))
+            },
+            _ => Err(ServerError::MissingMethod),
+        }
+    }
+
+    async fn handle_response(&self, _resp: JsonRpcResponse) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    async fn handle_shutdown(&self) -> Result<(), ServerError> {
+        Ok(())
+    }
+}
+
+/// Serves [McpFixture] on stdin and stdout until the client goes away.
+pub async fn serve_mcp_fixture() -> Result<ExitCode> {
+    let server = Server::new(McpFixture, tokio::io::stdin(), tokio::io::stdout())?;
+    server.init()?.await??;
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_suite() {
+        let suite = parse_suite(BUILTIN_SUITE).unwrap();
+        assert_eq!(suite.timeout, 60);
+        assert!(suite.cases.iter().any(|case| !case.mcp_fixtures.is_empty()));
+
+        assert!(parse_suite("cases: []").is_err());
+        assert!(parse_suite("cases:\n  - name: a\n    responses: [[hi]]\n    steps: []").is_err());
+        assert!(parse_suite("cases:\n  - name: a\n    responses: [hi]\n    steps:\n      - prompt: hi").is_err());
+        assert!(
+            parse_suite("cases:\n  - name: a\n    responses: [[{ name: x }]]\n    steps:\n      - prompt: hi").is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_case() {
+        let mut os = Os::new().await.unwrap();
+        let suite = parse_suite(BUILTIN_SUITE).unwrap();
+        let workspace = PathBuf::from("/workspace");
+        for case in suite.cases.iter().filter(|case| case.mcp_fixtures.is_empty()) {
+            let failures = run_case(&mut os, case, &workspace, suite.timeout).await.unwrap();
+            assert!(failures.is_empty(), "{}: {failures:?}", case.name);
+        }
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let reports = [
+            CaseReport {
+                name: "answers".to_string(),
+                duration: Duration::from_millis(1500),
+                ..Default::default()
+            },
+            CaseReport {
+                name: "uses <tools>".to_string(),
+                failures: vec!["step 1: the response does not contain \"Done\"".to_string()],
+                ..Default::default()
+            },
+            CaseReport {
+                name: "loads".to_string(),
+                error: Some("Failed to load the agent\ncaused by".to_string()),
+                ..Default::default()
+            },
+        ];
+        let xml = junit_report("e2e", &reports);
+        assert!(xml.contains(r#"<testsuite name="e2e" tests="3" failures="1" errors="1" time="1.500">"#));
+        assert!(xml.contains(r#"<testcase classname="e2e" name="answers" time="1.500"/>"#));
+        assert!(xml.contains(r#"name="uses &lt;tools&gt;""#));
+        assert!(xml.contains("the response does not contain &quot;Done&quot;</failure>"));
+        assert!(xml.contains(r#"<error message="Failed to load the agent"/>"#));
+    }
+
+    #[tokio::test]
+    async fn test_mcp_fixture() {
+        let tools = McpFixture.handle_incoming("tools/list", None).await.unwrap().unwrap();
+        assert_eq!(tools["tools"][0]["name"], "echo");
+        let result = McpFixture
+            .handle_incoming(
+                "tools/call",
+                Some(json!({ "name": "echo", "arguments": { "text": "ping" } })),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result["content"][0]["text"], "ping");
+        assert!(McpFixture.handle_incoming("prompts/list", None).await.is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::io::Write;
+use std::time::Duration;
+
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use super::consts::CONTEXT_WINDOW_SIZE;
+use super::model_registry::{
+    ModelInfo,
+    ModelSpeed,
+};
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+use crate::util::theme::theme;
+
+/// Requests with more tokens than this show an estimate and ask before being sent, unless
+/// `chat.largeRequestTokens` says otherwise.
+const DEFAULT_LARGE_REQUEST_TOKENS: usize = 100_000;
+/// Length of a typical response, used as the output estimate.
+const TYPICAL_OUTPUT_TOKENS: usize = 1_000;
+/// Approximate rate at which a model of medium speed processes the input of a request.
+const INPUT_TOKENS_PER_SEC: usize = 10_000;
+/// Approximate rate at which a model of medium speed generates the response.
+const OUTPUT_TOKENS_PER_SEC: usize = 50;
+
+/// Returns the number of tokens above which a request needs to be confirmed, or [None] if
+/// `chat.largeRequestTokens` is set to 0.
+pub fn large_request_tokens(settings: &Settings) -> Option<usize> {
+    match settings.get_int(Setting::ChatLargeRequestTokens) {
+        Some(tokens) if tokens <= 0 => None,
+        Some(tokens) => usize::try_from(tokens).ok(),
+        None => Some(DEFAULT_LARGE_REQUEST_TOKENS),
+    }
+}
+
+/// What sending a request is expected to take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestEstimate {
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+    /// Time until the response has been received in full
+    pub latency: Duration,
+    /// In tokens
+    pub context_window: usize,
+}
+
+impl RequestEstimate {
+    /// Estimates a request of `input_tokens` to `model`, which is assumed to be of medium speed
+    /// when it is missing from the model registry.
+    pub fn new(input_tokens: usize, model: Option<&ModelInfo>) -> Self {
+        let output_tokens = TYPICAL_OUTPUT_TOKENS;
+        let speed = model.map_or(ModelSpeed::Medium, |model| model.speed);
+        let millis = (input_tokens * 1000 / INPUT_TOKENS_PER_SEC + output_tokens * 1000 / OUTPUT_TOKENS_PER_SEC) * 100
+            / speed.relative_throughput();
+        Self {
+            input_tokens,
+            output_tokens,
+            latency: Duration::from_millis(millis as u64),
+            context_window: model.map_or(CONTEXT_WINDOW_SIZE, |model| model.context_window_tokens),
+        }
+    }
+
+    /// Share of the context window taken up by the input, in percent.
+    pub fn context_window_percent(&self) -> usize {
+        self.input_tokens * 100 / self.context_window
+    }
+
+    pub fn print(&self, output: &mut impl Write) -> std::io::Result<()> {
+        queue!(
+            output,
+            style::SetForegroundColor(theme().warning),
+            style::SetAttribute(Attribute::Bold),
+            style::Print("\nThis is a large request:\n"),
+            style::SetAttribute(Attribute::Reset),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        for (label, value) in [
+            (
+                "Input",
+                format!(
+                    "~{} tokens ({}% of the context window)",
+                    self.input_tokens,
+                    self.context_window_percent()
+                ),
+            ),
+            ("Output", format!("~{} tokens", self.output_tokens)),
+            ("Latency", format!("~{}s", self.latency.as_secs().max(1))),
+        ] {
+            queue!(
+                output,
+                style::Print(format!("  {label:<8} ")),
+                style::SetForegroundColor(theme().secondary),
+                style::Print(format!("{value}\n")),
+                style::SetForegroundColor(Color::Reset),
+            )?;
+        }
+        execute!(output, style::Print("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let estimate = RequestEstimate::new(150_000, None);
+        assert_eq!(estimate.output_tokens, TYPICAL_OUTPUT_TOKENS);
+        assert_eq!(estimate.latency, Duration::from_secs(35));
+        assert_eq!(estimate.context_window_percent(), 75);
+
+        let model = ModelInfo {
+            name: "fast".to_string(),
+            model_id: "FAST_V1".to_string(),
+            context_window_tokens: 300_000,
+            supports_images: false,
+            supports_tools: true,
+            speed: ModelSpeed::Fast,
+        };
+        let estimate = RequestEstimate::new(150_000, Some(&model));
+        assert_eq!(estimate.latency, Duration::from_millis(17_500));
+        assert_eq!(estimate.context_window_percent(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_large_request_tokens() {
+        let mut settings = Settings::new().await.unwrap();
+        assert_eq!(large_request_tokens(&settings), Some(DEFAULT_LARGE_REQUEST_TOKENS));
+
+        settings.set(Setting::ChatLargeRequestTokens, 5000).await.unwrap();
+        assert_eq!(large_request_tokens(&settings), Some(5000));
+
+        settings.set(Setting::ChatLargeRequestTokens, 0).await.unwrap();
+        assert_eq!(large_request_tokens(&settings), None);
+    }
+}
@@ -51,10 +51,8 @@ impl InputSource {
         use crate::database::settings::Setting;
 
         if let inner::Inner::Readline(rl) = &mut self.0 {
-            let key_char = match os.database.settings.get_string(Setting::SkimCommandKey) {
-                Some(key) if key.len() == 1 => key.chars().next().unwrap_or('s'),
-                _ => 's', // Default to 's' if setting is missing or invalid
-            };
+            let key_char =
+                super::prompt::skim_command_key(os.database.settings.get_string(Setting::SkimCommandKey).as_deref());
             rl.bind_sequence(
                 KeyEvent::ctrl(key_char),
                 EventHandler::Conditional(Box::new(SkimCommandSelector::new(
@@ -66,6 +64,37 @@ impl InputSource {
         }
     }
 
+    /// Binds Ctrl+K to the command palette.
+    #[cfg(unix)]
+    pub fn put_command_palette(
+        &mut self,
+        items: Vec<super::command_palette::PaletteItem>,
+        context_manager: std::sync::Arc<super::context::ContextManager>,
+        tool_names: Vec<String>,
+    ) {
+        use rustyline::{
+            EventHandler,
+            KeyEvent,
+        };
+
+        use super::command_palette::CommandPalette;
+
+        if let inner::Inner::Readline(rl) = &mut self.0 {
+            let Some(selection) = rl.helper().map(|helper| helper.palette_selection()) else {
+                return;
+            };
+            rl.bind_sequence(
+                KeyEvent::ctrl('k'),
+                EventHandler::Conditional(Box::new(CommandPalette::new(
+                    items,
+                    selection,
+                    context_manager,
+                    tool_names,
+                ))),
+            );
+        }
+    }
+
     #[allow(dead_code)]
     pub fn new_mock(lines: Vec<String>) -> Self {
         Self(inner::Inner::Mock { index: 0, lines })
@@ -78,6 +107,11 @@ impl InputSource {
                 let curr_line = rl.readline(prompt);
                 match curr_line {
                     Ok(line) => {
+                        // The command palette accepts an empty line to run what was selected in it
+                        let line = rl
+                            .helper()
+                            .and_then(|helper| helper.take_palette_selection())
+                            .unwrap_or(line);
                         let _ = rl.add_history_entry(line.as_str());
 
                         if let Some(helper) = rl.helper_mut() {
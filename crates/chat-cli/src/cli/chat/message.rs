@@ -11,6 +11,7 @@ use tracing::{
 };
 
 use super::consts::{
+    CLOUD_ENVIRONMENT_VAR,
     MAX_CURRENT_WORKING_DIRECTORY_LEN,
     MAX_USER_MESSAGE_SIZE,
 };
@@ -28,6 +29,7 @@ use super::util::{
 use crate::api_client::model::{
     AssistantResponseMessage,
     EnvState,
+    EnvironmentVariable,
     ImageBlock,
     Tool,
     ToolResult,
@@ -37,6 +39,7 @@ use crate::api_client::model::{
     UserInputMessage,
     UserInputMessageContext,
 };
+use crate::util::system_info::cloud_environment;
 
 const USER_ENTRY_START_HEADER: &str = "--- USER MESSAGE BEGIN ---\n";
 const USER_ENTRY_END_HEADER: &str = "--- USER MESSAGE END ---\n\n";
@@ -47,6 +50,9 @@ pub struct UserMessage {
     pub env_context: UserEnvContext,
     pub content: UserMessageContent,
     pub images: Option<Vec<ImageBlock>>,
+    /// Whether the turn this message is part of is left out when the conversation is saved.
+    #[serde(skip)]
+    pub ephemeral: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,6 +109,7 @@ impl UserMessage {
             images: None,
             additional_context: String::new(),
             env_context: UserEnvContext::generate_new(),
+            ephemeral: false,
             content: UserMessageContent::Prompt { prompt },
         }
     }
@@ -112,6 +119,7 @@ impl UserMessage {
             images: None,
             additional_context: String::new(),
             env_context: UserEnvContext::generate_new(),
+            ephemeral: false,
             content: UserMessageContent::CancelledToolUses {
                 prompt,
                 tool_use_results: tool_use_ids
@@ -131,6 +139,7 @@ impl UserMessage {
         Self {
             additional_context: String::new(),
             env_context: UserEnvContext::generate_new(),
+            ephemeral: false,
             content: UserMessageContent::ToolUseResults {
                 tool_use_results: results,
             },
@@ -142,6 +151,7 @@ impl UserMessage {
         Self {
             additional_context: String::new(),
             env_context: UserEnvContext::generate_new(),
+            ephemeral: false,
             content: UserMessageContent::ToolUseResults {
                 tool_use_results: results,
             },
@@ -498,6 +508,14 @@ pub fn build_env_state() -> EnvState {
         },
     }
 
+    // Lets the model know e.g. that nothing can be opened in a browser
+    if let Some(cloud_environment) = cloud_environment() {
+        env_state.environment_variables.push(EnvironmentVariable {
+            key: CLOUD_ENVIRONMENT_VAR.to_string(),
+            value: cloud_environment.to_string(),
+        });
+    }
+
     env_state
 }
 
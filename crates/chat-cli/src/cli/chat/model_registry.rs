@@ -0,0 +1,269 @@
+//! What Q knows about each model: how large its context window is, what it can be sent and how
+//! fast it responds. The registry bundled with Q is replaced by the one at `chat.modelRegistryUrl`
+//! when that is set, which is cached and fetched again once a day.
+
+use std::sync::{
+    LazyLock,
+    OnceLock,
+};
+use std::time::Duration;
+
+use eyre::{
+    Result,
+    bail,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tracing::{
+    debug,
+    warn,
+};
+
+use super::consts::CONTEXT_WINDOW_SIZE;
+use crate::database::settings::Setting;
+use crate::os::{
+    Fs,
+    Os,
+};
+use crate::util::directories;
+
+/// Age after which the cached registry is fetched again.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+static REGISTRY: OnceLock<ModelRegistry> = OnceLock::new();
+static BUNDLED: LazyLock<ModelRegistry> = LazyLock::new(ModelRegistry::bundled);
+
+/// How fast a model responds compared to the others.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelSpeed {
+    Fast,
+    #[default]
+    Medium,
+    Slow,
+}
+
+impl ModelSpeed {
+    /// Throughput relative to a medium model, in percent.
+    pub fn relative_throughput(self) -> usize {
+        match self {
+            Self::Fast => 200,
+            Self::Medium => 100,
+            Self::Slow => 50,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fast => "fast",
+            Self::Medium => "medium",
+            Self::Slow => "slow",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    /// Name used with `--model`, `/model` and `chat.defaultModel`
+    pub name: String,
+    /// Id the service knows the model by
+    pub model_id: String,
+    /// In tokens
+    pub context_window_tokens: usize,
+    /// Whether images can be attached to requests
+    #[serde(default)]
+    pub supports_images: bool,
+    /// Whether the model can be offered tools
+    #[serde(default = "default_true")]
+    pub supports_tools: bool,
+    #[serde(default)]
+    pub speed: ModelSpeed,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl ModelInfo {
+    /// Summary of the capabilities, e.g. `200k context, images, medium speed`.
+    pub fn description(&self) -> String {
+        let mut parts = vec![format!("{}k context", self.context_window_tokens / 1000)];
+        if self.supports_images {
+            parts.push("images".to_string());
+        }
+        if !self.supports_tools {
+            parts.push("no tools".to_string());
+        }
+        parts.push(format!("{} speed", self.speed.as_str()));
+        parts.join(", ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelRegistry {
+    pub models: Vec<ModelInfo>,
+}
+
+impl ModelRegistry {
+    pub fn bundled() -> Self {
+        serde_json::from_str(include_str!("./models.json")).expect("models.json is valid json")
+    }
+
+    /// The registry last fetched from `chat.modelRegistryUrl`, or the bundled one if there is
+    /// none.
+    pub async fn load(os: &Os) -> Self {
+        let Ok(path) = directories::model_registry_cache_path() else {
+            return Self::bundled();
+        };
+        let Ok(content) = os.fs.read_to_string(&path).await else {
+            return Self::bundled();
+        };
+        Self::parse(&content).unwrap_or_else(|err| {
+            warn!(?err, "Ignoring the cached model registry");
+            Self::bundled()
+        })
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let registry: Self = serde_json::from_str(content)?;
+        if registry.models.is_empty() {
+            bail!("The model registry lists no models");
+        }
+        Ok(registry)
+    }
+
+    /// Fetches the registry at `url` and caches it for the sessions that follow.
+    pub async fn refresh(fs: &Fs, url: &str) -> Result<Self> {
+        let content = crate::request::new_client()?
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let registry = Self::parse(&content)?;
+
+        let path = directories::model_registry_cache_path()?;
+        if let Some(parent) = path.parent() {
+            fs.create_dir_all(parent).await?;
+        }
+        fs.write(&path, content).await?;
+        Ok(registry)
+    }
+
+    pub fn get(&self, model_id: &str) -> Option<&ModelInfo> {
+        self.models.iter().find(|model| model.model_id == model_id)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&ModelInfo> {
+        self.models.iter().find(|model| model.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The model called `name_or_id`, or with that id.
+    pub fn find(&self, name_or_id: &str) -> Option<&ModelInfo> {
+        self.find_by_name(name_or_id).or_else(|| self.get(name_or_id))
+    }
+
+    /// The context window of `model_id` in tokens, [CONTEXT_WINDOW_SIZE] for models the registry
+    /// does not know.
+    pub fn context_window(&self, model_id: Option<&str>) -> usize {
+        model_id
+            .and_then(|id| self.get(id))
+            .map_or(CONTEXT_WINDOW_SIZE, |model| model.context_window_tokens)
+    }
+
+    /// Whether images can be sent to `model_id`. Unknown models are assumed to accept them.
+    pub fn supports_images(&self, model_id: Option<&str>) -> bool {
+        model_id
+            .and_then(|id| self.get(id))
+            .is_none_or(|model| model.supports_images)
+    }
+}
+
+/// Selects the cached registry for the rest of the process and fetches it again in the background
+/// when it is out of date. Only the first call has an effect.
+pub async fn init(os: &Os) {
+    if REGISTRY.get().is_some() {
+        return;
+    }
+    let _ = REGISTRY.set(ModelRegistry::load(os).await);
+    refresh_in_background(os).await;
+}
+
+/// The current registry, the bundled one unless [init] selected another.
+pub fn models() -> &'static ModelRegistry {
+    REGISTRY.get().unwrap_or(&BUNDLED)
+}
+
+/// Fetches the registry from `chat.modelRegistryUrl` in the background when the cached copy is
+/// missing or older than a day. The fetched registry is used from the next session on.
+async fn refresh_in_background(os: &Os) {
+    let Some(url) = os.database.settings.get_string(Setting::ChatModelRegistryUrl) else {
+        return;
+    };
+    let Ok(path) = directories::model_registry_cache_path() else {
+        return;
+    };
+    let is_fresh = os
+        .fs
+        .symlink_metadata(&path)
+        .await
+        .ok()
+        .and_then(|metadata| metadata.modified().ok())
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < REFRESH_INTERVAL);
+    if is_fresh {
+        return;
+    }
+
+    let fs = os.fs.clone();
+    tokio::spawn(async move {
+        match ModelRegistry::refresh(&fs, &url).await {
+            Ok(registry) => debug!(models = registry.models.len(), "Refreshed the model registry"),
+            Err(err) => warn!(?err, "Failed to refresh the model registry from {url}"),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_registry() {
+        let registry = ModelRegistry::bundled();
+        assert!(!registry.models.is_empty());
+
+        let model = registry.find_by_name("CLAUDE-4-SONNET").unwrap();
+        assert_eq!(registry.get(&model.model_id), Some(model));
+        assert_eq!(registry.find(&model.model_id), Some(model));
+        assert_eq!(registry.find("claude-4-sonnet"), Some(model));
+        assert_eq!(registry.find("unknown"), None);
+        assert_eq!(
+            registry.context_window(Some(&model.model_id)),
+            model.context_window_tokens
+        );
+        assert_eq!(registry.context_window(Some("unknown")), CONTEXT_WINDOW_SIZE);
+        assert_eq!(registry.context_window(None), CONTEXT_WINDOW_SIZE);
+    }
+
+    #[test]
+    fn test_parse_registry() {
+        let registry = ModelRegistry::parse(
+            r#"{"models": [{"name": "small", "modelId": "SMALL_V1", "contextWindowTokens": 32000, "speed": "fast"}]}"#,
+        )
+        .unwrap();
+        let model = registry.get("SMALL_V1").unwrap();
+        assert!(!model.supports_images);
+        assert!(model.supports_tools);
+        assert_eq!(model.description(), "32k context, fast speed");
+        assert!(!registry.supports_images(Some("SMALL_V1")));
+        assert!(registry.supports_images(Some("unknown")));
+
+        assert!(ModelRegistry::parse(r#"{"models": []}"#).is_err());
+        assert!(ModelRegistry::parse("not json").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! Notifications sent when unattended work finishes, i.e. an autonomous run with `/auto` or
+//! `q script run`. They are configured with two settings:
+//!
+//! - `chat.completionCommand`: a shell command that receives the [Completion] as JSON on stdin,
+//!   with its status and summary also in `Q_COMPLETION_STATUS` and `Q_COMPLETION_SUMMARY`
+//! - `chat.completionWebhook`: a URL that the [Completion] is POSTed to as JSON
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// How long the command and the webhook each have to complete.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The payload of a completion notification.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Completion {
+    /// What finished, `auto` or `script`
+    pub source: &'static str,
+    /// `succeeded` or `failed`, or for autonomous runs the reason they stopped
+    pub status: String,
+    pub summary: String,
+    /// Files written while working
+    pub artifacts: Vec<String>,
+    pub conversation_id: String,
+}
+
+/// Sends the notifications configured in the settings, returning a message for each one that
+/// failed. A failed notification never fails the work it reports on.
+pub async fn notify(os: &Os, completion: &Completion) -> Vec<String> {
+    let mut failures = Vec::new();
+    if let Some(command) = os.database.settings.get_string(Setting::ChatCompletionCommand) {
+        if let Err(err) = run_command(&command, completion).await {
+            failures.push(format!("Completion command failed: {err}"));
+        }
+    }
+    if let Some(url) = os.database.settings.get_string(Setting::ChatCompletionWebhook) {
+        if let Err(err) = post_webhook(&url, completion).await {
+            failures.push(format!("Completion webhook failed: {err}"));
+        }
+    }
+    failures
+}
+
+async fn run_command(command: &str, completion: &Completion) -> Result<()> {
+    #[cfg(unix)]
+    let mut cmd = tokio::process::Command::new("bash");
+    #[cfg(unix)]
+    cmd.arg("-c");
+    #[cfg(windows)]
+    let mut cmd = tokio::process::Command::new("cmd");
+    #[cfg(windows)]
+    cmd.arg("/C");
+
+    let mut child = cmd
+        .arg(command)
+        .env("Q_COMPLETION_STATUS", &completion.status)
+        .env("Q_COMPLETION_SUMMARY", &completion.summary)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdin = child.stdin.take();
+    let payload = serde_json::to_vec(completion)?;
+
+    // Writing counts towards the timeout, since a command that doesn't read its input blocks the
+    // write once the payload fills the pipe
+    let run = async move {
+        if let Some(mut stdin) = stdin {
+            // The command may exit without reading its input
+            let _ = stdin.write_all(&payload).await;
+        }
+        child.wait_with_output().await
+    };
+    let output = match tokio::time::timeout(NOTIFY_TIMEOUT, run).await {
+        Ok(output) => output?,
+        Err(_) => bail!("timed out after {}s", NOTIFY_TIMEOUT.as_secs()),
+    };
+    if !output.status.success() {
+        return Err(eyre!(
+            "exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+async fn post_webhook(url: &str, completion: &Completion) -> Result<()> {
+    let response = crate::request::new_client()?
+        .post(url)
+        .timeout(NOTIFY_TIMEOUT)
+        .json(completion)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        bail!("{url} responded with {}", response.status());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completion() -> Completion {
+        Completion {
+            source: "script",
+            status: "succeeded".to_string(),
+            summary: "2 of 2 steps passed".to_string(),
+            artifacts: vec!["out.md".to_string()],
+            conversation_id: "conv".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_payload() {
+        assert_eq!(
+            serde_json::to_value(completion()).unwrap(),
+            serde_json::json!({
+                "source": "script",
+                "status": "succeeded",
+                "summary": "2 of 2 steps passed",
+                "artifacts": ["out.md"],
+                "conversation_id": "conv",
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let command = format!("echo \"$Q_COMPLETION_STATUS\" > {0} && cat >> {0}", out.display());
+        run_command(&command, &completion()).await.unwrap();
+
+        let written = std::fs::read_to_string(&out).unwrap();
+        let (status, payload) = written.split_once('\n').unwrap();
+        assert_eq!(status, "succeeded");
+        assert_eq!(payload, serde_json::to_string(&completion()).unwrap());
+
+        let err = run_command("echo oops >&2; exit 3", &completion()).await.unwrap_err();
+        assert!(err.to_string().contains("oops"));
+    }
+}
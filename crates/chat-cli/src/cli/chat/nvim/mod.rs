@@ -0,0 +1,693 @@
+//! `q nvim-server`, which drives a chat session over msgpack-RPC on stdin and stdout so that a
+//! Neovim plugin can host it, e.g. with `jobstart(['q', 'nvim-server'], #{rpc: v:true})`.
+//!
+//! The plugin calls these methods with `rpcrequest` or `rpcnotify`:
+//! - `chat(prompt, selections?)` starts a response. Each selection is a map with `path`,
+//!   `startLine`, `endLine` and `text`, sent along with the prompt as context.
+//! - `resolve(id, accepted)` answers a `diff` or `confirm` event.
+//! - `cancel()` stops the current response.
+//! - `clear()` starts a new conversation.
+//!
+//! Progress is reported by calling the Vimscript function given by `--callback` with a single map
+//! whose `type` is one of `chunk`, `diff`, `confirm`, `toolResult`, `done`, `cancelled` or `error`.
+//! Changes to files are sent as `diff` events with the original and proposed contents, and are
+//! only written once the plugin resolves them, unless fs_write is trusted.
+
+mod msgpack;
+
+use std::future::Future;
+use std::process::ExitCode;
+
+use clap::Args;
+use eyre::Result;
+use msgpack::Value;
+use serde_json::json;
+use tokio::io::{
+    AsyncReadExt,
+    AsyncWrite,
+    AsyncWriteExt,
+};
+use tokio::sync::mpsc;
+use tracing::{
+    debug,
+    error,
+    warn,
+};
+
+use super::conversation::ConversationState;
+use super::message::{
+    AssistantMessage,
+    AssistantToolUse,
+    ToolUseResult,
+    ToolUseResultBlock,
+};
+use super::parser::{
+    ResponseEvent,
+    ResponseParser,
+};
+use super::tools::{
+    Tool,
+    cancellable,
+    sanitize_path_tool_arg,
+};
+use crate::api_client::model::ToolResultStatus;
+use crate::cli::agent::PermissionEvalResult;
+use crate::cli::agent::content_filter::ContentFilter;
+use crate::cli::agent::egress::Egress;
+use crate::os::Os;
+
+const REJECTED_MESSAGE: &str = "The user rejected this tool use in the editor.";
+
+/// Serve a chat session to a Neovim plugin over msgpack-RPC on stdin and stdout
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct NvimServerArgs {
+    /// Agent to use
+    #[arg(long)]
+    pub agent: Option<String>,
+    /// Model to use
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Vimscript function called with each event, e.g. v:lua.require'amazonq'.on_event
+    #[arg(long, default_value = "QChatEvent")]
+    pub callback: String,
+}
+
+impl NvimServerArgs {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        let conversation =
+            super::oneshot::conversation_with_tools(os, self.agent.as_deref(), self.model.as_deref()).await?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(read_messages(tokio::io::stdin(), sender));
+
+        NvimServer::new(conversation, receiver, tokio::io::stdout(), self.callback)
+            .run(os)
+            .await?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// A msgpack-RPC message received from Neovim.
+#[derive(Debug, Clone, PartialEq)]
+enum Incoming {
+    Request {
+        id: u64,
+        method: String,
+        params: Vec<Value>,
+    },
+    Notification {
+        method: String,
+        params: Vec<Value>,
+    },
+}
+
+impl Incoming {
+    fn parse(value: Value) -> Option<Self> {
+        let fields = value.as_array()?;
+        let params = |value: &Value| value.as_array().map(<[Value]>::to_vec).unwrap_or_default();
+        match fields {
+            [kind, id, method, args] if kind.as_u64() == Some(0) => Some(Self::Request {
+                id: id.as_u64()?,
+                method: method.as_str()?.to_string(),
+                params: params(args),
+            }),
+            [kind, method, args] if kind.as_u64() == Some(2) => Some(Self::Notification {
+                method: method.as_str()?.to_string(),
+                params: params(args),
+            }),
+            // Responses to our notifications are never expected
+            _ => None,
+        }
+    }
+
+    fn method(&self) -> &str {
+        match self {
+            Self::Request { method, .. } | Self::Notification { method, .. } => method,
+        }
+    }
+
+    fn params(&self) -> &[Value] {
+        match self {
+            Self::Request { params, .. } | Self::Notification { params, .. } => params,
+        }
+    }
+}
+
+/// Decodes messages from `input` until it is closed or invalid.
+async fn read_messages(mut input: impl tokio::io::AsyncRead + Unpin, sender: mpsc::UnboundedSender<Incoming>) {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 8192];
+    loop {
+        match Value::decode(&buf) {
+            Ok(Some((value, len))) => {
+                buf.drain(..len);
+                match Incoming::parse(value) {
+                    Some(message) => {
+                        if sender.send(message).is_err() {
+                            return;
+                        }
+                    },
+                    None => warn!("ignoring unexpected msgpack-rpc message"),
+                }
+                continue;
+            },
+            Ok(None) => (),
+            Err(err) => {
+                error!(?err, "invalid msgpack-rpc input");
+                return;
+            },
+        }
+
+        match input.read(&mut chunk).await {
+            Ok(0) => return,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(err) => {
+                error!(?err, "failed to read msgpack-rpc input");
+                return;
+            },
+        }
+    }
+}
+
+/// How a message received while a response is in progress affects it.
+enum Interruption {
+    /// The message was answered and the response continues
+    None,
+    Cancel,
+    Resolved(bool),
+}
+
+/// Result of awaiting something while a response is in progress.
+enum Step<T> {
+    Done(T),
+    Cancelled,
+}
+
+struct NvimServer<W> {
+    conversation: ConversationState,
+    incoming: mpsc::UnboundedReceiver<Incoming>,
+    output: W,
+    callback: String,
+}
+
+impl<W: AsyncWrite + Unpin> NvimServer<W> {
+    fn new(
+        conversation: ConversationState,
+        incoming: mpsc::UnboundedReceiver<Incoming>,
+        output: W,
+        callback: String,
+    ) -> Self {
+        Self {
+            conversation,
+            incoming,
+            output,
+            callback,
+        }
+    }
+
+    /// Serves requests until Neovim closes the channel.
+    async fn run(mut self, os: &mut Os) -> Result<()> {
+        while let Some(message) = self.incoming.recv().await {
+            debug!(?message, "received msgpack-rpc message");
+            let result = match message.method() {
+                "chat" => match message.params().first().and_then(Value::as_str) {
+                    Some(prompt) => {
+                        let prompt = prompt_with_selections(prompt, message.params().get(1));
+                        self.reply(&message, Ok(Value::Bool(true))).await?;
+                        if let Err(err) = self.respond(os, prompt).await {
+                            error!(?err, "failed to respond to the prompt");
+                            self.conversation.reset_next_user_message();
+                            self.notify(json!({ "type": "error", "message": err.to_string() }))
+                                .await?;
+                        }
+                        continue;
+                    },
+                    None => Err("chat expects a prompt".to_string()),
+                },
+                "cancel" | "clear" => {
+                    if message.method() == "clear" {
+                        self.conversation.clear(false);
+                    }
+                    Ok(Value::Nil)
+                },
+                "resolve" => Err("nothing is waiting to be resolved".to_string()),
+                other => Err(format!("unknown method '{other}'")),
+            };
+            self.reply(&message, result).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends the prompt and streams the response, running tools until the model is done.
+    async fn respond(&mut self, os: &mut Os, prompt: String) -> Result<()> {
+        self.conversation.set_next_user_message(prompt).await;
+        let mut run_hooks = true;
+        loop {
+            let conv_state = self
+                .conversation
+                .as_sendable_conversation_state(os, &mut std::io::sink(), run_hooks)
+                .await?;
+            run_hooks = false;
+
+            let response = match self.interruptible(os.client.send_message(conv_state)).await? {
+                Step::Done(response) => response?,
+                Step::Cancelled => return self.cancelled(os, &[]).await,
+            };
+
+            let mut parser = ResponseParser::new(response);
+            let message = loop {
+                match self.interruptible(parser.recv()).await? {
+                    Step::Done(event) => match event? {
+                        ResponseEvent::AssistantText(text) => {
+                            self.notify(json!({ "type": "chunk", "text": text })).await?;
+                        },
+                        ResponseEvent::ToolUseStart { .. } | ResponseEvent::ToolUse(_) => (),
+                        ResponseEvent::EndStream { message } => break message,
+                    },
+                    Step::Cancelled => return self.cancelled(os, &[]).await,
+                }
+            };
+
+            let tool_uses = message.tool_uses().map(<[_]>::to_vec).unwrap_or_default();
+            self.conversation.push_assistant_message(os, message);
+            if tool_uses.is_empty() {
+                return self.notify(json!({ "type": "done" })).await;
+            }
+
+            match self.run_tools(os, &tool_uses).await? {
+                Step::Done(results) => self.conversation.add_tool_results(results),
+                Step::Cancelled => return self.cancelled(os, &tool_uses).await,
+            }
+        }
+    }
+
+    /// Validates the tools, asks the plugin for the ones that need approval and runs them.
+    async fn run_tools(&mut self, os: &mut Os, tool_uses: &[AssistantToolUse]) -> Result<Step<Vec<ToolUseResult>>> {
+        let mut results = Vec::new();
+        for tool_use in tool_uses {
+            let error_result = |text: String| ToolUseResult {
+                tool_use_id: tool_use.id.clone(),
+                content: vec![ToolUseResultBlock::Text(text)],
+                status: ToolResultStatus::Error,
+            };
+
+            let mut tool = match self.conversation.tool_manager.get_tool_from_tool_use(tool_use.clone()) {
+                Ok(tool) => tool,
+                Err(result) => {
+                    results.push(result.into());
+                    continue;
+                },
+            };
+            if let Tool::ManageTodo(manage_todo) = &mut tool {
+                manage_todo.set_todo_list(self.conversation.todo_list.clone());
+            }
+            if let Tool::WebFetch(web_fetch) = &mut tool {
+                let agent = self.conversation.agents.get_active();
+                web_fetch.set_egress(Egress::resolve(&os.database.settings, agent.map(|a| &a.egress)));
+            }
+            if let Tool::GrepSearch(grep_search) = &mut tool {
+                let agent = self.conversation.agents.get_active();
+                grep_search.set_content_filter(ContentFilter::resolve(
+                    &os.database.settings,
+                    agent.map(|a| &a.content_filter),
+                ));
+            }
+            if let Tool::ExecuteCommand(execute_command) = &mut tool {
+                let agent = self.conversation.agents.get_active();
+                execute_command.set_sandbox(agent.and_then(|a| a.sandbox.clone()));
+            }
+            if let Err(err) = tool.validate(os).await {
+                results.push(error_result(format!("Failed to validate tool parameters: {err}")));
+                continue;
+            }
+
+            let permission = match self.conversation.agents.trust_all_tools {
+                true => PermissionEvalResult::Allow,
+                false => self
+                    .conversation
+                    .agents
+                    .get_active()
+                    .map_or(PermissionEvalResult::Ask, |agent| tool.requires_acceptance(os, agent)),
+            };
+            let trusted = match permission {
+                PermissionEvalResult::Allow => true,
+                PermissionEvalResult::Ask => false,
+                PermissionEvalResult::Deny => {
+                    results.push(error_result(format!(
+                        "Tool use with {} was rejected because the arguments supplied were forbidden",
+                        tool_use.name
+                    )));
+                    continue;
+                },
+            };
+
+            if let Tool::FsWrite(fs_write) = &tool {
+                let path = sanitize_path_tool_arg(os, fs_write.path());
+                let original = match os.fs.exists(&path) {
+                    true => os.fs.read_to_string(&path).await?,
+                    false => String::new(),
+                };
+                let proposed = match fs_write.updated_content(original.clone()) {
+                    Ok(proposed) => proposed,
+                    Err(err) => {
+                        results.push(error_result(format!("An error occurred processing the tool: \n{err}")));
+                        continue;
+                    },
+                };
+                self.notify(json!({
+                    "type": "diff",
+                    "id": tool_use.id,
+                    "path": path.to_string_lossy(),
+                    "original": original,
+                    "proposed": proposed,
+                    "pending": !trusted,
+                }))
+                .await?;
+            } else if !trusted {
+                let mut description = Vec::new();
+                tool.queue_description(os, &mut description).await?;
+                self.notify(json!({
+                    "type": "confirm",
+                    "id": tool_use.id,
+                    "tool": tool.display_name(),
+                    "description": strip_ansi_escapes::strip_str(String::from_utf8_lossy(&description)),
+                }))
+                .await?;
+            }
+
+            if !trusted {
+                match self.wait_for_resolution(&tool_use.id).await? {
+                    Step::Done(true) => (),
+                    Step::Done(false) => {
+                        results.push(error_result(REJECTED_MESSAGE.to_string()));
+                        continue;
+                    },
+                    Step::Cancelled => return Ok(Step::Cancelled),
+                }
+            }
+
+            let timeout = self
+                .conversation
+                .agents
+                .get_active()
+                .and_then(|agent| tool.timeout(agent));
+            let result = match cancellable(tool.invoke(os, &mut std::io::sink()), timeout).await {
+                Ok(output) => {
+                    if let Tool::ManageTodo(manage_todo) = &tool {
+                        if let Err(err) = manage_todo.apply(&mut self.conversation.todo_list) {
+                            warn!(?err, "failed to update the task list");
+                        }
+                    }
+                    ToolUseResult {
+                        tool_use_id: tool_use.id.clone(),
+                        content: vec![output.into()],
+                        status: ToolResultStatus::Success,
+                    }
+                },
+                Err(err) => error_result(format!("An error occurred processing the tool: \n{err}")),
+            };
+            self.notify(json!({
+                "type": "toolResult",
+                "id": tool_use.id,
+                "tool": tool.display_name(),
+                "success": matches!(result.status, ToolResultStatus::Success),
+            }))
+            .await?;
+            results.push(result);
+        }
+
+        Ok(Step::Done(results))
+    }
+
+    /// Records that the response was cancelled, abandoning `tool_uses` that did not run.
+    async fn cancelled(&mut self, os: &mut Os, tool_uses: &[AssistantToolUse]) -> Result<()> {
+        match tool_uses.is_empty() {
+            true => self.conversation.reset_next_user_message(),
+            false => {
+                self.conversation.abandon_tool_use(
+                    tool_uses.iter().map(|t| t.id.as_str()),
+                    "The user interrupted the tool execution.".to_string(),
+                );
+                self.conversation
+                    .as_sendable_conversation_state(os, &mut std::io::sink(), false)
+                    .await?;
+                self.conversation.push_assistant_message(
+                    os,
+                    AssistantMessage::new_response(
+                        None,
+                        "Tool uses were interrupted, waiting for the next user prompt".to_string(),
+                    ),
+                );
+            },
+        }
+        self.notify(json!({ "type": "cancelled" })).await
+    }
+
+    /// Awaits `future` while answering the messages received in the meantime.
+    async fn interruptible<T>(&mut self, future: impl Future<Output = T>) -> Result<Step<T>> {
+        tokio::pin!(future);
+        loop {
+            let message = tokio::select! {
+                output = &mut future => return Ok(Step::Done(output)),
+                message = self.incoming.recv() => message,
+            };
+            match self.interrupt(message, None).await? {
+                Interruption::None | Interruption::Resolved(_) => (),
+                Interruption::Cancel => return Ok(Step::Cancelled),
+            }
+        }
+    }
+
+    async fn wait_for_resolution(&mut self, id: &str) -> Result<Step<bool>> {
+        loop {
+            let message = self.incoming.recv().await;
+            match self.interrupt(message, Some(id)).await? {
+                Interruption::None => (),
+                Interruption::Cancel => return Ok(Step::Cancelled),
+                Interruption::Resolved(accepted) => return Ok(Step::Done(accepted)),
+            }
+        }
+    }
+
+    /// Answers a message received while a response is in progress. `resolving` is the id of the
+    /// event waiting for `resolve`, if any.
+    async fn interrupt(&mut self, message: Option<Incoming>, resolving: Option<&str>) -> Result<Interruption> {
+        // Neovim closed the channel
+        let Some(message) = message else {
+            return Ok(Interruption::Cancel);
+        };
+
+        let (result, interruption) = match message.method() {
+            "cancel" => (Ok(Value::Nil), Interruption::Cancel),
+            "resolve" => match (message.params(), resolving) {
+                ([id, accepted], Some(resolving)) if id.as_str() == Some(resolving) => (
+                    Ok(Value::Nil),
+                    Interruption::Resolved(accepted.as_bool().unwrap_or(false)),
+                ),
+                _ => (
+                    Err("nothing is waiting to be resolved with this id".to_string()),
+                    Interruption::None,
+                ),
+            },
+            other => (
+                Err(format!("cannot {other} while a response is in progress")),
+                Interruption::None,
+            ),
+        };
+        self.reply(&message, result).await?;
+        Ok(interruption)
+    }
+
+    async fn reply(&mut self, message: &Incoming, result: Result<Value, String>) -> Result<()> {
+        match message {
+            Incoming::Request { id, .. } => {
+                let (error, result) = match result {
+                    Ok(value) => (Value::Nil, value),
+                    Err(err) => (Value::from(err.as_str()), Value::Nil),
+                };
+                self.send(Value::Array(vec![Value::Int(1), Value::UInt(*id), error, result]))
+                    .await
+            },
+            Incoming::Notification { method, .. } => {
+                if let Err(err) = result {
+                    warn!(?method, ?err, "failed to handle notification");
+                }
+                Ok(())
+            },
+        }
+    }
+
+    /// Calls the callback of the plugin with `event`.
+    async fn notify(&mut self, event: serde_json::Value) -> Result<()> {
+        let call = Value::Array(vec![
+            Value::from(self.callback.as_str()),
+            Value::Array(vec![event.into()]),
+        ]);
+        self.send(Value::Array(vec![Value::Int(2), "nvim_call_function".into(), call]))
+            .await
+    }
+
+    async fn send(&mut self, value: Value) -> Result<()> {
+        let mut buf = Vec::new();
+        value.encode(&mut buf);
+        self.output.write_all(&buf).await?;
+        self.output.flush().await?;
+        Ok(())
+    }
+}
+
+/// Appends the text selected in the editor to the prompt.
+fn prompt_with_selections(prompt: &str, selections: Option<&Value>) -> String {
+    let mut prompt = prompt.to_string();
+    for selection in selections.and_then(Value::as_array).unwrap_or_default() {
+        let Some(text) = selection.get("text").and_then(Value::as_str) else {
+            continue;
+        };
+        let path = selection.get("path").and_then(Value::as_str).unwrap_or("the editor");
+        let lines = match (
+            selection.get("startLine").and_then(Value::as_u64),
+            selection.get("endLine").and_then(Value::as_u64),
+        ) {
+            (Some(start), Some(end)) => format!(" (lines {start}-{end})"),
+            _ => String::new(),
+        };
+        prompt.push_str(&format!("\n\nSelected in {path}{lines}:\n```\n{text}\n```"));
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::cli::agent::Agents;
+    use crate::cli::chat::tool_manager::ToolManager;
+    use crate::cli::chat::tools::ToolSpec;
+
+    #[test]
+    fn test_prompt_with_selections() {
+        let selections = Value::from(json!([
+            { "path": "src/main.rs", "startLine": 3, "endLine": 4, "text": "let x = 1;\nlet y = 2;" },
+            { "text": "hello" },
+            { "path": "ignored.rs" },
+        ]));
+        assert_eq!(
+            prompt_with_selections("explain", Some(&selections)),
+            "explain\n\nSelected in src/main.rs (lines 3-4):\n```\nlet x = 1;\nlet y = 2;\n```\n\nSelected in the editor:\n```\nhello\n```"
+        );
+        assert_eq!(prompt_with_selections("explain", None), "explain");
+    }
+
+    #[test]
+    fn test_parse_incoming() {
+        let request = Value::from(json!([0, 7, "chat", ["hi"]]));
+        assert_eq!(
+            Incoming::parse(request),
+            Some(Incoming::Request {
+                id: 7,
+                method: "chat".to_string(),
+                params: vec!["hi".into()],
+            })
+        );
+        let notification = Value::from(json!([2, "cancel", []]));
+        assert_eq!(
+            Incoming::parse(notification),
+            Some(Incoming::Notification {
+                method: "cancel".to_string(),
+                params: vec![],
+            })
+        );
+        assert_eq!(Incoming::parse(Value::from(json!([1, 7, null, null]))), None);
+    }
+
+    /// Reads the events passed to the callback from the output of the server, skipping replies.
+    async fn next_event(output: &mut (impl tokio::io::AsyncRead + Unpin), buf: &mut Vec<u8>) -> Value {
+        loop {
+            if let Some((value, len)) = Value::decode(buf).unwrap() {
+                buf.drain(..len);
+                let fields = value.as_array().unwrap().to_vec();
+                if fields[0].as_u64() == Some(2) {
+                    return fields[2].as_array().unwrap()[1].as_array().unwrap()[0].clone();
+                }
+                continue;
+            }
+            let mut chunk = [0; 4096];
+            let n = output.read(&mut chunk).await.unwrap();
+            assert_ne!(n, 0, "server closed its output");
+            buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_flow() {
+        let mut os = Os::new().await.unwrap();
+        os.client.set_mock_output(json!([
+            [
+                "Creating the file",
+                {
+                    "tool_use_id": "1",
+                    "name": "fs_write",
+                    "args": {
+                        "command": "create",
+                        "file_text": "Hello, world!",
+                        "path": "/file.txt",
+                    }
+                }
+            ],
+            ["Done!"],
+        ]));
+        let tool_config =
+            serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("../tools/tool_index.json")).unwrap();
+        let conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_config,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (server_output, mut output) = tokio::io::duplex(1 << 16);
+        let server = NvimServer::new(conversation, receiver, server_output, "QChatEvent".to_string());
+
+        let client = async {
+            let mut buf = Vec::new();
+            let request = |id: u64, method: &str, params: serde_json::Value| {
+                Incoming::parse(Value::from(json!([0, id, method, params]))).unwrap()
+            };
+            sender.send(request(1, "chat", json!(["create a file"]))).unwrap();
+
+            let event = next_event(&mut output, &mut buf).await;
+            assert_eq!(event.get("type").and_then(Value::as_str), Some("chunk"));
+            assert_eq!(event.get("text").and_then(Value::as_str), Some("Creating the file"));
+
+            let event = next_event(&mut output, &mut buf).await;
+            assert_eq!(event.get("type").and_then(Value::as_str), Some("diff"));
+            assert_eq!(event.get("original").and_then(Value::as_str), Some(""));
+            assert_eq!(event.get("proposed").and_then(Value::as_str), Some("Hello, world!\n"));
+            assert_eq!(event.get("pending").and_then(Value::as_bool), Some(true));
+
+            // A new prompt is rejected until the diff is resolved
+            sender.send(request(2, "chat", json!(["again"]))).unwrap();
+            sender.send(request(3, "resolve", json!(["1", true]))).unwrap();
+
+            let event = next_event(&mut output, &mut buf).await;
+            assert_eq!(event.get("type").and_then(Value::as_str), Some("toolResult"));
+            assert_eq!(event.get("success").and_then(Value::as_bool), Some(true));
+            let event = next_event(&mut output, &mut buf).await;
+            assert_eq!(event.get("text").and_then(Value::as_str), Some("Done!"));
+            let event = next_event(&mut output, &mut buf).await;
+            assert_eq!(event.get("type").and_then(Value::as_str), Some("done"));
+            drop(sender);
+        };
+
+        let (result, ()) = tokio::join!(server.run(&mut os), client);
+        result.unwrap();
+        assert_eq!(os.fs.read_to_string("/file.txt").await.unwrap(), "Hello, world!\n");
+    }
+}
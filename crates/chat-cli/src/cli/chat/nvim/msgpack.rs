@@ -0,0 +1,405 @@
+//! The subset of MessagePack needed to speak msgpack-RPC with Neovim, see
+//! <https://github.com/msgpack/msgpack/blob/master/spec.md>.
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    /// Only used for integers that do not fit in an [i64]
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Binary(Vec<u8>),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    /// Extension types, which Neovim uses for buffer, window and tabpage handles
+    Ext(i8, Vec<u8>),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("invalid msgpack marker 0x{0:02x}")]
+    InvalidMarker(u8),
+    #[error("msgpack string is not valid utf-8")]
+    InvalidUtf8,
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Int(i) => u64::try_from(*i).ok(),
+            Value::UInt(u) => Some(*u),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// The value of the entry whose key is the string `key`, if this is a map.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k.as_str() == Some(key)).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Value::Nil => buf.push(0xc0),
+            Value::Bool(false) => buf.push(0xc2),
+            Value::Bool(true) => buf.push(0xc3),
+            Value::Int(i) => encode_int(*i, buf),
+            Value::UInt(u) => match i64::try_from(*u) {
+                Ok(i) => encode_int(i, buf),
+                Err(_) => {
+                    buf.push(0xcf);
+                    buf.extend_from_slice(&u.to_be_bytes());
+                },
+            },
+            Value::Float(f) => {
+                buf.push(0xcb);
+                buf.extend_from_slice(&f.to_be_bytes());
+            },
+            Value::String(s) => {
+                encode_len(s.len(), Some(0xa0), [0xd9, 0xda, 0xdb], buf);
+                buf.extend_from_slice(s.as_bytes());
+            },
+            Value::Binary(b) => {
+                encode_len(b.len(), None, [0xc4, 0xc5, 0xc6], buf);
+                buf.extend_from_slice(b);
+            },
+            Value::Array(values) => {
+                encode_container_len(values.len(), 0x90, [0xdc, 0xdd], buf);
+                for value in values {
+                    value.encode(buf);
+                }
+            },
+            Value::Map(entries) => {
+                encode_container_len(entries.len(), 0x80, [0xde, 0xdf], buf);
+                for (key, value) in entries {
+                    key.encode(buf);
+                    value.encode(buf);
+                }
+            },
+            Value::Ext(kind, data) => {
+                match data.len() {
+                    1 => buf.push(0xd4),
+                    2 => buf.push(0xd5),
+                    4 => buf.push(0xd6),
+                    8 => buf.push(0xd7),
+                    16 => buf.push(0xd8),
+                    len => encode_len(len, None, [0xc7, 0xc8, 0xc9], buf),
+                }
+                buf.push(*kind as u8);
+                buf.extend_from_slice(data);
+            },
+        }
+    }
+
+    /// Decodes the value at the start of `buf`, returning it with the number of bytes it took.
+    /// Returns `Ok(None)` if `buf` does not hold a complete value yet.
+    pub fn decode(buf: &[u8]) -> Result<Option<(Value, usize)>, DecodeError> {
+        let mut reader = Reader { buf, pos: 0 };
+        match reader.value() {
+            Ok(value) => Ok(Some((value, reader.pos))),
+            Err(ReadError::Incomplete) => Ok(None),
+            Err(ReadError::Invalid(err)) => Err(err),
+        }
+    }
+}
+
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Nil,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => Value::Int(i),
+                (None, Some(u)) => Value::UInt(u),
+                _ => Value::Float(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Array(values) => Value::Array(values.into_iter().map(Into::into).collect()),
+            serde_json::Value::Object(map) => {
+                Value::Map(map.into_iter().map(|(k, v)| (Value::String(k), v.into())).collect())
+            },
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+fn encode_int(i: i64, buf: &mut Vec<u8>) {
+    match i {
+        0..=0x7f => buf.push(i as u8),
+        -32..=-1 => buf.push(i as i8 as u8),
+        0x80..=0xff => buf.extend_from_slice(&[0xcc, i as u8]),
+        0x100..=0xffff => {
+            buf.push(0xcd);
+            buf.extend_from_slice(&(i as u16).to_be_bytes());
+        },
+        0x1_0000..=0xffff_ffff => {
+            buf.push(0xce);
+            buf.extend_from_slice(&(i as u32).to_be_bytes());
+        },
+        0x1_0000_0000.. => {
+            buf.push(0xcf);
+            buf.extend_from_slice(&(i as u64).to_be_bytes());
+        },
+        -0x80..=-33 => buf.extend_from_slice(&[0xd0, i as i8 as u8]),
+        -0x8000..=-0x81 => {
+            buf.push(0xd1);
+            buf.extend_from_slice(&(i as i16).to_be_bytes());
+        },
+        -0x8000_0000..=-0x8001 => {
+            buf.push(0xd2);
+            buf.extend_from_slice(&(i as i32).to_be_bytes());
+        },
+        _ => {
+            buf.push(0xd3);
+            buf.extend_from_slice(&i.to_be_bytes());
+        },
+    }
+}
+
+/// Writes the header of a string, binary or extension value. `fix` is the marker of the fixed
+/// size format, which only strings have, and `markers` those of the 8, 16 and 32 bit formats.
+fn encode_len(len: usize, fix: Option<u8>, markers: [u8; 3], buf: &mut Vec<u8>) {
+    match (len, fix) {
+        (0..32, Some(fix)) => buf.push(fix | len as u8),
+        (0..=0xff, _) => buf.extend_from_slice(&[markers[0], len as u8]),
+        (0..=0xffff, _) => {
+            buf.push(markers[1]);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        },
+        _ => {
+            buf.push(markers[2]);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        },
+    }
+}
+
+fn encode_container_len(len: usize, fix: u8, markers: [u8; 2], buf: &mut Vec<u8>) {
+    match len {
+        0..16 => buf.push(fix | len as u8),
+        16..=0xffff => {
+            buf.push(markers[0]);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        },
+        _ => {
+            buf.push(markers[1]);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        },
+    }
+}
+
+enum ReadError {
+    Incomplete,
+    Invalid(DecodeError),
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Result<&[u8], ReadError> {
+        let end = self.pos.checked_add(len).ok_or(ReadError::Incomplete)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(ReadError::Incomplete)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReadError> {
+        Ok(self.bytes(N)?.try_into().expect("slice has the requested length"))
+    }
+
+    fn u8(&mut self) -> Result<u8, ReadError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn len(&mut self, size: usize) -> Result<usize, ReadError> {
+        Ok(match size {
+            1 => self.u8()? as usize,
+            2 => u16::from_be_bytes(self.array()?) as usize,
+            _ => u32::from_be_bytes(self.array()?) as usize,
+        })
+    }
+
+    fn string(&mut self, len: usize) -> Result<Value, ReadError> {
+        match String::from_utf8(self.bytes(len)?.to_vec()) {
+            Ok(s) => Ok(Value::String(s)),
+            Err(_) => Err(ReadError::Invalid(DecodeError::InvalidUtf8)),
+        }
+    }
+
+    fn values(&mut self, len: usize) -> Result<Vec<Value>, ReadError> {
+        (0..len).map(|_| self.value()).collect()
+    }
+
+    fn entries(&mut self, len: usize) -> Result<Vec<(Value, Value)>, ReadError> {
+        (0..len).map(|_| Ok((self.value()?, self.value()?))).collect()
+    }
+
+    fn ext(&mut self, len: usize) -> Result<Value, ReadError> {
+        let kind = self.u8()? as i8;
+        Ok(Value::Ext(kind, self.bytes(len)?.to_vec()))
+    }
+
+    fn value(&mut self) -> Result<Value, ReadError> {
+        let marker = self.u8()?;
+        Ok(match marker {
+            0x00..=0x7f => Value::Int(marker as i64),
+            0x80..=0x8f => Value::Map(self.entries((marker & 0x0f) as usize)?),
+            0x90..=0x9f => Value::Array(self.values((marker & 0x0f) as usize)?),
+            0xa0..=0xbf => self.string((marker & 0x1f) as usize)?,
+            0xc0 => Value::Nil,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xc4..=0xc6 => {
+                let len = self.len(1 << (marker - 0xc4))?;
+                Value::Binary(self.bytes(len)?.to_vec())
+            },
+            0xc7..=0xc9 => {
+                let len = self.len(1 << (marker - 0xc7))?;
+                self.ext(len)?
+            },
+            0xca => Value::Float(f32::from_be_bytes(self.array()?) as f64),
+            0xcb => Value::Float(f64::from_be_bytes(self.array()?)),
+            0xcc => Value::Int(self.u8()? as i64),
+            0xcd => Value::Int(u16::from_be_bytes(self.array()?) as i64),
+            0xce => Value::Int(u32::from_be_bytes(self.array()?) as i64),
+            0xcf => {
+                let u = u64::from_be_bytes(self.array()?);
+                i64::try_from(u).map_or(Value::UInt(u), Value::Int)
+            },
+            0xd0 => Value::Int(self.u8()? as i8 as i64),
+            0xd1 => Value::Int(i16::from_be_bytes(self.array()?) as i64),
+            0xd2 => Value::Int(i32::from_be_bytes(self.array()?) as i64),
+            0xd3 => Value::Int(i64::from_be_bytes(self.array()?)),
+            0xd4..=0xd8 => self.ext(1 << (marker - 0xd4))?,
+            0xd9..=0xdb => {
+                let len = self.len(1 << (marker - 0xd9))?;
+                self.string(len)?
+            },
+            0xdc | 0xdd => {
+                let len = self.len(2 << (marker - 0xdc))?;
+                Value::Array(self.values(len)?)
+            },
+            0xde | 0xdf => {
+                let len = self.len(2 << (marker - 0xde))?;
+                Value::Map(self.entries(len)?)
+            },
+            0xe0..=0xff => Value::Int(marker as i8 as i64),
+            0xc1 => return Err(ReadError::Invalid(DecodeError::InvalidMarker(marker))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(value: Value) {
+        let mut buf = Vec::new();
+        value.encode(&mut buf);
+        assert_eq!(Value::decode(&buf), Ok(Some((value, buf.len()))));
+        // Every strict prefix is incomplete rather than invalid
+        for end in 0..buf.len() {
+            assert_eq!(Value::decode(&buf[..end]), Ok(None));
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for i in [
+            0,
+            1,
+            127,
+            128,
+            255,
+            256,
+            65535,
+            65536,
+            1 << 40,
+            i64::MAX,
+            -1,
+            -32,
+            -33,
+            -128,
+            -129,
+            i64::MIN,
+        ] {
+            roundtrip(Value::Int(i));
+        }
+        roundtrip(Value::UInt(u64::MAX));
+        roundtrip(Value::Nil);
+        roundtrip(Value::Bool(true));
+        roundtrip(Value::Float(1.5));
+        roundtrip(Value::String("héllo".to_string()));
+        roundtrip(Value::String("x".repeat(40)));
+        roundtrip(Value::String("x".repeat(300)));
+        roundtrip(Value::Binary(vec![1, 2, 3]));
+        roundtrip(Value::Array((0..20).map(Value::Int).collect()));
+        roundtrip(Value::Map(vec![("key".into(), Value::Array(vec![Value::Nil]))]));
+        roundtrip(Value::Ext(0, vec![1]));
+        roundtrip(Value::Ext(2, vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_decode() {
+        // [0, 1, "chat", ["hi"]] as sent by rpcrequest()
+        let buf = [
+            0x94, 0x00, 0x01, 0xa4, b'c', b'h', b'a', b't', 0x91, 0xa2, b'h', b'i', 0xc0,
+        ];
+        let (value, len) = Value::decode(&buf).unwrap().unwrap();
+        assert_eq!(len, buf.len() - 1);
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                Value::Int(0),
+                Value::Int(1),
+                "chat".into(),
+                Value::Array(vec!["hi".into()])
+            ])
+        );
+        assert_eq!(Value::decode(&[0xc1]), Err(DecodeError::InvalidMarker(0xc1)));
+        assert_eq!(Value::decode(&[0xa1, 0xff]), Err(DecodeError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_from_json() {
+        let value = Value::from(serde_json::json!({ "type": "chunk", "n": -2, "ok": true }));
+        assert_eq!(value.get("type").and_then(Value::as_str), Some("chunk"));
+        assert_eq!(value.get("n"), Some(&Value::Int(-2)));
+        assert_eq!(value.get("ok").and_then(Value::as_bool), Some(true));
+        assert_eq!(value.get("missing"), None);
+    }
+}
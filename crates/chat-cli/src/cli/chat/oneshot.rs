@@ -0,0 +1,108 @@
+//! Single prompts answered outside of a chat session, for commands that only need the text of one
+//! response.
+
+use std::collections::HashMap;
+
+use eyre::bail;
+use serde::de::DeserializeOwned;
+
+use super::ChatError;
+use super::cli::model::default_model_id;
+use super::conversation::ConversationState;
+use super::model_registry::{
+    self,
+    models,
+};
+use super::parser::{
+    ResponseEvent,
+    ResponseParser,
+};
+use super::tool_manager::{
+    ToolManager,
+    ToolManagerBuilder,
+};
+use crate::cli::agent::Agents;
+use crate::database::settings::Setting;
+use crate::os::Os;
+
+/// Sends `prompt` along with the context of the active agent and returns the text of the
+/// response. No tools are offered to the model, so it answers from the prompt and context alone.
+pub async fn ask(os: &mut Os, agents: Agents, model_id: Option<String>, prompt: String) -> Result<String, ChatError> {
+    let conversation_id = uuid::Uuid::new_v4().to_string();
+    let mut conversation = ConversationState::new(
+        &conversation_id,
+        agents,
+        HashMap::new(),
+        ToolManager::default(),
+        model_id,
+    )
+    .await;
+    conversation.set_next_user_message(prompt).await;
+    let conv_state = conversation
+        .as_sendable_conversation_state(os, &mut std::io::sink(), false)
+        .await?;
+
+    let mut parser = ResponseParser::new(os.client.send_message(conv_state).await?);
+    loop {
+        if let ResponseEvent::EndStream { message } = parser.recv().await? {
+            return Ok(message.content().to_string());
+        }
+    }
+}
+
+/// Creates a conversation with the tools of the agent loaded, for sessions that are driven by
+/// something other than the terminal. Nothing is printed while loading the agent and its tools.
+pub async fn conversation_with_tools(
+    os: &mut Os,
+    agent: Option<&str>,
+    model: Option<&str>,
+) -> eyre::Result<ConversationState> {
+    let agents = Agents::load(os, agent, true, &mut std::io::sink()).await;
+    conversation_with_agents(os, agents, model).await
+}
+
+/// Like [conversation_with_tools], for agents that were already loaded.
+pub async fn conversation_with_agents(
+    os: &mut Os,
+    agents: Agents,
+    model: Option<&str>,
+) -> eyre::Result<ConversationState> {
+    model_registry::init(os).await;
+    let agent_model_id = super::agent_model_id(agents.get_active(), &mut std::io::sink())?;
+    let model_id = match model {
+        Some(name) => super::model_id_from_name(name)?,
+        None => match agent_model_id.or_else(|| {
+            os.database
+                .settings
+                .get_string(Setting::ChatDefaultModel)
+                .and_then(|name| models().find(&name))
+                .map(|model| model.model_id.clone())
+        }) {
+            Some(model_id) => model_id,
+            None => default_model_id(os).await.to_owned(),
+        },
+    };
+
+    let conversation_id = uuid::Uuid::new_v4().to_string();
+    // Prompts are not offered, so nothing is ever sent on these channels.
+    let (_prompt_request_sender, prompt_request_receiver) = std::sync::mpsc::channel::<Option<String>>();
+    let (prompt_response_sender, _prompt_response_receiver) = std::sync::mpsc::channel::<Vec<String>>();
+    let mut tool_manager = ToolManagerBuilder::default()
+        .prompt_list_sender(prompt_response_sender)
+        .prompt_list_receiver(prompt_request_receiver)
+        .conversation_id(&conversation_id)
+        .agent(agents.get_active().cloned().unwrap_or_default())
+        .build(os, Box::new(std::io::sink()), false)
+        .await?;
+    let tool_config = tool_manager.load_tools(os, &mut std::io::sink()).await?;
+    Ok(ConversationState::new(&conversation_id, agents, tool_config, tool_manager, Some(model_id)).await)
+}
+
+/// Parses the JSON object in a response, which the model may surround with other text or a code
+/// fence even when asked not to.
+pub fn parse_json<T: DeserializeOwned>(response: &str) -> eyre::Result<T> {
+    let (Some(start), Some(end)) = (response.find('{'), response.rfind('}')) else {
+        bail!("no JSON object in the response");
+    };
+    Ok(serde_json::from_str(&response[start..=end])?)
+}
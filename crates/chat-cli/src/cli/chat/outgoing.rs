@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::hash::{
+    DefaultHasher,
+    Hash,
+    Hasher,
+};
+use std::io::Write;
+
+use crossterm::style::{
+    self,
+    Attribute,
+    Color,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+
+use super::token_counter::TokenCounter;
+use super::util::document_to_serde_value;
+use crate::api_client::model::{
+    ConversationState as FigConversationState,
+    ImageSource,
+    ToolResultContentBlock,
+};
+use crate::util::theme::theme;
+
+/// A single piece of data that is about to be sent to the backend for the first time, shown to
+/// the user when `chat.reviewOutgoing` is enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutgoingItem {
+    pub label: String,
+    /// The text that is sent, empty for images
+    pub content: String,
+    pub bytes: usize,
+    pub tokens: usize,
+}
+
+impl OutgoingItem {
+    fn new(label: String, content: &str) -> Self {
+        Self {
+            label,
+            content: content.to_string(),
+            bytes: content.len(),
+            tokens: TokenCounter::count_tokens(content),
+        }
+    }
+}
+
+/// Tracks what has already been reviewed so that only the delta is shown for each request.
+///
+/// The conversation history is resent with every request, so anything in it has necessarily been
+/// reviewed already. Context files however are re-read every time and may have changed.
+#[derive(Debug, Default)]
+pub struct OutgoingReview {
+    context_hashes: HashMap<String, u64>,
+}
+
+impl OutgoingReview {
+    /// Returns the items in `state` that have not been reviewed before.
+    ///
+    /// `context_files` are the `(path, content)` of the context files that are included with the
+    /// request and `tool_names` maps tool use ids to the name of the tool.
+    pub fn delta(
+        &self,
+        state: &FigConversationState,
+        context_files: &[(String, String)],
+        tool_names: &HashMap<String, String>,
+    ) -> Vec<OutgoingItem> {
+        let mut items = context_files
+            .iter()
+            .filter(|(path, content)| self.context_hashes.get(path) != Some(&hash(content)))
+            .map(|(path, content)| OutgoingItem::new(format!("Context file {path}"), content))
+            .collect::<Vec<_>>();
+
+        let message = &state.user_input_message;
+        let tool_results = message
+            .user_input_message_context
+            .as_ref()
+            .and_then(|ctx| ctx.tool_results.as_ref());
+        for result in tool_results.into_iter().flatten() {
+            let content = result
+                .content
+                .iter()
+                .map(|block| match block {
+                    ToolResultContentBlock::Text(text) => text.clone(),
+                    ToolResultContentBlock::Json(json) => document_to_serde_value(json.clone()).to_string(),
+                })
+                .collect::<String>();
+            let name = tool_names
+                .get(&result.tool_use_id)
+                .map_or("unknown tool", String::as_str);
+            items.push(OutgoingItem::new(format!("Tool result of {name}"), &content));
+        }
+
+        for (i, image) in message.images.iter().flatten().enumerate() {
+            let bytes = match &image.source {
+                ImageSource::Bytes(bytes) => bytes.len(),
+                _ => 0,
+            };
+            items.push(OutgoingItem {
+                label: format!("Image {} ({:?})", i + 1, image.format),
+                content: String::new(),
+                bytes,
+                tokens: 0,
+            });
+        }
+
+        if !message.content.is_empty() {
+            items.push(OutgoingItem::new("Message".to_string(), &message.content));
+        }
+
+        items
+    }
+
+    /// Records the context files as reviewed.
+    pub fn mark_reviewed(&mut self, context_files: &[(String, String)]) {
+        self.context_hashes.extend(
+            context_files
+                .iter()
+                .map(|(path, content)| (path.clone(), hash(content))),
+        );
+    }
+}
+
+fn hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Prints each item with its size followed by its content, then the total size.
+pub fn print_items(output: &mut impl Write, items: &[OutgoingItem]) -> std::io::Result<()> {
+    let width = items.iter().map(|item| item.label.len()).max().unwrap_or(0);
+    queue!(
+        output,
+        style::SetAttribute(Attribute::Bold),
+        style::Print("\nThe following will be sent:\n"),
+        style::SetAttribute(Attribute::Reset),
+    )?;
+    for item in items {
+        queue!(
+            output,
+            style::Print(format!("  {:<width$}  ", item.label)),
+            style::SetForegroundColor(theme().secondary),
+            style::Print(format!("{} bytes, ~{} tokens\n", item.bytes, item.tokens)),
+            style::SetForegroundColor(Color::Reset),
+        )?;
+        for line in item.content.lines() {
+            queue!(
+                output,
+                style::SetForegroundColor(theme().secondary),
+                style::Print("    │ "),
+                style::SetForegroundColor(Color::Reset),
+                style::Print(format!("{line}\n")),
+            )?;
+        }
+    }
+    let (bytes, tokens) = items.iter().fold((0, 0), |(bytes, tokens), item| {
+        (bytes + item.bytes, tokens + item.tokens)
+    });
+    execute!(
+        output,
+        style::SetAttribute(Attribute::Bold),
+        style::Print(format!("  {:<width$}  {bytes} bytes, ~{tokens} tokens\n\n", "Total")),
+        style::SetAttribute(Attribute::Reset),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::model::{
+        ToolResult,
+        ToolResultStatus,
+        UserInputMessage,
+        UserInputMessageContext,
+    };
+
+    fn state(content: &str, tool_results: Option<Vec<ToolResult>>) -> FigConversationState {
+        FigConversationState {
+            conversation_id: None,
+            user_input_message: UserInputMessage {
+                content: content.to_string(),
+                user_input_message_context: Some(UserInputMessageContext {
+                    env_state: None,
+                    git_state: None,
+                    tool_results,
+                    tools: None,
+                }),
+                user_intent: None,
+                images: None,
+                model_id: None,
+            },
+            history: None,
+        }
+    }
+
+    #[test]
+    fn test_delta() {
+        let mut review = OutgoingReview::default();
+        let files = vec![
+            ("AmazonQ.md".to_string(), "rules".to_string()),
+            ("README.md".to_string(), "readme".to_string()),
+        ];
+
+        let items = review.delta(&state("hello", None), &files, &HashMap::new());
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[2], OutgoingItem::new("Message".to_string(), "hello"));
+
+        // Only changed context files are shown once reviewed
+        review.mark_reviewed(&files);
+        let files = vec![
+            ("AmazonQ.md".to_string(), "rules".to_string()),
+            ("README.md".to_string(), "new readme".to_string()),
+        ];
+        let items = review.delta(&state("hi", None), &files, &HashMap::new());
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].label, "Context file README.md");
+    }
+
+    #[test]
+    fn test_delta_tool_results() {
+        let review = OutgoingReview::default();
+        let results = vec![ToolResult {
+            tool_use_id: "id_1".to_string(),
+            content: vec![ToolResultContentBlock::Text("output".to_string())],
+            status: ToolResultStatus::Success,
+        }];
+        let names = HashMap::from([("id_1".to_string(), "fs_read".to_string())]);
+
+        let items = review.delta(&state("", Some(results)), &[], &names);
+        assert_eq!(items, vec![OutgoingItem::new(
+            "Tool result of fs_read".to_string(),
+            "output"
+        )]);
+    }
+
+    #[test]
+    fn test_print_items() {
+        let items = vec![OutgoingItem::new("Message".to_string(), "first line\nsecond line")];
+        let mut output = Vec::new();
+        print_items(&mut output, &items).unwrap();
+        let output = strip_ansi_escapes::strip_str(String::from_utf8_lossy(&output));
+        assert!(output.contains("    │ first line\n    │ second line\n"));
+    }
+}
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::io::Write;
 
 use crossterm::style::{
@@ -45,11 +46,7 @@ use winnow::token::{
     take_while,
 };
 
-const CODE_COLOR: Color = Color::Green;
-const HEADING_COLOR: Color = Color::Magenta;
-const BLOCKQUOTE_COLOR: Color = Color::DarkGrey;
-const URL_TEXT_COLOR: Color = Color::Blue;
-const URL_LINK_COLOR: Color = Color::DarkGrey;
+use crate::util::theme::theme;
 
 const DEFAULT_RULE_WIDTH: usize = 40;
 
@@ -61,6 +58,16 @@ pub enum Error<'a> {
     Winnow(Partial<&'a str>, ErrorKind),
 }
 
+impl Error<'_> {
+    /// Describes the error without the input it failed on, so that it can be reported.
+    pub fn kind(&self) -> String {
+        match self {
+            Error::Stdio(err) => err.kind().to_string(),
+            Error::Winnow(_, kind) => kind.to_string(),
+        }
+    }
+}
+
 impl<'a> ParserError<Partial<&'a str>> for Error<'a> {
     fn from_error_kind(input: &Partial<&'a str>, kind: ErrorKind) -> Self {
         Self::Winnow(*input, kind)
@@ -193,8 +200,13 @@ fn text<'a, 'b>(
     move |i| {
         let content = take_while(1.., |t| AsChar::is_alphanum(t) || "+,.!?\"".contains(t)).parse_next(i)?;
         queue_newline_or_advance(&mut o, state, content.width())?;
+        // The default theme keeps the terminal's own foreground color
+        if theme().assistant_text == Color::Reset {
+            return queue(&mut o, style::Print(content));
+        }
+        queue(&mut o, style::SetForegroundColor(theme().assistant_text))?;
         queue(&mut o, style::Print(content))?;
-        Ok(())
+        queue(&mut o, style::ResetColor)
     }
 }
 
@@ -211,7 +223,7 @@ fn heading<'a, 'b>(
         let print = format!("{level} ");
 
         queue_newline_or_advance(&mut o, state, print.width())?;
-        queue(&mut o, style::SetForegroundColor(HEADING_COLOR))?;
+        queue(&mut o, style::SetForegroundColor(theme().heading))?;
         queue(&mut o, style::SetAttribute(Attribute::Bold))?;
         queue(&mut o, style::Print(print))
     }
@@ -281,15 +293,23 @@ fn code<'a, 'b>(
     move |i| {
         "`".parse_next(i)?;
         let code = terminated(take_until(0.., "`"), "`").parse_next(i)?;
-        let out = code.replace("&amp;", "&").replace("&gt;", ">").replace("&lt;", "<");
+        let out = unescape(code);
 
         queue_newline_or_advance(&mut o, state, out.width())?;
-        queue(&mut o, style::SetForegroundColor(Color::Green))?;
+        queue(&mut o, style::SetForegroundColor(theme().code))?;
         queue(&mut o, style::Print(out))?;
         queue(&mut o, style::ResetColor)
     }
 }
 
+/// Replaces the HTML entities in `text`, without allocating for the text that has none.
+fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.replace("&amp;", "&").replace("&gt;", ">").replace("&lt;", "<"))
+}
+
 fn blockquote<'a, 'b>(
     mut o: impl Write + 'b,
     state: &'b mut ParseState,
@@ -304,7 +324,7 @@ fn blockquote<'a, 'b>(
             .len();
         let print = "│ ".repeat(level);
 
-        queue(&mut o, style::SetForegroundColor(BLOCKQUOTE_COLOR))?;
+        queue(&mut o, style::SetForegroundColor(theme().quote))?;
         queue_newline_or_advance(&mut o, state, print.width())?;
         queue(&mut o, style::Print(print))
     }
@@ -393,7 +413,7 @@ fn citation<'a, 'b>(
         state.citations.push((num.to_owned(), link.to_owned()));
 
         queue_newline_or_advance(&mut o, state, num.width() + 1)?;
-        queue(&mut o, style::SetForegroundColor(URL_TEXT_COLOR))?;
+        queue(&mut o, style::SetForegroundColor(theme().link_text))?;
         queue(&mut o, style::Print(format!("[^{num}]")))?;
         queue(&mut o, style::ResetColor)
     }
@@ -429,9 +449,9 @@ fn url<'a, 'b>(
 
         // Only generate output if the complete URL pattern matches
         queue_newline_or_advance(&mut o, state, display.width() + 1)?;
-        queue(&mut o, style::SetForegroundColor(URL_TEXT_COLOR))?;
+        queue(&mut o, style::SetForegroundColor(theme().link_text))?;
         queue(&mut o, style::Print(format!("{display} ")))?;
-        queue(&mut o, style::SetForegroundColor(URL_LINK_COLOR))?;
+        queue(&mut o, style::SetForegroundColor(theme().link_url))?;
         state.column += link.width();
         queue(&mut o, style::Print(link))?;
         queue(&mut o, style::ResetColor)
@@ -560,7 +580,7 @@ fn codeblock_begin<'a, 'b>(
             queue(&mut o, style::Print(format!("{}\n", language).bold()))?;
         }
 
-        queue(&mut o, style::SetForegroundColor(CODE_COLOR))?;
+        queue(&mut o, style::SetForegroundColor(theme().code))?;
 
         Ok(())
     }
@@ -693,24 +713,29 @@ mod tests {
         style::SetAttribute(Attribute::Bold),
         style::Print("java\n"),
         style::SetAttribute(Attribute::Reset),
-        style::SetForegroundColor(CODE_COLOR),
+        style::SetForegroundColor(theme().code),
         style::Print("hello world!"),
         style::ResetColor,
     ]);
     validate!(code_1, "`print`", [
-        style::SetForegroundColor(CODE_COLOR),
+        style::SetForegroundColor(theme().code),
         style::Print("print"),
         style::ResetColor,
     ]);
+    validate!(code_2, "`a &lt; b &amp;&amp; c`", [
+        style::SetForegroundColor(theme().code),
+        style::Print("a < b && c"),
+        style::ResetColor,
+    ]);
     validate!(url_1, "[google](google.com)", [
-        style::SetForegroundColor(URL_TEXT_COLOR),
+        style::SetForegroundColor(theme().link_text),
         style::Print("google "),
-        style::SetForegroundColor(URL_LINK_COLOR),
+        style::SetForegroundColor(theme().link_url),
         style::Print("google.com"),
         style::ResetColor,
     ]);
     validate!(citation_1, "[[1]](google.com)", [
-        style::SetForegroundColor(URL_TEXT_COLOR),
+        style::SetForegroundColor(theme().link_text),
         style::Print("[^1]"),
         style::ResetColor,
     ]);
@@ -736,7 +761,7 @@ mod tests {
     validate!(fallback_1, "+ % @ . ? ", [style::Print("+ % @ . ?")]);
     validate!(horizontal_rule_1, "---", [style::Print("━".repeat(80))]);
     validate!(heading_1, "# Hello World", [
-        style::SetForegroundColor(HEADING_COLOR),
+        style::SetForegroundColor(theme().heading),
         style::SetAttribute(Attribute::Bold),
         style::Print("# Hello World"),
     ]);
@@ -744,7 +769,7 @@ mod tests {
     validate!(bulleted_item_2, "* bullet", [style::Print("• bullet")]);
     validate!(numbered_item_1, "1. number", [style::Print("1. number")]);
     validate!(blockquote_1, "> hello", [
-        style::SetForegroundColor(BLOCKQUOTE_COLOR),
+        style::SetForegroundColor(theme().quote),
         style::Print("│ hello"),
     ]);
     validate!(square_bracket_1, "[test]", [style::Print("[test]")]);
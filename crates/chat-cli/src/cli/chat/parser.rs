@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::time::{
     Duration,
     Instant,
@@ -15,8 +16,13 @@ use super::message::{
     AssistantMessage,
     AssistantToolUse,
 };
-use crate::api_client::model::ChatResponseStream;
 use crate::api_client::send_message_output::SendMessageOutput;
+use crate::api_client::stream_parser::{
+    Frame,
+    StreamError,
+    StreamEvent,
+    StreamParser,
+};
 use crate::telemetry::ReasonCode;
 
 #[derive(Debug, Error)]
@@ -34,6 +40,7 @@ impl RecvError {
             RecvErrorKind::Json(_) => None,
             RecvErrorKind::StreamTimeout { .. } => None,
             RecvErrorKind::UnexpectedToolUseEos { .. } => None,
+            RecvErrorKind::Stream(_) => None,
         }
     }
 }
@@ -45,6 +52,7 @@ impl ReasonCode for RecvError {
             RecvErrorKind::Json(_) => "RecvErrorJson".to_string(),
             RecvErrorKind::StreamTimeout { .. } => "RecvErrorStreamTimeout".to_string(),
             RecvErrorKind::UnexpectedToolUseEos { .. } => "RecvErrorUnexpectedToolUseEos".to_string(),
+            RecvErrorKind::Stream(_) => "RecvErrorMalformedFrames".to_string(),
         }
     }
 }
@@ -66,6 +74,8 @@ pub enum RecvErrorKind {
     Client(#[from] crate::api_client::ApiClientError),
     #[error("{0}")]
     Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Stream(#[from] StreamError),
     /// An error was encountered while waiting for the next event in the stream after a noticeably
     /// long wait time.
     ///
@@ -92,7 +102,8 @@ pub enum RecvErrorKind {
     },
 }
 
-/// State associated with parsing a [ChatResponseStream] into a [Message].
+/// State associated with parsing a [SendMessageOutput] into a [Message]. The events are parsed by
+/// a [StreamParser], this turns them into [ResponseEvent]'s and assembles the message.
 ///
 /// # Usage
 ///
@@ -102,17 +113,22 @@ pub enum RecvErrorKind {
 pub struct ResponseParser {
     /// The response to consume and parse into a sequence of [Ev].
     response: SendMessageOutput,
-    /// Buffer to hold the next event in [SendMessageOutput].
-    peek: Option<ChatResponseStream>,
+    stream: StreamParser,
+    /// Events parsed from the response but not returned yet.
+    events: VecDeque<StreamEvent>,
+    /// Whether the response has no more events.
+    ended: bool,
     /// Message identifier for the assistant's response. Randomly generated on creation.
     message_id: String,
     /// Buffer for holding the accumulated assistant response.
     assistant_text: String,
     /// Tool uses requested by the model.
     tool_uses: Vec<AssistantToolUse>,
-    /// Whether or not we are currently receiving tool use delta events. Tuple of
-    /// `Some((tool_use_id, name))` if true, [None] otherwise.
-    parsing_tool_use: Option<(String, String)>,
+    /// When the tool use currently being received started.
+    tool_use_start: Option<Instant>,
+    /// Number of events received so far, used to drop the stream with `Q_FAULT_INJECT`.
+    #[cfg(feature = "fault-injection")]
+    received_events: usize,
 }
 
 impl ResponseParser {
@@ -121,65 +137,36 @@ impl ResponseParser {
         info!(?message_id, "Generated new message id");
         Self {
             response,
-            peek: None,
+            stream: StreamParser::new(),
+            events: VecDeque::new(),
+            ended: false,
             message_id,
             assistant_text: String::new(),
             tool_uses: Vec::new(),
-            parsing_tool_use: None,
+            tool_use_start: None,
+            #[cfg(feature = "fault-injection")]
+            received_events: 0,
         }
     }
 
-    /// Consumes the associated [ConverseStreamResponse] until a valid [ResponseEvent] is parsed.
+    /// Consumes the associated [SendMessageOutput] until a valid [ResponseEvent] is parsed.
     pub async fn recv(&mut self) -> Result<ResponseEvent, RecvError> {
-        if let Some((id, name)) = self.parsing_tool_use.take() {
-            let tool_use = self.parse_tool_use(id, name).await?;
-            self.tool_uses.push(tool_use.clone());
-            return Ok(ResponseEvent::ToolUse(tool_use));
-        }
-
-        // First, handle discarding AssistantResponseEvent's that immediately precede a
-        // CodeReferenceEvent.
-        let peek = self.peek().await?;
-        if let Some(ChatResponseStream::AssistantResponseEvent { content }) = peek {
-            // Cloning to bypass borrowchecker stuff.
-            let content = content.clone();
-            self.next().await?;
-            match self.peek().await? {
-                Some(ChatResponseStream::CodeReferenceEvent(_)) => (),
-                _ => {
+        loop {
+            match self.events.pop_front() {
+                Some(StreamEvent::Text(content)) => {
                     self.assistant_text.push_str(&content);
                     return Ok(ResponseEvent::AssistantText(content));
                 },
-            }
-        }
-
-        loop {
-            match self.next().await {
-                Ok(Some(output)) => match output {
-                    ChatResponseStream::AssistantResponseEvent { content } => {
-                        self.assistant_text.push_str(&content);
-                        return Ok(ResponseEvent::AssistantText(content));
-                    },
-                    ChatResponseStream::InvalidStateEvent { reason, message } => {
-                        error!(%reason, %message, "invalid state event");
-                    },
-                    ChatResponseStream::ToolUseEvent {
-                        tool_use_id,
-                        name,
-                        input,
-                        stop,
-                    } => {
-                        debug_assert!(input.is_none(), "Unexpected initial content in first tool use event");
-                        debug_assert!(
-                            stop.is_none_or(|v| !v),
-                            "Unexpected immediate stop in first tool use event"
-                        );
-                        self.parsing_tool_use = Some((tool_use_id.clone(), name.clone()));
-                        return Ok(ResponseEvent::ToolUseStart { name });
-                    },
-                    _ => {},
+                Some(StreamEvent::ToolUseStart { name, .. }) => {
+                    self.tool_use_start = Some(Instant::now());
+                    return Ok(ResponseEvent::ToolUseStart { name });
+                },
+                Some(StreamEvent::ToolUse { id, name, input }) => {
+                    let tool_use = self.parse_tool_use(id, name, input)?;
+                    self.tool_uses.push(tool_use.clone());
+                    return Ok(ResponseEvent::ToolUse(tool_use));
                 },
-                Ok(None) => {
+                None if self.ended => {
                     let message_id = Some(self.message_id.clone());
                     let content = std::mem::take(&mut self.assistant_text);
                     let message = if self.tool_uses.is_empty() {
@@ -193,35 +180,35 @@ impl ResponseParser {
                     };
                     return Ok(ResponseEvent::EndStream { message });
                 },
-                Err(err) => return Err(err),
+                None => match self.next().await? {
+                    Some(frame) => {
+                        if let Err(err) = self.stream.push(frame, &mut self.events) {
+                            return Err(self.error(err));
+                        }
+                    },
+                    None => {
+                        self.stream.finish(&mut self.events);
+                        self.ended = true;
+                    },
+                },
             }
         }
     }
 
-    /// Consumes the response stream until a valid [ToolUse] is parsed.
-    ///
-    /// The arguments are the fields from the first [ChatResponseStream::ToolUseEvent] consumed.
-    async fn parse_tool_use(&mut self, id: String, name: String) -> Result<AssistantToolUse, RecvError> {
-        let mut tool_string = String::new();
-        let start = Instant::now();
-        while let Some(ChatResponseStream::ToolUseEvent { .. }) = self.peek().await? {
-            if let Some(ChatResponseStream::ToolUseEvent { input, stop, .. }) = self.next().await? {
-                if let Some(i) = input {
-                    tool_string.push_str(&i);
-                }
-                if let Some(true) = stop {
-                    break;
-                }
-            }
-        }
-
-        let args = match serde_json::from_str(&tool_string) {
+    /// Parses the input of a tool use received in full, or until the response ended.
+    #[allow(clippy::result_large_err)]
+    fn parse_tool_use(&mut self, id: String, name: String, input: String) -> Result<AssistantToolUse, RecvError> {
+        let args = match serde_json::from_str(&input) {
             Ok(args) => args,
-            Err(err) if !tool_string.is_empty() => {
+            Err(err) if !input.is_empty() => {
                 // If we failed deserializing after waiting for a long time, then this is most
                 // likely bedrock responding with a stop event for some reason without actually
                 // including the tool contents. Essentially, the tool was too large.
-                let time_elapsed = start.elapsed();
+                let time_elapsed = self
+                    .tool_use_start
+                    .take()
+                    .map(|start| start.elapsed())
+                    .unwrap_or_default();
                 let args = serde_json::Value::Object(
                     [(
                         "key".to_string(),
@@ -232,7 +219,7 @@ impl ResponseParser {
                     .into_iter()
                     .collect(),
                 );
-                if self.peek().await?.is_none() {
+                if self.ended && self.events.is_empty() {
                     error!(
                         "Received an unexpected end of stream after spending ~{}s receiving tool events",
                         time_elapsed.as_secs_f64()
@@ -273,34 +260,29 @@ impl ResponseParser {
         })
     }
 
-    /// Returns the next event in the [SendMessageOutput] without consuming it.
-    async fn peek(&mut self) -> Result<Option<&ChatResponseStream>, RecvError> {
-        if self.peek.is_some() {
-            return Ok(self.peek.as_ref());
-        }
-        match self.next().await? {
-            Some(v) => {
-                self.peek = Some(v);
-                Ok(self.peek.as_ref())
-            },
-            None => Ok(None),
-        }
-    }
-
-    /// Consumes the next [SendMessageOutput] event.
-    async fn next(&mut self) -> Result<Option<ChatResponseStream>, RecvError> {
-        if let Some(ev) = self.peek.take() {
-            return Ok(Some(ev));
-        }
+    /// Receives the next frame of the [SendMessageOutput]. Events that couldn't be decoded are
+    /// returned as [Frame::Malformed] for the [StreamParser] to skip.
+    async fn next(&mut self) -> Result<Option<Frame>, RecvError> {
         trace!("Attempting to recv next event");
+        #[cfg(feature = "fault-injection")]
+        {
+            let injected = crate::util::fault_injection::injector()
+                .stream_event(self.received_events)
+                .await;
+            if let Err(err) = injected {
+                return Err(self.error(err));
+            }
+            self.received_events += 1;
+        }
         let start = std::time::Instant::now();
         let result = self.response.recv().await;
         let duration = std::time::Instant::now().duration_since(start);
         match result {
             Ok(r) => {
                 trace!(?r, "Received new event");
-                Ok(r)
+                Ok(r.map(Frame::Event))
             },
+            Err(err) if err.is_malformed_frame() => Ok(Some(Frame::Malformed(err.to_string()))),
             Err(err) => {
                 if duration.as_secs() >= 59 {
                     Err(self.error(RecvErrorKind::StreamTimeout { source: err, duration }))
@@ -345,6 +327,7 @@ pub enum ResponseEvent {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api_client::model::ChatResponseStream;
 
     #[tokio::test]
     async fn test_parse() {
@@ -0,0 +1,162 @@
+//! Guards writes to paths that a misresolved relative path could silently end up at: outside the
+//! workspace, inside dotfiles or in system directories. Each agent configures it with the
+//! `pathGuard` setting of fs_write: "confirm" (the default) asks twice even for trusted tools,
+//! "block" rejects the write and "off" disables the guard.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use serde::Deserialize;
+
+use super::plan_approval::workspace_root;
+use super::tools::{
+    Tool,
+    resolve_path,
+    sanitize_path_tool_arg,
+};
+use super::workspace::Workspace;
+use crate::cli::agent::Agent;
+use crate::os::Os;
+
+const SYSTEM_DIRS: &[&str] = &[
+    "/bin",
+    "/boot",
+    "/dev",
+    "/etc",
+    "/lib",
+    "/lib64",
+    "/opt",
+    "/proc",
+    "/sbin",
+    "/sys",
+    "/usr",
+    "/var",
+    "/Library",
+    "/System",
+    "C:\\Program Files",
+    "C:\\Windows",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathGuardMode {
+    #[default]
+    Confirm,
+    Block,
+    Off,
+}
+
+impl PathGuardMode {
+    pub fn for_agent(agent: &Agent) -> Self {
+        agent
+            .tools_settings
+            .get("fs_write")
+            .and_then(|settings| settings.get("pathGuard"))
+            .and_then(|mode| serde_json::from_value(mode.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardReason {
+    OutsideWorkspace,
+    Dotfile,
+    SystemDirectory,
+}
+
+impl GuardReason {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::OutsideWorkspace => "outside the workspace",
+            Self::Dotfile => "inside a dotfile",
+            Self::SystemDirectory => "in a system directory",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuardedPath {
+    pub path: PathBuf,
+    pub reason: GuardReason,
+}
+
+impl std::fmt::Display for GuardedPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is {}", self.path.display(), self.reason.describe())
+    }
+}
+
+/// Returns the path `tool` writes to along with why it is guarded, if it is. Paths in any root of
+/// a multi-root `workspace` are inside the workspace.
+pub fn check(os: &Os, tool: &Tool, workspace: &Workspace) -> Option<GuardedPath> {
+    let Tool::FsWrite(fs_write) = tool else {
+        return None;
+    };
+    let cwd = os.env.current_dir().ok()?;
+    let path = resolve_path(&cwd.join(sanitize_path_tool_arg(os, fs_write.path())));
+    let home = os.env.home().map(|home| resolve_path(&home));
+    let root = match workspace.additional_roots().find(|root| path.starts_with(&root.path)) {
+        Some(root) => root.path.clone(),
+        None => workspace_root(&cwd),
+    };
+    let reason = classify(&path, &root, home.as_deref())?;
+    Some(GuardedPath { path, reason })
+}
+
+fn classify(path: &Path, workspace_root: &Path, home: Option<&Path>) -> Option<GuardReason> {
+    // Dotfiles directly in the home directory, unless the workspace is one of them
+    let home_entry = home.and_then(|home| Some(home.join(path.strip_prefix(home).ok()?.components().next()?)));
+    if home_entry.is_some_and(|entry| {
+        entry
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            && !workspace_root.starts_with(&entry)
+    }) {
+        return Some(GuardReason::Dotfile);
+    }
+
+    if let Ok(relative) = path.strip_prefix(workspace_root) {
+        return relative.starts_with(".git").then_some(GuardReason::Dotfile);
+    }
+    if SYSTEM_DIRS.iter().any(|dir| path.starts_with(dir)) {
+        return Some(GuardReason::SystemDirectory);
+    }
+    Some(GuardReason::OutsideWorkspace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let home = Path::new("/home/user");
+        let workspace = Path::new("/home/user/project");
+        let classify = |path: &str| classify(Path::new(path), workspace, Some(home));
+
+        assert_eq!(classify("/home/user/project/src/main.rs"), None);
+        assert_eq!(classify("/home/user/project/.github/workflows/ci.yml"), None);
+        assert_eq!(classify("/home/user/project/.git/config"), Some(GuardReason::Dotfile));
+        assert_eq!(classify("/home/user/.bashrc"), Some(GuardReason::Dotfile));
+        assert_eq!(classify("/home/user/.ssh/config"), Some(GuardReason::Dotfile));
+        assert_eq!(
+            classify("/home/user/other/main.rs"),
+            Some(GuardReason::OutsideWorkspace)
+        );
+        assert_eq!(classify("/tmp/scratch.txt"), Some(GuardReason::OutsideWorkspace));
+        assert_eq!(classify("/etc/hosts"), Some(GuardReason::SystemDirectory));
+
+        // A workspace inside a dot directory, or the home directory itself
+        let dotfiles = Path::new("/home/user/.config/nvim");
+        assert_eq!(
+            super::classify(Path::new("/home/user/.config/nvim/init.lua"), dotfiles, Some(home)),
+            None
+        );
+        assert_eq!(
+            super::classify(Path::new("/home/user/.zshrc"), home, Some(home)),
+            Some(GuardReason::Dotfile)
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use sha2::{
+    Digest,
+    Sha256,
+};
+
+use super::tools::Tool;
+
+/// Sent to the model in place of the result of the first mutating tool use in a workspace that
+/// has no approved plan, when `chat.requirePlanApproval` is enabled.
+pub const PLAN_REQUIRED_MESSAGE: &str = "Before making any changes in this workspace, present your plan as a task list using the manage_todo tool, then stop and wait for me to approve it. Do not modify files or run commands until then.";
+
+/// Sent to the model when the user rejects the presented plan.
+pub const PLAN_REJECTED_MESSAGE: &str =
+    "I did not approve the plan. Ask a follow up question clarifying how the plan should change";
+
+/// Whether the tool can modify the workspace and therefore needs an approved plan first.
+pub fn is_mutating(tool: &Tool) -> bool {
+    match tool {
+        Tool::FsWrite(_) | Tool::ExecuteCommand(_) => true,
+        Tool::Git(git) => !git.is_read_only(),
+        Tool::UseAws(use_aws) => use_aws.requires_acceptance(),
+        _ => false,
+    }
+}
+
+/// The root of the workspace containing `cwd`: the closest ancestor that is a git repository, or
+/// `cwd` itself otherwise.
+pub fn workspace_root(cwd: &Path) -> PathBuf {
+    let cwd = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+    cwd.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(&cwd)
+        .to_path_buf()
+}
+
+/// Identifies the workspace containing `cwd` in the database without storing its path.
+pub fn workspace_fingerprint(cwd: &Path) -> String {
+    hex::encode(Sha256::digest(workspace_root(cwd).to_string_lossy().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_fingerprint() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        let nested = repo.join("src").join("cli");
+        let other = dir.path().join("other");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+
+        assert_eq!(workspace_root(&nested), repo.canonicalize().unwrap());
+        assert_eq!(workspace_fingerprint(&nested), workspace_fingerprint(&repo));
+        assert_ne!(workspace_fingerprint(&other), workspace_fingerprint(&repo));
+        assert_eq!(workspace_root(&other), other.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_is_mutating() {
+        let git = |args: serde_json::Value| Tool::Git(serde_json::from_value(args).unwrap());
+        let use_aws = |operation_name: &str| {
+            Tool::UseAws(
+                serde_json::from_value(serde_json::json!({
+                    "service_name": "s3",
+                    "operation_name": operation_name,
+                    "region": "us-west-2",
+                    "label": "",
+                }))
+                .unwrap(),
+            )
+        };
+
+        assert!(!is_mutating(&git(serde_json::json!({ "command": "status" }))));
+        assert!(is_mutating(&git(
+            serde_json::json!({ "command": "commit", "message": "Fix typo" })
+        )));
+        assert!(!is_mutating(&use_aws("list-buckets")));
+        assert!(is_mutating(&use_aws("put-object")));
+    }
+}
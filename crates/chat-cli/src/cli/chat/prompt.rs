@@ -1,4 +1,8 @@
 use std::borrow::Cow;
+use std::sync::{
+    Arc,
+    Mutex,
+};
 
 use eyre::Result;
 use rustyline::completion::{
@@ -22,21 +26,28 @@ use rustyline::{
     Cmd,
     Completer,
     CompletionType,
+    ConditionalEventHandler,
     Config,
     Context,
     EditMode,
     Editor,
+    Event,
+    EventContext,
     EventHandler,
     Helper,
     Hinter,
     KeyCode,
     KeyEvent,
     Modifiers,
+    RepeatCount,
 };
 use winnow::stream::AsChar;
 
 pub use super::prompt_parser::generate_prompt;
-use super::prompt_parser::parse_prompt_components;
+use super::prompt_parser::{
+    INCOGNITO_PREFIX,
+    parse_prompt_components,
+};
 use crate::database::settings::Setting;
 use crate::os::Os;
 
@@ -52,7 +63,10 @@ pub const COMMANDS: &[&str] = &[
     "/tools trust-all",
     "/tools reset",
     "/mcp",
+    "/mcp logs",
     "/model",
+    "/model list",
+    "/model set",
     "/agent",
     "/agent help",
     "/agent list",
@@ -67,6 +81,7 @@ pub const COMMANDS: &[&str] = &[
     "/context show",
     "/context show --expand",
     "/context add",
+    "/context add-resource",
     "/context rm",
     "/context clear",
     "/hooks",
@@ -82,7 +97,18 @@ pub const COMMANDS: &[&str] = &[
     "/usage",
     "/save",
     "/load",
+    "/export",
+    "/resume",
     "/subscribe",
+    "/auto",
+    "/todo",
+    "/todo add",
+    "/todo done",
+    "/todo clear",
+    "/roots",
+    "/roots add",
+    "/roots rm",
+    "/roots index",
 ];
 
 /// Complete commands that start with a slash
@@ -305,6 +331,8 @@ pub struct ChatHelper {
     #[rustyline(Hinter)]
     hinter: ChatHinter,
     validator: MultiLineValidator,
+    /// Set by the command palette to the line it runs
+    palette_selection: Arc<Mutex<Option<String>>>,
 }
 
 impl ChatHelper {
@@ -312,6 +340,16 @@ impl ChatHelper {
     pub fn update_hinter_history(&mut self, command: &str) {
         self.hinter.update_history(command);
     }
+
+    #[cfg(unix)]
+    pub fn palette_selection(&self) -> Arc<Mutex<Option<String>>> {
+        Arc::clone(&self.palette_selection)
+    }
+
+    /// Takes the line selected in the command palette, if the line was accepted to run it.
+    pub fn take_palette_selection(&self) -> Option<String> {
+        self.palette_selection.lock().ok()?.take()
+    }
 }
 
 impl Validator for ChatHelper {
@@ -341,6 +379,10 @@ impl Highlighter for ChatHelper {
         if let Some(components) = parse_prompt_components(prompt) {
             let mut result = String::new();
 
+            if components.incognito {
+                result.push_str(&INCOGNITO_PREFIX.dark_grey().to_string());
+            }
+
             // Add profile part if present
             if let Some(profile) = components.profile {
                 result.push_str(&format!("[{}] ", profile).cyan().to_string());
@@ -387,6 +429,7 @@ pub fn rl(
         completer: ChatCompleter::new(sender, receiver),
         hinter: ChatHinter::new(history_hints_enabled),
         validator: MultiLineValidator,
+        palette_selection: Arc::default(),
     };
 
     let mut rl = Editor::with_config(config)?;
@@ -404,15 +447,49 @@ pub fn rl(
         EventHandler::Simple(Cmd::Insert(1, "\n".to_string())),
     );
 
-    // Add custom keybinding for Ctrl+F to accept hint (like fish shell)
+    // Add custom keybinding for Ctrl+F to accept hint (like fish shell), keeping forward-char
+    // everywhere else
     rl.bind_sequence(
         KeyEvent(KeyCode::Char('f'), Modifiers::CTRL),
-        EventHandler::Simple(Cmd::CompleteHint),
+        EventHandler::Conditional(Box::new(AcceptHintOrDefault)),
     );
 
     Ok(rl)
 }
 
+/// Ctrl + key combinations of the standard emacs editing set provided by rustyline, which must
+/// not be rebound by other features:
+/// - `a`/`e`, `b`/`f` - beginning/end of line, backward/forward char
+/// - `d`, `h` - delete char forward/backward
+/// - `k`, `u`, `w` - kill to end of line, to beginning of line, previous word (`k` opens the
+///   command palette on an empty line, where there is nothing to kill)
+/// - `y` - yank the last kill (`Alt + y` cycles through the kill ring)
+/// - `t` - transpose chars
+///
+/// `Alt + b`/`Alt + f` (word motion), `Alt + d`/`Alt + Backspace` (kill word) and `Alt + t`
+/// (transpose words) are provided as well.
+pub const EMACS_CTRL_KEYS: &[char] = &['a', 'b', 'd', 'e', 'f', 'h', 'k', 't', 'u', 'w', 'y'];
+
+/// Accepts the displayed hint when the cursor is at the end of the line and otherwise falls back
+/// to the default binding of the key.
+struct AcceptHintOrDefault;
+
+impl ConditionalEventHandler for AcceptHintOrDefault {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext<'_>) -> Option<Cmd> {
+        (ctx.has_hint() && ctx.pos() == ctx.line().len()).then_some(Cmd::CompleteHint)
+    }
+}
+
+/// The key that opens the fuzzy command selector together with Ctrl, configured with
+/// `chat.skimCommandKey`. Keys of the standard editing set are not allowed.
+pub fn skim_command_key(setting: Option<&str>) -> char {
+    let mut chars = setting.unwrap_or_default().chars();
+    match (chars.next(), chars.next()) {
+        (Some(key), None) if !EMACS_CTRL_KEYS.contains(&key.to_ascii_lowercase()) => key,
+        _ => 's',
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crossterm::style::Stylize;
@@ -442,6 +519,16 @@ mod tests {
         assert!(completions.contains(&"/help".to_string()));
     }
 
+    #[test]
+    fn test_skim_command_key() {
+        assert_eq!(skim_command_key(None), 's');
+        assert_eq!(skim_command_key(Some("g")), 'g');
+        assert_eq!(skim_command_key(Some("gg")), 's');
+        // Would shadow kill-word and yank
+        assert_eq!(skim_command_key(Some("w")), 's');
+        assert_eq!(skim_command_key(Some("Y")), 's');
+    }
+
     #[test]
     fn test_chat_completer_no_completion() {
         let (prompt_request_sender, _) = std::sync::mpsc::channel::<Option<String>>();
@@ -469,6 +556,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            palette_selection: Arc::default(),
         };
 
         // Test basic prompt highlighting
@@ -485,6 +573,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            palette_selection: Arc::default(),
         };
 
         // Test warning prompt highlighting
@@ -501,6 +590,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            palette_selection: Arc::default(),
         };
 
         // Test profile prompt highlighting
@@ -517,6 +607,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            palette_selection: Arc::default(),
         };
 
         // Test profile + warning prompt highlighting
@@ -536,6 +627,7 @@ mod tests {
             completer: ChatCompleter::new(prompt_request_sender, prompt_response_receiver),
             hinter: ChatHinter::new(true),
             validator: MultiLineValidator,
+            palette_selection: Arc::default(),
         };
 
         // Test invalid prompt format (should return as-is)
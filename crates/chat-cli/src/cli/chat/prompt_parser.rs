@@ -1,17 +1,26 @@
+/// Shown before the prompt while nothing from the session is being saved.
+pub const INCOGNITO_PREFIX: &str = "(incognito) ";
+
 /// Components extracted from a prompt string
 #[derive(Debug, PartialEq)]
 pub struct PromptComponents {
+    pub incognito: bool,
     pub profile: Option<String>,
     pub warning: bool,
 }
 
 /// Parse prompt components from a plain text prompt
 pub fn parse_prompt_components(prompt: &str) -> Option<PromptComponents> {
-    // Expected format: "[profile] !> " or "> " or "!> " etc.
+    // Expected format: "(incognito) [profile] !> " or "> " or "!> " etc.
     let mut profile = None;
     let mut warning = false;
     let mut remaining = prompt.trim();
 
+    let incognito = remaining.starts_with(INCOGNITO_PREFIX);
+    if incognito {
+        remaining = &remaining[INCOGNITO_PREFIX.len()..];
+    }
+
     // Check for profile pattern [profile]
     if let Some(start) = remaining.find('[') {
         if let Some(end) = remaining.find(']') {
@@ -30,7 +39,11 @@ pub fn parse_prompt_components(prompt: &str) -> Option<PromptComponents> {
 
     // Should end with "> "
     if remaining.trim_end() == ">" {
-        Some(PromptComponents { profile, warning })
+        Some(PromptComponents {
+            incognito,
+            profile,
+            warning,
+        })
     } else {
         None
     }
@@ -87,6 +100,12 @@ mod tests {
         assert_eq!(components.profile.as_deref(), Some("dev"));
         assert!(components.warning);
 
+        // Test incognito prompt
+        let components = parse_prompt_components("(incognito) [dev] > ").unwrap();
+        assert!(components.incognito);
+        assert_eq!(components.profile.as_deref(), Some("dev"));
+        assert!(!parse_prompt_components("[dev] > ").unwrap().incognito);
+
         // Test invalid prompt
         assert!(parse_prompt_components("invalid").is_none());
     }
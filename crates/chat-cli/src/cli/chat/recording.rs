@@ -0,0 +1,154 @@
+//! Recording with `q chat --record`. Everything the session prints, along with what is typed at
+//! its prompts, is written to an [asciinema v2](https://docs.asciinema.org/manual/asciicast/v2/)
+//! file that can be replayed with `asciinema play` or embedded in a web page.
+//!
+//! Output is recorded a line at a time so that secrets are masked before they are written, even
+//! when they are printed over several writes while a response streams in.
+
+use std::fs::File;
+use std::io::{
+    BufWriter,
+    Write,
+};
+use std::path::Path;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::time::{
+    Instant,
+    SystemTime,
+    UNIX_EPOCH,
+};
+
+use eyre::Result;
+
+use crate::util::redact::redact_secrets;
+
+/// Partial lines longer than this are written out anyway, e.g. a long progress bar.
+const MAX_PENDING_BYTES: usize = 16 * 1024;
+
+/// A session being recorded. Clones write to the same file, which is completed once the last one
+/// is dropped.
+#[derive(Clone)]
+pub struct Recording(Arc<Mutex<RecordingInner>>);
+
+struct RecordingInner {
+    file: BufWriter<File>,
+    start: Instant,
+    /// Output since the last complete line
+    pending: Vec<u8>,
+}
+
+impl Drop for RecordingInner {
+    fn drop(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        self.write_event(&String::from_utf8_lossy(&pending)).ok();
+        self.file.flush().ok();
+    }
+}
+
+impl Recording {
+    /// Creates the recording at `path`, overwriting it, for a terminal of `width` by `height`.
+    pub fn create(path: &Path, width: u16, height: u16) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let env = ["SHELL", "TERM"]
+            .into_iter()
+            .filter_map(|var| Some((var.to_string(), std::env::var(var).ok()?.into())))
+            .collect::<serde_json::Map<_, _>>();
+        let header = serde_json::json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": timestamp,
+            "env": env,
+        });
+        writeln!(file, "{header}")?;
+        file.flush()?;
+        Ok(Self(Arc::new(Mutex::new(RecordingInner {
+            file,
+            start: Instant::now(),
+            pending: Vec::new(),
+        }))))
+    }
+
+    /// Records `bytes` as output of the session.
+    pub fn record(&self, bytes: &[u8]) {
+        let Ok(mut inner) = self.0.lock() else {
+            return;
+        };
+        inner.pending.extend_from_slice(bytes);
+        let complete = match inner.pending.iter().rposition(|byte| *byte == b'\n') {
+            Some(index) => index + 1,
+            None if inner.pending.len() > MAX_PENDING_BYTES => utf8_boundary(&inner.pending),
+            None => return,
+        };
+        let lines = inner.pending.drain(..complete).collect::<Vec<_>>();
+        inner.write_event(&String::from_utf8_lossy(&lines)).ok();
+    }
+}
+
+impl RecordingInner {
+    fn write_event(&mut self, output: &str) -> std::io::Result<()> {
+        if output.is_empty() {
+            return Ok(());
+        }
+        let time = self.start.elapsed().as_secs_f64();
+        let event = serde_json::json!([time, "o", redact_secrets(output)]);
+        writeln!(self.file, "{event}")?;
+        self.file.flush()
+    }
+}
+
+/// The length of the longest prefix of `bytes` that doesn't end in the middle of a character.
+fn utf8_boundary(bytes: &[u8]) -> usize {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => bytes.len(),
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        Err(_) => bytes.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.cast");
+        let recording = Recording::create(&path, 120, 40).unwrap();
+        recording.record(b"> hello\n");
+        // A secret split over several writes is still masked
+        recording.record(b"your key is AKIAIOSF");
+        recording.record(b"ODNN7EXAMPLE, done\nno newline");
+        drop(recording);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 120);
+        let output = lines[1..]
+            .iter()
+            .map(|event| {
+                assert_eq!(event[1], "o");
+                event[2].as_str().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(output, ["> hello\n", "your key is <redacted>, done\n", "no newline"]);
+    }
+
+    #[test]
+    fn test_utf8_boundary() {
+        let bytes = "café".as_bytes();
+        assert_eq!(utf8_boundary(bytes), bytes.len());
+        assert_eq!(utf8_boundary(&bytes[..bytes.len() - 1]), 3);
+    }
+}
@@ -0,0 +1,592 @@
+//! `q script run`, which runs a conversation declared in a YAML file, e.g.
+//!
+//! ```yaml
+//! agent: reviewer
+//! timeout: 300
+//! steps:
+//!   - name: find the entry point
+//!     prompt: Which file contains the main function?
+//!     approve: [fs_read]
+//!     expect:
+//!       contains: [main.rs]
+//!       tools: [fs_read]
+//!   - prompt: Summarize what it does in one paragraph
+//!     export: summary.md
+//! export: transcript.html
+//! ```
+//!
+//! Each step sends its prompt and runs tools until the model is done. A tool only runs if the agent
+//! trusts it or the step lists it under `approve`, and asking for any other tool fails the step.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::Stylize;
+use eyre::{
+    Result,
+    bail,
+};
+use regex::Regex;
+use serde::Deserialize;
+
+use super::cli::export::{
+    ExportFormat,
+    render,
+};
+use super::conversation::ConversationState;
+use super::message::{
+    AssistantToolUse,
+    ToolUseResult,
+    ToolUseResultBlock,
+};
+use super::notify::{
+    Completion,
+    notify,
+};
+use super::oneshot;
+use super::parser::{
+    ResponseEvent,
+    ResponseParser,
+};
+use super::tools::{
+    Tool,
+    cancellable,
+};
+use crate::api_client::model::ToolResultStatus;
+use crate::api_client::scheduler::RequestPriority;
+use crate::cli::agent::PermissionEvalResult;
+use crate::cli::agent::content_filter::ContentFilter;
+use crate::cli::agent::egress::Egress;
+use crate::os::Os;
+use crate::util::datetime::DateTimeFormat;
+use crate::util::theme::theme;
+
+/// Approves every tool for a step.
+const APPROVE_ALL: &str = "*";
+
+/// Run a conversation declared in a YAML file, for runbooks and for regression testing agents
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct ScriptArgs {
+    #[command(subcommand)]
+    cmd: ScriptSubcommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum ScriptSubcommand {
+    /// Run the steps of a script, exiting with an error if any of their expectations are not met
+    Run {
+        /// Path of the script
+        path: PathBuf,
+        /// Agent to use instead of the one in the script
+        #[arg(long)]
+        agent: Option<String>,
+        /// Model to use instead of the one in the script
+        #[arg(long)]
+        model: Option<String>,
+        /// Run the remaining steps after a step fails
+        #[arg(long)]
+        keep_going: bool,
+        /// Do not print the responses, only the outcome of each step
+        #[arg(long, short)]
+        quiet: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Script {
+    agent: Option<String>,
+    model: Option<String>,
+    /// Seconds each step may take, including the tools it runs
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+    steps: Vec<ScriptStep>,
+    /// Path to write the transcript of the whole conversation to, as Markdown or HTML depending
+    /// on its extension
+    export: Option<PathBuf>,
+}
+
+fn default_timeout() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ScriptStep {
+    pub(super) name: Option<String>,
+    prompt: String,
+    /// Tools that may run in this step without being trusted by the agent, or `*` for all of them
+    #[serde(default)]
+    approve: Vec<String>,
+    #[serde(default)]
+    pub(super) expect: Expectations,
+    /// Path to write the final response of this step to
+    export: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Expectations {
+    /// Text the response must contain
+    #[serde(default)]
+    contains: Vec<String>,
+    /// Text the response must not contain
+    #[serde(default)]
+    not_contains: Vec<String>,
+    /// Regular expression the response must match
+    matches: Option<String>,
+    /// Tools that must have run successfully
+    #[serde(default)]
+    tools: Vec<String>,
+    /// Tools that must have been asked for and rejected, since the step does not approve them
+    #[serde(default)]
+    rejected: Vec<String>,
+}
+
+/// What happened while running a step.
+#[derive(Debug, Default)]
+pub(super) struct StepOutcome {
+    response: String,
+    tools_run: Vec<String>,
+    unapproved: Vec<String>,
+    /// Files written by tools
+    written: Vec<String>,
+}
+
+impl ScriptArgs {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        let ScriptSubcommand::Run {
+            path,
+            agent,
+            model,
+            keep_going,
+            quiet,
+        } = self.cmd;
+
+        let script = parse_script(&os.fs.read_to_string(&path).await?)?;
+        os.client.set_request_priority(RequestPriority::Background);
+        let agent = agent.or(script.agent.clone());
+        let model = model.or(script.model.clone());
+        let mut conversation = oneshot::conversation_with_tools(os, agent.as_deref(), model.as_deref()).await?;
+
+        let mut stderr = std::io::stderr();
+        let mut failed = 0;
+        let mut artifacts = Vec::new();
+        for (i, step) in script.steps.iter().enumerate() {
+            let name = step.name.clone().unwrap_or_else(|| format!("step {}", i + 1));
+            writeln!(stderr, "{} {name}", "▶".with(theme().info))?;
+
+            let outcome = match tokio::time::timeout(
+                Duration::from_secs(script.timeout),
+                run_step(os, &mut conversation, step, &mut stderr),
+            )
+            .await
+            {
+                Ok(outcome) => outcome?,
+                Err(_) => {
+                    // The conversation is left waiting on the response, so no later step can run
+                    let summary = format!("{name}: no response after {}s", script.timeout);
+                    writeln!(stderr, "{} {summary}", "✘".with(theme().error))?;
+                    let completion = completion(&conversation, false, summary, artifacts);
+                    notify_completion(os, &completion, &mut stderr).await?;
+                    return Ok(ExitCode::FAILURE);
+                },
+            };
+
+            if !quiet {
+                println!("{}\n", outcome.response.trim_end());
+            }
+            artifacts.extend(outcome.written.iter().cloned());
+            if let Some(export) = &step.export {
+                os.fs.write(export, &outcome.response).await?;
+                artifacts.push(export.to_string_lossy().into_owned());
+            }
+
+            let failures = check(&step.expect, &outcome)?;
+            if failures.is_empty() {
+                writeln!(stderr, "{} {name}", "✔".with(theme().success))?;
+                continue;
+            }
+            failed += 1;
+            writeln!(stderr, "{} {name}", "✘".with(theme().error))?;
+            for failure in failures {
+                writeln!(stderr, "    {failure}")?;
+            }
+            if !keep_going {
+                break;
+            }
+        }
+
+        if let Some(export) = &script.export {
+            let dates = DateTimeFormat::new(&os.database.settings);
+            let transcript = render(
+                &conversation,
+                ExportFormat::for_path(export),
+                &dates.date_time(dates.now()),
+            );
+            os.fs.write(export, transcript).await?;
+            artifacts.push(export.to_string_lossy().into_owned());
+        }
+
+        if failed > 0 {
+            writeln!(stderr, "\n{failed} step(s) failed")?;
+        }
+        let summary = match failed {
+            0 => format!("{} step(s) passed", script.steps.len()),
+            _ => format!("{failed} of {} step(s) failed", script.steps.len()),
+        };
+        let completion = completion(&conversation, failed == 0, summary, artifacts);
+        notify_completion(os, &completion, &mut stderr).await?;
+
+        Ok(match failed {
+            0 => ExitCode::SUCCESS,
+            _ => ExitCode::FAILURE,
+        })
+    }
+}
+
+fn completion(
+    conversation: &ConversationState,
+    succeeded: bool,
+    summary: String,
+    mut artifacts: Vec<String>,
+) -> Completion {
+    artifacts.sort();
+    artifacts.dedup();
+    Completion {
+        source: "script",
+        status: match succeeded {
+            true => "succeeded",
+            false => "failed",
+        }
+        .to_string(),
+        summary,
+        artifacts,
+        conversation_id: conversation.conversation_id().to_string(),
+    }
+}
+
+/// Sends the completion notifications, reporting the ones that failed without failing the run.
+async fn notify_completion(os: &Os, completion: &Completion, stderr: &mut impl Write) -> Result<()> {
+    for failure in notify(os, completion).await {
+        writeln!(stderr, "{} {failure}", "!".with(theme().warning))?;
+    }
+    Ok(())
+}
+
+fn parse_script(contents: &str) -> Result<Script> {
+    let script: Script = serde_yaml::from_str(contents)?;
+    if script.steps.is_empty() {
+        bail!("The script has no steps");
+    }
+    for step in &script.steps {
+        if let Some(pattern) = &step.expect.matches {
+            Regex::new(pattern)?;
+        }
+    }
+    Ok(script)
+}
+
+/// Sends the prompt of the step and runs tools until the model is done.
+pub(super) async fn run_step(
+    os: &mut Os,
+    conversation: &mut ConversationState,
+    step: &ScriptStep,
+    stderr: &mut impl Write,
+) -> Result<StepOutcome> {
+    let mut outcome = StepOutcome::default();
+    conversation.set_next_user_message(step.prompt.clone()).await;
+    let mut run_hooks = true;
+    loop {
+        let conv_state = conversation
+            .as_sendable_conversation_state(os, &mut std::io::sink(), run_hooks)
+            .await?;
+        run_hooks = false;
+
+        let mut parser = ResponseParser::new(os.client.send_message(conv_state).await?);
+        let message = loop {
+            if let ResponseEvent::EndStream { message } = parser.recv().await? {
+                break message;
+            }
+        };
+
+        outcome.response = message.content().to_string();
+        let tool_uses = message.tool_uses().map(<[_]>::to_vec).unwrap_or_default();
+        conversation.push_assistant_message(os, message);
+        if tool_uses.is_empty() {
+            return Ok(outcome);
+        }
+
+        let results = run_tools(os, conversation, step, &tool_uses, &mut outcome, stderr).await?;
+        conversation.add_tool_results(results);
+    }
+}
+
+async fn run_tools(
+    os: &mut Os,
+    conversation: &mut ConversationState,
+    step: &ScriptStep,
+    tool_uses: &[AssistantToolUse],
+    outcome: &mut StepOutcome,
+    stderr: &mut impl Write,
+) -> Result<Vec<ToolUseResult>> {
+    let mut results = Vec::new();
+    for tool_use in tool_uses {
+        let error_result = |text: String| ToolUseResult {
+            tool_use_id: tool_use.id.clone(),
+            content: vec![ToolUseResultBlock::Text(text)],
+            status: ToolResultStatus::Error,
+        };
+
+        let mut tool = match conversation.tool_manager.get_tool_from_tool_use(tool_use.clone()) {
+            Ok(tool) => tool,
+            Err(result) => {
+                results.push(result.into());
+                continue;
+            },
+        };
+        if let Tool::ManageTodo(manage_todo) = &mut tool {
+            manage_todo.set_todo_list(conversation.todo_list.clone());
+        }
+        if let Tool::WebFetch(web_fetch) = &mut tool {
+            let agent = conversation.agents.get_active();
+            web_fetch.set_egress(Egress::resolve(&os.database.settings, agent.map(|a| &a.egress)));
+        }
+        if let Tool::GrepSearch(grep_search) = &mut tool {
+            let agent = conversation.agents.get_active();
+            grep_search.set_content_filter(ContentFilter::resolve(
+                &os.database.settings,
+                agent.map(|a| &a.content_filter),
+            ));
+        }
+        if let Tool::ExecuteCommand(execute_command) = &mut tool {
+            let agent = conversation.agents.get_active();
+            execute_command.set_sandbox(agent.and_then(|a| a.sandbox.clone()));
+        }
+        if let Err(err) = tool.validate(os).await {
+            results.push(error_result(format!("Failed to validate tool parameters: {err}")));
+            continue;
+        }
+
+        let permission = match conversation.agents.trust_all_tools {
+            true => PermissionEvalResult::Allow,
+            false => conversation
+                .agents
+                .get_active()
+                .map_or(PermissionEvalResult::Ask, |agent| tool.requires_acceptance(os, agent)),
+        };
+        let approved = step
+            .approve
+            .iter()
+            .any(|name| name == APPROVE_ALL || *name == tool_use.name);
+        match permission {
+            PermissionEvalResult::Allow => (),
+            PermissionEvalResult::Ask if approved => (),
+            PermissionEvalResult::Ask => {
+                outcome.unapproved.push(tool_use.name.clone());
+                results.push(error_result(format!(
+                    "Tool use with {} was rejected because the script does not approve it",
+                    tool_use.name
+                )));
+                continue;
+            },
+            PermissionEvalResult::Deny => {
+                results.push(error_result(format!(
+                    "Tool use with {} was rejected because the arguments supplied were forbidden",
+                    tool_use.name
+                )));
+                continue;
+            },
+        }
+
+        let timeout = conversation.agents.get_active().and_then(|agent| tool.timeout(agent));
+        let result = match cancellable(tool.invoke(os, &mut std::io::sink()), timeout).await {
+            Ok(output) => {
+                if let Tool::ManageTodo(manage_todo) = &tool {
+                    if let Err(err) = manage_todo.apply(&mut conversation.todo_list) {
+                        tracing::warn!(?err, "failed to update the task list");
+                    }
+                }
+                if let Tool::FsWrite(fs_write) = &tool {
+                    outcome.written.push(fs_write.path().to_string());
+                }
+                writeln!(stderr, "  {} {}", "●".with(theme().secondary), tool.display_name())?;
+                outcome.tools_run.push(tool_use.name.clone());
+                ToolUseResult {
+                    tool_use_id: tool_use.id.clone(),
+                    content: vec![output.into()],
+                    status: ToolResultStatus::Success,
+                }
+            },
+            Err(err) => {
+                writeln!(
+                    stderr,
+                    "  {} {} failed: {err}",
+                    "●".with(theme().error),
+                    tool.display_name()
+                )?;
+                error_result(format!("An error occurred processing the tool: \n{err}"))
+            },
+        };
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+/// Describes each expectation of the step that the outcome does not meet.
+pub(super) fn check(expect: &Expectations, outcome: &StepOutcome) -> Result<Vec<String>> {
+    let mut failures = Vec::new();
+    for tool in &outcome.unapproved {
+        if !expect.rejected.contains(tool) {
+            failures.push(format!("asked to use {tool}, which the step does not approve"));
+        }
+    }
+    for text in &expect.contains {
+        if !outcome.response.contains(text.as_str()) {
+            failures.push(format!("the response does not contain {text:?}"));
+        }
+    }
+    for text in &expect.not_contains {
+        if outcome.response.contains(text.as_str()) {
+            failures.push(format!("the response contains {text:?}"));
+        }
+    }
+    if let Some(pattern) = &expect.matches {
+        if !Regex::new(pattern)?.is_match(&outcome.response) {
+            failures.push(format!("the response does not match /{pattern}/"));
+        }
+    }
+    for tool in &expect.tools {
+        if !outcome.tools_run.contains(tool) {
+            failures.push(format!("{tool} did not run"));
+        }
+    }
+    for tool in &expect.rejected {
+        if !outcome.unapproved.contains(tool) {
+            failures.push(format!("{tool} was not rejected"));
+        }
+    }
+    Ok(failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::cli::agent::Agents;
+    use crate::cli::chat::tool_manager::ToolManager;
+    use crate::cli::chat::tools::ToolSpec;
+
+    const SCRIPT: &str = r"
+agent: reviewer
+steps:
+  - name: create
+    prompt: Create the file
+    approve: [fs_write]
+    expect:
+      contains: [Done]
+      matches: ^Do
+      tools: [fs_write]
+  - prompt: Create it again
+    export: out.md
+export: transcript.html
+";
+
+    #[test]
+    fn test_parse_script() {
+        let script = parse_script(SCRIPT).unwrap();
+        assert_eq!(script.agent.as_deref(), Some("reviewer"));
+        assert_eq!(script.timeout, 300);
+        assert_eq!(script.steps.len(), 2);
+        assert_eq!(script.steps[0].approve, vec!["fs_write"]);
+        assert_eq!(script.steps[0].expect.matches.as_deref(), Some("^Do"));
+        assert_eq!(script.steps[1].name, None);
+        assert_eq!(script.steps[1].export, Some(PathBuf::from("out.md")));
+        assert_eq!(script.export, Some(PathBuf::from("transcript.html")));
+
+        assert!(parse_script("steps: []").is_err());
+        assert!(parse_script("steps:\n  - prompt: hi\n    expect:\n      matches: '('").is_err());
+        // Typos are errors rather than silently ignored expectations
+        assert!(parse_script("steps:\n  - prompt: hi\n    expect:\n      contain: [x]").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let outcome = StepOutcome {
+            response: "Done! The file is created.".to_string(),
+            tools_run: vec!["fs_write".to_string()],
+            unapproved: vec!["execute_bash".to_string(), "use_aws".to_string()],
+            written: vec![],
+        };
+        let expect = Expectations {
+            contains: vec!["created".to_string(), "deleted".to_string()],
+            not_contains: vec!["Done".to_string()],
+            matches: Some("^Done".to_string()),
+            tools: vec!["fs_write".to_string(), "fs_read".to_string()],
+            rejected: vec!["use_aws".to_string(), "fs_write".to_string()],
+        };
+        assert_eq!(check(&expect, &outcome).unwrap(), vec![
+            "asked to use execute_bash, which the step does not approve",
+            "the response does not contain \"deleted\"",
+            "the response contains \"Done\"",
+            "fs_read did not run",
+            "fs_write was not rejected",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_run_steps() {
+        let mut os = Os::new().await.unwrap();
+        let tool_use = json!({
+            "tool_use_id": "1",
+            "name": "fs_write",
+            "args": {
+                "command": "create",
+                "file_text": "Hello, world!",
+                "path": "/file.txt",
+            }
+        });
+        os.client.set_mock_output(json!([
+            ["Creating the file", tool_use],
+            ["Done!"],
+            ["Creating it again", tool_use],
+            ["I was not allowed to"],
+        ]));
+        let tool_config =
+            serde_json::from_str::<HashMap<String, ToolSpec>>(include_str!("tools/tool_index.json")).unwrap();
+        let mut conversation = ConversationState::new(
+            "fake_conv_id",
+            Agents::default(),
+            tool_config,
+            ToolManager::default(),
+            None,
+        )
+        .await;
+        let script = parse_script(SCRIPT).unwrap();
+
+        let outcome = run_step(&mut os, &mut conversation, &script.steps[0], &mut std::io::sink())
+            .await
+            .unwrap();
+        assert_eq!(outcome.response, "Done!");
+        assert_eq!(outcome.tools_run, vec!["fs_write"]);
+        assert!(check(&script.steps[0].expect, &outcome).unwrap().is_empty());
+        assert_eq!(os.fs.read_to_string("/file.txt").await.unwrap(), "Hello, world!\n");
+
+        let outcome = run_step(&mut os, &mut conversation, &script.steps[1], &mut std::io::sink())
+            .await
+            .unwrap();
+        assert_eq!(outcome.response, "I was not allowed to");
+        assert!(outcome.tools_run.is_empty());
+        assert_eq!(outcome.unapproved, vec!["fs_write"]);
+    }
+}
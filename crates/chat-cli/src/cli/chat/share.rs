@@ -0,0 +1,279 @@
+//! Live sharing with `q chat --share`. Everything the session prints, along with what is typed at
+//! its prompts, is mirrored to a Unix socket in the runtime directory, where `q attach` shows it
+//! in another terminal. Viewers can't send anything back, so tools are still only approved in the
+//! terminal that owns the session.
+
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::ExitCode;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use clap::Args;
+use eyre::{
+    Result,
+    bail,
+};
+use tokio::sync::broadcast;
+
+use super::recording::Recording;
+use crate::util::CLI_BINARY_NAME;
+
+/// How much of the transcript is kept for viewers that attach late.
+const BACKLOG_BYTES: usize = 256 * 1024;
+
+/// The directory holding the sockets of shared sessions.
+fn share_dir() -> Result<PathBuf> {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            Ok(crate::util::directories::runtime_dir()?.join("qshare"))
+        } else {
+            bail!("Sharing sessions is only supported on macOS and Linux")
+        }
+    }
+}
+
+/// A session being shared. Clones mirror to the same viewers, and the socket is removed once the
+/// last one is dropped.
+#[derive(Clone)]
+pub struct Share(Arc<ShareInner>);
+
+struct ShareInner {
+    path: PathBuf,
+    /// The latest output, replayed to viewers when they attach
+    backlog: Mutex<Vec<u8>>,
+    sender: broadcast::Sender<Arc<[u8]>>,
+}
+
+impl Drop for ShareInner {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+impl Share {
+    /// Starts sharing the session with id `session`.
+    pub fn start(session: &str) -> Result<Self> {
+        let dir = share_dir()?;
+        std::fs::create_dir_all(&dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+        }
+        Self::bind(dir.join(format!("{session}.sock")))
+    }
+
+    #[cfg(unix)]
+    fn bind(path: PathBuf) -> Result<Self> {
+        std::fs::remove_file(&path).ok();
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        let (sender, _) = broadcast::channel(1024);
+        let share = Self(Arc::new(ShareInner {
+            path,
+            backlog: Mutex::new(Vec::new()),
+            sender,
+        }));
+        tokio::spawn(accept(listener, Arc::downgrade(&share.0)));
+        Ok(share)
+    }
+
+    #[cfg(not(unix))]
+    fn bind(_path: PathBuf) -> Result<Self> {
+        bail!("Sharing sessions is only supported on macOS and Linux")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0.path
+    }
+
+    /// Sends `bytes` to every viewer.
+    pub fn send(&self, bytes: &[u8]) {
+        let Ok(mut backlog) = self.0.backlog.lock() else {
+            return;
+        };
+        backlog.extend_from_slice(bytes);
+        let excess = backlog.len().saturating_sub(BACKLOG_BYTES);
+        backlog.drain(..excess);
+        // Sent while holding the lock so that viewers attaching meanwhile see everything once
+        let _ = self.0.sender.send(bytes.into());
+    }
+}
+
+#[cfg(unix)]
+async fn accept(listener: tokio::net::UnixListener, share: std::sync::Weak<ShareInner>) {
+    use tokio::io::AsyncWriteExt;
+
+    while let Ok((mut stream, _)) = listener.accept().await {
+        let Some(share) = share.upgrade() else {
+            return;
+        };
+        let (backlog, mut receiver) = match share.backlog.lock() {
+            Ok(backlog) => (backlog.clone(), share.sender.subscribe()),
+            Err(_) => return,
+        };
+        drop(share);
+
+        tokio::spawn(async move {
+            if stream.write_all(&backlog).await.is_err() {
+                return;
+            }
+            loop {
+                match receiver.recv().await {
+                    Ok(bytes) => {
+                        if stream.write_all(&bytes).await.is_err() {
+                            return;
+                        }
+                    },
+                    Err(broadcast::error::RecvError::Lagged(_)) => (),
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+}
+
+/// Writer that mirrors everything written to it to a [Share] and a [Recording], if the session
+/// is shared or recorded.
+pub struct Mirrored<W> {
+    inner: W,
+    share: Option<Share>,
+    recording: Option<Recording>,
+}
+
+impl<W: Write> Mirrored<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            share: None,
+            recording: None,
+        }
+    }
+
+    pub fn share(&mut self, share: Share) {
+        self.share = Some(share);
+    }
+
+    pub fn record(&mut self, recording: Recording) {
+        self.recording = Some(recording);
+    }
+}
+
+impl<W: Write> Write for Mirrored<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(share) = &self.share {
+            share.send(&buf[..written]);
+        }
+        if let Some(recording) = &self.recording {
+            recording.record(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Follow a chat session shared with `q chat --share`, read-only
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct AttachArgs {
+    /// Id of the shared session, or the start of it, or the path of its socket (e.g. one forwarded
+    /// over SSH). Defaults to the only session being shared
+    pub session: Option<String>,
+}
+
+impl AttachArgs {
+    pub async fn execute(self) -> Result<ExitCode> {
+        let path = match self.session {
+            Some(session) if Path::new(&session).exists() => PathBuf::from(session),
+            session => find_socket(&share_dir()?, session.as_deref())?,
+        };
+        attach(&path).await
+    }
+}
+
+/// Finds the socket of the shared session whose id starts with `session`, or of the only shared
+/// session.
+fn find_socket(dir: &Path, session: Option<&str>) -> Result<PathBuf> {
+    let mut sockets = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let id = path.file_stem().map(|id| id.to_string_lossy()).unwrap_or_default();
+            path.extension().is_some_and(|ext| ext == "sock") && id.starts_with(session.unwrap_or_default())
+        })
+        .collect::<Vec<_>>();
+    match (sockets.len(), session) {
+        (1, _) => Ok(sockets.remove(0)),
+        (0, Some(session)) => bail!("No shared session matches {session}"),
+        (0, None) => bail!("No sessions are being shared, start one with {CLI_BINARY_NAME} chat --share"),
+        (_, _) => {
+            let ids = sockets
+                .iter()
+                .filter_map(|path| path.file_stem().map(|id| format!("\n  {}", id.to_string_lossy())))
+                .collect::<String>();
+            bail!("Several sessions are being shared, choose one of:{ids}")
+        },
+    }
+}
+
+#[cfg(unix)]
+async fn attach(path: &Path) -> Result<ExitCode> {
+    let mut stream = match tokio::net::UnixStream::connect(path).await {
+        Ok(stream) => stream,
+        Err(err) => bail!(
+            "Failed to attach to {}, the session may have ended: {err}",
+            path.display()
+        ),
+    };
+    eprintln!("Attached read-only, press Ctrl+C to detach.\n");
+    tokio::io::copy(&mut stream, &mut tokio::io::stdout()).await?;
+    eprintln!("\nThe shared session ended.");
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(not(unix))]
+async fn attach(_path: &Path) -> Result<ExitCode> {
+    bail!("Sharing sessions is only supported on macOS and Linux")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_share() {
+        let dir = tempfile::tempdir().unwrap();
+        let share = Share::bind(dir.path().join("abc.sock")).unwrap();
+        let mut output = Mirrored::new(Vec::new());
+        output.share(share.clone());
+        output.write_all(b"before, ").unwrap();
+
+        let path = find_socket(dir.path(), Some("a")).unwrap();
+        assert_eq!(path, share.path());
+        assert!(find_socket(dir.path(), Some("b")).is_err());
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        // Wait for the viewer to be subscribed before sending more
+        let mut backlog = [0; 8];
+        stream.read_exact(&mut backlog).await.unwrap();
+        output.write_all(b"after").unwrap();
+        drop(output);
+        drop(share);
+
+        let mut rest = String::new();
+        stream.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(format!("{}{rest}", String::from_utf8_lossy(&backlog)), "before, after");
+        assert!(!path.exists());
+    }
+}
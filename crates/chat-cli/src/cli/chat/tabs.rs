@@ -0,0 +1,473 @@
+//! Tabbed mode with `q chat --tabs`. Each tab is a `q chat` process of its own, run in a
+//! pseudo-terminal, so that conversations keep their own agent, tools and history while sharing
+//! the login, settings and database. Only the active tab is drawn. The others keep running, and
+//! their latest output is replayed when they are switched to.
+
+use std::process::ExitCode;
+
+use eyre::{
+    Result,
+    bail,
+};
+
+use super::ChatArgs;
+
+/// How much of the output of each tab is kept to redraw it when it is switched to.
+const BACKLOG_BYTES: usize = 256 * 1024;
+/// One for each of Alt+1..9.
+const MAX_TABS: usize = 9;
+/// Shown in the tab bar when there is room for it.
+const HINT: &str = "Alt+1..9 switch  Alt+t new tab";
+
+/// What the bytes read from the terminal at once do.
+#[derive(Debug, PartialEq, Eq)]
+enum Key {
+    /// Alt+1..9, by the index of the tab
+    SwitchTo(usize),
+    /// Alt+t
+    NewTab,
+    /// Anything else, which is sent to the active tab
+    Input,
+}
+
+fn parse_key(bytes: &[u8]) -> Key {
+    match bytes {
+        [0x1b, digit @ b'1'..=b'9'] => Key::SwitchTo(usize::from(digit - b'1')),
+        [0x1b, b't'] => Key::NewTab,
+        _ => Key::Input,
+    }
+}
+
+/// Appends `bytes` to `backlog`, dropping the oldest output beyond [BACKLOG_BYTES]. What is kept
+/// starts at a line, so that a replay doesn't begin in the middle of an escape sequence.
+fn push_backlog(backlog: &mut Vec<u8>, bytes: &[u8]) {
+    backlog.extend_from_slice(bytes);
+    let excess = backlog.len().saturating_sub(BACKLOG_BYTES);
+    if excess > 0 {
+        let start = backlog[excess..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(excess, |i| excess + i + 1);
+        backlog.drain(..start);
+    }
+}
+
+/// The labels of the tab bar, e.g. `1`, `2*` for a tab with output that wasn't seen yet.
+fn tab_labels(unseen: &[bool]) -> Vec<String> {
+    unseen
+        .iter()
+        .enumerate()
+        .map(|(i, unseen)| format!("{}{}", i + 1, if *unseen { "*" } else { "" }))
+        .collect()
+}
+
+/// The arguments of `q chat` for a tab. Only the first tab resumes the conversation of the
+/// directory and is asked the first question.
+fn tab_args(args: &ChatArgs, first: bool) -> Vec<String> {
+    let mut res = vec!["chat".to_string()];
+    if let Some(agent) = &args.agent {
+        res.extend(["--agent".to_string(), agent.clone()]);
+    }
+    if let Some(model) = &args.model {
+        res.extend(["--model".to_string(), model.clone()]);
+    }
+    if args.trust_all_tools {
+        res.push("--trust-all-tools".to_string());
+    }
+    if let Some(tools) = &args.trust_tools {
+        res.push(format!("--trust-tools={}", tools.join(",")));
+    }
+    if args.accept_large_requests {
+        res.push("--accept-large-requests".to_string());
+    }
+    if args.incognito {
+        res.push("--incognito".to_string());
+    }
+    if first {
+        if args.resume {
+            res.push("--resume".to_string());
+        }
+        if let Some(input) = &args.input {
+            res.extend(["--".to_string(), input.clone()]);
+        }
+    }
+    res
+}
+
+/// Runs `q chat` in tabs until the last one exits.
+pub async fn run(args: &ChatArgs) -> Result<ExitCode> {
+    if args.no_interactive || args.verdict {
+        bail!("--tabs can't be combined with --no-interactive or --verdict");
+    }
+    if args.share || args.record.is_some() {
+        bail!("--tabs can't be combined with --share or --record");
+    }
+    let first = tab_args(args, true);
+    let next = tab_args(args, false);
+    tokio::task::spawn_blocking(move || imp::run(first, next)).await?
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::*;
+
+    pub fn run(_first: Vec<String>, _next: Vec<String>) -> Result<ExitCode> {
+        bail!("Tabs are only supported on macOS and Linux")
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::File;
+    use std::io::{
+        IsTerminal,
+        Read,
+        Write,
+    };
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+    use std::process::{
+        Child,
+        Command,
+        Stdio,
+    };
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use crossterm::style::{
+        self,
+        Attribute,
+        Stylize,
+    };
+    use crossterm::{
+        cursor,
+        queue,
+        terminal,
+    };
+    use nix::pty::Winsize;
+
+    use super::*;
+    use crate::util::theme::theme;
+
+    /// How often the size of the terminal is checked while nothing happens.
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    enum Event {
+        Input(Vec<u8>),
+        Output(usize, Vec<u8>),
+        Closed(usize),
+    }
+
+    struct Tab {
+        /// Stays the same when the tabs before it close
+        id: usize,
+        child: Child,
+        master: File,
+        backlog: Vec<u8>,
+        unseen: bool,
+    }
+
+    struct Tabs {
+        tabs: Vec<Tab>,
+        active: usize,
+        next_id: usize,
+        /// Columns and rows of the terminal, of which the last row is the tab bar
+        size: (u16, u16),
+        events: mpsc::Sender<Event>,
+        stdout: std::io::Stdout,
+    }
+
+    pub fn run(first: Vec<String>, next: Vec<String>) -> Result<ExitCode> {
+        if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+            bail!("--tabs requires a terminal");
+        }
+        let (events, receiver) = mpsc::channel();
+        let input = events.clone();
+        std::thread::spawn(move || {
+            let mut stdin = std::io::stdin();
+            let mut buf = [0; 1024];
+            while let Ok(n @ 1..) = stdin.read(&mut buf) {
+                if input.send(Event::Input(buf[..n].to_vec())).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut tabs = Tabs {
+            tabs: Vec::new(),
+            active: 0,
+            next_id: 0,
+            size: terminal::size()?,
+            events,
+            stdout: std::io::stdout(),
+        };
+        terminal::enable_raw_mode()?;
+        queue!(tabs.stdout, terminal::EnterAlternateScreen)?;
+        let result = tabs.run(&receiver, first, next);
+        // Resets the scroll region before leaving
+        queue!(tabs.stdout, style::Print("\x1b[r"), terminal::LeaveAlternateScreen).ok();
+        tabs.stdout.flush().ok();
+        terminal::disable_raw_mode().ok();
+        for tab in &mut tabs.tabs {
+            tab.child.kill().ok();
+            tab.child.wait().ok();
+        }
+        result.map(|()| ExitCode::SUCCESS)
+    }
+
+    impl Tabs {
+        fn run(&mut self, receiver: &mpsc::Receiver<Event>, first: Vec<String>, next: Vec<String>) -> Result<()> {
+            self.open(&first)?;
+            self.redraw()?;
+            loop {
+                let event = match receiver.recv_timeout(POLL_INTERVAL) {
+                    Ok(event) => Some(event),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+                };
+                let size = terminal::size()?;
+                if size != self.size {
+                    self.size = size;
+                    for tab in &self.tabs {
+                        resize(&tab.master, self.winsize());
+                    }
+                    self.redraw()?;
+                }
+
+                match event {
+                    Some(Event::Input(bytes)) => match parse_key(&bytes) {
+                        Key::SwitchTo(index) if index < self.tabs.len() => {
+                            self.active = index;
+                            self.redraw()?;
+                        },
+                        Key::NewTab if self.tabs.len() < MAX_TABS => {
+                            self.open(&next)?;
+                            self.active = self.tabs.len() - 1;
+                            self.redraw()?;
+                        },
+                        Key::SwitchTo(_) | Key::NewTab => (),
+                        Key::Input => {
+                            if let Some(tab) = self.tabs.get_mut(self.active) {
+                                tab.master.write_all(&bytes)?;
+                            }
+                        },
+                    },
+                    Some(Event::Output(id, bytes)) => {
+                        let Some(index) = self.tabs.iter().position(|tab| tab.id == id) else {
+                            continue;
+                        };
+                        let tab = &mut self.tabs[index];
+                        push_backlog(&mut tab.backlog, &bytes);
+                        if index == self.active {
+                            self.stdout.write_all(&bytes)?;
+                        } else {
+                            tab.unseen = true;
+                        }
+                        self.draw_bar()?;
+                    },
+                    Some(Event::Closed(id)) => {
+                        let Some(index) = self.tabs.iter().position(|tab| tab.id == id) else {
+                            continue;
+                        };
+                        let mut tab = self.tabs.remove(index);
+                        tab.child.wait().ok();
+                        if self.tabs.is_empty() {
+                            return Ok(());
+                        }
+                        if self.active >= index && self.active > 0 {
+                            self.active -= 1;
+                        }
+                        self.redraw()?;
+                    },
+                    None => (),
+                }
+            }
+        }
+
+        /// The size of the pseudo-terminals, which leave out the tab bar.
+        fn winsize(&self) -> Winsize {
+            Winsize {
+                ws_row: self.size.1.saturating_sub(1).max(1),
+                ws_col: self.size.0,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            }
+        }
+
+        /// Starts `q chat` with `args` in a new tab.
+        fn open(&mut self, args: &[String]) -> Result<()> {
+            let pty = nix::pty::openpty(&self.winsize(), None)?;
+            let mut command = Command::new(std::env::current_exe()?);
+            command
+                .args(args)
+                .stdin(Stdio::from(pty.slave.try_clone()?))
+                .stdout(Stdio::from(pty.slave.try_clone()?))
+                .stderr(Stdio::from(pty.slave));
+            // Makes the pseudo-terminal the controlling terminal of the tab, so that Ctrl+C and
+            // resizes only reach the tab they are meant for.
+            unsafe {
+                command.pre_exec(|| {
+                    if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+            let child = command.spawn()?;
+            drop(command);
+
+            let id = self.next_id;
+            self.next_id += 1;
+            let master = File::from(pty.master);
+            let mut reader = master.try_clone()?;
+            let events = self.events.clone();
+            std::thread::spawn(move || {
+                let mut buf = [0; 8192];
+                // Reading fails once the tab exits and the pseudo-terminal is closed
+                while let Ok(n @ 1..) = reader.read(&mut buf) {
+                    if events.send(Event::Output(id, buf[..n].to_vec())).is_err() {
+                        return;
+                    }
+                }
+                events.send(Event::Closed(id)).ok();
+            });
+
+            self.tabs.push(Tab {
+                id,
+                child,
+                master,
+                backlog: Vec::new(),
+                unseen: false,
+            });
+            Ok(())
+        }
+
+        /// Draws the active tab from its backlog.
+        fn redraw(&mut self) -> Result<()> {
+            let rows = self.size.1.saturating_sub(1).max(1);
+            queue!(
+                self.stdout,
+                // Keeps the output of the tabs from scrolling over the tab bar
+                style::Print(format!("\x1b[1;{rows}r")),
+                terminal::Clear(terminal::ClearType::All),
+                cursor::MoveTo(0, 0),
+            )?;
+            if let Some(tab) = self.tabs.get_mut(self.active) {
+                tab.unseen = false;
+                self.stdout.write_all(&tab.backlog)?;
+            }
+            self.draw_bar()
+        }
+
+        fn draw_bar(&mut self) -> Result<()> {
+            let unseen = self.tabs.iter().map(|tab| tab.unseen).collect::<Vec<_>>();
+            let labels = tab_labels(&unseen);
+            queue!(
+                self.stdout,
+                cursor::SavePosition,
+                cursor::MoveTo(0, self.size.1.saturating_sub(1)),
+                terminal::Clear(terminal::ClearType::CurrentLine),
+            )?;
+            let mut width = 0;
+            for (i, label) in labels.iter().enumerate() {
+                let label = format!(" {label} ");
+                width += label.len();
+                if i == self.active {
+                    queue!(
+                        self.stdout,
+                        style::PrintStyledContent(label.attribute(Attribute::Reverse))
+                    )?;
+                } else {
+                    queue!(self.stdout, style::Print(label))?;
+                }
+            }
+            if width + HINT.len() + 2 <= usize::from(self.size.0) {
+                queue!(
+                    self.stdout,
+                    style::PrintStyledContent(format!("  {HINT}").with(theme().secondary))
+                )?;
+            }
+            queue!(self.stdout, cursor::RestorePosition)?;
+            self.stdout.flush()?;
+            Ok(())
+        }
+    }
+
+    fn resize(master: &File, winsize: Winsize) {
+        // SAFETY: TIOCSWINSZ only reads the winsize it is given
+        unsafe {
+            libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &winsize);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::{
+        Cli,
+        RootSubcommand,
+    };
+    use crate::util::CHAT_BINARY_NAME;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key(b"\x1b1"), Key::SwitchTo(0));
+        assert_eq!(parse_key(b"\x1b9"), Key::SwitchTo(8));
+        assert_eq!(parse_key(b"\x1bt"), Key::NewTab);
+        assert_eq!(parse_key(b"\x1b0"), Key::Input);
+        assert_eq!(parse_key(b"1"), Key::Input);
+        assert_eq!(parse_key(b"\x1b[A"), Key::Input);
+    }
+
+    #[test]
+    fn test_push_backlog() {
+        let mut backlog = Vec::new();
+        push_backlog(&mut backlog, b"hello\n");
+        assert_eq!(backlog, b"hello\n");
+
+        let line = [b'a'; 1023];
+        for _ in 0..BACKLOG_BYTES / 1024 + 1 {
+            push_backlog(&mut backlog, &line);
+            push_backlog(&mut backlog, b"\n");
+        }
+        assert!(backlog.len() <= BACKLOG_BYTES);
+        assert!(backlog.starts_with(&line));
+        assert_eq!(tab_labels(&[false, true]), ["1", "2*"]);
+    }
+
+    #[test]
+    fn test_tab_args() {
+        let Some(RootSubcommand::Chat(args)) = Cli::parse_from([
+            CHAT_BINARY_NAME,
+            "chat",
+            "--tabs",
+            "--agent",
+            "dev",
+            "--trust-tools=fs_read,git",
+            "--resume",
+            "fix the build",
+        ])
+        .subcommand
+        else {
+            panic!("expected the chat subcommand");
+        };
+        assert_eq!(tab_args(&args, true), [
+            "chat",
+            "--agent",
+            "dev",
+            "--trust-tools=fs_read,git",
+            "--resume",
+            "--",
+            "fix the build"
+        ]);
+        assert_eq!(tab_args(&args, false), [
+            "chat",
+            "--agent",
+            "dev",
+            "--trust-tools=fs_read,git"
+        ]);
+    }
+}
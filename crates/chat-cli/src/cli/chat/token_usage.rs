@@ -0,0 +1,108 @@
+//! Tokens used by the requests of a session. The service doesn't report token counts in its
+//! response metadata, so they are estimated with [super::token_counter] from what is sent and
+//! received, the same way the context window is measured.
+
+/// Tokens of the requests made for one or more turns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TurnUsage {
+    /// The prompt and the tool results sent for it, each adding a request
+    pub requests: usize,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+}
+
+impl std::ops::AddAssign for TurnUsage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.requests += rhs.requests;
+        self.input_tokens += rhs.input_tokens;
+        self.output_tokens += rhs.output_tokens;
+    }
+}
+
+/// The usage of each turn of a session, from the first prompt on.
+#[derive(Debug, Default)]
+pub struct TokenUsage {
+    turns: Vec<TurnUsage>,
+    /// Input tokens of the request being answered
+    pending_input: Option<usize>,
+}
+
+impl TokenUsage {
+    /// Starts a new turn for a prompt of the user.
+    pub fn start_turn(&mut self) {
+        self.turns.push(TurnUsage::default());
+    }
+
+    pub fn request_sent(&mut self, input_tokens: usize) {
+        self.pending_input = Some(input_tokens);
+    }
+
+    /// Adds the request that was just answered to the current turn. Requests that fail are left
+    /// out, since what they used isn't known.
+    pub fn response_received(&mut self, output_tokens: usize) {
+        let Some(input_tokens) = self.pending_input.take() else {
+            return;
+        };
+        if self.turns.is_empty() {
+            self.start_turn();
+        }
+        if let Some(turn) = self.turns.last_mut() {
+            *turn += TurnUsage {
+                requests: 1,
+                input_tokens,
+                output_tokens,
+            };
+        }
+    }
+
+    pub fn turns(&self) -> &[TurnUsage] {
+        &self.turns
+    }
+
+    pub fn total(&self) -> TurnUsage {
+        let mut total = TurnUsage::default();
+        for turn in &self.turns {
+            total += *turn;
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_usage() {
+        let mut usage = TokenUsage::default();
+        usage.start_turn();
+        usage.request_sent(1000);
+        usage.response_received(200);
+        usage.request_sent(1500);
+        usage.response_received(100);
+        usage.start_turn();
+        usage.request_sent(1800);
+        // A failed request doesn't count
+        usage.request_sent(1800);
+        usage.response_received(50);
+        usage.response_received(50);
+
+        assert_eq!(usage.turns(), [
+            TurnUsage {
+                requests: 2,
+                input_tokens: 2500,
+                output_tokens: 300,
+            },
+            TurnUsage {
+                requests: 1,
+                input_tokens: 1800,
+                output_tokens: 50,
+            },
+        ]);
+        assert_eq!(usage.total(), TurnUsage {
+            requests: 3,
+            input_tokens: 4300,
+            output_tokens: 350,
+        });
+    }
+}
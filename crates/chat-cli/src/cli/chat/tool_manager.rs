@@ -21,10 +21,12 @@ use std::sync::atomic::{
 use std::sync::{
     Arc,
     RwLock as SyncRwLock,
+    Weak,
 };
 use std::time::{
     Duration,
     Instant,
+    SystemTime,
 };
 
 use crossterm::{
@@ -58,6 +60,7 @@ use crate::api_client::model::{
     ToolResultContentBlock,
     ToolResultStatus,
 };
+use crate::cli::agent::egress::Egress;
 use crate::cli::agent::{
     Agent,
     McpServerConfig,
@@ -76,9 +79,19 @@ use crate::cli::chat::tools::execute::ExecuteCommand;
 use crate::cli::chat::tools::fs_read::FsRead;
 use crate::cli::chat::tools::fs_write::FsWrite;
 use crate::cli::chat::tools::gh_issue::GhIssue;
+use crate::cli::chat::tools::git::Git;
+use crate::cli::chat::tools::grep_search::GrepSearch;
 use crate::cli::chat::tools::knowledge::Knowledge;
+use crate::cli::chat::tools::manage_todo::ManageTodo;
 use crate::cli::chat::tools::thinking::Thinking;
 use crate::cli::chat::tools::use_aws::UseAws;
+#[cfg(feature = "wasm-plugins")]
+use crate::cli::chat::tools::wasm_tool::WasmTool;
+use crate::cli::chat::tools::wasm_tool::{
+    WasmToolConfig,
+    agent_wasm_tools,
+};
+use crate::cli::chat::tools::web_fetch::WebFetch;
 use crate::cli::chat::tools::{
     Tool,
     ToolOrigin,
@@ -94,6 +107,7 @@ use crate::os::Os;
 use crate::telemetry::TelemetryThread;
 use crate::util::MCP_SERVER_TOOL_DELIMITER;
 use crate::util::directories::home_dir;
+use crate::util::theme::theme;
 
 const NAMESPACE_DELIMITER: &str = "___";
 // This applies for both mcp server and tool name since in the end the tool name as seen by the
@@ -183,6 +197,8 @@ impl ToolManagerBuilder {
         interactive: bool,
     ) -> eyre::Result<ToolManager> {
         let McpServerConfig { mcp_servers } = self.agent.as_ref().map(|a| a.mcp_servers.clone()).unwrap_or_default();
+        let egress = Egress::resolve(&os.database.settings, self.agent.as_ref().map(|a| &a.egress));
+        let mcp_config_paths = self.agent.as_ref().map(|a| a.mcp_config_paths(os)).unwrap_or_default();
         debug_assert!(self.conversation_id.is_some());
         let conversation_id = self.conversation_id.ok_or(eyre::eyre!("Missing conversation id"))?;
 
@@ -203,13 +219,13 @@ impl ToolManagerBuilder {
                 if server_name.contains(MCP_SERVER_TOOL_DELIMITER) {
                     let _ = queue!(
                         output,
-                        style::SetForegroundColor(style::Color::Red),
+                        style::SetForegroundColor(theme().error),
                         style::Print("✗ Invalid server name "),
-                        style::SetForegroundColor(style::Color::Blue),
+                        style::SetForegroundColor(theme().label),
                         style::Print(&server_name),
                         style::ResetColor,
                         style::Print(". Server name cannot contain "),
-                        style::SetForegroundColor(style::Color::Yellow),
+                        style::SetForegroundColor(theme().warning),
                         style::Print(MCP_SERVER_TOOL_DELIMITER),
                         style::ResetColor,
                         style::Print("\n")
@@ -218,20 +234,27 @@ impl ToolManagerBuilder {
                 } else if server_name == "builtin" {
                     let _ = queue!(
                         output,
-                        style::SetForegroundColor(style::Color::Red),
+                        style::SetForegroundColor(theme().error),
                         style::Print("✗ Invalid server name "),
-                        style::SetForegroundColor(style::Color::Blue),
+                        style::SetForegroundColor(theme().label),
                         style::Print(&server_name),
                         style::ResetColor,
                         style::Print(". Server name cannot contain reserved word "),
-                        style::SetForegroundColor(style::Color::Yellow),
+                        style::SetForegroundColor(theme().warning),
                         style::Print("builtin"),
                         style::ResetColor,
                         style::Print(" (it is used to denote native tools)\n")
                     );
                     None
                 } else {
-                    let custom_tool_client = CustomToolClient::from_config(server_name.clone(), server_config);
+                    let url = server_config
+                        .url
+                        .as_deref()
+                        .filter(|_| !server_config.transport.is_stdio());
+                    let custom_tool_client = match url.map(|url| egress.check_url(url)) {
+                        Some(Err(violation)) => Err(violation.into()),
+                        _ => CustomToolClient::from_config(server_name.clone(), server_config, &egress),
+                    };
                     Some((server_name, custom_tool_client))
                 }
             })
@@ -252,96 +275,60 @@ impl ToolManagerBuilder {
         {
             let (tx, mut rx) = tokio::sync::mpsc::channel::<LoadingMsg>(50);
             let disabled_servers_display_clone = disabled_servers_display.clone();
+            let mut progress = LoadingProgress::new(loading_servers.keys().cloned().collect());
             (
                 Some(tokio::task::spawn(async move {
-                    let mut spinner_logo_idx: usize = 0;
-                    let mut complete: usize = 0;
-                    let mut failed: usize = 0;
-
                     // Show disabled servers immediately
                     for server_name in &disabled_servers_display_clone {
                         queue_disabled_message(server_name, &mut output)?;
                     }
 
                     if total > 0 {
-                        queue_init_message(spinner_logo_idx, complete, failed, total, &mut output)?;
+                        progress.draw(&mut output)?;
                     }
 
                     loop {
                         match tokio::time::timeout(Duration::from_millis(50), rx.recv()).await {
                             Ok(Some(recv_result)) => match recv_result {
                                 LoadingMsg::Done { name, time } => {
-                                    complete += 1;
-                                    execute!(
-                                        output,
-                                        cursor::MoveToColumn(0),
-                                        cursor::MoveUp(1),
-                                        terminal::Clear(terminal::ClearType::CurrentLine),
-                                    )?;
+                                    progress.clear(&mut output)?;
+                                    progress.finish(&name, true);
                                     queue_success_message(&name, &time, &mut output)?;
-                                    queue_init_message(spinner_logo_idx, complete, failed, total, &mut output)?;
+                                    progress.draw(&mut output)?;
                                 },
                                 LoadingMsg::Error { name, msg, time } => {
-                                    failed += 1;
-                                    execute!(
-                                        output,
-                                        cursor::MoveToColumn(0),
-                                        cursor::MoveUp(1),
-                                        terminal::Clear(terminal::ClearType::CurrentLine),
-                                    )?;
+                                    progress.clear(&mut output)?;
+                                    progress.finish(&name, false);
                                     queue_failure_message(&name, &msg, time.as_str(), &mut output)?;
-                                    queue_init_message(spinner_logo_idx, complete, failed, total, &mut output)?;
+                                    progress.draw(&mut output)?;
                                 },
                                 LoadingMsg::Warn { name, msg, time } => {
-                                    complete += 1;
-                                    execute!(
-                                        output,
-                                        cursor::MoveToColumn(0),
-                                        cursor::MoveUp(1),
-                                        terminal::Clear(terminal::ClearType::CurrentLine),
-                                    )?;
+                                    progress.clear(&mut output)?;
+                                    progress.finish(&name, true);
                                     let msg = eyre::eyre!(msg.to_string());
                                     queue_warn_message(&name, &msg, time.as_str(), &mut output)?;
-                                    queue_init_message(spinner_logo_idx, complete, failed, total, &mut output)?;
+                                    progress.draw(&mut output)?;
                                 },
                                 LoadingMsg::Terminate { still_loading } => {
+                                    progress.clear(&mut output)?;
                                     if !still_loading.is_empty() && total > 0 {
-                                        execute!(
-                                            output,
-                                            cursor::MoveToColumn(0),
-                                            cursor::MoveUp(1),
-                                            terminal::Clear(terminal::ClearType::CurrentLine),
-                                        )?;
                                         let msg = still_loading.iter().fold(String::new(), |mut acc, server_name| {
                                             acc.push_str(format!("\n - {server_name}").as_str());
                                             acc
                                         });
                                         let msg = eyre::eyre!(msg);
-                                        queue_incomplete_load_message(complete, total, &msg, &mut output)?;
-                                    } else if total > 0 {
-                                        // Clear the loading line if we have enabled servers
-                                        execute!(
-                                            output,
-                                            cursor::MoveToColumn(0),
-                                            cursor::MoveUp(1),
-                                            terminal::Clear(terminal::ClearType::CurrentLine),
-                                        )?;
+                                        queue_incomplete_load_message(progress.complete, total, &msg, &mut output)?;
                                     }
                                     execute!(output, style::Print("\n"),)?;
                                     break;
                                 },
                             },
-                            Err(_e) => {
-                                spinner_logo_idx = (spinner_logo_idx + 1) % SPINNER_CHARS.len();
-                                execute!(
-                                    output,
-                                    cursor::SavePosition,
-                                    cursor::MoveToColumn(0),
-                                    cursor::MoveUp(1),
-                                    style::Print(SPINNER_CHARS[spinner_logo_idx]),
-                                    cursor::RestorePosition
-                                )?;
+                            Err(_e) if total > 0 => {
+                                progress.tick();
+                                progress.clear(&mut output)?;
+                                progress.draw(&mut output)?;
                             },
+                            Err(_e) => (),
                             _ => break,
                         }
                         output.flush()?;
@@ -535,6 +522,9 @@ impl ToolManagerBuilder {
                             Err(e) => {
                                 // Log error to chat Log
                                 error!("Error loading server {server_name}: {:?}", e);
+                                // Successful loads are recorded in [process_tool_specs]
+                                let _ =
+                                    telemetry_clone.send_mcp_server_init(conv_id_clone.clone(), Some(e.to_string()), 0);
                                 // Maintain a record of the server load:
                                 let mut buf_writer = BufWriter::new(&mut record_temp_buf);
                                 let _ = queue_failure_message(server_name.as_str(), &e, &time_taken, &mut buf_writer);
@@ -610,9 +600,6 @@ impl ToolManagerBuilder {
                 },
                 Err(e) => {
                     error!("Error initializing mcp client for server {}: {:?}", name, &e);
-                    os.telemetry
-                        .send_mcp_server_init(conversation_id.clone(), Some(e.to_string()), 0)
-                        .ok();
                     let _ = messenger.send_tools_list_result(Err(e)).await;
                 },
             }
@@ -622,17 +609,20 @@ impl ToolManagerBuilder {
         let sender = self.prompt_list_sender.take();
         let receiver = self.prompt_list_receiver.take();
         let prompts = Arc::new(SyncRwLock::new(HashMap::default()));
-        // TODO: accommodate hot reload of mcp servers
+        // The clients are shared with the task so that it keeps working when the servers are
+        // reloaded, see [ToolManager::reload]
+        let prompt_clients = Arc::new(SyncRwLock::new(downgrade_clients(&clients)));
         if let (Some(sender), Some(receiver)) = (sender, receiver) {
-            let clients = clients.iter().fold(HashMap::new(), |mut acc, (n, c)| {
-                acc.insert(n.clone(), Arc::downgrade(c));
-                acc
-            });
+            let prompt_clients_clone = prompt_clients.clone();
             let prompts_clone = prompts.clone();
             tokio::task::spawn_blocking(move || {
                 let receiver = Arc::new(std::sync::Mutex::new(receiver));
                 loop {
                     let search_word = receiver.lock().map_err(|e| eyre::eyre!("{:?}", e))?.recv()?;
+                    let clients = prompt_clients_clone
+                        .read()
+                        .map_err(|e| eyre::eyre!("Error retrieving read lock on clients for tab complete {}", e))?
+                        .clone();
                     if clients
                         .values()
                         .any(|client| client.upgrade().is_some_and(|c| c.is_prompts_out_of_date()))
@@ -707,10 +697,14 @@ impl ToolManagerBuilder {
             });
         }
 
+        let mcp_config = McpConfigSnapshot::take(os, mcp_config_paths).await;
+
         Ok(ToolManager {
             conversation_id,
             clients,
             prompts,
+            prompt_clients,
+            mcp_config,
             pending_clients: pending,
             notify: Some(notify),
             loading_status_sender,
@@ -784,6 +778,44 @@ type ServerName = String;
 /// tool name).
 type NewToolSpecs = Arc<Mutex<HashMap<ServerName, (HashMap<ModelToolName, ToolInfo>, Vec<ToolSpec>)>>>;
 
+/// Weak references to the clients of each server, so that holding them does not keep the server
+/// processes alive.
+type PromptClients = Arc<SyncRwLock<HashMap<ServerName, Weak<CustomToolClient>>>>;
+
+fn downgrade_clients(
+    clients: &HashMap<ServerName, Arc<CustomToolClient>>,
+) -> HashMap<ServerName, Weak<CustomToolClient>> {
+    clients
+        .iter()
+        .map(|(name, client)| (name.clone(), Arc::downgrade(client)))
+        .collect()
+}
+
+/// The files MCP servers are configured in, along with when each was last modified (or [None] if
+/// it does not exist).
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+struct McpConfigSnapshot(Vec<(PathBuf, Option<SystemTime>)>);
+
+impl McpConfigSnapshot {
+    async fn take(os: &Os, paths: Vec<PathBuf>) -> Self {
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            let modified = os
+                .fs
+                .symlink_metadata(&path)
+                .await
+                .ok()
+                .and_then(|metadata| metadata.modified().ok());
+            files.push((path, modified));
+        }
+        Self(files)
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        self.0.iter().map(|(path, _)| path.clone()).collect()
+    }
+}
+
 #[derive(Default, Debug)]
 /// Manages the lifecycle and interactions with tools from various sources, including MCP servers.
 /// This struct is responsible for initializing tools, handling tool requests, and maintaining
@@ -818,6 +850,13 @@ pub struct ToolManager {
     /// cases where multiple servers offer prompts with the same name.
     pub prompts: Arc<SyncRwLock<HashMap<String, Vec<PromptBundle>>>>,
 
+    /// The clients the prompt cache is filled from when prompts are tab completed.
+    prompt_clients: PromptClients,
+
+    /// Modification times of the files the MCP servers were configured in, as of when they were
+    /// loaded. Used to tell when the servers need to be reloaded.
+    mcp_config: McpConfigSnapshot,
+
     /// A notifier to understand if the initial loading has completed.
     /// This is only used for initial loading and is discarded after.
     notify: Option<Arc<Notify>>,
@@ -852,6 +891,10 @@ pub struct ToolManager {
     /// List of disabled MCP server names for display purposes
     disabled_servers: Vec<String>,
 
+    /// Tools backed by WebAssembly modules that are declared in the agent config, keyed by tool
+    /// name. Unlike tools from MCP servers these are not namespaced.
+    pub wasm_tools: HashMap<String, WasmToolConfig>,
+
     /// A collection of preferences that pertains to the conversation.
     /// As far as tool manager goes, this is relevant for tool and server filters
     pub agent: Arc<Mutex<Agent>>,
@@ -865,11 +908,14 @@ impl Clone for ToolManager {
             has_new_stuff: self.has_new_stuff.clone(),
             new_tool_specs: self.new_tool_specs.clone(),
             prompts: self.prompts.clone(),
+            prompt_clients: self.prompt_clients.clone(),
+            mcp_config: self.mcp_config.clone(),
             tn_map: self.tn_map.clone(),
             schema: self.schema.clone(),
             is_interactive: self.is_interactive,
             mcp_load_record: self.mcp_load_record.clone(),
             disabled_servers: self.disabled_servers.clone(),
+            wasm_tools: self.wasm_tools.clone(),
             ..Default::default()
         }
     }
@@ -884,7 +930,8 @@ impl ToolManager {
         let tx = self.loading_status_sender.take();
         let notify = self.notify.take();
         self.schema = {
-            let tool_list = &self.agent.lock().await.tools;
+            let agent = self.agent.lock().await;
+            let tool_list = &agent.tools;
             let is_allow_all = tool_list.len() == 1 && tool_list.first().is_some_and(|n| n == "*");
             let is_allow_native = tool_list.iter().any(|t| t.as_str() == "@builtin");
             let mut tool_specs =
@@ -932,6 +979,29 @@ impl ToolManager {
                 });
             }
 
+            let mut wasm_tools = agent_wasm_tools(&agent);
+            wasm_tools.retain(|name, _| {
+                if tool_specs.contains_key(name) {
+                    warn!("Wasm tool {name} conflicts with a built-in tool and will not be loaded");
+                    return false;
+                }
+                is_allow_all || tool_list.contains(name)
+            });
+            self.wasm_tools.clear();
+            for (name, mut config) in wasm_tools {
+                match config.describe(os, &name).await {
+                    Ok(()) => {
+                        self.wasm_tools.insert(name, config);
+                    },
+                    Err(err) => warn!(?err, "Wasm tool {name} will not be loaded"),
+                }
+            }
+            tool_specs.extend(
+                self.wasm_tools
+                    .iter()
+                    .map(|(name, config)| (name.clone(), config.tool_spec(name))),
+            );
+
             tool_specs
         };
         let load_tools = self
@@ -939,7 +1009,7 @@ impl ToolManager {
             .values()
             .map(|c| {
                 let clone = Arc::clone(c);
-                async move { clone.init().await }
+                async move { clone.init_with_retries().await }
             })
             .collect::<Vec<_>>();
         let initial_poll = stream::iter(load_tools)
@@ -1059,6 +1129,17 @@ impl ToolManager {
             "report_issue" => Tool::GhIssue(serde_json::from_value::<GhIssue>(value.args).map_err(map_err)?),
             "thinking" => Tool::Thinking(serde_json::from_value::<Thinking>(value.args).map_err(map_err)?),
             "knowledge" => Tool::Knowledge(serde_json::from_value::<Knowledge>(value.args).map_err(map_err)?),
+            "manage_todo" => Tool::ManageTodo(serde_json::from_value::<ManageTodo>(value.args).map_err(map_err)?),
+            "web_fetch" => Tool::WebFetch(serde_json::from_value::<WebFetch>(value.args).map_err(map_err)?),
+            "grep_search" => Tool::GrepSearch(serde_json::from_value::<GrepSearch>(value.args).map_err(map_err)?),
+            "git" => Tool::Git(serde_json::from_value::<Git>(value.args).map_err(map_err)?),
+            #[cfg(feature = "wasm-plugins")]
+            name if self.wasm_tools.contains_key(name) => Tool::Wasm(WasmTool {
+                name: name.to_string(),
+                config: self.wasm_tools[name].clone(),
+                args: value.args,
+                egress: Default::default(),
+            }),
             // Note that this name is namespaced with server_name{DELIMITER}tool_name
             name => {
                 // Note: tn_map also has tools that underwent no transformation. In otherwords, if
@@ -1346,6 +1427,47 @@ impl ToolManager {
     pub async fn pending_clients(&self) -> Vec<String> {
         self.pending_clients.read().await.iter().cloned().collect::<Vec<_>>()
     }
+
+    /// Whether any of the files the MCP servers are configured in changed since they were loaded.
+    pub async fn is_mcp_config_stale(&self, os: &Os) -> bool {
+        McpConfigSnapshot::take(os, self.mcp_config.paths()).await != self.mcp_config
+    }
+
+    /// Takes the changes made to the MCP config files so far as seen, without reloading the
+    /// servers.
+    pub async fn acknowledge_mcp_config(&mut self, os: &Os) {
+        self.mcp_config = McpConfigSnapshot::take(os, self.mcp_config.paths()).await;
+    }
+
+    /// Replaces the MCP servers with the ones `agent` is configured with and loads their tools,
+    /// which are returned like [Self::load_tools] does. The servers that were running are shut down
+    /// once the new ones are in place.
+    pub async fn reload(
+        &mut self,
+        os: &mut Os,
+        agent: Agent,
+        stderr: &mut impl Write,
+    ) -> eyre::Result<HashMap<String, ToolSpec>> {
+        let mut tool_manager = ToolManagerBuilder::default()
+            .conversation_id(&self.conversation_id)
+            .agent(agent)
+            .build(os, Box::new(std::io::stderr()), self.is_interactive)
+            .await?;
+
+        // Hand the prompt cache and the task that tab completes prompts over to the new servers
+        if let Ok(mut prompt_clients) = self.prompt_clients.write() {
+            *prompt_clients = downgrade_clients(&tool_manager.clients);
+        }
+        if let Ok(mut prompts) = self.prompts.write() {
+            prompts.clear();
+        }
+        tool_manager.prompt_clients = self.prompt_clients.clone();
+        tool_manager.prompts = self.prompts.clone();
+
+        let tool_specs = tool_manager.load_tools(os, stderr).await?;
+        *self = tool_manager;
+        Ok(tool_specs)
+    }
 }
 
 #[inline]
@@ -1463,63 +1585,125 @@ fn sanitize_name(orig: String, regex: &regex::Regex, hasher: &mut impl Hasher) -
 fn queue_success_message(name: &str, time_taken: &str, output: &mut impl Write) -> eyre::Result<()> {
     Ok(queue!(
         output,
-        style::SetForegroundColor(style::Color::Green),
+        style::SetForegroundColor(theme().success),
         style::Print("✓ "),
-        style::SetForegroundColor(style::Color::Blue),
+        style::SetForegroundColor(theme().label),
         style::Print(name),
         style::ResetColor,
         style::Print(" loaded in "),
-        style::SetForegroundColor(style::Color::Yellow),
+        style::SetForegroundColor(theme().warning),
         style::Print(format!("{time_taken} s\n")),
         style::ResetColor,
     )?)
 }
 
-fn queue_init_message(
-    spinner_logo_idx: usize,
+/// The live part of the loading display: a spinner row for every server that is still loading,
+/// followed by the overall progress. It is cleared and drawn again whenever something changes,
+/// with the messages of the servers that finished printed above it.
+struct LoadingProgress {
+    loading: Vec<(String, Instant)>,
+    total: usize,
     complete: usize,
     failed: usize,
-    total: usize,
-    output: &mut impl Write,
-) -> eyre::Result<()> {
-    if total == complete {
-        queue!(
-            output,
-            style::SetForegroundColor(style::Color::Green),
-            style::Print("✓"),
-            style::ResetColor,
-        )?;
-    } else if total == complete + failed {
-        queue!(
-            output,
-            style::SetForegroundColor(style::Color::Red),
-            style::Print("✗"),
-            style::ResetColor,
-        )?;
-    } else {
-        queue!(output, style::Print(SPINNER_CHARS[spinner_logo_idx]))?;
+    spinner_idx: usize,
+    /// Number of rows drawn last time
+    rows: u16,
+}
+
+impl LoadingProgress {
+    fn new(mut servers: Vec<String>) -> Self {
+        servers.sort();
+        let now = Instant::now();
+        Self {
+            total: servers.len(),
+            loading: servers.into_iter().map(|name| (name, now)).collect(),
+            complete: 0,
+            failed: 0,
+            spinner_idx: 0,
+            rows: 0,
+        }
     }
-    queue!(
-        output,
-        style::SetForegroundColor(style::Color::Blue),
-        style::Print(format!(" {}", complete)),
-        style::ResetColor,
-        style::Print(" of "),
-        style::SetForegroundColor(style::Color::Blue),
-        style::Print(format!("{} ", total)),
-        style::ResetColor,
-        style::Print("mcp servers initialized."),
-    )?;
-    if total > complete + failed {
+
+    fn tick(&mut self) {
+        self.spinner_idx = (self.spinner_idx + 1) % SPINNER_CHARS.len();
+    }
+
+    fn finish(&mut self, name: &str, succeeded: bool) {
+        self.loading.retain(|(server_name, _)| server_name != name);
+        match succeeded {
+            true => self.complete += 1,
+            false => self.failed += 1,
+        }
+    }
+
+    fn clear(&mut self, output: &mut impl Write) -> eyre::Result<()> {
+        if self.rows > 0 {
+            queue!(
+                output,
+                cursor::MoveToColumn(0),
+                cursor::MoveUp(self.rows),
+                terminal::Clear(terminal::ClearType::FromCursorDown),
+            )?;
+        }
+        self.rows = 0;
+        Ok(())
+    }
+
+    fn draw(&mut self, output: &mut impl Write) -> eyre::Result<()> {
+        let spinner = SPINNER_CHARS[self.spinner_idx];
+        for (name, started) in &self.loading {
+            queue!(
+                output,
+                style::Print(format!("{spinner} ")),
+                style::SetForegroundColor(theme().label),
+                style::Print(name),
+                style::SetForegroundColor(theme().secondary),
+                style::Print(format!(" {:.1} s\n", started.elapsed().as_secs_f64())),
+                style::ResetColor,
+            )?;
+        }
+
+        if self.total == self.complete {
+            queue!(
+                output,
+                style::SetForegroundColor(theme().success),
+                style::Print("✓"),
+                style::ResetColor,
+            )?;
+        } else if self.total == self.complete + self.failed {
+            queue!(
+                output,
+                style::SetForegroundColor(theme().error),
+                style::Print("✗"),
+                style::ResetColor,
+            )?;
+        } else {
+            queue!(output, style::Print(spinner))?;
+        }
         queue!(
             output,
-            style::SetForegroundColor(style::Color::Blue),
-            style::Print(" ctrl-c "),
+            style::SetForegroundColor(theme().label),
+            style::Print(format!(" {}", self.complete)),
+            style::ResetColor,
+            style::Print(" of "),
+            style::SetForegroundColor(theme().label),
+            style::Print(format!("{} ", self.total)),
             style::ResetColor,
-            style::Print("to start chatting now")
+            style::Print("mcp servers initialized."),
         )?;
+        if !self.loading.is_empty() {
+            queue!(
+                output,
+                style::SetForegroundColor(theme().label),
+                style::Print(" ctrl-c "),
+                style::ResetColor,
+                style::Print("to start chatting now")
+            )?;
+        }
+        queue!(output, style::Print("\n"))?;
+        self.rows = u16::try_from(self.loading.len() + 1).unwrap_or(u16::MAX);
+        Ok(())
     }
-    Ok(queue!(output, style::Print("\n"))?)
 }
 
 fn queue_failure_message(
@@ -1531,13 +1715,13 @@ fn queue_failure_message(
     use crate::util::CHAT_BINARY_NAME;
     Ok(queue!(
         output,
-        style::SetForegroundColor(style::Color::Red),
+        style::SetForegroundColor(theme().error),
         style::Print("✗ "),
-        style::SetForegroundColor(style::Color::Blue),
+        style::SetForegroundColor(theme().label),
         style::Print(name),
         style::ResetColor,
         style::Print(" has failed to load after"),
-        style::SetForegroundColor(style::Color::Yellow),
+        style::SetForegroundColor(theme().warning),
         style::Print(format!(" {time} s")),
         style::ResetColor,
         style::Print("\n - "),
@@ -1553,13 +1737,13 @@ fn queue_failure_message(
 fn queue_warn_message(name: &str, msg: &eyre::Report, time: &str, output: &mut impl Write) -> eyre::Result<()> {
     Ok(queue!(
         output,
-        style::SetForegroundColor(style::Color::Yellow),
+        style::SetForegroundColor(theme().warning),
         style::Print("⚠ "),
-        style::SetForegroundColor(style::Color::Blue),
+        style::SetForegroundColor(theme().label),
         style::Print(name),
         style::ResetColor,
         style::Print(" has loaded in"),
-        style::SetForegroundColor(style::Color::Yellow),
+        style::SetForegroundColor(theme().warning),
         style::Print(format!(" {time} s")),
         style::ResetColor,
         style::Print(" with the following warning:\n"),
@@ -1571,9 +1755,9 @@ fn queue_warn_message(name: &str, msg: &eyre::Report, time: &str, output: &mut i
 fn queue_disabled_message(name: &str, output: &mut impl Write) -> eyre::Result<()> {
     Ok(queue!(
         output,
-        style::SetForegroundColor(style::Color::DarkGrey),
+        style::SetForegroundColor(theme().secondary),
         style::Print("○ "),
-        style::SetForegroundColor(style::Color::Blue),
+        style::SetForegroundColor(theme().label),
         style::Print(name),
         style::ResetColor,
         style::Print(" is disabled\n"),
@@ -1589,13 +1773,13 @@ fn queue_incomplete_load_message(
 ) -> eyre::Result<()> {
     Ok(queue!(
         output,
-        style::SetForegroundColor(style::Color::Yellow),
+        style::SetForegroundColor(theme().warning),
         style::Print("⚠"),
-        style::SetForegroundColor(style::Color::Blue),
+        style::SetForegroundColor(theme().label),
         style::Print(format!(" {}", complete)),
         style::ResetColor,
         style::Print(" of "),
-        style::SetForegroundColor(style::Color::Blue),
+        style::SetForegroundColor(theme().label),
         style::Print(format!("{} ", total)),
         style::ResetColor,
         style::Print("mcp servers initialized."),
@@ -1611,6 +1795,28 @@ fn queue_incomplete_load_message(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_loading_progress() {
+        let mut progress = LoadingProgress::new(vec!["slow".to_string(), "fast".to_string()]);
+        let mut output = Vec::new();
+        progress.draw(&mut output).unwrap();
+        assert_eq!(progress.rows, 3);
+        let drawn = strip_ansi_escapes::strip_str(String::from_utf8_lossy(&output));
+        assert!(drawn.find("fast").unwrap() < drawn.find("slow").unwrap());
+        assert!(drawn.contains("0 of 2"));
+
+        progress.clear(&mut output).unwrap();
+        progress.finish("fast", true);
+        progress.finish("slow", false);
+        assert_eq!(progress.rows, 0);
+        output.clear();
+        progress.draw(&mut output).unwrap();
+        assert_eq!(progress.rows, 1);
+        let drawn = strip_ansi_escapes::strip_str(String::from_utf8_lossy(&output));
+        assert!(drawn.contains("1 of 2"));
+        assert!(!drawn.contains("ctrl-c"));
+    }
+
     #[test]
     fn test_sanitize_server_name() {
         let regex = regex::Regex::new(VALID_TOOL_NAME).unwrap();
@@ -1631,4 +1837,24 @@ mod tests {
         let sanitized = sanitize_name(with_delim, &regex, &mut hasher);
         assert_eq!(sanitized, "abc");
     }
+
+    #[tokio::test]
+    async fn test_mcp_config_stale() {
+        let os = Os::new().await.unwrap();
+        os.fs.write("/mcp.json", r#"{"mcpServers": {}}"#).await.unwrap();
+        let mut tool_manager = ToolManager {
+            mcp_config: McpConfigSnapshot::take(&os, vec![PathBuf::from("/mcp.json"), PathBuf::from("/missing.json")])
+                .await,
+            ..Default::default()
+        };
+        assert!(!tool_manager.is_mcp_config_stale(&os).await);
+
+        // Creating a config that did not exist counts as a change as well
+        os.fs.write("/missing.json", r#"{"mcpServers": {}}"#).await.unwrap();
+        assert!(tool_manager.is_mcp_config_stale(&os).await);
+
+        tool_manager.acknowledge_mcp_config(&os).await;
+        assert!(!tool_manager.is_mcp_config_stale(&os).await);
+        assert!(!ToolManager::default().is_mcp_config_stale(&os).await);
+    }
 }
@@ -1,7 +1,11 @@
 use std::collections::HashMap;
 use std::io::Write;
 use std::sync::Arc;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{
+    AtomicU32,
+    Ordering,
+};
+use std::time::Duration;
 
 use crossterm::{
     queue,
@@ -17,6 +21,7 @@ use tokio::sync::RwLock;
 use tracing::warn;
 
 use super::InvokeOutput;
+use crate::cli::agent::egress::Egress;
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
@@ -26,21 +31,51 @@ use crate::cli::chat::token_counter::TokenCounter;
 use crate::mcp_client::{
     Client as McpClient,
     ClientConfig as McpClientConfig,
+    HttpClientConfig as McpHttpClientConfig,
+    HttpTransport,
+    HttpTransportKind,
     JsonRpcResponse,
     JsonRpcStdioTransport,
     MessageContent,
     Messenger,
     PromptGet,
+    ResourceInfo,
+    ResourceReadResult,
+    ResourcesListResult,
     ServerCapabilities,
+    StderrLog,
     StdioTransport,
     ToolCallResult,
 };
 use crate::os::Os;
+use crate::util::theme::theme;
+
+/// How Q connects to an MCP server.
+#[derive(Clone, Copy, Default, Serialize, Deserialize, Debug, Eq, PartialEq, JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum McpTransport {
+    /// Launches `command` and talks to it over stdin and stdout
+    #[default]
+    Stdio,
+    /// Streamable HTTP, messages are POSTed to `url`
+    Http,
+    /// HTTP with server-sent events, the remote transport of protocol revision 2024-11-05
+    Sse,
+}
+
+impl McpTransport {
+    pub fn is_stdio(&self) -> bool {
+        *self == Self::Stdio
+    }
+}
 
-// TODO: support http transport type
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, JsonSchema)]
 pub struct CustomToolConfig {
-    /// The command string used to initialize the mcp server
+    /// How to connect to the server: stdio (the default), http or sse
+    #[serde(default, skip_serializing_if = "McpTransport::is_stdio")]
+    pub transport: McpTransport,
+    /// The command string used to initialize the mcp server, for the stdio transport
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub command: String,
     /// A list of arguments to be used to run the command with
     #[serde(default)]
@@ -48,9 +83,23 @@ pub struct CustomToolConfig {
     /// A list of environment variables to run the command with
     #[serde(skip_serializing_if = "Option::is_none")]
     pub env: Option<HashMap<String, String>>,
+    /// The url of the server, for the http and sse transports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Headers sent with every request to the server, e.g. for authorization. Values may refer to
+    /// environment variables, as in `Bearer $API_TOKEN`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headers: Option<HashMap<String, String>>,
     /// Timeout for each mcp request in ms
     #[serde(default = "default_timeout")]
     pub timeout: u64,
+    /// Time the server has to start and complete the initialize handshake in ms, after which the
+    /// attempt counts as failed. Only the request timeout applies when unset
+    #[serde(rename = "initTimeoutMs", skip_serializing_if = "Option::is_none")]
+    pub init_timeout_ms: Option<u64>,
+    /// How many times a failed initialization is retried, with exponential backoff
+    #[serde(rename = "maxRetries", default, skip_serializing_if = "is_zero")]
+    pub max_retries: u32,
     /// A boolean flag to denote whether or not to load this mcp server
     #[serde(default)]
     pub disabled: bool,
@@ -59,10 +108,45 @@ pub struct CustomToolConfig {
     pub is_from_legacy_mcp_json: bool,
 }
 
+impl CustomToolConfig {
+    /// What the server is launched with or connected to, for display.
+    pub fn target(&self) -> &str {
+        match self.transport {
+            McpTransport::Stdio => &self.command,
+            McpTransport::Http | McpTransport::Sse => self.url.as_deref().unwrap_or_default(),
+        }
+    }
+}
+
+/// Number of stderr lines included when a tool call fails.
+const DIAGNOSTIC_LINES: usize = 10;
+/// Delay before the first retry of a failed initialization, doubled for every retry after it.
+const INIT_BACKOFF_BASE: Duration = Duration::from_millis(500);
+const INIT_BACKOFF_MAX: Duration = Duration::from_secs(10);
+
 pub fn default_timeout() -> u64 {
     120 * 1000
 }
 
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// How the initialization of a server is bounded and retried, from `initTimeoutMs` and
+/// `maxRetries` in its config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InitPolicy {
+    pub timeout: Option<Duration>,
+    pub max_retries: u32,
+}
+
+/// How long to wait before the given retry of an initialization, starting from 1.
+pub fn init_backoff(retry: u32) -> Duration {
+    INIT_BACKOFF_BASE
+        .saturating_mul(2_u32.saturating_pow(retry.saturating_sub(1)))
+        .min(INIT_BACKOFF_MAX)
+}
+
 #[derive(Debug)]
 pub enum CustomToolClient {
     Stdio {
@@ -70,36 +154,92 @@ pub enum CustomToolClient {
         server_name: String,
         client: McpClient<StdioTransport>,
         server_capabilities: RwLock<Option<ServerCapabilities>>,
+        init_policy: InitPolicy,
+        /// Number of initialization attempts made so far
+        init_attempts: AtomicU32,
+    },
+    Http {
+        server_name: String,
+        client: McpClient<HttpTransport>,
+        server_capabilities: RwLock<Option<ServerCapabilities>>,
+        init_policy: InitPolicy,
+        init_attempts: AtomicU32,
     },
 }
 
 impl CustomToolClient {
-    // TODO: add support for http transport
-    pub fn from_config(server_name: String, config: CustomToolConfig) -> Result<Self> {
+    pub fn from_config(server_name: String, config: CustomToolConfig, egress: &Egress) -> Result<Self> {
         let CustomToolConfig {
+            transport,
             command,
             args,
             env,
+            url,
+            headers,
             timeout,
+            init_timeout_ms,
+            max_retries,
             disabled: _,
             ..
         } = config;
-        let mcp_client_config = McpClientConfig {
+        let init_policy = InitPolicy {
+            timeout: init_timeout_ms.map(Duration::from_millis),
+            max_retries,
+        };
+        let client_info = serde_json::json!({
+           "name": "Q CLI Chat",
+           "version": "1.0.0"
+        });
+        let kind = match transport {
+            McpTransport::Stdio => {
+                let mcp_client_config = McpClientConfig {
+                    server_name: server_name.clone(),
+                    bin_path: command.clone(),
+                    args,
+                    timeout,
+                    client_info,
+                    env,
+                };
+                let client = McpClient::<JsonRpcStdioTransport>::from_config(mcp_client_config)?;
+                return Ok(CustomToolClient::Stdio {
+                    server_name,
+                    client,
+                    server_capabilities: RwLock::new(None),
+                    init_policy,
+                    init_attempts: AtomicU32::new(0),
+                });
+            },
+            McpTransport::Http => HttpTransportKind::StreamableHttp,
+            McpTransport::Sse => HttpTransportKind::Sse,
+        };
+
+        let Some(url) = url else {
+            eyre::bail!("{server_name} has no url to connect to");
+        };
+        let headers = headers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| -> Result<(String, String)> {
+                let value = shellexpand::env(&value)?.into_owned();
+                Ok((name, value))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        let mcp_client_config = McpHttpClientConfig {
             server_name: server_name.clone(),
-            bin_path: command.clone(),
-            args,
+            url,
+            kind,
+            headers,
+            egress: egress.clone(),
             timeout,
-            client_info: serde_json::json!({
-               "name": "Q CLI Chat",
-               "version": "1.0.0"
-            }),
-            env,
+            client_info,
         };
-        let client = McpClient::<JsonRpcStdioTransport>::from_config(mcp_client_config)?;
-        Ok(CustomToolClient::Stdio {
+        let client = McpClient::<HttpTransport>::from_http_config(mcp_client_config)?;
+        Ok(CustomToolClient::Http {
             server_name,
             client,
             server_capabilities: RwLock::new(None),
+            init_policy,
+            init_attempts: AtomicU32::new(0),
         })
     }
 
@@ -121,6 +261,78 @@ impl CustomToolClient {
                 server_capabilities.write().await.replace(cap);
                 Ok(())
             },
+            CustomToolClient::Http {
+                client,
+                server_capabilities,
+                ..
+            } => {
+                if let Some(messenger) = &client.messenger {
+                    let _ = messenger.send_init_msg().await;
+                }
+                let cap = client.init().await?;
+                server_capabilities.write().await.replace(cap);
+                Ok(())
+            },
+        }
+    }
+
+    /// Runs [Self::init], retrying attempts that fail or take longer than `initTimeoutMs` up to
+    /// `maxRetries` times with exponential backoff. When every attempt fails, the error is
+    /// reported through the messenger so that the server stops counting as loading.
+    pub async fn init_with_retries(&self) {
+        let (CustomToolClient::Stdio {
+            init_policy,
+            init_attempts,
+            ..
+        }
+        | CustomToolClient::Http {
+            init_policy,
+            init_attempts,
+            ..
+        }) = self;
+        let server_name = self.get_server_name();
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            init_attempts.store(attempt, Ordering::Relaxed);
+            let result = match init_policy.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, self.init())
+                    .await
+                    .unwrap_or_else(|_| Err(eyre::eyre!("timed out after {} ms", timeout.as_millis()))),
+                None => self.init().await,
+            };
+            let err = match result {
+                Ok(()) => return,
+                Err(err) => err,
+            };
+
+            if attempt > init_policy.max_retries {
+                let err = match attempt {
+                    1 => err,
+                    _ => err.wrap_err(format!("failed to initialize after {attempt} attempts")),
+                };
+                if let Some(messenger) = self.messenger() {
+                    let _ = messenger.send_tools_list_result(Err(err)).await;
+                }
+                return;
+            }
+            let delay = init_backoff(attempt);
+            warn!(?err, attempt, ?delay, "failed to initialize {server_name}, retrying");
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Number of initialization attempts made so far, more than 1 when it had to be retried.
+    pub fn init_attempts(&self) -> u32 {
+        let (CustomToolClient::Stdio { init_attempts, .. } | CustomToolClient::Http { init_attempts, .. }) = self;
+        init_attempts.load(Ordering::Relaxed)
+    }
+
+    fn messenger(&self) -> Option<&dyn Messenger> {
+        match self {
+            CustomToolClient::Stdio { client, .. } => client.messenger.as_deref(),
+            CustomToolClient::Http { client, .. } => client.messenger.as_deref(),
         }
     }
 
@@ -129,24 +341,81 @@ impl CustomToolClient {
             CustomToolClient::Stdio { client, .. } => {
                 client.messenger = Some(messenger);
             },
+            CustomToolClient::Http { client, .. } => {
+                client.messenger = Some(messenger);
+            },
         }
     }
 
     pub fn get_server_name(&self) -> &str {
         match self {
-            CustomToolClient::Stdio { server_name, .. } => server_name.as_str(),
+            CustomToolClient::Stdio { server_name, .. } | CustomToolClient::Http { server_name, .. } => {
+                server_name.as_str()
+            },
         }
     }
 
     pub async fn request(&self, method: &str, params: Option<serde_json::Value>) -> Result<JsonRpcResponse> {
         match self {
             CustomToolClient::Stdio { client, .. } => Ok(client.request(method, params).await?),
+            CustomToolClient::Http { client, .. } => Ok(client.request(method, params).await?),
+        }
+    }
+
+    /// Sends a request and returns its result, or the error the server responded with.
+    async fn request_result(&self, method: &str, params: Option<serde_json::Value>) -> Result<serde_json::Value> {
+        let resp = self.request(method, params).await?;
+        match (resp.result, resp.error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(err)) => eyre::bail!("{} failed: {}", method, err.message),
+            (None, None) => eyre::bail!("{} returned nothing", method),
+        }
+    }
+
+    /// Whether the server offered resources when it was initialized.
+    pub async fn supports_resources(&self) -> bool {
+        let (CustomToolClient::Stdio {
+            server_capabilities, ..
+        }
+        | CustomToolClient::Http {
+            server_capabilities, ..
+        }) = self;
+        server_capabilities
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|cap| cap.resources.is_some())
+    }
+
+    /// Lists the resources the server offers with `resources/list`.
+    pub async fn list_resources(&self) -> Result<Vec<ResourceInfo>> {
+        let ResourcesListResult { resources, .. } =
+            serde_json::from_value(self.request_result("resources/list", None).await?)?;
+        Ok(resources
+            .into_iter()
+            .filter_map(|resource| serde_json::from_value(resource).ok())
+            .collect())
+    }
+
+    /// Reads the resource at `uri` with `resources/read`.
+    pub async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult> {
+        let result = self
+            .request_result("resources/read", Some(serde_json::json!({ "uri": uri })))
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    pub fn stderr_log(&self) -> &StderrLog {
+        match self {
+            CustomToolClient::Stdio { client, .. } => client.stderr_log(),
+            CustomToolClient::Http { client, .. } => client.stderr_log(),
         }
     }
 
     pub fn list_prompt_gets(&self) -> Arc<std::sync::RwLock<HashMap<String, PromptGet>>> {
         match self {
             CustomToolClient::Stdio { client, .. } => client.prompt_gets.clone(),
+            CustomToolClient::Http { client, .. } => client.prompt_gets.clone(),
         }
     }
 
@@ -154,18 +423,21 @@ impl CustomToolClient {
     pub async fn notify(&self, method: &str, params: Option<serde_json::Value>) -> Result<()> {
         match self {
             CustomToolClient::Stdio { client, .. } => Ok(client.notify(method, params).await?),
+            CustomToolClient::Http { client, .. } => Ok(client.notify(method, params).await?),
         }
     }
 
     pub fn is_prompts_out_of_date(&self) -> bool {
         match self {
             CustomToolClient::Stdio { client, .. } => client.is_prompts_out_of_date.load(Ordering::Relaxed),
+            CustomToolClient::Http { client, .. } => client.is_prompts_out_of_date.load(Ordering::Relaxed),
         }
     }
 
     pub fn prompts_updated(&self) {
         match self {
             CustomToolClient::Stdio { client, .. } => client.is_prompts_out_of_date.store(false, Ordering::Relaxed),
+            CustomToolClient::Http { client, .. } => client.is_prompts_out_of_date.store(false, Ordering::Relaxed),
         }
     }
 }
@@ -189,14 +461,17 @@ pub struct CustomTool {
 impl CustomTool {
     pub async fn invoke(&self, _os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
         // Assuming a response shape as per https://spec.modelcontextprotocol.io/specification/2024-11-05/server/tools/#calling-tools
-        let resp = self.client.request(self.method.as_str(), self.params.clone()).await?;
+        let resp = match self.client.request(self.method.as_str(), self.params.clone()).await {
+            Ok(resp) => resp,
+            Err(err) => return Err(self.with_diagnostics(err.to_string())),
+        };
         let result = match resp.result {
             Some(result) => result,
             None => {
                 let failure = resp.error.map_or("Unknown error encountered".to_string(), |err| {
                     serde_json::to_string(&err).unwrap_or_default()
                 });
-                return Err(eyre::eyre!(failure));
+                return Err(self.with_diagnostics(failure));
             },
         };
 
@@ -209,22 +484,36 @@ impl CustomTool {
                 }
                 Ok(InvokeOutput {
                     output: super::OutputKind::Json(serde_json::json!(de_result)),
+                    ..Default::default()
                 })
             },
             Err(e) => {
                 warn!("Tool call result deserialization failed: {:?}", e);
                 Ok(InvokeOutput {
                     output: super::OutputKind::Json(result.clone()),
+                    ..Default::default()
                 })
             },
         }
     }
 
+    /// Appends the recent stderr output of the server to a failure, since the error returned over
+    /// the protocol (if any) rarely explains why the server misbehaved.
+    fn with_diagnostics(&self, failure: String) -> eyre::Report {
+        match self.client.stderr_log().diagnostics(DIAGNOSTIC_LINES) {
+            Some(diagnostics) => eyre::eyre!(
+                "{failure}\n\n{diagnostics}Run /mcp logs {} to see more.",
+                self.client.get_server_name()
+            ),
+            None => eyre::eyre!(failure),
+        }
+    }
+
     pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
         queue!(
             output,
             style::Print("Running "),
-            style::SetForegroundColor(style::Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print(&self.name),
             style::ResetColor,
         )?;
@@ -279,3 +568,34 @@ impl CustomTool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_policy_config() {
+        let config: CustomToolConfig = serde_json::from_value(serde_json::json!({
+            "command": "slow-server",
+            "initTimeoutMs": 3000,
+            "maxRetries": 2
+        }))
+        .unwrap();
+        assert_eq!(config.init_timeout_ms, Some(3000));
+        assert_eq!(config.max_retries, 2);
+
+        // Unset values are left out so that existing configs are written back unchanged
+        let config: CustomToolConfig = serde_json::from_value(serde_json::json!({ "command": "server" })).unwrap();
+        let value = serde_json::to_value(&config).unwrap();
+        assert!(value.get("initTimeoutMs").is_none());
+        assert!(value.get("maxRetries").is_none());
+    }
+
+    #[test]
+    fn test_init_backoff() {
+        assert_eq!(init_backoff(1), Duration::from_millis(500));
+        assert_eq!(init_backoff(2), Duration::from_secs(1));
+        assert_eq!(init_backoff(3), Duration::from_secs(2));
+        assert_eq!(init_backoff(10), INIT_BACKOFF_MAX);
+    }
+}
@@ -1,14 +1,19 @@
+use std::collections::HashMap;
 use std::io::Write;
 
 use crossterm::queue;
 use crossterm::style::{
     self,
-    Color,
 };
-use eyre::Result;
+use eyre::{
+    Result,
+    bail,
+};
+use globset::Glob;
 use serde::Deserialize;
 use tracing::error;
 
+use crate::cli::agent::sandbox::SandboxConfig;
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
@@ -19,7 +24,13 @@ use crate::cli::chat::tools::{
     OutputKind,
 };
 use crate::cli::chat::util::truncate_safe;
+use crate::database::settings::Setting;
 use crate::os::Os;
+use crate::util::process::ResourceUsage;
+use crate::util::theme::theme;
+
+mod toolchain;
+pub use toolchain::ToolchainNote;
 
 // Platform-specific modules
 #[cfg(windows)]
@@ -41,9 +52,24 @@ pub const READONLY_COMMANDS: &[&str] = &[
 pub struct ExecuteCommand {
     pub command: String,
     pub summary: Option<String>,
+    /// Versions of the programs the command assumes, as semver requirements such as `>=18`
+    #[serde(default)]
+    pub assumed_versions: HashMap<String, String>,
+    /// Set by [Self::validate], see [toolchain]. Notes found once the command is approved are only
+    /// added to its output.
+    #[serde(skip)]
+    toolchain_notes: Vec<ToolchainNote>,
+    /// Set by the session, see [Self::set_sandbox]
+    #[serde(skip)]
+    sandbox: Option<SandboxConfig>,
 }
 
 impl ExecuteCommand {
+    /// Sets the sandbox of the agent, which the command is run in.
+    pub fn set_sandbox(&mut self, sandbox: Option<SandboxConfig>) {
+        self.sandbox = sandbox;
+    }
+
     pub fn requires_acceptance(&self, allowed_commands: Option<&Vec<String>>, allow_read_only: bool) -> bool {
         let default_arr = vec![];
         let allowed_commands = allowed_commands.unwrap_or(&default_arr);
@@ -95,7 +121,10 @@ impl ExecuteCommand {
                     return true;
                 },
                 Some(cmd) => {
-                    if allowed_commands.contains(cmd) {
+                    if allowed_commands
+                        .iter()
+                        .any(|pattern| matches_command_pattern(pattern, &cmd_args))
+                    {
                         continue;
                     }
                     let is_cmd_read_only = READONLY_COMMANDS.contains(&cmd.as_str());
@@ -110,16 +139,47 @@ impl ExecuteCommand {
         false
     }
 
-    pub async fn invoke(&self, output: &mut impl Write) -> Result<InvokeOutput> {
-        let output = run_command(&self.command, MAX_TOOL_RESPONSE_SIZE / 3, Some(output)).await?;
-        let result = serde_json::json!({
+    pub async fn invoke(&self, os: &Os, output: &mut impl Write) -> Result<InvokeOutput> {
+        let mut toolchain_notes = self.toolchain_notes.clone();
+        if toolchain_checks_enabled(os) {
+            // The rest of the programs are only asked for their versions now that the command is approved
+            let notes = toolchain::check_versions(os, &self.command, &self.assumed_versions, true).await;
+            for note in &notes {
+                queue!(
+                    output,
+                    style::SetForegroundColor(theme().warning),
+                    style::Print(format!("⚠ {note}\n")),
+                    style::ResetColor
+                )?;
+            }
+            toolchain_notes.extend(notes);
+        }
+
+        let output = run_command(
+            &self.command,
+            self.sandbox.as_ref(),
+            MAX_TOOL_RESPONSE_SIZE / 3,
+            Some(output),
+        )
+        .await?;
+        let mut result = serde_json::json!({
             "exit_status": output.exit_status.unwrap_or(0).to_string(),
             "stdout": output.stdout,
             "stderr": output.stderr,
+            "resource_usage": output.resource_usage,
         });
+        if !toolchain_notes.is_empty() {
+            result["toolchain_notes"] = toolchain_notes
+                .iter()
+                .map(|note| note.to_string())
+                .collect::<Vec<_>>()
+                .into();
+        }
 
         Ok(InvokeOutput {
             output: OutputKind::Json(result),
+            resource_usage: Some(output.resource_usage),
+            ..Default::default()
         })
     }
 
@@ -133,12 +193,30 @@ impl ExecuteCommand {
 
         queue!(
             output,
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print(&self.command),
             style::Print("\n"),
             style::ResetColor
         )?;
 
+        for note in &self.toolchain_notes {
+            queue!(
+                output,
+                style::SetForegroundColor(theme().warning),
+                style::Print(format!("⚠ {note}\n")),
+                style::ResetColor
+            )?;
+        }
+
+        if let Some(sandbox) = &self.sandbox {
+            queue!(
+                output,
+                style::SetForegroundColor(theme().secondary),
+                style::Print(format!("Sandboxed: {sandbox}\n")),
+                style::ResetColor
+            )?;
+        }
+
         // Add the summary if available
         if let Some(ref summary) = self.summary {
             super::display_purpose(Some(summary), output)?;
@@ -149,8 +227,19 @@ impl ExecuteCommand {
         Ok(())
     }
 
-    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
-        // TODO: probably some small amount of PATH checking
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        if toolchain_checks_enabled(os) {
+            let mut notes = toolchain::check(os, &self.command);
+            notes.extend(toolchain::check_versions(os, &self.command, &self.assumed_versions, false).await);
+            self.toolchain_notes = notes;
+        }
+        // Commands are never run outside of the sandbox the agent asks for
+        if let Some(sandbox) = &self.sandbox {
+            let program = sandbox.program()?;
+            if toolchain::find_in_path(os, program).is_none() {
+                bail!("The agent runs commands in a sandbox with {program}, which isn't installed");
+            }
+        }
         Ok(())
     }
 
@@ -173,8 +262,10 @@ impl ExecuteCommand {
         let Self { command, .. } = self;
         let tool_name = if cfg!(windows) { "execute_cmd" } else { "execute_bash" };
         let is_in_allowlist = agent.allowed_tools.contains("execute_bash");
+        // The settings scope the tool whether or not it is trusted, so that only the allowed and
+        // read-only commands run without asking
         match agent.tools_settings.get(tool_name) {
-            Some(settings) if is_in_allowlist => {
+            Some(settings) => {
                 let Settings {
                     allowed_commands,
                     denied_commands,
@@ -209,12 +300,31 @@ impl ExecuteCommand {
     }
 }
 
+/// Whether a command, split into its arguments, starts with those of `pattern`, such as `git *`
+/// or `cargo test`. Each word of the pattern is a glob matched against one argument, and a
+/// trailing `*` also matches no arguments at all.
+fn matches_command_pattern(pattern: &str, args: &[String]) -> bool {
+    let Some(mut words) = shlex::split(pattern) else {
+        return false;
+    };
+    if words.last().is_some_and(|word| word == "*") {
+        words.pop();
+    }
+    !words.is_empty()
+        && words.len() <= args.len()
+        && words
+            .iter()
+            .zip(args)
+            .all(|(word, arg)| Glob::new(word).map_or(word == arg, |glob| glob.compile_matcher().is_match(arg)))
+}
+
 pub struct CommandResult {
     pub exit_status: Option<i32>,
     /// Truncated stdout
     pub stdout: String,
     /// Truncated stderr
     pub stderr: String,
+    pub resource_usage: ResourceUsage,
 }
 
 // Helper function to format command output with truncation
@@ -226,6 +336,15 @@ pub fn format_output(output: &str, max_size: usize) -> String {
     )
 }
 
+fn toolchain_checks_enabled(os: &Os) -> bool {
+    !cfg!(windows)
+        && !os
+            .database
+            .settings
+            .get_bool(Setting::ChatDisableToolchainChecks)
+            .unwrap_or(false)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -267,4 +386,72 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_matches_command_pattern() {
+        let matches = |pattern: &str, command: &str| matches_command_pattern(pattern, &shlex::split(command).unwrap());
+
+        assert!(matches("git", "git push --force"));
+        assert!(matches("git *", "git status"));
+        assert!(matches("git *", "git"));
+        assert!(matches("cargo test", "cargo test --workspace"));
+        assert!(matches("git log --oneline", "git log --oneline -n 5"));
+        assert!(matches("npm run test*", "npm run test:unit"));
+        assert!(!matches("cargo test", "cargo publish"));
+        assert!(!matches("cargo test", "cargo"));
+        assert!(!matches("git *", "gitk"));
+        assert!(!matches("", "git status"));
+    }
+
+    #[test]
+    fn test_eval_perm_with_command_patterns() {
+        let tool_name = if cfg!(windows) { "execute_cmd" } else { "execute_bash" };
+        let agent = |allowed_tools: Vec<&str>| {
+            serde_json::from_value::<Agent>(serde_json::json!({
+                "allowedTools": allowed_tools,
+                "toolsSettings": {
+                    tool_name: {
+                        "allowedCommands": ["git *", "cargo test"],
+                        "deniedCommands": ["git push"],
+                    },
+                },
+            }))
+            .unwrap()
+        };
+        let eval = |agent: &Agent, command: &str| {
+            serde_json::from_value::<ExecuteCommand>(serde_json::json!({ "command": command }))
+                .unwrap()
+                .eval_perm(agent)
+        };
+
+        // The allowed commands apply whether or not the tool is trusted
+        for agent in [agent(vec![]), agent(vec!["execute_bash"])] {
+            assert_eq!(eval(&agent, "git status"), PermissionEvalResult::Allow);
+            assert_eq!(eval(&agent, "cargo test -p chat_cli"), PermissionEvalResult::Allow);
+            assert_eq!(eval(&agent, "git diff | grep fn"), PermissionEvalResult::Allow);
+            assert_eq!(eval(&agent, "cargo publish"), PermissionEvalResult::Ask);
+            assert_eq!(eval(&agent, "cargo test && rm -rf target"), PermissionEvalResult::Ask);
+            assert_eq!(eval(&agent, "git push origin main"), PermissionEvalResult::Deny);
+        }
+    }
+
+    #[test]
+    fn test_eval_perm_without_allowed_commands() {
+        let tool_name = if cfg!(windows) { "execute_cmd" } else { "execute_bash" };
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "allowedTools": ["execute_bash"],
+            "toolsSettings": { tool_name: { "deniedCommands": ["git push"] } },
+        }))
+        .unwrap();
+        let eval = |command: &str| {
+            serde_json::from_value::<ExecuteCommand>(serde_json::json!({ "command": command }))
+                .unwrap()
+                .eval_perm(&agent)
+        };
+
+        // Settings without allowed commands still ask before commands that aren't read-only
+        assert_eq!(eval("ls -la"), PermissionEvalResult::Allow);
+        assert_eq!(eval("rm -rf target"), PermissionEvalResult::Ask);
+        assert_eq!(eval("git push origin main"), PermissionEvalResult::Deny);
+    }
 }
@@ -0,0 +1,385 @@
+//! Sanity checks of the programs a command runs. Before the command is shown to the user, programs
+//! that aren't on the PATH are looked up under a known alternative name (e.g. `python3` for
+//! `python`), and the version the model assumes for the program the command starts with is
+//! compared with the installed one if it is a well known toolchain. The versions it assumes for the
+//! other programs the command runs are only compared once the user approved it, since that means
+//! running them. The notes are shown to the user and returned to the model with the output of the
+//! command, which is run as the model wrote it, so that its next commands use what is actually
+//! installed.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::time::Duration;
+
+use regex::Regex;
+use semver::{
+    Version,
+    VersionReq,
+};
+use tracing::warn;
+
+use crate::os::Os;
+
+/// Programs commonly installed under another name, tried in order when one isn't on the PATH.
+const ALTERNATIVES: &[(&str, &[&str])] = &[
+    ("python", &["python3"]),
+    ("python3", &["python"]),
+    ("pip", &["pip3"]),
+    ("pip3", &["pip"]),
+    ("fd", &["fdfind"]),
+    ("bat", &["batcat"]),
+];
+
+/// Words that are followed by the program that is actually run.
+const WRAPPERS: &[&str] = &[
+    "sudo", "env", "time", "nohup", "exec", "command", "nice", "xargs", "if", "then", "else", "elif", "while", "until",
+    "do", "!",
+];
+
+/// Shell builtins and keywords, which aren't looked up on the PATH.
+const BUILTINS: &[&str] = &[
+    ".", ":", "[", "[[", "alias", "bg", "break", "case", "cd", "continue", "declare", "done", "echo", "esac", "eval",
+    "exit", "export", "false", "fg", "fi", "for", "function", "getopts", "hash", "history", "jobs", "kill", "local",
+    "popd", "printf", "pushd", "pwd", "read", "readonly", "return", "set", "shift", "source", "test", "trap", "true",
+    "type", "ulimit", "umask", "unalias", "unset", "wait", "{", "}",
+];
+
+/// Toolchains whose `--version` only prints their version, which is asked for before the command
+/// is approved when it starts with one of them.
+const VERSION_PROBES: &[&str] = &[
+    "node",
+    "npm",
+    "npx",
+    "yarn",
+    "pnpm",
+    "bun",
+    "deno",
+    "python",
+    "python3",
+    "pip",
+    "pip3",
+    "ruby",
+    "gem",
+    "cargo",
+    "rustc",
+    "java",
+    "mvn",
+    "gradle",
+    "dotnet",
+    "php",
+    "perl",
+    "git",
+    "make",
+    "cmake",
+    "gcc",
+    "clang",
+    "terraform",
+];
+
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolchainNote {
+    /// The program isn't on the PATH, but `alternative` is
+    Alternative { program: String, alternative: String },
+    /// The program isn't on the PATH and there is no known alternative
+    Missing { program: String },
+    /// The installed version doesn't satisfy the one the model assumed
+    Version {
+        program: String,
+        installed: String,
+        assumed: String,
+    },
+}
+
+impl fmt::Display for ToolchainNote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Alternative { program, alternative } => {
+                write!(f, "`{program}` is not installed, but `{alternative}` is")
+            },
+            Self::Missing { program } => write!(f, "`{program}` was not found on the PATH"),
+            Self::Version {
+                program,
+                installed,
+                assumed,
+            } => write!(
+                f,
+                "`{program}` {installed} is installed, but the command assumes {assumed}"
+            ),
+        }
+    }
+}
+
+/// Checks that the programs run by `command` are installed, naming the alternatives of those that
+/// aren't.
+pub fn check(os: &Os, command: &str) -> Vec<ToolchainNote> {
+    let mut notes = Vec::new();
+    let mut checked = Vec::new();
+    for program in programs(command) {
+        if checked.contains(&program) || BUILTINS.contains(&program) || find_in_path(os, program).is_some() {
+            continue;
+        }
+        checked.push(program);
+        let alternative = ALTERNATIVES
+            .iter()
+            .find(|(name, _)| *name == program)
+            .and_then(|(_, alternatives)| alternatives.iter().find(|alt| find_in_path(os, alt).is_some()));
+        notes.push(match alternative {
+            Some(alternative) => ToolchainNote::Alternative {
+                program: program.to_string(),
+                alternative: (*alternative).to_string(),
+            },
+            None => ToolchainNote::Missing {
+                program: program.to_string(),
+            },
+        });
+    }
+    notes
+}
+
+/// Compares the versions in `assumed_versions` (program to semver requirement, e.g. `node` to
+/// `>=18`) with the installed ones, for the programs `command` runs. Before the user approved the
+/// command, only the toolchain it starts with is asked for its version.
+pub async fn check_versions(
+    os: &Os,
+    command: &str,
+    assumed_versions: &HashMap<String, String>,
+    approved: bool,
+) -> Vec<ToolchainNote> {
+    let programs = programs(command);
+    let mut notes = Vec::new();
+    for (program, assumed) in assumed_versions {
+        let probed_before_approval =
+            programs.first() == Some(&program.as_str()) && VERSION_PROBES.contains(&program.as_str());
+        if probed_before_approval == approved || !programs.contains(&program.as_str()) {
+            continue;
+        }
+        let Some(path) = find_in_path(os, program) else {
+            continue;
+        };
+        let Ok(requirement) = VersionReq::parse(assumed) else {
+            warn!("Ignoring the version {assumed} assumed for {program}, it is not a valid requirement");
+            continue;
+        };
+        if let Some(installed) = installed_version(&path).await {
+            if !requirement.matches(&installed) {
+                notes.push(ToolchainNote::Version {
+                    program: program.clone(),
+                    installed: installed.to_string(),
+                    assumed: assumed.clone(),
+                });
+            }
+        }
+    }
+    notes
+}
+
+/// The programs a command runs: the first word of each command
+/// of its pipelines, lists and substitutions, after any variable assignments and wrappers like
+/// `sudo`. Quoted words and words with paths or variables in them are left out.
+fn programs(command: &str) -> Vec<&str> {
+    let mut programs = Vec::new();
+    let mut expecting_program = true;
+    let mut chars = command.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() && c != '\n' {
+            chars.next();
+            continue;
+        }
+        if matches!(c, ';' | '|' | '&' | '(' | ')' | '\n') {
+            chars.next();
+            expecting_program = c != ')';
+            continue;
+        }
+
+        let mut end = start;
+        let mut quote = None;
+        let mut quoted = false;
+        let mut previous = None;
+        while let Some(&(i, c)) = chars.peek() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => (),
+                None if c == '\'' || c == '"' => {
+                    quote = Some(c);
+                    quoted = true;
+                },
+                // `&` is part of redirections like `2>&1`
+                None if c == '&' && matches!(previous, Some('>' | '<')) => (),
+                None if c.is_whitespace() || matches!(c, ';' | '|' | '&' | '(' | ')') => break,
+                None => (),
+            }
+            previous = Some(c);
+            end = i + c.len_utf8();
+            chars.next();
+        }
+
+        let word = &command[start..end];
+        if !expecting_program || word.contains(['<', '>']) {
+            continue;
+        }
+        let is_assignment = word.find('=').is_some_and(|i| i > 0);
+        if is_assignment || WRAPPERS.contains(&word) || word.starts_with('-') {
+            continue;
+        }
+        expecting_program = false;
+        if !quoted && !word.contains(['/', '$', '`', '\\', '*', '?']) {
+            programs.push(word);
+        }
+    }
+    programs
+}
+
+pub(super) fn find_in_path(os: &Os, program: &str) -> Option<PathBuf> {
+    let path = os.env.get_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| os.fs.exists(candidate))
+}
+
+/// Runs `<program> --version` and reads the first version in what it prints.
+async fn installed_version(program: &Path) -> Option<Version> {
+    let output = tokio::time::timeout(
+        VERSION_TIMEOUT,
+        tokio::process::Command::new(program)
+            .arg("--version")
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    // Some programs, like older versions of python, print their version to stderr
+    let printed = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    parse_version(&printed)
+}
+
+fn parse_version(printed: &str) -> Option<Version> {
+    let regex = Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?").ok()?;
+    let captures = regex.captures(printed)?;
+    let part = |i: usize| captures.get(i).map_or(Some(0), |m| m.as_str().parse().ok());
+    Some(Version::new(part(1)?, part(2)?, part(3)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_programs() {
+        let names = programs;
+
+        assert_eq!(names("python -m venv .venv"), ["python"]);
+        assert_eq!(names("cd app && npm install 2>&1 | tee log.txt"), ["cd", "npm", "tee"]);
+        assert_eq!(names("FOO=1 sudo -E pip install -r 'requirements.txt'; ls"), [
+            "pip", "ls"
+        ]);
+        assert_eq!(names("echo $(which node) > out.txt"), ["echo", "which"]);
+        assert_eq!(names("./gradlew build && \"$HOME/bin/tool\""), Vec::<&str>::new());
+        assert_eq!(names("if true; then make; fi"), ["true", "make", "fi"]);
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/usr/bin").await.unwrap();
+        os.fs.write("/usr/bin/python3", "").await.unwrap();
+        os.fs.write("/usr/bin/ls", "").await.unwrap();
+        unsafe { os.env.set_var("PATH", "/usr/local/bin:/usr/bin") };
+
+        let notes = check(&os, "python -V && ls | python script.py && cargo build && cd ..");
+        assert_eq!(notes, vec![
+            ToolchainNote::Alternative {
+                program: "python".to_string(),
+                alternative: "python3".to_string(),
+            },
+            ToolchainNote::Missing {
+                program: "cargo".to_string(),
+            },
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_check_versions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // The programs are run from a real directory that is mirrored in the test file system,
+        // where they are looked up
+        let os = Os::new().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let ran = dir.path().join("ran");
+        for program in ["node", "tool"] {
+            let path = dir.path().join(program);
+            let script = format!("#!/bin/sh\necho {program} >> {}\necho v16.0.0\n", ran.display());
+            std::fs::write(&path, script).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            os.fs.create_dir_all(dir.path()).await.unwrap();
+            os.fs.write(&path, "").await.unwrap();
+        }
+        unsafe { os.env.set_var("PATH", dir.path()) };
+        let assumed = HashMap::from([
+            ("node".to_string(), ">=18".to_string()),
+            ("tool".to_string(), ">=20".to_string()),
+        ]);
+        let probed = |notes: Vec<ToolchainNote>| {
+            let mut programs = notes
+                .into_iter()
+                .map(|note| match note {
+                    ToolchainNote::Version { program, .. } => program,
+                    note => panic!("unexpected note {note:?}"),
+                })
+                .collect::<Vec<_>>();
+            programs.sort();
+            programs
+        };
+        let runs = || std::fs::read_to_string(&ran).unwrap_or_default();
+
+        // Before approval only the toolchain the command starts with is run
+        assert_eq!(
+            probed(check_versions(&os, "node app.js | tool", &assumed, false).await),
+            ["node"]
+        );
+        assert!(
+            check_versions(&os, "tool && node app.js", &assumed, false)
+                .await
+                .is_empty()
+        );
+        assert_eq!(runs(), "node\n");
+        // And the rest of the programs the command runs once it is approved
+        assert_eq!(
+            probed(check_versions(&os, "node app.js | tool", &assumed, true).await),
+            ["tool"]
+        );
+        assert_eq!(
+            probed(check_versions(&os, "tool && node app.js", &assumed, true).await),
+            ["node", "tool"]
+        );
+        // Programs the command doesn't run are never run
+        assert!(check_versions(&os, "ls", &assumed, true).await.is_empty());
+        let mut runs = runs().lines().map(str::to_string).collect::<Vec<_>>();
+        runs.sort();
+        assert_eq!(runs, ["node", "node", "tool", "tool"]);
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("v18.19.0"), Some(Version::new(18, 19, 0)));
+        assert_eq!(parse_version("Python 3.11"), Some(Version::new(3, 11, 0)));
+        assert_eq!(
+            parse_version("cargo 1.87.0 (99624be96 2025-05-06)"),
+            Some(Version::new(1, 87, 0))
+        );
+        assert_eq!(parse_version("unknown"), None);
+    }
+}
@@ -6,7 +6,14 @@ use eyre::{
     Context as EyreContext,
     Result,
 };
-use tokio::io::AsyncBufReadExt;
+use tokio::io::{
+    AsyncBufReadExt,
+    AsyncReadExt,
+};
+use tokio::process::{
+    ChildStderr,
+    ChildStdout,
+};
 use tokio::select;
 use tracing::error;
 
@@ -14,42 +21,53 @@ use super::{
     CommandResult,
     format_output,
 };
+use crate::cli::agent::sandbox::SandboxConfig;
+use crate::util::process::MeteredChild;
 
 /// Run a bash command on Unix systems.
 /// # Arguments
 /// * `command` - The command to run
+/// * `sandbox` - the sandbox to run the command in, if any
 /// * `max_result_size` - max size of output streams, truncating if required
 /// * `updates` - output stream to push informational messages about the progress
 /// # Returns
 /// A [`CommandResult`]
 pub async fn run_command<W: Write>(
     command: &str,
+    sandbox: Option<&SandboxConfig>,
     max_result_size: usize,
     mut updates: Option<W>,
 ) -> Result<CommandResult> {
     let shell = std::env::var("AMAZON_Q_CHAT_SHELL").unwrap_or("bash".to_string());
+    let args = match sandbox {
+        Some(sandbox) => sandbox.command(&shell, command, &std::env::current_dir()?)?,
+        None => vec![shell, "-c".to_string(), command.to_string()],
+    };
 
     // We need to maintain a handle on stderr and stdout, but pipe it to the terminal as well
-    let mut child = tokio::process::Command::new(shell)
-        .arg("-c")
-        .arg(command)
+    let mut child = std::process::Command::new(&args[0])
+        .args(&args[1..])
         .stdin(Stdio::inherit())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .wrap_err_with(|| format!("Unable to spawn command '{}'", command))?;
+    // The command is killed when it times out or is cancelled
+    let metered = MeteredChild::new(&child);
+    let mut stdout = ChildStdout::from_std(child.stdout.take().unwrap())?;
+    let mut stderr = ChildStderr::from_std(child.stderr.take().unwrap())?;
+    let wait = metered.wait();
+    tokio::pin!(wait);
 
     let stdout_final: String;
     let stderr_final: String;
-    let exit_status;
+    let (exit_status, resource_usage);
 
     // Buffered output vs all-at-once
     if let Some(u) = updates.as_mut() {
-        let stdout = child.stdout.take().unwrap();
         let stdout = tokio::io::BufReader::new(stdout);
         let mut stdout = stdout.lines();
 
-        let stderr = child.stderr.take().unwrap();
         let stderr = tokio::io::BufReader::new(stderr);
         let mut stderr = stderr.lines();
 
@@ -59,7 +77,7 @@ pub async fn run_command<W: Write>(
 
         let mut stdout_done = false;
         let mut stderr_done = false;
-        exit_status = loop {
+        (exit_status, resource_usage) = loop {
             select! {
                 biased;
                 line = stdout.next_line(), if !stdout_done => match line {
@@ -84,8 +102,8 @@ pub async fn run_command<W: Write>(
                     Ok(None) => stderr_done = true,
                     Err(err) => error!(%err, "Failed to read stderr of child process"),
                 },
-                exit_status = child.wait() => {
-                    break exit_status;
+                exited = &mut wait => {
+                    break exited;
                 },
             };
         }
@@ -101,18 +119,22 @@ pub async fn run_command<W: Write>(
         // NOTE: If we don't split this logic, then any writes to stdout while calling
         // this function concurrently may cause the piped child output to be ignored
 
-        let output = child
-            .wait_with_output()
-            .await
-            .wrap_err_with(|| format!("No exit status for '{}'", command))?;
+        let mut stdout_bytes = Vec::new();
+        let mut stderr_bytes = Vec::new();
+        ((exit_status, resource_usage), _, _) = tokio::try_join!(
+            &mut wait,
+            stdout.read_to_end(&mut stdout_bytes),
+            stderr.read_to_end(&mut stderr_bytes)
+        )
+        .wrap_err_with(|| format!("No exit status for '{}'", command))?;
 
-        exit_status = output.status;
-        stdout_final = String::from_utf8_lossy(&output.stdout).to_string();
-        stderr_final = String::from_utf8_lossy(&output.stderr).to_string();
+        stdout_final = String::from_utf8_lossy(&stdout_bytes).to_string();
+        stderr_final = String::from_utf8_lossy(&stderr_bytes).to_string();
     }
 
     Ok(CommandResult {
         exit_status: exit_status.code(),
+        resource_usage,
         stdout: format_output(&stdout_final, max_result_size),
         stderr: format_output(&stderr_final, max_result_size),
     })
@@ -122,10 +144,12 @@ pub async fn run_command<W: Write>(
 mod tests {
     use crate::cli::chat::tools::OutputKind;
     use crate::cli::chat::tools::execute::ExecuteCommand;
+    use crate::os::Os;
 
     #[ignore = "todo: fix failing on musl for some reason"]
     #[tokio::test]
     async fn test_execute_bash_tool() {
+        let os = Os::new().await.unwrap();
         let mut stdout = std::io::stdout();
 
         // Verifying stdout
@@ -134,7 +158,7 @@ mod tests {
         });
         let out = serde_json::from_value::<ExecuteCommand>(v)
             .unwrap()
-            .invoke(&mut stdout)
+            .invoke(&os, &mut stdout)
             .await
             .unwrap();
 
@@ -152,7 +176,7 @@ mod tests {
         });
         let out = serde_json::from_value::<ExecuteCommand>(v)
             .unwrap()
-            .invoke(&mut stdout)
+            .invoke(&os, &mut stdout)
             .await
             .unwrap();
 
@@ -170,7 +194,7 @@ mod tests {
         });
         let out = serde_json::from_value::<ExecuteCommand>(v)
             .unwrap()
-            .invoke(&mut stdout)
+            .invoke(&os, &mut stdout)
             .await
             .unwrap();
         if let OutputKind::Json(json) = out.output {
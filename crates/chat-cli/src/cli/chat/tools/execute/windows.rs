@@ -5,6 +5,7 @@ use std::process::Stdio;
 use eyre::{
     Context as EyreContext,
     Result,
+    bail,
 };
 use tokio::io::AsyncBufReadExt;
 use tokio::select;
@@ -14,19 +15,26 @@ use super::{
     CommandResult,
     format_output,
 };
+use crate::cli::agent::sandbox::SandboxConfig;
+use crate::util::process::ResourceUsage;
 
 /// Run a command on Windows using cmd.exe.
 /// # Arguments
 /// * `command` - The command to run
+/// * `sandbox` - must be None, commands can't be sandboxed on Windows
 /// * `max_result_size` - max size of output streams, truncating if required
 /// * `updates` - output stream to push informational messages about the progress
 /// # Returns
 /// A [`CommandResult`]
 pub async fn run_command<W: Write>(
     command: &str,
+    sandbox: Option<&SandboxConfig>,
     max_result_size: usize,
     mut updates: Option<W>,
 ) -> Result<CommandResult> {
+    if sandbox.is_some() {
+        bail!("Commands can't be sandboxed on Windows");
+    }
     // We need to maintain a handle on stderr and stdout, but pipe it to the terminal as well
     let mut child = tokio::process::Command::new("cmd")
         .arg("/C")
@@ -34,6 +42,8 @@ pub async fn run_command<W: Write>(
         .stdin(Stdio::inherit())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        // The command is killed when it times out or is cancelled
+        .kill_on_drop(true)
         .spawn()
         .wrap_err_with(|| format!("Unable to spawn command '{}'", command))?;
 
@@ -107,6 +117,7 @@ pub async fn run_command<W: Write>(
 
     Ok(CommandResult {
         exit_status: exit_status.code(),
+        resource_usage: ResourceUsage::default(),
         stdout: format_output(&stdout_final, max_result_size),
         stderr: format_output(&stderr_final, max_result_size),
     })
@@ -116,9 +127,11 @@ pub async fn run_command<W: Write>(
 mod tests {
     use crate::cli::chat::tools::OutputKind;
     use crate::cli::chat::tools::execute::ExecuteCommand;
+    use crate::os::Os;
 
     #[tokio::test]
     async fn test_execute_cmd_tool() {
+        let os = Os::new().await.unwrap();
         let mut stdout = std::io::stdout();
 
         // Verifying stdout
@@ -127,7 +140,7 @@ mod tests {
         });
         let out = serde_json::from_value::<ExecuteCommand>(v)
             .unwrap()
-            .invoke(&mut stdout)
+            .invoke(&os, &mut stdout)
             .await
             .unwrap();
 
@@ -145,7 +158,7 @@ mod tests {
         });
         let out = serde_json::from_value::<ExecuteCommand>(v)
             .unwrap()
-            .invoke(&mut stdout)
+            .invoke(&os, &mut stdout)
             .await
             .unwrap();
 
@@ -163,7 +176,7 @@ mod tests {
         });
         let out = serde_json::from_value::<ExecuteCommand>(v)
             .unwrap()
-            .invoke(&mut stdout)
+            .invoke(&os, &mut stdout)
             .await
             .unwrap();
         if let OutputKind::Json(json) = out.output {
@@ -0,0 +1,308 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use bstr::ByteSlice;
+use crossterm::style::Stylize;
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use syntect::easy::HighlightLines;
+use syntect::util::{
+    LinesWithEndings,
+    as_24_bit_terminal_escaped,
+};
+use tokio::io::AsyncWriteExt;
+
+use super::fs_write::{
+    SYNTAX_SET,
+    THEME_SET,
+};
+use super::{
+    InvokeOutput,
+    OutputKind,
+    Tool,
+    supports_truecolor,
+};
+use crate::cli::agent::Agent;
+use crate::os::Os;
+use crate::util::theme::theme;
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How the output of a tool is rendered in the terminal, configured per tool under
+/// `toolFormatters` in the agent config.
+///
+/// Formatters only change what is displayed. The model always receives the raw tool output.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ToolFormatter {
+    /// One of the formatters that ship with the CLI
+    Builtin(BuiltinFormatter),
+    /// A shell command that receives the raw output on stdin and prints the formatted output
+    Command(String),
+    /// A template where `{{tool}}` and `{{output}}` are replaced with the tool name and its
+    /// output. If the output is json, values can be referenced by JSON pointer, e.g.
+    /// `{{/resource_changes/0/address}}`
+    Template(String),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum BuiltinFormatter {
+    /// Pretty prints and highlights json
+    Json,
+    /// Highlights yaml
+    Yaml,
+    /// Colors added, removed and hunk header lines of a unified diff
+    Diff,
+}
+
+/// Returns the formatter the agent configured for the tool, if any.
+///
+/// Native tools are looked up by name. Tools from MCP servers are looked up as
+/// `@{server}/{tool}`, falling back to `@{server}` to apply a formatter to all of a server's tools.
+pub fn find_formatter<'a>(agent: &'a Agent, tool: &Tool) -> Option<&'a ToolFormatter> {
+    tool.settings_keys()
+        .iter()
+        .find_map(|key| agent.tool_formatters.get(key))
+}
+
+/// Renders the tool output with the given formatter. Returns [None] if the output has nothing to
+/// format, e.g. it only contains images.
+pub async fn format_output(
+    os: &Os,
+    formatter: &ToolFormatter,
+    tool_name: &str,
+    output: &InvokeOutput,
+) -> Result<Option<String>> {
+    let Some(raw) = raw_text(&output.output) else {
+        return Ok(None);
+    };
+
+    let formatted = match formatter {
+        ToolFormatter::Builtin(BuiltinFormatter::Json) => match serde_json::from_str::<serde_json::Value>(&raw) {
+            Ok(value) => highlight(os, &serde_json::to_string_pretty(&value)?, "json")?,
+            Err(_) => raw,
+        },
+        ToolFormatter::Builtin(BuiltinFormatter::Yaml) => highlight(os, &raw, "yaml")?,
+        ToolFormatter::Builtin(BuiltinFormatter::Diff) => color_diff(&raw),
+        ToolFormatter::Command(command) => run_command(command, &raw).await?,
+        ToolFormatter::Template(template) => render_template(template, tool_name, &raw),
+    };
+    Ok(Some(formatted))
+}
+
+/// The text of the tool output. Results from MCP servers are unwrapped to their text content.
+fn raw_text(output: &OutputKind) -> Option<String> {
+    match output {
+        OutputKind::Text(text) | OutputKind::Mixed { text, .. } => Some(text.clone()),
+        OutputKind::Json(value) => {
+            let texts = value
+                .get("content")
+                .and_then(|c| c.as_array())
+                .map(|content| {
+                    content
+                        .iter()
+                        .filter_map(|c| c.get("text").and_then(|t| t.as_str()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            match texts.is_empty() {
+                true => Some(value.to_string()),
+                false => Some(texts.join("\n")),
+            }
+        },
+        OutputKind::Images(_) => None,
+    }
+}
+
+fn highlight(os: &Os, text: &str, extension: &str) -> Result<String> {
+    if !supports_truecolor(os) {
+        return Ok(text.to_string());
+    }
+    let Some(syntax) = SYNTAX_SET.find_syntax_by_extension(extension) else {
+        return Ok(text.to_string());
+    };
+
+    let mut highlighter = HighlightLines::new(syntax, &THEME_SET.themes["base16-ocean.dark"]);
+    let mut res = String::new();
+    for line in LinesWithEndings::from(text) {
+        let ranges = highlighter.highlight_line(line, &SYNTAX_SET)?;
+        res.push_str(&as_24_bit_terminal_escaped(&ranges[..], false));
+    }
+    res.push_str("\x1b[0m");
+    Ok(res)
+}
+
+fn color_diff(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.starts_with("+++") || line.starts_with("---") {
+                line.bold().to_string()
+            } else if line.starts_with('+') {
+                line.with(theme().diff_add).to_string()
+            } else if line.starts_with('-') {
+                line.with(theme().diff_remove).to_string()
+            } else if line.starts_with("@@") {
+                line.with(theme().info).to_string()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_template(template: &str, tool_name: &str, raw: &str) -> String {
+    let json = serde_json::from_str::<serde_json::Value>(raw).ok();
+    let mut res = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        res.push_str(&rest[..start]);
+        let key = rest[start + 2..start + end].trim();
+        match key {
+            "tool" => res.push_str(tool_name),
+            "output" => res.push_str(raw),
+            pointer if pointer.starts_with('/') => match json.as_ref().and_then(|json| json.pointer(pointer)) {
+                Some(serde_json::Value::String(s)) => res.push_str(s),
+                Some(value) => res.push_str(&value.to_string()),
+                None => (),
+            },
+            _ => res.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    res.push_str(rest);
+    res
+}
+
+async fn run_command(command: &str, raw: &str) -> Result<String> {
+    #[cfg(unix)]
+    let mut cmd = tokio::process::Command::new("bash");
+    #[cfg(unix)]
+    cmd.arg("-c");
+    #[cfg(windows)]
+    let mut cmd = tokio::process::Command::new("cmd");
+    #[cfg(windows)]
+    cmd.arg("/C");
+
+    let mut child = cmd
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdin = child.stdin.take();
+
+    // The input is written while the output is read, since a formatter that streams its output
+    // stops reading once its stdout fills up, and both count towards the timeout
+    let write = async move {
+        if let Some(mut stdin) = stdin {
+            // The formatter may exit without reading all of its input
+            let _ = stdin.write_all(raw.as_bytes()).await;
+        }
+    };
+    let run = async move {
+        let ((), output) = tokio::join!(write, child.wait_with_output());
+        output
+    };
+    let output = match tokio::time::timeout(COMMAND_TIMEOUT, run).await {
+        Ok(output) => output?,
+        Err(_) => bail!("formatter timed out after {} ms", COMMAND_TIMEOUT.as_millis()),
+    };
+    if !output.status.success() {
+        return Err(eyre!(
+            "formatter exited with {}: {}",
+            output.status,
+            output.stderr.to_str_lossy().trim()
+        ));
+    }
+    Ok(output.stdout.to_str_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatter_deser() {
+        let formatters =
+            serde_json::from_value::<std::collections::HashMap<String, ToolFormatter>>(serde_json::json!({
+                "execute_bash": { "command": "cat -n" },
+                "@terraform": { "builtin": "json" },
+                "@git/diff": { "builtin": "diff" },
+                "fs_read": { "template": "{{tool}}: {{output}}" }
+            }))
+            .unwrap();
+        assert_eq!(formatters["@terraform"], ToolFormatter::Builtin(BuiltinFormatter::Json));
+        assert_eq!(formatters["execute_bash"], ToolFormatter::Command("cat -n".to_string()));
+    }
+
+    #[test]
+    fn test_raw_text() {
+        let mcp_result = serde_json::json!({ "content": [{ "type": "text", "text": "hello" }] });
+        assert_eq!(raw_text(&OutputKind::Json(mcp_result)).as_deref(), Some("hello"));
+        assert_eq!(
+            raw_text(&OutputKind::Json(serde_json::json!({ "a": 1 }))).as_deref(),
+            Some(r#"{"a":1}"#)
+        );
+        assert_eq!(raw_text(&OutputKind::Images(vec![])), None);
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(render_template("{{tool}} said {{ output }}", "t", "hi"), "t said hi");
+        assert_eq!(
+            render_template(
+                "{{/plan/add}} to add, {{/missing}}{{unknown}}",
+                "t",
+                r#"{"plan":{"add":3}}"#
+            ),
+            "3 to add, {{unknown}}"
+        );
+        assert_eq!(render_template("unterminated {{", "t", "hi"), "unterminated {{");
+    }
+
+    #[test]
+    fn test_color_diff() {
+        let colored = color_diff("@@ -1 +1 @@\n-old\n+new\n same");
+        assert!(colored.contains(&"-old".red().to_string()));
+        assert!(colored.contains(&"+new".green().to_string()));
+        assert!(colored.ends_with("\n same"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_format_output_command() {
+        let os = Os::new().await.unwrap();
+        let output = InvokeOutput {
+            output: OutputKind::Text("hello".to_string()),
+            ..Default::default()
+        };
+        let formatted = format_output(&os, &ToolFormatter::Command("tr a-z A-Z".to_string()), "t", &output)
+            .await
+            .unwrap();
+        assert_eq!(formatted.as_deref(), Some("HELLO"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_command_streaming() {
+        // More than fits in the pipes, which a formatter that streams its output only reads as its
+        // output is read
+        let raw = "line\n".repeat(200_000);
+        let formatted = run_command("cat -n", &raw).await.unwrap();
+        assert_eq!(formatted.lines().count(), 200_000);
+    }
+}
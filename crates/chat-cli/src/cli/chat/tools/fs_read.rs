@@ -5,7 +5,6 @@ use std::io::Write;
 use crossterm::queue;
 use crossterm::style::{
     self,
-    Color,
 };
 use eyre::{
     Result,
@@ -30,6 +29,7 @@ use super::{
     InvokeOutput,
     MAX_TOOL_RESPONSE_SIZE,
     OutputKind,
+    eval_path_perm,
     format_path,
     sanitize_path_tool_arg,
 };
@@ -45,6 +45,7 @@ use crate::cli::chat::util::images::{
     pre_process,
 };
 use crate::os::Os;
+use crate::util::theme::theme;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct FsRead {
@@ -63,6 +64,19 @@ pub enum FsReadOperation {
 }
 
 impl FsRead {
+    /// The paths read by all of the operations, as provided by the model.
+    pub fn paths(&self) -> Vec<&str> {
+        self.operations
+            .iter()
+            .flat_map(|op| match op {
+                FsReadOperation::Line(fs_line) => vec![fs_line.path.as_str()],
+                FsReadOperation::Directory(fs_directory) => vec![fs_directory.path.as_str()],
+                FsReadOperation::Search(fs_search) => vec![fs_search.path.as_str()],
+                FsReadOperation::Image(fs_image) => fs_image.image_paths.iter().map(String::as_str).collect(),
+            })
+            .collect()
+    }
+
     pub async fn validate(&mut self, os: &Os) -> Result<()> {
         if self.operations.is_empty() {
             bail!("At least one operation must be provided");
@@ -82,7 +96,7 @@ impl FsRead {
             queue!(
                 updates,
                 style::Print("Batch fs_read operation with "),
-                style::SetForegroundColor(Color::Green),
+                style::SetForegroundColor(theme().success),
                 style::Print(self.operations.len()),
                 style::ResetColor,
                 style::Print(" operations:\n")
@@ -99,7 +113,7 @@ impl FsRead {
         }
     }
 
-    pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
+    pub fn eval_perm(&self, os: &Os, agent: &Agent) -> PermissionEvalResult {
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "camelCase")]
         struct Settings {
@@ -163,19 +177,23 @@ impl FsRead {
                                     FsReadOperation::Line(FsLine { path, .. })
                                     | FsReadOperation::Directory(FsDirectory { path, .. })
                                     | FsReadOperation::Search(FsSearch { path, .. }) => {
-                                        if deny_set.is_match(path) {
-                                            return PermissionEvalResult::Deny;
-                                        }
-                                        if allow_set.is_match(path) {
-                                            return PermissionEvalResult::Allow;
+                                        if let Some(result) = eval_path_perm(os, path, &allow_set, &deny_set) {
+                                            return result;
                                         }
                                     },
                                     FsReadOperation::Image(fs_image) => {
-                                        let paths = &fs_image.image_paths;
-                                        if paths.iter().any(|path| deny_set.is_match(path)) {
+                                        let results = fs_image
+                                            .image_paths
+                                            .iter()
+                                            .map(|path| eval_path_perm(os, path, &allow_set, &deny_set))
+                                            .collect::<Vec<_>>();
+                                        if results.contains(&Some(PermissionEvalResult::Deny)) {
                                             return PermissionEvalResult::Deny;
                                         }
-                                        if paths.iter().all(|path| allow_set.is_match(path)) {
+                                        if results
+                                            .iter()
+                                            .all(|result| *result == Some(PermissionEvalResult::Allow))
+                                        {
                                             return PermissionEvalResult::Allow;
                                         }
                                     },
@@ -291,14 +309,17 @@ impl FsRead {
                         text: combined_text,
                         images: all_images,
                     },
+                    ..Default::default()
                 })
             } else if !all_images.is_empty() {
                 Ok(InvokeOutput {
                     output: OutputKind::Images(all_images),
+                    ..Default::default()
                 })
             } else {
                 Ok(InvokeOutput {
                     output: OutputKind::Text(combined_text),
+                    ..Default::default()
                 })
             }
         }
@@ -366,6 +387,7 @@ impl FsImage {
         super::queue_function_result("Successfully read image", updates, false, false)?;
         Ok(InvokeOutput {
             output: OutputKind::Images(valid_images),
+            ..Default::default()
         })
     }
 
@@ -373,7 +395,7 @@ impl FsImage {
         queue!(
             updates,
             style::Print("Reading images: "),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print(&self.image_paths.join("\n")),
             style::Print("\n"),
             style::ResetColor,
@@ -414,7 +436,7 @@ impl FsLine {
         queue!(
             updates,
             style::Print("Reading file: "),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print(&self.path),
             style::ResetColor,
             style::Print(", "),
@@ -427,7 +449,7 @@ impl FsLine {
             _ if end == line_count => Ok(queue!(
                 updates,
                 style::Print("from line "),
-                style::SetForegroundColor(Color::Green),
+                style::SetForegroundColor(theme().success),
                 style::Print(start),
                 style::ResetColor,
                 style::Print(" to end of file"),
@@ -435,11 +457,11 @@ impl FsLine {
             _ => Ok(queue!(
                 updates,
                 style::Print("from line "),
-                style::SetForegroundColor(Color::Green),
+                style::SetForegroundColor(theme().success),
                 style::Print(start),
                 style::ResetColor,
                 style::Print(" to "),
-                style::SetForegroundColor(Color::Green),
+                style::SetForegroundColor(theme().success),
                 style::Print(end),
                 style::ResetColor,
             )?),
@@ -498,6 +520,7 @@ time. You tried to read {byte_count} bytes. Try executing with fewer lines speci
 
         Ok(InvokeOutput {
             output: OutputKind::Text(file_contents),
+            ..Default::default()
         })
     }
 
@@ -542,11 +565,11 @@ impl FsSearch {
         queue!(
             updates,
             style::Print("Searching: "),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print(&self.path),
             style::ResetColor,
             style::Print(" for pattern: "),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print(&self.pattern.to_lowercase()),
             style::ResetColor,
         )?;
@@ -603,6 +626,7 @@ impl FsSearch {
 
         Ok(InvokeOutput {
             output: OutputKind::Text(serde_json::to_string(&results)?),
+            ..Default::default()
         })
     }
 
@@ -637,7 +661,7 @@ impl FsDirectory {
         queue!(
             updates,
             style::Print("Reading directory: "),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print(&self.path),
             style::ResetColor,
             style::Print(" "),
@@ -746,6 +770,7 @@ impl FsDirectory {
 
         Ok(InvokeOutput {
             output: OutputKind::Text(result),
+            ..Default::default()
         })
     }
 
@@ -1,11 +1,13 @@
 use std::io::Write;
-use std::path::Path;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::sync::LazyLock;
 
 use crossterm::queue;
 use crossterm::style::{
     self,
-    Color,
 };
 use eyre::{
     ContextCompat as _,
@@ -33,6 +35,7 @@ use tracing::{
 
 use super::{
     InvokeOutput,
+    eval_path_perm,
     format_path,
     sanitize_path_tool_arg,
     supports_truecolor,
@@ -41,10 +44,13 @@ use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
 };
+use crate::cli::chat::artifacts::ArtifactKind;
 use crate::os::Os;
+use crate::util::directories;
+use crate::util::theme::theme;
 
-static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
-static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+pub(super) static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+pub(super) static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "command")]
@@ -82,6 +88,21 @@ pub enum FsWrite {
 
 impl FsWrite {
     pub async fn invoke(&self, os: &Os, output: &mut impl Write) -> Result<InvokeOutput> {
+        let backup = back_up(os, &sanitize_path_tool_arg(os, self.path())).await;
+        if let Err(err) = self.write(os, output).await {
+            if let Some(backup) = &backup {
+                os.fs.remove_file(backup).await.ok();
+            }
+            return Err(err);
+        }
+
+        Ok(InvokeOutput {
+            artifacts: backup.map(|path| (ArtifactKind::Backup, path)).into_iter().collect(),
+            ..Default::default()
+        })
+    }
+
+    async fn write(&self, os: &Os, output: &mut impl Write) -> Result<()> {
         let cwd = os.env.current_dir()?;
         match self {
             FsWrite::Create { path, .. } => {
@@ -99,89 +120,101 @@ impl FsWrite {
                 queue!(
                     output,
                     style::Print(invoke_description),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(format_path(cwd, &path)),
                     style::ResetColor,
                     style::Print("\n"),
                 )?;
 
                 write_to_file(os, path, file_text).await?;
-                Ok(Default::default())
+                Ok(())
             },
-            FsWrite::StrReplace {
-                path, old_str, new_str, ..
-            } => {
+            FsWrite::StrReplace { path, .. } => {
                 let path = sanitize_path_tool_arg(os, path);
                 let file = os.fs.read_to_string(&path).await?;
-                let matches = file.match_indices(old_str).collect::<Vec<_>>();
                 queue!(
                     output,
                     style::Print("Updating: "),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(format_path(cwd, &path)),
                     style::ResetColor,
                     style::Print("\n"),
                 )?;
-                match matches.len() {
-                    0 => Err(eyre!("no occurrences of \"{old_str}\" were found")),
-                    1 => {
-                        let file = file.replacen(old_str, new_str, 1);
-                        os.fs.write(path, file).await?;
-                        Ok(Default::default())
-                    },
-                    x => Err(eyre!("{x} occurrences of old_str were found when only 1 is expected")),
-                }
+                os.fs.write(path, self.updated_content(file)?).await?;
+                Ok(())
             },
-            FsWrite::Insert {
-                path,
-                insert_line,
-                new_str,
-                ..
-            } => {
+            FsWrite::Insert { path, .. } => {
                 let path = sanitize_path_tool_arg(os, path);
-                let mut file = os.fs.read_to_string(&path).await?;
+                let file = os.fs.read_to_string(&path).await?;
                 queue!(
                     output,
                     style::Print("Updating: "),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(format_path(cwd, &path)),
                     style::ResetColor,
                     style::Print("\n"),
                 )?;
 
-                // Get the index of the start of the line to insert at.
-                let num_lines = file.lines().enumerate().map(|(i, _)| i + 1).last().unwrap_or(1);
-                let insert_line = insert_line.clamp(&0, &num_lines);
-                let mut i = 0;
-                for _ in 0..*insert_line {
-                    let line_len = &file[i..].find("\n").map_or(file[i..].len(), |i| i + 1);
-                    i += line_len;
-                }
-                file.insert_str(i, new_str);
-                write_to_file(os, &path, file).await?;
-                Ok(Default::default())
+                write_to_file(os, &path, self.updated_content(file)?).await?;
+                Ok(())
             },
-            FsWrite::Append { path, new_str, .. } => {
+            FsWrite::Append { path, .. } => {
                 let path = sanitize_path_tool_arg(os, path);
 
                 queue!(
                     output,
                     style::Print("Appending to: "),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(format_path(cwd, &path)),
                     style::ResetColor,
                     style::Print("\n"),
                 )?;
 
-                let mut file = os.fs.read_to_string(&path).await?;
+                let file = os.fs.read_to_string(&path).await?;
+                write_to_file(os, path, self.updated_content(file)?).await?;
+                Ok(())
+            },
+        }
+    }
+
+    /// The contents of the file at [Self::path] after the write, given its current contents.
+    pub fn updated_content(&self, mut file: String) -> Result<String> {
+        let mut content = match self {
+            FsWrite::Create { .. } => self.canonical_create_command_text(),
+            FsWrite::StrReplace { old_str, new_str, .. } => {
+                return match file.match_indices(old_str).count() {
+                    0 => Err(eyre!("no occurrences of \"{old_str}\" were found")),
+                    1 => Ok(file.replacen(old_str, new_str, 1)),
+                    x => Err(eyre!("{x} occurrences of old_str were found when only 1 is expected")),
+                };
+            },
+            FsWrite::Insert {
+                insert_line, new_str, ..
+            } => {
+                // Get the index of the start of the line to insert at.
+                let num_lines = file.lines().enumerate().map(|(i, _)| i + 1).last().unwrap_or(1);
+                let insert_line = insert_line.clamp(&0, &num_lines);
+                let mut i = 0;
+                for _ in 0..*insert_line {
+                    let line_len = &file[i..].find("\n").map_or(file[i..].len(), |i| i + 1);
+                    i += line_len;
+                }
+                file.insert_str(i, new_str);
+                file
+            },
+            FsWrite::Append { new_str, .. } => {
                 if !file.ends_with_newline() {
                     file.push('\n');
                 }
                 file.push_str(new_str);
-                write_to_file(os, path, file).await?;
-                Ok(Default::default())
+                file
             },
+        };
+        // Like write_to_file, which ends every file it writes with a newline
+        if !content.ends_with_newline() {
+            content.push('\n');
         }
+        Ok(content)
     }
 
     pub fn queue_description(&self, os: &Os, output: &mut impl Write) -> Result<()> {
@@ -295,21 +328,25 @@ impl FsWrite {
         Ok(())
     }
 
-    fn print_relative_path(&self, os: &Os, output: &mut impl Write) -> Result<()> {
-        let cwd = os.env.current_dir()?;
-        let path = match self {
+    /// The path of the file being written, as provided by the model.
+    pub fn path(&self) -> &str {
+        match self {
             FsWrite::Create { path, .. } => path,
             FsWrite::StrReplace { path, .. } => path,
             FsWrite::Insert { path, .. } => path,
             FsWrite::Append { path, .. } => path,
-        };
+        }
+    }
+
+    fn print_relative_path(&self, os: &Os, output: &mut impl Write) -> Result<()> {
+        let cwd = os.env.current_dir()?;
         // Sanitize the path to handle tilde expansion
-        let path = sanitize_path_tool_arg(os, path);
+        let path = sanitize_path_tool_arg(os, self.path());
         let relative_path = format_path(cwd, &path);
         queue!(
             output,
             style::Print("Path: "),
-            style::SetForegroundColor(Color::Green),
+            style::SetForegroundColor(theme().success),
             style::Print(&relative_path),
             style::ResetColor,
             style::Print("\n\n"),
@@ -346,7 +383,7 @@ impl FsWrite {
         }
     }
 
-    pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
+    pub fn eval_perm(&self, os: &Os, agent: &Agent) -> PermissionEvalResult {
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "camelCase")]
         struct Settings {
@@ -357,8 +394,10 @@ impl FsWrite {
         }
 
         let is_in_allowlist = agent.allowed_tools.contains("fs_write");
+        // The settings scope the tool whether or not it is trusted, so that only writes to the
+        // allowed paths go ahead without asking
         match agent.tools_settings.get("fs_write") {
-            Some(settings) if is_in_allowlist => {
+            Some(settings) => {
                 let Settings {
                     allowed_paths,
                     denied_paths,
@@ -400,11 +439,8 @@ impl FsWrite {
                             | Self::Insert { path, .. }
                             | Self::Append { path, .. }
                             | Self::StrReplace { path, .. } => {
-                                if deny_set.is_match(path) {
-                                    return PermissionEvalResult::Deny;
-                                }
-                                if allow_set.is_match(path) {
-                                    return PermissionEvalResult::Allow;
+                                if let Some(result) = eval_path_perm(os, path, &allow_set, &deny_set) {
+                                    return result;
                                 }
                             },
                         }
@@ -428,6 +464,33 @@ impl FsWrite {
     }
 }
 
+/// Copies the file at `path` to [directories::chat_backups_dir] before it is changed, returning
+/// where the copy was written. New files are not backed up.
+async fn back_up(os: &Os, path: &Path) -> Option<PathBuf> {
+    if !os.fs.exists(path) {
+        return None;
+    }
+    let file_name = path.file_name()?.to_string_lossy();
+    let backup = directories::chat_backups_dir()
+        .ok()?
+        .join(format!("{}-{file_name}", uuid::Uuid::new_v4().simple()));
+    let result: std::io::Result<()> = async {
+        if let Some(parent) = backup.parent() {
+            os.fs.create_dir_all(parent).await?;
+        }
+        os.fs.copy(path, &backup).await?;
+        Ok(())
+    }
+    .await;
+    match result {
+        Ok(()) => Some(backup),
+        Err(err) => {
+            warn!(?err, "failed to back up {}", path.display());
+            None
+        },
+    }
+}
+
 /// Writes `content` to `path`, adding a newline if necessary.
 async fn write_to_file(os: &Os, path: impl AsRef<Path>, mut content: String) -> Result<()> {
     let path_ref = path.as_ref();
@@ -533,19 +596,17 @@ fn print_diff(
         // Define the colors per line.
         let (text_color, gutter_bg_color, line_bg_color) = match (change.tag(), new_str.truecolor) {
             (similar::ChangeTag::Equal, true) => (style::Color::Reset, new_str.gutter_bg, new_str.line_bg),
-            (similar::ChangeTag::Delete, true) => (
-                style::Color::Reset,
-                style::Color::Rgb { r: 79, g: 40, b: 40 },
-                style::Color::Rgb { r: 36, g: 25, b: 28 },
-            ),
-            (similar::ChangeTag::Insert, true) => (
-                style::Color::Reset,
-                style::Color::Rgb { r: 40, g: 67, b: 43 },
-                style::Color::Rgb { r: 24, g: 38, b: 30 },
-            ),
+            (similar::ChangeTag::Delete, true) => {
+                let (gutter_bg, line_bg) = theme().diff_remove_bg;
+                (style::Color::Reset, gutter_bg, line_bg)
+            },
+            (similar::ChangeTag::Insert, true) => {
+                let (gutter_bg, line_bg) = theme().diff_add_bg;
+                (style::Color::Reset, gutter_bg, line_bg)
+            },
             (similar::ChangeTag::Equal, false) => (style::Color::Reset, new_str.gutter_bg, new_str.line_bg),
-            (similar::ChangeTag::Delete, false) => (style::Color::Red, new_str.gutter_bg, new_str.line_bg),
-            (similar::ChangeTag::Insert, false) => (style::Color::Green, new_str.gutter_bg, new_str.line_bg),
+            (similar::ChangeTag::Delete, false) => (theme().diff_remove, new_str.gutter_bg, new_str.line_bg),
+            (similar::ChangeTag::Insert, false) => (theme().diff_add, new_str.gutter_bg, new_str.line_bg),
         };
         // Define the change tag character to print, if any.
         let sign = match change.tag() {
@@ -911,11 +972,16 @@ mod tests {
             "old_str": "1: Hello world!",
             "new_str": "1: Goodbye world!",
         });
-        serde_json::from_value::<FsWrite>(v)
+        let original = os.fs.read_to_string(TEST_FILE_PATH).await.unwrap();
+        let output = serde_json::from_value::<FsWrite>(v)
             .unwrap()
             .invoke(&os, &mut stdout)
             .await
             .unwrap();
+        let [(ArtifactKind::Backup, backup)] = output.artifacts.as_slice() else {
+            panic!("expected a backup of the file, found {:?}", output.artifacts);
+        };
+        assert_eq!(os.fs.read_to_string(backup).await.unwrap(), original);
         assert_eq!(
             os.fs
                 .read_to_string(TEST_FILE_PATH)
@@ -1169,4 +1235,46 @@ mod tests {
         let nested_content = os.fs.read_to_string(&nested_file_path).await.unwrap();
         assert_eq!(nested_content, "content in nested path\n");
     }
+
+    #[tokio::test]
+    async fn test_eval_perm_with_allowed_paths() {
+        let os = Os::new().await.unwrap();
+        let agent = |allowed_tools: Vec<&str>, settings: serde_json::Value| {
+            serde_json::from_value::<Agent>(serde_json::json!({
+                "allowedTools": allowed_tools,
+                "toolsSettings": { "fs_write": settings },
+            }))
+            .unwrap()
+        };
+        let eval = |agent: &Agent, path: &str| {
+            serde_json::from_value::<FsWrite>(serde_json::json!({
+                "path": path,
+                "command": "create",
+                "file_text": "",
+            }))
+            .unwrap()
+            .eval_perm(&os, agent)
+        };
+        let cwd = os.env.current_dir().unwrap();
+
+        // The allowed paths apply whether or not the tool is trusted
+        let settings = serde_json::json!({ "allowedPaths": ["src/**"], "deniedPaths": ["src/secrets/**"] });
+        for agent in [agent(vec![], settings.clone()), agent(vec!["fs_write"], settings)] {
+            assert_eq!(eval(&agent, "src/main.rs"), PermissionEvalResult::Allow);
+            assert_eq!(
+                eval(&agent, &cwd.join("src/main.rs").to_string_lossy()),
+                PermissionEvalResult::Allow
+            );
+            assert_eq!(eval(&agent, "Cargo.toml"), PermissionEvalResult::Ask);
+            assert_eq!(eval(&agent, "src/../Cargo.toml"), PermissionEvalResult::Ask);
+            assert_eq!(eval(&agent, "src/secrets/key"), PermissionEvalResult::Deny);
+        }
+
+        // Settings without allowed paths still ask before writing, trusted or not
+        let settings = serde_json::json!({ "deniedPaths": ["src/secrets/**"] });
+        let trusted = agent(vec!["fs_write"], settings.clone());
+        assert_eq!(eval(&trusted, "Cargo.toml"), PermissionEvalResult::Ask);
+        assert_eq!(eval(&trusted, "src/secrets/key"), PermissionEvalResult::Deny);
+        assert_eq!(eval(&agent(vec![], settings), "Cargo.toml"), PermissionEvalResult::Ask);
+    }
 }
@@ -0,0 +1,354 @@
+use std::io::Write;
+
+use crossterm::{
+    queue,
+    style,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use serde::Deserialize;
+use tracing::error;
+
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+};
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+use crate::util::theme::theme;
+
+const DEFAULT_LOG_COUNT: usize = 20;
+const MAX_LOG_COUNT: usize = 200;
+/// Config the read-only commands run with, so that they don't start the file system monitor
+/// program the repository configures.
+const READ_ONLY_CONFIG: [&str; 2] = ["-c", "core.fsmonitor="];
+
+/// Structured access to the git repository of the current directory, so that agents can inspect
+/// and commit changes without being allowed to run arbitrary commands.
+///
+/// Arguments are passed to git as they are rather than through a shell, and revisions can't start
+/// with `-` so that they can't be mistaken for options.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum Git {
+    Status,
+    Diff(GitDiff),
+    Log(GitLog),
+    Blame(GitBlame),
+    Commit(GitCommit),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitDiff {
+    /// Show the staged changes rather than the unstaged ones
+    #[serde(default)]
+    pub staged: bool,
+    /// Revision to compare the working tree with, e.g. `main` or `HEAD~3`
+    pub revision: Option<String>,
+    /// Only show a summary of the changed files
+    #[serde(default)]
+    pub stat: bool,
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitLog {
+    pub max_count: Option<usize>,
+    /// Revision or range to list, e.g. `main..HEAD`
+    pub revision: Option<String>,
+    /// Only list the commits that changed this path
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitBlame {
+    pub path: String,
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitCommit {
+    pub message: String,
+    /// Paths staged before committing. Without any, only what is already staged is committed
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+impl Git {
+    /// The name of the command, as used in the `allowedCommands` and `deniedCommands` settings.
+    fn command_name(&self) -> &'static str {
+        match self {
+            Git::Status => "status",
+            Git::Diff(_) => "diff",
+            Git::Log(_) => "log",
+            Git::Blame(_) => "blame",
+            Git::Commit(_) => "commit",
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        !matches!(self, Git::Commit(_))
+    }
+
+    /// The invocations of git the command runs, in order. The read-only commands run without
+    /// asking, so they don't use external diff or textconv programs the repository configures.
+    fn invocations(&self) -> Vec<Vec<String>> {
+        let args = |args: &[&str]| args.iter().map(|arg| (*arg).to_string()).collect::<Vec<_>>();
+        match self {
+            Git::Status => vec![args(&["status", "--short", "--branch"])],
+            Git::Diff(diff) => {
+                let mut invocation = args(&["diff", "--no-ext-diff", "--no-textconv"]);
+                if diff.staged {
+                    invocation.push("--cached".to_string());
+                }
+                if diff.stat {
+                    invocation.push("--stat".to_string());
+                }
+                invocation.extend(diff.revision.clone());
+                invocation.push("--".to_string());
+                invocation.extend(diff.paths.iter().cloned());
+                vec![invocation]
+            },
+            Git::Log(log) => {
+                let max_count = log.max_count.unwrap_or(DEFAULT_LOG_COUNT).clamp(1, MAX_LOG_COUNT);
+                let mut invocation = args(&[
+                    "log",
+                    "--no-ext-diff",
+                    "--no-textconv",
+                    "--date=short",
+                    "--format=%h %ad %an: %s",
+                ]);
+                invocation.push(format!("--max-count={max_count}"));
+                invocation.extend(log.revision.clone());
+                invocation.push("--".to_string());
+                invocation.extend(log.path.clone());
+                vec![invocation]
+            },
+            Git::Blame(blame) => {
+                let mut invocation = args(&["blame", "--no-textconv", "--date=short"]);
+                match (blame.start_line, blame.end_line) {
+                    (Some(start), Some(end)) => invocation.push(format!("-L{start},{end}")),
+                    (Some(start), None) => invocation.push(format!("-L{start},")),
+                    (None, Some(end)) => invocation.push(format!("-L1,{end}")),
+                    (None, None) => (),
+                }
+                invocation.extend(["--".to_string(), blame.path.clone()]);
+                vec![invocation]
+            },
+            Git::Commit(commit) => {
+                let mut invocations = Vec::new();
+                if !commit.paths.is_empty() {
+                    let mut add = args(&["add", "--"]);
+                    add.extend(commit.paths.iter().cloned());
+                    invocations.push(add);
+                }
+                invocations.push(args(&["commit", "-m", &commit.message]));
+                invocations
+            },
+        }
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        let revision = match self {
+            Git::Diff(diff) => diff.revision.as_deref(),
+            Git::Log(log) => log.revision.as_deref(),
+            _ => None,
+        };
+        if revision.is_some_and(|revision| revision.starts_with('-')) {
+            bail!("Revisions can't start with -, use the fields of the command for its options");
+        }
+        match self {
+            Git::Blame(blame)
+                if blame
+                    .start_line
+                    .zip(blame.end_line)
+                    .is_some_and(|(start, end)| start > end) =>
+            {
+                bail!("start_line must not be after end_line");
+            },
+            Git::Commit(commit) if commit.message.trim().is_empty() => bail!("The commit message is empty"),
+            _ => (),
+        }
+        if run(os, &READ_ONLY_CONFIG, &["rev-parse", "--is-inside-work-tree"])
+            .await
+            .is_err()
+        {
+            bail!("{} is not in a git repository", os.env.current_dir()?.display());
+        }
+        Ok(())
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        for invocation in self.invocations() {
+            // Commit messages are shown in full below rather than as an argument
+            let shown = match self {
+                Git::Commit(_) if invocation[0] == "commit" => "git commit".to_string(),
+                _ => format!("git {}", invocation.join(" ")),
+            };
+            queue!(
+                output,
+                style::Print("Running: "),
+                style::SetForegroundColor(theme().success),
+                style::Print(shown),
+                style::ResetColor,
+                style::Print("\n"),
+            )?;
+        }
+        if let Git::Commit(commit) = self {
+            queue!(
+                output,
+                style::Print("Message:\n"),
+                style::SetForegroundColor(theme().secondary),
+                style::Print(&commit.message),
+                style::ResetColor,
+                style::Print("\n"),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _output: impl Write) -> Result<InvokeOutput> {
+        let mut text = String::new();
+        let config: &[&str] = match self.is_read_only() {
+            true => &READ_ONLY_CONFIG,
+            false => &[],
+        };
+        for invocation in self.invocations() {
+            let args = invocation.iter().map(String::as_str).collect::<Vec<_>>();
+            text.push_str(&run(os, config, &args).await?);
+        }
+        if text.trim().is_empty() {
+            text = match self {
+                Git::Diff(_) => "No changes".to_string(),
+                Git::Log(_) => "No commits".to_string(),
+                _ => text,
+            };
+        }
+        truncate_safe_in_place(&mut text, MAX_TOOL_RESPONSE_SIZE, "\n... (truncated)");
+
+        Ok(InvokeOutput {
+            output: OutputKind::Text(text),
+            ..Default::default()
+        })
+    }
+
+    pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Settings {
+            #[serde(default)]
+            allowed_commands: Vec<String>,
+            #[serde(default)]
+            denied_commands: Vec<String>,
+        }
+
+        let is_in_allowlist = agent.allowed_tools.contains("git");
+        if let Some(settings) = agent.tools_settings.get("git") {
+            let settings = match serde_json::from_value::<Settings>(settings.clone()) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    error!("Failed to deserialize tool settings for git: {:?}", e);
+                    return PermissionEvalResult::Ask;
+                },
+            };
+            let command = self.command_name();
+            if settings.denied_commands.iter().any(|denied| denied == command) {
+                return PermissionEvalResult::Deny;
+            }
+            if settings.allowed_commands.iter().any(|allowed| allowed == command) {
+                return PermissionEvalResult::Allow;
+            }
+        }
+        match is_in_allowlist || self.is_read_only() {
+            true => PermissionEvalResult::Allow,
+            false => PermissionEvalResult::Ask,
+        }
+    }
+}
+
+/// Runs git in the current directory with the `-c` options of `config`, returning what it printed
+/// or failing with its error.
+async fn run(os: &Os, config: &[&str], args: &[&str]) -> Result<String> {
+    let output = tokio::process::Command::new("git")
+        .arg("--no-pager")
+        .args(config)
+        .args(args)
+        .current_dir(os.env.current_dir()?)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // `git commit` explains why there is nothing to commit on stdout
+        let message = match stderr.trim() {
+            "" => stdout.trim(),
+            stderr => stderr,
+        };
+        bail!("git {} failed: {message}", args[0]);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(args: serde_json::Value) -> Git {
+        serde_json::from_value(args).unwrap()
+    }
+
+    #[test]
+    fn test_invocations() {
+        assert_eq!(git(serde_json::json!({ "command": "status" })).invocations(), [[
+            "status", "--short", "--branch"
+        ]]);
+        assert_eq!(
+            git(serde_json::json!({ "command": "diff", "staged": true, "paths": ["src"] })).invocations(),
+            [["diff", "--no-ext-diff", "--no-textconv", "--cached", "--", "src"]]
+        );
+        assert_eq!(
+            git(serde_json::json!({ "command": "blame", "path": "README.md", "start_line": 3 })).invocations(),
+            [["blame", "--no-textconv", "--date=short", "-L3,", "--", "README.md"]]
+        );
+        assert_eq!(
+            git(serde_json::json!({ "command": "commit", "message": "Fix typo", "paths": ["README.md"] }))
+                .invocations(),
+            [vec!["add", "--", "README.md"], vec!["commit", "-m", "Fix typo"]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_rejects_options() {
+        let os = Os::new().await.unwrap();
+        let mut log = git(serde_json::json!({ "command": "log", "revision": "--output=/etc/passwd" }));
+        assert!(log.validate(&os).await.is_err());
+    }
+
+    #[test]
+    fn test_eval_perm() {
+        let status = git(serde_json::json!({ "command": "status" }));
+        let commit = git(serde_json::json!({ "command": "commit", "message": "Fix typo" }));
+
+        let agent = Agent::default();
+        assert_eq!(status.eval_perm(&agent), PermissionEvalResult::Allow);
+        assert_eq!(commit.eval_perm(&agent), PermissionEvalResult::Ask);
+
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "toolsSettings": { "git": { "allowedCommands": ["commit"], "deniedCommands": ["blame"] } },
+        }))
+        .unwrap();
+        assert_eq!(commit.eval_perm(&agent), PermissionEvalResult::Allow);
+        let blame = git(serde_json::json!({ "command": "blame", "path": "README.md" }));
+        assert_eq!(blame.eval_perm(&agent), PermissionEvalResult::Deny);
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::Arc;
+
+use globset::{
+    GlobBuilder,
+    GlobMatcher,
+};
+
+use crate::os::Os;
+
+/// The rules of a `.gitignore` file, which apply to the paths under the directory it is in.
+#[derive(Debug)]
+pub struct Gitignore {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    matcher: GlobMatcher,
+    /// Rules starting with `!` re-include what an earlier rule excluded
+    negated: bool,
+    /// Rules ending with `/` only match directories
+    dir_only: bool,
+}
+
+impl Gitignore {
+    /// Reads the `.gitignore` file in `dir`, if there is one.
+    pub async fn read(os: &Os, dir: &Path) -> Option<Self> {
+        let contents = os.fs.read_to_string(dir.join(".gitignore")).await.ok()?;
+        Some(Self::parse(dir, &contents))
+    }
+
+    fn parse(dir: &Path, contents: &str) -> Self {
+        let rules = contents.lines().filter_map(parse_rule).collect();
+        Self {
+            dir: dir.to_path_buf(),
+            rules,
+        }
+    }
+
+    /// Whether `path` is ignored (`Some(true)`) or re-included (`Some(false)`) by the rules, or
+    /// None if none of them match it.
+    fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.matcher.is_match(relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(pattern) => (true, pattern),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(pattern) => (true, pattern),
+        None => (false, pattern),
+    };
+    // Patterns with a separator are relative to the directory of the file, others match at any
+    // depth below it
+    let glob = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{pattern}"),
+    };
+    let matcher = GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .build()
+        .ok()?
+        .compile_matcher();
+    Some(Rule {
+        matcher,
+        negated,
+        dir_only,
+    })
+}
+
+/// Whether `path` is ignored by the `.gitignore` files that apply to it, ordered from the
+/// outermost directory. The rules of files closer to the path take precedence.
+pub fn is_ignored(gitignores: &[Arc<Gitignore>], path: &Path, is_dir: bool) -> bool {
+    gitignores
+        .iter()
+        .rev()
+        .find_map(|gitignore| gitignore.matches(path, is_dir))
+        .unwrap_or(false)
+}
+
+/// Tells whether files found anywhere in a repository are ignored, reading each `.gitignore` file
+/// once. Paths are taken as they are on disk, as glob expansion returns them, rather than through
+/// [Os::fs].
+#[derive(Debug, Default)]
+pub struct GitignoreCache {
+    dirs: HashMap<PathBuf, Option<Arc<Gitignore>>>,
+}
+
+impl GitignoreCache {
+    async fn get(&mut self, dir: &Path) -> Option<Arc<Gitignore>> {
+        if let Some(gitignore) = self.dirs.get(dir) {
+            return gitignore.clone();
+        }
+        let gitignore = tokio::fs::read_to_string(dir.join(".gitignore"))
+            .await
+            .ok()
+            .map(|contents| Arc::new(Gitignore::parse(dir, &contents)));
+        self.dirs.insert(dir.to_path_buf(), gitignore.clone());
+        gitignore
+    }
+
+    /// Whether the file at `path` is inside `.git`, or it or a directory it is in is ignored by
+    /// the `.gitignore` files up to the root of its repository. Files outside of a repository are
+    /// never ignored.
+    pub async fn is_ignored(&mut self, path: &Path) -> bool {
+        if path.components().any(|component| component.as_os_str() == ".git") {
+            return true;
+        }
+
+        let mut dirs = Vec::new();
+        let mut in_repository = false;
+        for dir in path.ancestors().skip(1) {
+            dirs.push(dir);
+            if tokio::fs::try_exists(dir.join(".git")).await.unwrap_or(false) {
+                in_repository = true;
+                break;
+            }
+        }
+        if !in_repository {
+            return false;
+        }
+
+        let mut gitignores = Vec::new();
+        for (i, dir) in dirs.iter().rev().enumerate() {
+            // Directories below the root are matched against the rules of the ones above them
+            if i > 0 && is_ignored(&gitignores, dir, true) {
+                return true;
+            }
+            gitignores.extend(self.get(dir).await);
+        }
+        is_ignored(&gitignores, path, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ignored() {
+        let root = Arc::new(Gitignore::parse(
+            Path::new("/repo"),
+            "# build output\ntarget/\n*.log\n!keep.log\n/dist\ndocs/*.html\n",
+        ));
+        let nested = Arc::new(Gitignore::parse(Path::new("/repo/web"), "!*.log\nnode_modules\n"));
+        let ignored = |path: &str, is_dir: bool| is_ignored(&[root.clone(), nested.clone()], Path::new(path), is_dir);
+
+        assert!(ignored("/repo/target", true));
+        assert!(ignored("/repo/crates/cli/target", true));
+        assert!(!ignored("/repo/target", false));
+        assert!(ignored("/repo/debug.log", false));
+        assert!(!ignored("/repo/keep.log", false));
+        assert!(ignored("/repo/dist", true));
+        assert!(!ignored("/repo/src/dist", true));
+        assert!(ignored("/repo/docs/index.html", false));
+        assert!(!ignored("/repo/docs/api/index.html", false));
+        assert!(!ignored("/repo/src/main.rs", false));
+
+        // Rules closer to the path take precedence
+        assert!(!ignored("/repo/web/server.log", false));
+        assert!(ignored("/repo/web/node_modules", true));
+        assert!(!ignored("/repo/node_modules", true));
+    }
+
+    #[tokio::test]
+    async fn test_gitignore_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(repo.join("target/debug")).unwrap();
+        std::fs::write(repo.join(".gitignore"), "target/\n*.log\n").unwrap();
+
+        let mut cache = GitignoreCache::default();
+        assert!(cache.is_ignored(&repo.join("target/debug/main.rs")).await);
+        assert!(cache.is_ignored(&repo.join("debug.log")).await);
+        assert!(cache.is_ignored(&repo.join(".git/HEAD")).await);
+        assert!(!cache.is_ignored(&repo.join("src/main.rs")).await);
+    }
+}
@@ -0,0 +1,392 @@
+pub mod gitignore;
+
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::Arc;
+
+use crossterm::{
+    queue,
+    style,
+};
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use gitignore::{
+    Gitignore,
+    is_ignored,
+};
+use globset::{
+    GlobBuilder,
+    GlobMatcher,
+};
+use regex::{
+    Regex,
+    RegexBuilder,
+};
+use serde::Deserialize;
+
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+    format_path,
+    sanitize_path_tool_arg,
+};
+use crate::cli::agent::content_filter::ContentFilter;
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+use crate::util::theme::theme;
+
+const DEFAULT_MAX_RESULTS: usize = 100;
+const MAX_RESULTS: usize = 500;
+const MAX_CONTEXT_LINES: usize = 5;
+/// Matching lines are cut off after this many bytes, so that minified files don't flood the output
+const MAX_LINE_LENGTH: usize = 250;
+/// Files larger than this are skipped, they are rarely source code
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrepSearch {
+    /// Regular expression to search for
+    pub pattern: String,
+    /// File or directory to search, the current directory by default
+    pub path: Option<String>,
+    /// Glob the paths of the files searched have to match, e.g. `*.rs` or `src/**/*.ts`
+    pub include: Option<String>,
+    #[serde(default)]
+    pub case_insensitive: bool,
+    /// Lines of context shown around each match
+    #[serde(default)]
+    pub context_lines: usize,
+    pub max_results: Option<usize>,
+    /// Set by the session, files it blocks aren't searched
+    #[serde(skip)]
+    content_filter: ContentFilter,
+}
+
+/// A file with matches, along with the lines shown for it.
+struct FileMatches {
+    path: PathBuf,
+    /// Line numbers, whether they match, and their contents
+    lines: Vec<(usize, bool, String)>,
+}
+
+impl GrepSearch {
+    /// Sets the content filter whose blocked files are skipped.
+    pub fn set_content_filter(&mut self, content_filter: ContentFilter) {
+        self.content_filter = content_filter;
+    }
+
+    fn regex(&self) -> Result<Regex> {
+        RegexBuilder::new(&self.pattern)
+            .case_insensitive(self.case_insensitive)
+            .build()
+            .wrap_err_with(|| format!("{} is not a valid regular expression", self.pattern))
+    }
+
+    fn include(&self) -> Result<Option<GlobMatcher>> {
+        let Some(include) = &self.include else {
+            return Ok(None);
+        };
+        // Globs without a separator match the file name at any depth
+        let glob = match include.contains('/') {
+            true => include.clone(),
+            false => format!("**/{include}"),
+        };
+        let matcher = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .build()
+            .wrap_err_with(|| format!("{include} is not a valid glob"))?
+            .compile_matcher();
+        Ok(Some(matcher))
+    }
+
+    /// The current directory, within the chroot of the tests.
+    fn cwd(os: &Os) -> Result<PathBuf> {
+        Ok(os.fs.chroot_path(os.env.current_dir()?))
+    }
+
+    fn root(&self, os: &Os) -> Result<PathBuf> {
+        let cwd = Self::cwd(os)?;
+        Ok(match &self.path {
+            Some(path) => cwd.join(sanitize_path_tool_arg(os, path)),
+            None => cwd,
+        })
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        self.regex()?;
+        self.include()?;
+        let root = self.root(os)?;
+        if !os.fs.exists(&root) {
+            bail!("{} does not exist", root.display());
+        }
+        Ok(())
+    }
+
+    pub fn queue_description(&self, os: &Os, output: &mut impl Write) -> Result<()> {
+        let cwd = Self::cwd(os)?;
+        let root = self.root(os)?;
+        queue!(
+            output,
+            style::Print("Searching for: "),
+            style::SetForegroundColor(theme().success),
+            style::Print(&self.pattern),
+            style::ResetColor,
+            style::Print(" in "),
+            style::SetForegroundColor(theme().success),
+            style::Print(match format_path(&cwd, &root) {
+                path if path.is_empty() => ".".to_string(),
+                path => path,
+            }),
+            style::ResetColor,
+        )?;
+        if let Some(include) = &self.include {
+            queue!(
+                output,
+                style::Print(" ("),
+                style::SetForegroundColor(theme().success),
+                style::Print(include),
+                style::ResetColor,
+                style::Print(")"),
+            )?;
+        }
+        queue!(output, style::Print("\n"))?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, os: &Os, _output: impl Write) -> Result<InvokeOutput> {
+        let regex = self.regex()?;
+        let include = self.include()?;
+        let root = self.root(os)?;
+        let max_results = self.max_results.unwrap_or(DEFAULT_MAX_RESULTS).clamp(1, MAX_RESULTS);
+        let context_lines = self.context_lines.min(MAX_CONTEXT_LINES);
+
+        let mut files = Vec::new();
+        let mut matches = 0;
+        let mut truncated = false;
+        for path in self.files(os, &root).await? {
+            let relative = path.strip_prefix(&root).unwrap_or(&path);
+            if include.as_ref().is_some_and(|include| !include.is_match(relative)) {
+                continue;
+            }
+            let Some(found) = search_file(os, &path, &regex, context_lines, max_results - matches).await else {
+                continue;
+            };
+            matches += found.lines.iter().filter(|(_, is_match, _)| *is_match).count();
+            files.push(found);
+            if matches >= max_results {
+                truncated = true;
+                break;
+            }
+        }
+
+        let cwd = Self::cwd(os)?;
+        let mut text = String::new();
+        for file in &files {
+            text.push_str(&format_path(&cwd, &file.path));
+            text.push('\n');
+            let mut previous = None;
+            for (number, is_match, line) in &file.lines {
+                if previous.is_some_and(|previous| previous + 1 < *number) {
+                    text.push_str("--\n");
+                }
+                previous = Some(*number);
+                text.push_str(&format!("{number}{}{line}\n", if *is_match { ':' } else { '-' }));
+            }
+            text.push('\n');
+        }
+        text.push_str(&match (matches, truncated) {
+            (0, _) => "No matches found".to_string(),
+            (_, false) => format!("Found {matches} match(es) in {} file(s)", files.len()),
+            (_, true) => format!(
+                "Stopped after {matches} matches in {} file(s), narrow down the search or raise max_results for more",
+                files.len()
+            ),
+        });
+        truncate_safe_in_place(
+            &mut text,
+            MAX_TOOL_RESPONSE_SIZE,
+            "\n... (truncated, narrow down the search for more)",
+        );
+
+        Ok(InvokeOutput {
+            output: OutputKind::Text(text),
+            ..Default::default()
+        })
+    }
+
+    /// The files under `root` in a stable order, leaving out the ones ignored by `.gitignore`
+    /// files, blocked by the content filter or inside `.git`.
+    async fn files(&self, os: &Os, root: &Path) -> Result<Vec<PathBuf>> {
+        let metadata = os.fs.symlink_metadata(root).await?;
+        if metadata.is_file() {
+            let blocked = self.content_filter.blocked_reason(root, Some(metadata.len())).is_some();
+            return Ok(if blocked { vec![] } else { vec![root.to_path_buf()] });
+        }
+
+        // The .gitignore files above the root apply as well, up to the root of the repository
+        let mut inherited = Vec::new();
+        let mut dir = root;
+        while let Some(parent) = dir.parent().filter(|_| !os.fs.exists(dir.join(".git"))) {
+            inherited.extend(Gitignore::read(os, parent).await.map(Arc::new));
+            dir = parent;
+        }
+        inherited.reverse();
+
+        let mut files = Vec::new();
+        let mut dirs = vec![(root.to_path_buf(), inherited)];
+        while let Some((dir, mut gitignores)) = dirs.pop() {
+            gitignores.extend(Gitignore::read(os, &dir).await.map(Arc::new));
+            let mut entries = Vec::new();
+            let mut read_dir = os.fs.read_dir(&dir).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                let metadata = entry.metadata().await?;
+                if metadata.is_symlink() || entry.file_name() == ".git" {
+                    continue;
+                }
+                entries.push((dir.join(entry.file_name()), metadata.is_dir(), metadata.len()));
+            }
+            entries.sort();
+
+            let mut subdirs = Vec::new();
+            for (path, is_dir, size) in entries {
+                if is_ignored(&gitignores, &path, is_dir) {
+                    continue;
+                }
+                if is_dir {
+                    subdirs.push((path, gitignores.clone()));
+                } else if size <= MAX_FILE_SIZE && self.content_filter.blocked_reason(&path, Some(size)).is_none() {
+                    files.push(path);
+                }
+            }
+            // Popped in order, after the files of this directory
+            dirs.extend(subdirs.into_iter().rev());
+        }
+        Ok(files)
+    }
+
+    pub fn eval_perm(agent: &Agent) -> PermissionEvalResult {
+        match agent.allowed_tools.contains("grep_search") {
+            true => PermissionEvalResult::Allow,
+            false => PermissionEvalResult::Ask,
+        }
+    }
+}
+
+/// Searches `path` for `regex`, stopping after `max_matches` matches, or returns None if it has no
+/// matches or isn't a text file.
+async fn search_file(
+    os: &Os,
+    path: &Path,
+    regex: &Regex,
+    context_lines: usize,
+    max_matches: usize,
+) -> Option<FileMatches> {
+    let bytes = os.fs.read(path).await.ok()?;
+    if bytes.iter().take(8192).any(|byte| *byte == 0) {
+        return None;
+    }
+    let contents = String::from_utf8_lossy(&bytes);
+    let lines = contents.lines().collect::<Vec<_>>();
+
+    let mut shown = Vec::new();
+    let mut matches = 0;
+    for (index, line) in lines.iter().enumerate() {
+        if !regex.is_match(line) {
+            continue;
+        }
+        let start = index.saturating_sub(context_lines);
+        let end = (index + context_lines).min(lines.len() - 1);
+        for (i, shown_line) in lines.iter().enumerate().take(end + 1).skip(start) {
+            if shown.last().is_some_and(|(last, _, _)| *last > i) {
+                continue;
+            }
+            let mut text = (*shown_line).to_string();
+            truncate_safe_in_place(&mut text, MAX_LINE_LENGTH, " ...");
+            // Lines shown as context after a match can be matches themselves
+            shown.push((i + 1, i == index || regex.is_match(shown_line), text));
+        }
+        matches += 1;
+        if matches >= max_matches {
+            break;
+        }
+    }
+
+    (matches > 0).then(|| FileMatches {
+        path: path.to_path_buf(),
+        lines: shown,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn search(os: &Os, args: serde_json::Value) -> String {
+        let mut grep_search = serde_json::from_value::<GrepSearch>(args).unwrap();
+        grep_search.validate(os).await.unwrap();
+        let output = grep_search.invoke(os, std::io::sink()).await.unwrap();
+        output.as_str().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_grep_search() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/repo/.git").await.unwrap();
+        os.fs.create_dir_all("/repo/src").await.unwrap();
+        os.fs.create_dir_all("/repo/target").await.unwrap();
+        os.fs.write("/repo/.gitignore", "target/\n").await.unwrap();
+        os.fs
+            .write("/repo/src/main.rs", "fn main() {\n    run();\n}\n\n\nfn run() {}\n")
+            .await
+            .unwrap();
+        os.fs.write("/repo/src/lib.rs", "pub fn Run() {}\n").await.unwrap();
+        os.fs.write("/repo/target/main.rs", "fn run() {}\n").await.unwrap();
+        os.fs.write("/repo/.git/HEAD", "fn run()").await.unwrap();
+        os.fs.write("/repo/logo.png", b"\x89PNG\0fn run()").await.unwrap();
+
+        let output = search(&os, serde_json::json!({ "pattern": "fn run", "path": "/repo" })).await;
+        assert_eq!(
+            output,
+            "repo/src/main.rs\n6:fn run() {}\n\nFound 1 match(es) in 1 file(s)"
+        );
+
+        let output = search(
+            &os,
+            serde_json::json!({ "pattern": "run\\(", "path": "/repo", "case_insensitive": true, "context_lines": 1, "include": "*.rs" }),
+        )
+        .await;
+        assert_eq!(
+            output,
+            "repo/src/lib.rs\n1:pub fn Run() {}\n\nrepo/src/main.rs\n1-fn main() {\n2:    run();\n3-}\n--\n5-\n6:fn run() {}\n\nFound 3 match(es) in 2 file(s)"
+        );
+
+        let output = search(
+            &os,
+            serde_json::json!({ "pattern": "fn", "path": "/repo", "max_results": 1 }),
+        )
+        .await;
+        assert!(
+            output.ends_with(
+                "Stopped after 1 matches in 1 file(s), narrow down the search or raise max_results for more"
+            )
+        );
+
+        let output = search(
+            &os,
+            serde_json::json!({ "pattern": "nothing", "path": "/repo/src/main.rs" }),
+        )
+        .await;
+        assert_eq!(output, "No matches found");
+    }
+}
@@ -3,7 +3,6 @@ use std::io::Write;
 use crossterm::queue;
 use crossterm::style::{
     self,
-    Color,
 };
 use eyre::Result;
 use serde::Deserialize;
@@ -16,6 +15,7 @@ use super::{
 use crate::database::settings::Setting;
 use crate::os::Os;
 use crate::util::knowledge_store::KnowledgeStore;
+use crate::util::theme::theme;
 
 /// The Knowledge tool allows storing and retrieving information across chat sessions.
 /// It provides semantic search capabilities for files, directories, and text content.
@@ -152,7 +152,7 @@ impl Knowledge {
                 queue!(
                     updates,
                     style::Print("Adding to knowledge base: "),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(&add.name),
                     style::ResetColor,
                 )?;
@@ -164,7 +164,7 @@ impl Knowledge {
                     queue!(
                         updates,
                         style::Print(format!(" ({}: ", path_type)),
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::Print(&add.value),
                         style::ResetColor,
                         style::Print(")\n")
@@ -175,7 +175,7 @@ impl Knowledge {
                         queue!(
                             updates,
                             style::Print(" (text: "),
-                            style::SetForegroundColor(Color::Blue),
+                            style::SetForegroundColor(theme().label),
                             style::Print(format!("{}...", preview)),
                             style::ResetColor,
                             style::Print(")\n")
@@ -184,7 +184,7 @@ impl Knowledge {
                         queue!(
                             updates,
                             style::Print(" (text: "),
-                            style::SetForegroundColor(Color::Blue),
+                            style::SetForegroundColor(theme().label),
                             style::Print(&add.value),
                             style::ResetColor,
                             style::Print(")\n")
@@ -197,7 +197,7 @@ impl Knowledge {
                     queue!(
                         updates,
                         style::Print("Removing from knowledge base by name: "),
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::Print(&remove.name),
                         style::ResetColor,
                     )?;
@@ -205,7 +205,7 @@ impl Knowledge {
                     queue!(
                         updates,
                         style::Print("Removing from knowledge base by ID: "),
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::Print(&remove.context_id),
                         style::ResetColor,
                     )?;
@@ -213,7 +213,7 @@ impl Knowledge {
                     queue!(
                         updates,
                         style::Print("Removing from knowledge base by path: "),
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::Print(&remove.path),
                         style::ResetColor,
                     )?;
@@ -221,7 +221,7 @@ impl Knowledge {
                     queue!(
                         updates,
                         style::Print("Removing from knowledge base: "),
-                        style::SetForegroundColor(Color::Yellow),
+                        style::SetForegroundColor(theme().warning),
                         style::Print("No identifier provided"),
                         style::ResetColor,
                     )?;
@@ -234,7 +234,7 @@ impl Knowledge {
                     queue!(
                         updates,
                         style::Print(" with ID: "),
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::Print(&update.context_id),
                         style::ResetColor,
                     )?;
@@ -242,7 +242,7 @@ impl Knowledge {
                     queue!(
                         updates,
                         style::Print(" with name: "),
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::Print(&update.name),
                         style::ResetColor,
                     )?;
@@ -253,7 +253,7 @@ impl Knowledge {
                 queue!(
                     updates,
                     style::Print(format!(" using new {}: ", path_type)),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(&update.path),
                     style::ResetColor,
                 )?;
@@ -262,7 +262,7 @@ impl Knowledge {
                 queue!(
                     updates,
                     style::Print("Clearing "),
-                    style::SetForegroundColor(Color::Yellow),
+                    style::SetForegroundColor(theme().warning),
                     style::Print("all"),
                     style::ResetColor,
                     style::Print(" knowledge base entries"),
@@ -272,7 +272,7 @@ impl Knowledge {
                 queue!(
                     updates,
                     style::Print("Searching knowledge base for: "),
-                    style::SetForegroundColor(Color::Green),
+                    style::SetForegroundColor(theme().success),
                     style::Print(&search.query),
                     style::ResetColor,
                 )?;
@@ -281,7 +281,7 @@ impl Knowledge {
                     queue!(
                         updates,
                         style::Print(" in context: "),
-                        style::SetForegroundColor(Color::Green),
+                        style::SetForegroundColor(theme().success),
                         style::Print(context_id),
                         style::ResetColor,
                     )?;
@@ -360,6 +360,7 @@ impl Knowledge {
                         output: OutputKind::Text(
                             "Error: No path provided for update. Please specify a path to update with.".to_string(),
                         ),
+                        ..Default::default()
                     });
                 }
 
@@ -368,6 +369,7 @@ impl Knowledge {
                 if !path.exists() {
                     return Ok(InvokeOutput {
                         output: OutputKind::Text(format!("Error: Path '{}' does not exist", update.path)),
+                        ..Default::default()
                     });
                 }
 
@@ -469,6 +471,7 @@ impl Knowledge {
 
         Ok(InvokeOutput {
             output: OutputKind::Text(result),
+            ..Default::default()
         })
     }
 
@@ -0,0 +1,325 @@
+use std::io::Write;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+    Color,
+    Stylize,
+};
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    InvokeOutput,
+    OutputKind,
+};
+use crate::util::theme::theme;
+
+/// Status of a single task in a [TodoList].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    #[default]
+    Pending,
+    InProgress,
+    Done,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub task: String,
+    #[serde(default)]
+    pub status: TodoStatus,
+}
+
+/// The tasks the model is working through, kept as part of the conversation state so that long
+/// multi-step work stays on track. Updated by the model with the `manage_todo` tool and by the
+/// user with `/todo`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoList {
+    pub items: Vec<TodoItem>,
+}
+
+impl TodoList {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn add(&mut self, tasks: impl IntoIterator<Item = String>) {
+        self.items.extend(tasks.into_iter().map(|task| TodoItem {
+            task,
+            status: TodoStatus::Pending,
+        }));
+    }
+
+    /// Sets the status of the task at the 1-based `index`.
+    pub fn set_status(&mut self, index: usize, status: TodoStatus) -> Result<()> {
+        let item = self.item_mut(index)?;
+        item.status = status;
+        Ok(())
+    }
+
+    /// Removes the task at the 1-based `index`.
+    pub fn remove(&mut self, index: usize) -> Result<TodoItem> {
+        self.item_mut(index)?;
+        Ok(self.items.remove(index - 1))
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Returns the number of tasks that are done and the total number of tasks.
+    pub fn progress(&self) -> (usize, usize) {
+        let done = self.items.iter().filter(|i| i.status == TodoStatus::Done).count();
+        (done, self.items.len())
+    }
+
+    /// The task currently in progress, or the first pending one.
+    pub fn current(&self) -> Option<&TodoItem> {
+        self.items
+            .iter()
+            .find(|i| i.status == TodoStatus::InProgress)
+            .or_else(|| self.items.iter().find(|i| i.status == TodoStatus::Pending))
+    }
+
+    fn item_mut(&mut self, index: usize) -> Result<&mut TodoItem> {
+        let len = self.items.len();
+        index
+            .checked_sub(1)
+            .and_then(|i| self.items.get_mut(i))
+            .ok_or_else(|| eyre!("no task at index {index}, the list has {len} tasks"))
+    }
+
+    /// Renders the list as a numbered checklist.
+    pub fn queue_checklist(&self, output: &mut impl Write) -> Result<()> {
+        for (i, item) in self.items.iter().enumerate() {
+            let (mark, task) = match item.status {
+                TodoStatus::Pending => ("[ ]".dark_grey(), item.task.clone().reset()),
+                TodoStatus::InProgress => ("[~]".yellow(), item.task.clone().bold()),
+                TodoStatus::Done => ("[x]".green(), item.task.clone().dark_grey()),
+            };
+            queue!(
+                output,
+                style::Print(format!("{:>3}. ", i + 1)),
+                style::PrintStyledContent(mark),
+                style::Print(" "),
+                style::PrintStyledContent(task),
+                style::Print("\n"),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The list as plain text, as returned to the model.
+    fn to_text(&self) -> String {
+        if self.items.is_empty() {
+            return "The task list is empty.".to_string();
+        }
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let mark = match item.status {
+                    TodoStatus::Pending => " ",
+                    TodoStatus::InProgress => "~",
+                    TodoStatus::Done => "x",
+                };
+                format!("{}. [{mark}] {}", i + 1, item.task)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Lets the model keep a task list for the current conversation. The tasks are stored in the
+/// conversation state, which is provided through [Self::set_todo_list] before the tool is invoked.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ManageTodo {
+    /// Replaces the list with new pending tasks
+    Create {
+        tasks: Vec<String>,
+        #[serde(skip_deserializing)]
+        todo_list: Option<TodoList>,
+    },
+    /// Appends pending tasks to the list
+    Add {
+        tasks: Vec<String>,
+        #[serde(skip_deserializing)]
+        todo_list: Option<TodoList>,
+    },
+    /// Updates the status of a task
+    Update {
+        index: usize,
+        status: TodoStatus,
+        #[serde(skip_deserializing)]
+        todo_list: Option<TodoList>,
+    },
+    /// Removes a task
+    Remove {
+        index: usize,
+        #[serde(skip_deserializing)]
+        todo_list: Option<TodoList>,
+    },
+    /// Returns the current list
+    View {
+        #[serde(skip_deserializing)]
+        todo_list: Option<TodoList>,
+    },
+}
+
+impl ManageTodo {
+    /// Sets the current list of the conversation, required before calling [Self::invoke].
+    pub fn set_todo_list(&mut self, list: TodoList) {
+        match self {
+            ManageTodo::Create { todo_list, .. }
+            | ManageTodo::Add { todo_list, .. }
+            | ManageTodo::Update { todo_list, .. }
+            | ManageTodo::Remove { todo_list, .. }
+            | ManageTodo::View { todo_list } => *todo_list = Some(list),
+        }
+    }
+
+    fn todo_list(&self) -> Option<&TodoList> {
+        match self {
+            ManageTodo::Create { todo_list, .. }
+            | ManageTodo::Add { todo_list, .. }
+            | ManageTodo::Update { todo_list, .. }
+            | ManageTodo::Remove { todo_list, .. }
+            | ManageTodo::View { todo_list } => todo_list.as_ref(),
+        }
+    }
+
+    /// Applies the command to `list`.
+    pub fn apply(&self, list: &mut TodoList) -> Result<()> {
+        match self {
+            ManageTodo::Create { tasks, .. } => {
+                list.clear();
+                list.add(tasks.iter().cloned());
+            },
+            ManageTodo::Add { tasks, .. } => list.add(tasks.iter().cloned()),
+            ManageTodo::Update { index, status, .. } => list.set_status(*index, *status)?,
+            ManageTodo::Remove { index, .. } => {
+                list.remove(*index)?;
+            },
+            ManageTodo::View { .. } => (),
+        }
+        Ok(())
+    }
+
+    /// Returns the list after applying the command. The conversation state itself is updated by
+    /// the caller with [Self::apply] once the invocation succeeds.
+    pub async fn invoke(&self, output: &mut impl Write) -> Result<InvokeOutput> {
+        let Some(list) = self.todo_list() else {
+            bail!("manage_todo: Required tool context (TodoList) not set by the program.");
+        };
+        let mut list = list.clone();
+        self.apply(&mut list)?;
+
+        if !matches!(self, ManageTodo::View { .. }) {
+            list.queue_checklist(output)?;
+        }
+
+        Ok(InvokeOutput {
+            output: OutputKind::Text(list.to_text()),
+            ..Default::default()
+        })
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        let description = match self {
+            ManageTodo::Create { tasks, .. } => format!("Creating a task list with {} tasks", tasks.len()),
+            ManageTodo::Add { tasks, .. } => format!("Adding {} tasks to the task list", tasks.len()),
+            ManageTodo::Update { index, status, .. } => {
+                let status = match status {
+                    TodoStatus::Pending => "pending",
+                    TodoStatus::InProgress => "in progress",
+                    TodoStatus::Done => "done",
+                };
+                format!("Marking task {index} as {status}")
+            },
+            ManageTodo::Remove { index, .. } => format!("Removing task {index}"),
+            ManageTodo::View { .. } => "Viewing the task list".to_string(),
+        };
+        queue!(
+            output,
+            style::SetForegroundColor(theme().secondary),
+            style::Print(description),
+            style::SetForegroundColor(Color::Reset),
+            style::Print("\n"),
+        )?;
+        Ok(())
+    }
+
+    pub async fn validate(&mut self, _os: &crate::os::Os) -> Result<()> {
+        match self {
+            ManageTodo::Create { tasks, .. } | ManageTodo::Add { tasks, .. }
+                if tasks.iter().any(|task| task.trim().is_empty()) =>
+            {
+                bail!("Tasks must not be empty");
+            },
+            ManageTodo::Update { index, .. } | ManageTodo::Remove { index, .. } if *index == 0 => {
+                bail!("Task indices start at 1");
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(list: &TodoList) -> Vec<(&str, TodoStatus)> {
+        list.items.iter().map(|i| (i.task.as_str(), i.status)).collect()
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut list = TodoList::default();
+        let commands = [
+            serde_json::json!({ "command": "create", "tasks": ["write parser", "add tests"] }),
+            serde_json::json!({ "command": "add", "tasks": ["update docs"] }),
+            serde_json::json!({ "command": "update", "index": 1, "status": "done" }),
+            serde_json::json!({ "command": "update", "index": 2, "status": "in_progress" }),
+            serde_json::json!({ "command": "remove", "index": 3 }),
+        ];
+        for command in commands {
+            let command = serde_json::from_value::<ManageTodo>(command).unwrap();
+            command.apply(&mut list).unwrap();
+        }
+
+        assert_eq!(tasks(&list), vec![
+            ("write parser", TodoStatus::Done),
+            ("add tests", TodoStatus::InProgress)
+        ]);
+        assert_eq!(list.progress(), (1, 2));
+        assert_eq!(list.current().unwrap().task, "add tests");
+        assert_eq!(list.to_text(), "1. [x] write parser\n2. [~] add tests");
+
+        let command = serde_json::from_value::<ManageTodo>(serde_json::json!({ "command": "remove", "index": 5 }));
+        assert!(command.unwrap().apply(&mut list).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invoke() {
+        let mut command =
+            serde_json::from_value::<ManageTodo>(serde_json::json!({ "command": "add", "tasks": ["b"] })).unwrap();
+        assert!(command.invoke(&mut vec![]).await.is_err());
+
+        let mut list = TodoList::default();
+        list.add(["a".to_string()]);
+        command.set_todo_list(list);
+        let output = command.invoke(&mut vec![]).await.unwrap();
+        assert_eq!(output.as_str(), "1. [ ] a\n2. [ ] b");
+    }
+}
@@ -1,18 +1,28 @@
 pub mod custom_tool;
 pub mod execute;
+pub mod formatter;
 pub mod fs_read;
 pub mod fs_write;
 pub mod gh_issue;
+pub mod git;
+pub mod grep_search;
 pub mod knowledge;
+pub mod manage_todo;
 pub mod thinking;
 pub mod use_aws;
+pub mod wasm_tool;
+pub mod web_fetch;
 
 use std::borrow::Borrow;
+use std::future::Future;
 use std::io::Write;
+use std::ops::Range;
 use std::path::{
+    Component,
     Path,
     PathBuf,
 };
+use std::time::Duration;
 
 use crossterm::queue;
 use crossterm::style::{
@@ -21,28 +31,47 @@ use crossterm::style::{
 };
 use custom_tool::CustomTool;
 use execute::ExecuteCommand;
-use eyre::Result;
+use eyre::{
+    Result,
+    bail,
+};
 use fs_read::FsRead;
 use fs_write::FsWrite;
 use gh_issue::GhIssue;
+use git::Git;
+use globset::GlobSet;
+use grep_search::GrepSearch;
 use knowledge::Knowledge;
+use manage_todo::ManageTodo;
 use serde::{
     Deserialize,
     Serialize,
 };
 use thinking::Thinking;
 use use_aws::UseAws;
+#[cfg(feature = "wasm-plugins")]
+use wasm_tool::WasmTool;
+use web_fetch::WebFetch;
 
+use super::artifacts::ArtifactKind;
 use super::consts::MAX_TOOL_RESPONSE_SIZE;
+use super::path_guard::GuardedPath;
 use super::util::images::RichImageBlocks;
+use crate::cli::agent::content_filter::ContentFilter;
 use crate::cli::agent::{
     Agent,
     PermissionEvalResult,
 };
 use crate::os::Os;
-
-pub const DEFAULT_APPROVE: [&str; 1] = ["fs_read"];
-pub const NATIVE_TOOLS: [&str; 7] = [
+use crate::util::MCP_SERVER_TOOL_DELIMITER;
+use crate::util::datetime::format_duration;
+use crate::util::process::ResourceUsage;
+use crate::util::theme::theme;
+
+pub const DEFAULT_APPROVE: [&str; 2] = ["fs_read", "grep_search"];
+/// How many independent tool uses run at the same time, unless `chat.toolParallelism` is set.
+pub const DEFAULT_TOOL_PARALLELISM: usize = 4;
+pub const NATIVE_TOOLS: [&str; 11] = [
     "fs_read",
     "fs_write",
     #[cfg(windows)]
@@ -53,6 +82,10 @@ pub const NATIVE_TOOLS: [&str; 7] = [
     "gh_issue",
     "knowledge",
     "thinking",
+    "manage_todo",
+    "web_fetch",
+    "grep_search",
+    "git",
 ];
 
 /// Represents an executable tool use.
@@ -67,6 +100,12 @@ pub enum Tool {
     GhIssue(GhIssue),
     Knowledge(Knowledge),
     Thinking(Thinking),
+    #[cfg(feature = "wasm-plugins")]
+    Wasm(WasmTool),
+    ManageTodo(ManageTodo),
+    WebFetch(WebFetch),
+    GrepSearch(GrepSearch),
+    Git(Git),
 }
 
 impl Tool {
@@ -84,35 +123,93 @@ impl Tool {
             Tool::GhIssue(_) => "gh_issue",
             Tool::Knowledge(_) => "knowledge",
             Tool::Thinking(_) => "thinking (prerelease)",
+            #[cfg(feature = "wasm-plugins")]
+            Tool::Wasm(wasm_tool) => &wasm_tool.name,
+            Tool::ManageTodo(_) => "manage_todo",
+            Tool::WebFetch(_) => "web_fetch",
+            Tool::GrepSearch(_) => "grep_search",
+            Tool::Git(_) => "git",
         }
         .to_owned()
     }
 
+    /// The keys the tool is configured under in the agent, most specific first. MCP tools can also
+    /// be configured for their whole server.
+    pub fn settings_keys(&self) -> Vec<String> {
+        match self {
+            Tool::Custom(custom_tool) => {
+                let server_name = custom_tool.client.get_server_name();
+                vec![
+                    format!("@{server_name}{MCP_SERVER_TOOL_DELIMITER}{}", custom_tool.name),
+                    format!("@{server_name}"),
+                ]
+            },
+            tool => vec![tool.display_name()],
+        }
+    }
+
+    /// How long the tool can run before it is cancelled, from `timeoutMs` in its `toolsSettings`.
+    pub fn timeout(&self, agent: &Agent) -> Option<Duration> {
+        self.settings_keys()
+            .iter()
+            .filter_map(|key| agent.tools_settings.get(key.as_str()))
+            .find_map(|settings| settings.get("timeoutMs")?.as_u64())
+            .map(Duration::from_millis)
+    }
+
     /// Whether or not the tool should prompt the user to accept before [Self::invoke] is called.
-    pub fn requires_acceptance(&self, agent: &Agent) -> PermissionEvalResult {
+    pub fn requires_acceptance(&self, os: &Os, agent: &Agent) -> PermissionEvalResult {
         match self {
-            Tool::FsRead(fs_read) => fs_read.eval_perm(agent),
-            Tool::FsWrite(fs_write) => fs_write.eval_perm(agent),
+            Tool::FsRead(fs_read) => fs_read.eval_perm(os, agent),
+            Tool::FsWrite(fs_write) => fs_write.eval_perm(os, agent),
             Tool::ExecuteCommand(execute_command) => execute_command.eval_perm(agent),
             Tool::UseAws(use_aws) => use_aws.eval_perm(agent),
             Tool::Custom(custom_tool) => custom_tool.eval_perm(agent),
             Tool::GhIssue(_) => PermissionEvalResult::Allow,
             Tool::Thinking(_) => PermissionEvalResult::Allow,
             Tool::Knowledge(_) => PermissionEvalResult::Ask,
+            #[cfg(feature = "wasm-plugins")]
+            Tool::Wasm(wasm_tool) => wasm_tool.eval_perm(agent),
+            Tool::ManageTodo(_) => PermissionEvalResult::Allow,
+            Tool::WebFetch(_) => WebFetch::eval_perm(agent),
+            Tool::GrepSearch(_) => GrepSearch::eval_perm(agent),
+            Tool::Git(git) => git.eval_perm(agent),
+        }
+    }
+
+    /// Whether the tool only reads, so that it can run at the same time as the other tool uses of
+    /// a response that only read.
+    pub fn is_independent(&self) -> bool {
+        match self {
+            Tool::FsRead(_) | Tool::GrepSearch(_) | Tool::WebFetch(_) | Tool::Thinking(_) => true,
+            Tool::Git(git) => git.is_read_only(),
+            Tool::UseAws(use_aws) => !use_aws.requires_acceptance(),
+            _ => false,
         }
     }
 
     /// Invokes the tool asynchronously
     pub async fn invoke(&self, os: &Os, stdout: &mut impl Write) -> Result<InvokeOutput> {
+        #[cfg(feature = "fault-injection")]
+        crate::util::fault_injection::injector()
+            .tool(&self.display_name())
+            .await?;
+
         match self {
             Tool::FsRead(fs_read) => fs_read.invoke(os, stdout).await,
             Tool::FsWrite(fs_write) => fs_write.invoke(os, stdout).await,
-            Tool::ExecuteCommand(execute_command) => execute_command.invoke(stdout).await,
+            Tool::ExecuteCommand(execute_command) => execute_command.invoke(os, stdout).await,
             Tool::UseAws(use_aws) => use_aws.invoke(os, stdout).await,
             Tool::Custom(custom_tool) => custom_tool.invoke(os, stdout).await,
             Tool::GhIssue(gh_issue) => gh_issue.invoke(os, stdout).await,
             Tool::Knowledge(knowledge) => knowledge.invoke(os, stdout).await,
             Tool::Thinking(think) => think.invoke(stdout).await,
+            #[cfg(feature = "wasm-plugins")]
+            Tool::Wasm(wasm_tool) => wasm_tool.invoke(os, stdout).await,
+            Tool::ManageTodo(manage_todo) => manage_todo.invoke(stdout).await,
+            Tool::WebFetch(web_fetch) => web_fetch.invoke(os, stdout).await,
+            Tool::GrepSearch(grep_search) => grep_search.invoke(os, stdout).await,
+            Tool::Git(git) => git.invoke(os, stdout).await,
         }
     }
 
@@ -127,6 +224,12 @@ impl Tool {
             Tool::GhIssue(gh_issue) => gh_issue.queue_description(output),
             Tool::Knowledge(knowledge) => knowledge.queue_description(os, output).await,
             Tool::Thinking(thinking) => thinking.queue_description(output),
+            #[cfg(feature = "wasm-plugins")]
+            Tool::Wasm(wasm_tool) => wasm_tool.queue_description(output),
+            Tool::ManageTodo(manage_todo) => manage_todo.queue_description(output),
+            Tool::WebFetch(web_fetch) => web_fetch.queue_description(output),
+            Tool::GrepSearch(grep_search) => grep_search.queue_description(os, output),
+            Tool::Git(git) => git.queue_description(output),
         }
     }
 
@@ -141,7 +244,49 @@ impl Tool {
             Tool::GhIssue(gh_issue) => gh_issue.validate(os).await,
             Tool::Knowledge(knowledge) => knowledge.validate(os).await,
             Tool::Thinking(think) => think.validate(os).await,
+            #[cfg(feature = "wasm-plugins")]
+            Tool::Wasm(wasm_tool) => wasm_tool.validate(os).await,
+            Tool::ManageTodo(manage_todo) => manage_todo.validate(os).await,
+            Tool::WebFetch(web_fetch) => web_fetch.validate(os).await,
+            Tool::GrepSearch(grep_search) => grep_search.validate(os).await,
+            Tool::Git(git) => git.validate(os).await,
+        }
+    }
+
+    /// Returns the message sent back to the model if the tool would access a file that is blocked
+    /// by `filter`.
+    ///
+    /// This covers the paths passed to `fs_read`, `fs_write`, `git blame` and `git diff`, as well
+    /// as the files they resolve to through symlinks. `grep_search` skips blocked files itself.
+    /// Nothing else is checked, because the files that `execute_bash`, `use_aws`, MCP tools and
+    /// `git diff` without paths access are not known ahead of time.
+    pub async fn content_filter_refusal(&self, os: &Os, filter: &ContentFilter) -> Option<String> {
+        if filter.is_empty() {
+            return None;
         }
+        let paths = match self {
+            Tool::FsRead(fs_read) => fs_read.paths(),
+            Tool::FsWrite(fs_write) => vec![fs_write.path()],
+            Tool::Git(Git::Blame(blame)) => vec![blame.path.as_str()],
+            Tool::Git(Git::Diff(diff)) => diff.paths.iter().map(String::as_str).collect(),
+            _ => return None,
+        };
+        let cwd = os.env.current_dir().ok();
+        for path in paths {
+            let path = sanitize_path_tool_arg(os, path);
+            let size = match os.fs.metadata(&path).await {
+                Ok(metadata) if metadata.is_file() => Some(metadata.len()),
+                _ => None,
+            };
+            let resolved = cwd.as_ref().map(|cwd| resolve_path(&cwd.join(&path)));
+            let blocked = std::iter::once(&path)
+                .chain(&resolved)
+                .find_map(|path| Some((path, filter.blocked_reason(path, size)?)));
+            if let Some((path, reason)) = blocked {
+                return Some(ContentFilter::refusal(path, &reason));
+            }
+        }
+        None
     }
 }
 
@@ -224,8 +369,45 @@ fn tool_origin() -> ToolOrigin {
 pub struct QueuedTool {
     pub id: String,
     pub name: String,
+    /// The input of the tool as given by the model
+    pub args: serde_json::Value,
     pub accepted: bool,
     pub tool: Tool,
+    /// Set when the tool writes to a guarded path, which has to be confirmed twice
+    pub guarded: Option<GuardedPath>,
+}
+
+/// Runs a tool use until it completes, unless it runs for longer than `timeout` or is cancelled
+/// with Ctrl-C. Either fails only this tool use, and the others of the response carry on.
+pub async fn cancellable(
+    invocation: impl Future<Output = Result<InvokeOutput>>,
+    timeout: Option<Duration>,
+) -> Result<InvokeOutput> {
+    let deadline = async {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        result = invocation => result,
+        () = deadline => bail!("Timed out after {}", format_duration(timeout.unwrap_or_default())),
+        Ok(()) = tokio::signal::ctrl_c() => bail!("Cancelled by the user"),
+    }
+}
+
+/// Splits the tool uses of a response into the batches they are run in, in order. Consecutive
+/// independent tool uses share a batch and run concurrently, while every other tool use runs on
+/// its own so that it sees the effects of the ones before it.
+pub fn parallel_batches(independent: &[bool], parallelism: usize) -> Vec<Range<usize>> {
+    let mut batches: Vec<Range<usize>> = Vec::new();
+    for (i, is_independent) in independent.iter().enumerate() {
+        match batches.last_mut() {
+            Some(batch) if parallelism > 1 && *is_independent && independent[batch.start] => batch.end = i + 1,
+            _ => batches.push(i..i + 1),
+        }
+    }
+    batches
 }
 
 /// The schema specification describing a tool's fields.
@@ -236,6 +418,10 @@ pub struct InputSchema(pub serde_json::Value);
 #[derive(Debug, Default)]
 pub struct InvokeOutput {
     pub output: OutputKind,
+    /// Resources used by the subprocess the tool ran, if any
+    pub resource_usage: Option<ResourceUsage>,
+    /// Files the tool left behind, tracked with the conversation so they can be cleaned up
+    pub artifacts: Vec<(ArtifactKind, PathBuf)>,
 }
 
 impl InvokeOutput {
@@ -288,6 +474,102 @@ pub fn sanitize_path_tool_arg(os: &Os, path: impl AsRef<Path>) -> PathBuf {
     os.fs.chroot_path(res)
 }
 
+/// Removes `.` and `..` from `path` and resolves symlinks in the part of it that exists, since
+/// the file itself may not exist yet. Symlinks are resolved one component at a time before any
+/// `..` that follows them is applied, the way the OS does when the path is opened.
+pub fn resolve_path(path: &Path) -> PathBuf {
+    let normalized = resolve_components(path, 0);
+    normalized
+        .ancestors()
+        .find_map(|ancestor| {
+            let canonical = ancestor.canonicalize().ok()?;
+            let rest = normalized.strip_prefix(ancestor).ok()?;
+            // Joining an empty path would add a trailing separator
+            Some(match rest.as_os_str().is_empty() {
+                true => canonical,
+                false => canonical.join(rest),
+            })
+        })
+        .unwrap_or(normalized)
+}
+
+/// How many symlinks [resolve_path] follows before it leaves the rest of a path unresolved, as
+/// with `SYMLOOP_MAX`.
+const MAX_SYMLINKS: usize = 40;
+
+fn resolve_components(path: &Path, followed: usize) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            // Whatever precedes it has had its symlinks resolved already
+            Component::ParentDir => {
+                resolved.pop();
+            },
+            Component::Normal(name) => {
+                let next = resolved.join(name);
+                resolved = match std::fs::read_link(&next) {
+                    // A relative target is relative to the directory containing the link, and an
+                    // absolute one replaces it when joined
+                    Ok(target) if followed < MAX_SYMLINKS => resolve_components(&resolved.join(target), followed + 1),
+                    _ => next,
+                };
+            },
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+/// Evaluates a path argument of a tool against the `allowedPaths` and `deniedPaths` globs of its
+/// settings, returning `None` when neither decides. The globs are matched against the path as
+/// given and against where it leads once `..` and symlinks are resolved, so the path is denied
+/// when either matches and only allowed when both do. Where it leads is also matched relative to
+/// the current directory, so that globs such as `src/**` cover absolute paths as well.
+pub fn eval_path_perm(os: &Os, path: &str, allow_set: &GlobSet, deny_set: &GlobSet) -> Option<PermissionEvalResult> {
+    let cwd = os.env.current_dir().unwrap_or_default();
+    let (absolute, resolved) = resolve_path_arg(path, &cwd, os.env.home().as_deref());
+    let relative = absolute
+        .strip_prefix(resolve_path(&cwd))
+        .ok()
+        .filter(|relative| !relative.as_os_str().is_empty());
+    if deny_set.is_match(path)
+        || deny_set.is_match(&absolute)
+        || resolved.as_ref().is_some_and(|resolved| deny_set.is_match(resolved))
+        || relative.is_some_and(|relative| deny_set.is_match(relative))
+    {
+        return Some(PermissionEvalResult::Deny);
+    }
+    if (allow_set.is_match(path) && resolved.is_some_and(|resolved| allow_set.is_match(resolved)))
+        || relative.is_some_and(|relative| allow_set.is_match(relative))
+    {
+        return Some(PermissionEvalResult::Allow);
+    }
+    None
+}
+
+/// Resolves a path argument, returning where it leads as an absolute path and written the way it
+/// was given: relative to `cwd`, under `~` or absolute. There is no such form when a relative
+/// path leads out of `cwd` or a `~` path out of `home`.
+fn resolve_path_arg(path: &str, cwd: &Path, home: Option<&Path>) -> (PathBuf, Option<String>) {
+    let given = Path::new(path);
+    let (absolute, root, prefix) = match (given.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => (home.join(rest), Some(resolve_path(home)), "~/"),
+        (Ok(_), None) => return (given.to_path_buf(), None),
+        (Err(_), _) if given.is_absolute() => (given.to_path_buf(), None, ""),
+        (Err(_), _) => (cwd.join(given), Some(resolve_path(cwd)), ""),
+    };
+    let resolved = resolve_path(&absolute);
+    let in_given_form = match root {
+        Some(root) => resolved
+            .strip_prefix(root)
+            .ok()
+            .map(|relative| format!("{prefix}{}", relative.display())),
+        None => Some(resolved.to_string_lossy().into_owned()),
+    };
+    (resolved, in_given_form)
+}
+
 /// Converts `path` to a relative path according to the current working directory `cwd`.
 fn absolute_to_relative(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<PathBuf> {
     let cwd = cwd.as_ref().canonicalize()?;
@@ -318,7 +600,7 @@ fn absolute_to_relative(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> Result
 }
 
 /// Small helper for formatting the path as a relative path, if able.
-fn format_path(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> String {
+pub fn format_path(cwd: impl AsRef<Path>, path: impl AsRef<Path>) -> String {
     absolute_to_relative(cwd, path.as_ref())
         .map(|p| p.to_string_lossy().to_string())
         // If we have three consecutive ".." then it should probably just stay as an absolute path.
@@ -347,7 +629,7 @@ pub fn display_purpose(purpose: Option<&String>, updates: &mut impl Write) -> Re
             style::Print(super::CONTINUATION_LINE),
             style::Print("\n"),
             style::Print(super::PURPOSE_ARROW),
-            style::SetForegroundColor(Color::Blue),
+            style::SetForegroundColor(theme().label),
             style::Print("Purpose: "),
             style::ResetColor,
             style::Print(purpose),
@@ -369,9 +651,9 @@ pub fn queue_function_result(result: &str, updates: &mut impl Write, is_error: b
 
     // Determine symbol and color
     let (symbol, color) = match (is_error, use_bullet) {
-        (true, _) => (super::ERROR_EXCLAMATION, Color::Red),
+        (true, _) => (super::ERROR_EXCLAMATION, theme().error),
         (false, true) => (super::TOOL_BULLET, Color::Reset),
-        (false, false) => (super::SUCCESS_TICK, Color::Green),
+        (false, false) => (super::SUCCESS_TICK, theme().success),
     };
 
     queue!(updates, style::Print("\n"))?;
@@ -408,6 +690,84 @@ mod tests {
     use super::*;
     use crate::os::ACTIVE_USER_HOME;
 
+    #[tokio::test]
+    async fn test_cancellable() {
+        let result = cancellable(std::future::pending(), Some(Duration::from_millis(10))).await;
+        assert_eq!(result.unwrap_err().to_string(), "Timed out after 10ms");
+        assert!(cancellable(async { Ok(InvokeOutput::default()) }, None).await.is_ok());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "toolsSettings": { "git": { "timeoutMs": 30000 } },
+        }))
+        .unwrap();
+        let tool = Tool::Git(serde_json::from_value(serde_json::json!({ "command": "status" })).unwrap());
+        assert_eq!(tool.timeout(&agent), Some(Duration::from_secs(30)));
+        assert_eq!(tool.timeout(&Agent::default()), None);
+    }
+
+    #[tokio::test]
+    async fn test_content_filter_refusal() {
+        let os = Os::new().await.unwrap();
+        let filter = ContentFilter {
+            denied_extensions: vec!["pem".to_string()],
+            ..Default::default()
+        };
+        let git = |args| Tool::Git(serde_json::from_value(args).unwrap());
+
+        let diff = git(serde_json::json!({ "command": "diff", "paths": ["src", "cert.pem"] }));
+        assert!(diff.content_filter_refusal(&os, &filter).await.is_some());
+        let blame = git(serde_json::json!({ "command": "blame", "path": "cert.pem" }));
+        assert!(blame.content_filter_refusal(&os, &filter).await.is_some());
+        let diff = git(serde_json::json!({ "command": "diff", "paths": ["src"] }));
+        assert!(diff.content_filter_refusal(&os, &filter).await.is_none());
+        assert!(
+            blame
+                .content_filter_refusal(&os, &ContentFilter::default())
+                .await
+                .is_none()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_content_filter_refusal_symlink() {
+        let os = Os::new().await.unwrap();
+        os.fs.write("/cert.pem", "secret").await.unwrap();
+        os.fs.write("/large.txt", "x".repeat(100)).await.unwrap();
+        os.fs.symlink("/cert.pem", "/notes.txt").await.unwrap();
+        os.fs.symlink("/large.txt", "/small.txt").await.unwrap();
+        let read = |path: &str| {
+            Tool::FsRead(
+                serde_json::from_value(serde_json::json!({ "operations": [{ "path": path, "mode": "Line" }] }))
+                    .unwrap(),
+            )
+        };
+
+        let filter = ContentFilter {
+            denied_extensions: vec!["pem".to_string()],
+            ..Default::default()
+        };
+        let refusal = read("/notes.txt").content_filter_refusal(&os, &filter).await;
+        assert!(refusal.is_some_and(|refusal| refusal.contains("cert.pem")));
+
+        let filter = ContentFilter {
+            max_file_size: Some(10),
+            ..Default::default()
+        };
+        assert!(read("/small.txt").content_filter_refusal(&os, &filter).await.is_some());
+    }
+
+    #[test]
+    fn test_parallel_batches() {
+        let independent = [true, true, false, true, true, true, false, false];
+        assert_eq!(parallel_batches(&independent, 4), vec![0..2, 2..3, 3..6, 6..7, 7..8]);
+        assert_eq!(parallel_batches(&independent[..2], 1), vec![0..1, 1..2]);
+        assert!(parallel_batches(&[], 4).is_empty());
+    }
+
     #[tokio::test]
     async fn test_tilde_path_expansion() {
         let os = Os::new().await.unwrap();
@@ -474,4 +834,113 @@ mod tests {
         )
         .await;
     }
+
+    #[test]
+    fn test_resolve_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        assert_eq!(
+            resolve_path(&dir.path().join("src/../../escaped.txt")),
+            root.parent().unwrap().join("escaped.txt")
+        );
+        assert_eq!(
+            resolve_path(&dir.path().join("./new/file.rs")),
+            root.join("new/file.rs")
+        );
+        std::fs::write(root.join("main.rs"), "").unwrap();
+        assert_eq!(resolve_path(&dir.path().join("main.rs")), root.join("main.rs"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_path_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("allowed")).unwrap();
+        std::fs::create_dir_all(root.join("elsewhere/nested")).unwrap();
+        std::os::unix::fs::symlink(root.join("elsewhere/nested"), root.join("allowed/link")).unwrap();
+        std::os::unix::fs::symlink("../elsewhere/new.txt", root.join("allowed/dangling")).unwrap();
+
+        // `..` after a symlink leads out of where the symlink points, as it does when opened
+        assert_eq!(resolve_path(&root.join("allowed/link/../x")), root.join("elsewhere/x"));
+        // A symlink to a file that doesn't exist yet still leads to where it would be written
+        assert_eq!(
+            resolve_path(&root.join("allowed/dangling")),
+            root.join("elsewhere/new.txt")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_eval_path_perm_symlinks() {
+        use globset::{
+            Glob,
+            GlobSetBuilder,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("allowed")).unwrap();
+        std::fs::create_dir_all(root.join("secret")).unwrap();
+        std::fs::write(root.join("secret/key"), "hunter2").unwrap();
+        std::os::unix::fs::symlink(root.join("secret"), root.join("allowed/link")).unwrap();
+        std::os::unix::fs::symlink(root.join("secret/key"), root.join("allowed/key")).unwrap();
+
+        let glob_set = |glob: &str| {
+            GlobSetBuilder::new()
+                .add(Glob::new(&format!("{}/{glob}", root.display())).unwrap())
+                .build()
+                .unwrap()
+        };
+        let allow_set = glob_set("allowed/**");
+        let no_denials = GlobSet::empty();
+        let os = Os::new().await.unwrap();
+        let eval = |path: &str, deny_set: &GlobSet| {
+            eval_path_perm(&os, &format!("{}/{path}", root.display()), &allow_set, deny_set)
+        };
+
+        assert_eq!(eval("allowed/notes.md", &no_denials), Some(PermissionEvalResult::Allow));
+        // Neither a symlinked directory or file nor `..` leads out of the allowed paths
+        assert_eq!(eval("allowed/link/key", &no_denials), None);
+        assert_eq!(eval("allowed/key", &no_denials), None);
+        assert_eq!(eval("allowed/../secret/key", &no_denials), None);
+        assert_eq!(eval("allowed/link/../secret/key", &no_denials), None);
+        // And denied paths are denied whichever way they are reached
+        let deny_set = glob_set("secret/**");
+        assert_eq!(eval("allowed/link/key", &deny_set), Some(PermissionEvalResult::Deny));
+        assert_eq!(eval("allowed/key", &deny_set), Some(PermissionEvalResult::Deny));
+        assert_eq!(
+            eval("allowed/../secret/key", &deny_set),
+            Some(PermissionEvalResult::Deny)
+        );
+        assert_eq!(
+            eval("allowed/link/../secret/key", &deny_set),
+            Some(PermissionEvalResult::Deny)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_path_arg() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let cwd = root.join("project");
+        let home = root.join("home");
+        std::fs::create_dir_all(cwd.join("src")).unwrap();
+        std::fs::create_dir_all(&home).unwrap();
+        std::os::unix::fs::symlink(&home, cwd.join("src/home")).unwrap();
+
+        let form = |path: &str| resolve_path_arg(path, &cwd, Some(&home)).1;
+        assert_eq!(form("src/main.rs"), Some("src/main.rs".to_string()));
+        assert_eq!(form("./src/../README.md"), Some("README.md".to_string()));
+        assert_eq!(form("~/.bashrc"), Some("~/.bashrc".to_string()));
+        // Relative paths that lead out of the current directory have no relative form
+        assert_eq!(form("../other/file"), None);
+        assert_eq!(form("src/home/.bashrc"), None);
+        assert_eq!(form("~/../project/src/main.rs"), None);
+        assert_eq!(
+            form("/etc/../tmp/file"),
+            Some(resolve_path(Path::new("/tmp/file")).to_string_lossy().into_owned())
+        );
+    }
 }
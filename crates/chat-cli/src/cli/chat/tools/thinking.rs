@@ -14,6 +14,7 @@ use super::{
 };
 use crate::database::settings::Setting;
 use crate::os::Os;
+use crate::util::theme::theme;
 
 /// The Think tool allows the model to reason through complex problems during response generation.
 /// It provides a dedicated space for the model to process information from tool call results,
@@ -40,7 +41,7 @@ impl Thinking {
             // Show a preview of the thought that will be displayed
             queue!(
                 output,
-                style::SetForegroundColor(Color::Blue),
+                style::SetForegroundColor(theme().label),
                 style::Print("I'll share my reasoning process: "),
                 style::SetForegroundColor(Color::Reset),
                 style::Print(&self.thought),
@@ -58,6 +59,7 @@ impl Thinking {
         // 2. When disabled or empty: Nothing should be shown
         Ok(InvokeOutput {
             output: OutputKind::Text(String::new()),
+            ..Default::default()
         })
     }
 
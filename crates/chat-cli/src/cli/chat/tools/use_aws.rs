@@ -28,8 +28,11 @@ use crate::cli::agent::{
     PermissionEvalResult,
 };
 use crate::os::Os;
+use crate::util::process::output_with_usage;
 
 const READONLY_OPS: [&str; 6] = ["get", "describe", "list", "ls", "search", "batch_get"];
+/// The verbs of the operations allowed in [UseAwsMode::ReadOnly].
+const READ_ONLY_MODE_VERBS: [&str; 3] = ["get", "describe", "list"];
 
 /// The environment variable name where we set additional metadata for the AWS CLI user agent.
 const USER_AGENT_ENV_VAR: &str = "AWS_EXECUTION_ENV";
@@ -49,11 +52,30 @@ pub struct UseAws {
     pub label: Option<String>,
 }
 
+/// How the permissions of `use_aws` are evaluated, from the `mode` of its tool settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum UseAwsMode {
+    #[default]
+    Default,
+    /// Only `Describe*`, `List*` and `Get*` operations run without confirmation, even when the
+    /// tool or the service is trusted
+    ReadOnly,
+}
+
 impl UseAws {
     pub fn requires_acceptance(&self) -> bool {
         !READONLY_OPS.iter().any(|op| self.operation_name.starts_with(op))
     }
 
+    /// Whether the operation only reads, judged by its verb. Stricter than
+    /// [Self::requires_acceptance], since it backs [UseAwsMode::ReadOnly].
+    fn is_read_only_operation(&self) -> bool {
+        let operation = self.operation_name.to_case(Case::Kebab);
+        let verb = operation.split('-').next().unwrap_or_default();
+        READ_ONLY_MODE_VERBS.contains(&verb)
+    }
+
     pub async fn invoke(&self, _os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
         let mut command = tokio::process::Command::new("aws");
         command.envs(std::env::vars());
@@ -94,12 +116,8 @@ impl UseAws {
                 }
             }
         }
-        let output = command
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .wrap_err_with(|| format!("Unable to spawn command '{:?}'", self))?
-            .wait_with_output()
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let (output, resource_usage) = output_with_usage(&mut command)
             .await
             .wrap_err_with(|| format!("Unable to spawn command '{:?}'", self))?;
         let status = output.status.code().unwrap_or(0).to_string();
@@ -131,8 +149,11 @@ impl UseAws {
                 output: OutputKind::Json(serde_json::json!({
                     "exit_status": status,
                     "stdout": stdout,
-                    "stderr": stderr.clone()
+                    "stderr": stderr.clone(),
+                    "resource_usage": resource_usage,
                 })),
+                resource_usage: Some(resource_usage),
+                ..Default::default()
             })
         } else {
             Err(eyre::eyre!(stderr))
@@ -198,21 +219,42 @@ impl UseAws {
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "camelCase")]
         struct Settings {
+            #[serde(default)]
             allowed_services: Vec<String>,
+            #[serde(default)]
             denied_services: Vec<String>,
+            #[serde(default)]
+            mode: UseAwsMode,
         }
 
         let Self { service_name, .. } = self;
         let is_in_allowlist = agent.allowed_tools.contains("use_aws");
-        match agent.tools_settings.get("use_aws") {
+        let settings = match agent.tools_settings.get("use_aws") {
+            Some(settings) => match serde_json::from_value::<Settings>(settings.clone()) {
+                Ok(settings) => Some(settings),
+                Err(e) => {
+                    error!("Failed to deserialize tool settings for use_aws: {:?}", e);
+                    return PermissionEvalResult::Ask;
+                },
+            },
+            None => None,
+        };
+        // The read-only mode holds whether or not the tool is trusted, so that it can be trusted
+        // in accounts where nothing may change without confirmation
+        if let Some(settings) = settings
+            .as_ref()
+            .filter(|settings| settings.mode == UseAwsMode::ReadOnly)
+        {
+            if settings.denied_services.contains(service_name) {
+                return PermissionEvalResult::Deny;
+            }
+            return match self.is_read_only_operation() {
+                true => PermissionEvalResult::Allow,
+                false => PermissionEvalResult::Ask,
+            };
+        }
+        match settings {
             Some(settings) if is_in_allowlist => {
-                let settings = match serde_json::from_value::<Settings>(settings.clone()) {
-                    Ok(settings) => settings,
-                    Err(e) => {
-                        error!("Failed to deserialize tool settings for use_aws: {:?}", e);
-                        return PermissionEvalResult::Ask;
-                    },
-                };
                 if settings.denied_services.contains(service_name) {
                     return PermissionEvalResult::Deny;
                 }
@@ -271,6 +313,31 @@ mod tests {
         assert!(cmd.requires_acceptance());
     }
 
+    #[test]
+    fn test_eval_perm_read_only_mode() {
+        let agent = serde_json::from_value::<Agent>(serde_json::json!({
+            "allowedTools": ["use_aws"],
+            "toolsSettings": { "use_aws": { "mode": "readOnly", "allowedServices": ["s3"], "deniedServices": ["iam"] } },
+        }))
+        .unwrap();
+        let call = |service: &str, operation: &str| {
+            use_aws! {{
+                "service_name": service,
+                "operation_name": operation,
+                "region": "us-west-2"
+            }}
+            .eval_perm(&agent)
+        };
+
+        assert_eq!(call("ec2", "describe-instances"), PermissionEvalResult::Allow);
+        assert_eq!(call("s3api", "GetObject"), PermissionEvalResult::Allow);
+        assert_eq!(call("s3", "put-object"), PermissionEvalResult::Ask);
+        // Operations that merely start like a read-only verb are still confirmed
+        assert_eq!(call("ssm", "getaway"), PermissionEvalResult::Ask);
+        assert_eq!(call("dynamodb", "batch-write-item"), PermissionEvalResult::Ask);
+        assert_eq!(call("iam", "list-users"), PermissionEvalResult::Deny);
+    }
+
     #[test]
     fn test_use_aws_deser() {
         let cmd = use_aws! {{
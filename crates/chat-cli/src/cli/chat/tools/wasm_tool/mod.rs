@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    InputSchema,
+    ToolOrigin,
+    ToolSpec,
+};
+use crate::cli::agent::Agent;
+
+#[cfg(feature = "wasm-plugins")]
+mod runtime;
+#[cfg(feature = "wasm-plugins")]
+pub use runtime::WasmTool;
+
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+
+/// A tool implemented as a WASI (preview 1) module, declared under `wasmTools` in the agent
+/// config.
+///
+/// The module is run as a command: the tool input is written to its stdin as json and whatever it
+/// writes to stdout is the tool output. A non zero exit code is treated as a failure, in which
+/// case stderr is returned to the model instead.
+///
+/// The description and input schema can be left to the module, which is then run once with
+/// `--describe` when the tools are loaded and writes them to stdout.
+///
+/// Running the modules needs the `wasm-plugins` feature. Without it the config is still accepted,
+/// but the tools it declares are not loaded.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmToolConfig {
+    /// Path to the .wasm module. Relative paths are resolved against the directory containing the
+    /// agent config
+    pub module: String,
+    /// Description of the tool that is sent to the model. Asked to the module when left out
+    #[serde(default)]
+    pub description: String,
+    /// JSON schema of the tool input. Asked to the module along with the description when left
+    /// out, and an object without properties otherwise
+    #[serde(default)]
+    pub input_schema: Option<serde_json::Value>,
+    /// Capabilities granted to the module. By default a module has no access to the filesystem,
+    /// the network or the environment
+    #[serde(default)]
+    pub grants: WasmGrants,
+    /// Maximum time in milliseconds the module is allowed to run for
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmGrants {
+    /// Directories the module can read from. Relative paths are resolved against the current
+    /// working directory and are exposed to the module under the same path
+    #[serde(default)]
+    pub read: Vec<String>,
+    /// Directories the module can read from and write to
+    #[serde(default)]
+    pub write: Vec<String>,
+    /// Whether the module is allowed to open network connections and resolve host names
+    #[serde(default)]
+    pub network: bool,
+    /// Names of environment variables passed through to the module
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
+fn default_input_schema() -> serde_json::Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT_MS
+}
+
+impl WasmToolConfig {
+    /// Without the `wasm-plugins` feature there is no runtime to ask the module, or to run it
+    #[cfg(not(feature = "wasm-plugins"))]
+    pub async fn describe(&mut self, _os: &crate::os::Os, name: &str) -> eyre::Result<()> {
+        eyre::bail!("{name} is a wasm tool, and this build of Q does not include the wasm runtime")
+    }
+
+    pub fn tool_spec(&self, name: &str) -> ToolSpec {
+        ToolSpec {
+            name: name.to_string(),
+            description: self.description.clone(),
+            input_schema: InputSchema(self.input_schema.clone().unwrap_or_else(default_input_schema)),
+            tool_origin: ToolOrigin::Native,
+        }
+    }
+}
+
+/// Resolves the wasm tools declared by the agent, keyed by tool name, with module paths made
+/// absolute.
+pub fn agent_wasm_tools(agent: &Agent) -> HashMap<String, WasmToolConfig> {
+    let base = agent.path.as_deref().and_then(Path::parent);
+    agent
+        .wasm_tools
+        .iter()
+        .map(|(name, config)| {
+            let mut config = config.clone();
+            if let Some(base) = base {
+                config.module = base.join(&config.module).to_string_lossy().to_string();
+            }
+            (name.clone(), config)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_config_deser() {
+        let config = serde_json::from_value::<WasmToolConfig>(serde_json::json!({
+            "module": "tools/count.wasm",
+            "description": "Counts words",
+            "grants": { "read": ["."], "network": true }
+        }))
+        .unwrap();
+        assert_eq!(config.timeout, DEFAULT_TIMEOUT_MS);
+        assert_eq!(config.grants.read, vec!["."]);
+        assert!(config.grants.write.is_empty());
+        assert!(config.grants.network);
+    }
+
+    #[test]
+    fn test_agent_wasm_tools() {
+        let mut agent = Agent {
+            path: Some(PathBuf::from("/workspace/.amazonq/agents/dev.json")),
+            ..Default::default()
+        };
+        agent.wasm_tools.insert(
+            "count".to_string(),
+            serde_json::from_value(serde_json::json!({ "module": "count.wasm", "description": "" })).unwrap(),
+        );
+        let tools = agent_wasm_tools(&agent);
+        assert_eq!(tools["count"].module, "/workspace/.amazonq/agents/count.wasm");
+    }
+}
@@ -0,0 +1,400 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use bstr::ByteSlice;
+use crossterm::{
+    queue,
+    style,
+};
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::Deserialize;
+use wasmtime::{
+    Config,
+    Engine,
+    Linker,
+    Module,
+    Store,
+};
+use wasmtime_wasi::pipe::{
+    MemoryInputPipe,
+    MemoryOutputPipe,
+};
+use wasmtime_wasi::preview1::WasiP1Ctx;
+use wasmtime_wasi::{
+    DirPerms,
+    FilePerms,
+    I32Exit,
+    SocketAddrUse,
+    WasiCtxBuilder,
+};
+
+use super::{
+    WasmGrants,
+    WasmToolConfig,
+};
+use crate::cli::agent::egress::Egress;
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::CONTINUATION_LINE;
+use crate::cli::chat::consts::MAX_TOOL_RESPONSE_SIZE;
+use crate::cli::chat::tools::{
+    InvokeOutput,
+    OutputKind,
+};
+use crate::os::Os;
+use crate::util::theme::theme;
+
+/// Version of the interface between Q and the modules, which modules that describe themselves
+/// declare as `abi`. Modules declaring a later version need a later version of Q.
+const ABI_VERSION: u32 = 1;
+/// The argument a module is run with to describe itself.
+const DESCRIBE_ARG: &str = "--describe";
+/// Amount of fuel a module may consume before yielding back to the executor, which is what allows
+/// a module stuck in a loop to be interrupted by the timeout.
+const FUEL_YIELD_INTERVAL: u64 = 10_000;
+
+static ENGINE: LazyLock<Result<Engine, String>> = LazyLock::new(|| {
+    let mut config = Config::new();
+    config.async_support(true).consume_fuel(true);
+    Engine::new(&config).map_err(|e| e.to_string())
+});
+
+/// What a module run with [DESCRIBE_ARG] writes to stdout.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmToolDescription {
+    /// The [ABI_VERSION] the module was written for
+    pub abi: u32,
+    pub description: String,
+    #[serde(default)]
+    pub input_schema: Option<serde_json::Value>,
+}
+
+impl WasmToolConfig {
+    /// Asks the module for the description and input schema when the config leaves them out. The
+    /// module is given none of its grants for this.
+    pub async fn describe(&mut self, os: &Os, name: &str) -> Result<()> {
+        if !self.description.is_empty() && self.input_schema.is_some() {
+            return Ok(());
+        }
+        let output = run(os, self, name, &[DESCRIBE_ARG], Vec::new(), None, &Egress::default()).await?;
+        let description = serde_json::from_slice::<WasmToolDescription>(&output)
+            .map_err(|e| eyre!("{name} did not describe itself: {e}"))?;
+        if description.abi > ABI_VERSION {
+            bail!(
+                "{name} needs version {} of the wasm tool interface, this version of Q supports version {ABI_VERSION}",
+                description.abi
+            );
+        }
+        if self.description.is_empty() {
+            self.description = description.description;
+        }
+        if self.input_schema.is_none() {
+            self.input_schema = description.input_schema;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WasmTool {
+    pub name: String,
+    pub config: WasmToolConfig,
+    pub args: serde_json::Value,
+    /// Set by the session, see [Self::set_egress]
+    pub egress: Egress,
+}
+
+impl WasmTool {
+    /// Sets the egress policy that the connections of a module granted the network have to comply
+    /// with.
+    pub fn set_egress(&mut self, egress: Egress) {
+        self.egress = egress;
+    }
+
+    pub async fn invoke(&self, os: &Os, _updates: impl Write) -> Result<InvokeOutput> {
+        let stdin = serde_json::to_vec(&self.args)?;
+        let grants = Some(&self.config.grants);
+        let stdout = run(os, &self.config, &self.name, &[], stdin, grants, &self.egress).await?;
+        let stdout = stdout.to_str_lossy().to_string();
+        Ok(InvokeOutput {
+            output: match serde_json::from_str::<serde_json::Value>(&stdout) {
+                Ok(value) if value.is_object() || value.is_array() => OutputKind::Json(value),
+                _ => OutputKind::Text(stdout),
+            },
+            ..Default::default()
+        })
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        queue!(
+            output,
+            style::Print("Running WebAssembly tool "),
+            style::SetForegroundColor(theme().success),
+            style::Print(&self.name),
+            style::ResetColor,
+        )?;
+
+        let WasmGrants {
+            read,
+            write,
+            network,
+            env,
+        } = &self.config.grants;
+        let grants = [
+            (!read.is_empty()).then(|| format!("read {}", read.join(", "))),
+            (!write.is_empty()).then(|| format!("write {}", write.join(", "))),
+            network.then(|| "network".to_string()),
+            (!env.is_empty()).then(|| format!("env {}", env.join(", "))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        if !grants.is_empty() {
+            queue!(
+                output,
+                style::SetForegroundColor(theme().secondary),
+                style::Print(format!(" (grants: {})", grants.join("; "))),
+                style::ResetColor,
+            )?;
+        }
+
+        let params = serde_json::to_string_pretty(&self.args)?
+            .lines()
+            .map(|p| format!("{CONTINUATION_LINE} {p}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        queue!(
+            output,
+            style::Print(" with the param:\n"),
+            style::Print(params),
+            style::Print("\n")
+        )?;
+        Ok(())
+    }
+
+    pub async fn validate(&mut self, os: &Os) -> Result<()> {
+        let module = PathBuf::from(&self.config.module);
+        if !os.fs.exists(&module) {
+            bail!("The module for {} does not exist: {}", self.name, module.display());
+        }
+        Ok(())
+    }
+
+    pub fn eval_perm(&self, agent: &Agent) -> PermissionEvalResult {
+        if agent.allowed_tools.contains(&self.name) {
+            PermissionEvalResult::Allow
+        } else {
+            PermissionEvalResult::Ask
+        }
+    }
+}
+
+/// Runs the module of `config` as a command with `args` following its name and `stdin` as input,
+/// with the capabilities of `grants` only, and returns what it wrote to stdout. Connections are
+/// limited to the addresses `egress` allows.
+async fn run(
+    os: &Os,
+    config: &WasmToolConfig,
+    name: &str,
+    args: &[&str],
+    stdin: Vec<u8>,
+    grants: Option<&WasmGrants>,
+    egress: &Egress,
+) -> Result<Vec<u8>> {
+    let engine = ENGINE
+        .as_ref()
+        .map_err(|e| eyre!("Failed to start the wasm runtime: {e}"))?;
+    let wasm = os.fs.read(&config.module).await?;
+    let module = Module::new(engine, &wasm).map_err(|e| eyre!("Failed to compile {name}: {e}"))?;
+
+    let stdout = MemoryOutputPipe::new(MAX_TOOL_RESPONSE_SIZE);
+    let stderr = MemoryOutputPipe::new(MAX_TOOL_RESPONSE_SIZE);
+    let mut builder = WasiCtxBuilder::new();
+    builder
+        .arg(name)
+        .args(args)
+        .stdin(MemoryInputPipe::new(stdin))
+        .stdout(stdout.clone())
+        .stderr(stderr.clone());
+    if let Some(grants) = grants {
+        grant(os, grants, egress, &mut builder)?;
+    }
+
+    let mut store = Store::new(engine, builder.build_p1());
+    store.set_fuel(u64::MAX).map_err(|e| eyre!(e.to_string()))?;
+    store
+        .fuel_async_yield_interval(Some(FUEL_YIELD_INTERVAL))
+        .map_err(|e| eyre!(e.to_string()))?;
+
+    let mut linker = Linker::<WasiP1Ctx>::new(engine);
+    wasmtime_wasi::preview1::add_to_linker_async(&mut linker, |ctx| ctx).map_err(|e| eyre!(e.to_string()))?;
+
+    let run = async {
+        let instance = linker.instantiate_async(&mut store, &module).await?;
+        let start = instance.get_typed_func::<(), ()>(&mut store, "_start")?;
+        start.call_async(&mut store, ()).await
+    };
+    let timeout = Duration::from_millis(config.timeout);
+    let exit_code = match tokio::time::timeout(timeout, run).await {
+        Ok(Ok(())) => 0,
+        Ok(Err(err)) => match err.downcast_ref::<I32Exit>() {
+            Some(I32Exit(code)) => *code,
+            None => bail!("{name} failed: {err}"),
+        },
+        Err(_) => bail!("{name} timed out after {} ms", timeout.as_millis()),
+    };
+
+    if exit_code != 0 {
+        let stderr = stderr.contents();
+        bail!("{name} exited with code {exit_code}: {}", stderr.to_str_lossy().trim());
+    }
+    Ok(stdout.contents().to_vec())
+}
+
+/// Adds the capabilities of `grants` to the wasi context. Everything else is denied.
+fn grant(os: &Os, grants: &WasmGrants, egress: &Egress, builder: &mut WasiCtxBuilder) -> Result<()> {
+    let WasmGrants {
+        read,
+        write,
+        network,
+        env,
+    } = grants;
+
+    let cwd = os.env.current_dir()?;
+    let dirs = read
+        .iter()
+        .map(|dir| (dir, DirPerms::READ, FilePerms::READ))
+        .chain(write.iter().map(|dir| (dir, DirPerms::all(), FilePerms::all())));
+    for (dir, dir_perms, file_perms) in dirs {
+        let host_path = os.fs.chroot_path(cwd.join(dir));
+        builder
+            .preopened_dir(&host_path, dir, dir_perms, file_perms)
+            .map_err(|e| eyre!("Failed to grant access to {}: {e}", host_path.display()))?;
+    }
+
+    if *network {
+        let egress = egress.clone();
+        // The module resolves names itself, so connections are only known by their address and
+        // are checked against the CIDR rules. Binding a local address is left alone.
+        builder
+            .socket_addr_check(move |addr, addr_use| {
+                let allowed = match addr_use {
+                    SocketAddrUse::TcpBind | SocketAddrUse::UdpBind => true,
+                    SocketAddrUse::TcpConnect | SocketAddrUse::UdpConnect | SocketAddrUse::UdpOutgoingDatagram => {
+                        egress.check_addr(addr.ip()).is_ok()
+                    },
+                };
+                Box::pin(async move { allowed })
+            })
+            .allow_ip_name_lookup(true);
+    }
+
+    for key in env {
+        if let Ok(value) = os.env.get(key) {
+            builder.env(key, value);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::chat::tools::wasm_tool::DEFAULT_TIMEOUT_MS;
+
+    /// A WASI command that copies stdin to stdout
+    const ECHO_WAT: &str = r#"
+        (module
+          (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "_start")
+            (i32.store (i32.const 0) (i32.const 64))
+            (i32.store (i32.const 4) (i32.const 1024))
+            (drop (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8)))
+            (i32.store (i32.const 4) (i32.load (i32.const 8)))
+            (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))
+    "#;
+
+    /// A WASI command that exits with code 3
+    const EXIT_WAT: &str = r#"
+        (module
+          (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+          (memory (export "memory") 1)
+          (func (export "_start") (call $proc_exit (i32.const 3))))
+    "#;
+
+    /// A WASI command that describes itself, whatever its arguments
+    const DESCRIBE_WAT: &str = r#"
+        (module
+          (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 64) "{\"abi\":1,\"description\":\"Counts words\",\"inputSchema\":{\"type\":\"object\",\"required\":[\"text\"]}}")
+          (func (export "_start")
+            (i32.store (i32.const 0) (i32.const 64))
+            (i32.store (i32.const 4) (i32.const 90))
+            (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))
+    "#;
+
+    async fn tool(os: &Os, wat: &str, args: serde_json::Value) -> WasmTool {
+        let path = PathBuf::from("/tool.wat");
+        os.fs.write(&path, wat).await.unwrap();
+        WasmTool {
+            name: "test_tool".to_string(),
+            config: WasmToolConfig {
+                module: path.to_string_lossy().to_string(),
+                description: "test".to_string(),
+                input_schema: None,
+                grants: Default::default(),
+                timeout: DEFAULT_TIMEOUT_MS,
+            },
+            args,
+            egress: Egress::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_describe() {
+        let os = Os::new().await.unwrap();
+        let mut config = tool(&os, DESCRIBE_WAT, serde_json::json!({})).await.config;
+        config.description = String::new();
+        config.describe(&os, "count").await.unwrap();
+        let spec = config.tool_spec("count");
+        assert_eq!(spec.description, "Counts words");
+        assert_eq!(spec.input_schema.0["required"], serde_json::json!(["text"]));
+
+        // The config takes precedence, and modules that don't describe themselves aren't run
+        let mut config = tool(&os, EXIT_WAT, serde_json::json!({})).await.config;
+        config.input_schema = Some(serde_json::json!({ "type": "object" }));
+        config.describe(&os, "exit").await.unwrap();
+        assert_eq!(config.description, "test");
+        config.description = String::new();
+        assert!(config.describe(&os, "exit").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invoke_echo() {
+        let os = Os::new().await.unwrap();
+        let tool = tool(&os, ECHO_WAT, serde_json::json!({ "text": "hello" })).await;
+        let output = tool.invoke(&os, std::io::sink()).await.unwrap();
+        assert!(matches!(output.output, OutputKind::Json(value) if value["text"] == "hello"));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_exit_code() {
+        let os = Os::new().await.unwrap();
+        let tool = tool(&os, EXIT_WAT, serde_json::json!({})).await;
+        let err = tool.invoke(&os, std::io::sink()).await.unwrap_err();
+        assert!(err.to_string().contains("exited with code 3"));
+    }
+}
@@ -0,0 +1,569 @@
+//! Conversion of HTML pages to Markdown for the model. Only the main content of the page is kept:
+//! scripts, navigation, headers, footers and the like are dropped, and when the page marks its
+//! main content with `<main>` or `<article>` everything else is dropped as well.
+
+use url::Url;
+
+/// Elements whose contents are never part of the main content.
+const BOILERPLATE: &[&str] = &[
+    "aside", "button", "dialog", "footer", "form", "head", "header", "iframe", "menu", "nav", "noscript", "select",
+    "svg", "template", "title",
+];
+
+/// Elements whose contents are raw text rather than markup.
+const RAW_TEXT: &[&str] = &["script", "style", "textarea"];
+
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "figcaption",
+    "figure",
+    "main",
+    "p",
+    "section",
+    "summary",
+    "table",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+        self_closing: bool,
+    },
+    End(String),
+    Text(String),
+}
+
+impl Token {
+    fn attr(&self, attr: &str) -> Option<&str> {
+        match self {
+            Token::Start { attrs, .. } => attrs
+                .iter()
+                .find(|(name, _)| name == attr)
+                .map(|(_, value)| value.as_str()),
+            _ => None,
+        }
+    }
+
+    fn is_start(&self, tag: &str) -> bool {
+        matches!(self, Token::Start { name, .. } if name == tag)
+    }
+}
+
+/// Converts the HTML page at `base` to Markdown, starting with its title.
+pub fn to_markdown(html: &str, base: &Url) -> String {
+    let tokens = tokenize(html);
+    let title = title(&tokens);
+    let mut markdown = Markdown::new(base);
+    for token in main_content(&tokens) {
+        markdown.push(token);
+    }
+    let body = markdown.finish();
+    match title {
+        Some(title) if !body.starts_with("# ") => format!("# {title}\n\n{body}"),
+        _ => body,
+    }
+}
+
+fn tokenize(html: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(decode_entities(rest)));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(decode_entities(&rest[..start])));
+        }
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+
+        let (is_end, tag) = match rest[1..].strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, &rest[1..]),
+        };
+        let name_len = tag
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+            .unwrap_or(tag.len());
+        if name_len == 0 {
+            // Not a tag, e.g. "a < b"
+            tokens.push(Token::Text("<".to_string()));
+            rest = &rest[1..];
+            continue;
+        }
+        let name = tag[..name_len].to_ascii_lowercase();
+        let (attrs, self_closing, after) = parse_attrs(&tag[name_len..]);
+        rest = after;
+
+        if is_end {
+            tokens.push(Token::End(name));
+            continue;
+        }
+        let raw_text = RAW_TEXT.contains(&name.as_str()) && !self_closing;
+        tokens.push(Token::Start {
+            name: name.clone(),
+            attrs,
+            self_closing: self_closing || VOID.contains(&name.as_str()),
+        });
+        if raw_text {
+            let end = rest
+                .to_ascii_lowercase()
+                .find(&format!("</{name}"))
+                .unwrap_or(rest.len());
+            if name == "textarea" {
+                tokens.push(Token::Text(decode_entities(&rest[..end])));
+            }
+            rest = &rest[end..];
+        }
+    }
+    tokens
+}
+
+/// Parses the attributes of a tag up to its closing `>`, returning them along with whether the
+/// tag closes itself and the rest of the input.
+fn parse_attrs(mut input: &str) -> (Vec<(String, String)>, bool, &str) {
+    let mut attrs = Vec::new();
+    loop {
+        input = input.trim_start();
+        match input.chars().next() {
+            None => return (attrs, false, input),
+            Some('>') => return (attrs, false, &input[1..]),
+            Some('/') if input[1..].starts_with('>') => return (attrs, true, &input[2..]),
+            Some('/') => {
+                input = &input[1..];
+                continue;
+            },
+            _ => (),
+        }
+
+        let name_len = input
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(input.len())
+            .max(1);
+        let name = input[..name_len].to_ascii_lowercase();
+        input = input[name_len..].trim_start();
+
+        let mut value = String::new();
+        if let Some(rest) = input.strip_prefix('=') {
+            let rest = rest.trim_start();
+            match rest.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = rest[1..].find(quote).map_or(rest.len(), |end| end + 1);
+                    value = decode_entities(&rest[1..end]);
+                    input = rest.get(end + 1..).unwrap_or_default();
+                },
+                _ => {
+                    let end = rest.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(rest.len());
+                    value = decode_entities(&rest[..end]);
+                    input = &rest[end..];
+                },
+            }
+        }
+        attrs.push((name, value));
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let decoded_char = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "hellip" => Some('…'),
+            "copy" => Some('©'),
+            _ => {
+                let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            },
+        });
+        match (entity, decoded_char) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            },
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn title(tokens: &[Token]) -> Option<String> {
+    let start = tokens.iter().position(|token| token.is_start("title"))?;
+    let title = tokens[start + 1..]
+        .iter()
+        .take_while(|token| !matches!(token, Token::End(name) if name == "title"))
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<String>();
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+/// The tokens of the first `<main>` element, or of the first `<article>`, or all of them if the
+/// page has neither.
+fn main_content(tokens: &[Token]) -> &[Token] {
+    let is_main = |token: &Token| token.is_start("main") || token.attr("role") == Some("main");
+    let start = tokens
+        .iter()
+        .position(is_main)
+        .or_else(|| tokens.iter().position(|token| token.is_start("article")));
+    let Some(start) = start else {
+        return tokens;
+    };
+    let Token::Start { name, .. } = &tokens[start] else {
+        return tokens;
+    };
+
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::Start {
+                name: tag,
+                self_closing: false,
+                ..
+            } if tag == name => depth += 1,
+            Token::End(tag) if tag == name => {
+                depth -= 1;
+                if depth == 0 {
+                    return &tokens[start..=i];
+                }
+            },
+            _ => (),
+        }
+    }
+    &tokens[start..]
+}
+
+struct Markdown<'a> {
+    base: &'a Url,
+    out: String,
+    /// Depth of boilerplate elements the current token is in
+    skip: usize,
+    /// Depth of `<pre>` elements the current token is in
+    pre: usize,
+    /// Whitespace was seen since the last text
+    pending_space: bool,
+    /// The open lists, with the number of the next item for ordered ones
+    lists: Vec<Option<usize>>,
+    /// The open links, with where their text starts in the output and their target
+    links: Vec<(usize, Option<String>)>,
+}
+
+impl<'a> Markdown<'a> {
+    fn new(base: &'a Url) -> Self {
+        Self {
+            base,
+            out: String::new(),
+            skip: 0,
+            pre: 0,
+            pending_space: false,
+            lists: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, token: &Token) {
+        match token {
+            Token::Start { name, self_closing, .. } if BOILERPLATE.contains(&name.as_str()) => {
+                if !self_closing {
+                    self.skip += 1;
+                }
+            },
+            Token::End(name) if BOILERPLATE.contains(&name.as_str()) => {
+                self.skip = self.skip.saturating_sub(1);
+            },
+            _ if self.skip > 0 => (),
+            Token::Text(text) => self.text(text),
+            Token::Start { name, .. } => self.start(name, token),
+            Token::End(name) => self.end(name),
+        }
+    }
+
+    fn start(&mut self, name: &str, token: &Token) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.block();
+                let level = name[1..].parse().unwrap_or(1);
+                self.out.push_str(&"#".repeat(level));
+                self.out.push(' ');
+            },
+            "br" => self.newline(),
+            "hr" => {
+                self.block();
+                self.out.push_str("---");
+                self.block();
+            },
+            "pre" => {
+                self.block();
+                self.out.push_str("```\n");
+                self.pre += 1;
+            },
+            "code" | "kbd" | "samp" if self.pre == 0 => self.inline("`"),
+            "strong" | "b" => self.inline("**"),
+            "em" | "i" => self.inline("_"),
+            "ul" => {
+                self.list_break();
+                self.lists.push(None);
+            },
+            "ol" => {
+                self.list_break();
+                let start = token.attr("start").and_then(|start| start.parse().ok());
+                self.lists.push(Some(start.unwrap_or(1)));
+            },
+            "li" => {
+                self.newline();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    },
+                    _ => "- ".to_string(),
+                };
+                self.out.push_str(&indent);
+                self.out.push_str(&marker);
+            },
+            "blockquote" => {
+                self.block();
+                self.out.push_str("> ");
+            },
+            "dt" | "dd" | "tr" => self.newline(),
+            "td" | "th" => self.inline(" | "),
+            "a" => {
+                self.flush_space();
+                let href = token.attr("href").and_then(|href| self.link_target(href));
+                self.links.push((self.out.len(), href));
+            },
+            "img" => {
+                if let Some(alt) = token.attr("alt").filter(|alt| !alt.trim().is_empty()) {
+                    self.inline(&format!("[{}]", alt.trim()));
+                }
+            },
+            _ if BLOCKS.contains(&name) => self.block(),
+            _ => (),
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "p" | "blockquote" | "table" => self.block(),
+            "pre" => {
+                self.pre = self.pre.saturating_sub(1);
+                if !self.out.ends_with('\n') {
+                    self.out.push('\n');
+                }
+                self.out.push_str("```");
+                self.block();
+            },
+            "code" | "kbd" | "samp" if self.pre == 0 => self.out.push('`'),
+            "strong" | "b" => self.out.push_str("**"),
+            "em" | "i" => self.out.push('_'),
+            "ul" | "ol" => {
+                self.lists.pop();
+                self.list_break();
+            },
+            "li" | "dt" | "dd" | "tr" => self.newline(),
+            "a" => {
+                if let Some((start, Some(href))) = self.links.pop() {
+                    let text = self.out[start..].trim().to_string();
+                    if !text.is_empty() && text != href {
+                        self.out.truncate(start);
+                        self.out.push_str(&format!("[{text}]({href})"));
+                    }
+                }
+            },
+            _ if BLOCKS.contains(&name) => self.block(),
+            _ => (),
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.pre > 0 {
+            self.out.push_str(text);
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.pending_space = true;
+            } else {
+                self.flush_space();
+                self.out.push(c);
+            }
+        }
+    }
+
+    fn inline(&mut self, text: &str) {
+        self.flush_space();
+        self.out.push_str(text);
+    }
+
+    fn flush_space(&mut self) {
+        if self.pending_space && !self.out.is_empty() && !self.out.ends_with([' ', '\n']) {
+            self.out.push(' ');
+        }
+        self.pending_space = false;
+    }
+
+    fn newline(&mut self) {
+        self.pending_space = false;
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    /// Separates a list from what surrounds it, unless it is nested in another one.
+    fn list_break(&mut self) {
+        match self.lists.is_empty() {
+            true => self.block(),
+            false => self.newline(),
+        }
+    }
+
+    fn block(&mut self) {
+        self.trim_end();
+        if !self.out.is_empty() {
+            self.out.push_str("\n\n");
+        }
+    }
+
+    fn trim_end(&mut self) {
+        self.pending_space = false;
+        let trimmed = self.out.trim_end().len();
+        self.out.truncate(trimmed);
+    }
+
+    fn link_target(&self, href: &str) -> Option<String> {
+        let href = href.trim();
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+            return None;
+        }
+        self.base.join(href).ok().map(|url| url.to_string())
+    }
+
+    fn finish(mut self) -> String {
+        self.trim_end();
+        let mut out = String::with_capacity(self.out.len());
+        let mut blank_lines = 0;
+        for line in self.out.lines() {
+            let line = line.trim_end();
+            blank_lines = if line.is_empty() { blank_lines + 1 } else { 0 };
+            if blank_lines < 2 {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        out.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_markdown() {
+        let html = r##"<!DOCTYPE html>
+<html>
+<head><title>Vec in std::vec - Rust</title><style>body { color: red; }</style></head>
+<body>
+  <nav><a href="/">Home</a> <a href="/docs">Docs</a></nav>
+  <main>
+    <h1>Struct <code>Vec</code></h1>
+    <p>A contiguous   growable
+       array type, written as <code>Vec&lt;T&gt;</code>. See <a href="../slice/index.html">slices</a>
+       and <a href="#examples">below</a>.</p>
+    <script>if (a < b) { document.write("<p>nope</p>") }</script>
+    <ul><li>Fast</li><li><strong>Safe</strong></li></ul>
+    <ol start="3"><li>three</li><li>four</li></ol>
+    <pre><code>let v = vec![1, 2];
+assert_eq!(v.len(), 2);</code></pre>
+    <!-- a comment -->
+  </main>
+  <footer>Copyright</footer>
+</body>
+</html>"##;
+        let base = Url::parse("https://doc.rust-lang.org/std/vec/struct.Vec.html").unwrap();
+        assert_eq!(to_markdown(html, &base), indoc::indoc! {"
+            # Struct `Vec`
+
+            A contiguous growable array type, written as `Vec<T>`. See [slices](https://doc.rust-lang.org/std/slice/index.html) and below.
+
+            - Fast
+            - **Safe**
+
+            3. three
+            4. four
+
+            ```
+            let v = vec![1, 2];
+            assert_eq!(v.len(), 2);
+            ```"
+        });
+    }
+
+    #[test]
+    fn test_to_markdown_without_main() {
+        let html = "<title> Notes </title><header>Site</header><div><h2>Install</h2>Run <kbd>make</kbd> &amp; wait&hellip;<br>Done</div>";
+        let base = Url::parse("https://example.com").unwrap();
+        assert_eq!(
+            to_markdown(html, &base),
+            "# Notes\n\n## Install\n\nRun `make` & wait…\nDone"
+        );
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(
+            decode_entities("a &lt; b &amp;&amp; c &#62; d &#x27;e&#x27;"),
+            "a < b && c > d 'e'"
+        );
+        assert_eq!(decode_entities("AT&T &unknown; &"), "AT&T &unknown; &");
+    }
+}
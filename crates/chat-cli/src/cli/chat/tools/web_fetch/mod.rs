@@ -0,0 +1,350 @@
+mod html;
+
+use std::io::Write;
+use std::time::Duration;
+
+use crossterm::queue;
+use crossterm::style::{
+    self,
+};
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use regex::Regex;
+use serde::Deserialize;
+use url::Url;
+
+use super::{
+    InvokeOutput,
+    MAX_TOOL_RESPONSE_SIZE,
+    OutputKind,
+};
+use crate::cli::agent::egress::Egress;
+use crate::cli::agent::{
+    Agent,
+    PermissionEvalResult,
+};
+use crate::cli::chat::util::truncate_safe_in_place;
+use crate::os::Os;
+use crate::request::client_builder;
+use crate::util::theme::theme;
+
+/// Pages are cut off after this many bytes are downloaded.
+const MAX_DOWNLOAD_BYTES: usize = 5 * 1024 * 1024;
+/// Bytes of content returned to the model when it does not ask for a length.
+const DEFAULT_MAX_LENGTH: usize = 50_000;
+const MAX_REDIRECTS: usize = 5;
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// The name rules are looked up under in robots.txt, before falling back to the rules for `*`.
+const ROBOTS_USER_AGENT: &str = "amazonq";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebFetch {
+    pub url: String,
+    /// Maximum length of the returned content in bytes
+    pub max_length: Option<usize>,
+    /// Set by the session, see [Self::set_egress]
+    #[serde(skip)]
+    egress: Egress,
+}
+
+impl WebFetch {
+    /// Sets the egress policy that the page and any redirects have to comply with.
+    pub fn set_egress(&mut self, egress: Egress) {
+        self.egress = egress;
+    }
+
+    pub async fn validate(&mut self, _os: &Os) -> Result<()> {
+        let url = Url::parse(&self.url).wrap_err_with(|| format!("{} is not a valid url", self.url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("Only http and https urls can be fetched");
+        }
+        self.egress.check_url(url.as_str())?;
+        Ok(())
+    }
+
+    pub fn queue_description(&self, output: &mut impl Write) -> Result<()> {
+        queue!(
+            output,
+            style::Print("Fetching: "),
+            style::SetForegroundColor(theme().success),
+            style::Print(&self.url),
+            style::ResetColor,
+            style::Print("\n"),
+        )?;
+        Ok(())
+    }
+
+    pub async fn invoke(&self, _os: &Os, _output: impl Write) -> Result<InvokeOutput> {
+        let (url, mut content, cut_off) = fetch_text(Url::parse(&self.url)?, self.egress.clone()).await?;
+        let max_length = self
+            .max_length
+            .unwrap_or(DEFAULT_MAX_LENGTH)
+            .min(MAX_TOOL_RESPONSE_SIZE);
+        if cut_off && content.len() <= max_length {
+            content.push_str("\n\n... (the page is too large and was cut off)");
+        }
+        truncate_safe_in_place(
+            &mut content,
+            max_length,
+            "\n\n... (truncated, fetch again with a larger max_length for more)",
+        );
+
+        Ok(InvokeOutput {
+            output: OutputKind::Text(format!("Content of {url}:\n\n{content}")),
+            ..Default::default()
+        })
+    }
+
+    pub fn eval_perm(agent: &Agent) -> PermissionEvalResult {
+        match agent.allowed_tools.contains("web_fetch") {
+            true => PermissionEvalResult::Allow,
+            false => PermissionEvalResult::Ask,
+        }
+    }
+}
+
+/// Fetches `url` if its robots.txt allows it, converting HTML pages to Markdown. Returns the url
+/// the content was fetched from after redirects, the content and whether it was cut off at
+/// [MAX_DOWNLOAD_BYTES].
+pub async fn fetch_text(url: Url, egress: Egress) -> Result<(Url, String, bool)> {
+    let client = client(egress)?;
+    if !robots_txt_allows(&client, &url).await {
+        bail!(
+            "The robots.txt of {} does not allow fetching {url}",
+            url.host_str().unwrap_or_default()
+        );
+    }
+
+    let response = client.get(url.clone()).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("Fetching {url} failed with status {status}");
+    }
+    let url = response.url().clone();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let (body, cut_off) = read_capped(response, MAX_DOWNLOAD_BYTES).await?;
+    let body = String::from_utf8_lossy(&body);
+
+    let content = if content_type.contains("html") || (content_type.is_empty() && looks_like_html(&body)) {
+        html::to_markdown(&body, &url)
+    } else if is_text(&content_type) {
+        body.into_owned()
+    } else {
+        bail!("{url} can't be read as text, its content type is {content_type}");
+    };
+    Ok((url, content, cut_off))
+}
+
+/// A client that only connects to addresses and follows redirects `egress` allows.
+fn client(egress: Egress) -> Result<reqwest::Client> {
+    Ok(client_builder()
+        .redirect(egress.redirect_policy(MAX_REDIRECTS))
+        .dns_resolver(egress.resolver())
+        .timeout(TIMEOUT)
+        .build()?)
+}
+
+async fn read_capped(mut response: reqwest::Response, max_bytes: usize) -> Result<(Vec<u8>, bool)> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > max_bytes {
+            body.truncate(max_bytes);
+            return Ok((body, true));
+        }
+    }
+    Ok((body, false))
+}
+
+fn looks_like_html(body: &str) -> bool {
+    let start = body
+        .trim_start()
+        .chars()
+        .take(15)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
+
+fn is_text(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || ["json", "xml", "javascript", "yaml", "toml"]
+            .iter()
+            .any(|kind| content_type.contains(kind))
+}
+
+/// Whether the robots.txt of the site lets us fetch `url`. Sites without one, or whose robots.txt
+/// can't be fetched, allow everything.
+async fn robots_txt_allows(client: &reqwest::Client, url: &Url) -> bool {
+    let Ok(robots_url) = url.join("/robots.txt") else {
+        return true;
+    };
+    let robots = match client.get(robots_url).send().await {
+        Ok(response) if response.status().is_success() => response.text().await.unwrap_or_default(),
+        _ => return true,
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+    robots_allows(&robots, &path)
+}
+
+/// Evaluates the rules of a robots.txt for `path` as described in RFC 9309: the rules of the
+/// groups for our user agent apply, or else those for `*`, and the longest matching rule wins,
+/// with allow rules winning ties.
+fn robots_allows(robots: &str, path: &str) -> bool {
+    struct Group {
+        user_agents: Vec<String>,
+        /// Whether each rule allows or disallows, along with its pattern
+        rules: Vec<(bool, String)>,
+    }
+
+    let mut groups: Vec<Group> = Vec::new();
+    let mut reading_user_agents = false;
+    for line in robots.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "user-agent" => {
+                if !reading_user_agents {
+                    groups.push(Group {
+                        user_agents: Vec::new(),
+                        rules: Vec::new(),
+                    });
+                    reading_user_agents = true;
+                }
+                if let Some(group) = groups.last_mut() {
+                    group.user_agents.push(value.to_ascii_lowercase());
+                }
+            },
+            key @ ("allow" | "disallow") => {
+                reading_user_agents = false;
+                if let Some(group) = groups.last_mut().filter(|_| !value.is_empty()) {
+                    group.rules.push((key == "allow", value.to_string()));
+                }
+            },
+            _ => (),
+        }
+    }
+
+    let applies_to = |user_agent: &str| {
+        groups
+            .iter()
+            .filter(|group| group.user_agents.iter().any(|agent| agent == user_agent))
+            .collect::<Vec<_>>()
+    };
+    let mut applicable = applies_to(ROBOTS_USER_AGENT);
+    if applicable.is_empty() {
+        applicable = applies_to("*");
+    }
+    applicable
+        .into_iter()
+        .flat_map(|group| &group.rules)
+        .filter(|(_, pattern)| robots_pattern_matches(pattern, path))
+        .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+        .is_none_or(|(allow, _)| *allow)
+}
+
+/// Matches a robots.txt path pattern, where `*` matches anything and a trailing `$` anchors the
+/// pattern to the end of the path.
+fn robots_pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let regex = format!(
+        "^{}{}",
+        pattern.split('*').map(regex::escape).collect::<Vec<_>>().join(".*"),
+        if anchored { "$" } else { "" }
+    );
+    Regex::new(&regex).is_ok_and(|regex| regex.is_match(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::agent::egress::EgressPolicy;
+
+    #[test]
+    fn test_robots_allows() {
+        let robots = "
+            # Comments are ignored
+            User-agent: *
+            Disallow: /private/
+            Allow: /private/docs/
+            Disallow: /*.pdf$
+
+            User-agent: badbot
+            Disallow: /
+        ";
+        assert!(robots_allows(robots, "/docs/index.html"));
+        assert!(!robots_allows(robots, "/private/keys"));
+        assert!(robots_allows(robots, "/private/docs/guide"));
+        assert!(!robots_allows(robots, "/manual.pdf"));
+        assert!(robots_allows(robots, "/manual.pdf?download=1"));
+        assert!(robots_allows("", "/"));
+
+        // Rules for our user agent replace the rules for everyone
+        let robots = "User-agent: *\nDisallow: /\n\nUser-agent: AmazonQ\nUser-agent: other\nDisallow: /drafts";
+        assert!(robots_allows(robots, "/guide"));
+        assert!(!robots_allows(robots, "/drafts/next"));
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let os = Os::new().await.unwrap();
+        let web_fetch = |url: &str| serde_json::from_value::<WebFetch>(serde_json::json!({ "url": url })).unwrap();
+
+        assert!(web_fetch("https://docs.rs/tokio").validate(&os).await.is_ok());
+        assert!(web_fetch("file:///etc/passwd").validate(&os).await.is_err());
+        assert!(web_fetch("not a url").validate(&os).await.is_err());
+
+        let mut blocked = web_fetch("http://169.254.169.254/latest/meta-data");
+        let policy = EgressPolicy {
+            denied_cidrs: vec!["169.254.0.0/16".to_string()],
+            ..Default::default()
+        };
+        blocked.set_egress(Egress::resolve(&os.database.settings, Some(&policy)));
+        assert!(blocked.validate(&os).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_resolved_host() {
+        let os = Os::new().await.unwrap();
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/")
+            .with_header("content-type", "text/plain")
+            .with_body("hello")
+            .create_async()
+            .await;
+        let url = Url::parse(&server.url().replace("127.0.0.1", "localhost")).unwrap();
+
+        // Hosts given by name are checked against the CIDR rules by the addresses they resolve to
+        let policy = EgressPolicy {
+            denied_cidrs: vec!["127.0.0.0/8".to_string(), "::1".to_string()],
+            ..Default::default()
+        };
+        let egress = Egress::resolve(&os.database.settings, Some(&policy));
+        assert!(egress.check_url(url.as_str()).is_ok());
+        let err = fetch_text(url.clone(), egress).await.unwrap_err();
+        assert!(format!("{err:?}").contains("blocked by the egress policy"), "{err:?}");
+
+        let (_, content, _) = fetch_text(url, Egress::resolve(&os.database.settings, None))
+            .await
+            .unwrap();
+        assert_eq!(content, "hello");
+    }
+}
@@ -0,0 +1,147 @@
+//! Web documents in the context, added with `/context add https://...`. They are fetched the way
+//! the `web_fetch` tool fetches pages, within the egress policy of the agent, and cached under the
+//! data directory. The cached copy is used until it is older than `chat.contextUrlRefreshMinutes`,
+//! an hour by default, and is kept if fetching the document again fails.
+
+use std::path::PathBuf;
+
+use eyre::{
+    Result,
+    WrapErr,
+    bail,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+use time::OffsetDateTime;
+use tracing::warn;
+use url::Url;
+
+use super::tools::web_fetch::fetch_text;
+use crate::cli::agent::egress::Egress;
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::util::directories;
+
+const DEFAULT_REFRESH_MINUTES: i64 = 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDocument {
+    url: String,
+    /// Unix timestamp of when the document was fetched
+    fetched_at: i64,
+    content: String,
+}
+
+/// Whether a context rule is a web document rather than a path.
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+fn cache_path(url: &str) -> Result<PathBuf> {
+    Ok(directories::chat_context_url_cache_dir()?.join(format!("{}.json", hex::encode(Sha256::digest(url)))))
+}
+
+/// Returns the content of the document at `url`, from the cache unless it is stale or `refresh`
+/// is set.
+pub async fn read(os: &Os, url: &str, egress: Egress, refresh: bool) -> Result<String> {
+    let path = cache_path(url)?;
+    let cached = match os.fs.read_to_string(&path).await {
+        Ok(cached) => serde_json::from_str::<CachedDocument>(&cached).ok(),
+        Err(_) => None,
+    };
+
+    let refresh_secs = os
+        .database
+        .settings
+        .get_int(Setting::ChatContextUrlRefreshMinutes)
+        .unwrap_or(DEFAULT_REFRESH_MINUTES)
+        * 60;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    if let Some(cached) = &cached {
+        if !refresh && now - cached.fetched_at < refresh_secs {
+            return Ok(cached.content.clone());
+        }
+    }
+
+    match fetch(url, egress).await {
+        Ok(content) => {
+            let document = CachedDocument {
+                url: url.to_string(),
+                fetched_at: now,
+                content,
+            };
+            if let Some(parent) = path.parent() {
+                os.fs.create_dir_all(parent).await?;
+            }
+            os.fs.write(&path, serde_json::to_string(&document)?).await?;
+            Ok(document.content)
+        },
+        Err(err) => match cached {
+            Some(cached) => {
+                warn!(%url, ?err, "Failed to refresh the context document, using the cached copy");
+                Ok(cached.content)
+            },
+            None => Err(err),
+        },
+    }
+}
+
+async fn fetch(url: &str, egress: Egress) -> Result<String> {
+    let parsed = Url::parse(url).wrap_err_with(|| format!("{url} is not a valid url"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        bail!("Only http and https urls can be added");
+    }
+    egress.check_url(parsed.as_str())?;
+
+    let (_, mut content, cut_off) = fetch_text(parsed, egress).await?;
+    if cut_off {
+        content.push_str("\n\n... (the page is too large and was cut off)");
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn cache(os: &Os, url: &str, age_secs: i64, content: &str) {
+        let document = CachedDocument {
+            url: url.to_string(),
+            fetched_at: OffsetDateTime::now_utc().unix_timestamp() - age_secs,
+            content: content.to_string(),
+        };
+        let path = cache_path(url).unwrap();
+        os.fs.create_dir_all(path.parent().unwrap()).await.unwrap();
+        os.fs
+            .write(&path, serde_json::to_string(&document).unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_cached() {
+        let os = Os::new().await.unwrap();
+        // Nothing listens on port 1, so fetching fails
+        let url = "http://127.0.0.1:1/docs";
+
+        cache(&os, url, 60, "fresh").await;
+        assert_eq!(read(&os, url, Egress::default(), false).await.unwrap(), "fresh");
+
+        // A stale copy is kept when the document can't be fetched again
+        cache(&os, url, 2 * 60 * 60, "stale").await;
+        assert_eq!(read(&os, url, Egress::default(), false).await.unwrap(), "stale");
+
+        assert!(
+            read(&os, "http://127.0.0.1:1/missing", Egress::default(), false)
+                .await
+                .is_err()
+        );
+        assert!(is_url("https://docs.rs") && !is_url("docs/https.md"));
+    }
+}
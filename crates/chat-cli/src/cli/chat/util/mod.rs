@@ -113,28 +113,78 @@ fn should_play_bell() -> bool {
     false
 }
 
-/// This is a simple greedy algorithm that drops the largest files first
-/// until the total size is below the limit
-///
-/// # Arguments
-/// * `files` - A mutable reference to a vector of tuples: (filename, content). This file will be
-///   sorted but the content will not be changed.
+/// A file that is truncated to fit the budget keeps at least this many tokens, or is dropped.
+pub const MIN_TRUNCATED_TOKENS: usize = 500;
+/// Appended to context files that were truncated to fit the budget.
+const CONTEXT_TRUNCATED_SUFFIX: &str = "\n[Truncated to fit the token budget of the context files]";
+
+/// What the token budget of the context files left of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetOutcome {
+    Included,
+    /// Cut down to about this many tokens
+    Truncated(usize),
+    Dropped,
+}
+
+/// A context file after [apply_context_budget].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetedFile {
+    pub name: String,
+    /// The content sent to the model, which is empty for a dropped file
+    pub content: String,
+    /// Tokens of the whole file
+    pub tokens: usize,
+    pub outcome: BudgetOutcome,
+}
+
+/// Fits context files into `budget` tokens. Files are taken from the smallest to the largest, so
+/// that as many as possible are included in full. The first file that doesn't fit is truncated to
+/// what is left of the budget if that is at least [MIN_TRUNCATED_TOKENS], and dropped otherwise,
+/// along with every larger file.
 ///
-/// Returns the dropped files
-pub fn drop_matched_context_files(files: &mut [(String, String)], limit: usize) -> Result<Vec<(String, String)>> {
-    files.sort_by(|a, b| TokenCounter::count_tokens(&b.1).cmp(&TokenCounter::count_tokens(&a.1)));
-    let mut total_size = 0;
-    let mut dropped_files = Vec::new();
-
-    for (filename, content) in files.iter() {
-        let size = TokenCounter::count_tokens(content);
-        if total_size + size > limit {
-            dropped_files.push((filename.clone(), content.clone()));
+/// Returns the files in the order they were given.
+pub fn apply_context_budget(files: Vec<(String, String)>, budget: usize) -> Vec<BudgetedFile> {
+    let mut order = (0..files.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| TokenCounter::count_tokens(&files[*i].1));
+
+    let mut outcomes = vec![BudgetOutcome::Dropped; files.len()];
+    let mut remaining = budget;
+    for i in order {
+        let tokens = TokenCounter::count_tokens(&files[i].1);
+        if tokens <= remaining {
+            outcomes[i] = BudgetOutcome::Included;
+            remaining -= tokens;
         } else {
-            total_size += size;
+            if remaining >= MIN_TRUNCATED_TOKENS {
+                outcomes[i] = BudgetOutcome::Truncated(remaining);
+            }
+            break;
         }
     }
-    Ok(dropped_files)
+
+    files
+        .into_iter()
+        .zip(outcomes)
+        .map(|((name, mut content), outcome)| {
+            let tokens = TokenCounter::count_tokens(&content);
+            match outcome {
+                BudgetOutcome::Included => (),
+                BudgetOutcome::Truncated(kept) => truncate_safe_in_place(
+                    &mut content,
+                    TokenCounter::token_to_chars(kept),
+                    CONTEXT_TRUNCATED_SUFFIX,
+                ),
+                BudgetOutcome::Dropped => content.clear(),
+            }
+            BudgetedFile {
+                name,
+                content,
+                tokens,
+                outcome,
+            }
+        })
+        .collect()
 }
 
 pub fn serde_value_to_document(value: serde_json::Value) -> Document {
@@ -229,24 +279,39 @@ mod tests {
     }
 
     #[test]
-    fn test_drop_matched_context_files() {
-        let mut files = vec![
-            ("file1".to_string(), "This is a test file".to_string()),
-            (
-                "file3".to_string(),
-                "Yet another test file that's has the largest context file".to_string(),
-            ),
+    fn test_apply_context_budget() {
+        let files = vec![
+            ("large".to_string(), "a".repeat(8000)),
+            ("small".to_string(), "b".repeat(400)),
+            ("medium".to_string(), "c".repeat(4000)),
+            ("largest".to_string(), "d".repeat(40_000)),
         ];
-        let limit = 9;
 
-        let dropped_files = drop_matched_context_files(&mut files, limit).unwrap();
-        assert_eq!(dropped_files.len(), 1);
-        assert_eq!(dropped_files[0].0, "file3");
-        assert_eq!(files.len(), 2);
+        // small (100 tokens) and medium (1000) fit, leaving 900 tokens to truncate large (2000) to
+        let budgeted = apply_context_budget(files.clone(), 2000);
+        let outcomes = budgeted.iter().map(|file| file.outcome).collect::<Vec<_>>();
+        assert_eq!(outcomes, [
+            BudgetOutcome::Truncated(900),
+            BudgetOutcome::Included,
+            BudgetOutcome::Included,
+            BudgetOutcome::Dropped,
+        ]);
+        assert_eq!(budgeted[0].tokens, 2000);
+        assert_eq!(budgeted[0].content.len(), 3600);
+        assert!(budgeted[0].content.ends_with(CONTEXT_TRUNCATED_SUFFIX));
+        assert_eq!(budgeted[2].content, files[2].1);
+        assert!(budgeted[3].content.is_empty());
 
-        for (filename, _) in dropped_files.iter() {
-            files.retain(|(f, _)| f != filename);
-        }
-        assert_eq!(files.len(), 1);
+        // Too little is left to truncate large, so it is dropped
+        let outcomes = apply_context_budget(files, 1500)
+            .into_iter()
+            .map(|file| file.outcome)
+            .collect::<Vec<_>>();
+        assert_eq!(outcomes, [
+            BudgetOutcome::Dropped,
+            BudgetOutcome::Included,
+            BudgetOutcome::Included,
+            BudgetOutcome::Dropped,
+        ]);
     }
 }
@@ -0,0 +1,100 @@
+//! Verdicts for `q chat --no-interactive --verdict`. Once the conversation is done, the model is
+//! asked whether the task it was given succeeded, and its answer decides the exit status so that
+//! CI steps can gate on it.
+
+use std::process::ExitCode;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::oneshot;
+
+/// Exit status when the model could not decide, or its answer could not be read.
+pub const NEEDS_HUMAN_EXIT_CODE: u8 = 3;
+
+pub const VERDICT_PROMPT: &str = "The conversation is over. Assess whether the task you were given has been \
+completed successfully. Do not use any tools. Respond with only a JSON object of the form \
+{\"verdict\": \"success\" | \"failure\" | \"needs_human\", \"reason\": \"<one sentence>\"}. Use \
+\"needs_human\" when you cannot tell, or when a person has to review or decide something first.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Failure,
+    NeedsHuman,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::NeedsHuman => "needs_human",
+        }
+    }
+
+    /// 0 for success, 1 for failure and [NEEDS_HUMAN_EXIT_CODE] otherwise, leaving 2 to usage
+    /// errors.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Success => 0,
+            Self::Failure => 1,
+            Self::NeedsHuman => NEEDS_HUMAN_EXIT_CODE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Verdict {
+    pub verdict: Outcome,
+    pub reason: String,
+}
+
+impl Verdict {
+    /// Reads the verdict in a response to [VERDICT_PROMPT]. A response without one is treated as
+    /// needing a human, since nothing can be concluded from it.
+    pub fn parse(response: &str) -> Self {
+        oneshot::parse_json(response).unwrap_or_else(|err| Self {
+            verdict: Outcome::NeedsHuman,
+            reason: format!("the model did not give a verdict: {err}"),
+        })
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(self.verdict.exit_code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let verdict =
+            Verdict::parse("```json\n{\"verdict\": \"needs_human\", \"reason\": \"The plan drops a column.\"}\n```");
+        assert_eq!(verdict, Verdict {
+            verdict: Outcome::NeedsHuman,
+            reason: "The plan drops a column.".to_string(),
+        });
+        assert_eq!(
+            Verdict::parse("{\"verdict\": \"success\", \"reason\": \"\"}").verdict,
+            Outcome::Success
+        );
+
+        let verdict = Verdict::parse("Everything looks good to me!");
+        assert_eq!(verdict.verdict, Outcome::NeedsHuman);
+        assert!(verdict.reason.starts_with("the model did not give a verdict"));
+        assert_eq!(Verdict::parse("{\"verdict\": \"maybe\"}").verdict, Outcome::NeedsHuman);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(Outcome::Success.exit_code(), 0);
+        assert_eq!(Outcome::Failure.exit_code(), 1);
+        assert_eq!(Outcome::NeedsHuman.exit_code(), NEEDS_HUMAN_EXIT_CODE);
+    }
+}
@@ -0,0 +1,234 @@
+//! Workspaces with several roots, such as the packages of a monorepo or an app next to the repo
+//! of its infrastructure. Roots besides the directory chat was started in are declared in
+//! `.amazonq/workspace.json`:
+//!
+//! ```json
+//! { "roots": [{ "path": "../infra" }, { "path": "packages/api", "name": "api" }] }
+//! ```
+//!
+//! The context files the agent lists with relative paths are looked up in every root, tool uses
+//! show the root of the paths they operate on, and `/roots` adds and removes roots at runtime.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use eyre::{
+    Result,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::tools::resolve_path;
+use crate::os::Os;
+use crate::util::directories::workspace_config_path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceRoot {
+    pub name: String,
+    /// The resolved path of the root
+    pub path: PathBuf,
+    /// The path as it was declared, written back when saving
+    declared: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WorkspaceConfig {
+    #[serde(default)]
+    roots: Vec<RootConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RootConfig {
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+/// The roots of the workspace, starting with the directory chat was started in.
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    roots: Vec<WorkspaceRoot>,
+}
+
+impl Workspace {
+    /// Loads the roots declared in the workspace config. Without one, the directory chat was
+    /// started in is the only root.
+    pub async fn load(os: &Os) -> Result<Self> {
+        let cwd = os.env.current_dir()?;
+        let mut workspace = Self::single(&cwd);
+        let path = workspace_config_path(os)?;
+        if !os.fs.exists(&path) {
+            return Ok(workspace);
+        }
+
+        let config: WorkspaceConfig = serde_json::from_str(&os.fs.read_to_string(&path).await?)
+            .map_err(|err| eyre!("{} is not valid: {err}", path.display()))?;
+        for root in config.roots {
+            workspace.add(&cwd, &root.path, root.name)?;
+        }
+        Ok(workspace)
+    }
+
+    /// A workspace whose only root is `cwd`.
+    pub fn single(cwd: &Path) -> Self {
+        let path = resolve_path(cwd);
+        Self {
+            roots: vec![WorkspaceRoot {
+                name: default_name(&path),
+                path,
+                declared: ".".to_string(),
+            }],
+        }
+    }
+
+    /// Writes the roots other than the first one to the workspace config.
+    pub async fn save(&self, os: &Os) -> Result<()> {
+        let config = WorkspaceConfig {
+            roots: self
+                .roots
+                .iter()
+                .skip(1)
+                .map(|root| RootConfig {
+                    path: root.declared.clone(),
+                    name: (root.name != default_name(&root.path)).then(|| root.name.clone()),
+                })
+                .collect(),
+        };
+        let path = workspace_config_path(os)?;
+        if let Some(parent) = path.parent() {
+            os.fs.create_dir_all(parent).await?;
+        }
+        os.fs.write(&path, serde_json::to_string_pretty(&config)?).await?;
+        Ok(())
+    }
+
+    pub fn roots(&self) -> &[WorkspaceRoot] {
+        &self.roots
+    }
+
+    pub fn is_multi_root(&self) -> bool {
+        self.roots.len() > 1
+    }
+
+    /// The roots other than the directory chat was started in.
+    pub fn additional_roots(&self) -> impl Iterator<Item = &WorkspaceRoot> {
+        self.roots.iter().skip(1)
+    }
+
+    /// The innermost root containing `path`, so that a package declared inside the directory chat
+    /// was started in takes precedence over it.
+    pub fn root_of(&self, path: &Path) -> Option<&WorkspaceRoot> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(&root.path))
+            .max_by_key(|root| root.path.components().count())
+    }
+
+    /// Adds `path`, relative to `cwd`, as a root named `name`, or after its directory by default.
+    pub fn add(&mut self, cwd: &Path, path: &str, name: Option<String>) -> Result<&WorkspaceRoot> {
+        let resolved = resolve_path(&cwd.join(path));
+        let name = name.unwrap_or_else(|| default_name(&resolved));
+        if let Some(root) = self.roots.iter().find(|root| root.path == resolved) {
+            bail!("{} is already a root, named {}", resolved.display(), root.name);
+        }
+        if self.roots.iter().any(|root| root.name == name) {
+            bail!("There is already a root named {name}, choose another name with --name");
+        }
+        self.roots.push(WorkspaceRoot {
+            name,
+            path: resolved,
+            declared: path.to_string(),
+        });
+        Ok(&self.roots[self.roots.len() - 1])
+    }
+
+    /// Removes the root named `name`. The directory chat was started in can't be removed.
+    pub fn remove(&mut self, name: &str) -> Result<WorkspaceRoot> {
+        match self.roots.iter().position(|root| root.name == name) {
+            Some(0) => bail!("{name} is the directory chat was started in, it can't be removed"),
+            Some(index) => Ok(self.roots.remove(index)),
+            None => bail!("There is no root named {name}, see /roots list"),
+        }
+    }
+
+    /// Lets the model know about the roots, or None if there is only one.
+    pub fn describe(&self) -> Option<String> {
+        if !self.is_multi_root() {
+            return None;
+        }
+        let roots = self
+            .roots
+            .iter()
+            .map(|root| format!("\n- {}: {}", root.name, root.path.display()))
+            .collect::<String>();
+        Some(format!(
+            "This workspace has several roots. Relative paths are resolved against the first one, use the paths below to work in the others:{roots}"
+        ))
+    }
+}
+
+fn default_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_and_save() {
+        let os = Os::new().await.unwrap();
+        let cwd = os.env.current_dir().unwrap();
+        let workspace = Workspace::load(&os).await.unwrap();
+        assert!(!workspace.is_multi_root());
+        assert!(workspace.describe().is_none());
+
+        let config_path = workspace_config_path(&os).unwrap();
+        os.fs.create_dir_all(config_path.parent().unwrap()).await.unwrap();
+        os.fs
+            .write(
+                &config_path,
+                r#"{ "roots": [{ "path": "packages/api" }, { "path": "../infra", "name": "deploy" }] }"#,
+            )
+            .await
+            .unwrap();
+        let mut workspace = Workspace::load(&os).await.unwrap();
+        let names = workspace
+            .roots()
+            .iter()
+            .map(|root| root.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names[1..], ["api", "deploy"]);
+
+        // The innermost root wins
+        let api = resolve_path(&cwd.join("packages/api/src/main.rs"));
+        assert_eq!(workspace.root_of(&api).map(|root| root.name.as_str()), Some("api"));
+        let readme = resolve_path(&cwd.join("README.md"));
+        assert_eq!(workspace.root_of(&readme), workspace.roots().first());
+        let infra = resolve_path(&cwd.join("../infra/main.tf"));
+        assert_eq!(workspace.root_of(&infra).map(|root| root.name.as_str()), Some("deploy"));
+
+        assert!(workspace.add(&cwd, "packages/api/", None).is_err());
+        assert!(workspace.add(&cwd, "packages/web", Some("api".to_string())).is_err());
+        assert!(workspace.remove(&workspace.roots()[0].name.clone()).is_err());
+        workspace.remove("api").unwrap();
+        workspace.add(&cwd, "packages/web", None).unwrap();
+        workspace.save(&os).await.unwrap();
+
+        let workspace = Workspace::load(&os).await.unwrap();
+        let declared = workspace
+            .additional_roots()
+            .map(|root| (root.name.as_str(), root.declared.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(declared, [("deploy", "../infra"), ("web", "packages/web")]);
+    }
+}
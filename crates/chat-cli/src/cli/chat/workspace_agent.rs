@@ -0,0 +1,237 @@
+//! Selection of the agent a repository pins, `.amazonq/agents/default.json` unless
+//! `chat.workspaceAgentPath` says otherwise, when a session is started without `--agent`. Like an
+//! .editorconfig, the closest one between the current directory and the root of the repository
+//! applies.
+//!
+//! An agent config decides which tools run without asking, so a pinned agent is only selected once
+//! the user trusted it. The answer is remembered for the path of the config and what it resolves
+//! to along with the configs it extends, which means a change to any of them is asked about again.
+
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use crossterm::style::{
+    self,
+    Color,
+    Stylize,
+};
+use crossterm::{
+    execute,
+    queue,
+};
+use eyre::Result;
+use serde_json::Value;
+use sha2::{
+    Digest,
+    Sha256,
+};
+use tracing::warn;
+
+use super::plan_approval::workspace_root;
+use crate::cli::agent::{
+    Agent,
+    grants,
+    resolve_agent_config,
+};
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::util::theme::theme;
+
+/// Where a repository pins its agent, relative to any directory up to the root of the repository.
+pub const DEFAULT_WORKSPACE_AGENT_PATH: &str = ".amazonq/agents/default.json";
+
+/// The config of the agent pinned for `cwd`, if any.
+pub fn find(os: &Os, cwd: &Path) -> Option<PathBuf> {
+    let relative = os
+        .database
+        .settings
+        .get_string(Setting::ChatWorkspaceAgentPath)
+        .unwrap_or_else(|| DEFAULT_WORKSPACE_AGENT_PATH.to_string());
+    let root = workspace_root(cwd);
+    let cwd = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+    for dir in cwd.ancestors() {
+        let path = dir.join(&relative);
+        if path.is_file() {
+            return Some(path);
+        }
+        if dir == root {
+            break;
+        }
+    }
+    None
+}
+
+/// Loads the agent pinned for the current directory if the user trusts it, asking them the first
+/// time when the session is interactive.
+pub async fn select(os: &Os, output: &mut impl Write, interactive: bool) -> Result<Option<Agent>> {
+    let Some(path) = find(os, &os.env.current_dir()?) else {
+        return Ok(None);
+    };
+    let content = tokio::fs::read(&path).await?;
+    let loaded = match resolve_agent_config(os, &path, &content).await {
+        Ok(config) => Agent::load_from_config(os, &path, config.clone())
+            .await
+            .map(|agent| (agent, config)),
+        Err(err) => Err(err),
+    };
+    let (agent, config) = match loaded {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            warning(output, format!("Not using the agent at {}: {err:#}\n", path.display()))?;
+            return Ok(None);
+        },
+    };
+
+    let fingerprint = trust_fingerprint(&path, &config);
+    let trusted = match os.database.get_workspace_agent_trust(&fingerprint) {
+        Ok(Some(trusted)) => trusted,
+        Ok(None) | Err(_) if !interactive => {
+            warning(
+                output,
+                format!(
+                    "Not using the agent at {}, which hasn't been trusted yet. Start an interactive session to trust it\n",
+                    path.display()
+                ),
+            )?;
+            return Ok(None);
+        },
+        Ok(None) | Err(_) => {
+            let trusted = confirm_trust(output, &path, &agent)?;
+            if let Err(err) = os.database.set_workspace_agent_trust(&fingerprint, trusted) {
+                warn!(?err, "failed to record the trust of the workspace agent");
+            }
+            trusted
+        },
+    };
+    if !trusted {
+        return Ok(None);
+    }
+
+    execute!(
+        output,
+        style::SetForegroundColor(theme().secondary),
+        style::Print(format!("Using the agent pinned at {}\n", path.display())),
+        style::SetForegroundColor(Color::Reset),
+    )?;
+    Ok(Some(agent))
+}
+
+/// Shows what the agent is allowed to do and asks whether to use it.
+fn confirm_trust(output: &mut impl Write, path: &Path, agent: &Agent) -> Result<bool> {
+    queue!(
+        output,
+        style::Print(format!("\nThis repository pins the agent at {}\n", path.display())),
+    )?;
+    if let Some(description) = &agent.description {
+        queue!(output, style::Print(format!("  {description}\n")))?;
+    }
+    for grant in grants(agent) {
+        queue!(output, style::Print(format!("  {grant}\n")))?;
+    }
+    execute!(
+        output,
+        style::Print(
+            "Trust it and use it in this repository? You won't be asked again unless it changes. [y/n]: "
+                .with(theme().secondary)
+        ),
+    )?;
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim(), "y" | "Y"))
+}
+
+fn warning(output: &mut impl Write, message: String) -> Result<()> {
+    execute!(
+        output,
+        style::SetForegroundColor(theme().warning),
+        style::Print("WARNING: "),
+        style::SetForegroundColor(Color::Reset),
+        style::Print(message),
+    )?;
+    Ok(())
+}
+
+/// Identifies the config at `path` in the database by what it resolves to once merged with the
+/// configs it extends, so that changing any of them changes what the user trusted.
+fn trust_fingerprint(path: &Path, config: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update([0]);
+    hasher.update(config.to_string().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_find() {
+        let mut os = Os::new().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        let nested = repo.join("src").join("cli");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find(&os, &nested), None);
+
+        let pinned = repo.canonicalize().unwrap().join(DEFAULT_WORKSPACE_AGENT_PATH);
+        std::fs::create_dir_all(pinned.parent().unwrap()).unwrap();
+        std::fs::write(&pinned, "{}").unwrap();
+        assert_eq!(find(&os, &nested), Some(pinned.clone()));
+        assert_eq!(find(&os, &repo), Some(pinned));
+
+        // Agents pinned above the root of the repository don't apply
+        let outside = dir.path().join(DEFAULT_WORKSPACE_AGENT_PATH);
+        std::fs::create_dir_all(outside.parent().unwrap()).unwrap();
+        std::fs::write(&outside, "{}").unwrap();
+        std::fs::remove_dir_all(repo.join(".amazonq")).unwrap();
+        assert_eq!(find(&os, &nested), None);
+
+        os.database
+            .settings
+            .set(Setting::ChatWorkspaceAgentPath, "agent.json")
+            .await
+            .unwrap();
+        std::fs::write(nested.join("agent.json"), "{}").unwrap();
+        assert_eq!(
+            find(&os, &nested),
+            Some(nested.canonicalize().unwrap().join("agent.json"))
+        );
+    }
+
+    async fn fingerprint(os: &Os, path: &Path, content: &str) -> String {
+        let config = resolve_agent_config(os, path, content.as_bytes()).await.unwrap();
+        trust_fingerprint(path, &config)
+    }
+
+    #[tokio::test]
+    async fn test_trust_fingerprint() {
+        let os = Os::new().await.unwrap();
+        let path = Path::new("/repo/.amazonq/agents/default.json");
+        assert_eq!(fingerprint(&os, path, "{}").await, fingerprint(&os, path, "{}").await);
+        assert_ne!(
+            fingerprint(&os, path, "{}").await,
+            fingerprint(&os, path, r#"{ "allowedTools": ["*"] }"#).await
+        );
+        assert_ne!(
+            fingerprint(&os, path, "{}").await,
+            fingerprint(&os, Path::new("/other/.amazonq/agents/default.json"), "{}").await
+        );
+
+        // Changing a config the pinned one extends changes what is trusted
+        let extending = r#"{ "extends": "../base.json" }"#;
+        os.fs.create_dir_all("/repo/.amazonq/agents").await.unwrap();
+        os.fs.write("/repo/.amazonq/base.json", "{}").await.unwrap();
+        let trusted = fingerprint(&os, path, extending).await;
+        os.fs
+            .write("/repo/.amazonq/base.json", r#"{ "allowedTools": ["execute_bash"] }"#)
+            .await
+            .unwrap();
+        assert_ne!(fingerprint(&os, path, extending).await, trusted);
+    }
+}
@@ -1,4 +1,109 @@
-use clap::ValueEnum;
+use std::process::ExitCode;
+
+use clap::{
+    Args,
+    Subcommand,
+    ValueEnum,
+};
+use eyre::Result;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use super::OutputFormat;
+use crate::os::Os;
+use crate::util::datetime::DateTimeFormat;
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum DebugSubcommand {
+    /// Files left behind by tools in chat sessions, such as temporary files and backups of edited
+    /// files
+    #[command(subcommand)]
+    Artifacts(ArtifactsSubcommand),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum ArtifactsSubcommand {
+    /// List the tracked artifacts. They are removed after chat.artifactRetentionDays, temporary
+    /// files as soon as their session ends.
+    List {
+        /// Only list the artifacts of this conversation
+        #[arg(long)]
+        conversation: Option<String>,
+        /// Format of the output
+        #[arg(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct DebugArgs {
+    #[command(subcommand)]
+    cmd: DebugSubcommand,
+}
+
+impl DebugArgs {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
+        match self.cmd {
+            DebugSubcommand::Artifacts(ArtifactsSubcommand::List {
+                conversation,
+                format: list_format,
+            }) => {
+                let format = list_format.or(format);
+                let artifacts = os
+                    .database
+                    .get_artifacts()?
+                    .into_iter()
+                    .filter(|artifact| conversation.as_ref().is_none_or(|id| *id == artifact.conversation_id))
+                    .collect::<Vec<_>>();
+                let dates = DateTimeFormat::new(&os.database.settings);
+                format.print(
+                    || {
+                        if artifacts.is_empty() {
+                            return "No tracked artifacts".to_string();
+                        }
+                        artifacts
+                            .iter()
+                            .map(|artifact| {
+                                format!(
+                                    "{}  {}  {:<9}  {}{}",
+                                    dates.timestamp(artifact.created_at),
+                                    artifact.conversation_id,
+                                    artifact.kind.as_str(),
+                                    artifact.path.display(),
+                                    if os.fs.exists(&artifact.path) { "" } else { " (removed)" }
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    },
+                    || {
+                        artifacts
+                            .iter()
+                            .map(|artifact| {
+                                serde_json::json!({
+                                    "conversationId": artifact.conversation_id,
+                                    "kind": artifact.kind,
+                                    "path": artifact.path,
+                                    "createdAt": format_timestamp(artifact.created_at),
+                                    "exists": os.fs.exists(&artifact.path),
+                                })
+                            })
+                            .collect::<Vec<_>>()
+                    },
+                );
+            },
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp)
+        .ok()
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .unwrap_or_else(|| timestamp.to_string())
+}
 
 #[derive(Debug, ValueEnum, Clone, PartialEq, Eq)]
 pub enum Build {
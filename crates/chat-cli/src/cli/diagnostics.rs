@@ -35,8 +35,9 @@ pub struct DiagnosticArgs {
 }
 
 impl DiagnosticArgs {
-    pub async fn execute(&self, os: &Os) -> Result<ExitCode> {
-        let spinner = if stdout().is_terminal() {
+    pub async fn execute(&self, os: &Os, format: OutputFormat) -> Result<ExitCode> {
+        let format = self.format.or(format);
+        let spinner = if stdout().is_terminal() && !format.is_json() {
             Some(Spinner::new(Spinners::Dots, "Generating...".into()))
         } else {
             None
@@ -59,7 +60,7 @@ impl DiagnosticArgs {
             println!();
         }
 
-        self.format.print(
+        format.print(
             || diagnostics.user_readable().expect("Failed to run user_readable()"),
             || &diagnostics,
         );
@@ -0,0 +1,450 @@
+//! `q git resolve`, a guided workflow for merge conflicts. Each conflict is shown with both sides
+//! and what surrounds it, the model proposes a resolution that is previewed as a diff, and files
+//! are staged once all of their conflicts are resolved.
+
+use std::io::{
+    IsTerminal,
+    Write,
+};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{
+    Args,
+    Subcommand,
+};
+use crossterm::style::Stylize;
+use eyre::{
+    Result,
+    bail,
+};
+use serde::Deserialize;
+use similar::TextDiff;
+
+use crate::cli::agent::Agents;
+use crate::cli::chat::oneshot;
+use crate::cli::git_hooks::git;
+use crate::os::Os;
+use crate::util::theme::theme;
+use crate::util::{
+    CLI_BINARY_NAME,
+    choose,
+};
+
+const RESOLVE_PROMPT: &str = "You are resolving a merge conflict. Combine the changes of both sides so that the intent of each is kept, and only drop a change when the other side supersedes it. Respond with only a JSON object of the form {\"resolution\": \"the lines that replace the whole conflict, without conflict markers\", \"explanation\": \"one sentence on how the sides were combined\"}.";
+
+/// Lines of the file shown around a conflict.
+const CONTEXT_LINES: usize = 3;
+/// Lines of the file around a conflict that are sent to the model.
+const PROMPT_CONTEXT_LINES: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct GitArgs {
+    #[command(subcommand)]
+    cmd: GitSubcommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum GitSubcommand {
+    /// Resolve merge conflicts one at a time, with resolutions proposed by Amazon Q
+    Resolve {
+        /// Only resolve the conflicts in these files
+        paths: Vec<PathBuf>,
+        /// The agent whose context is used to propose resolutions
+        #[arg(long)]
+        agent: Option<String>,
+    },
+}
+
+/// A file split at its conflict markers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Conflict(Conflict),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Conflict {
+    /// Line the conflict starts at, from 1
+    line: usize,
+    ours_label: String,
+    theirs_label: String,
+    ours: String,
+    /// The common ancestor, present with the diff3 and zdiff3 conflict styles
+    base: Option<String>,
+    theirs: String,
+    /// The conflict as it is written in the file, markers included
+    raw: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Proposal {
+    resolution: String,
+    #[serde(default)]
+    explanation: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Choice {
+    Accept,
+    Ours,
+    Theirs,
+    Skip,
+    Stop,
+}
+
+impl Choice {
+    fn label(&self) -> &'static str {
+        match self {
+            Choice::Accept => "Accept the proposed resolution",
+            Choice::Ours => "Keep ours",
+            Choice::Theirs => "Keep theirs",
+            Choice::Skip => "Skip this conflict",
+            Choice::Stop => "Stop resolving",
+        }
+    }
+}
+
+impl GitArgs {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        match self.cmd {
+            GitSubcommand::Resolve { paths, agent } => resolve(os, &paths, agent.as_deref()).await,
+        }
+    }
+}
+
+async fn resolve(os: &mut Os, only: &[PathBuf], agent: Option<&str>) -> Result<ExitCode> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        bail!("{CLI_BINARY_NAME} git resolve asks before applying each resolution, so it has to be run in a terminal");
+    }
+    let mut stderr = std::io::stderr();
+    let cwd = os.env.current_dir()?;
+    let root = PathBuf::from(git(os, &["rev-parse", "--show-toplevel"]).await?.trim());
+    let only = only.iter().map(|path| cwd.join(path)).collect::<Vec<_>>();
+    let files = git(os, &["diff", "--name-only", "--diff-filter=U"])
+        .await?
+        .lines()
+        .map(|path| root.join(path))
+        .filter(|path| only.is_empty() || only.iter().any(|only| path.starts_with(only)))
+        .collect::<Vec<_>>();
+    if files.is_empty() {
+        writeln!(stderr, "No conflicted files")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let agents = Agents::load(os, agent, true, &mut std::io::sink()).await;
+    let mut resolved_files = 0;
+    'files: for path in &files {
+        let display_path = path.strip_prefix(&cwd).unwrap_or(path).display().to_string();
+        let contents = os.fs.read_to_string(path).await?;
+        let mut segments = parse_conflicts(&contents)?;
+        let conflicts = segments
+            .iter()
+            .filter(|segment| matches!(segment, Segment::Conflict(_)))
+            .count();
+        if conflicts == 0 {
+            writeln!(stderr, "{display_path} has no conflict markers left")?;
+            continue;
+        }
+
+        let mut stop = false;
+        let mut number = 0;
+        for index in 0..segments.len() {
+            let Segment::Conflict(conflict) = &segments[index] else {
+                continue;
+            };
+            number += 1;
+            let (before, after) = context(&segments, index);
+            writeln!(
+                stderr,
+                "\n{} {}",
+                format!("{display_path}:{}", conflict.line).bold(),
+                format!("(conflict {number} of {conflicts})").with(theme().secondary)
+            )?;
+            print_conflict(
+                &mut stderr,
+                conflict,
+                last_lines(&before, CONTEXT_LINES),
+                first_lines(&after, CONTEXT_LINES),
+            )?;
+
+            writeln!(stderr, "{}", "Proposing a resolution...".with(theme().secondary))?;
+            let prompt = resolve_prompt(&display_path, conflict, &before, &after);
+            let proposal = match oneshot::ask(os, agents.clone(), None, prompt).await {
+                Ok(response) => oneshot::parse_json::<Proposal>(&response).map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+            let mut choices = vec![Choice::Ours, Choice::Theirs, Choice::Skip, Choice::Stop];
+            let resolution = match proposal {
+                Ok(proposal) => {
+                    let resolution = with_line_ending(proposal.resolution, &conflict.raw);
+                    if !proposal.explanation.is_empty() {
+                        writeln!(stderr, "\n{}", proposal.explanation)?;
+                    }
+                    print_diff(&mut stderr, &conflict.raw, &resolution)?;
+                    choices.insert(0, Choice::Accept);
+                    Some(resolution)
+                },
+                Err(err) => {
+                    writeln!(
+                        stderr,
+                        "{} {err}",
+                        "Could not propose a resolution:".with(theme().warning)
+                    )?;
+                    None
+                },
+            };
+
+            let labels = choices.iter().map(Choice::label).collect::<Vec<_>>();
+            let choice = match choose("How should the conflict be resolved?", &labels)? {
+                Some(index) => choices[index],
+                None => Choice::Stop,
+            };
+            let replacement = match choice {
+                Choice::Accept => resolution,
+                Choice::Ours => Some(conflict.ours.clone()),
+                Choice::Theirs => Some(conflict.theirs.clone()),
+                Choice::Skip => None,
+                Choice::Stop => {
+                    stop = true;
+                    None
+                },
+            };
+            if let Some(replacement) = replacement {
+                segments[index] = Segment::Text(replacement);
+            }
+            if stop {
+                break;
+            }
+        }
+
+        let remaining = segments
+            .iter()
+            .filter(|segment| matches!(segment, Segment::Conflict(_)))
+            .count();
+        if remaining < conflicts {
+            os.fs.write(path, join_segments(&segments)).await?;
+        }
+        if remaining == 0 {
+            git(os, &["add", "--", &path.to_string_lossy()]).await?;
+            resolved_files += 1;
+            writeln!(
+                stderr,
+                "{} Resolved and staged {display_path}",
+                "✔".with(theme().success)
+            )?;
+        } else {
+            writeln!(stderr, "{display_path} still has {remaining} conflict(s)")?;
+        }
+        if stop {
+            break 'files;
+        }
+    }
+
+    writeln!(stderr, "\nResolved {resolved_files} of {} file(s)", files.len())?;
+    if resolved_files == files.len() {
+        writeln!(
+            stderr,
+            "Review the result, then continue with {} or {}",
+            "git commit".with(theme().success),
+            "git rebase --continue".with(theme().success)
+        )?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Splits `contents` at the conflict markers git writes into conflicted files.
+fn parse_conflicts(contents: &str) -> Result<Vec<Segment>> {
+    enum Part {
+        Ours,
+        Base,
+        Theirs,
+    }
+
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut conflict: Option<(Conflict, Part)> = None;
+    for (index, line) in contents.split_inclusive('\n').enumerate() {
+        let marker = line.trim_end_matches(['\r', '\n']);
+        match conflict.as_mut() {
+            None => match marker.strip_prefix("<<<<<<<") {
+                Some(label) => {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                    conflict = Some((
+                        Conflict {
+                            line: index + 1,
+                            ours_label: label.trim().to_string(),
+                            theirs_label: String::new(),
+                            ours: String::new(),
+                            base: None,
+                            theirs: String::new(),
+                            raw: line.to_string(),
+                        },
+                        Part::Ours,
+                    ));
+                },
+                None => text.push_str(line),
+            },
+            Some((current, part)) => {
+                current.raw.push_str(line);
+                if marker.starts_with("|||||||") {
+                    current.base = Some(String::new());
+                    *part = Part::Base;
+                } else if marker == "=======" {
+                    *part = Part::Theirs;
+                } else if let Some(label) = marker.strip_prefix(">>>>>>>") {
+                    current.theirs_label = label.trim().to_string();
+                    let (current, _) = conflict.take().expect("a conflict is being parsed");
+                    segments.push(Segment::Conflict(current));
+                } else {
+                    match part {
+                        Part::Ours => current.ours.push_str(line),
+                        Part::Base => current.base.get_or_insert_default().push_str(line),
+                        Part::Theirs => current.theirs.push_str(line),
+                    }
+                }
+            },
+        }
+    }
+    if let Some((conflict, _)) = conflict {
+        bail!("The conflict at line {} has no end marker", conflict.line);
+    }
+    segments.push(Segment::Text(text));
+    segments.retain(|segment| !matches!(segment, Segment::Text(text) if text.is_empty()));
+    Ok(segments)
+}
+
+/// The file as it is once the resolved conflicts have been replaced.
+fn join_segments(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.as_str(),
+            Segment::Conflict(conflict) => conflict.raw.as_str(),
+        })
+        .collect()
+}
+
+/// The text before and after the segment at `index`, with other conflicts as they are written.
+fn context(segments: &[Segment], index: usize) -> (String, String) {
+    (join_segments(&segments[..index]), join_segments(&segments[index + 1..]))
+}
+
+fn last_lines(text: &str, count: usize) -> &str {
+    let start = text
+        .trim_end_matches('\n')
+        .rmatch_indices('\n')
+        .nth(count.saturating_sub(1))
+        .map_or(0, |(index, _)| index + 1);
+    &text[start..]
+}
+
+fn first_lines(text: &str, count: usize) -> &str {
+    let end = text
+        .match_indices('\n')
+        .nth(count.saturating_sub(1))
+        .map_or(text.len(), |(index, _)| index + 1);
+    &text[..end]
+}
+
+/// Resolutions end with a line break when the conflict did, so that they don't run into the next
+/// line.
+fn with_line_ending(mut resolution: String, raw: &str) -> String {
+    if !resolution.is_empty() && !resolution.ends_with('\n') && raw.ends_with('\n') {
+        resolution.push('\n');
+    }
+    resolution
+}
+
+fn resolve_prompt(path: &str, conflict: &Conflict, before: &str, after: &str) -> String {
+    let mut prompt = format!(
+        "{RESOLVE_PROMPT}\n\nFile: {path}\n\nBefore the conflict:\n```\n{}```\n\nOurs ({}):\n```\n{}```\n",
+        last_lines(before, PROMPT_CONTEXT_LINES),
+        conflict.ours_label,
+        conflict.ours
+    );
+    if let Some(base) = &conflict.base {
+        prompt.push_str(&format!("\nCommon ancestor:\n```\n{base}```\n"));
+    }
+    prompt.push_str(&format!(
+        "\nTheirs ({}):\n```\n{}```\n\nAfter the conflict:\n```\n{}```",
+        conflict.theirs_label,
+        conflict.theirs,
+        first_lines(after, PROMPT_CONTEXT_LINES)
+    ));
+    prompt
+}
+
+fn print_conflict(output: &mut impl Write, conflict: &Conflict, before: &str, after: &str) -> Result<()> {
+    write!(output, "{}", before.with(theme().secondary))?;
+    writeln!(
+        output,
+        "{}",
+        format!("── ours ({}) ──", conflict.ours_label).with(theme().label)
+    )?;
+    write!(output, "{}", conflict.ours)?;
+    if let Some(base) = &conflict.base {
+        writeln!(output, "{}", "── common ancestor ──".with(theme().secondary))?;
+        write!(output, "{base}")?;
+    }
+    writeln!(
+        output,
+        "{}",
+        format!("── theirs ({}) ──", conflict.theirs_label).with(theme().info)
+    )?;
+    write!(output, "{}", conflict.theirs)?;
+    writeln!(output, "{}", "──".with(theme().secondary))?;
+    write!(output, "{}", after.with(theme().secondary))?;
+    Ok(())
+}
+
+fn print_diff(output: &mut impl Write, raw: &str, resolution: &str) -> Result<()> {
+    writeln!(output, "\nProposed resolution:")?;
+    for line in TextDiff::from_lines(raw, resolution).unified_diff().to_string().lines() {
+        let line = match line.chars().next() {
+            Some('+') => line.with(theme().diff_add),
+            Some('-') => line.with(theme().diff_remove),
+            Some('@') => line.with(theme().secondary),
+            _ => line.stylize(),
+        };
+        writeln!(output, "{line}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFLICTED: &str = "fn main() {\n<<<<<<< HEAD\n    println!(\"hello\");\n||||||| base\n    println!(\"hi\");\n=======\n    println!(\"hi there\");\n>>>>>>> feature\n}\n";
+
+    #[test]
+    fn test_parse_conflicts() {
+        let segments = parse_conflicts(CONFLICTED).unwrap();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], Segment::Text("fn main() {\n".to_string()));
+        let Segment::Conflict(conflict) = &segments[1] else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(conflict.line, 2);
+        assert_eq!(conflict.ours_label, "HEAD");
+        assert_eq!(conflict.theirs_label, "feature");
+        assert_eq!(conflict.ours, "    println!(\"hello\");\n");
+        assert_eq!(conflict.base.as_deref(), Some("    println!(\"hi\");\n"));
+        assert_eq!(conflict.theirs, "    println!(\"hi there\");\n");
+        assert_eq!(join_segments(&segments), CONFLICTED);
+
+        assert!(parse_conflicts("<<<<<<< HEAD\na\n=======\n").is_err());
+    }
+
+    #[test]
+    fn test_context_lines() {
+        let text = "a\nb\nc\nd\n";
+        assert_eq!(last_lines(text, 2), "c\nd\n");
+        assert_eq!(last_lines(text, 10), text);
+        assert_eq!(first_lines(text, 2), "a\nb\n");
+        assert_eq!(first_lines(text, 10), text);
+        assert_eq!(with_line_ending("x".to_string(), "raw\n"), "x\n");
+        assert_eq!(with_line_ending(String::new(), "raw\n"), "");
+    }
+}
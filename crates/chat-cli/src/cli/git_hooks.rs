@@ -0,0 +1,697 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{
+    Args,
+    Subcommand,
+    ValueEnum,
+};
+use crossterm::style::Stylize;
+use eyre::{
+    Result,
+    bail,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+use crate::cli::agent::Agents;
+use crate::cli::chat::oneshot;
+use crate::os::Os;
+use crate::util::CLI_BINARY_NAME;
+
+/// Marks hooks written by `q hooks install`, so they can be told apart from hooks of other tools.
+const HOOK_MARKER: &str = "# Installed by q hooks install";
+
+const REVIEW_PROMPT: &str = "You are reviewing a change before it is committed. Review the diff below for bugs, security issues and clear violations of the conventions of the project, ignoring matters of style. Respond with only a JSON object of the form {\"findings\": [{\"severity\": \"low|medium|high|critical\", \"file\": \"path\", \"line\": 1, \"message\": \"what is wrong and how to fix it\"}]}, with an empty list if there is nothing to report.";
+
+/// Object name git uses in pre-push hooks for refs that do not exist.
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
+/// Reviews older than this are removed from the cache.
+const CACHE_MAX_AGE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct HooksArgs {
+    #[command(subcommand)]
+    cmd: HooksSubcommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum HooksSubcommand {
+    /// Install a git hook in the current repository
+    Install {
+        hook: HookKind,
+        /// The git hook to install it as
+        #[arg(long, value_enum, default_value_t)]
+        stage: GitHookStage,
+        #[command(flatten)]
+        options: ReviewOptions,
+        /// Replace a hook that was not installed by this command
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Remove a git hook installed with `q hooks install`
+    Uninstall {
+        hook: HookKind,
+        /// The git hook it was installed as
+        #[arg(long, value_enum, default_value_t)]
+        stage: GitHookStage,
+    },
+    /// Run a hook, as the installed git hook does. In CI, pass --base to review the changes since
+    /// the target branch and --format to annotate the pull or merge request with the findings.
+    Run {
+        hook: HookKind,
+        #[arg(long, value_enum, default_value_t)]
+        stage: GitHookStage,
+        /// Review the changes since this revision instead of those of the git hook stage
+        #[arg(long, value_name = "REV")]
+        base: Option<String>,
+        /// Format of the findings printed to stdout
+        #[arg(long, short, value_enum, default_value_t)]
+        format: ReviewFormat,
+        #[command(flatten)]
+        options: ReviewOptions,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HookKind {
+    /// Review the change with an agent and block it on findings above a severity threshold
+    PreCommitReview,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum GitHookStage {
+    /// Review the staged changes of each commit
+    #[default]
+    PreCommit,
+    /// Review the commits being pushed
+    PrePush,
+}
+
+impl GitHookStage {
+    fn hook_name(&self) -> &'static str {
+        match self {
+            Self::PreCommit => "pre-commit",
+            Self::PrePush => "pre-push",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReviewFormat {
+    /// Human readable findings on stderr
+    #[default]
+    Text,
+    /// GitHub Actions workflow commands, which annotate the files changed in the pull request
+    GithubAnnotations,
+    /// A GitLab code quality report, to be saved as the `codequality` artifact of the job
+    GitlabCodequality,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// The GitHub workflow command that annotates a finding of this severity.
+    fn github_command(&self) -> &'static str {
+        match self {
+            Self::Low => "notice",
+            Self::Medium => "warning",
+            Self::High | Self::Critical => "error",
+        }
+    }
+
+    /// The severity of a GitLab code quality issue for a finding of this severity.
+    fn gitlab_severity(&self) -> &'static str {
+        match self {
+            Self::Low => "minor",
+            Self::Medium => "major",
+            Self::High => "critical",
+            Self::Critical => "blocker",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct ReviewOptions {
+    /// Agent whose context is used for the review
+    #[arg(long)]
+    agent: Option<String>,
+    /// Block on findings of at least this severity
+    #[arg(long, value_enum, default_value_t = Severity::High)]
+    threshold: Severity,
+    /// Allow the change without a review if it takes longer than this many seconds
+    #[arg(long, default_value_t = 60, value_name = "SECONDS")]
+    timeout: u64,
+    /// Only review the first this many bytes of the diff
+    #[arg(long, default_value_t = 100_000, value_name = "BYTES")]
+    max_diff_bytes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Finding {
+    severity: Severity,
+    #[serde(default)]
+    file: Option<String>,
+    #[serde(default)]
+    line: Option<u64>,
+    message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Review {
+    findings: Vec<Finding>,
+}
+
+impl HooksArgs {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        let mut stderr = std::io::stderr();
+
+        match self.cmd {
+            HooksSubcommand::Install {
+                hook: HookKind::PreCommitReview,
+                stage,
+                options,
+                force,
+            } => {
+                let path = hook_path(os, stage).await?;
+                if os.fs.exists(&path) && !force && !os.fs.read_to_string(&path).await?.contains(HOOK_MARKER) {
+                    bail!(
+                        "{} already exists and was not installed by {CLI_BINARY_NAME}, use --force to replace it",
+                        path.display()
+                    );
+                }
+                if let Some(parent) = path.parent() {
+                    os.fs.create_dir_all(parent).await?;
+                }
+                os.fs.write(&path, hook_script(stage, &options)).await?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    os.fs
+                        .set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+                        .await?;
+                }
+
+                writeln!(
+                    stderr,
+                    "Installed the pre-commit-review hook at {}. Skip it for a single {} with {}",
+                    path.display().to_string().green(),
+                    match stage {
+                        GitHookStage::PreCommit => "commit",
+                        GitHookStage::PrePush => "push",
+                    },
+                    "--no-verify".green()
+                )?;
+                Ok(ExitCode::SUCCESS)
+            },
+            HooksSubcommand::Uninstall {
+                hook: HookKind::PreCommitReview,
+                stage,
+            } => {
+                let path = hook_path(os, stage).await?;
+                if !os.fs.exists(&path) || !os.fs.read_to_string(&path).await?.contains(HOOK_MARKER) {
+                    bail!("No hook installed by {CLI_BINARY_NAME} was found at {}", path.display());
+                }
+                os.fs.remove_file(&path).await?;
+                writeln!(stderr, "Removed {}", path.display())?;
+                Ok(ExitCode::SUCCESS)
+            },
+            HooksSubcommand::Run {
+                hook: HookKind::PreCommitReview,
+                stage,
+                base,
+                format,
+                options,
+            } => {
+                let findings = review(os, stage, base.as_deref(), &options, &mut stderr)
+                    .await?
+                    .unwrap_or_default();
+                print_findings(
+                    &findings,
+                    format,
+                    options.threshold,
+                    &mut std::io::stdout(),
+                    &mut stderr,
+                )?;
+
+                let blocking = findings.iter().filter(|f| f.severity >= options.threshold).count();
+                if blocking == 0 {
+                    return Ok(ExitCode::SUCCESS);
+                }
+                writeln!(
+                    stderr,
+                    "\n{} found {blocking} issue(s) of {} severity or higher. Fix them, or skip the review with {}",
+                    CLI_BINARY_NAME.bold(),
+                    options.threshold,
+                    "--no-verify".green()
+                )?;
+                Ok(ExitCode::FAILURE)
+            },
+        }
+    }
+}
+
+/// Reviews the changes, returning [None] when the review could not be completed. Anything that
+/// prevents the review from completing lets the change through with a warning, so that a hook can
+/// never leave a repository stuck.
+async fn review(
+    os: &mut Os,
+    stage: GitHookStage,
+    base: Option<&str>,
+    options: &ReviewOptions,
+    stderr: &mut impl Write,
+) -> Result<Option<Vec<Finding>>> {
+    let diff = match (base, stage) {
+        (Some(base), _) => git(os, &["diff", "--no-color", "--no-ext-diff", &format!("{base}...HEAD")]).await?,
+        (None, GitHookStage::PreCommit) => git(os, &["diff", "--cached", "--no-color", "--no-ext-diff"]).await?,
+        (None, GitHookStage::PrePush) => {
+            let mut refs = String::new();
+            tokio::io::stdin().read_to_string(&mut refs).await?;
+            pushed_diff(os, &refs).await?
+        },
+    };
+    if diff.trim().is_empty() {
+        return Ok(Some(Vec::new()));
+    }
+    let diff = truncate(&diff, options.max_diff_bytes);
+
+    let cache_dir = PathBuf::from(git(os, &["rev-parse", "--git-path", "q-review-cache"]).await?.trim());
+    let cache_dir = os.env.current_dir()?.join(cache_dir);
+    let key = hex::encode(Sha256::digest(format!(
+        "{}\0{}",
+        options.agent.as_deref().unwrap_or_default(),
+        diff
+    )));
+    let cache_path = cache_dir.join(format!("{key}.json"));
+
+    if let Ok(cached) = os.fs.read_to_string(&cache_path).await {
+        return Ok(Some(serde_json::from_str::<Review>(&cached)?.findings));
+    }
+
+    if !crate::auth::is_logged_in(&mut os.database).await {
+        writeln!(stderr, "Skipping the review: not logged in to {CLI_BINARY_NAME}")?;
+        return Ok(None);
+    }
+
+    writeln!(stderr, "Reviewing the change...")?;
+    let agents = Agents::load(os, options.agent.as_deref(), true, &mut std::io::sink()).await;
+    let prompt = format!("{REVIEW_PROMPT}\n\n```diff\n{diff}\n```");
+    let response = match tokio::time::timeout(
+        Duration::from_secs(options.timeout),
+        oneshot::ask(os, agents, None, prompt),
+    )
+    .await
+    {
+        Ok(Ok(response)) => response,
+        Ok(Err(err)) => {
+            writeln!(stderr, "Skipping the review: {err}")?;
+            return Ok(None);
+        },
+        Err(_) => {
+            writeln!(stderr, "Skipping the review: no response after {}s", options.timeout)?;
+            return Ok(None);
+        },
+    };
+    let findings = match parse_findings(&response) {
+        Ok(findings) => findings,
+        Err(err) => {
+            warn!(?err, ?response, "failed to parse the review");
+            writeln!(stderr, "Skipping the review: the response could not be parsed")?;
+            return Ok(None);
+        },
+    };
+
+    prune_cache(os, &cache_dir).await;
+    os.fs.create_dir_all(&cache_dir).await?;
+    os.fs
+        .write(
+            &cache_path,
+            serde_json::to_string(&Review {
+                findings: findings.clone(),
+            })?,
+        )
+        .await?;
+    Ok(Some(findings))
+}
+
+fn print_findings(
+    findings: &[Finding],
+    format: ReviewFormat,
+    threshold: Severity,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> Result<()> {
+    match format {
+        ReviewFormat::Text => {
+            for finding in findings {
+                let location = match (&finding.file, finding.line) {
+                    (Some(file), Some(line)) => format!("{file}:{line}: "),
+                    (Some(file), None) => format!("{file}: "),
+                    _ => String::new(),
+                };
+                let severity = format!("[{}]", finding.severity);
+                let severity = match finding.severity >= threshold {
+                    true => severity.red(),
+                    false => severity.yellow(),
+                };
+                writeln!(stderr, "{severity} {location}{}", finding.message)?;
+            }
+        },
+        ReviewFormat::GithubAnnotations => {
+            for finding in findings {
+                let mut properties = vec![format!(
+                    "title={}",
+                    escape_github_property(&format!("{CLI_BINARY_NAME} review: {}", finding.severity))
+                )];
+                if let Some(file) = &finding.file {
+                    properties.push(format!("file={}", escape_github_property(file)));
+                    if let Some(line) = finding.line {
+                        properties.push(format!("line={line}"));
+                    }
+                }
+                writeln!(
+                    stdout,
+                    "::{} {}::{}",
+                    finding.severity.github_command(),
+                    properties.join(","),
+                    escape_github_data(&finding.message)
+                )?;
+            }
+        },
+        ReviewFormat::GitlabCodequality => {
+            let issues = findings
+                .iter()
+                .map(|finding| {
+                    let path = finding.file.as_deref().unwrap_or(".");
+                    let line = finding.line.unwrap_or(1);
+                    serde_json::json!({
+                        "description": finding.message,
+                        "check_name": format!("{CLI_BINARY_NAME}-review"),
+                        "fingerprint": hex::encode(Sha256::digest(format!("{path}\0{line}\0{}", finding.message))),
+                        "severity": finding.severity.gitlab_severity(),
+                        "location": {
+                            "path": path,
+                            "lines": { "begin": line },
+                        },
+                    })
+                })
+                .collect::<Vec<_>>();
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&issues)?)?;
+        },
+    }
+    Ok(())
+}
+
+/// Escapes the message of a GitHub workflow command.
+fn escape_github_data(data: &str) -> String {
+    data.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escapes a property value of a GitHub workflow command.
+fn escape_github_property(value: &str) -> String {
+    escape_github_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// The diff of the commits being pushed, given the refs git passes to pre-push hooks on stdin.
+async fn pushed_diff(os: &Os, refs: &str) -> Result<String> {
+    let mut diff = String::new();
+    for line in refs.lines() {
+        let [_, local_sha, _, remote_sha] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            continue;
+        };
+        // Deleting a remote branch
+        if local_sha == NULL_SHA {
+            continue;
+        }
+        // A new branch is reviewed from where it forked off the default branch of the remote
+        let base = match remote_sha {
+            NULL_SHA => match git(os, &["merge-base", local_sha, "origin/HEAD"]).await {
+                Ok(base) => base.trim().to_string(),
+                Err(_) => continue,
+            },
+            sha => sha.to_string(),
+        };
+        diff.push_str(&git(os, &["diff", "--no-color", "--no-ext-diff", &base, local_sha]).await?);
+    }
+    Ok(diff)
+}
+
+async fn hook_path(os: &Os, stage: GitHookStage) -> Result<PathBuf> {
+    let path = git(os, &[
+        "rev-parse",
+        "--git-path",
+        &format!("hooks/{}", stage.hook_name()),
+    ])
+    .await?;
+    Ok(os.env.current_dir()?.join(path.trim()))
+}
+
+pub(super) async fn git(os: &Os, args: &[&str]) -> Result<String> {
+    let output = tokio::process::Command::new("git")
+        .args(args)
+        .current_dir(os.env.current_dir()?)
+        .output()
+        .await?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Removes cached reviews that are too old to be relevant anymore.
+async fn prune_cache(os: &Os, cache_dir: &std::path::Path) {
+    let Ok(mut entries) = os.fs.read_dir(cache_dir).await else {
+        return;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let expired = entry
+            .metadata()
+            .await
+            .ok()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > CACHE_MAX_AGE);
+        if expired {
+            os.fs.remove_file(entry.path()).await.ok();
+        }
+    }
+}
+
+fn hook_script(stage: GitHookStage, options: &ReviewOptions) -> String {
+    let mut command = format!(
+        "{CLI_BINARY_NAME} hooks run pre-commit-review --stage {} --threshold {} --timeout {} --max-diff-bytes {}",
+        stage.hook_name(),
+        options.threshold,
+        options.timeout,
+        options.max_diff_bytes
+    );
+    if let Some(agent) = &options.agent {
+        command.push_str(&format!(" --agent '{}'", agent.replace('\'', r"'\''")));
+    }
+    format!(
+        "#!/bin/sh\n{HOOK_MARKER} pre-commit-review, skip it with --no-verify\ncommand -v {CLI_BINARY_NAME} >/dev/null 2>&1 || exit 0\nexec {command}\n"
+    )
+}
+
+/// Truncates `diff` to at most `max_bytes` on a line boundary, noting that it was truncated.
+fn truncate(diff: &str, max_bytes: usize) -> String {
+    if diff.len() <= max_bytes {
+        return diff.to_string();
+    }
+    let mut end = max_bytes;
+    while !diff.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = diff[..end].rfind('\n').map_or(0, |i| i + 1);
+    format!("{}[the rest of the diff was omitted]\n", &diff[..end])
+}
+
+/// Parses the findings from the response, which may surround the JSON object with other text.
+fn parse_findings(response: &str) -> Result<Vec<Finding>> {
+    Ok(oneshot::parse_json::<Review>(response)?.findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::{
+        Cli,
+        RootSubcommand,
+    };
+
+    #[test]
+    fn test_parse_install() {
+        let cli = Cli::parse_from(["q", "hooks", "install", "pre-commit-review", "--agent", "reviewer"]);
+        assert_eq!(
+            cli.subcommand,
+            Some(RootSubcommand::Hooks(HooksArgs {
+                cmd: HooksSubcommand::Install {
+                    hook: HookKind::PreCommitReview,
+                    stage: GitHookStage::PreCommit,
+                    options: ReviewOptions {
+                        agent: Some("reviewer".to_string()),
+                        threshold: Severity::High,
+                        timeout: 60,
+                        max_diff_bytes: 100_000,
+                    },
+                    force: false,
+                },
+            }))
+        );
+    }
+
+    #[test]
+    fn test_hook_script() {
+        let options = ReviewOptions {
+            agent: Some("it's".to_string()),
+            threshold: Severity::Medium,
+            timeout: 30,
+            max_diff_bytes: 1000,
+        };
+        let script = hook_script(GitHookStage::PrePush, &options);
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains(HOOK_MARKER));
+        assert!(script.ends_with(
+            "exec q hooks run pre-commit-review --stage pre-push --threshold medium --timeout 30 --max-diff-bytes 1000 --agent 'it'\\''s'\n"
+        ));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("a\nb\n", 10), "a\nb\n");
+        assert_eq!(truncate("a\nbcd\n", 4), "a\n[the rest of the diff was omitted]\n");
+    }
+
+    #[test]
+    fn test_parse_run() {
+        let cli = Cli::parse_from([
+            "q",
+            "hooks",
+            "run",
+            "pre-commit-review",
+            "--base",
+            "origin/main",
+            "--format",
+            "github-annotations",
+        ]);
+        assert!(matches!(
+            cli.subcommand,
+            Some(RootSubcommand::Hooks(HooksArgs {
+                cmd: HooksSubcommand::Run {
+                    base: Some(_),
+                    format: ReviewFormat::GithubAnnotations,
+                    ..
+                },
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_print_findings() {
+        let findings = vec![
+            Finding {
+                severity: Severity::High,
+                file: Some("src/a,b.rs".to_string()),
+                line: Some(3),
+                message: "100% wrong\nhere".to_string(),
+            },
+            Finding {
+                severity: Severity::Low,
+                file: None,
+                line: None,
+                message: "typo".to_string(),
+            },
+        ];
+
+        let mut stdout = Vec::new();
+        print_findings(
+            &findings,
+            ReviewFormat::GithubAnnotations,
+            Severity::High,
+            &mut stdout,
+            &mut std::io::sink(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            "::error title=q review%3A high,file=src/a%2Cb.rs,line=3::100%25 wrong%0Ahere\n::notice title=q review%3A low::typo\n"
+        );
+
+        let mut stdout = Vec::new();
+        print_findings(
+            &findings,
+            ReviewFormat::GitlabCodequality,
+            Severity::High,
+            &mut stdout,
+            &mut std::io::sink(),
+        )
+        .unwrap();
+        let issues: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        assert_eq!(issues[0]["severity"], "critical");
+        assert_eq!(issues[0]["location"]["path"], "src/a,b.rs");
+        assert_eq!(issues[0]["location"]["lines"]["begin"], 3);
+        assert_eq!(issues[1]["severity"], "minor");
+        assert_eq!(issues[1]["location"]["path"], ".");
+        assert_ne!(issues[0]["fingerprint"], issues[1]["fingerprint"]);
+    }
+
+    #[test]
+    fn test_parse_findings() {
+        let response = r#"Here is the review:
+```json
+{"findings": [{"severity": "high", "file": "src/main.rs", "line": 3, "message": "unwrap on user input"}, {"severity": "low", "message": "typo"}]}
+```"#;
+        assert_eq!(parse_findings(response).unwrap(), vec![
+            Finding {
+                severity: Severity::High,
+                file: Some("src/main.rs".to_string()),
+                line: Some(3),
+                message: "unwrap on user input".to_string(),
+            },
+            Finding {
+                severity: Severity::Low,
+                file: None,
+                line: None,
+                message: "typo".to_string(),
+            },
+        ]);
+        assert_eq!(parse_findings(r#"{"findings": []}"#).unwrap(), vec![]);
+        assert!(parse_findings("looks good").is_err());
+        assert!(Severity::Critical > Severity::High);
+    }
+}
@@ -0,0 +1,188 @@
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::ExitCode;
+
+use clap::Args;
+use crossterm::style::Stylize;
+use eyre::Result;
+
+use crate::cli::agent::{
+    Agent,
+    McpServerConfig,
+    validate_agent_config,
+    validate_agent_name,
+};
+use crate::cli::chat::tool_manager::workspace_mcp_config_path;
+use crate::os::Os;
+use crate::util::{
+    CLI_BINARY_NAME,
+    directories,
+    input,
+};
+
+const RULES_FILE_NAME: &str = "project.md";
+
+const RULES_TEMPLATE: &str = "# Project rules
+
+Files under .amazonq/rules are included as context for every chat session in this workspace.
+Use them to describe conventions the assistant should follow, for example:
+
+- How to build, lint and test the project
+- Code style and naming conventions
+- Directories or files that should not be modified
+";
+
+#[derive(Clone, Debug, Args, PartialEq, Eq, Default)]
+pub struct InitArgs {
+    /// Name of the starter agent. If not provided, you will be prompted for one
+    #[arg(long, short)]
+    pub name: Option<String>,
+    /// Overwrite files that already exist
+    #[arg(long, short)]
+    pub force: bool,
+}
+
+impl InitArgs {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        let mut stderr = std::io::stderr();
+
+        let default_name = os
+            .env
+            .current_dir()?
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .filter(|name| validate_agent_name(name).is_ok())
+            .unwrap_or_else(|| "workspace".to_string());
+
+        let name = match self.name {
+            Some(name) => name,
+            None => match input("Agent name", Some(&default_name))? {
+                name if name.trim().is_empty() => default_name,
+                name => name.trim().to_string(),
+            },
+        };
+        validate_agent_name(&name)?;
+
+        let written = scaffold(os, &name, self.force).await?;
+
+        writeln!(stderr)?;
+        for (path, created) in written {
+            match created {
+                true => writeln!(stderr, "📁 Created {}", path.display())?,
+                false => writeln!(
+                    stderr,
+                    "{} {} already exists, skipping (use --force to overwrite)",
+                    "!".yellow(),
+                    path.display()
+                )?,
+            }
+        }
+        writeln!(
+            stderr,
+            "\nStart chatting with this workspace agent by running {}\n",
+            format!("{CLI_BINARY_NAME} chat --agent {name}").magenta()
+        )?;
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Writes the starter agent, the rules template and the mcp config under the workspace .amazonq
+/// directory.
+///
+/// Returns every path that was considered along with whether it was written.
+async fn scaffold(os: &Os, name: &str, force: bool) -> Result<Vec<(PathBuf, bool)>> {
+    let agent_path = directories::chat_workspace_agent_dir(os)?.join(format!("{name}.json"));
+    let rules_path = directories::chat_workspace_rules_dir(os)?.join(RULES_FILE_NAME);
+    let mcp_path = workspace_mcp_config_path(os)?;
+
+    let agent = Agent {
+        name: name.to_string(),
+        description: Some(format!("Workspace agent for {name}")),
+        ..Default::default()
+    };
+    let agent_content = agent.to_str_pretty()?;
+    validate_agent_config(&agent_content)?;
+
+    let mcp_content = serde_json::to_string_pretty(&serde_json::json!({
+        "mcpServers": McpServerConfig::default()
+    }))?;
+
+    let mut res = Vec::new();
+    for (path, content) in [
+        (agent_path, agent_content.as_str()),
+        (rules_path, RULES_TEMPLATE),
+        (mcp_path, mcp_content.as_str()),
+    ] {
+        let created = write_file(os, &path, content, force).await?;
+        res.push((path, created));
+    }
+
+    Ok(res)
+}
+
+async fn write_file(os: &Os, path: &Path, content: &str, force: bool) -> Result<bool> {
+    if os.fs.exists(path) && !force {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        os.fs.create_dir_all(parent).await?;
+    }
+    os.fs.write(path, content).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::RootSubcommand;
+    use crate::util::test::assert_parse;
+
+    #[test]
+    fn test_init_parse() {
+        assert_parse!(["init"], RootSubcommand::Init(InitArgs::default()));
+        assert_parse!(
+            ["init", "--name", "my-agent", "--force"],
+            RootSubcommand::Init(InitArgs {
+                name: Some("my-agent".to_string()),
+                force: true,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scaffold() {
+        let os = Os::new().await.unwrap();
+
+        let written = scaffold(&os, "my-agent", false).await.unwrap();
+        assert!(written.iter().all(|(_, created)| *created));
+
+        let agent_path = directories::chat_workspace_agent_dir(&os)
+            .unwrap()
+            .join("my-agent.json");
+        let agent_content = os.fs.read_to_string(&agent_path).await.unwrap();
+        validate_agent_config(&agent_content).unwrap();
+        let agent = serde_json::from_str::<Agent>(&agent_content).unwrap();
+        assert!(agent.resources.iter().any(|r| r.contains(".amazonq/rules")));
+
+        let mcp_config = McpServerConfig::load_from_file(&os, workspace_mcp_config_path(&os).unwrap())
+            .await
+            .unwrap();
+        assert!(mcp_config.mcp_servers.is_empty());
+
+        // Existing files are left untouched unless forced
+        let rules_path = directories::chat_workspace_rules_dir(&os)
+            .unwrap()
+            .join(RULES_FILE_NAME);
+        os.fs.write(&rules_path, "custom rules").await.unwrap();
+        let written = scaffold(&os, "my-agent", false).await.unwrap();
+        assert!(written.iter().all(|(_, created)| !*created));
+        assert_eq!(os.fs.read_to_string(&rules_path).await.unwrap(), "custom rules");
+
+        scaffold(&os, "my-agent", true).await.unwrap();
+        assert_eq!(os.fs.read_to_string(&rules_path).await.unwrap(), RULES_TEMPLATE);
+    }
+}
@@ -0,0 +1,295 @@
+use std::io::Read;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::ExitCode;
+
+use amzn_codewhisperer_client::types::{
+    FileContext,
+    ProgrammingLanguage,
+};
+use clap::Args;
+use eyre::{
+    Result,
+    bail,
+};
+use serde_json::json;
+
+use super::OutputFormat;
+use crate::os::Os;
+
+/// Maximum number of characters the service accepts on each side of the cursor.
+const MAX_CONTEXT_CHARS: usize = 10240;
+
+/// Generate a single code completion at a position in a file, for editor integrations.
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct InlineArgs {
+    /// The file being edited. Its name and extension are sent to the service, and its contents
+    /// unless --stdin is given.
+    file: PathBuf,
+    /// Line of the cursor, starting at 1
+    #[arg(long)]
+    line: usize,
+    /// Column of the cursor in characters, starting at 1
+    #[arg(long)]
+    column: usize,
+    /// Read the contents of the buffer from stdin instead of the file, e.g. for unsaved changes
+    #[arg(long)]
+    stdin: bool,
+    /// Only send this many lines before and after the cursor as context
+    #[arg(long, value_name = "LINES")]
+    context_lines: Option<usize>,
+    /// Programming language of the file, inferred from its extension by default
+    #[arg(long)]
+    language: Option<String>,
+    /// Maximum number of alternative completions to request
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(i32).range(1..=10))]
+    max_results: i32,
+    /// Format of the output. Plain prints the best completion, json includes the alternatives and
+    /// their code references.
+    #[arg(long, short, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+impl InlineArgs {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
+        let format = self.format.or(format);
+        let content = match self.stdin {
+            true => {
+                let mut buffer = String::new();
+                std::io::stdin().read_to_string(&mut buffer)?;
+                buffer
+            },
+            false => os.fs.read_to_string(&self.file).await?,
+        };
+
+        let (left, right) = split_at_cursor(&content, self.line, self.column, self.context_lines)?;
+        let language = self
+            .language
+            .as_deref()
+            .or_else(|| language_for_path(&self.file))
+            .unwrap_or("plaintext");
+        let filename = self
+            .file
+            .file_name()
+            .map_or_else(|| self.file.to_string_lossy(), |name| name.to_string_lossy());
+
+        let file_context = FileContext::builder()
+            .left_file_content(left)
+            .right_file_content(right)
+            .filename(filename)
+            .programming_language(ProgrammingLanguage::builder().language_name(language).build()?)
+            .build()?;
+
+        let completions = os.client.generate_completions(file_context, self.max_results).await?;
+
+        format.print(
+            || completions.first().map(|c| c.content()).unwrap_or_default().to_string(),
+            || {
+                json!({
+                    "completions": completions.iter().map(|completion| {
+                        json!({
+                            "content": completion.content(),
+                            "references": completion.references().iter().map(|reference| {
+                                json!({
+                                    "licenseName": reference.license_name(),
+                                    "repository": reference.repository(),
+                                    "url": reference.url(),
+                                })
+                            }).collect::<Vec<_>>(),
+                        })
+                    }).collect::<Vec<_>>(),
+                })
+            },
+        );
+
+        Ok(match completions.is_empty() {
+            true => ExitCode::FAILURE,
+            false => ExitCode::SUCCESS,
+        })
+    }
+}
+
+/// Splits `content` into the text before and after the 1-based `line` and `column`, keeping at
+/// most `context_lines` lines on each side of the cursor and the characters closest to it that fit
+/// within the service limit.
+fn split_at_cursor(
+    content: &str,
+    line: usize,
+    column: usize,
+    context_lines: Option<usize>,
+) -> Result<(String, String)> {
+    if line == 0 || column == 0 {
+        bail!("line and column start at 1");
+    }
+
+    let lines = content.split_inclusive('\n').collect::<Vec<_>>();
+    // The empty line after a trailing newline is a valid position too
+    let line_count = lines.len() + usize::from(content.is_empty() || content.ends_with('\n'));
+    if line > line_count {
+        bail!("line {line} is past the end of the file, which has {line_count} lines");
+    }
+
+    let cursor_line = lines.get(line - 1).copied().unwrap_or_default();
+    let line_chars = cursor_line.trim_end_matches(['\r', '\n']).chars().count();
+    if column > line_chars + 1 {
+        bail!("column {column} is past the end of line {line}, which has {line_chars} characters");
+    }
+    let split = cursor_line
+        .char_indices()
+        .nth(column - 1)
+        .map_or(cursor_line.len(), |(i, _)| i);
+
+    let first = context_lines.map_or(0, |n| (line - 1).saturating_sub(n));
+    let last = context_lines.map_or(lines.len(), |n| (line + n).min(lines.len()));
+    let mut left = lines[first..line - 1].concat();
+    left.push_str(&cursor_line[..split]);
+    let mut right = cursor_line[split..].to_string();
+    right.push_str(&lines[line.min(lines.len())..last].concat());
+
+    let left_chars = left.chars().count();
+    if left_chars > MAX_CONTEXT_CHARS {
+        left = left.chars().skip(left_chars - MAX_CONTEXT_CHARS).collect();
+    }
+    if right.chars().count() > MAX_CONTEXT_CHARS {
+        right = right.chars().take(MAX_CONTEXT_CHARS).collect();
+    }
+
+    Ok((left, right))
+}
+
+/// The name the service uses for the language of `path`, based on its extension.
+fn language_for_path(path: &Path) -> Option<&'static str> {
+    Some(match path.extension()?.to_str()? {
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "java" => "java",
+        "cs" => "csharp",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "go" => "go",
+        "kt" | "kts" => "kotlin",
+        "php" => "php",
+        "rb" => "ruby",
+        "rs" => "rust",
+        "scala" => "scala",
+        "sh" | "bash" | "zsh" => "shell",
+        "sql" => "sql",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "vue" => "vue",
+        "tf" | "hcl" => "tf",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::{
+        Cli,
+        RootSubcommand,
+    };
+
+    #[test]
+    fn test_split_at_cursor() {
+        let content = "fn main() {\n    let x = 1;\n}\n";
+        assert_eq!(
+            split_at_cursor(content, 2, 9, None).unwrap(),
+            ("fn main() {\n    let ".to_string(), "x = 1;\n}\n".to_string())
+        );
+        // End of a line and of the file
+        assert_eq!(
+            split_at_cursor(content, 2, 15, None).unwrap(),
+            ("fn main() {\n    let x = 1;".to_string(), "\n}\n".to_string())
+        );
+        assert_eq!(
+            split_at_cursor("abc", 1, 4, None).unwrap(),
+            ("abc".to_string(), String::new())
+        );
+        assert_eq!(split_at_cursor("", 1, 1, None).unwrap(), (String::new(), String::new()));
+        // Columns count characters rather than bytes
+        assert_eq!(
+            split_at_cursor("héllo", 1, 3, None).unwrap(),
+            ("hé".to_string(), "llo".to_string())
+        );
+
+        assert!(split_at_cursor(content, 0, 1, None).is_err());
+        assert_eq!(
+            split_at_cursor(content, 4, 1, None).unwrap(),
+            (content.to_string(), String::new())
+        );
+        assert!(split_at_cursor(content, 5, 1, None).is_err());
+        assert!(split_at_cursor(content, 1, 13, None).is_err());
+    }
+
+    #[test]
+    fn test_split_at_cursor_context_lines() {
+        let content = "1\n2\n3\n4\n5\n";
+        assert_eq!(
+            split_at_cursor(content, 3, 1, Some(1)).unwrap(),
+            ("2\n".to_string(), "3\n4\n".to_string())
+        );
+        assert_eq!(
+            split_at_cursor(content, 1, 2, Some(0)).unwrap(),
+            ("1".to_string(), "\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = [
+            "q",
+            "inline",
+            "src/main.rs",
+            "--line",
+            "3",
+            "--column",
+            "5",
+            "--stdin",
+            "-f",
+            "json",
+        ];
+        assert_eq!(
+            Cli::parse_from(args).subcommand,
+            Some(RootSubcommand::Inline(InlineArgs {
+                file: PathBuf::from("src/main.rs"),
+                line: 3,
+                column: 5,
+                stdin: true,
+                context_lines: None,
+                language: None,
+                max_results: 1,
+                format: OutputFormat::Json,
+            }))
+        );
+        assert!(
+            Cli::try_parse_from([
+                "q",
+                "inline",
+                "a.rs",
+                "--line",
+                "1",
+                "--column",
+                "1",
+                "--max-results",
+                "0"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_language_for_path() {
+        assert_eq!(language_for_path(Path::new("src/main.rs")), Some("rust"));
+        assert_eq!(language_for_path(Path::new("app.tsx")), Some("tsx"));
+        assert_eq!(language_for_path(Path::new("Makefile")), None);
+        assert_eq!(language_for_path(Path::new("notes.txt")), None);
+    }
+}
@@ -0,0 +1,38 @@
+use std::process::ExitCode;
+
+use clap::{
+    Args,
+    Subcommand,
+};
+use eyre::Result;
+
+use crate::cli::chat::{
+    E2eArgs,
+    serve_mcp_fixture,
+};
+use crate::os::Os;
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum InternalSubcommand {
+    /// Run the chat loop against a mock backend with a suite of scripted conversations and write
+    /// a JUnit report, to smoke test a build without credentials
+    E2e(E2eArgs),
+    /// Serve the MCP server the e2e suites use as a fixture on stdin and stdout
+    McpFixture,
+}
+
+/// Commands for testing builds, which are not meant to be run by users
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct InternalArgs {
+    #[command(subcommand)]
+    cmd: InternalSubcommand,
+}
+
+impl InternalArgs {
+    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+        match self.cmd {
+            InternalSubcommand::E2e(args) => args.execute(os).await,
+            InternalSubcommand::McpFixture => serve_mcp_fixture().await,
+        }
+    }
+}
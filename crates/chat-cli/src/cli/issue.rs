@@ -1,12 +1,41 @@
+use std::collections::HashMap;
 use std::process::ExitCode;
 
-use clap::Args;
-use eyre::Result;
+use clap::{
+    Args,
+    Subcommand,
+};
+use eyre::{
+    Result,
+    bail,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use url::Url;
 
-use crate::os::Os;
+use super::OutputFormat;
+use crate::cli::agent::Agents;
+use crate::cli::chat::oneshot;
+use crate::os::{
+    Env,
+    Os,
+};
+
+/// Maximum number of candidate files from the local search that are given to the model.
+const MAX_CANDIDATES: usize = 10;
+
+/// Search terms that match more files than this are too generic to point at the cause.
+const MAX_FILES_PER_TERM: usize = 25;
+
+const TRIAGE_PROMPT: &str = "Triage the issue below for the maintainers of this repository. Use the candidate files, which were found by searching the repository for terms in the issue, where they are relevant. Respond with only a JSON object of the form {\"summary\": \"what is reported, in a few sentences\", \"likely_files\": [{\"path\": \"path\", \"reason\": \"why it is likely involved\"}], \"next_steps\": [\"step\"]}.";
 
 #[derive(Clone, Debug, Args, PartialEq, Eq)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct IssueArgs {
+    #[command(subcommand)]
+    cmd: Option<IssueSubcommand>,
     /// Force issue creation
     #[arg(long, short = 'f')]
     force: bool,
@@ -14,8 +43,49 @@ pub struct IssueArgs {
     description: Vec<String>,
 }
 
+#[derive(Clone, Debug, Subcommand, PartialEq, Eq)]
+pub enum IssueSubcommand {
+    /// Summarize an issue or bug report and point out the code it most likely concerns
+    Summarize {
+        /// A file containing the report, or the URL of a GitHub or GitLab issue. Private
+        /// repositories need a token in GITHUB_TOKEN or GITLAB_TOKEN, which is only sent to
+        /// gitlab.com or the instance in GITLAB_HOST.
+        source: String,
+        /// Agent whose context is used for the triage
+        #[arg(long)]
+        agent: Option<String>,
+        /// Format of the output
+        #[arg(long, short, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Triage {
+    summary: String,
+    #[serde(default)]
+    likely_files: Vec<LikelyFile>,
+    #[serde(default)]
+    next_steps: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LikelyFile {
+    path: String,
+    reason: String,
+}
+
 impl IssueArgs {
-    pub async fn execute(&self, os: &Os) -> Result<ExitCode> {
+    pub async fn execute(&self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
+        if let Some(IssueSubcommand::Summarize {
+            source,
+            agent,
+            format: summary_format,
+        }) = &self.cmd
+        {
+            return summarize(os, source, agent.as_deref(), summary_format.or(format)).await;
+        }
+
         let joined_description = self.description.join(" ").trim().to_owned();
 
         let issue_title = match joined_description.len() {
@@ -38,3 +108,236 @@ impl IssueArgs {
         Ok(ExitCode::SUCCESS)
     }
 }
+
+async fn summarize(os: &mut Os, source: &str, agent: Option<&str>, format: OutputFormat) -> Result<ExitCode> {
+    let report = match Url::parse(source) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => fetch_issue(os, &url).await?,
+        _ => os.fs.read_to_string(source).await?,
+    };
+
+    let candidates = candidate_files(os, &report).await;
+    let mut prompt = format!("{TRIAGE_PROMPT}\n\n<issue>\n{report}\n</issue>");
+    if !candidates.is_empty() {
+        prompt.push_str("\n\nCandidate files:\n");
+        for (path, terms) in &candidates {
+            prompt.push_str(&format!("- {path} (matches {})\n", terms.join(", ")));
+        }
+    }
+
+    eprintln!("Triaging the issue...");
+    let agents = Agents::load(os, agent, true, &mut std::io::sink()).await;
+    let response = oneshot::ask(os, agents, None, prompt).await?;
+    let triage: Triage = oneshot::parse_json(&response)?;
+
+    format.print(
+        || {
+            let mut text = format!("Summary\n  {}\n", triage.summary);
+            if !triage.likely_files.is_empty() {
+                text.push_str("\nLikely files\n");
+                for file in &triage.likely_files {
+                    text.push_str(&format!("  {}  {}\n", file.path, file.reason));
+                }
+            }
+            if !triage.next_steps.is_empty() {
+                text.push_str("\nNext steps\n");
+                for (i, step) in triage.next_steps.iter().enumerate() {
+                    text.push_str(&format!("  {}. {step}\n", i + 1));
+                }
+            }
+            text
+        },
+        || &triage,
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Fetches the title and description of a GitHub or GitLab issue.
+async fn fetch_issue(os: &Os, url: &Url) -> Result<String> {
+    let segments = url.path_segments().map(|s| s.collect::<Vec<_>>()).unwrap_or_default();
+    let client = crate::request::new_client()?;
+
+    let (request, body_field) = match (url.host_str(), &segments[..]) {
+        (Some("github.com"), [owner, repo, "issues", number]) => {
+            let mut request = client
+                .get(format!("https://api.github.com/repos/{owner}/{repo}/issues/{number}"))
+                .header("Accept", "application/vnd.github+json");
+            if let Ok(token) = os.env.get("GITHUB_TOKEN").or_else(|_| os.env.get("GH_TOKEN")) {
+                request = request.bearer_auth(token);
+            }
+            (request, "body")
+        },
+        (Some(host), [project @ .., "-", "issues", number]) if !project.is_empty() => {
+            let project = url::form_urlencoded::byte_serialize(project.join("/").as_bytes()).collect::<String>();
+            let mut request = client.get(format!(
+                "{}://{host}/api/v4/projects/{project}/issues/{number}",
+                url.scheme()
+            ));
+            if let (true, Ok(token)) = (is_trusted_gitlab(&os.env, url), os.env.get("GITLAB_TOKEN")) {
+                request = request.header("PRIVATE-TOKEN", token);
+            }
+            (request, "description")
+        },
+        _ => bail!("{url} is not a GitHub or GitLab issue, save the report to a file and pass its path instead"),
+    };
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        bail!("Failed to fetch {url}: {}", response.status());
+    }
+    let issue: serde_json::Value = response.json().await?;
+    Ok(format!(
+        "# {}\n\n{}",
+        issue["title"].as_str().unwrap_or_default(),
+        issue[body_field].as_str().unwrap_or_default()
+    ))
+}
+
+/// Whether `GITLAB_TOKEN` may be sent to the host of `url`, which is only the case over https to
+/// gitlab.com or the instance named by `GITLAB_HOST`. Anyone can host a page with a GitLab shaped
+/// issue path, so the token would otherwise leak to whichever issue url is summarized.
+fn is_trusted_gitlab(env: &Env, url: &Url) -> bool {
+    let Some(host) = url.host_str().filter(|_| url.scheme() == "https") else {
+        return false;
+    };
+    let gitlab_host = env.get("GITLAB_HOST").ok().map(|gitlab_host| {
+        // GITLAB_HOST is a host name or, as glab also accepts, the url of the instance
+        Url::parse(&gitlab_host)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or(gitlab_host)
+    });
+    host.eq_ignore_ascii_case("gitlab.com")
+        || gitlab_host.is_some_and(|gitlab_host| host.eq_ignore_ascii_case(&gitlab_host))
+}
+
+/// Searches the repository for the terms of the report that look like code, returning the files
+/// with the most matches along with the terms they matched.
+async fn candidate_files(os: &Os, report: &str) -> Vec<(String, Vec<String>)> {
+    let Ok(cwd) = os.env.current_dir() else {
+        return Vec::new();
+    };
+
+    let mut matches: HashMap<String, Vec<String>> = HashMap::new();
+    for term in search_terms(report) {
+        let Ok(output) = tokio::process::Command::new("git")
+            .args(["grep", "-I", "-l", "-F", "-e", &term])
+            .current_dir(&cwd)
+            .output()
+            .await
+        else {
+            // Not having git is no reason to fail the triage
+            return Vec::new();
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let files = stdout.lines().collect::<Vec<_>>();
+        if files.len() > MAX_FILES_PER_TERM {
+            continue;
+        }
+        for file in files {
+            matches.entry(file.to_string()).or_default().push(term.clone());
+        }
+    }
+
+    let mut candidates = matches.into_iter().collect::<Vec<_>>();
+    candidates.sort_by(|(a_path, a_terms), (b_path, b_terms)| {
+        b_terms.len().cmp(&a_terms.len()).then_with(|| a_path.cmp(b_path))
+    });
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+/// Terms of the report worth searching the code for: anything in backticks, and words that look
+/// like identifiers or paths rather than prose.
+fn search_terms(report: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut push = |term: &str| {
+        let term = term.trim_matches(|c: char| !c.is_alphanumeric() && !"_/".contains(c));
+        if term.len() >= 4 && !terms.iter().any(|t| t == term) {
+            terms.push(term.to_string());
+        }
+    };
+
+    for (i, span) in report.split('`').enumerate() {
+        // Odd spans are inside backticks, unless they are whole lines of a code block
+        if i % 2 == 1 && !span.contains('\n') {
+            push(span);
+        }
+    }
+    for word in report.split(|c: char| c.is_whitespace() || "`()[]{}<>\"',;".contains(c)) {
+        let looks_like_code = word.contains('_')
+            || word.contains("::")
+            || word.contains('/')
+            || word.chars().skip(1).any(|c| c.is_uppercase()) && word.chars().any(|c| c.is_lowercase());
+        if looks_like_code && !word.starts_with("http") {
+            push(word);
+        }
+    }
+
+    terms.truncate(20);
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cli::{
+        Cli,
+        RootSubcommand,
+    };
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Cli::parse_from(["q", "issue", "summarize", "bug.md", "-f", "json"]).subcommand,
+            Some(RootSubcommand::Issue(IssueArgs {
+                cmd: Some(IssueSubcommand::Summarize {
+                    source: "bug.md".to_string(),
+                    agent: None,
+                    format: OutputFormat::Json,
+                }),
+                force: false,
+                description: vec![],
+            }))
+        );
+        assert_eq!(
+            Cli::parse_from(["q", "issue", "crash", "on", "startup"]).subcommand,
+            Some(RootSubcommand::Issue(IssueArgs {
+                cmd: None,
+                force: false,
+                description: vec!["crash".to_string(), "on".to_string(), "startup".to_string()],
+            }))
+        );
+    }
+
+    #[test]
+    fn test_is_trusted_gitlab() {
+        let trusted = |env: &Env, url: &str| is_trusted_gitlab(env, &Url::parse(url).unwrap());
+
+        let env = Env::from_slice(&[]);
+        assert!(trusted(&env, "https://gitlab.com/group/project/-/issues/1"));
+        assert!(!trusted(&env, "http://gitlab.com/group/project/-/issues/1"));
+        assert!(!trusted(&env, "https://evil.example/group/project/-/issues/1"));
+        assert!(!trusted(&env, "https://gitlab.example.com/group/project/-/issues/1"));
+
+        for gitlab_host in ["gitlab.example.com", "https://gitlab.example.com"] {
+            let env = Env::from_slice(&[("GITLAB_HOST", gitlab_host)]);
+            assert!(trusted(&env, "https://gitlab.example.com/group/project/-/issues/1"));
+            assert!(!trusted(&env, "http://gitlab.example.com/group/project/-/issues/1"));
+            assert!(!trusted(&env, "https://evil.example/group/project/-/issues/1"));
+        }
+    }
+
+    #[test]
+    fn test_search_terms() {
+        let report = "Crash in `load_agents` when the config has a comment.\n\nSee src/cli/agent/mod.rs and \
+                      AgentConfig, https://example.com/a_b. The app just exits.\n```\nstack trace\n```";
+        assert_eq!(search_terms(report), vec![
+            "load_agents",
+            "src/cli/agent/mod.rs",
+            "AgentConfig"
+        ]);
+    }
+}
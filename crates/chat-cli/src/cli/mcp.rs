@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::process::ExitCode;
 
 use clap::{
@@ -16,7 +19,9 @@ use eyre::{
     Result,
     bail,
 };
+use serde::Serialize;
 
+use super::OutputFormat;
 use super::agent::{
     Agent,
     Agents,
@@ -28,12 +33,14 @@ use crate::cli::chat::tool_manager::{
 };
 use crate::cli::chat::tools::custom_tool::{
     CustomToolConfig,
+    McpTransport,
     default_timeout,
 };
 use crate::os::Os;
 use crate::util::directories;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Scope {
     Workspace,
     Global,
@@ -48,6 +55,49 @@ impl std::fmt::Display for Scope {
     }
 }
 
+/// A configured server as printed by `mcp list` and `mcp status` in the JSON formats. Like the
+/// plain output, it only names the environment variables and headers since their values usually
+/// hold credentials.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct McpServerOutput {
+    name: String,
+    scope: Scope,
+    config_path: PathBuf,
+    transport: McpTransport,
+    /// The command of stdio servers, the url of remote ones
+    target: String,
+    timeout: u64,
+    init_timeout_ms: Option<u64>,
+    max_retries: u32,
+    disabled: bool,
+    env_vars: Vec<String>,
+    headers: Vec<String>,
+}
+
+impl McpServerOutput {
+    fn new(name: &str, scope: Scope, config_path: &Path, cfg: &CustomToolConfig) -> Self {
+        let sorted_keys = |map: Option<&HashMap<String, String>>| {
+            let mut keys = map.map(|m| m.keys().cloned().collect::<Vec<_>>()).unwrap_or_default();
+            keys.sort();
+            keys
+        };
+        Self {
+            name: name.to_string(),
+            scope,
+            config_path: config_path.to_path_buf(),
+            transport: cfg.transport,
+            target: cfg.target().to_string(),
+            timeout: cfg.timeout,
+            init_timeout_ms: cfg.init_timeout_ms,
+            max_retries: cfg.max_retries,
+            disabled: cfg.disabled,
+            env_vars: sorted_keys(cfg.env.as_ref()),
+            headers: sorted_keys(cfg.headers.as_ref()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, clap::Subcommand)]
 pub enum McpSubcommand {
     /// Add or replace a configured server
@@ -64,13 +114,13 @@ pub enum McpSubcommand {
 }
 
 impl McpSubcommand {
-    pub async fn execute(self, os: &mut Os, output: &mut impl Write) -> Result<ExitCode> {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat, output: &mut impl Write) -> Result<ExitCode> {
         match self {
             Self::Add(args) => args.execute(os, output).await?,
             Self::Remove(args) => args.execute(os, output).await?,
-            Self::List(args) => args.execute(os, output).await?,
+            Self::List(args) => args.execute(os, format, output).await?,
             Self::Import(args) => args.execute(os, output).await?,
-            Self::Status(args) => args.execute(os, output).await?,
+            Self::Status(args) => args.execute(os, format, output).await?,
         }
 
         output.flush()?;
@@ -84,8 +134,17 @@ pub struct AddArgs {
     #[arg(long)]
     pub name: String,
     /// The command used to launch the server
-    #[arg(long)]
-    pub command: String,
+    #[arg(long, required_unless_present = "url")]
+    pub command: Option<String>,
+    /// The url of a remote server, instead of a command
+    #[arg(long, conflicts_with = "command")]
+    pub url: Option<String>,
+    /// How to connect to the server, http by default for a url
+    #[arg(long, value_enum)]
+    pub transport: Option<McpTransport>,
+    /// Header to send with every request to a remote server, e.g. 'Authorization: Bearer $TOKEN'
+    #[arg(long = "header", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
     /// Arguments to pass to the command
     #[arg(long, action = ArgAction::Append, allow_hyphen_values = true, value_delimiter = ',')]
     pub args: Vec<String>,
@@ -108,6 +167,31 @@ pub struct AddArgs {
 }
 
 impl AddArgs {
+    fn server_config(&self) -> Result<CustomToolConfig> {
+        let transport = match (self.transport, &self.url) {
+            (Some(transport), _) => transport,
+            (None, Some(_)) => McpTransport::Http,
+            (None, None) => McpTransport::Stdio,
+        };
+        match (transport, &self.url) {
+            (McpTransport::Stdio, Some(_)) => bail!("A url cannot be used with the stdio transport"),
+            (McpTransport::Http | McpTransport::Sse, None) => bail!("The {transport:?} transport requires a --url"),
+            _ => (),
+        }
+
+        let merged_env = self.env.iter().flatten().collect::<HashMap<_, _>>();
+        Ok(serde_json::from_value(serde_json::json!({
+            "transport": transport,
+            "command": self.command.clone().unwrap_or_default(),
+            "args": self.args,
+            "env": merged_env,
+            "url": self.url,
+            "headers": (!self.headers.is_empty()).then(|| self.headers.iter().cloned().collect::<HashMap<_, _>>()),
+            "timeout": self.timeout.unwrap_or(default_timeout()),
+            "disabled": self.disabled,
+        }))?)
+    }
+
     pub async fn execute(self, os: &Os, output: &mut impl Write) -> Result<()> {
         match self.agent.as_deref() {
             Some(agent_name) => {
@@ -123,14 +207,7 @@ impl AddArgs {
                     );
                 }
 
-                let merged_env = self.env.into_iter().flatten().collect::<HashMap<_, _>>();
-                let tool: CustomToolConfig = serde_json::from_value(serde_json::json!({
-                    "command": self.command,
-                    "args": self.args,
-                    "env": merged_env,
-                    "timeout": self.timeout.unwrap_or(default_timeout()),
-                    "disabled": self.disabled,
-                }))?;
+                let tool = self.server_config()?;
 
                 mcp_servers.insert(self.name.clone(), tool);
                 let json = agent.to_str_pretty()?;
@@ -149,14 +226,7 @@ impl AddArgs {
                     );
                 }
 
-                let merged_env = self.env.into_iter().flatten().collect::<HashMap<_, _>>();
-                let tool: CustomToolConfig = serde_json::from_value(serde_json::json!({
-                    "command": self.command,
-                    "args": self.args,
-                    "env": merged_env,
-                    "timeout": self.timeout.unwrap_or(default_timeout()),
-                    "disabled": self.disabled,
-                }))?;
+                let tool = self.server_config()?;
 
                 mcp_servers.mcp_servers.insert(self.name.clone(), tool);
                 mcp_servers.save_to_file(os, &global_config_path).await?;
@@ -252,8 +322,23 @@ pub struct ListArgs {
 }
 
 impl ListArgs {
-    pub async fn execute(self, os: &mut Os, output: &mut impl Write) -> Result<()> {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat, output: &mut impl Write) -> Result<()> {
         let configs = get_mcp_server_configs(os, self.scope).await?;
+        if format.is_json() {
+            let servers = configs
+                .iter()
+                .flat_map(|(scope, path, cfg_opt)| {
+                    cfg_opt.iter().flat_map(|cfg| {
+                        cfg.mcp_servers
+                            .iter()
+                            .map(|(name, tool_cfg)| McpServerOutput::new(name, *scope, path, tool_cfg))
+                    })
+                })
+                .collect::<Vec<_>>();
+            format.print(String::new, || servers);
+            return Ok(());
+        }
+
         if configs.is_empty() {
             writeln!(output, "No MCP server configurations found.\n")?;
             return Ok(());
@@ -266,7 +351,7 @@ impl ListArgs {
                 Some(cfg) if !cfg.mcp_servers.is_empty() => {
                     for (name, tool_cfg) in &cfg.mcp_servers {
                         let status = if tool_cfg.disabled { " (disabled)" } else { "" };
-                        writeln!(output, "    • {name:<12} {}{}", tool_cfg.command, status)?;
+                        writeln!(output, "    • {name:<12} {}{}", tool_cfg.target(), status)?;
                     }
                 },
                 _ => {
@@ -336,20 +421,39 @@ pub struct StatusArgs {
 }
 
 impl StatusArgs {
-    pub async fn execute(self, os: &mut Os, output: &mut impl Write) -> Result<()> {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat, output: &mut impl Write) -> Result<()> {
         let configs = get_mcp_server_configs(os, None).await?;
-        let mut found = false;
+        let mut found = Vec::new();
 
         for (sc, path, cfg_opt) in configs {
             if let Some(cfg) = cfg_opt.and_then(|c| c.mcp_servers.get(&self.name).cloned()) {
-                found = true;
+                found.push(McpServerOutput::new(&self.name, sc, &path, &cfg));
+                if format.is_json() {
+                    continue;
+                }
                 execute!(
                     output,
                     style::Print("\n─────────────\n"),
                     style::Print(format!("Scope   : {}\n", scope_display(&sc))),
                     style::Print(format!("File    : {}\n", path.display())),
-                    style::Print(format!("Command : {}\n", cfg.command)),
+                    style::Print(match cfg.transport {
+                        McpTransport::Stdio => format!("Command : {}\n", cfg.command),
+                        // Header values usually hold credentials, so only their names are shown
+                        McpTransport::Http | McpTransport::Sse => format!(
+                            "Url     : {}\nHeaders : {}\n",
+                            cfg.target(),
+                            cfg.headers
+                                .as_ref()
+                                .map_or_else(|| "(none)".into(), |h| h.keys().cloned().collect::<Vec<_>>().join(", "))
+                        ),
+                    }),
                     style::Print(format!("Timeout : {} ms\n", cfg.timeout)),
+                    style::Print(format!(
+                        "Init    : {}, {} retries\n",
+                        cfg.init_timeout_ms
+                            .map_or_else(|| "no timeout".into(), |ms| format!("{ms} ms timeout")),
+                        cfg.max_retries
+                    )),
                     style::Print(format!("Disabled: {}\n", cfg.disabled)),
                     style::Print(format!(
                         "Env Vars: {}\n",
@@ -360,12 +464,16 @@ impl StatusArgs {
                 )?;
             }
         }
-        writeln!(output, "\n")?;
-
-        if !found {
+        if found.is_empty() {
             bail!("No MCP server named '{}' found in any scope/profile\n", self.name);
         }
 
+        if format.is_json() {
+            format.print(String::new, || found);
+        } else {
+            writeln!(output, "\n")?;
+        }
+
         Ok(())
     }
 }
@@ -459,6 +567,13 @@ fn parse_env_vars(arg: &str) -> Result<HashMap<String, String>> {
     Ok(vars)
 }
 
+fn parse_header(arg: &str) -> Result<(String, String)> {
+    match arg.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.trim().to_string())),
+        _ => bail!("Invalid header '{arg}'. Expected 'name: value'"),
+    }
+}
+
 async fn load_cfg(os: &Os, p: &PathBuf) -> Result<McpServerConfig> {
     Ok(if os.fs.exists(p) {
         McpServerConfig::load_from_file(os, p).await?
@@ -515,7 +630,10 @@ mod tests {
         // 1. add
         AddArgs {
             name: "local".into(),
-            command: "echo hi".into(),
+            command: Some("echo hi".into()),
+            url: None,
+            transport: None,
+            headers: vec![],
             args: vec![
                 "awslabs.eks-mcp-server".to_string(),
                 "--allow-write".to_string(),
@@ -566,7 +684,10 @@ mod tests {
             ],
             RootSubcommand::Mcp(McpSubcommand::Add(AddArgs {
                 name: "test_server".to_string(),
-                command: "test_command".to_string(),
+                command: Some("test_command".to_string()),
+                url: None,
+                transport: None,
+                headers: vec![],
                 args: vec![
                     "awslabs.eks-mcp-server".to_string(),
                     "--allow-write".to_string(),
@@ -588,6 +709,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_mcp_subcommand_add_remote() {
+        assert_parse!(
+            [
+                "mcp",
+                "add",
+                "--name",
+                "remote",
+                "--url",
+                "https://example.com/mcp",
+                "--header",
+                "Authorization: Bearer $TOKEN"
+            ],
+            RootSubcommand::Mcp(McpSubcommand::Add(AddArgs {
+                name: "remote".to_string(),
+                command: None,
+                url: Some("https://example.com/mcp".to_string()),
+                transport: None,
+                headers: vec![("Authorization".to_string(), "Bearer $TOKEN".to_string())],
+                args: vec![],
+                agent: None,
+                env: vec![],
+                timeout: None,
+                disabled: false,
+                force: false,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_add_remote_server_config() {
+        let mut args = AddArgs {
+            name: "remote".to_string(),
+            command: None,
+            url: Some("https://example.com/mcp".to_string()),
+            transport: None,
+            headers: vec![],
+            args: vec![],
+            agent: None,
+            env: vec![],
+            timeout: None,
+            disabled: false,
+            force: false,
+        };
+        let config = args.server_config().unwrap();
+        assert_eq!(config.transport, McpTransport::Http);
+        assert_eq!(config.url.as_deref(), Some("https://example.com/mcp"));
+        assert!(config.headers.is_none());
+
+        args.url = None;
+        args.command = Some("x".to_string());
+        args.transport = Some(McpTransport::Sse);
+        assert!(args.server_config().is_err());
+    }
+
     #[test]
     fn test_mcp_subcomman_remove_workspace() {
         assert_parse!(
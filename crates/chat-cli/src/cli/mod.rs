@@ -1,23 +1,37 @@
-mod agent;
+pub(crate) mod agent;
 mod chat;
 mod debug;
 mod diagnostics;
 mod feed;
+mod git;
+mod git_hooks;
+mod history;
+mod init;
+mod inline;
+mod internal;
 mod issue;
 mod mcp;
 mod settings;
+mod snapshots;
+mod stats;
 mod user;
 
 use std::fmt::Display;
 use std::io::{
+    IsTerminal,
     Write as _,
     stdout,
 };
+use std::path::PathBuf;
 use std::process::ExitCode;
 
 use agent::AgentArgs;
 use anstream::println;
-pub use chat::ConversationState;
+pub use chat::{
+    Artifact,
+    ConversationState,
+    InterruptedTurn,
+};
 use clap::{
     ArgAction,
     CommandFactory,
@@ -27,17 +41,33 @@ use clap::{
 };
 use crossterm::style::Stylize;
 use eyre::{
+    Context,
     Result,
     bail,
 };
 use feed::Feed;
 use serde::Serialize;
+use snapshots::RestoreArgs;
+pub use snapshots::SessionSnapshot;
+use stats::StatsArgs;
+pub use stats::UsageDay;
 use tracing::{
     Level,
     debug,
 };
 
-use crate::cli::chat::ChatArgs;
+use crate::cli::chat::{
+    AttachArgs,
+    ChatArgs,
+    NvimServerArgs,
+    ScriptArgs,
+};
+use crate::cli::git::GitArgs;
+use crate::cli::git_hooks::HooksArgs;
+use crate::cli::history::HistoryArgs;
+use crate::cli::init::InitArgs;
+use crate::cli::inline::InlineArgs;
+use crate::cli::internal::InternalArgs;
 use crate::cli::mcp::McpSubcommand;
 use crate::cli::user::{
     LoginArgs,
@@ -47,11 +77,15 @@ use crate::logging::{
     LogArgs,
     initialize_logging,
 };
-use crate::os::Os;
+use crate::os::{
+    Env,
+    Os,
+};
 use crate::util::directories::logs_dir;
 use crate::util::{
     CLI_BINARY_NAME,
     GOV_REGIONS,
+    color,
 };
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
@@ -63,6 +97,9 @@ pub enum OutputFormat {
     Json,
     /// Outputs the results as pretty print JSON
     JsonPretty,
+    /// Outputs newline-delimited JSON events as they happen. Commands with a single result print
+    /// it as one line of JSON.
+    JsonStream,
 }
 
 impl OutputFormat {
@@ -75,10 +112,34 @@ impl OutputFormat {
     {
         match self {
             OutputFormat::Plain => println!("{}", text_fn()),
-            OutputFormat::Json => println!("{}", serde_json::to_string(&json_fn()).unwrap()),
+            OutputFormat::Json | OutputFormat::JsonStream => {
+                println!("{}", serde_json::to_string(&json_fn()).unwrap());
+            },
             OutputFormat::JsonPretty => println!("{}", serde_json::to_string_pretty(&json_fn()).unwrap()),
         }
     }
+
+    /// `self`, unless it was left at the default, in which case `fallback` (the `--format` given
+    /// before the subcommand) is used.
+    pub fn or(self, fallback: OutputFormat) -> OutputFormat {
+        match self {
+            OutputFormat::Plain => fallback,
+            format => format,
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        !matches!(self, OutputFormat::Plain)
+    }
+}
+
+/// Output of `version` in the JSON formats.
+#[derive(Debug, Serialize)]
+struct VersionOutput {
+    version: &'static str,
+    /// The requested changelog entries, or [None] if no changelog was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changelog: Option<Vec<feed::Entry>>,
 }
 
 /// The Amazon Q CLI
@@ -89,6 +150,14 @@ pub enum RootSubcommand {
     Agent(AgentArgs),
     /// AI assistant in your terminal
     Chat(ChatArgs),
+    /// Resolve merge conflicts with proposals from Amazon Q
+    Git(GitArgs),
+    /// Install git hooks that review changes before they are committed
+    Hooks(HooksArgs),
+    /// Scaffold a workspace .amazonq directory with a starter agent, rules and mcp config
+    Init(InitArgs),
+    /// Generate a code completion at a position in a file, for editor integrations
+    Inline(InlineArgs),
     /// Log in to Amazon Q
     Login(LoginArgs),
     /// Log out of Amazon Q
@@ -103,10 +172,12 @@ pub enum RootSubcommand {
     #[command(alias("setting"))]
     Settings(settings::SettingsArgs),
     /// Run diagnostic tests
-    #[command(alias("diagnostics"))]
+    #[command(alias("diagnostics"), alias("doctor"))]
     Diagnostic(diagnostics::DiagnosticArgs),
     /// Create a new Github issue
     Issue(issue::IssueArgs),
+    /// Inspect what chat sessions leave behind
+    Debug(debug::DebugArgs),
     /// Version
     #[command(hide = true)]
     Version {
@@ -115,9 +186,24 @@ pub enum RootSubcommand {
         #[arg(long, num_args = 0..=1, default_missing_value = "")]
         changelog: Option<String>,
     },
+    /// Export and import saved conversations
+    History(HistoryArgs),
     /// Model Context Protocol (MCP)
     #[command(subcommand)]
     Mcp(McpSubcommand),
+    /// Serve chat to a Neovim plugin over msgpack-RPC on stdin and stdout
+    NvimServer(NvimServerArgs),
+    /// Run conversations declared in YAML files
+    Script(ScriptArgs),
+    /// Follow a chat session shared with `q chat --share`, read-only
+    Attach(AttachArgs),
+    /// Show statistics of your own use of chat, such as sessions per week and the tools used
+    Stats(StatsArgs),
+    /// Put the files tools changed in a chat session back the way they were before it
+    Restore(RestoreArgs),
+    /// Commands for testing builds
+    #[command(hide = true)]
+    Internal(InternalArgs),
 }
 
 impl RootSubcommand {
@@ -125,14 +211,27 @@ impl RootSubcommand {
     ///
     /// Emitting telemetry takes a long time so the answer is usually no.
     pub fn valid_for_telemetry(&self) -> bool {
-        matches!(self, Self::Chat(_) | Self::Login(_) | Self::Profile | Self::Issue(_))
+        matches!(
+            self,
+            Self::Chat(ChatArgs { incognito: false, .. }) | Self::Login(_) | Self::Profile | Self::Issue(_)
+        )
     }
 
     pub fn requires_auth(&self) -> bool {
-        matches!(self, Self::Chat(_) | Self::Profile)
+        matches!(
+            self,
+            Self::Chat(ChatArgs { cmd: None, .. })
+                | Self::Git(_)
+                | Self::Inline(_)
+                | Self::NvimServer(_)
+                | Self::Script(_)
+                | Self::Profile
+        )
     }
 
-    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+    /// Runs the subcommand, printing its results in `format` unless it has a --format of its own
+    /// that was set.
+    pub async fn execute(self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
         // Check for auth on subcommands that require it.
         if self.requires_auth() && !crate::auth::is_logged_in(&mut os.database).await {
             bail!(
@@ -147,18 +246,33 @@ impl RootSubcommand {
         }
 
         match self {
-            Self::Agent(args) => args.execute(os).await,
-            Self::Diagnostic(args) => args.execute(os).await,
+            Self::Agent(args) => args.execute(os, format).await,
+            Self::Diagnostic(args) => args.execute(os, format).await,
             Self::Login(args) => args.execute(os).await,
-            Self::Logout => user::logout(os).await,
-            Self::User(args) => args.execute(os).await,
-            Self::Whoami(args) => args.execute(os).await,
-            Self::Profile => user::profile(os).await,
-            Self::Settings(settings_args) => settings_args.execute(os).await,
-            Self::Issue(args) => args.execute(os).await,
-            Self::Version { changelog } => Cli::print_version(changelog),
-            Self::Chat(args) => args.execute(os).await,
-            Self::Mcp(args) => args.execute(os, &mut std::io::stderr()).await,
+            Self::Logout => user::logout(os, format).await,
+            Self::User(args) => args.execute(os, format).await,
+            Self::Whoami(args) => args.execute(os, format).await,
+            Self::Profile => user::profile(os, user::ProfileArgs::default(), format).await,
+            Self::Settings(settings_args) => settings_args.execute(os, format).await,
+            Self::Issue(args) => args.execute(os, format).await,
+            Self::Debug(args) => args.execute(os, format).await,
+            Self::Version { changelog } => Cli::print_version(changelog, format),
+            Self::Chat(mut args) => {
+                args.format = args.format.or(format);
+                args.execute(os).await
+            },
+            Self::Git(args) => args.execute(os).await,
+            Self::Hooks(args) => args.execute(os).await,
+            Self::Init(args) => args.execute(os).await,
+            Self::Inline(args) => args.execute(os, format).await,
+            Self::History(args) => args.execute(os, format).await,
+            Self::Mcp(args) => args.execute(os, format, &mut std::io::stderr()).await,
+            Self::NvimServer(args) => args.execute(os).await,
+            Self::Script(args) => args.execute(os).await,
+            Self::Attach(args) => args.execute().await,
+            Self::Stats(args) => args.execute(os, format).await,
+            Self::Restore(args) => args.execute(os, format).await,
+            Self::Internal(args) => args.execute(os).await,
         }
     }
 }
@@ -174,6 +288,11 @@ impl Display for RootSubcommand {
         let name = match self {
             Self::Agent(_) => "agent",
             Self::Chat(_) => "chat",
+            Self::Git(_) => "git",
+            Self::Hooks(_) => "hooks",
+            Self::Init(_) => "init",
+            Self::Inline(_) => "inline",
+            Self::History(_) => "history",
             Self::Login(_) => "login",
             Self::Logout => "logout",
             Self::Whoami(_) => "whoami",
@@ -181,8 +300,15 @@ impl Display for RootSubcommand {
             Self::Settings(_) => "settings",
             Self::Diagnostic(_) => "diagnostic",
             Self::Issue(_) => "issue",
+            Self::Debug(_) => "debug",
             Self::Version { .. } => "version",
             Self::Mcp(_) => "mcp",
+            Self::NvimServer(_) => "nvim-server",
+            Self::Script(_) => "script",
+            Self::Attach(_) => "attach",
+            Self::Stats(_) => "stats",
+            Self::Restore(_) => "restore",
+            Self::Internal(_) => "internal",
             Self::User(_) => "user",
         };
 
@@ -198,6 +324,17 @@ pub struct Cli {
     /// Increase logging verbosity
     #[arg(long, short = 'v', action = ArgAction::Count, global = true)]
     pub verbose: u8,
+    /// Write sanitized HTTP requests and responses to this directory for debugging
+    #[arg(long, global = true, value_name = "DIR")]
+    pub debug_http: Option<PathBuf>,
+    /// Disable colored output, which is also disabled by setting NO_COLOR or when stdout is not a
+    /// terminal
+    #[arg(long, global = true)]
+    pub no_color: bool,
+    /// Format of the output of the subcommand, for subcommands that have no --format of their own
+    /// or leave it unset. Plain text is printed by default.
+    #[arg(long, value_enum)]
+    pub format: Option<OutputFormat>,
     /// Print help for all subcommands
     #[arg(long)]
     help_all: bool,
@@ -207,6 +344,12 @@ impl Cli {
     pub async fn execute(self) -> Result<ExitCode> {
         let subcommand = self.subcommand.unwrap_or_default();
 
+        color::init(color::colors_enabled(
+            &Env::new(),
+            self.no_color,
+            stdout().is_terminal(),
+        ));
+
         // Initialize our logger and keep around the guard so logging can perform as expected.
         let _log_guard = initialize_logging(LogArgs {
             log_level: match self.verbose > 0 {
@@ -238,8 +381,13 @@ impl Cli {
 
         debug!(command =? std::env::args().collect::<Vec<_>>(), "Command being ran");
 
+        if let Some(dir) = &self.debug_http {
+            crate::api_client::http_dump::enable(dir)
+                .wrap_err_with(|| format!("failed to create the http dump directory {}", dir.display()))?;
+        }
+
         let mut os = Os::new().await?;
-        let result = subcommand.execute(&mut os).await;
+        let result = subcommand.execute(&mut os, self.format.unwrap_or_default()).await;
 
         let telemetry_result = os.telemetry.finish().await;
         let exit_code = result?;
@@ -270,7 +418,21 @@ impl Cli {
         Ok(())
     }
 
-    fn print_version(changelog: Option<String>) -> Result<ExitCode> {
+    fn print_version(changelog: Option<String>, format: OutputFormat) -> Result<ExitCode> {
+        if format.is_json() {
+            let version = env!("CARGO_PKG_VERSION");
+            let changelog = changelog.map(|changelog| {
+                let feed = Feed::load();
+                match changelog.as_str() {
+                    "all" => feed.get_all_changelogs(),
+                    "" => feed.get_version_changelog(version).into_iter().collect(),
+                    other => feed.get_version_changelog(other).into_iter().collect(),
+                }
+            });
+            format.print(String::new, || VersionOutput { version, changelog });
+            return Ok(ExitCode::SUCCESS);
+        }
+
         // If no changelog is requested, display normal version information
         if changelog.is_none() {
             let _ = writeln!(stdout(), "{}", Self::command().render_version());
@@ -328,6 +490,7 @@ impl Cli {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::cli::chat::ChatSubcommand;
     use crate::util::CHAT_BINARY_NAME;
     use crate::util::test::assert_parse;
 
@@ -342,23 +505,33 @@ mod test {
         assert_eq!(Cli::parse_from([CHAT_BINARY_NAME, "-v"]), Cli {
             subcommand: None,
             verbose: 1,
+            debug_http: None,
+            no_color: false,
+            format: None,
             help_all: false,
         });
 
         assert_eq!(Cli::parse_from([CHAT_BINARY_NAME, "-vvv"]), Cli {
             subcommand: None,
             verbose: 3,
+            debug_http: None,
+            no_color: false,
+            format: None,
             help_all: false,
         });
 
         assert_eq!(Cli::parse_from([CHAT_BINARY_NAME, "--help-all"]), Cli {
             subcommand: None,
             verbose: 0,
+            debug_http: None,
+            no_color: false,
+            format: None,
             help_all: true,
         });
 
         assert_eq!(Cli::parse_from([CHAT_BINARY_NAME, "chat", "-vv"]), Cli {
             subcommand: Some(RootSubcommand::Chat(ChatArgs {
+                cmd: None,
                 resume: false,
                 input: None,
                 agent: None,
@@ -366,10 +539,63 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: false,
+                format: OutputFormat::Plain,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
             })),
             verbose: 2,
+            debug_http: None,
+            no_color: false,
+            format: None,
+            help_all: false,
+        });
+
+        assert_eq!(
+            Cli::parse_from([CHAT_BINARY_NAME, "chat", "--debug-http", "/tmp/dumps"]),
+            Cli {
+                subcommand: Some(RootSubcommand::Chat(ChatArgs::default())),
+                verbose: 0,
+                debug_http: Some(PathBuf::from("/tmp/dumps")),
+                no_color: false,
+                format: None,
+                help_all: false,
+            }
+        );
+
+        assert_eq!(Cli::parse_from([CHAT_BINARY_NAME, "chat", "--no-color"]), Cli {
+            subcommand: Some(RootSubcommand::Chat(ChatArgs::default())),
+            verbose: 0,
+            debug_http: None,
+            no_color: true,
+            format: None,
             help_all: false,
         });
+
+        assert_eq!(
+            Cli::parse_from([CHAT_BINARY_NAME, "--format", "json", "profile"]),
+            Cli {
+                subcommand: Some(RootSubcommand::Profile),
+                verbose: 0,
+                debug_http: None,
+                no_color: false,
+                format: Some(OutputFormat::Json),
+                help_all: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_output_format_or() {
+        assert_eq!(OutputFormat::Plain.or(OutputFormat::Json), OutputFormat::Json);
+        assert_eq!(
+            OutputFormat::JsonPretty.or(OutputFormat::Json),
+            OutputFormat::JsonPretty
+        );
+        assert!(!OutputFormat::Plain.or(OutputFormat::Plain).is_json());
     }
 
     #[test]
@@ -398,6 +624,7 @@ mod test {
         assert_parse!(
             ["chat", "--profile", "my-profile"],
             RootSubcommand::Chat(ChatArgs {
+                cmd: None,
                 resume: false,
                 input: None,
                 agent: Some("my-profile".to_string()),
@@ -405,6 +632,13 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: false,
+                format: OutputFormat::Plain,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
             })
         );
     }
@@ -414,6 +648,7 @@ mod test {
         assert_parse!(
             ["chat", "--profile", "my-profile", "Hello"],
             RootSubcommand::Chat(ChatArgs {
+                cmd: None,
                 resume: false,
                 input: Some("Hello".to_string()),
                 agent: Some("my-profile".to_string()),
@@ -421,6 +656,13 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: false,
+                format: OutputFormat::Plain,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
             })
         );
     }
@@ -430,6 +672,7 @@ mod test {
         assert_parse!(
             ["chat", "--profile", "my-profile", "--trust-all-tools"],
             RootSubcommand::Chat(ChatArgs {
+                cmd: None,
                 resume: false,
                 input: None,
                 agent: Some("my-profile".to_string()),
@@ -437,6 +680,13 @@ mod test {
                 trust_all_tools: true,
                 trust_tools: None,
                 no_interactive: false,
+                format: OutputFormat::Plain,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
             })
         );
     }
@@ -446,6 +696,7 @@ mod test {
         assert_parse!(
             ["chat", "--no-interactive", "--resume"],
             RootSubcommand::Chat(ChatArgs {
+                cmd: None,
                 resume: true,
                 input: None,
                 agent: None,
@@ -453,11 +704,19 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: true,
+                format: OutputFormat::Plain,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
             })
         );
         assert_parse!(
             ["chat", "--non-interactive", "-r"],
             RootSubcommand::Chat(ChatArgs {
+                cmd: None,
                 resume: true,
                 input: None,
                 agent: None,
@@ -465,6 +724,148 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: None,
                 no_interactive: true,
+                format: OutputFormat::Plain,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_user_export_token_format() {
+        assert_parse!(
+            ["user", "export-token", "--format", "credential-process"],
+            RootSubcommand::User(user::UserArgs {
+                subcommand: user::UserSubcommand::ExportToken {
+                    format: user::TokenFormat::CredentialProcess,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_export() {
+        assert_parse!(
+            ["chat", "export", "abc", "-o", "chat.html"],
+            RootSubcommand::Chat(ChatArgs {
+                cmd: Some(ChatSubcommand::Export {
+                    id: "abc".to_string(),
+                    output: Some(PathBuf::from("chat.html")),
+                    format: None,
+                }),
+                ..Default::default()
+            })
+        );
+        assert_parse!(
+            ["chat", "export this"],
+            RootSubcommand::Chat(ChatArgs {
+                input: Some("export this".to_string()),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_json_stream() {
+        assert_parse!(
+            ["chat", "--format", "json-stream", "hello"],
+            RootSubcommand::Chat(ChatArgs {
+                cmd: None,
+                resume: false,
+                input: Some("hello".to_string()),
+                agent: None,
+                model: None,
+                trust_all_tools: false,
+                trust_tools: None,
+                no_interactive: false,
+                format: OutputFormat::JsonStream,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_accept_large_requests() {
+        assert_parse!(
+            ["chat", "--no-interactive", "--accept-large-requests", "hello"],
+            RootSubcommand::Chat(ChatArgs {
+                input: Some("hello".to_string()),
+                no_interactive: true,
+                accept_large_requests: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_verdict() {
+        assert_parse!(
+            ["chat", "--no-interactive", "--verdict", "verify the migration plan"],
+            RootSubcommand::Chat(ChatArgs {
+                input: Some("verify the migration plan".to_string()),
+                no_interactive: true,
+                verdict: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_with_share() {
+        assert_parse!(
+            ["chat", "--share"],
+            RootSubcommand::Chat(ChatArgs {
+                share: true,
+                ..Default::default()
+            })
+        );
+        assert_parse!(["attach"], RootSubcommand::Attach(AttachArgs { session: None }));
+    }
+
+    #[test]
+    fn test_chat_with_record() {
+        assert_parse!(
+            ["chat", "--record", "demo.cast"],
+            RootSubcommand::Chat(ChatArgs {
+                record: Some(PathBuf::from("demo.cast")),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_chat_incognito() {
+        assert_parse!(
+            ["chat", "--incognito"],
+            RootSubcommand::Chat(ChatArgs {
+                incognito: true,
+                ..Default::default()
+            })
+        );
+        assert!(
+            !Cli::parse_from([CHAT_BINARY_NAME, "chat", "--incognito"])
+                .subcommand
+                .unwrap()
+                .valid_for_telemetry()
+        );
+    }
+
+    #[test]
+    fn test_chat_tabs() {
+        assert_parse!(
+            ["chat", "--tabs"],
+            RootSubcommand::Chat(ChatArgs {
+                tabs: true,
+                ..Default::default()
             })
         );
     }
@@ -474,6 +875,7 @@ mod test {
         assert_parse!(
             ["chat", "--trust-all-tools"],
             RootSubcommand::Chat(ChatArgs {
+                cmd: None,
                 resume: false,
                 input: None,
                 agent: None,
@@ -481,6 +883,13 @@ mod test {
                 trust_all_tools: true,
                 trust_tools: None,
                 no_interactive: false,
+                format: OutputFormat::Plain,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
             })
         );
     }
@@ -490,6 +899,7 @@ mod test {
         assert_parse!(
             ["chat", "--trust-tools="],
             RootSubcommand::Chat(ChatArgs {
+                cmd: None,
                 resume: false,
                 input: None,
                 agent: None,
@@ -497,6 +907,13 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: Some(vec!["".to_string()]),
                 no_interactive: false,
+                format: OutputFormat::Plain,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
             })
         );
     }
@@ -506,6 +923,7 @@ mod test {
         assert_parse!(
             ["chat", "--trust-tools=fs_read,fs_write"],
             RootSubcommand::Chat(ChatArgs {
+                cmd: None,
                 resume: false,
                 input: None,
                 agent: None,
@@ -513,6 +931,13 @@ mod test {
                 trust_all_tools: false,
                 trust_tools: Some(vec!["fs_read".to_string(), "fs_write".to_string()]),
                 no_interactive: false,
+                format: OutputFormat::Plain,
+                accept_large_requests: false,
+                verdict: false,
+                share: false,
+                record: None,
+                incognito: false,
+                tabs: false,
             })
         );
     }
@@ -54,7 +54,7 @@ pub struct SettingsArgs {
 }
 
 impl SettingsArgs {
-    pub async fn execute(&self, os: &mut Os) -> Result<ExitCode> {
+    pub async fn execute(&self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
         match self.cmd {
             Some(SettingsSubcommands::Open) => {
                 let file = directories::settings_path().context("Could not get settings path")?;
@@ -65,10 +65,14 @@ impl SettingsArgs {
                     bail!("The EDITOR environment variable is not set")
                 }
             },
-            Some(SettingsSubcommands::All { format, state }) => {
+            Some(SettingsSubcommands::All {
+                format: all_format,
+                state,
+            }) => {
+                let format = all_format.or(format);
                 let settings = match state {
                     true => os.database.get_all_entries()?,
-                    false => os.database.settings.map().clone(),
+                    false => os.database.settings.map(),
                 };
 
                 match format {
@@ -77,7 +81,9 @@ impl SettingsArgs {
                             println!("{key} = {value}");
                         }
                     },
-                    OutputFormat::Json => println!("{}", serde_json::to_string(&settings)?),
+                    OutputFormat::Json | OutputFormat::JsonStream => {
+                        println!("{}", serde_json::to_string(&settings)?);
+                    },
                     OutputFormat::JsonPretty => {
                         println!("{}", serde_json::to_string_pretty(&settings)?);
                     },
@@ -90,23 +96,24 @@ impl SettingsArgs {
                     return Ok(ExitCode::SUCCESS);
                 };
 
+                let format = self.format.or(format);
                 let key = Setting::try_from(key.as_str())?;
                 match (&self.value, self.delete) {
                     (None, false) => match os.database.settings.get(key) {
                         Some(value) => {
-                            match self.format {
+                            match format {
                                 OutputFormat::Plain => match value.as_str() {
                                     Some(value) => println!("{value}"),
                                     None => println!("{value:#}"),
                                 },
-                                OutputFormat::Json => println!("{value}"),
+                                OutputFormat::Json | OutputFormat::JsonStream => println!("{value}"),
                                 OutputFormat::JsonPretty => println!("{value:#}"),
                             }
                             Ok(ExitCode::SUCCESS)
                         },
-                        None => match self.format {
+                        None => match format {
                             OutputFormat::Plain => Err(eyre::eyre!("No value associated with {key}")),
-                            OutputFormat::Json | OutputFormat::JsonPretty => {
+                            OutputFormat::Json | OutputFormat::JsonPretty | OutputFormat::JsonStream => {
                                 println!("null");
                                 Ok(ExitCode::SUCCESS)
                             },
@@ -129,7 +136,9 @@ impl SettingsArgs {
                                 return Err(eyre::eyre!("No settings found matching {key}"));
                             },
                             1 => {
-                                println!("Removing {:?}", keys_to_remove[0]);
+                                if !format.is_json() {
+                                    println!("Removing {:?}", keys_to_remove[0]);
+                                }
                                 os.database
                                     .settings
                                     .remove(Setting::try_from(keys_to_remove[0].as_str())?)
@@ -138,13 +147,18 @@ impl SettingsArgs {
                             _ => {
                                 for key in &keys_to_remove {
                                     if let Ok(key) = Setting::try_from(key.as_str()) {
-                                        println!("Removing `{key}`");
+                                        if !format.is_json() {
+                                            println!("Removing `{key}`");
+                                        }
                                         os.database.settings.remove(key).await?;
                                     }
                                 }
                             },
                         }
 
+                        if format.is_json() {
+                            format.print(String::new, || json!({ "removed": keys_to_remove }));
+                        }
                         Ok(ExitCode::SUCCESS)
                     },
                     _ => Ok(ExitCode::SUCCESS),
@@ -0,0 +1,432 @@
+//! Snapshots of the files tools change in chat sessions, so that the changes of a session can be
+//! rolled back with `/revert` or `q restore --session <id>`, whether or not the workspace is under
+//! version control.
+//!
+//! The first time a session changes a file, its contents are copied into a store under
+//! [directories::chat_snapshots_dir] where they are named by their SHA-256, so that contents
+//! shared by several sessions are stored once. Each session lists the files it changed in the
+//! local database. Files larger than [MAX_FILE_SIZE] are not copied, and once the store is larger
+//! than `chat.snapshotMaxSizeMb` the oldest sessions are pruned.
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::ExitCode;
+
+use clap::Args;
+use eyre::{
+    Result,
+    bail,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+use time::OffsetDateTime;
+use tracing::{
+    debug,
+    warn,
+};
+
+use super::OutputFormat;
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::util::datetime::DateTimeFormat;
+use crate::util::directories;
+
+/// Files larger than this are not snapshotted, so they can't be restored.
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+/// Size of the store when `chat.snapshotMaxSizeMb` is not set.
+const DEFAULT_MAX_SIZE_MB: i64 = 512;
+
+/// The files changed in a chat session, as they were before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSnapshot {
+    pub conversation_id: String,
+    /// The working directory of the session
+    pub cwd: PathBuf,
+    /// Unix timestamp in seconds of the first change
+    pub created_at: i64,
+    pub files: Vec<FileSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSnapshot {
+    pub path: PathBuf,
+    pub state: FileState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum FileState {
+    /// The session created the file
+    Absent,
+    /// Stored under the SHA-256 of its contents
+    Stored { object: String, size: u64 },
+    /// Too large to be snapshotted
+    TooLarge { size: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "outcome")]
+pub enum RestoreOutcome {
+    Restored {
+        path: PathBuf,
+    },
+    /// Removed since the session created it
+    Removed {
+        path: PathBuf,
+    },
+    Failed {
+        path: PathBuf,
+        reason: String,
+    },
+}
+
+impl RestoreOutcome {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Restored { path } | Self::Removed { path } | Self::Failed { path, .. } => path,
+        }
+    }
+}
+
+impl std::fmt::Display for RestoreOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Restored { path } => write!(f, "restored {}", path.display()),
+            Self::Removed { path } => write!(f, "removed  {}", path.display()),
+            Self::Failed { path, reason } => write!(f, "failed   {}: {reason}", path.display()),
+        }
+    }
+}
+
+/// Snapshots the file at `path` before the session of `conversation_id` changes it, unless the
+/// session changed it before. Failures are logged rather than returned, so that they don't stop
+/// the change.
+pub async fn snapshot(os: &Os, conversation_id: &str, path: &Path) {
+    if let Err(err) = try_snapshot(os, conversation_id, path).await {
+        warn!(?err, "failed to snapshot {}", path.display());
+    }
+}
+
+async fn try_snapshot(os: &Os, conversation_id: &str, path: &Path) -> Result<()> {
+    let mut sessions = os.database.get_snapshots()?;
+    if sessions
+        .iter()
+        .any(|session| session.conversation_id == conversation_id && session.files.iter().any(|f| f.path == path))
+    {
+        return Ok(());
+    }
+
+    let state = match os.fs.symlink_metadata(path).await {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => FileState::Absent,
+        Err(err) => return Err(err.into()),
+        Ok(metadata) if metadata.len() > MAX_FILE_SIZE => FileState::TooLarge { size: metadata.len() },
+        Ok(_) => {
+            let contents = os.fs.read(path).await?;
+            let object = hex::encode(Sha256::digest(&contents));
+            let object_path = directories::chat_snapshots_dir()?.join(&object);
+            if !os.fs.exists(&object_path) {
+                if let Some(parent) = object_path.parent() {
+                    os.fs.create_dir_all(parent).await?;
+                }
+                os.fs.write(&object_path, &contents).await?;
+            }
+            FileState::Stored {
+                object,
+                size: contents.len() as u64,
+            }
+        },
+    };
+
+    let file = FileSnapshot {
+        path: path.to_path_buf(),
+        state,
+    };
+    match sessions
+        .iter_mut()
+        .find(|session| session.conversation_id == conversation_id)
+    {
+        Some(session) => session.files.push(file),
+        None => sessions.push(SessionSnapshot {
+            conversation_id: conversation_id.to_string(),
+            cwd: os.env.current_dir()?,
+            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+            files: vec![file],
+        }),
+    }
+    os.database.set_snapshots(&sessions)?;
+    Ok(())
+}
+
+/// Puts the files changed in the session of `conversation_id` back the way they were before it,
+/// only those among `paths` if any are given. Files that can't be restored are reported rather
+/// than stopping the others from being restored.
+pub async fn restore(os: &Os, conversation_id: &str, paths: &[PathBuf]) -> Result<Vec<RestoreOutcome>> {
+    let sessions = os.database.get_snapshots()?;
+    let Some(session) = sessions
+        .iter()
+        .find(|session| session.conversation_id == conversation_id)
+    else {
+        bail!("Tools haven't changed any files in session {conversation_id}");
+    };
+
+    let mut restored = Vec::new();
+    for file in &session.files {
+        if !paths.is_empty() && !paths.contains(&file.path) {
+            continue;
+        }
+        let path = file.path.clone();
+        let outcome = match &file.state {
+            FileState::Absent if !os.fs.exists(&path) => continue,
+            FileState::Absent => match os.fs.remove_file(&path).await {
+                Ok(()) => RestoreOutcome::Removed { path },
+                Err(err) => RestoreOutcome::Failed {
+                    path,
+                    reason: err.to_string(),
+                },
+            },
+            FileState::TooLarge { .. } => RestoreOutcome::Failed {
+                path,
+                reason: "it was too large to be snapshotted".to_string(),
+            },
+            FileState::Stored { object, .. } => match restore_object(os, object, &path).await {
+                Ok(()) => RestoreOutcome::Restored { path },
+                Err(err) => RestoreOutcome::Failed {
+                    path,
+                    reason: err.to_string(),
+                },
+            },
+        };
+        restored.push(outcome);
+    }
+    Ok(restored)
+}
+
+async fn restore_object(os: &Os, object: &str, path: &Path) -> Result<()> {
+    let object_path = directories::chat_snapshots_dir()?.join(object);
+    if !os.fs.exists(&object_path) {
+        bail!("its snapshot was pruned");
+    }
+    if let Some(parent) = path.parent() {
+        os.fs.create_dir_all(parent).await?;
+    }
+    os.fs.copy(&object_path, path).await?;
+    Ok(())
+}
+
+/// Drops the oldest sessions while the store is larger than `chat.snapshotMaxSizeMb`, then
+/// removes the contents no remaining session refers to.
+pub async fn prune(os: &Os) {
+    let sessions = match os.database.get_snapshots() {
+        Ok(sessions) => sessions,
+        Err(err) => {
+            warn!(?err, "failed to read the snapshots");
+            return;
+        },
+    };
+    let max_size_mb = os
+        .database
+        .settings
+        .get_int(Setting::ChatSnapshotMaxSizeMb)
+        .unwrap_or(DEFAULT_MAX_SIZE_MB)
+        .max(0) as u64;
+    let count = sessions.len();
+    let sessions = within_size(sessions, max_size_mb * 1024 * 1024);
+    if sessions.len() < count {
+        if let Err(err) = os.database.set_snapshots(&sessions) {
+            warn!(?err, "failed to update the snapshots");
+            return;
+        }
+    }
+
+    let Ok(dir) = directories::chat_snapshots_dir() else {
+        return;
+    };
+    let Ok(mut entries) = os.fs.read_dir(&dir).await else {
+        return;
+    };
+    let referenced = objects(&sessions).into_keys().collect::<HashSet<_>>();
+    let mut removed = 0;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if referenced.contains(entry.file_name().to_string_lossy().as_ref()) {
+            continue;
+        }
+        let path = dir.join(entry.file_name());
+        match os.fs.remove_file(&path).await {
+            Ok(()) => removed += 1,
+            Err(err) => warn!(?err, path = %path.display(), "failed to remove a snapshot"),
+        }
+    }
+    debug!(removed, sessions = sessions.len(), "pruned snapshots");
+}
+
+/// The sessions to keep so that the contents they refer to take up at most `max_size` bytes,
+/// dropping the oldest first.
+fn within_size(mut sessions: Vec<SessionSnapshot>, max_size: u64) -> Vec<SessionSnapshot> {
+    sessions.sort_by_key(|session| session.created_at);
+    while !sessions.is_empty() && objects(&sessions).values().sum::<u64>() > max_size {
+        sessions.remove(0);
+    }
+    sessions
+}
+
+/// The contents the sessions refer to, along with their sizes.
+fn objects(sessions: &[SessionSnapshot]) -> HashMap<&str, u64> {
+    sessions
+        .iter()
+        .flat_map(|session| &session.files)
+        .filter_map(|file| match &file.state {
+            FileState::Stored { object, size } => Some((object.as_str(), *size)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct RestoreArgs {
+    /// Id of the session to roll back, or a unique prefix of it. The sessions that can be rolled
+    /// back are listed when it is left out
+    #[arg(long)]
+    session: Option<String>,
+    /// Only restore these files
+    paths: Vec<PathBuf>,
+    /// Format of the output
+    #[arg(long, short, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+impl RestoreArgs {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
+        let format = self.format.or(format);
+        let sessions = os.database.get_snapshots()?;
+        let Some(id) = self.session else {
+            let dates = DateTimeFormat::new(&os.database.settings);
+            format.print(|| render_sessions(&sessions, &dates), || &sessions);
+            return Ok(ExitCode::SUCCESS);
+        };
+
+        let matching = sessions
+            .iter()
+            .filter(|session| session.conversation_id.starts_with(&id))
+            .collect::<Vec<_>>();
+        let session = match matching.as_slice() {
+            [session] => session,
+            [] => bail!("Tools haven't changed any files in session {id}, see q restore"),
+            _ => bail!("{id} matches several sessions, see q restore"),
+        };
+
+        let cwd = os.env.current_dir()?;
+        let paths = self.paths.iter().map(|path| cwd.join(path)).collect::<Vec<_>>();
+        let restored = restore(os, &session.conversation_id, &paths).await?;
+        format.print(
+            || match restored.is_empty() {
+                true => "Nothing to restore".to_string(),
+                false => restored.iter().map(|r| r.to_string()).collect::<Vec<_>>().join("\n"),
+            },
+            || &restored,
+        );
+        let failed = restored.iter().any(|r| matches!(r, RestoreOutcome::Failed { .. }));
+        Ok(if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+    }
+}
+
+fn render_sessions(sessions: &[SessionSnapshot], dates: &DateTimeFormat) -> String {
+    if sessions.is_empty() {
+        return "No sessions have changed files yet".to_string();
+    }
+    let mut sessions = sessions.iter().collect::<Vec<_>>();
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at));
+    sessions
+        .iter()
+        .map(|session| {
+            format!(
+                "{}  {}  {:>3} file(s)  {}",
+                session.conversation_id,
+                dates.timestamp(session.created_at),
+                session.files.len(),
+                session.cwd.display()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: &str, created_at: i64, objects: &[(&str, u64)]) -> SessionSnapshot {
+        SessionSnapshot {
+            conversation_id: id.to_string(),
+            cwd: PathBuf::from("/work"),
+            created_at,
+            files: objects
+                .iter()
+                .map(|(object, size)| FileSnapshot {
+                    path: PathBuf::from(format!("/work/{object}")),
+                    state: FileState::Stored {
+                        object: (*object).to_string(),
+                        size: *size,
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_and_restore() {
+        let os = Os::new().await.unwrap();
+        os.fs.create_dir_all("/work").await.unwrap();
+        os.fs.write("/work/main.rs", "v1").await.unwrap();
+        snapshot(&os, "conv", Path::new("/work/main.rs")).await;
+        snapshot(&os, "conv", Path::new("/work/new.rs")).await;
+        os.fs.write("/work/main.rs", "v2").await.unwrap();
+        os.fs.write("/work/new.rs", "new").await.unwrap();
+        // Only the contents from before the first change are kept
+        snapshot(&os, "conv", Path::new("/work/main.rs")).await;
+        os.fs.write("/work/main.rs", "v3").await.unwrap();
+
+        assert!(restore(&os, "other", &[]).await.is_err());
+        let restored = restore(&os, "conv", &[]).await.unwrap();
+        assert_eq!(restored, vec![
+            RestoreOutcome::Restored {
+                path: PathBuf::from("/work/main.rs")
+            },
+            RestoreOutcome::Removed {
+                path: PathBuf::from("/work/new.rs")
+            },
+        ]);
+        assert_eq!(os.fs.read_to_string("/work/main.rs").await.unwrap(), "v1");
+        assert!(!os.fs.exists("/work/new.rs"));
+    }
+
+    #[test]
+    fn test_within_size() {
+        let sessions = vec![
+            session("new", 3, &[("a", 10), ("c", 10)]),
+            session("old", 1, &[("a", 10), ("b", 50)]),
+            session("mid", 2, &[("a", 10)]),
+        ];
+        // Contents shared by sessions are counted once
+        assert_eq!(within_size(sessions.clone(), 70).len(), 3);
+        let kept = within_size(sessions.clone(), 30);
+        assert_eq!(
+            kept.iter().map(|s| s.conversation_id.as_str()).collect::<Vec<_>>(),
+            vec!["mid", "new"]
+        );
+        assert!(within_size(sessions, 0).is_empty());
+    }
+}
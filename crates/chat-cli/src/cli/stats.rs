@@ -0,0 +1,385 @@
+//! Local statistics of how chat is used, shown by `q stats`. Sessions, tool uses and response
+//! latencies are counted per day in the local database, nothing is sent anywhere. Counting can be
+//! turned off with `chat.disableStats`.
+
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Args;
+use eyre::Result;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use time::Date;
+use time::format_description::BorrowedFormatItem;
+use time::macros::format_description;
+use tracing::warn;
+
+use super::OutputFormat;
+use crate::database::settings::Setting;
+use crate::os::Os;
+use crate::util::datetime::{
+    DateTimeFormat,
+    format_duration,
+};
+
+/// Days older than this are dropped from the statistics.
+const RETENTION_DAYS: i64 = 366;
+const DATE_FORMAT: &[BorrowedFormatItem<'_>] = format_description!("[year]-[month]-[day]");
+/// Width of the longest bar of the charts.
+const CHART_WIDTH: usize = 40;
+const TOP_COUNT: usize = 5;
+
+/// The usage of chat over a day.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageDay {
+    /// The day in the user's timezone, as YYYY-MM-DD
+    pub date: String,
+    pub sessions: u32,
+    /// Sessions started by each agent
+    #[serde(default)]
+    pub agents: BTreeMap<String, u32>,
+    /// Outcomes of the uses suggested for each tool
+    #[serde(default)]
+    pub tools: BTreeMap<String, ToolUsage>,
+    pub responses: u32,
+    /// Total time between sending requests and the end of their responses
+    pub response_millis: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolUsage {
+    /// Ran without asking since the tool is trusted
+    pub trusted: u32,
+    pub accepted: u32,
+    pub rejected: u32,
+    /// Denied by the settings of the agent
+    pub denied: u32,
+}
+
+impl ToolUsage {
+    fn total(&self) -> u32 {
+        self.trusted + self.accepted + self.rejected + self.denied
+    }
+
+    fn add(&mut self, other: &ToolUsage) {
+        self.trusted += other.trusted;
+        self.accepted += other.accepted;
+        self.rejected += other.rejected;
+        self.denied += other.denied;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolOutcome {
+    Trusted,
+    Accepted,
+    Rejected,
+    Denied,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum UsageEvent<'a> {
+    SessionStarted { agent: &'a str },
+    ToolUse { tool: &'a str, outcome: ToolOutcome },
+    Response { latency: Duration },
+}
+
+/// Counts `event` in the statistics of the current day, unless `chat.disableStats` is set.
+pub fn record(os: &Os, event: UsageEvent<'_>) {
+    if os
+        .database
+        .settings
+        .get_bool(Setting::ChatDisableStats)
+        .unwrap_or(false)
+    {
+        return;
+    }
+    let result = os.database.get_usage_stats().and_then(|mut days| {
+        let today = DateTimeFormat::new(&os.database.settings).today();
+        apply(&mut days, today, event);
+        os.database.set_usage_stats(&days)
+    });
+    if let Err(err) = result {
+        warn!(?err, "failed to record usage statistics");
+    }
+}
+
+fn apply(days: &mut Vec<UsageDay>, today: Date, event: UsageEvent<'_>) {
+    let date = format_date(today);
+    let cutoff = format_date(today - time::Duration::days(RETENTION_DAYS));
+    days.retain(|day| day.date > cutoff);
+    let day = match days.iter().position(|day| day.date == date) {
+        Some(index) => &mut days[index],
+        None => {
+            days.push(UsageDay {
+                date,
+                ..Default::default()
+            });
+            days.last_mut().expect("a day was just pushed")
+        },
+    };
+
+    match event {
+        UsageEvent::SessionStarted { agent } => {
+            day.sessions += 1;
+            *day.agents.entry(agent.to_string()).or_default() += 1;
+        },
+        UsageEvent::ToolUse { tool, outcome } => {
+            let usage = day.tools.entry(tool.to_string()).or_default();
+            match outcome {
+                ToolOutcome::Trusted => usage.trusted += 1,
+                ToolOutcome::Accepted => usage.accepted += 1,
+                ToolOutcome::Rejected => usage.rejected += 1,
+                ToolOutcome::Denied => usage.denied += 1,
+            }
+        },
+        UsageEvent::Response { latency } => {
+            day.responses += 1;
+            day.response_millis += u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+        },
+    }
+}
+
+fn format_date(date: Date) -> String {
+    date.format(DATE_FORMAT).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct StatsArgs {
+    /// Number of weeks covered, including the current one
+    #[arg(long, default_value_t = 12)]
+    weeks: u32,
+    /// Format of the output
+    #[arg(long, short, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Summary {
+    /// Monday of the first week covered
+    since: String,
+    /// Sessions started in each week, by the date of its Monday
+    sessions_per_week: Vec<(String, u32)>,
+    top_agents: Vec<(String, u32)>,
+    top_tools: Vec<(String, u32)>,
+    tool_uses: ToolUsage,
+    /// Share of the tool uses that were confirmed which were accepted
+    acceptance_rate: Option<f64>,
+    responses: u32,
+    average_response_millis: Option<u64>,
+}
+
+impl StatsArgs {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
+        let format = self.format.or(format);
+        let days = os.database.get_usage_stats()?;
+        let dates = DateTimeFormat::new(&os.database.settings);
+        let summary = summarize(&days, dates.today(), self.weeks.max(1));
+        let disabled = os
+            .database
+            .settings
+            .get_bool(Setting::ChatDisableStats)
+            .unwrap_or(false);
+        format.print(|| render(&summary, &dates, disabled), || &summary);
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn summarize(days: &[UsageDay], today: Date, weeks: u32) -> Summary {
+    let monday = |date: Date| date - time::Duration::days(date.weekday().number_days_from_monday().into());
+    let first_week = monday(today) - time::Duration::weeks((weeks - 1).into());
+    let mut sessions_per_week = (0..weeks)
+        .map(|week| (format_date(first_week + time::Duration::weeks(week.into())), 0))
+        .collect::<Vec<_>>();
+
+    let mut agents = BTreeMap::<String, u32>::new();
+    let mut tools = BTreeMap::<String, ToolUsage>::new();
+    let mut tool_uses = ToolUsage::default();
+    let (mut responses, mut response_millis) = (0, 0);
+    for day in days {
+        let Ok(date) = Date::parse(&day.date, DATE_FORMAT) else {
+            continue;
+        };
+        if date < first_week || date > today {
+            continue;
+        }
+        let week = (date - first_week).whole_weeks() as usize;
+        sessions_per_week[week].1 += day.sessions;
+        for (agent, count) in &day.agents {
+            *agents.entry(agent.clone()).or_default() += count;
+        }
+        for (tool, usage) in &day.tools {
+            tools.entry(tool.clone()).or_default().add(usage);
+            tool_uses.add(usage);
+        }
+        responses += day.responses;
+        response_millis += day.response_millis;
+    }
+
+    let confirmed = tool_uses.accepted + tool_uses.rejected;
+    Summary {
+        since: format_date(first_week),
+        sessions_per_week,
+        top_agents: top(agents.into_iter()),
+        top_tools: top(tools.into_iter().map(|(tool, usage)| (tool, usage.total()))),
+        tool_uses,
+        acceptance_rate: (confirmed > 0).then(|| f64::from(tool_uses.accepted) / f64::from(confirmed)),
+        responses,
+        average_response_millis: (responses > 0).then(|| response_millis / u64::from(responses)),
+    }
+}
+
+/// The entries with the highest counts, highest first.
+fn top(counts: impl Iterator<Item = (String, u32)>) -> Vec<(String, u32)> {
+    let mut counts = counts.collect::<Vec<_>>();
+    counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+    counts.truncate(TOP_COUNT);
+    counts
+}
+
+fn render(summary: &Summary, dates: &DateTimeFormat, disabled: bool) -> String {
+    let mut text = String::new();
+    if disabled {
+        text.push_str("Statistics are not being counted since chat.disableStats is set\n\n");
+    }
+    text.push_str("Sessions per week\n");
+    let sessions_per_week = summary
+        .sessions_per_week
+        .iter()
+        .map(|(week, count)| match Date::parse(week, DATE_FORMAT) {
+            Ok(monday) => (dates.date(monday), *count),
+            Err(_) => (week.clone(), *count),
+        })
+        .collect::<Vec<_>>();
+    text.push_str(&chart(&sessions_per_week));
+    text.push_str("\nTop agents\n");
+    text.push_str(&chart(&summary.top_agents));
+    text.push_str("\nTop tools\n");
+    text.push_str(&chart(&summary.top_tools));
+
+    let ToolUsage {
+        trusted,
+        accepted,
+        rejected,
+        denied,
+    } = summary.tool_uses;
+    text.push_str(&format!(
+        "\nTool uses: {} ({trusted} trusted, {accepted} accepted, {rejected} rejected, {denied} denied)\n",
+        summary.tool_uses.total()
+    ));
+    if let Some(rate) = summary.acceptance_rate {
+        text.push_str(&format!(
+            "Acceptance rate: {:.0}% of the {} confirmed\n",
+            rate * 100.0,
+            accepted + rejected
+        ));
+    }
+    if let Some(average) = summary.average_response_millis {
+        text.push_str(&format!(
+            "Average response time: {} over {} responses\n",
+            format_duration(Duration::from_millis(average)),
+            summary.responses
+        ));
+    }
+    text.trim_end().to_string()
+}
+
+/// A horizontal bar chart of `rows`, scaled to the highest count.
+fn chart(rows: &[(String, u32)]) -> String {
+    if rows.is_empty() {
+        return "  (none)\n".to_string();
+    }
+    let max = rows.iter().map(|(_, count)| *count).max().unwrap_or_default().max(1);
+    let label_width = rows
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or_default();
+    rows.iter()
+        .map(|(label, count)| {
+            let width = (*count as usize * CHART_WIDTH).div_ceil(max as usize);
+            format!("  {label:<label_width$}  {} {count}\n", "█".repeat(width))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::date;
+
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let mut days = Vec::new();
+        // Too old to be kept
+        apply(&mut days, date!(2025 - 01 - 01), UsageEvent::SessionStarted {
+            agent: "default",
+        });
+        let today = date!(2026 - 10 - 16);
+        for (day, agent) in [
+            (date!(2026 - 10 - 05), "default"),
+            (today, "default"),
+            (today, "reviewer"),
+        ] {
+            apply(&mut days, day, UsageEvent::SessionStarted { agent });
+        }
+        for outcome in [
+            ToolOutcome::Trusted,
+            ToolOutcome::Accepted,
+            ToolOutcome::Accepted,
+            ToolOutcome::Rejected,
+        ] {
+            apply(&mut days, today, UsageEvent::ToolUse {
+                tool: "fs_write",
+                outcome,
+            });
+        }
+        apply(&mut days, today, UsageEvent::ToolUse {
+            tool: "fs_read",
+            outcome: ToolOutcome::Trusted,
+        });
+        for millis in [1000, 3000] {
+            apply(&mut days, today, UsageEvent::Response {
+                latency: Duration::from_millis(millis),
+            });
+        }
+        assert_eq!(days.len(), 2);
+
+        let summary = summarize(&days, today, 2);
+        assert_eq!(summary.since, "2026-10-05");
+        assert_eq!(summary.sessions_per_week, [
+            ("2026-10-05".to_string(), 1),
+            ("2026-10-12".to_string(), 2)
+        ]);
+        assert_eq!(summary.top_agents, [
+            ("default".to_string(), 2),
+            ("reviewer".to_string(), 1)
+        ]);
+        assert_eq!(summary.top_tools, [
+            ("fs_write".to_string(), 4),
+            ("fs_read".to_string(), 1)
+        ]);
+        assert_eq!(summary.acceptance_rate, Some(2.0 / 3.0));
+        assert_eq!(summary.average_response_millis, Some(2000));
+
+        // Weeks before the window are left out
+        let summary = summarize(&days, today, 1);
+        assert_eq!(summary.sessions_per_week, [("2026-10-12".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_chart() {
+        let rows = [("a".to_string(), 4), ("long".to_string(), 1)];
+        let chart = chart(&rows);
+        let lines = chart.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], format!("  a     {} 4", "█".repeat(CHART_WIDTH)));
+        assert_eq!(lines[1], format!("  long  {} 1", "█".repeat(CHART_WIDTH / 4)));
+    }
+}
@@ -7,7 +7,9 @@ use std::process::{
 use std::time::Duration;
 
 use anstream::{
+    eprint,
     eprintln,
+    print,
     println,
 };
 use clap::{
@@ -20,7 +22,9 @@ use eyre::{
     Result,
     bail,
 };
+use serde::Serialize;
 use serde_json::json;
+use time::format_description::well_known::Rfc3339;
 use tokio::signal::ctrl_c;
 use tracing::{
     error,
@@ -28,7 +32,10 @@ use tracing::{
 };
 
 use super::OutputFormat;
-use crate::api_client::list_available_profiles;
+use crate::api_client::{
+    Endpoint,
+    list_available_profiles,
+};
 use crate::auth::builder_id::{
     BuilderIdToken,
     PollCreateToken,
@@ -36,12 +43,22 @@ use crate::auth::builder_id::{
     poll_create_token,
     start_device_authorization,
 };
-use crate::auth::pkce::start_pkce_authorization;
+use crate::auth::identities;
+use crate::auth::pkce::{
+    start_manual_pkce_authorization,
+    start_pkce_authorization,
+};
+use crate::cli::chat::{
+    model_name,
+    session_default_model_id,
+};
+use crate::database::AuthProfile;
 use crate::os::Os;
 use crate::telemetry::{
     QProfileSwitchIntent,
     TelemetryResult,
 };
+use crate::util::datetime::format_duration;
 use crate::util::spinner::{
     Spinner,
     SpinnerComponent,
@@ -72,16 +89,38 @@ pub struct LoginArgs {
     /// redirects cannot be handled.
     #[arg(long)]
     pub use_device_flow: bool,
+
+    /// Print the authorization URL instead of opening a browser, and paste back the URL the
+    /// browser is redirected to. Useful over SSH when the device flow isn't enabled.
+    #[arg(long, conflicts_with = "use_device_flow")]
+    pub no_browser: bool,
+
+    /// Save the login under an alias, keeping the active login to switch back to with
+    /// `q user switch`
+    #[arg(long = "as", value_name = "ALIAS")]
+    pub alias: Option<String>,
+
+    /// Select the IAM Identity Center profile with this ARN instead of asking
+    #[arg(long, value_name = "ARN")]
+    pub profile_arn: Option<String>,
 }
 
 impl LoginArgs {
     pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
         if crate::auth::is_logged_in(&mut os.database).await {
-            eyre::bail!(
-                "Already logged in, please logout with {} first",
-                format!("{CLI_BINARY_NAME} logout").magenta()
-            );
+            if self.alias.is_none() {
+                eyre::bail!(
+                    "Already logged in, please logout with {} first, or keep this login with {}",
+                    format!("{CLI_BINARY_NAME} logout").magenta(),
+                    format!("{CLI_BINARY_NAME} login --as <alias>").magenta()
+                );
+            }
+            if let Some(saved) = identities::save_active(&os.database).await? {
+                eprintln!("Saved the active login as {}", saved.bold());
+            }
+            crate::auth::logout(&mut os.database).await?;
         }
+        os.database.set_active_identity(self.alias.as_deref())?;
 
         let login_method = match self.license {
             Some(LicenseType::Free) => AuthMethod::BuilderId,
@@ -102,6 +141,9 @@ impl LoginArgs {
                 }
             },
         };
+        if login_method == AuthMethod::BuilderId && self.profile_arn.is_some() {
+            bail!("--profile-arn is only for IAM Identity Center logins");
+        }
 
         match login_method {
             AuthMethod::BuilderId | AuthMethod::IdentityCenter => {
@@ -129,7 +171,9 @@ impl LoginArgs {
 
                 // Remote machine won't be able to handle browser opening and redirects,
                 // hence always use device code flow.
-                if is_remote() || self.use_device_flow {
+                if self.no_browser {
+                    try_pasted_authorization(os, start_url.clone(), region.clone()).await?;
+                } else if is_remote() || self.use_device_flow {
                     try_device_authorization(os, start_url.clone(), region.clone()).await?;
                 } else {
                     let (client, registration) = start_pkce_authorization(start_url.clone(), region.clone()).await?;
@@ -166,16 +210,32 @@ impl LoginArgs {
         };
 
         if login_method == AuthMethod::IdentityCenter {
-            select_profile_interactive(os, true).await?;
+            match &self.profile_arn {
+                Some(arn) => {
+                    let chosen = select_profile(os, ProfileSelector::Arn(arn), QProfileSwitchIntent::Auth).await?;
+                    eprintln!("Profile set to {}", chosen.profile_name);
+                },
+                None => select_profile_interactive(os, true).await?,
+            }
+        }
+
+        if self.alias.is_some() {
+            identities::save_active(&os.database).await?;
         }
 
         Ok(ExitCode::SUCCESS)
     }
 }
 
-pub async fn logout(os: &mut Os) -> Result<ExitCode> {
+pub async fn logout(os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
+    let _ = identities::forget_active(&os.database).await;
     let _ = crate::auth::logout(&mut os.database).await;
 
+    if format.is_json() {
+        format.print(String::new, || json!({ "loggedIn": false }));
+        return Ok(ExitCode::SUCCESS);
+    }
+
     eprintln!("You are now logged out");
     eprintln!(
         "Run {} to log back in to {PRODUCT_NAME}",
@@ -185,43 +245,168 @@ pub async fn logout(os: &mut Os) -> Result<ExitCode> {
     Ok(ExitCode::SUCCESS)
 }
 
-pub async fn export_token(os: &mut Os) -> Result<ExitCode> {
-    eprintln!();
-    eprintln!("{}", "Security Warning:".yellow().bold());
-    eprintln!("{}", "Never share your authentication tokens.".yellow());
-    eprintln!("{}", "These tokens grant access to your AWS account.".yellow());
-    eprintln!();
-
-    let token = BuilderIdToken::load(&os.database).await?;
-    match token {
-        Some(token) => {
-            let json = json!({
-                "accessToken": token.access_token.0,
-                "refreshToken": token.refresh_token.map(|s| s.0),
-            });
-            println!("{}", serde_json::to_string_pretty(&json)?);
-            Ok(ExitCode::SUCCESS)
-        }
-        None => {
-            bail!("Not logged in");
-        }
+/// The format `q user export-token` prints the tokens in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TokenFormat {
+    /// A JSON object with the tokens, their expiry and the login they belong to
+    #[default]
+    Json,
+    /// The JSON object a credential_process prints, with the access token and its expiry
+    CredentialProcess,
+}
+
+/// The tokens as printed by `q user export-token`. This is a contract with the tools reading it:
+/// fields can be added, but not removed or changed, without bumping the version.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenOutput {
+    version: u32,
+    access_token: String,
+    refresh_token: Option<String>,
+    /// RFC 3339
+    expires_at: String,
+    account_type: &'static str,
+    region: Option<String>,
+    start_url: Option<String>,
+}
+
+/// The access token as printed by `q user export-token --format credential-process`, in the
+/// format of the output of an AWS credential_process. The same contract as [TokenOutput] applies.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct CredentialProcessOutput {
+    version: u32,
+    access_token: String,
+    /// RFC 3339
+    expiration: String,
+}
+
+/// The token of the active login, refreshed if it expired.
+async fn load_token(os: &Os) -> Result<BuilderIdToken> {
+    match BuilderIdToken::load(&os.database).await? {
+        Some(token) => Ok(token),
+        None => bail!(
+            "Not logged in, please log in with {}",
+            format!("{CLI_BINARY_NAME} login").magenta()
+        ),
     }
 }
 
+pub async fn export_token(os: &mut Os, format: TokenFormat) -> Result<ExitCode> {
+    // The output of a credential_process is read by other tools, which don't show warnings
+    if format == TokenFormat::Json {
+        eprintln!();
+        eprintln!("{}", "Security Warning:".yellow().bold());
+        eprintln!("{}", "Never share your authentication tokens.".yellow());
+        eprintln!("{}", "These tokens grant access to your AWS account.".yellow());
+        eprintln!();
+    }
+
+    let token = load_token(os).await?;
+    println!("{}", token_json(token, format)?);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The tokens as `q user export-token` prints them in `format`.
+fn token_json(token: BuilderIdToken, format: TokenFormat) -> Result<String> {
+    let expires_at = token.expires_at.format(&Rfc3339)?;
+    Ok(match format {
+        TokenFormat::Json => serde_json::to_string_pretty(&TokenOutput {
+            version: 1,
+            account_type: match token.token_type() {
+                TokenType::BuilderId => "BuilderId",
+                TokenType::IamIdentityCenter => "IamIdentityCenter",
+            },
+            access_token: token.access_token.0,
+            refresh_token: token.refresh_token.map(|s| s.0),
+            expires_at,
+            region: token.region,
+            start_url: token.start_url,
+        })?,
+        TokenFormat::CredentialProcess => serde_json::to_string(&CredentialProcessOutput {
+            version: 1,
+            access_token: token.access_token.0,
+            expiration: expires_at,
+        })?,
+    })
+}
+
+/// Prints the access token of the active login, only the token when `raw` so that it can be
+/// piped into other tools.
+pub async fn token(os: &mut Os, raw: bool) -> Result<ExitCode> {
+    let token = load_token(os).await?;
+    if raw {
+        print!("{}", token.access_token.0);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let expires_at = token.expires_at.format(&Rfc3339)?;
+    println!("{}", token.access_token.0);
+    eprintln!("Expires at {expires_at}");
+    Ok(ExitCode::SUCCESS)
+}
+
+pub async fn switch(os: &mut Os, alias: Option<String>, format: OutputFormat) -> Result<ExitCode> {
+    let active = os.database.get_active_identity()?;
+    let Some(alias) = alias else {
+        let aliases = os.database.get_identity_aliases()?;
+        format.print(
+            || {
+                aliases
+                    .iter()
+                    .map(|alias| match active.as_ref() == Some(alias) {
+                        true => format!("* {alias}"),
+                        false => format!("  {alias}"),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            },
+            || json!({ "active": active, "saved": aliases }),
+        );
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    if active.as_deref() == Some(alias.as_str()) {
+        format.print(|| format!("Already using {alias}"), || json!({ "active": alias }));
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !identities::switch(&mut os.database, &alias).await? {
+        bail!(
+            "No login is saved as {alias}, save one with {}",
+            format!("{CLI_BINARY_NAME} login --as {alias}").magenta()
+        );
+    }
+    format.print(|| format!("Switched to {alias}"), || json!({ "active": alias }));
+    Ok(ExitCode::SUCCESS)
+}
+
 #[derive(Args, Debug, PartialEq, Eq, Clone, Default)]
 pub struct WhoamiArgs {
     /// Output format to use
     #[arg(long, short, value_enum, default_value_t)]
     format: OutputFormat,
+    /// Also show when the token expires, its scopes, the regions in use and the current model
+    #[arg(long)]
+    detailed: bool,
 }
 
 impl WhoamiArgs {
-    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
+        let format = self.format.or(format);
         let builder_id = BuilderIdToken::load(&os.database).await;
 
         match builder_id {
             Ok(Some(token)) => {
-                self.format.print(
+                let profile = match token.token_type() {
+                    TokenType::BuilderId => None,
+                    TokenType::IamIdentityCenter => os.database.get_auth_profile().ok().flatten(),
+                };
+                let alias = os.database.get_active_identity().ok().flatten();
+                let details = match self.detailed {
+                    true => Some(TokenDetails::new(os, &token).await),
+                    false => None,
+                };
+                format.print(
                     || match token.token_type() {
                         TokenType::BuilderId => "Logged in with Builder ID".into(),
                         TokenType::IamIdentityCenter => {
@@ -232,33 +417,109 @@ impl WhoamiArgs {
                         },
                     },
                     || {
-                        json!({
+                        let mut output = json!({
                             "accountType": match token.token_type() {
                                 TokenType::BuilderId => "BuilderId",
                                 TokenType::IamIdentityCenter => "IamIdentityCenter",
                             },
                             "startUrl": token.start_url,
                             "region": token.region,
-                        })
+                            "profile": profile.as_ref().map(ProfileOutput::from),
+                            "alias": alias,
+                        });
+                        if let (Some(details), Some(output)) = (&details, output.as_object_mut()) {
+                            if let Ok(serde_json::Value::Object(details)) = serde_json::to_value(details) {
+                                output.extend(details);
+                            }
+                        }
+                        output
                     },
                 );
 
-                if matches!(token.token_type(), TokenType::IamIdentityCenter) {
-                    if let Ok(Some(profile)) = os.database.get_auth_profile() {
-                        color_print::cprintln!("\n<em>Profile:</em>\n{}\n{}\n", profile.profile_name, profile.arn);
-                    }
+                if let (Some(alias), false) = (&alias, format.is_json()) {
+                    color_print::cprintln!("Saved as <em>{}</em>", alias);
+                }
+                if let (Some(profile), false) = (&profile, format.is_json()) {
+                    color_print::cprintln!("\n<em>Profile:</em>\n{}\n{}\n", profile.profile_name, profile.arn);
+                }
+                if let (Some(details), false) = (&details, format.is_json()) {
+                    details.print();
                 }
 
                 Ok(ExitCode::SUCCESS)
             },
             _ => {
-                self.format.print(|| "Not logged in", || json!({ "account": null }));
+                format.print(|| "Not logged in", || json!({ "account": null }));
                 Ok(ExitCode::FAILURE)
             },
         }
     }
 }
 
+/// What `whoami --detailed` shows on top of the account.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenDetails {
+    expires_at: String,
+    /// Negative once the token expired
+    expires_in_seconds: i64,
+    scopes: Vec<String>,
+    oauth_flow: String,
+    /// The region of the OIDC client the token was issued to
+    sso_region: String,
+    /// The region requests are sent to, which follows the profile
+    api_region: String,
+    model: ModelOutput,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelOutput {
+    id: String,
+    name: Option<String>,
+}
+
+impl TokenDetails {
+    async fn new(os: &Os, token: &BuilderIdToken) -> Self {
+        let model_id = session_default_model_id(os).await;
+        Self {
+            expires_at: token.expires_at.format(&Rfc3339).unwrap_or_default(),
+            expires_in_seconds: (token.expires_at - time::OffsetDateTime::now_utc()).whole_seconds(),
+            scopes: token.scopes.clone().unwrap_or_default(),
+            oauth_flow: format!("{:?}", token.oauth_flow),
+            sso_region: token.oidc_region().to_string(),
+            api_region: Endpoint::configured_value(&os.database).region.to_string(),
+            model: ModelOutput {
+                name: model_name(&model_id).map(str::to_string),
+                id: model_id,
+            },
+        }
+    }
+
+    fn print(&self) {
+        let validity = match u64::try_from(self.expires_in_seconds) {
+            Ok(secs) => format!("in {}", format_duration(Duration::from_secs(secs))),
+            Err(_) => "expired, it is refreshed on the next request".to_string(),
+        };
+        let scopes = match self.scopes.is_empty() {
+            true => "none".to_string(),
+            false => self.scopes.join(", "),
+        };
+        color_print::cprintln!("\n<em>Token:</em>");
+        println!("Expires at {} ({validity})", self.expires_at);
+        println!("Scopes: {scopes}");
+        println!("OAuth flow: {}", self.oauth_flow);
+        color_print::cprintln!("\n<em>Regions:</em>");
+        println!("Sign-in: {}", self.sso_region);
+        println!("API: {}", self.api_region);
+        color_print::cprintln!("\n<em>Model:</em>");
+        match &self.model.name {
+            Some(name) => println!("{name} ({})", self.model.id),
+            None => println!("{}", self.model.id),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum LicenseType {
     /// Free license with Builder ID
@@ -267,13 +528,67 @@ pub enum LicenseType {
     Pro,
 }
 
-pub async fn profile(os: &mut Os) -> Result<ExitCode> {
+/// An IAM Identity Center profile as printed in the JSON formats.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileOutput {
+    profile_name: String,
+    arn: String,
+}
+
+impl From<&AuthProfile> for ProfileOutput {
+    fn from(profile: &AuthProfile) -> Self {
+        Self {
+            profile_name: profile.profile_name.clone(),
+            arn: profile.arn.clone(),
+        }
+    }
+}
+
+#[derive(Args, Debug, PartialEq, Eq, Clone, Default)]
+pub struct ProfileArgs {
+    /// Select the profile with this ARN instead of asking
+    #[arg(long, conflicts_with = "name")]
+    pub arn: Option<String>,
+    /// Select the profile with this name instead of asking
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+pub async fn profile(os: &mut Os, args: ProfileArgs, format: OutputFormat) -> Result<ExitCode> {
     if let Ok(Some(token)) = BuilderIdToken::load(&os.database).await {
         if matches!(token.token_type(), TokenType::BuilderId) {
             bail!("This command is only available for Pro users");
         }
     }
 
+    let selector = match (&args.arn, &args.name) {
+        (Some(arn), _) => Some(ProfileSelector::Arn(arn)),
+        (None, Some(name)) => Some(ProfileSelector::Name(name)),
+        (None, None) => None,
+    };
+    if let Some(selector) = selector {
+        let chosen = select_profile(os, selector, QProfileSwitchIntent::User).await?;
+        format.print(
+            || format!("Profile set to {} ({})", chosen.profile_name, chosen.arn),
+            || json!({ "active": ProfileOutput::from(&chosen) }),
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Scripts cannot answer the prompt, so they are shown the profiles to choose from instead
+    if format.is_json() {
+        let profiles = list_available_profiles(&os.env, &os.fs, &mut os.database).await?;
+        let active = os.database.get_auth_profile()?;
+        format.print(String::new, || {
+            json!({
+                "active": active.as_ref().map(ProfileOutput::from),
+                "available": profiles.iter().map(ProfileOutput::from).collect::<Vec<_>>(),
+            })
+        });
+        return Ok(ExitCode::SUCCESS);
+    }
+
     select_profile_interactive(os, false).await?;
 
     Ok(ExitCode::SUCCESS)
@@ -298,11 +613,27 @@ impl Display for AuthMethod {
 
 
 
+
 #[derive(Subcommand, Debug, PartialEq, Eq)]
 #[derive(Clone)]
 pub enum UserSubcommand {
-    Profile,
-    ExportToken,
+    Profile(ProfileArgs),
+    /// Print the access and refresh tokens of the active login, refreshed if they expired
+    ExportToken {
+        /// Format of the tokens
+        #[arg(long, value_enum, default_value_t)]
+        format: TokenFormat,
+    },
+    /// Print the access token of the active login, refreshed if it expired
+    Token {
+        /// Print only the token, without a newline or its expiry, to pipe it into other tools
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Switch to a login saved with `q login --as <alias>`, or list the saved logins
+    Switch {
+        alias: Option<String>,
+    },
 }
 
 #[derive(Args, Debug, PartialEq, Eq, Clone)]
@@ -312,14 +643,36 @@ pub struct UserArgs {
 }
 
 impl UserArgs {
-    pub async fn execute(self, os: &mut Os) -> Result<ExitCode> {
+    pub async fn execute(self, os: &mut Os, format: OutputFormat) -> Result<ExitCode> {
         match self.subcommand {
-            UserSubcommand::Profile => profile(os).await,
-            UserSubcommand::ExportToken => export_token(os).await,
+            UserSubcommand::Profile(args) => profile(os, args, format).await,
+            UserSubcommand::ExportToken { format } => export_token(os, format).await,
+            UserSubcommand::Token { raw } => token(os, raw).await,
+            UserSubcommand::Switch { alias } => switch(os, alias, format).await,
         }
     }
 }
 
+async fn try_pasted_authorization(os: &mut Os, start_url: Option<String>, region: Option<String>) -> Result<()> {
+    let (client, registration) = start_manual_pkce_authorization(start_url, region).await?;
+
+    println!();
+    println!("Open this URL in a browser and approve the request:");
+    println!("{}", registration.url);
+    println!();
+    println!("The browser is then redirected to a page that fails to load, copy its URL from the address bar");
+    eprint!("Paste the URL: ");
+
+    let mut pasted = String::new();
+    std::io::stdin().read_line(&mut pasted)?;
+    registration
+        .finish_manual(&client, &pasted, Some(&mut os.database))
+        .await?;
+    os.telemetry.send_user_logged_in().ok();
+    println!("Logged in");
+    Ok(())
+}
+
 async fn try_device_authorization(os: &mut Os, start_url: Option<String>, region: Option<String>) -> Result<()> {
     let device_auth = start_device_authorization(&os.database, start_url.clone(), region.clone()).await?;
 
@@ -374,6 +727,59 @@ async fn try_device_authorization(os: &mut Os, start_url: Option<String>, region
     Ok(())
 }
 
+/// How a profile is selected without asking.
+#[derive(Debug, Clone, Copy)]
+enum ProfileSelector<'a> {
+    Arn(&'a str),
+    Name(&'a str),
+}
+
+/// Selects the available profile matching `selector`, for scripts which can't answer the prompt.
+async fn select_profile(
+    os: &mut Os,
+    selector: ProfileSelector<'_>,
+    intent: QProfileSwitchIntent,
+) -> Result<AuthProfile> {
+    let profiles = list_available_profiles(&os.env, &os.fs, &mut os.database).await?;
+    let matching = profiles
+        .iter()
+        .filter(|profile| match selector {
+            ProfileSelector::Arn(arn) => profile.arn == arn,
+            ProfileSelector::Name(name) => profile.profile_name == name,
+        })
+        .collect::<Vec<_>>();
+
+    let chosen = match (matching.as_slice(), selector) {
+        ([chosen], _) => (*chosen).clone(),
+        ([], _) => {
+            let available = profiles
+                .iter()
+                .map(|p| format!("  {} (arn: {})", p.profile_name, p.arn))
+                .collect::<Vec<_>>();
+            bail!(
+                "No such profile is available. The available profiles are:\n{}",
+                available.join("\n")
+            );
+        },
+        (_, ProfileSelector::Name(name)) => bail!("Several profiles are named {name}, select one with --arn"),
+        (_, ProfileSelector::Arn(arn)) => bail!("Several profiles have the ARN {arn}"),
+    };
+    os.database.set_auth_profile(&chosen)?;
+
+    if let Some(profile_region) = chosen.arn.split(':').nth(3) {
+        os.telemetry
+            .send_did_select_profile(
+                intent,
+                profile_region.to_string(),
+                TelemetryResult::Succeeded,
+                os.database.get_idc_region()?,
+                Some(profiles.len() as i64),
+            )
+            .ok();
+    }
+    Ok(chosen)
+}
+
 async fn select_profile_interactive(os: &mut Os, whoami: bool) -> Result<()> {
     let mut spinner = Spinner::new(vec![
         SpinnerComponent::Spinner,
@@ -466,3 +872,27 @@ async fn select_profile_interactive(os: &mut Os, whoami: bool) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::builder_id::OAuthFlow;
+    use crate::database::Secret;
+
+    #[test]
+    fn test_token_json_credential_process() {
+        let token = BuilderIdToken {
+            access_token: Secret("access".to_string()),
+            expires_at: time::OffsetDateTime::from_unix_timestamp(1_767_225_600).unwrap(),
+            refresh_token: Some(Secret("refresh".to_string())),
+            region: Some("us-east-1".to_string()),
+            start_url: None,
+            oauth_flow: OAuthFlow::DeviceCode,
+            scopes: None,
+        };
+        assert_eq!(
+            token_json(token, TokenFormat::CredentialProcess).unwrap(),
+            r#"{"Version":1,"AccessToken":"access","Expiration":"2026-01-01T00:00:00Z"}"#
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Secrets stored in the keychain of the OS instead of the database file, when
+//! `auth.secretStorage` is `keychain`: the macOS Keychain, the Windows Credential Manager, or the
+//! Secret Service through `secret-tool` on Linux.
+
+use super::DatabaseError;
+
+/// The service secrets are stored under, with their key as the account.
+const SERVICE: &str = "amazon-q-cli";
+
+fn keychain_error(err: impl std::fmt::Display) -> DatabaseError {
+    DatabaseError::Keychain(err.to_string())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use security_framework::passwords::{
+        delete_generic_password,
+        get_generic_password,
+        set_generic_password,
+    };
+
+    use super::{
+        DatabaseError,
+        SERVICE,
+        keychain_error,
+    };
+
+    const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+
+    pub fn get(key: &str) -> Result<Option<String>, DatabaseError> {
+        match get_generic_password(SERVICE, key) {
+            Ok(secret) => Ok(Some(String::from_utf8(secret)?)),
+            Err(err) if err.code() == ERR_SEC_ITEM_NOT_FOUND => Ok(None),
+            Err(err) => Err(keychain_error(err)),
+        }
+    }
+
+    pub fn set(key: &str, value: &str) -> Result<(), DatabaseError> {
+        set_generic_password(SERVICE, key, value.as_bytes()).map_err(keychain_error)
+    }
+
+    pub fn delete(key: &str) -> Result<(), DatabaseError> {
+        match delete_generic_password(SERVICE, key) {
+            Ok(()) => Ok(()),
+            Err(err) if err.code() == ERR_SEC_ITEM_NOT_FOUND => Ok(()),
+            Err(err) => Err(keychain_error(err)),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Win32::Foundation::ERROR_NOT_FOUND;
+    use windows::Win32::Security::Credentials::{
+        CRED_PERSIST_LOCAL_MACHINE,
+        CRED_TYPE_GENERIC,
+        CREDENTIALW,
+        CredDeleteW,
+        CredFree,
+        CredReadW,
+        CredWriteW,
+    };
+    use windows::core::{
+        PCWSTR,
+        PWSTR,
+    };
+
+    use super::{
+        DatabaseError,
+        SERVICE,
+        keychain_error,
+    };
+
+    /// The size the Credential Manager limits a credential to, longer secrets are split in several.
+    const MAX_BLOB_SIZE: usize = 5 * 512;
+
+    fn target(key: &str, chunk: usize) -> Vec<u16> {
+        let target = match chunk {
+            0 => format!("{SERVICE}:{key}"),
+            _ => format!("{SERVICE}:{key}#{chunk}"),
+        };
+        target.encode_utf16().chain([0]).collect()
+    }
+
+    fn is_not_found(err: &windows::core::Error) -> bool {
+        err.code() == ERROR_NOT_FOUND.to_hresult()
+    }
+
+    fn read(key: &str, chunk: usize) -> Result<Option<Vec<u8>>, DatabaseError> {
+        let target = target(key, chunk);
+        let mut credential = std::ptr::null_mut::<CREDENTIALW>();
+        // SAFETY: the credential read is freed once its blob is copied
+        match unsafe { CredReadW(PCWSTR(target.as_ptr()), CRED_TYPE_GENERIC, None, &mut credential) } {
+            Ok(()) => unsafe {
+                let blob = match (*credential).CredentialBlobSize {
+                    0 => Vec::new(),
+                    size => std::slice::from_raw_parts((*credential).CredentialBlob, size as usize).to_vec(),
+                };
+                CredFree(credential as *const _);
+                Ok(Some(blob))
+            },
+            Err(err) if is_not_found(&err) => Ok(None),
+            Err(err) => Err(keychain_error(err)),
+        }
+    }
+
+    fn write(key: &str, chunk: usize, blob: &[u8]) -> Result<(), DatabaseError> {
+        let mut target = target(key, chunk);
+        let credential = CREDENTIALW {
+            Type: CRED_TYPE_GENERIC,
+            TargetName: PWSTR(target.as_mut_ptr()),
+            CredentialBlobSize: blob.len() as u32,
+            CredentialBlob: blob.as_ptr() as *mut u8,
+            Persist: CRED_PERSIST_LOCAL_MACHINE,
+            ..Default::default()
+        };
+        // SAFETY: the credential only points to buffers that outlive the call
+        unsafe { CredWriteW(&credential, 0) }.map_err(keychain_error)
+    }
+
+    /// Deletes a chunk, returning whether it existed.
+    fn remove(key: &str, chunk: usize) -> Result<bool, DatabaseError> {
+        let target = target(key, chunk);
+        match unsafe { CredDeleteW(PCWSTR(target.as_ptr()), CRED_TYPE_GENERIC, None) } {
+            Ok(()) => Ok(true),
+            Err(err) if is_not_found(&err) => Ok(false),
+            Err(err) => Err(keychain_error(err)),
+        }
+    }
+
+    pub fn get(key: &str) -> Result<Option<String>, DatabaseError> {
+        let mut secret = Vec::new();
+        let mut chunk = 0;
+        while let Some(blob) = read(key, chunk)? {
+            secret.extend(blob);
+            chunk += 1;
+        }
+        match chunk {
+            0 => Ok(None),
+            _ => Ok(Some(String::from_utf8(secret)?)),
+        }
+    }
+
+    pub fn set(key: &str, value: &str) -> Result<(), DatabaseError> {
+        let mut chunks = 0;
+        for (chunk, blob) in value.as_bytes().chunks(MAX_BLOB_SIZE).enumerate() {
+            write(key, chunk, blob)?;
+            chunks += 1;
+        }
+        // Remove the chunks left over from a longer secret
+        while remove(key, chunks)? {
+            chunks += 1;
+        }
+        Ok(())
+    }
+
+    pub fn delete(key: &str) -> Result<(), DatabaseError> {
+        let mut chunk = 0;
+        while remove(key, chunk)? {
+            chunk += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::io::Write;
+    use std::process::{
+        Command,
+        Output,
+        Stdio,
+    };
+
+    use super::{
+        DatabaseError,
+        SERVICE,
+        keychain_error,
+    };
+
+    fn secret_tool(args: &[&str], input: Option<&str>) -> Result<Output, DatabaseError> {
+        let mut child = Command::new("secret-tool")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| keychain_error(format!("failed to run secret-tool, which is part of libsecret: {err}")))?;
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(input.as_bytes())?;
+        }
+        Ok(child.wait_with_output()?)
+    }
+
+    fn failure(output: &Output) -> DatabaseError {
+        keychain_error(format!(
+            "secret-tool failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+
+    pub fn get(key: &str) -> Result<Option<String>, DatabaseError> {
+        let output = secret_tool(&["lookup", "service", SERVICE, "account", key], None)?;
+        match (output.status.success(), output.stderr.is_empty()) {
+            (true, _) => Ok(Some(String::from_utf8(output.stdout)?)),
+            // A secret that isn't stored fails without saying anything
+            (false, true) => Ok(None),
+            (false, false) => Err(failure(&output)),
+        }
+    }
+
+    pub fn set(key: &str, value: &str) -> Result<(), DatabaseError> {
+        let label = format!("{SERVICE} {key}");
+        let output = secret_tool(
+            &["store", "--label", &label, "service", SERVICE, "account", key],
+            Some(value),
+        )?;
+        match output.status.success() {
+            true => Ok(()),
+            false => Err(failure(&output)),
+        }
+    }
+
+    pub fn delete(key: &str) -> Result<(), DatabaseError> {
+        let output = secret_tool(&["clear", "service", SERVICE, "account", key], None)?;
+        match output.status.success() || output.stderr.is_empty() {
+            true => Ok(()),
+            false => Err(failure(&output)),
+        }
+    }
+}
+
+/// Runs `f` on a thread where blocking is fine, since the keychain may keep a call waiting, e.g.
+/// until the user unlocks it.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, DatabaseError> + Send + 'static,
+) -> Result<T, DatabaseError> {
+    tokio::task::spawn_blocking(f).await.map_err(keychain_error)?
+}
+
+pub async fn get(key: &str) -> Result<Option<String>, DatabaseError> {
+    let key = key.to_string();
+    blocking(move || platform::get(&key)).await
+}
+
+pub async fn set(key: &str, value: &str) -> Result<(), DatabaseError> {
+    let (key, value) = (key.to_string(), value.to_string());
+    blocking(move || platform::set(&key, &value)).await
+}
+
+pub async fn delete(key: &str) -> Result<(), DatabaseError> {
+    let key = key.to_string();
+    blocking(move || platform::delete(&key)).await
+}
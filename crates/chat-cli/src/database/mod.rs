@@ -1,3 +1,4 @@
+mod keychain;
 pub mod settings;
 
 use std::ops::Deref;
@@ -25,16 +26,27 @@ use serde_json::{
     Map,
     Value,
 };
-use settings::Settings;
+use settings::{
+    Setting,
+    Settings,
+};
 use thiserror::Error;
 use tracing::{
+    debug,
     error,
     info,
     trace,
+    warn,
 };
 use uuid::Uuid;
 
-use crate::cli::ConversationState;
+use crate::cli::{
+    Artifact,
+    ConversationState,
+    InterruptedTurn,
+    SessionSnapshot,
+    UsageDay,
+};
 use crate::util::directories::{
     DirectoryError,
     database_path,
@@ -58,9 +70,17 @@ const CLIENT_ID_KEY: &str = "telemetryClientId";
 const CODEWHISPERER_PROFILE_KEY: &str = "api.codewhisperer.profile";
 const START_URL_KEY: &str = "auth.idc.start-url";
 const IDC_REGION_KEY: &str = "auth.idc.region";
+const IDENTITIES_KEY: &str = "auth.identities";
+const ACTIVE_IDENTITY_KEY: &str = "auth.activeIdentity";
 // We include this key to remove for backwards compatibility
 const CUSTOMIZATION_STATE_KEY: &str = "api.selectedCustomization";
 const PROFILE_MIGRATION_KEY: &str = "profile.Migrated";
+const PLAN_APPROVED_WORKSPACE_KEY_PREFIX: &str = "chat.planApproved.";
+const WORKSPACE_AGENT_TRUST_KEY_PREFIX: &str = "chat.workspaceAgentTrusted.";
+const INTERRUPTED_TURN_KEY_PREFIX: &str = "chat.interruptedTurn.";
+const ARTIFACTS_KEY: &str = "chat.artifacts";
+const USAGE_STATS_KEY: &str = "chat.usageStats";
+const SNAPSHOTS_KEY: &str = "chat.snapshots";
 
 const MIGRATIONS: &[Migration] = migrations![
     "000_migration_table",
@@ -144,6 +164,8 @@ pub enum DatabaseError {
     StrFromUtf8(#[from] std::str::Utf8Error),
     #[error("`{}` is not a valid setting", .0)]
     InvalidSetting(String),
+    #[error("Failed to access the keychain: {}", .0)]
+    Keychain(String),
 }
 
 impl<T> From<PoisonError<T>> for DatabaseError {
@@ -220,12 +242,15 @@ impl Database {
             }
         }
 
-        Ok(Self {
+        let mut database = Self {
             pool,
             settings: Settings::new().await?,
         }
         .migrate()
-        .map_err(|e| DbOpenError(e.to_string()))?)
+        .map_err(|e| DbOpenError(e.to_string()))?;
+        let profile = database.get_auth_profile().ok().flatten().map(|profile| profile.arn);
+        database.settings.set_profile(profile);
+        Ok(database)
     }
 
     /// Get all entries for dumping the persistent application state.
@@ -255,18 +280,43 @@ impl Database {
         self.get_json_entry(Table::State, CODEWHISPERER_PROFILE_KEY)
     }
 
-    /// Set the current user profile used to determine API endpoints.
+    /// Set the current user profile used to determine API endpoints, and whose settings apply.
     pub fn set_auth_profile(&mut self, profile: &AuthProfile) -> Result<(), DatabaseError> {
         self.set_json_entry(Table::State, CODEWHISPERER_PROFILE_KEY, profile)?;
+        self.settings.set_profile(Some(profile.arn.clone()));
         self.delete_entry(Table::State, CUSTOMIZATION_STATE_KEY)
     }
 
     /// Unset the current user profile used to determine API endpoints.
     pub fn unset_auth_profile(&mut self) -> Result<(), DatabaseError> {
         self.delete_entry(Table::State, CODEWHISPERER_PROFILE_KEY)?;
+        self.settings.set_profile(None);
         self.delete_entry(Table::State, CUSTOMIZATION_STATE_KEY)
     }
 
+    /// Get the aliases of the logins saved to switch between.
+    pub fn get_identity_aliases(&self) -> Result<Vec<String>, DatabaseError> {
+        Ok(self.get_json_entry(Table::State, IDENTITIES_KEY)?.unwrap_or_default())
+    }
+
+    /// Set the aliases of the logins saved to switch between.
+    pub fn set_identity_aliases(&self, aliases: &[String]) -> Result<usize, DatabaseError> {
+        self.set_json_entry(Table::State, IDENTITIES_KEY, aliases)
+    }
+
+    /// Get the alias of the active login, if it has one.
+    pub fn get_active_identity(&self) -> Result<Option<String>, DatabaseError> {
+        self.get_entry::<String>(Table::State, ACTIVE_IDENTITY_KEY)
+    }
+
+    /// Set the alias of the active login, or unset it when the login has none.
+    pub fn set_active_identity(&self, alias: Option<&str>) -> Result<(), DatabaseError> {
+        match alias {
+            Some(alias) => self.set_entry(Table::State, ACTIVE_IDENTITY_KEY, alias).map(|_| ()),
+            None => self.delete_entry(Table::State, ACTIVE_IDENTITY_KEY),
+        }
+    }
+
     /// Get the client ID used for telemetry requests.
     pub fn get_client_id(&mut self) -> Result<Option<Uuid>, DatabaseError> {
         Ok(self
@@ -311,6 +361,40 @@ impl Database {
         self.set_entry(Table::State, PROFILE_MIGRATION_KEY, true)
     }
 
+    /// Get if the user has approved a plan for changes in the workspace with the given fingerprint
+    pub fn is_workspace_plan_approved(&self, fingerprint: &str) -> Result<bool, DatabaseError> {
+        Ok(self
+            .get_entry::<i64>(
+                Table::State,
+                format!("{PLAN_APPROVED_WORKSPACE_KEY_PREFIX}{fingerprint}"),
+            )?
+            .is_some())
+    }
+
+    /// Record that the user approved a plan for changes in the workspace with the given fingerprint
+    pub fn set_workspace_plan_approved(&self, fingerprint: &str) -> Result<usize, DatabaseError> {
+        self.set_entry(
+            Table::State,
+            format!("{PLAN_APPROVED_WORKSPACE_KEY_PREFIX}{fingerprint}"),
+            time::OffsetDateTime::now_utc().unix_timestamp(),
+        )
+    }
+
+    /// Get whether the user trusted the workspace agent config with the given fingerprint, or
+    /// [None] if they haven't been asked
+    pub fn get_workspace_agent_trust(&self, fingerprint: &str) -> Result<Option<bool>, DatabaseError> {
+        self.get_entry::<bool>(Table::State, format!("{WORKSPACE_AGENT_TRUST_KEY_PREFIX}{fingerprint}"))
+    }
+
+    /// Record whether the user trusted the workspace agent config with the given fingerprint
+    pub fn set_workspace_agent_trust(&self, fingerprint: &str, trusted: bool) -> Result<usize, DatabaseError> {
+        self.set_entry(
+            Table::State,
+            format!("{WORKSPACE_AGENT_TRUST_KEY_PREFIX}{fingerprint}"),
+            trusted,
+        )
+    }
+
     // /// Get the model id used for last conversation state.
     // pub fn get_last_used_model_id(&self) -> Result<Option<String>, DatabaseError> {
     //     self.get_json_entry::<String>(Table::State, LAST_USED_MODEL_ID)
@@ -355,19 +439,143 @@ impl Database {
         self.set_json_entry(Table::Conversations, path, state)
     }
 
+    /// Get the turn journaled for the conversation at the given path while its response was
+    /// streaming.
+    pub fn get_interrupted_turn(&self, path: impl AsRef<Path>) -> Result<Option<InterruptedTurn>, DatabaseError> {
+        let path = match path.as_ref().to_str() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        self.get_json_entry(Table::State, format!("{INTERRUPTED_TURN_KEY_PREFIX}{path}"))
+    }
+
+    /// Journal the turn in flight for the conversation at the given path.
+    pub fn set_interrupted_turn(&self, path: impl AsRef<Path>, turn: &InterruptedTurn) -> Result<usize, DatabaseError> {
+        let path = match path.as_ref().to_str() {
+            Some(path) => path,
+            None => return Ok(0),
+        };
+
+        self.set_json_entry(Table::State, format!("{INTERRUPTED_TURN_KEY_PREFIX}{path}"), turn)
+    }
+
+    /// Remove the journaled turn for the conversation at the given path.
+    pub fn clear_interrupted_turn(&self, path: impl AsRef<Path>) -> Result<(), DatabaseError> {
+        let path = match path.as_ref().to_str() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        self.delete_entry(Table::State, format!("{INTERRUPTED_TURN_KEY_PREFIX}{path}"))
+    }
+
+    /// Get the files tracked as left behind by chat sessions
+    pub fn get_artifacts(&self) -> Result<Vec<Artifact>, DatabaseError> {
+        Ok(self.get_json_entry(Table::State, ARTIFACTS_KEY)?.unwrap_or_default())
+    }
+
+    /// Set the files tracked as left behind by chat sessions
+    pub fn set_artifacts(&self, artifacts: &[Artifact]) -> Result<usize, DatabaseError> {
+        self.set_json_entry(Table::State, ARTIFACTS_KEY, artifacts)
+    }
+
+    /// Get the daily usage statistics shown by `q stats`
+    pub fn get_usage_stats(&self) -> Result<Vec<UsageDay>, DatabaseError> {
+        Ok(self.get_json_entry(Table::State, USAGE_STATS_KEY)?.unwrap_or_default())
+    }
+
+    /// Set the daily usage statistics shown by `q stats`
+    pub fn set_usage_stats(&self, days: &[UsageDay]) -> Result<usize, DatabaseError> {
+        self.set_json_entry(Table::State, USAGE_STATS_KEY, days)
+    }
+
+    /// Get the files changed in each chat session, as they were before it
+    pub fn get_snapshots(&self) -> Result<Vec<SessionSnapshot>, DatabaseError> {
+        Ok(self.get_json_entry(Table::State, SNAPSHOTS_KEY)?.unwrap_or_default())
+    }
+
+    /// Set the files changed in each chat session, as they were before it
+    pub fn set_snapshots(&self, sessions: &[SessionSnapshot]) -> Result<usize, DatabaseError> {
+        self.set_json_entry(Table::State, SNAPSHOTS_KEY, sessions)
+    }
+
+    /// Get every chat conversation along with the path it is associated with. Conversations that
+    /// fail to deserialize are skipped.
+    pub fn get_all_conversations(&self) -> Result<Vec<(String, ConversationState)>, DatabaseError> {
+        Ok(self
+            .all_entries(Table::Conversations)?
+            .into_iter()
+            .filter_map(|(path, value)| match value {
+                Value::String(value) => match serde_json::from_str(&value) {
+                    Ok(conversation) => Some((path, conversation)),
+                    Err(err) => {
+                        warn!(%path, %err, "failed to deserialize conversation");
+                        None
+                    },
+                },
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Whether secrets are stored in the keychain of the OS instead of the database file.
+    fn uses_keychain(&self) -> bool {
+        self.settings
+            .get_string(Setting::AuthSecretStorage)
+            .is_some_and(|storage| storage == "keychain")
+    }
+
+    /// Gets a secret from the storage `auth.secretStorage` selects. A secret that is only in the
+    /// other storage, because it was stored before the setting changed, is moved over.
     pub async fn get_secret(&self, key: &str) -> Result<Option<Secret>, DatabaseError> {
         trace!(key, "getting secret");
-        Ok(self.get_entry::<String>(Table::Auth, key)?.map(Into::into))
+        if !self.uses_keychain() {
+            if let Some(secret) = self.get_entry::<String>(Table::Auth, key)? {
+                return Ok(Some(secret.into()));
+            }
+            // The keychain may not be available at all when it isn't used
+            let Ok(Some(secret)) = keychain::get(key).await else {
+                return Ok(None);
+            };
+            self.set_entry(Table::Auth, key, &secret)?;
+            keychain::delete(key).await?;
+            return Ok(Some(secret.into()));
+        }
+        if let Some(secret) = keychain::get(key).await? {
+            return Ok(Some(secret.into()));
+        }
+
+        let Some(secret) = self.get_entry::<String>(Table::Auth, key)? else {
+            return Ok(None);
+        };
+        keychain::set(key, &secret).await?;
+        self.delete_entry(Table::Auth, key)?;
+        Ok(Some(secret.into()))
     }
 
     pub async fn set_secret(&self, key: &str, value: &str) -> Result<(), DatabaseError> {
         trace!(key, "setting secret");
+        if self.uses_keychain() {
+            keychain::set(key, value).await?;
+            return self.delete_entry(Table::Auth, key);
+        }
         self.set_entry(Table::Auth, key, value)?;
         Ok(())
     }
 
+    /// Deletes a secret from both storages, so that a copy left in the one that isn't selected
+    /// can't be moved back by [Self::get_secret].
     pub async fn delete_secret(&self, key: &str) -> Result<(), DatabaseError> {
         trace!(key, "deleting secret");
+        match self.uses_keychain() {
+            true => keychain::delete(key).await?,
+            false => {
+                if let Err(err) = keychain::delete(key).await {
+                    debug!(key, %err, "failed to delete the secret from the keychain");
+                }
+            },
+        }
         self.delete_entry(Table::Auth, key)
     }
 
@@ -587,6 +795,27 @@ mod tests {
         assert!(db.get_entry::<bool>(Table::State, "bool").unwrap().is_some());
     }
 
+    #[tokio::test]
+    async fn workspace_plan_approval() {
+        let db = Database::new().await.unwrap();
+
+        assert!(!db.is_workspace_plan_approved("abc").unwrap());
+        db.set_workspace_plan_approved("abc").unwrap();
+        assert!(db.is_workspace_plan_approved("abc").unwrap());
+        assert!(!db.is_workspace_plan_approved("def").unwrap());
+    }
+
+    #[tokio::test]
+    async fn workspace_agent_trust() {
+        let db = Database::new().await.unwrap();
+
+        assert_eq!(db.get_workspace_agent_trust("abc").unwrap(), None);
+        db.set_workspace_agent_trust("abc", false).unwrap();
+        assert_eq!(db.get_workspace_agent_trust("abc").unwrap(), Some(false));
+        db.set_workspace_agent_trust("abc", true).unwrap();
+        assert_eq!(db.get_workspace_agent_trust("abc").unwrap(), Some(true));
+    }
+
     #[tokio::test]
     #[ignore = "not on ci"]
     async fn test_set_password() {
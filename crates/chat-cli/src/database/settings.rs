@@ -29,13 +29,52 @@ pub enum Setting {
     ChatEnableNotifications,
     ApiCodeWhispererService,
     ApiQService,
+    ApiMaxConcurrentRequests,
     McpInitTimeout,
     McpNoInteractiveTimeout,
     McpLoadedBefore,
     ChatDefaultModel,
     ChatDefaultAgent,
     ChatDisableAutoCompaction,
+    ChatAutoCompactPercent,
+    ChatHistoryStrategy,
     ChatEnableHistoryHints,
+    ChatCommandPluginTimeout,
+    ChatCommandPluginCleanEnv,
+    ChatReviewOutgoing,
+    ChatContentFilterDeniedPaths,
+    ChatContentFilterDeniedExtensions,
+    ChatContentFilterMaxFileSize,
+    ChatRequirePlanApproval,
+    ChatWorkspaceAgentPath,
+    ChatPromptTemplate,
+    ChatTheme,
+    TelemetryOtlpEndpoint,
+    TelemetryOtlpHeaders,
+    ChatCompletionCommand,
+    ChatCompletionWebhook,
+    ChatLargeRequestTokens,
+    ChatContextBudgetTokens,
+    ChatModelRegistryUrl,
+    ChatFallbackModel,
+    ChatArtifactRetentionDays,
+    ChatEgressAllowedDomains,
+    ChatEgressDeniedDomains,
+    ChatEgressAllowedCidrs,
+    ChatEgressDeniedCidrs,
+    ChatDisableToolchainChecks,
+    ChatDisableStats,
+    ChatLocale,
+    ChatTimezone,
+    ChatToolParallelism,
+    ChatSnapshotMaxSizeMb,
+    ChatDisableInjectionScan,
+    ChatDisableCodeBlockActions,
+    ChatEnableWorkspaceIndex,
+    ChatWorkspaceIndexResults,
+    ChatContextUrlRefreshMinutes,
+    AuthSecretStorage,
+    AuthBackgroundRefresh,
 }
 
 impl AsRef<str> for Setting {
@@ -53,13 +92,52 @@ impl AsRef<str> for Setting {
             Self::ChatEnableNotifications => "chat.enableNotifications",
             Self::ApiCodeWhispererService => "api.codewhisperer.service",
             Self::ApiQService => "api.q.service",
+            Self::ApiMaxConcurrentRequests => "api.maxConcurrentRequests",
             Self::McpInitTimeout => "mcp.initTimeout",
             Self::McpNoInteractiveTimeout => "mcp.noInteractiveTimeout",
             Self::McpLoadedBefore => "mcp.loadedBefore",
             Self::ChatDefaultModel => "chat.defaultModel",
             Self::ChatDefaultAgent => "chat.defaultAgent",
             Self::ChatDisableAutoCompaction => "chat.disableAutoCompaction",
+            Self::ChatAutoCompactPercent => "chat.autoCompactPercent",
+            Self::ChatHistoryStrategy => "chat.historyStrategy",
             Self::ChatEnableHistoryHints => "chat.enableHistoryHints",
+            Self::ChatCommandPluginTimeout => "chat.commandPluginTimeout",
+            Self::ChatCommandPluginCleanEnv => "chat.commandPluginCleanEnv",
+            Self::ChatReviewOutgoing => "chat.reviewOutgoing",
+            Self::ChatContentFilterDeniedPaths => "chat.contentFilter.deniedPaths",
+            Self::ChatContentFilterDeniedExtensions => "chat.contentFilter.deniedExtensions",
+            Self::ChatContentFilterMaxFileSize => "chat.contentFilter.maxFileSize",
+            Self::ChatRequirePlanApproval => "chat.requirePlanApproval",
+            Self::ChatWorkspaceAgentPath => "chat.workspaceAgentPath",
+            Self::ChatPromptTemplate => "chat.promptTemplate",
+            Self::ChatTheme => "chat.theme",
+            Self::TelemetryOtlpEndpoint => "telemetry.otlp.endpoint",
+            Self::TelemetryOtlpHeaders => "telemetry.otlp.headers",
+            Self::ChatCompletionCommand => "chat.completionCommand",
+            Self::ChatCompletionWebhook => "chat.completionWebhook",
+            Self::ChatLargeRequestTokens => "chat.largeRequestTokens",
+            Self::ChatContextBudgetTokens => "chat.contextBudgetTokens",
+            Self::ChatModelRegistryUrl => "chat.modelRegistryUrl",
+            Self::ChatFallbackModel => "chat.fallbackModel",
+            Self::ChatArtifactRetentionDays => "chat.artifactRetentionDays",
+            Self::ChatEgressAllowedDomains => "chat.egress.allowedDomains",
+            Self::ChatEgressDeniedDomains => "chat.egress.deniedDomains",
+            Self::ChatEgressAllowedCidrs => "chat.egress.allowedCidrs",
+            Self::ChatEgressDeniedCidrs => "chat.egress.deniedCidrs",
+            Self::ChatDisableToolchainChecks => "chat.disableToolchainChecks",
+            Self::ChatDisableStats => "chat.disableStats",
+            Self::ChatLocale => "chat.locale",
+            Self::ChatTimezone => "chat.timezone",
+            Self::ChatToolParallelism => "chat.toolParallelism",
+            Self::ChatSnapshotMaxSizeMb => "chat.snapshotMaxSizeMb",
+            Self::ChatDisableInjectionScan => "chat.disableInjectionScan",
+            Self::ChatDisableCodeBlockActions => "chat.disableCodeBlockActions",
+            Self::ChatEnableWorkspaceIndex => "chat.enableWorkspaceIndex",
+            Self::ChatWorkspaceIndexResults => "chat.workspaceIndexResults",
+            Self::ChatContextUrlRefreshMinutes => "chat.contextUrlRefreshMinutes",
+            Self::AuthSecretStorage => "auth.secretStorage",
+            Self::AuthBackgroundRefresh => "auth.backgroundRefresh",
         }
     }
 }
@@ -87,20 +165,80 @@ impl TryFrom<&str> for Setting {
             "chat.enableNotifications" => Ok(Self::ChatEnableNotifications),
             "api.codewhisperer.service" => Ok(Self::ApiCodeWhispererService),
             "api.q.service" => Ok(Self::ApiQService),
+            "api.maxConcurrentRequests" => Ok(Self::ApiMaxConcurrentRequests),
             "mcp.initTimeout" => Ok(Self::McpInitTimeout),
             "mcp.noInteractiveTimeout" => Ok(Self::McpNoInteractiveTimeout),
             "mcp.loadedBefore" => Ok(Self::McpLoadedBefore),
             "chat.defaultModel" => Ok(Self::ChatDefaultModel),
             "chat.defaultAgent" => Ok(Self::ChatDefaultAgent),
             "chat.disableAutoCompaction" => Ok(Self::ChatDisableAutoCompaction),
+            "chat.autoCompactPercent" => Ok(Self::ChatAutoCompactPercent),
+            "chat.historyStrategy" => Ok(Self::ChatHistoryStrategy),
             "chat.enableHistoryHints" => Ok(Self::ChatEnableHistoryHints),
+            "chat.commandPluginTimeout" => Ok(Self::ChatCommandPluginTimeout),
+            "chat.commandPluginCleanEnv" => Ok(Self::ChatCommandPluginCleanEnv),
+            "chat.reviewOutgoing" => Ok(Self::ChatReviewOutgoing),
+            "chat.contentFilter.deniedPaths" => Ok(Self::ChatContentFilterDeniedPaths),
+            "chat.contentFilter.deniedExtensions" => Ok(Self::ChatContentFilterDeniedExtensions),
+            "chat.contentFilter.maxFileSize" => Ok(Self::ChatContentFilterMaxFileSize),
+            "chat.requirePlanApproval" => Ok(Self::ChatRequirePlanApproval),
+            "chat.workspaceAgentPath" => Ok(Self::ChatWorkspaceAgentPath),
+            "chat.promptTemplate" => Ok(Self::ChatPromptTemplate),
+            "chat.theme" => Ok(Self::ChatTheme),
+            "telemetry.otlp.endpoint" => Ok(Self::TelemetryOtlpEndpoint),
+            "telemetry.otlp.headers" => Ok(Self::TelemetryOtlpHeaders),
+            "chat.completionCommand" => Ok(Self::ChatCompletionCommand),
+            "chat.completionWebhook" => Ok(Self::ChatCompletionWebhook),
+            "chat.largeRequestTokens" => Ok(Self::ChatLargeRequestTokens),
+            "chat.contextBudgetTokens" => Ok(Self::ChatContextBudgetTokens),
+            "chat.modelRegistryUrl" => Ok(Self::ChatModelRegistryUrl),
+            "chat.fallbackModel" => Ok(Self::ChatFallbackModel),
+            "chat.artifactRetentionDays" => Ok(Self::ChatArtifactRetentionDays),
+            "chat.egress.allowedDomains" => Ok(Self::ChatEgressAllowedDomains),
+            "chat.egress.deniedDomains" => Ok(Self::ChatEgressDeniedDomains),
+            "chat.egress.allowedCidrs" => Ok(Self::ChatEgressAllowedCidrs),
+            "chat.egress.deniedCidrs" => Ok(Self::ChatEgressDeniedCidrs),
+            "chat.disableToolchainChecks" => Ok(Self::ChatDisableToolchainChecks),
+            "chat.disableStats" => Ok(Self::ChatDisableStats),
+            "chat.locale" => Ok(Self::ChatLocale),
+            "chat.timezone" => Ok(Self::ChatTimezone),
+            "chat.toolParallelism" => Ok(Self::ChatToolParallelism),
+            "chat.snapshotMaxSizeMb" => Ok(Self::ChatSnapshotMaxSizeMb),
+            "chat.disableInjectionScan" => Ok(Self::ChatDisableInjectionScan),
+            "chat.disableCodeBlockActions" => Ok(Self::ChatDisableCodeBlockActions),
+            "chat.enableWorkspaceIndex" => Ok(Self::ChatEnableWorkspaceIndex),
+            "chat.workspaceIndexResults" => Ok(Self::ChatWorkspaceIndexResults),
+            "chat.contextUrlRefreshMinutes" => Ok(Self::ChatContextUrlRefreshMinutes),
+            "auth.secretStorage" => Ok(Self::AuthSecretStorage),
+            "auth.backgroundRefresh" => Ok(Self::AuthBackgroundRefresh),
             _ => Err(DatabaseError::InvalidSetting(value.to_string())),
         }
     }
 }
 
+/// The key under which the settings file keeps the settings of each IAM Identity Center profile, by
+/// the arn of the profile.
+const PROFILES_KEY: &str = "profiles";
+
+impl Setting {
+    /// Whether the setting is kept for each IAM Identity Center profile, so that switching profiles
+    /// switches the model and the agent, with its context and trusted tools, that are used.
+    pub fn is_profile_scoped(&self) -> bool {
+        matches!(
+            self,
+            Self::ChatDefaultModel | Self::ChatFallbackModel | Self::ChatDefaultAgent
+        )
+    }
+}
+
+/// The settings file. Profile scoped settings are read from and written to the active profile's
+/// settings, and read from the other settings when the profile doesn't set them.
 #[derive(Debug, Clone, Default)]
-pub struct Settings(Map<String, Value>);
+pub struct Settings {
+    values: Map<String, Value>,
+    /// The arn of the active IAM Identity Center profile
+    profile: Option<String>,
+}
 
 impl Settings {
     pub async fn new() -> Result<Self, DatabaseError> {
@@ -117,7 +255,7 @@ impl Settings {
             }
         }
 
-        Ok(Self(match path.exists() {
+        let values = match path.exists() {
             true => {
                 let mut file = RwLock::new(File::open(&path).await?);
                 let mut buf = Vec::new();
@@ -129,26 +267,73 @@ impl Settings {
                 file.write()?.write_all(b"{}").await?;
                 serde_json::Map::new()
             },
-        }))
+        };
+        Ok(Self { values, profile: None })
     }
 
-    pub fn map(&self) -> &'_ Map<String, Value> {
-        &self.0
+    /// Sets the arn of the active IAM Identity Center profile, whose settings apply from now on.
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
+    /// The settings that apply, with those of the active profile in place of the others.
+    pub fn map(&self) -> Map<String, Value> {
+        let mut map = self.values.clone();
+        map.remove(PROFILES_KEY);
+        if let Some(values) = self.profile_values() {
+            let scoped = values
+                .iter()
+                .filter(|(key, _)| Setting::try_from(key.as_str()).is_ok_and(|key| key.is_profile_scoped()));
+            map.extend(scoped.map(|(key, value)| (key.clone(), value.clone())));
+        }
+        map
     }
 
     pub fn get(&self, key: Setting) -> Option<&Value> {
-        self.0.get(key.as_ref())
+        key.is_profile_scoped()
+            .then(|| self.profile_values()?.get(key.as_ref()))
+            .flatten()
+            .or_else(|| self.values.get(key.as_ref()))
     }
 
     pub async fn set(&mut self, key: Setting, value: impl Into<serde_json::Value>) -> Result<(), DatabaseError> {
-        self.0.insert(key.to_string(), value.into());
+        let value = value.into();
+        match self.profile_values_mut(key) {
+            Some(values) => values.insert(key.to_string(), value),
+            None => self.values.insert(key.to_string(), value),
+        };
         self.save_to_file().await
     }
 
+    /// Removes a setting, for profile scoped settings only from the active profile's settings.
     pub async fn remove(&mut self, key: Setting) -> Result<Option<Value>, DatabaseError> {
-        let key = self.0.remove(key.as_ref());
+        let removed = match (key.is_profile_scoped(), &self.profile) {
+            (true, Some(profile)) => self
+                .values
+                .get_mut(PROFILES_KEY)
+                .and_then(|profiles| profiles.get_mut(profile))
+                .and_then(Value::as_object_mut)
+                .and_then(|values| values.remove(key.as_ref())),
+            _ => self.values.remove(key.as_ref()),
+        };
         self.save_to_file().await?;
-        Ok(key)
+        Ok(removed)
+    }
+
+    fn profile_values(&self) -> Option<&Map<String, Value>> {
+        self.values.get(PROFILES_KEY)?.get(self.profile.as_ref()?)?.as_object()
+    }
+
+    /// The active profile's settings when `key` is profile scoped, created if needed.
+    fn profile_values_mut(&mut self, key: Setting) -> Option<&mut Map<String, Value>> {
+        let profile = self.profile.clone().filter(|_| key.is_profile_scoped())?;
+        self.values
+            .entry(PROFILES_KEY)
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()?
+            .entry(profile)
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
     }
 
     pub fn get_bool(&self, key: Setting) -> Option<bool> {
@@ -163,6 +348,19 @@ impl Settings {
         self.get(key).and_then(|value| value.as_i64())
     }
 
+    /// Reads a list, given either as an array of strings or as a comma separated string.
+    pub fn get_list(&self, key: Setting) -> Vec<String> {
+        match self.get(key) {
+            Some(Value::Array(values)) => values.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            Some(Value::String(values)) => values
+                .split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
     pub async fn save_to_file(&self) -> Result<(), DatabaseError> {
         if cfg!(test) {
             return Ok(());
@@ -185,7 +383,7 @@ impl Settings {
         let mut file = RwLock::new(file_opts.open(&path).await?);
         let mut lock = file.write()?;
 
-        match serde_json::to_string_pretty(&self.0) {
+        match serde_json::to_string_pretty(&self.values) {
             Ok(json) => lock.write_all(json.as_bytes()).await?,
             Err(_err) => {
                 lock.seek(SeekFrom::Start(0)).await?;
@@ -245,4 +443,45 @@ mod test {
         assert_eq!(settings.get(Setting::ShareCodeWhispererContent), None);
         assert_eq!(settings.get(Setting::McpLoadedBefore), None);
     }
+
+    #[tokio::test]
+    async fn test_profile_settings() {
+        let mut settings = Settings::new().await.unwrap();
+        settings.set(Setting::ChatDefaultModel, "global").await.unwrap();
+
+        settings.set_profile(Some("arn:profile/a".to_string()));
+        assert_eq!(
+            settings.get_string(Setting::ChatDefaultModel).as_deref(),
+            Some("global")
+        );
+        settings.set(Setting::ChatDefaultModel, "model a").await.unwrap();
+        settings.set(Setting::ChatTheme, "light").await.unwrap();
+        assert_eq!(
+            settings.get_string(Setting::ChatDefaultModel).as_deref(),
+            Some("model a")
+        );
+        assert_eq!(
+            settings.map().get("chat.defaultModel"),
+            Some(&Value::String("model a".to_string()))
+        );
+        assert!(!settings.map().contains_key(PROFILES_KEY));
+
+        settings.set_profile(Some("arn:profile/b".to_string()));
+        assert_eq!(
+            settings.get_string(Setting::ChatDefaultModel).as_deref(),
+            Some("global")
+        );
+        assert_eq!(settings.get_string(Setting::ChatTheme).as_deref(), Some("light"));
+
+        settings.set_profile(Some("arn:profile/a".to_string()));
+        settings.remove(Setting::ChatDefaultModel).await.unwrap();
+        assert_eq!(
+            settings.get_string(Setting::ChatDefaultModel).as_deref(),
+            Some("global")
+        );
+
+        settings.set_profile(None);
+        settings.set(Setting::ChatDefaultModel, "other").await.unwrap();
+        assert_eq!(settings.get_string(Setting::ChatDefaultModel).as_deref(), Some("other"));
+    }
 }
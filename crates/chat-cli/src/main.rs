@@ -34,11 +34,18 @@ fn main() -> Result<ExitCode> {
     };
 
     let verbose = parsed.verbose > 0;
+    let json_errors = parsed.format.is_some_and(|format| format.is_json());
+    util::datetime::init_local_offset();
     let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
     let result = runtime.block_on(parsed.execute());
 
     match result {
         Ok(exit_code) => Ok(exit_code),
+        Err(err) if json_errors => {
+            // Scripts asked for JSON, so the error is reported in JSON as well
+            eprintln!("{}", serde_json::json!({ "error": err.to_string() }));
+            Ok(ExitCode::FAILURE)
+        },
         Err(err) => {
             if verbose || get_log_level_max() > LevelFilter::INFO {
                 eprintln!("{} {err:?}", "error:".bold().red());
@@ -25,6 +25,10 @@ use super::transport::base_protocol::{
     JsonRpcRequest,
     JsonRpcVersion,
 };
+use super::transport::http::{
+    HttpTransportKind,
+    JsonRpcHttpTransport,
+};
 use super::transport::stdio::JsonRpcStdioTransport;
 use super::transport::{
     self,
@@ -42,8 +46,10 @@ use super::{
     ResourceTemplatesListResult,
     ResourcesListResult,
     ServerCapabilities,
+    StderrLog,
     ToolsListResult,
 };
+use crate::cli::agent::egress::Egress;
 use crate::util::process::{
     Pid,
     terminate_process,
@@ -51,6 +57,7 @@ use crate::util::process::{
 
 pub type ClientInfo = serde_json::Value;
 pub type StdioTransport = JsonRpcStdioTransport;
+pub type HttpTransport = JsonRpcHttpTransport;
 
 /// Represents the capabilities of a client in the Model Context Protocol.
 /// This structure is sent to the server during initialization to communicate
@@ -84,6 +91,20 @@ pub struct ClientConfig {
     pub env: Option<HashMap<String, String>>,
 }
 
+/// Configuration of a client for a server reached over HTTP.
+#[derive(Debug)]
+pub struct HttpClientConfig {
+    pub server_name: String,
+    pub url: String,
+    pub kind: HttpTransportKind,
+    /// Headers sent with every request, e.g. for authorization
+    pub headers: HashMap<String, String>,
+    /// Where the client may connect to
+    pub egress: Egress,
+    pub timeout: u64,
+    pub client_info: serde_json::Value,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Error)]
 pub enum ClientError {
@@ -131,6 +152,8 @@ pub struct Client<T: Transport> {
     // TODO: move this to tool manager that way all the assets are treated equally
     pub prompt_gets: Arc<SyncRwLock<HashMap<String, PromptGet>>>,
     pub is_prompts_out_of_date: Arc<AtomicBool>,
+    /// Recent stderr output of the server process, or the connection errors of HTTP servers
+    stderr_log: StderrLog,
 }
 
 impl<T: Transport> Clone for Client<T> {
@@ -147,6 +170,7 @@ impl<T: Transport> Clone for Client<T> {
             messenger: None,
             prompt_gets: self.prompt_gets.clone(),
             is_prompts_out_of_date: self.is_prompts_out_of_date.clone(),
+            stderr_log: self.stderr_log.clone(),
         }
     }
 }
@@ -199,6 +223,7 @@ impl Client<StdioTransport> {
         let server_process_id = Some(Pid::from_u32(server_process_id));
 
         let transport = Arc::new(transport::stdio::JsonRpcStdioTransport::client(child)?);
+        let stderr_log = transport.stderr_log().cloned().unwrap_or_default();
         Ok(Self {
             server_name,
             transport,
@@ -209,6 +234,7 @@ impl Client<StdioTransport> {
             messenger: None,
             prompt_gets: Arc::new(SyncRwLock::new(HashMap::new())),
             is_prompts_out_of_date: Arc::new(AtomicBool::new(false)),
+            stderr_log,
         })
     }
 
@@ -266,6 +292,34 @@ impl Client<StdioTransport> {
     }
 }
 
+impl Client<HttpTransport> {
+    pub fn from_http_config(config: HttpClientConfig) -> Result<Self, ClientError> {
+        let HttpClientConfig {
+            server_name,
+            url,
+            kind,
+            headers,
+            egress,
+            timeout,
+            client_info,
+        } = config;
+        let transport = Arc::new(JsonRpcHttpTransport::client(&url, kind, &headers, egress)?);
+        let stderr_log = transport.log().clone();
+        Ok(Self {
+            server_name,
+            transport,
+            timeout,
+            server_process_id: None,
+            client_info,
+            current_id: Arc::new(AtomicU64::new(0)),
+            messenger: None,
+            prompt_gets: Arc::new(SyncRwLock::new(HashMap::new())),
+            is_prompts_out_of_date: Arc::new(AtomicBool::new(false)),
+            stderr_log,
+        })
+    }
+}
+
 impl<T> Drop for Client<T>
 where
     T: Transport,
@@ -283,6 +337,11 @@ impl<T> Client<T>
 where
     T: Transport,
 {
+    /// The latest lines the server wrote to stderr.
+    pub fn stderr_log(&self) -> &StderrLog {
+        &self.stderr_log
+    }
+
     /// Exchange of information specified as per https://spec.modelcontextprotocol.io/specification/2024-11-05/basic/lifecycle/#initialization
     ///
     /// Also done are the following:
@@ -466,10 +525,11 @@ where
         };
         tracing::trace!(target: "mcp", "To {}:\n{:#?}", self.server_name, request);
         let msg = JsonRpcMessage::Request(request);
+        // Listen before sending, transports like HTTP may receive the response while sending
+        let mut listener = self.transport.get_listener();
         time::timeout(Duration::from_millis(self.timeout), self.transport.send(&msg))
             .await
             .map_err(send_map_err)??;
-        let mut listener = self.transport.get_listener();
         let mut resp = time::timeout(Duration::from_millis(self.timeout), async {
             // we want to ignore all other messages sent by the server at this point and let the
             // background loop handle them
@@ -84,6 +84,47 @@ pub struct ResourceTemplatesListResult {
     pub next_cursor: Option<String>,
 }
 
+/// A resource offered by a server, as listed by `resources/list`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceInfo {
+    /// Unique identifier for the resource
+    pub uri: String,
+    /// Human-readable name
+    pub name: String,
+    /// Optional description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Optional MIME type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// Result of reading a resource with `resources/read`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceReadResult {
+    /// The contents of the resource, more than one when the uri names a collection
+    pub contents: Vec<ResourceReadContents>,
+}
+
+/// Contents of a read resource, either text or base64 encoded binary data
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceReadContents {
+    /// Uri of the resource the contents belong to
+    pub uri: String,
+    /// Optional MIME type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Text contents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Base64 encoded binary contents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 /// Result of prompt listing query
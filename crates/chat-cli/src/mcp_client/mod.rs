@@ -3,6 +3,7 @@ pub mod error;
 pub mod facilitator_types;
 pub mod messenger;
 pub mod server;
+pub mod stderr_log;
 pub mod transport;
 
 pub use client::*;
@@ -10,4 +11,5 @@ pub use facilitator_types::*;
 pub use messenger::*;
 #[allow(unused_imports)]
 pub use server::*;
+pub use stderr_log::*;
 pub use transport::*;
@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+/// Number of stderr lines kept per server.
+pub const STDERR_LOG_CAPACITY: usize = 500;
+
+#[derive(Debug, Default)]
+struct Inner {
+    lines: VecDeque<String>,
+    closed: bool,
+}
+
+/// Ring buffer of the most recent lines an MCP server wrote to stderr, shared between the
+/// transport that reads them and anything that needs to explain why the server failed.
+#[derive(Debug, Clone, Default)]
+pub struct StderrLog {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl StderrLog {
+    pub fn push(&self, line: String) {
+        if let Ok(mut inner) = self.inner.lock() {
+            if inner.lines.len() >= STDERR_LOG_CAPACITY {
+                inner.lines.pop_front();
+            }
+            inner.lines.push_back(line);
+        }
+    }
+
+    /// Marks stderr as closed, which in practice means that the server process has exited.
+    pub fn close(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.closed = true;
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock().is_ok_and(|inner| inner.closed)
+    }
+
+    /// Returns up to the last `n` lines, oldest first.
+    pub fn recent(&self, n: usize) -> Vec<String> {
+        self.inner
+            .lock()
+            .map(|inner| {
+                inner
+                    .lines
+                    .iter()
+                    .skip(inner.lines.len().saturating_sub(n))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Describes the state of the server for appending to an error message: the last `n` lines of
+    /// stderr and whether the server has exited. Returns [None] if there is nothing to add.
+    pub fn diagnostics(&self, n: usize) -> Option<String> {
+        let lines = self.recent(n);
+        let mut msg = String::new();
+        if self.is_closed() {
+            msg.push_str("The server process has exited.\n");
+        }
+        if !lines.is_empty() {
+            msg.push_str("Recent stderr output:\n");
+            for line in lines {
+                msg.push_str(&format!("  {line}\n"));
+            }
+        }
+        (!msg.is_empty()).then_some(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stderr_log() {
+        let log = StderrLog::default();
+        assert_eq!(log.diagnostics(5), None);
+
+        for i in 0..STDERR_LOG_CAPACITY + 3 {
+            log.push(format!("line {i}"));
+        }
+        assert_eq!(log.recent(usize::MAX).len(), STDERR_LOG_CAPACITY);
+        assert_eq!(log.recent(2), vec![
+            format!("line {}", STDERR_LOG_CAPACITY + 1),
+            format!("line {}", STDERR_LOG_CAPACITY + 2)
+        ]);
+
+        log.close();
+        assert_eq!(
+            log.diagnostics(1).unwrap(),
+            format!(
+                "The server process has exited.\nRecent stderr output:\n  line {}\n",
+                STDERR_LOG_CAPACITY + 2
+            )
+        );
+    }
+}
@@ -0,0 +1,553 @@
+//! Client side of the HTTP transports of MCP:
+//!
+//! - Streamable HTTP (https://modelcontextprotocol.io/specification/2025-03-26/basic/transports#streamable-http):
+//!   every message is POSTed to the endpoint, which answers with either a JSON body or an SSE
+//!   stream of messages. Messages the server initiates arrive on an SSE stream opened with a GET to
+//!   the same endpoint once the session is initialized, if the server offers one.
+//! - HTTP with SSE (https://spec.modelcontextprotocol.io/specification/2024-11-05/basic/transports#http-with-sse):
+//!   an SSE stream is opened with a GET first, its `endpoint` event names the URL messages are
+//!   POSTed to and every message from the server arrives on that stream.
+//!
+//! The event stream is reopened when it drops, and POSTs are retried when the connection fails.
+//! Connection errors and reconnects are kept in a [StderrLog] in place of the stderr of stdio
+//! servers. Redirects, the endpoint named by the server and the addresses its host resolves to
+//! are held to the egress policy of the session.
+
+use std::collections::HashMap;
+use std::sync::{
+    Arc,
+    Mutex as SyncMutex,
+};
+use std::time::Duration;
+
+use reqwest::header::{
+    ACCEPT,
+    CONTENT_TYPE,
+    HeaderMap,
+    HeaderName,
+    HeaderValue,
+};
+use reqwest::{
+    Method,
+    RequestBuilder,
+    Response,
+    StatusCode,
+};
+use tokio::sync::{
+    broadcast,
+    watch,
+};
+use tokio::task::JoinHandle;
+use url::Url;
+
+use super::base_protocol::JsonRpcMessage;
+use super::{
+    Listener,
+    LogListener,
+    Transport,
+    TransportError,
+};
+use crate::cli::agent::egress::Egress;
+use crate::mcp_client::StderrLog;
+
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+/// Consecutive failures to connect after which the event stream or a POST is given up on.
+const MAX_RECONNECTS: u32 = 5;
+/// Delay before the first reconnect, doubled for every further one.
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_REDIRECTS: usize = 10;
+
+/// Which of the HTTP transports a server speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpTransportKind {
+    StreamableHttp,
+    Sse,
+}
+
+#[derive(Debug)]
+pub struct JsonRpcHttpTransport {
+    shared: Arc<Shared>,
+    receiver: broadcast::Receiver<Result<JsonRpcMessage, TransportError>>,
+    log_receiver: broadcast::Receiver<String>,
+    /// Task reading the event stream opened with a GET
+    stream_task: SyncMutex<Option<JoinHandle<()>>>,
+}
+
+/// State shared with the tasks reading event streams.
+#[derive(Debug)]
+struct Shared {
+    client: reqwest::Client,
+    url: Url,
+    headers: HeaderMap,
+    kind: HttpTransportKind,
+    egress: Egress,
+    session_id: SyncMutex<Option<String>>,
+    /// Where the SSE transport POSTs messages to, learned from the `endpoint` event
+    endpoint: watch::Sender<Option<Url>>,
+    tx: broadcast::Sender<Result<JsonRpcMessage, TransportError>>,
+    log_tx: broadcast::Sender<String>,
+    log: StderrLog,
+}
+
+impl JsonRpcHttpTransport {
+    /// Creates the transport for the server at `url`, sending `headers` (e.g. for authorization)
+    /// with every request and only connecting where `egress` allows.
+    pub fn client(
+        url: &str,
+        kind: HttpTransportKind,
+        headers: &HashMap<String, String>,
+        egress: Egress,
+    ) -> Result<Self, TransportError> {
+        let url = Url::parse(url).map_err(|err| TransportError::Custom(format!("Invalid url {url}: {err}")))?;
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            let name = HeaderName::try_from(name.as_str())
+                .map_err(|err| TransportError::Custom(format!("Invalid header name {name}: {err}")))?;
+            let value = HeaderValue::try_from(value.as_str())
+                .map_err(|err| TransportError::Custom(format!("Invalid value for header {name}: {err}")))?;
+            header_map.insert(name, value);
+        }
+        let client = crate::request::client_builder()
+            .cookie_store(true)
+            .redirect(egress.redirect_policy(MAX_REDIRECTS))
+            .dns_resolver(egress.resolver())
+            .build()
+            .map_err(|err| TransportError::Custom(err.to_string()))?;
+
+        let (tx, receiver) = broadcast::channel::<Result<JsonRpcMessage, TransportError>>(100);
+        let (log_tx, log_receiver) = broadcast::channel::<String>(100);
+        let shared = Arc::new(Shared {
+            client,
+            url,
+            headers: header_map,
+            kind,
+            egress,
+            session_id: SyncMutex::new(None),
+            endpoint: watch::Sender::new(None),
+            tx,
+            log_tx,
+            log: StderrLog::default(),
+        });
+
+        // The SSE transport cannot send anything before the stream names the endpoint
+        let stream_task = match kind {
+            HttpTransportKind::StreamableHttp => None,
+            HttpTransportKind::Sse => Some(tokio::spawn(shared.clone().listen())),
+        };
+        Ok(Self {
+            shared,
+            receiver,
+            log_receiver,
+            stream_task: SyncMutex::new(stream_task),
+        })
+    }
+
+    /// Connection errors and reconnects.
+    pub fn log(&self) -> &StderrLog {
+        &self.shared.log
+    }
+
+    fn start_stream(&self) {
+        if let Ok(mut stream_task) = self.stream_task.lock() {
+            if stream_task.is_none() {
+                *stream_task = Some(tokio::spawn(self.shared.clone().listen()));
+            }
+        }
+    }
+}
+
+impl Shared {
+    fn log(&self, line: String) {
+        tracing::debug!(target: "mcp", "{}: {line}", self.url);
+        self.log.push(line.clone());
+        let _ = self.log_tx.send(line);
+    }
+
+    fn session_id(&self) -> Option<String> {
+        self.session_id.lock().ok().and_then(|id| id.clone())
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        let mut request = self.client.request(method, url).headers(self.headers.clone());
+        if let Some(session_id) = self.session_id() {
+            request = request.header(SESSION_ID_HEADER, session_id);
+        }
+        request
+    }
+
+    /// Forwards the messages in `body`, a single message or a batch of them.
+    fn deliver(&self, body: &[u8]) {
+        match serde_json::from_slice::<JsonRpcMessage>(body) {
+            Ok(msg) => {
+                let _ = self.tx.send(Ok(msg));
+            },
+            Err(err) => match serde_json::from_slice::<Vec<JsonRpcMessage>>(body) {
+                Ok(batch) => {
+                    for msg in batch {
+                        let _ = self.tx.send(Ok(msg));
+                    }
+                },
+                Err(_) => {
+                    let _ = self.tx.send(Err(err.into()));
+                },
+            },
+        }
+    }
+
+    /// The URL messages are POSTed to, waiting for the `endpoint` event with the SSE transport.
+    async fn endpoint(&self) -> Result<Url, TransportError> {
+        match self.kind {
+            HttpTransportKind::StreamableHttp => Ok(self.url.clone()),
+            HttpTransportKind::Sse => {
+                let mut endpoint = self.endpoint.subscribe();
+                let endpoint = endpoint
+                    .wait_for(Option::is_some)
+                    .await
+                    .map_err(|err| TransportError::Custom(format!("No endpoint to send messages to: {err}")))?;
+                Ok(endpoint.clone().expect("waited for the endpoint"))
+            },
+        }
+    }
+
+    /// POSTs a message, retrying when the connection fails.
+    async fn post(&self, url: Url, body: Vec<u8>) -> Result<Response, TransportError> {
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .request(Method::POST, url.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .header(ACCEPT, "application/json, text/event-stream")
+                .body(body.clone())
+                .send()
+                .await;
+            match result {
+                Err(err) if err.is_connect() && attempt < MAX_RECONNECTS => {
+                    let delay = RECONNECT_DELAY * 2u32.pow(attempt);
+                    attempt += 1;
+                    self.log(format!("Failed to connect: {err}, retrying in {}ms", delay.as_millis()));
+                    tokio::time::sleep(delay).await;
+                },
+                Err(err) => {
+                    self.log(format!("Failed to send a message: {err}"));
+                    return Err(TransportError::Custom(err.to_string()));
+                },
+                Ok(response) => return Ok(response),
+            }
+        }
+    }
+
+    /// Keeps an event stream open with a GET, reconnecting whenever it drops.
+    async fn listen(self: Arc<Self>) {
+        let mut last_event_id = None;
+        let mut failures = 0;
+        loop {
+            let mut request = self
+                .request(Method::GET, self.url.clone())
+                .header(ACCEPT, "text/event-stream");
+            if let Some(id) = &last_event_id {
+                request = request.header(LAST_EVENT_ID_HEADER, id);
+            }
+            let result = match request.send().await {
+                Ok(response)
+                    if self.kind == HttpTransportKind::StreamableHttp
+                        && response.status() == StatusCode::METHOD_NOT_ALLOWED =>
+                {
+                    // The server does not offer a stream for messages it initiates
+                    return;
+                },
+                Ok(response) if response.status().is_success() => {
+                    failures = 0;
+                    self.read_events(response, &mut last_event_id).await
+                },
+                Ok(response) => Err(TransportError::Custom(format!("responded with {}", response.status()))),
+                Err(err) => Err(TransportError::Custom(err.to_string())),
+            };
+
+            failures += 1;
+            if failures > MAX_RECONNECTS {
+                self.log(format!(
+                    "Giving up on the event stream after {MAX_RECONNECTS} reconnects"
+                ));
+                let _ = self.tx.send(Err(TransportError::Custom(
+                    "The event stream of the server closed".into(),
+                )));
+                return;
+            }
+            if self.kind == HttpTransportKind::Sse {
+                // The reconnected stream names a new endpoint
+                self.endpoint.send_replace(None);
+            }
+            let delay = RECONNECT_DELAY * 2u32.pow(failures - 1);
+            let reason = match result {
+                Ok(()) => "closed".to_string(),
+                Err(err) => format!("failed: {err}"),
+            };
+            self.log(format!(
+                "Event stream {reason}, reconnecting in {}ms",
+                delay.as_millis()
+            ));
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Forwards the messages of an event stream until it ends.
+    async fn read_events(
+        &self,
+        mut response: Response,
+        last_event_id: &mut Option<String>,
+    ) -> Result<(), TransportError> {
+        let mut parser = SseParser::default();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|err| TransportError::Custom(err.to_string()))?
+        {
+            for event in parser.push(&chunk) {
+                if event.id.is_some() {
+                    last_event_id.clone_from(&event.id);
+                }
+                match event.event.as_deref() {
+                    Some("endpoint") => match self.url.join(event.data.trim()) {
+                        Ok(endpoint) => match self.egress.check_url(endpoint.as_str()) {
+                            Ok(()) => {
+                                self.endpoint.send_replace(Some(endpoint));
+                            },
+                            Err(violation) => self.log(format!("Ignoring the endpoint {endpoint}: {violation}")),
+                        },
+                        Err(err) => self.log(format!("Invalid endpoint {}: {err}", event.data)),
+                    },
+                    None | Some("message") => self.deliver(event.data.as_bytes()),
+                    Some(_) => (),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for JsonRpcHttpTransport {
+    async fn send(&self, msg: &JsonRpcMessage) -> Result<(), TransportError> {
+        let url = self.shared.endpoint().await?;
+        let response = self.shared.post(url, serde_json::to_vec(msg)?).await?;
+
+        if let Some(session_id) = response.headers().get(SESSION_ID_HEADER) {
+            if let (Ok(session_id), Ok(mut current)) = (session_id.to_str(), self.shared.session_id.lock()) {
+                *current = Some(session_id.to_string());
+            }
+        }
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND && self.shared.session_id().is_some() {
+            // The session has to be started over with a new initialize request
+            if let Ok(mut session_id) = self.shared.session_id.lock() {
+                *session_id = None;
+            }
+            self.shared.log("The server ended the session".to_string());
+            return Err(TransportError::Custom("The server ended the session".into()));
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let err = format!("The server responded with {status}: {}", body.trim());
+            self.shared.log(err.clone());
+            return Err(TransportError::Custom(err));
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if content_type.starts_with("text/event-stream") {
+            // Responses to requests may take a while, the listener picks them up as they arrive
+            let shared = self.shared.clone();
+            tokio::spawn(async move {
+                if let Err(err) = shared.read_events(response, &mut None).await {
+                    shared.log(format!("Failed to read the response stream: {err}"));
+                }
+            });
+        } else if content_type.starts_with("application/json") {
+            let body = response
+                .bytes()
+                .await
+                .map_err(|err| TransportError::Custom(err.to_string()))?;
+            self.shared.deliver(&body);
+        }
+
+        if matches!(msg, JsonRpcMessage::Notification(notif) if notif.method == "notifications/initialized") {
+            self.start_stream();
+        }
+        Ok(())
+    }
+
+    fn get_listener(&self) -> impl Listener {
+        HttpListener {
+            receiver: self.receiver.resubscribe(),
+        }
+    }
+
+    async fn shutdown(&self) -> Result<(), TransportError> {
+        if let Some(stream_task) = self.stream_task.lock().ok().and_then(|mut task| task.take()) {
+            stream_task.abort();
+        }
+        if self.shared.kind == HttpTransportKind::StreamableHttp && self.shared.session_id().is_some() {
+            // Servers may not allow clients to end sessions, which is fine
+            let _ = self
+                .shared
+                .request(Method::DELETE, self.shared.url.clone())
+                .send()
+                .await;
+        }
+        Ok(())
+    }
+
+    fn get_log_listener(&self) -> impl LogListener {
+        HttpLogListener {
+            receiver: self.log_receiver.resubscribe(),
+        }
+    }
+}
+
+impl Drop for JsonRpcHttpTransport {
+    fn drop(&mut self) {
+        if let Some(stream_task) = self.stream_task.get_mut().ok().and_then(Option::take) {
+            stream_task.abort();
+        }
+    }
+}
+
+pub struct HttpListener {
+    pub receiver: broadcast::Receiver<Result<JsonRpcMessage, TransportError>>,
+}
+
+#[async_trait::async_trait]
+impl Listener for HttpListener {
+    async fn recv(&mut self) -> Result<JsonRpcMessage, TransportError> {
+        self.receiver.recv().await?
+    }
+}
+
+pub struct HttpLogListener {
+    pub receiver: broadcast::Receiver<String>,
+}
+
+#[async_trait::async_trait]
+impl LogListener for HttpLogListener {
+    async fn recv(&mut self) -> Result<String, TransportError> {
+        Ok(self.receiver.recv().await?)
+    }
+}
+
+/// An event of a `text/event-stream`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct SseEvent {
+    event: Option<String>,
+    data: String,
+    id: Option<String>,
+}
+
+/// Incremental parser for `text/event-stream` bodies, see
+/// https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation
+#[derive(Debug, Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    event: SseEvent,
+    has_data: bool,
+}
+
+impl SseParser {
+    /// Consumes a chunk of the stream, returning the events it completes.
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                // A blank line dispatches the event, unless it has no data
+                let event = std::mem::take(&mut self.event);
+                if std::mem::take(&mut self.has_data) {
+                    events.push(event);
+                }
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.event.event = Some(value.to_string()),
+                "data" => {
+                    if self.has_data {
+                        self.event.data.push('\n');
+                    }
+                    self.event.data.push_str(value);
+                    self.has_data = true;
+                },
+                "id" => self.event.id = Some(value.to_string()),
+                _ => (),
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_parser() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: endpoint\r\ndata: /messages?session").is_empty());
+        assert_eq!(parser.push(b"=1\r\n\r\n: keep-alive\n\n"), vec![SseEvent {
+            event: Some("endpoint".to_string()),
+            data: "/messages?session=1".to_string(),
+            id: None,
+        }]);
+
+        let events = parser.push(b"id: 7\ndata: {\"a\":\ndata: 1}\n\ndata:{}\n\n");
+        assert_eq!(events, vec![
+            SseEvent {
+                event: None,
+                data: "{\"a\":\n1}".to_string(),
+                id: Some("7".to_string()),
+            },
+            SseEvent {
+                event: None,
+                data: "{}".to_string(),
+                id: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let headers = HashMap::from([("Authorization".to_string(), "Bearer token".to_string())]);
+        assert!(
+            JsonRpcHttpTransport::client(
+                "not a url",
+                HttpTransportKind::StreamableHttp,
+                &headers,
+                Egress::default()
+            )
+            .is_err()
+        );
+
+        let headers = HashMap::from([("Bad Header".to_string(), "value".to_string())]);
+        assert!(
+            JsonRpcHttpTransport::client(
+                "https://example.com/mcp",
+                HttpTransportKind::StreamableHttp,
+                &headers,
+                Egress::default()
+            )
+            .is_err()
+        );
+    }
+}
@@ -24,6 +24,7 @@ use super::{
     Transport,
     TransportError,
 };
+use crate::mcp_client::StderrLog;
 
 #[derive(Debug)]
 pub enum JsonRpcStdioTransport {
@@ -31,6 +32,7 @@ pub enum JsonRpcStdioTransport {
         stdin: Arc<Mutex<ChildStdin>>,
         receiver: broadcast::Receiver<Result<JsonRpcMessage, TransportError>>,
         log_receiver: broadcast::Receiver<String>,
+        stderr_log: StderrLog,
     },
     Server {
         stdout: Arc<Mutex<Stdout>>,
@@ -80,12 +82,16 @@ impl JsonRpcStdioTransport {
             return Err(TransportError::Custom("No stderr found on child process".to_owned()));
         };
         let (log_tx, log_receiver) = broadcast::channel::<String>(100);
+        let stderr_log = StderrLog::default();
+        let stderr_log_clone = stderr_log.clone();
         tokio::task::spawn(async move {
             let stderr = tokio::io::BufReader::new(stderr);
             let mut lines = stderr.lines();
             while let Ok(Some(line)) = lines.next_line().await {
+                stderr_log_clone.push(line.clone());
                 let _ = log_tx.send(line);
             }
+            stderr_log_clone.close();
         });
         let stdin = Arc::new(Mutex::new(stdin));
         Self::spawn_reader(stdout, tx);
@@ -93,6 +99,7 @@ impl JsonRpcStdioTransport {
             stdin,
             receiver,
             log_receiver,
+            stderr_log,
         })
     }
 
@@ -102,6 +109,15 @@ impl JsonRpcStdioTransport {
         let stdout = Arc::new(Mutex::new(stdout));
         Ok(JsonRpcStdioTransport::Server { stdout, receiver })
     }
+
+    /// The recent stderr output of the server process, [None] for the server side of the
+    /// transport.
+    pub fn stderr_log(&self) -> Option<&StderrLog> {
+        match self {
+            JsonRpcStdioTransport::Client { stderr_log, .. } => Some(stderr_log),
+            JsonRpcStdioTransport::Server { .. } => None,
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -120,6 +120,8 @@ impl EnvVarDiagnostic {
                     "PATH",
                     "TERM",
                     "ZDOTDIR",
+                    // AWS vars
+                    "AWS_EXECUTION_ENV",
                     // Linux vars
                     "XDG_CURRENT_DESKTOP",
                     "XDG_SESSION_DESKTOP",
@@ -161,6 +163,8 @@ pub struct CurrentEnvironment {
     pub in_wsl: bool,
     #[serde(skip_serializing_if = "is_false")]
     pub in_codespaces: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloud_environment: Option<&'static str>,
 }
 
 impl CurrentEnvironment {
@@ -183,6 +187,7 @@ impl CurrentEnvironment {
         let in_ci = crate::util::system_info::in_ci();
         let in_wsl = crate::util::system_info::in_wsl();
         let in_codespaces = crate::util::system_info::in_codespaces();
+        let cloud_environment = crate::util::system_info::cloud_environment().map(|env| env.as_str());
 
         CurrentEnvironment {
             cwd,
@@ -192,6 +197,7 @@ impl CurrentEnvironment {
             in_ci,
             in_wsl,
             in_codespaces,
+            cloud_environment,
         }
     }
 }
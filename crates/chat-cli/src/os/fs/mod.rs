@@ -362,6 +362,17 @@ impl Fs {
         }
     }
 
+    /// Query the metadata about a file, following symlinks.
+    ///
+    /// This is a proxy to [`tokio::fs::metadata`]
+    pub async fn metadata(&self, path: impl AsRef<Path>) -> io::Result<std::fs::Metadata> {
+        match self {
+            Self::Real => fs::metadata(path).await,
+            Self::Chroot(root) => fs::metadata(append(root.path(), path)).await,
+            Self::Fake(_) => panic!("unimplemented"),
+        }
+    }
+
     /// Query the metadata about a file without following symlinks.
     ///
     /// This is a proxy to [`tokio::fs::symlink_metadata`]
@@ -4,7 +4,10 @@ use std::sync::{
     LazyLock,
 };
 
-use reqwest::Client;
+use reqwest::{
+    Client,
+    ClientBuilder,
+};
 use rustls::{
     ClientConfig,
     RootCertStore,
@@ -29,11 +32,14 @@ pub enum RequestError {
 }
 
 pub fn new_client() -> Result<Client, RequestError> {
-    Ok(Client::builder()
+    Ok(client_builder().cookie_store(true).build()?)
+}
+
+/// A builder for clients with the TLS configuration and user agent of [new_client].
+pub fn client_builder() -> ClientBuilder {
+    Client::builder()
         .use_preconfigured_tls(client_config())
         .user_agent(USER_AGENT.chars().filter(|c| c.is_ascii_graphic()).collect::<String>())
-        .cookie_store(true)
-        .build()?)
 }
 
 pub fn create_default_root_cert_store() -> RootCertStore {
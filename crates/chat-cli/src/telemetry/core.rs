@@ -1,12 +1,24 @@
 use std::fmt::Debug;
-use std::time::SystemTime;
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
 
 pub use amzn_toolkit_telemetry_client::types::MetricDatum;
+use serde_json::{
+    Value,
+    json,
+};
 use strum::{
     Display,
     EnumString,
 };
+use tracing::{
+    debug,
+    error,
+};
 
+use crate::os::Env;
 use crate::telemetry::definitions::IntoMetricDatum;
 use crate::telemetry::definitions::metrics::{
     AmazonqDidSelectProfile,
@@ -20,6 +32,7 @@ use crate::telemetry::definitions::metrics::{
     CodewhispererterminalMcpServerInit,
     CodewhispererterminalRefreshCredentials,
     CodewhispererterminalToolUseSuggested,
+    CodewhispererterminalUiError,
     CodewhispererterminalUserLoggedIn,
 };
 use crate::telemetry::definitions::types::{
@@ -28,13 +41,18 @@ use crate::telemetry::definitions::types::{
     CodewhispererterminalCustomToolOutputTokenSize,
     CodewhispererterminalIsToolValid,
     CodewhispererterminalMcpServerInitFailureReason,
+    CodewhispererterminalToolCpuTimeMs,
+    CodewhispererterminalToolExitSignal,
     CodewhispererterminalToolName,
+    CodewhispererterminalToolPeakMemoryKb,
     CodewhispererterminalToolUseId,
     CodewhispererterminalToolUseIsSuccess,
     CodewhispererterminalToolsPerMcpServer,
     CodewhispererterminalUserInputId,
     CodewhispererterminalUtteranceId,
 };
+use crate::util::process::ResourceUsage;
+use crate::util::system_info::in_cloudshell;
 
 /// A serializable telemetry event that can be sent or queued.
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -72,7 +90,7 @@ impl Event {
                     create_time: self.created_time,
                     value: None,
                     credential_start_url: self.credential_start_url.map(Into::into),
-                    codewhispererterminal_in_cloudshell: None,
+                    codewhispererterminal_in_cloudshell: Some(in_cloudshell().into()),
                 }
                 .into_metric_datum(),
             ),
@@ -90,7 +108,7 @@ impl Event {
                     result: Some(result.to_string().into()),
                     reason: reason.map(Into::into),
                     oauth_flow: Some(oauth_flow.into()),
-                    codewhispererterminal_in_cloudshell: None,
+                    codewhispererterminal_in_cloudshell: Some(in_cloudshell().into()),
                 }
                 .into_metric_datum(),
             ),
@@ -100,7 +118,7 @@ impl Event {
                     value: None,
                     credential_start_url: self.credential_start_url.map(Into::into),
                     codewhispererterminal_subcommand: Some(subcommand.into()),
-                    codewhispererterminal_in_cloudshell: None,
+                    codewhispererterminal_in_cloudshell: Some(in_cloudshell().into()),
                 }
                 .into_metric_datum(),
             ),
@@ -121,7 +139,7 @@ impl Event {
                     codewhispererterminal_chat_slash_subcommand: subcommand.map(Into::into),
                     result: Some(result.to_string().into()),
                     reason: reason.map(Into::into),
-                    codewhispererterminal_in_cloudshell: None,
+                    codewhispererterminal_in_cloudshell: Some(in_cloudshell().into()),
                 }
                 .into_metric_datum(),
             ),
@@ -131,7 +149,7 @@ impl Event {
                     value: None,
                     credential_start_url: self.credential_start_url.map(Into::into),
                     amazonq_conversation_id: Some(conversation_id.into()),
-                    codewhispererterminal_in_cloudshell: None,
+                    codewhispererterminal_in_cloudshell: Some(in_cloudshell().into()),
                     codewhispererterminal_model: model.map(Into::into),
                 }
                 .into_metric_datum(),
@@ -142,7 +160,7 @@ impl Event {
                     value: None,
                     credential_start_url: self.credential_start_url.map(Into::into),
                     amazonq_conversation_id: Some(conversation_id.into()),
-                    codewhispererterminal_in_cloudshell: None,
+                    codewhispererterminal_in_cloudshell: Some(in_cloudshell().into()),
                     codewhispererterminal_model: model.map(Into::into),
                 }
                 .into_metric_datum(),
@@ -157,6 +175,7 @@ impl Event {
                 reason_desc,
                 status_code,
                 model,
+                fallback_from_model,
                 ..
             } => Some(
                 CodewhispererterminalAddChatMessage {
@@ -167,13 +186,14 @@ impl Event {
                     codewhispererterminal_utterance_id: message_id.map(Into::into),
                     credential_start_url: self.credential_start_url.map(Into::into),
                     sso_region: self.sso_region.map(Into::into),
-                    codewhispererterminal_in_cloudshell: None,
+                    codewhispererterminal_in_cloudshell: Some(in_cloudshell().into()),
                     codewhispererterminal_context_file_length: context_file_length.map(|l| l as i64).map(Into::into),
                     result: result.to_string().into(),
                     reason: reason.map(Into::into),
                     reason_desc: reason_desc.map(Into::into),
                     status_code: status_code.map(|v| v as i64).map(Into::into),
                     codewhispererterminal_model: model.map(Into::into),
+                    codewhispererterminal_fallback_from_model: fallback_from_model.map(Into::into),
                 }
                 .into_metric_datum(),
             ),
@@ -190,6 +210,7 @@ impl Event {
                 input_token_size,
                 output_token_size,
                 custom_tool_call_latency,
+                resource_usage,
                 model,
             } => Some(
                 CodewhispererterminalToolUseSuggested {
@@ -211,6 +232,18 @@ impl Event {
                         .map(|s| CodewhispererterminalCustomToolOutputTokenSize(s as i64)),
                     codewhispererterminal_custom_tool_latency: custom_tool_call_latency
                         .map(|l| CodewhispererterminalCustomToolLatency(l as i64)),
+                    codewhispererterminal_tool_peak_memory_kb: resource_usage
+                        .as_ref()
+                        .and_then(|u| u.peak_rss_kb)
+                        .map(|kb| CodewhispererterminalToolPeakMemoryKb(kb as i64)),
+                    codewhispererterminal_tool_cpu_time_ms: resource_usage
+                        .as_ref()
+                        .and_then(|u| u.cpu_time_ms)
+                        .map(|ms| CodewhispererterminalToolCpuTimeMs(ms as i64)),
+                    codewhispererterminal_tool_exit_signal: resource_usage
+                        .as_ref()
+                        .and_then(|u| u.exit_signal)
+                        .map(|s| CodewhispererterminalToolExitSignal(s as i64)),
                     codewhispererterminal_model: model.map(Into::into),
                 }
                 .into_metric_datum(),
@@ -291,8 +324,151 @@ impl Event {
                 }
                 .into_metric_datum(),
             ),
+            EventType::UiError {
+                conversation_id,
+                kind,
+                reason,
+                terminal,
+            } => Some(
+                CodewhispererterminalUiError {
+                    create_time: self.created_time,
+                    value: None,
+                    credential_start_url: self.credential_start_url.map(Into::into),
+                    amazonq_conversation_id: conversation_id.map(Into::into),
+                    codewhispererterminal_in_cloudshell: Some(in_cloudshell().into()),
+                    codewhispererterminal_ui_error_kind: Some(kind.to_string().into()),
+                    reason: reason.map(Into::into),
+                    codewhispererterminal_terminal: terminal.term.map(Into::into),
+                    codewhispererterminal_terminal_program: terminal.program.map(Into::into),
+                    codewhispererterminal_terminal_multiplexer: terminal.multiplexer.map(Into::into),
+                    codewhispererterminal_terminal_colors: Some(terminal.colors.into()),
+                    codewhispererterminal_terminal_width: terminal.width.map(i64::from).map(Into::into),
+                    codewhispererterminal_terminal_height: terminal.height.map(i64::from).map(Into::into),
+                }
+                .into_metric_datum(),
+            ),
+        }
+    }
+}
+
+/// Exports events as OTLP log records to an OpenTelemetry collector over HTTP, using the JSON
+/// encoding of `ExportLogsServiceRequest`.
+#[derive(Debug, Clone)]
+pub struct OtlpExporter {
+    client: reqwest::Client,
+    logs_url: String,
+    headers: Vec<(String, String)>,
+}
+
+impl OtlpExporter {
+    /// Creates an exporter for the collector at `endpoint`, the base URL that `/v1/logs` is
+    /// appended to, sending `headers` (e.g. for authentication) with every request.
+    pub fn new(endpoint: &str, headers: Vec<(String, String)>) -> Result<Self, crate::request::RequestError> {
+        Ok(Self {
+            client: crate::request::new_client()?,
+            logs_url: format!("{}/v1/logs", endpoint.trim_end_matches('/')),
+            headers,
+        })
+    }
+
+    pub async fn export(&self, event: &Event) {
+        let body = otlp_export_request(std::slice::from_ref(event));
+        debug!(url = %self.logs_url, ?event, "Exporting telemetry event to OTLP collector");
+
+        let mut request = self.client.post(&self.logs_url).json(&body);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        match request.send().await {
+            Ok(response) if !response.status().is_success() => {
+                error!(status = %response.status(), url = %self.logs_url, "OTLP collector rejected telemetry event");
+            },
+            Ok(_) => {},
+            Err(err) => error!(%err, url = %self.logs_url, "Failed to export telemetry event to OTLP collector"),
+        }
+    }
+}
+
+/// Builds an `ExportLogsServiceRequest` with one log record per event. The body of each record
+/// is the event type and the attributes are the fields of the event.
+pub fn otlp_export_request(events: &[Event]) -> Value {
+    json!({
+        "resourceLogs": [{
+            "resource": {
+                "attributes": [
+                    otlp_attribute("service.name", &json!(crate::util::CLI_BINARY_NAME)),
+                    otlp_attribute("service.version", &json!(env!("CARGO_PKG_VERSION"))),
+                    otlp_attribute("os.type", &json!(std::env::consts::OS)),
+                    otlp_attribute("host.arch", &json!(std::env::consts::ARCH)),
+                ]
+            },
+            "scopeLogs": [{
+                "scope": { "name": "amazon-q-cli", "version": env!("CARGO_PKG_VERSION") },
+                "logRecords": events.iter().map(otlp_log_record).collect::<Vec<_>>(),
+            }]
+        }]
+    })
+}
+
+fn otlp_log_record(event: &Event) -> Value {
+    let unix_nanos = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+            .to_string()
+    };
+
+    let mut attributes = Vec::new();
+    let mut name = String::new();
+    if let Ok(Value::Object(fields)) = serde_json::to_value(event) {
+        for (key, value) in &fields {
+            match key.as_str() {
+                "type" => name = value.as_str().unwrap_or_default().to_string(),
+                "createdTime" => {},
+                _ if value.is_null() => {},
+                _ => attributes.push(otlp_attribute(key, value)),
+            }
         }
     }
+    attributes.insert(0, otlp_attribute("event.name", &json!(name)));
+
+    let mut record = json!({
+        "observedTimeUnixNano": unix_nanos(SystemTime::now()),
+        "severityNumber": 9,
+        "severityText": "INFO",
+        "body": { "stringValue": name },
+        "attributes": attributes,
+    });
+    if let Some(created_time) = event.created_time {
+        record["timeUnixNano"] = json!(unix_nanos(created_time));
+    }
+    record
+}
+
+fn otlp_attribute(key: &str, value: &Value) -> Value {
+    json!({ "key": key, "value": otlp_any_value(value) })
+}
+
+/// Converts a JSON value to an OTLP `AnyValue`. 64 bit integers are encoded as strings, as the
+/// OTLP JSON encoding requires.
+fn otlp_any_value(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({}),
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => json!({ "intValue": i.to_string() }),
+            None => json!({ "doubleValue": n.as_f64() }),
+        },
+        Value::String(s) => json!({ "stringValue": s }),
+        Value::Array(values) => {
+            json!({ "arrayValue": { "values": values.iter().map(otlp_any_value).collect::<Vec<_>>() } })
+        },
+        Value::Object(fields) => json!({
+            "kvlistValue": {
+                "values": fields.iter().map(|(key, value)| otlp_attribute(key, value)).collect::<Vec<_>>()
+            }
+        }),
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -334,6 +510,8 @@ pub enum EventType {
         reason_desc: Option<String>,
         status_code: Option<u16>,
         model: Option<String>,
+        /// The model selected before failing over to the one in `model`
+        fallback_from_model: Option<String>,
     },
     ToolUseSuggested {
         conversation_id: String,
@@ -348,6 +526,7 @@ pub enum EventType {
         input_token_size: Option<usize>,
         output_token_size: Option<usize>,
         custom_tool_call_latency: Option<usize>,
+        resource_usage: Option<ResourceUsage>,
         model: Option<String>,
     },
     McpServerInit {
@@ -376,6 +555,12 @@ pub enum EventType {
         conversation_id: String,
         context_file_length: Option<usize>,
     },
+    UiError {
+        conversation_id: Option<String>,
+        kind: UiErrorKind,
+        reason: Option<String>,
+        terminal: TerminalFingerprint,
+    },
 }
 
 #[derive(Debug)]
@@ -392,6 +577,8 @@ pub struct ToolUseEventBuilder {
     pub input_token_size: Option<usize>,
     pub output_token_size: Option<usize>,
     pub custom_tool_call_latency: Option<usize>,
+    /// Resources used by the subprocess the tool ran, if any
+    pub resource_usage: Option<ResourceUsage>,
     pub model: Option<String>,
 }
 
@@ -410,6 +597,7 @@ impl ToolUseEventBuilder {
             input_token_size: None,
             output_token_size: None,
             custom_tool_call_latency: None,
+            resource_usage: None,
             model,
         }
     }
@@ -455,6 +643,82 @@ impl From<SuggestionState> for amzn_codewhisperer_client::types::SuggestionState
     }
 }
 
+/// What went wrong rendering the terminal UI.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, EnumString, Display, serde::Serialize, serde::Deserialize)]
+pub enum UiErrorKind {
+    /// Rendering panicked, and the rest of the response was printed without formatting
+    Panic,
+    /// The markdown of a response couldn't be parsed
+    MarkdownParse,
+}
+
+/// The terminal the CLI runs in, to tell rendering bugs of different terminal emulators apart.
+/// Only the kind of terminal is kept, never versions, paths or session ids.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TerminalFingerprint {
+    /// `TERM`, e.g. `xterm-256color`
+    pub term: Option<String>,
+    /// The terminal emulator, e.g. `iTerm.app`
+    pub program: Option<String>,
+    pub multiplexer: Option<String>,
+    /// `truecolor`, `256`, `16` or `none`
+    pub colors: String,
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+}
+
+impl TerminalFingerprint {
+    pub fn detect(env: &Env) -> Self {
+        Self::from_env(env, crossterm::terminal::size().ok())
+    }
+
+    fn from_env(env: &Env, size: Option<(u16, u16)>) -> Self {
+        let var = |key: &str| env.get(key).ok().filter(|value| !value.is_empty());
+        let term = var("TERM").and_then(|term| anonymize(&term));
+
+        let program = var("TERM_PROGRAM")
+            .and_then(|program| anonymize(&program))
+            .or_else(|| var("WT_SESSION").map(|_| "WindowsTerminal".to_string()))
+            .or_else(|| var("KITTY_WINDOW_ID").map(|_| "kitty".to_string()))
+            .or_else(|| var("ALACRITTY_WINDOW_ID").map(|_| "alacritty".to_string()))
+            .or_else(|| var("KONSOLE_VERSION").map(|_| "konsole".to_string()))
+            .or_else(|| var("VTE_VERSION").map(|_| "vte".to_string()));
+
+        let multiplexer = [("TMUX", "tmux"), ("ZELLIJ", "zellij"), ("STY", "screen")]
+            .into_iter()
+            .find(|&(key, _)| var(key).is_some())
+            .map(|(_, name)| name.to_string());
+
+        let colors = if var("NO_COLOR").is_some() || term.as_deref() == Some("dumb") {
+            "none"
+        } else if var("COLORTERM").is_some_and(|colors| colors == "truecolor" || colors == "24bit") {
+            "truecolor"
+        } else if term.as_deref().is_some_and(|term| term.contains("256color")) {
+            "256"
+        } else {
+            "16"
+        };
+
+        Self {
+            term,
+            program,
+            multiplexer,
+            colors: colors.to_string(),
+            width: size.map(|(width, _)| width),
+            height: size.map(|(_, height)| height),
+        }
+    }
+}
+
+/// Keeps `value` only if it names a kind of terminal, rather than e.g. a path.
+fn anonymize(value: &str) -> Option<String> {
+    let valid = value.len() <= 32
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'));
+    valid.then(|| value.to_string())
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, EnumString, Display, serde::Serialize, serde::Deserialize)]
 pub enum TelemetryResult {
     Succeeded,
@@ -473,3 +737,103 @@ pub enum QProfileSwitchIntent {
     Update,
     Reload,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_otlp_log_record() {
+        let mut event = Event::new(EventType::ChatAddedMessage {
+            conversation_id: "conv".to_string(),
+            message_id: Some("msg".to_string()),
+            request_id: None,
+            context_file_length: Some(12),
+            result: TelemetryResult::Succeeded,
+            reason: None,
+            reason_desc: None,
+            status_code: None,
+            model: Some("claude".to_string()),
+            fallback_from_model: None,
+        });
+        event.created_time = Some(UNIX_EPOCH + Duration::from_secs(2));
+        event.set_start_url("https://example.awsapps.com/start".to_string());
+
+        let record = otlp_log_record(&event);
+        assert_eq!(record["timeUnixNano"], json!("2000000000"));
+        assert_eq!(record["body"], json!({ "stringValue": "chatAddedMessage" }));
+
+        let attributes = record["attributes"].as_array().unwrap();
+        let attribute = |key: &str| attributes.iter().find(|a| a["key"] == key).map(|a| a["value"].clone());
+        assert_eq!(
+            attribute("event.name"),
+            Some(json!({ "stringValue": "chatAddedMessage" }))
+        );
+        assert_eq!(attribute("conversation_id"), Some(json!({ "stringValue": "conv" })));
+        assert_eq!(attribute("context_file_length"), Some(json!({ "intValue": "12" })));
+        assert_eq!(attribute("result"), Some(json!({ "stringValue": "Succeeded" })));
+        assert_eq!(
+            attribute("credentialStartUrl"),
+            Some(json!({ "stringValue": "https://example.awsapps.com/start" }))
+        );
+        assert_eq!(attribute("request_id"), None);
+        assert_eq!(attribute("createdTime"), None);
+        assert_eq!(attribute("type"), None);
+    }
+
+    #[test]
+    fn test_terminal_fingerprint() {
+        let env = Env::from_slice(&[
+            ("TERM", "xterm-256color"),
+            ("TERM_PROGRAM", "iTerm.app"),
+            ("TERM_PROGRAM_VERSION", "3.5.0"),
+            ("TMUX", "/tmp/tmux-501/default,1234,0"),
+            ("COLORTERM", "truecolor"),
+        ]);
+        assert_eq!(
+            TerminalFingerprint::from_env(&env, Some((120, 40))),
+            TerminalFingerprint {
+                term: Some("xterm-256color".to_string()),
+                program: Some("iTerm.app".to_string()),
+                multiplexer: Some("tmux".to_string()),
+                colors: "truecolor".to_string(),
+                width: Some(120),
+                height: Some(40),
+            }
+        );
+
+        let env = Env::from_slice(&[
+            ("TERM", "/home/user/terminfo"),
+            ("WT_SESSION", "abc"),
+            ("NO_COLOR", "1"),
+        ]);
+        let fingerprint = TerminalFingerprint::from_env(&env, None);
+        assert_eq!(fingerprint.term, None);
+        assert_eq!(fingerprint.program.as_deref(), Some("WindowsTerminal"));
+        assert_eq!(fingerprint.colors, "none");
+        assert_eq!(fingerprint.width, None);
+    }
+
+    #[test]
+    fn test_otlp_any_value() {
+        assert_eq!(otlp_any_value(&json!(true)), json!({ "boolValue": true }));
+        assert_eq!(otlp_any_value(&json!(1.5)), json!({ "doubleValue": 1.5 }));
+        assert_eq!(
+            otlp_any_value(&json!({ "cpuTimeMs": 3, "peaks": ["a"] })),
+            json!({ "kvlistValue": { "values": [
+                { "key": "cpuTimeMs", "value": { "intValue": "3" } },
+                { "key": "peaks", "value": { "arrayValue": { "values": [{ "stringValue": "a" }] } } },
+            ] } })
+        );
+    }
+
+    #[test]
+    fn test_otlp_export_request() {
+        let request = otlp_export_request(&[Event::new(EventType::UserLoggedIn {})]);
+        let records = &request["resourceLogs"][0]["scopeLogs"][0]["logRecords"];
+        assert_eq!(records.as_array().unwrap().len(), 1);
+        assert_eq!(records[0]["body"], json!({ "stringValue": "userLoggedIn" }));
+    }
+}
@@ -4,8 +4,17 @@ pub mod definitions;
 pub mod endpoint;
 mod install_method;
 
-use core::ToolUseEventBuilder;
+use core::{
+    OtlpExporter,
+    TerminalFingerprint,
+    ToolUseEventBuilder,
+};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
 
 use amzn_codewhisperer_client::types::{
     ChatAddMessageEvent,
@@ -65,6 +74,7 @@ pub use crate::telemetry::core::{
     EventType,
     QProfileSwitchIntent,
     TelemetryResult,
+    UiErrorKind,
 };
 use crate::util::system_info::os_version;
 
@@ -175,6 +185,8 @@ impl Clone for TelemetrySender {
 pub struct TelemetryThread {
     handle: Option<JoinHandle<()>>,
     tx: TelemetrySender,
+    /// Shared by every clone, see [Self::set_errors_only].
+    errors_only: Arc<AtomicBool>,
 }
 
 impl Clone for TelemetryThread {
@@ -182,6 +194,7 @@ impl Clone for TelemetryThread {
         Self {
             handle: None,
             tx: self.tx.clone(),
+            errors_only: Arc::clone(&self.errors_only),
         }
     }
 }
@@ -201,9 +214,23 @@ impl TelemetryThread {
         Ok(Self {
             handle: Some(handle),
             tx,
+            errors_only: Arc::new(AtomicBool::new(false)),
         })
     }
 
+    /// Stops sending any event but response errors, e.g. for an incognito chat session, or starts
+    /// sending them again.
+    pub fn set_errors_only(&self, errors_only: bool) {
+        self.errors_only.store(errors_only, Ordering::Relaxed);
+    }
+
+    fn send(&self, event: Event) -> Result<(), TelemetryError> {
+        if self.errors_only.load(Ordering::Relaxed) && !matches!(event.ty, EventType::MessageResponseError { .. }) {
+            return Ok(());
+        }
+        Ok(self.tx.send(event)?)
+    }
+
     pub async fn finish(self) -> Result<(), TelemetryError> {
         drop(self.tx);
         if let Some(handle) = self.handle {
@@ -223,13 +250,27 @@ impl TelemetryThread {
     }
 
     pub fn send_user_logged_in(&self) -> Result<(), TelemetryError> {
-        Ok(self.tx.send(Event::new(EventType::UserLoggedIn {}))?)
+        self.send(Event::new(EventType::UserLoggedIn {}))
+    }
+
+    pub fn send_refresh_credentials(
+        &self,
+        result: TelemetryResult,
+        reason: Option<String>,
+        oauth_flow: String,
+    ) -> Result<(), TelemetryError> {
+        self.send(Event::new(EventType::RefreshCredentials {
+            request_id: String::new(),
+            result,
+            reason,
+            oauth_flow,
+        }))
     }
 
     pub fn send_cli_subcommand_executed(&self, subcommand: &RootSubcommand) -> Result<(), TelemetryError> {
-        Ok(self.tx.send(Event::new(EventType::CliSubcommandExecuted {
+        self.send(Event::new(EventType::CliSubcommandExecuted {
             subcommand: subcommand.to_string(),
-        }))?)
+        }))
     }
 
     pub async fn send_chat_slash_command_executed(
@@ -249,7 +290,7 @@ impl TelemetryThread {
             reason,
         });
         set_start_url_and_region(database, &mut event).await;
-        Ok(self.tx.send(event)?)
+        self.send(event)
     }
 
     #[allow(clippy::too_many_arguments)] // TODO: Should make a parameters struct.
@@ -265,6 +306,7 @@ impl TelemetryThread {
         reason_desc: Option<String>,
         status_code: Option<u16>,
         model: Option<String>,
+        fallback_from_model: Option<String>,
     ) -> Result<(), TelemetryError> {
         let mut event = Event::new(EventType::ChatAddedMessage {
             conversation_id,
@@ -276,14 +318,15 @@ impl TelemetryThread {
             reason_desc,
             status_code,
             model,
+            fallback_from_model,
         });
         set_start_url_and_region(database, &mut event).await;
 
-        Ok(self.tx.send(event)?)
+        self.send(event)
     }
 
     pub fn send_tool_use_suggested(&self, event: ToolUseEventBuilder) -> Result<(), TelemetryError> {
-        Ok(self.tx.send(Event::new(EventType::ToolUseSuggested {
+        self.send(Event::new(EventType::ToolUseSuggested {
             conversation_id: event.conversation_id,
             utterance_id: event.utterance_id,
             user_input_id: event.user_input_id,
@@ -296,8 +339,9 @@ impl TelemetryThread {
             input_token_size: event.input_token_size,
             output_token_size: event.output_token_size,
             custom_tool_call_latency: event.custom_tool_call_latency,
+            resource_usage: event.resource_usage,
             model: event.model,
-        }))?)
+        }))
     }
 
     pub fn send_mcp_server_init(
@@ -306,11 +350,11 @@ impl TelemetryThread {
         init_failure_reason: Option<String>,
         number_of_tools: usize,
     ) -> Result<(), TelemetryError> {
-        Ok(self.tx.send(Event::new(crate::telemetry::EventType::McpServerInit {
+        self.send(Event::new(crate::telemetry::EventType::McpServerInit {
             conversation_id,
             init_failure_reason,
             number_of_tools,
-        }))?)
+        }))
     }
 
     pub fn send_did_select_profile(
@@ -321,13 +365,13 @@ impl TelemetryThread {
         sso_region: Option<String>,
         profile_count: Option<i64>,
     ) -> Result<(), TelemetryError> {
-        Ok(self.tx.send(Event::new(EventType::DidSelectProfile {
+        self.send(Event::new(EventType::DidSelectProfile {
             source,
             amazonq_profile_region,
             result,
             sso_region,
             profile_count,
-        }))?)
+        }))
     }
 
     pub fn send_profile_state(
@@ -337,12 +381,12 @@ impl TelemetryThread {
         result: TelemetryResult,
         sso_region: Option<String>,
     ) -> Result<(), TelemetryError> {
-        Ok(self.tx.send(Event::new(EventType::ProfileState {
+        self.send(Event::new(EventType::ProfileState {
             source,
             amazonq_profile_region,
             result,
             sso_region,
-        }))?)
+        }))
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -366,7 +410,24 @@ impl TelemetryThread {
         });
         set_start_url_and_region(database, &mut event).await;
 
-        Ok(self.tx.send(event)?)
+        self.send(event)
+    }
+
+    /// Reports that rendering the terminal UI failed or degraded, along with the kind of terminal
+    /// it happened in.
+    pub fn send_ui_error(
+        &self,
+        env: &Env,
+        conversation_id: Option<String>,
+        kind: UiErrorKind,
+        reason: Option<String>,
+    ) -> Result<(), TelemetryError> {
+        self.send(Event::new(EventType::UiError {
+            conversation_id,
+            kind,
+            reason,
+            terminal: TerminalFingerprint::detect(env),
+        }))
     }
 }
 
@@ -380,12 +441,43 @@ async fn set_start_url_and_region(database: &Database, event: &mut Event) {
     }
 }
 
+/// Creates the exporter for the OTLP collector configured in `telemetry.otlp.endpoint`. This is
+/// independent of `telemetry.enabled`, so events can be sent to a collector alone by disabling
+/// telemetry.
+fn otlp_exporter(database: &Database) -> Option<OtlpExporter> {
+    let endpoint = database.settings.get_string(Setting::TelemetryOtlpEndpoint)?;
+    let headers = database
+        .settings
+        .get_string(Setting::TelemetryOtlpHeaders)
+        .map(|headers| parse_otlp_headers(&headers))
+        .unwrap_or_default();
+
+    match OtlpExporter::new(&endpoint, headers) {
+        Ok(exporter) => Some(exporter),
+        Err(err) => {
+            error!(%err, "Failed to create the OTLP exporter");
+            None
+        },
+    }
+}
+
+/// Parses headers in the `key1=value1,key2=value2` format of `OTEL_EXPORTER_OTLP_HEADERS`.
+fn parse_otlp_headers(headers: &str) -> Vec<(String, String)> {
+    headers
+        .split(',')
+        .filter_map(|header| header.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
 #[derive(Debug)]
 struct TelemetryClient {
     client_id: Uuid,
     telemetry_enabled: bool,
     codewhisperer_client: Option<ApiClient>,
     toolkit_telemetry_client: Option<ToolkitTelemetryClient>,
+    otlp_exporter: Option<OtlpExporter>,
 }
 
 impl TelemetryClient {
@@ -453,14 +545,18 @@ impl TelemetryClient {
             telemetry_enabled,
             toolkit_telemetry_client,
             codewhisperer_client,
+            otlp_exporter: otlp_exporter(database),
         })
     }
 
-    /// Sends a telemetry event to both the CW and toolkit API's. If the clients do not exist, then
-    /// telemetry is not sent.
+    /// Sends a telemetry event to both the CW and toolkit API's, and to the OTLP collector if one
+    /// is configured. If the clients do not exist, then telemetry is not sent.
     ///
     /// See [TelemetryClient::new] for which conditions the clients are created for.
     async fn send_event(&self, event: Event) {
+        if let Some(otlp_exporter) = &self.otlp_exporter {
+            otlp_exporter.export(&event).await;
+        }
         self.send_cw_telemetry_event(&event).await;
         self.send_telemetry_toolkit_metric(event).await;
     }
@@ -618,6 +714,18 @@ mod test {
         assert_eq!(context.ide_version.as_deref(), Some(PRODUCT_VERSION));
     }
 
+    #[test]
+    fn test_parse_otlp_headers() {
+        assert_eq!(
+            parse_otlp_headers("Authorization=Bearer abc, x-team = q,invalid,=empty"),
+            vec![
+                ("Authorization".to_string(), "Bearer abc".to_string()),
+                ("x-team".to_string(), "q".to_string()),
+            ]
+        );
+        assert!(parse_otlp_headers("").is_empty());
+    }
+
     #[tracing_test::traced_test]
     #[tokio::test]
     #[ignore = "needs auth which is not in CI"]
@@ -661,6 +769,7 @@ mod test {
                 None,
                 None,
                 None,
+                None,
             )
             .await
             .ok();
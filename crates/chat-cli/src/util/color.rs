@@ -0,0 +1,191 @@
+//! Whether output is colored, decided once at startup from `--no-color`, the `NO_COLOR`,
+//! `CLICOLOR` and `CLICOLOR_FORCE` environment variables and whether stdout is a terminal.
+
+use std::io::{
+    self,
+    Write,
+};
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+
+use crate::os::Env;
+
+static COLORS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Decides whether output should be colored, following <https://no-color.org> and
+/// <https://bixense.com/clicolors>.
+pub fn colors_enabled(env: &Env, no_color_flag: bool, stdout_is_terminal: bool) -> bool {
+    if no_color_flag || env.get("NO_COLOR").is_ok_and(|value| !value.is_empty()) {
+        return false;
+    }
+    if env
+        .get("CLICOLOR_FORCE")
+        .is_ok_and(|value| !value.is_empty() && value != "0")
+    {
+        return true;
+    }
+    if env.get("CLICOLOR").is_ok_and(|value| value == "0") {
+        return false;
+    }
+    stdout_is_terminal
+}
+
+/// Applies the decision of [colors_enabled] to every output path: crossterm commands and
+/// [crossterm::style::Stylize], the `anstream` print macros and [StyleFilter].
+pub fn init(enabled: bool) {
+    COLORS_ENABLED.store(enabled, Ordering::Relaxed);
+    crossterm::style::force_color_output(enabled);
+    if !enabled {
+        anstream::ColorChoice::Never.write_global();
+    }
+}
+
+pub fn enabled() -> bool {
+    COLORS_ENABLED.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilterState {
+    Text,
+    /// After an ESC byte
+    Escape,
+    /// Inside a control sequence, i.e. after `ESC [`
+    Csi,
+}
+
+/// Writer that drops SGR escape sequences (colors and text attributes) when colors are disabled,
+/// including those of strings styled ahead of time, e.g. with [color_print::cstr]. Other escape
+/// sequences such as cursor movement are kept. Sequences split across writes are handled.
+#[derive(Debug)]
+pub struct StyleFilter<W> {
+    inner: W,
+    strip: fn() -> bool,
+    muted: bool,
+    state: FilterState,
+    pending: Vec<u8>,
+    /// Reused for the output of every write that has sequences stripped
+    out: Vec<u8>,
+}
+
+impl<W: Write> StyleFilter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            strip: || !enabled(),
+            muted: false,
+            state: FilterState::Text,
+            pending: Vec::new(),
+            out: Vec::new(),
+        }
+    }
+
+    /// Discards everything written from now on, for output that is replaced by another format.
+    pub fn mute(&mut self) {
+        self.muted = true;
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write> Write for StyleFilter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.muted {
+            return Ok(buf.len());
+        }
+        if self.state == FilterState::Text && !(self.strip)() {
+            return self.inner.write(buf);
+        }
+
+        let mut out = std::mem::take(&mut self.out);
+        out.clear();
+        for &byte in buf {
+            match self.state {
+                FilterState::Text if byte == 0x1b => {
+                    self.state = FilterState::Escape;
+                    self.pending.push(byte);
+                },
+                FilterState::Text => out.push(byte),
+                FilterState::Escape if byte == b'[' => {
+                    self.state = FilterState::Csi;
+                    self.pending.push(byte);
+                },
+                FilterState::Escape => {
+                    out.append(&mut self.pending);
+                    out.push(byte);
+                    self.state = FilterState::Text;
+                },
+                FilterState::Csi => {
+                    self.pending.push(byte);
+                    // A final byte ends the sequence, which is an SGR sequence if it is 'm'
+                    if (0x40..=0x7e).contains(&byte) {
+                        match byte {
+                            b'm' => self.pending.clear(),
+                            _ => out.append(&mut self.pending),
+                        }
+                        self.state = FilterState::Text;
+                    }
+                },
+            }
+        }
+        let written = self.inner.write_all(&out);
+        self.out = out;
+        written?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors_enabled() {
+        let env = |vars: &[(&str, &str)]| Env::from_slice(vars);
+        assert!(colors_enabled(&env(&[]), false, true));
+        assert!(!colors_enabled(&env(&[]), false, false));
+        assert!(!colors_enabled(&env(&[]), true, true));
+        assert!(!colors_enabled(&env(&[("NO_COLOR", "1")]), false, true));
+        assert!(colors_enabled(&env(&[("NO_COLOR", "")]), false, true));
+        assert!(!colors_enabled(&env(&[("CLICOLOR", "0")]), false, true));
+        assert!(colors_enabled(&env(&[("CLICOLOR_FORCE", "1")]), false, false));
+        assert!(!colors_enabled(
+            &env(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]),
+            false,
+            false
+        ));
+    }
+
+    #[test]
+    fn test_style_filter() {
+        let mut filter = StyleFilter {
+            strip: || true,
+            ..StyleFilter::new(Vec::new())
+        };
+        write!(filter, "\x1b[38;5;1mred\x1b[0m \x1b[2K").unwrap();
+        // Sequences split across writes
+        filter.write_all(b"\x1b").unwrap();
+        filter.write_all(b"[1").unwrap();
+        filter.write_all(b"mbold\x1b[").unwrap();
+        filter.write_all(b"22m\x1b[1G").unwrap();
+        assert_eq!(filter.inner, b"red \x1b[2Kbold\x1b[1G");
+
+        let mut filter = StyleFilter {
+            strip: || false,
+            ..StyleFilter::new(Vec::new())
+        };
+        write!(filter, "\x1b[31mred\x1b[0m").unwrap();
+        assert_eq!(filter.inner, b"\x1b[31mred\x1b[0m");
+
+        filter.mute();
+        write!(filter, "hidden").unwrap();
+        assert_eq!(filter.inner, b"\x1b[31mred\x1b[0m");
+    }
+}
@@ -0,0 +1,222 @@
+//! Dates, times and durations as they are shown to users, in their timezone and in the date order
+//! of their locale. Both can be overridden with the `chat.timezone` and `chat.locale` settings.
+//!
+//! Output meant for other programs, such as JSON, keeps using RFC 3339 in UTC.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use time::{
+    Date,
+    OffsetDateTime,
+    UtcOffset,
+};
+
+use crate::database::settings::{
+    Setting,
+    Settings,
+};
+
+static LOCAL_OFFSET: OnceLock<UtcOffset> = OnceLock::new();
+
+/// Languages whose dates are written as day.month.year.
+const DOTTED_DAY_FIRST_LANGUAGES: &[&str] = &[
+    "cs", "da", "de", "et", "fi", "hr", "is", "lv", "nb", "nn", "no", "pl", "ro", "ru", "sk", "sl", "sr", "tr", "uk",
+];
+/// Languages whose dates are written with the year first.
+const YEAR_FIRST_LANGUAGES: &[&str] = &["hu", "ja", "ko", "lt", "sv", "zh"];
+/// Regions that use a 12-hour clock.
+const TWELVE_HOUR_REGIONS: &[&str] = &["AU", "CA", "IN", "NZ", "PH", "US"];
+
+/// Reads the offset of the local timezone. This has to happen before any other thread is started,
+/// since the offset can't be read soundly once there are several on most Unix platforms.
+pub fn init_local_offset() {
+    if let Ok(offset) = UtcOffset::current_local_offset() {
+        LOCAL_OFFSET.set(offset).ok();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    YearMonthDay,
+    MonthDayYear,
+    DayMonthYear,
+}
+
+/// How dates and times are shown to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTimeFormat {
+    offset: UtcOffset,
+    order: DateOrder,
+    separator: char,
+    twelve_hour: bool,
+}
+
+impl DateTimeFormat {
+    /// The format from the `chat.locale` and `chat.timezone` settings, or from the locale and
+    /// timezone of the system for those that aren't set.
+    pub fn new(settings: &Settings) -> Self {
+        let offset = settings
+            .get_string(Setting::ChatTimezone)
+            .and_then(|timezone| parse_offset(&timezone))
+            .or_else(|| LOCAL_OFFSET.get().copied())
+            .unwrap_or(UtcOffset::UTC);
+        let locale = settings
+            .get_string(Setting::ChatLocale)
+            .or_else(system_locale)
+            .unwrap_or_default();
+        Self::for_locale(&locale, offset)
+    }
+
+    /// The format for a POSIX or BCP 47 locale name such as `de_DE.UTF-8` or `en-US`. Unknown
+    /// locales, and `C` or `POSIX`, get ISO 8601 dates.
+    fn for_locale(locale: &str, offset: UtcOffset) -> Self {
+        let name = locale.split(['.', '@']).next().unwrap_or_default();
+        let (language, region) = match name.split_once(['_', '-']) {
+            Some((language, region)) => (language.to_lowercase(), region.to_uppercase()),
+            None => (name.to_lowercase(), String::new()),
+        };
+        let (order, separator) = match language.as_str() {
+            "" | "c" | "posix" => (DateOrder::YearMonthDay, '-'),
+            "en" if region == "US" || region == "PH" => (DateOrder::MonthDayYear, '/'),
+            "en" if region.is_empty() || region == "CA" => (DateOrder::YearMonthDay, '-'),
+            "ja" | "zh" => (DateOrder::YearMonthDay, '/'),
+            language if YEAR_FIRST_LANGUAGES.contains(&language) => (DateOrder::YearMonthDay, '-'),
+            language if DOTTED_DAY_FIRST_LANGUAGES.contains(&language) => (DateOrder::DayMonthYear, '.'),
+            "nl" => (DateOrder::DayMonthYear, '-'),
+            _ => (DateOrder::DayMonthYear, '/'),
+        };
+        Self {
+            offset,
+            order,
+            separator,
+            twelve_hour: language == "en" && TWELVE_HOUR_REGIONS.contains(&region.as_str()),
+        }
+    }
+
+    /// The current time in the user's timezone.
+    pub fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc().to_offset(self.offset)
+    }
+
+    /// The current date in the user's timezone.
+    pub fn today(&self) -> Date {
+        self.now().date()
+    }
+
+    pub fn date(&self, date: Date) -> String {
+        let (year, month, day) = (date.year(), u8::from(date.month()), date.day());
+        let sep = self.separator;
+        match self.order {
+            DateOrder::YearMonthDay => format!("{year}{sep}{month:02}{sep}{day:02}"),
+            DateOrder::MonthDayYear => format!("{month:02}{sep}{day:02}{sep}{year}"),
+            DateOrder::DayMonthYear => format!("{day:02}{sep}{month:02}{sep}{year}"),
+        }
+    }
+
+    /// The date and time to the minute, in the user's timezone.
+    pub fn date_time(&self, time: OffsetDateTime) -> String {
+        let time = time.to_offset(self.offset);
+        let (hour, minute) = (time.hour(), time.minute());
+        let clock = match self.twelve_hour {
+            true => {
+                let period = if hour < 12 { "AM" } else { "PM" };
+                format!("{}:{minute:02} {period}", (hour + 11) % 12 + 1)
+            },
+            false => format!("{hour:02}:{minute:02}"),
+        };
+        format!("{} {clock}", self.date(time.date()))
+    }
+
+    /// Like [Self::date_time] for a Unix timestamp in seconds, which is shown as it is if it is out
+    /// of range.
+    pub fn timestamp(&self, timestamp: i64) -> String {
+        match OffsetDateTime::from_unix_timestamp(timestamp) {
+            Ok(time) => self.date_time(time),
+            Err(_) => timestamp.to_string(),
+        }
+    }
+}
+
+/// The locale for dates, from the same variables the C library reads it from.
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// Parses `UTC` or an offset such as `+02:00` or `-0530`. `local`, like anything else, is left to
+/// the timezone of the system.
+fn parse_offset(timezone: &str) -> Option<UtcOffset> {
+    let timezone = timezone.trim();
+    if timezone.eq_ignore_ascii_case("utc") || timezone.eq_ignore_ascii_case("z") {
+        return Some(UtcOffset::UTC);
+    }
+    let (sign, rest) = match timezone.split_at_checked(1)? {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let digits = rest.replace(':', "");
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i8>().ok()?, 0),
+        4 => (digits[..2].parse::<i8>().ok()?, digits[2..].parse::<i8>().ok()?),
+        _ => return None,
+    };
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+/// A duration in the largest units that matter, e.g. `850ms`, `4.2s`, `2m 10s` or `1h 30m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, (secs % 3600) / 60, secs % 60) {
+        (0, 0, 0) => format!("{}ms", duration.as_millis()),
+        (0, 0, s) if s < 10 => format!("{:.1}s", duration.as_secs_f64()),
+        (0, 0, s) => format!("{s}s"),
+        (0, m, 0) => format!("{m}m"),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, m, _) => format!("{h}h {m}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_for_locale() {
+        let time = datetime!(2026-03-07 18:05 UTC);
+        let format = |locale: &str| DateTimeFormat::for_locale(locale, UtcOffset::UTC).date_time(time);
+        assert_eq!(format("en_US.UTF-8"), "03/07/2026 6:05 PM");
+        assert_eq!(format("en_GB.UTF-8"), "07/03/2026 18:05");
+        assert_eq!(format("de-DE"), "07.03.2026 18:05");
+        assert_eq!(format("ja_JP.UTF-8"), "2026/03/07 18:05");
+        assert_eq!(format("C"), "2026-03-07 18:05");
+        assert_eq!(format(""), "2026-03-07 18:05");
+
+        let tokyo = DateTimeFormat::for_locale("C", parse_offset("+09:00").unwrap());
+        assert_eq!(tokyo.date_time(time), "2026-03-08 03:05");
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("UTC"), Some(UtcOffset::UTC));
+        assert_eq!(parse_offset("-0530"), UtcOffset::from_hms(-5, -30, 0).ok());
+        assert_eq!(parse_offset("+2"), UtcOffset::from_hms(2, 0, 0).ok());
+        assert_eq!(parse_offset("local"), None);
+        assert_eq!(parse_offset("Europe/Berlin"), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(850)), "850ms");
+        assert_eq!(format_duration(Duration::from_millis(4200)), "4.2s");
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(600)), "10m");
+        assert_eq!(format_duration(Duration::from_secs(130)), "2m 10s");
+        assert_eq!(format_duration(Duration::from_secs(5400)), "1h 30m");
+    }
+}
@@ -151,6 +151,27 @@ pub fn chat_local_agent_dir() -> Result<PathBuf> {
     Ok(cwd.join(agent_config_dir()))
 }
 
+/// The workspace `.amazonq` directory, as scaffolded by `q init`
+pub fn workspace_config_dir(os: &Os) -> Result<PathBuf> {
+    Ok(os.env.current_dir()?.join(".amazonq"))
+}
+
+/// The workspace config declaring the roots of a multi-root workspace
+pub fn workspace_config_path(os: &Os) -> Result<PathBuf> {
+    Ok(workspace_config_dir(os)?.join("workspace.json"))
+}
+
+/// The directory containing agents defined under the workspace `.amazonq` directory
+pub fn chat_workspace_agent_dir(os: &Os) -> Result<PathBuf> {
+    Ok(workspace_config_dir(os)?.join("agents"))
+}
+
+/// The directory containing the workspace rules files. These are included as resources by the
+/// default agent.
+pub fn chat_workspace_rules_dir(os: &Os) -> Result<PathBuf> {
+    Ok(workspace_config_dir(os)?.join("rules"))
+}
+
 /// The relative path to the agent configuration directory
 ///
 /// This directory contains agent configuration files for Amazon Q.
@@ -161,6 +182,11 @@ pub fn agent_config_dir() -> PathBuf {
     PathBuf::from(".aws/amazonq/agents")
 }
 
+/// The directory containing executables that are exposed as slash commands in `q chat`
+pub fn chat_commands_dir(os: &Os) -> Result<PathBuf> {
+    Ok(home_dir(os)?.join(".aws").join("amazonq").join("commands"))
+}
+
 /// The directory to the directory containing config for the `/context` feature in `q chat`.
 pub fn chat_global_context_path(os: &Os) -> Result<PathBuf> {
     Ok(home_dir(os)?.join(".aws").join("amazonq").join("global_context.json"))
@@ -182,6 +208,31 @@ pub fn database_path() -> Result<PathBuf> {
     Ok(fig_data_dir()?.join("data.sqlite3"))
 }
 
+/// The directory containing copies of files taken before tools in `q chat` changed them
+pub fn chat_backups_dir() -> Result<PathBuf> {
+    Ok(fig_data_dir()?.join("backups"))
+}
+
+/// The store of the contents files had before chat sessions changed them, for `q restore`
+pub fn chat_snapshots_dir() -> Result<PathBuf> {
+    Ok(fig_data_dir()?.join("snapshots"))
+}
+
+/// The cache of the web documents added to the context of `q chat` with `/context add <url>`
+pub fn chat_context_url_cache_dir() -> Result<PathBuf> {
+    Ok(fig_data_dir()?.join("context-urls"))
+}
+
+/// The contents of context files restored from conversation bundles by `q history import`
+pub fn chat_imported_context_dir() -> Result<PathBuf> {
+    Ok(fig_data_dir()?.join("imported-context"))
+}
+
+/// The path to the model registry last fetched from `chat.modelRegistryUrl`
+pub fn model_registry_cache_path() -> Result<PathBuf> {
+    Ok(fig_data_dir()?.join("models.json"))
+}
+
 #[cfg(test)]
 mod linux_tests {
     use super::*;
@@ -0,0 +1,206 @@
+//! Failures injected on demand into tool and API paths so that retry, cancellation and error
+//! reporting can be exercised in automated tests. Only compiled with the `fault-injection`
+//! feature.
+//!
+//! Faults are configured with the `Q_FAULT_INJECT` environment variable as a comma separated list
+//! of `<target>=<action>` pairs, e.g.
+//! `Q_FAULT_INJECT="tool:fs_write=fail,tool:*=delay:500,stream=drop:10"`.
+//!
+//! Targets:
+//! - `tool:<name>` - invoking the tool with the given name, or any tool for `tool:*`
+//! - `send_message` - sending a chat request to the backend
+//! - `stream` - receiving each event of a chat response
+//!
+//! Actions:
+//! - `fail` or `fail:<message>` - return an error
+//! - `delay:<ms>` - wait before continuing
+//! - `drop:<n>` - drop the connection once `n` events have been received (stream only)
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use tracing::{
+    error,
+    warn,
+};
+
+use crate::api_client::ApiClientError;
+
+pub const FAULT_INJECT_ENV: &str = "Q_FAULT_INJECT";
+
+static INJECTOR: LazyLock<FaultInjector> = LazyLock::new(|| {
+    let Ok(spec) = std::env::var(FAULT_INJECT_ENV) else {
+        return FaultInjector::default();
+    };
+    match FaultInjector::parse(&spec) {
+        Ok(injector) => {
+            warn!(?injector, "fault injection is enabled");
+            injector
+        },
+        Err(err) => {
+            error!(%err, "ignoring invalid {FAULT_INJECT_ENV}");
+            FaultInjector::default()
+        },
+    }
+});
+
+/// The faults configured through `Q_FAULT_INJECT`.
+pub fn injector() -> &'static FaultInjector {
+    &INJECTOR
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    /// A tool by name, [None] matching every tool
+    Tool(Option<String>),
+    SendMessage,
+    Stream,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Fail(Option<String>),
+    Delay(Duration),
+    Drop(usize),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FaultInjector {
+    faults: Vec<(Target, Action)>,
+}
+
+impl FaultInjector {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut faults = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((target, action)) = entry.split_once('=') else {
+                return Err(format!("expected <target>=<action>, got '{entry}'"));
+            };
+            let target = match target.trim() {
+                "send_message" => Target::SendMessage,
+                "stream" => Target::Stream,
+                "tool:*" => Target::Tool(None),
+                other => match other.strip_prefix("tool:") {
+                    Some(name) if !name.is_empty() => Target::Tool(Some(name.to_string())),
+                    _ => return Err(format!("unknown target '{other}'")),
+                },
+            };
+            let (kind, arg) = match action.trim().split_once(':') {
+                Some((kind, arg)) => (kind, Some(arg)),
+                None => (action.trim(), None),
+            };
+            let action = match (kind, arg) {
+                ("fail", message) => Action::Fail(message.map(str::to_string)),
+                ("delay", Some(ms)) => Action::Delay(Duration::from_millis(
+                    ms.parse().map_err(|err| format!("invalid delay '{ms}': {err}"))?,
+                )),
+                ("drop", Some(n)) if target == Target::Stream => {
+                    Action::Drop(n.parse().map_err(|err| format!("invalid event count '{n}': {err}"))?)
+                },
+                _ => return Err(format!("invalid action '{action}' for '{}'", entry)),
+            };
+            faults.push((target, action));
+        }
+        Ok(Self { faults })
+    }
+
+    fn actions(&self, target: &Target) -> impl Iterator<Item = &Action> {
+        self.faults.iter().filter_map(move |(t, action)| match (t, target) {
+            (Target::Tool(None), Target::Tool(_)) => Some(action),
+            (t, target) if t == target => Some(action),
+            _ => None,
+        })
+    }
+
+    /// Applies the faults configured for invoking the tool `name`.
+    pub async fn tool(&self, name: &str) -> eyre::Result<()> {
+        for action in self.actions(&Target::Tool(Some(name.to_string()))) {
+            match action {
+                Action::Fail(message) => {
+                    eyre::bail!("{}", message.as_deref().unwrap_or("injected tool failure"))
+                },
+                Action::Delay(delay) => tokio::time::sleep(*delay).await,
+                Action::Drop(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the faults configured for sending a chat request.
+    pub async fn send_message(&self) -> Result<(), ApiClientError> {
+        for action in self.actions(&Target::SendMessage) {
+            match action {
+                Action::Fail(message) => {
+                    return Err(ApiClientError::InjectedFault(
+                        message.clone().unwrap_or("injected send_message failure".to_string()),
+                    ));
+                },
+                Action::Delay(delay) => tokio::time::sleep(*delay).await,
+                Action::Drop(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the faults configured for receiving the next event of a chat response, given the
+    /// number of events received so far.
+    pub async fn stream_event(&self, received: usize) -> Result<(), ApiClientError> {
+        for action in self.actions(&Target::Stream) {
+            match action {
+                Action::Fail(message) => {
+                    return Err(ApiClientError::InjectedFault(
+                        message.clone().unwrap_or("injected stream failure".to_string()),
+                    ));
+                },
+                Action::Delay(delay) => tokio::time::sleep(*delay).await,
+                Action::Drop(n) if received >= *n => {
+                    return Err(ApiClientError::InjectedFault(format!(
+                        "connection dropped after {n} events"
+                    )));
+                },
+                Action::Drop(_) => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let injector =
+            FaultInjector::parse("tool:fs_write=fail:disk full, tool:*=delay:10,send_message=fail,stream=drop:3")
+                .unwrap();
+        assert_eq!(injector.faults, vec![
+            (
+                Target::Tool(Some("fs_write".to_string())),
+                Action::Fail(Some("disk full".to_string()))
+            ),
+            (Target::Tool(None), Action::Delay(Duration::from_millis(10))),
+            (Target::SendMessage, Action::Fail(None)),
+            (Target::Stream, Action::Drop(3)),
+        ]);
+
+        assert!(FaultInjector::parse("fs_write=fail").is_err());
+        assert!(FaultInjector::parse("tool:fs_write").is_err());
+        assert!(FaultInjector::parse("tool:fs_write=drop:3").is_err());
+        assert!(FaultInjector::parse("stream=delay:soon").is_err());
+        assert_eq!(FaultInjector::parse("").unwrap(), FaultInjector::default());
+    }
+
+    #[tokio::test]
+    async fn test_faults() {
+        let injector = FaultInjector::parse("tool:fs_write=fail:disk full,tool:*=delay:1,stream=drop:2").unwrap();
+        assert_eq!(injector.tool("fs_write").await.unwrap_err().to_string(), "disk full");
+        assert!(injector.tool("fs_read").await.is_ok());
+        assert!(injector.send_message().await.is_ok());
+        assert!(injector.stream_event(1).await.is_ok());
+        assert!(matches!(
+            injector.stream_event(2).await,
+            Err(ApiClientError::InjectedFault(_))
+        ));
+    }
+}
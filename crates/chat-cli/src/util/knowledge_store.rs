@@ -4,9 +4,12 @@ use std::sync::{
 };
 
 use eyre::Result;
-use semantic_search_client::KnowledgeContext;
 use semantic_search_client::client::AsyncSemanticSearchClient;
 use semantic_search_client::types::SearchResult;
+use semantic_search_client::{
+    FileFilter,
+    KnowledgeContext,
+};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
@@ -66,6 +69,16 @@ impl KnowledgeStore {
 
     /// Add context - delegates to async client
     pub async fn add(&mut self, name: &str, path_str: &str) -> Result<String, String> {
+        self.add_excluding(name, path_str, None).await
+    }
+
+    /// Add context, leaving out the files `exclude` excludes
+    pub async fn add_excluding(
+        &mut self,
+        name: &str,
+        path_str: &str,
+        exclude: Option<FileFilter>,
+    ) -> Result<String, String> {
         let path_buf = std::path::PathBuf::from(path_str);
         let canonical_path = path_buf
             .canonicalize()
@@ -73,7 +86,13 @@ impl KnowledgeStore {
 
         match self
             .client
-            .add_context_from_path(&canonical_path, name, &format!("Knowledge context for {}", name), true)
+            .add_context_from_path_excluding(
+                &canonical_path,
+                name,
+                &format!("Knowledge context for {}", name),
+                true,
+                exclude,
+            )
             .await
         {
             Ok((operation_id, _)) => Ok(format!(
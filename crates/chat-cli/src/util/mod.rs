@@ -1,12 +1,20 @@
+pub mod color;
 pub mod consts;
+pub mod datetime;
 pub mod directories;
+#[cfg(feature = "fault-injection")]
+pub mod fault_injection;
+pub mod injection;
 pub mod knowledge_store;
 pub mod open;
 pub mod process;
+pub mod redact;
 pub mod spinner;
 pub mod system_info;
+pub mod template;
 #[cfg(test)]
 pub mod test;
+pub mod theme;
 
 use std::fmt::Display;
 use std::io::{
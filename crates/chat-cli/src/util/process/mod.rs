@@ -1,3 +1,7 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
 pub use sysinfo::Pid;
 
 #[cfg(target_os = "windows")]
@@ -9,3 +13,15 @@ pub use windows::*;
 mod unix;
 #[cfg(not(windows))]
 pub use unix::*;
+
+/// Resources used by a subprocess, measured with `wait4` when it is reaped. Not measured on
+/// Windows.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// Peak resident set size in KiB
+    pub peak_rss_kb: Option<u64>,
+    /// User and system CPU time in milliseconds
+    pub cpu_time_ms: Option<u64>,
+    /// The signal that terminated the process, if any
+    pub exit_signal: Option<i32>,
+}
@@ -1,11 +1,151 @@
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{
+    ExitStatus,
+    Output,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
 use nix::sys::signal::Signal;
 use sysinfo::Pid;
+use tokio::io::{
+    AsyncRead,
+    AsyncReadExt,
+};
+
+use super::ResourceUsage;
 
 pub fn terminate_process(pid: Pid) -> Result<(), String> {
     let nix_pid = nix::unistd::Pid::from_raw(pid.as_u32() as i32);
     nix::sys::signal::kill(nix_pid, Signal::SIGTERM).map_err(|e| format!("Failed to terminate process: {}", e))
 }
 
+/// A child process whose resource usage is collected with `wait4` on its own pid when it exits.
+///
+/// The process is killed if this is dropped before the process was reaped, e.g. when the tool
+/// times out or is cancelled.
+pub struct MeteredChild {
+    pid: libc::pid_t,
+    reaped: Arc<Mutex<bool>>,
+}
+
+impl MeteredChild {
+    /// Takes over waiting on `child`, which must not be waited on through any other handle.
+    pub fn new(child: &std::process::Child) -> Self {
+        Self {
+            pid: child.id() as libc::pid_t,
+            reaped: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    /// Waits for the process to exit and reaps it.
+    pub async fn wait(&self) -> io::Result<(ExitStatus, ResourceUsage)> {
+        let pid = self.pid;
+        let reaped = Arc::clone(&self.reaped);
+        tokio::task::spawn_blocking(move || reap(pid, &reaped))
+            .await
+            .map_err(io::Error::other)?
+    }
+}
+
+impl Drop for MeteredChild {
+    fn drop(&mut self) {
+        let reaped = self.reaped.lock().unwrap_or_else(|err| err.into_inner());
+        if !*reaped {
+            // The pid can't have been reused since the process has not been reaped yet
+            // SAFETY: kill has no memory safety requirements
+            unsafe { libc::kill(self.pid, libc::SIGKILL) };
+        }
+    }
+}
+
+fn reap(pid: libc::pid_t, reaped: &Mutex<bool>) -> io::Result<(ExitStatus, ResourceUsage)> {
+    // Wait for the exit without reaping, so that the pid can't be reused while a concurrent drop
+    // may still kill it.
+    loop {
+        let mut info = std::mem::MaybeUninit::<libc::siginfo_t>::zeroed();
+        // SAFETY: waitid only writes to the provided struct
+        let res = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                info.as_mut_ptr(),
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if res == 0 {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+
+    let mut reaped = reaped.lock().unwrap_or_else(|err| err.into_inner());
+    let mut status = 0;
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    // SAFETY: wait4 only writes to the provided status and struct
+    let res = unsafe { libc::wait4(pid, &mut status, 0, usage.as_mut_ptr()) };
+    if res != pid {
+        return Err(io::Error::last_os_error());
+    }
+    *reaped = true;
+    // SAFETY: the struct was zero initialized and filled in on success
+    let usage = unsafe { usage.assume_init() };
+
+    let status = ExitStatus::from_raw(status);
+    Ok((status, ResourceUsage {
+        peak_rss_kb: Some(maxrss_kb(&usage)),
+        cpu_time_ms: Some(cpu_time_ms(&usage)),
+        exit_signal: status.signal(),
+    }))
+}
+
+/// Runs `command` to completion like [tokio::process::Command::output], and measures the
+/// resources it used with [MeteredChild].
+pub async fn output_with_usage(command: &mut tokio::process::Command) -> io::Result<(Output, ResourceUsage)> {
+    let mut child = command.as_std_mut().spawn()?;
+    let metered = MeteredChild::new(&child);
+    let stdout = child
+        .stdout
+        .take()
+        .map(tokio::process::ChildStdout::from_std)
+        .transpose()?;
+    let stderr = child
+        .stderr
+        .take()
+        .map(tokio::process::ChildStderr::from_std)
+        .transpose()?;
+
+    let ((status, usage), stdout, stderr) = tokio::try_join!(metered.wait(), read_all(stdout), read_all(stderr))?;
+    Ok((Output { status, stdout, stderr }, usage))
+}
+
+async fn read_all(pipe: Option<impl AsyncRead + Unpin>) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    if let Some(mut pipe) = pipe {
+        pipe.read_to_end(&mut buf).await?;
+    }
+    Ok(buf)
+}
+
+fn cpu_time_ms(usage: &libc::rusage) -> u64 {
+    let ms = |tv: libc::timeval| tv.tv_sec as u64 * 1000 + tv.tv_usec as u64 / 1000;
+    ms(usage.ru_utime) + ms(usage.ru_stime)
+}
+
+fn maxrss_kb(usage: &libc::rusage) -> u64 {
+    // Reported in bytes on macOS and in kilobytes elsewhere
+    match cfg!(target_os = "macos") {
+        true => usage.ru_maxrss as u64 / 1024,
+        false => usage.ru_maxrss as u64,
+    }
+}
+
 #[cfg(test)]
 #[cfg(not(windows))]
 mod tests {
@@ -50,6 +190,40 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    #[allow(clippy::zombie_processes)] // Reaped by the MeteredChild
+    async fn test_metered_child() {
+        let child = Command::new("sh").arg("-c").arg("kill -KILL $$").spawn().unwrap();
+        let (status, usage) = MeteredChild::new(&child).wait().await.unwrap();
+        assert_eq!(status.signal(), Some(Signal::SIGKILL as i32));
+        assert_eq!(usage.exit_signal, Some(Signal::SIGKILL as i32));
+        assert!(usage.cpu_time_ms.is_some());
+        assert!(usage.peak_rss_kb.is_some_and(|kb| kb > 0));
+    }
+
+    #[tokio::test]
+    async fn test_metered_child_killed_on_drop() {
+        let mut child = spawn_test_process();
+        drop(MeteredChild::new(&child));
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(Signal::SIGKILL as i32));
+    }
+
+    #[tokio::test]
+    async fn test_output_with_usage() {
+        let mut command = tokio::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo out; echo err >&2; exit 3")
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let (output, usage) = output_with_usage(&mut command).await.unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+        assert_eq!(usage.exit_signal, None);
+    }
+
     #[test]
     fn test_terminate_nonexistent_process() {
         // Use a likely invalid PID
@@ -1,4 +1,5 @@
 use std::ops::Deref;
+use std::process::Output;
 
 use sysinfo::Pid;
 use windows::Win32::Foundation::{
@@ -11,6 +12,14 @@ use windows::Win32::System::Threading::{
     TerminateProcess,
 };
 
+use super::ResourceUsage;
+
+/// Runs `command` to completion like [tokio::process::Command::output]. Resource usage of child
+/// processes is not measured on Windows.
+pub async fn output_with_usage(command: &mut tokio::process::Command) -> std::io::Result<(Output, ResourceUsage)> {
+    Ok((command.output().await?, ResourceUsage::default()))
+}
+
 /// Terminate a process on Windows using the Windows API
 pub fn terminate_process(pid: Pid) -> Result<(), String> {
     unsafe {
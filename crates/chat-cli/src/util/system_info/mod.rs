@@ -175,7 +175,70 @@ pub fn in_wsl() -> bool {
 /// Is the calling binary running on a remote instance
 pub fn is_remote() -> bool {
     // TODO(chay): Add detection for inside docker container
-    in_ssh() || in_wsl() || std::env::var_os("Q_FAKE_IS_REMOTE").is_some()
+    in_ssh() || in_wsl() || cloud_environment().is_some() || std::env::var_os("Q_FAKE_IS_REMOTE").is_some()
+}
+
+/// An AWS environment the CLI runs in. None of them can open a browser on the machine of the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudEnvironment {
+    CloudShell,
+    Cloud9,
+    Ec2,
+}
+
+impl CloudEnvironment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloudEnvironment::CloudShell => "CloudShell",
+            CloudEnvironment::Cloud9 => "Cloud9",
+            CloudEnvironment::Ec2 => "EC2",
+        }
+    }
+}
+
+impl std::fmt::Display for CloudEnvironment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// CloudShell and Cloud9 are told apart by their environment variables, which are set in every
+/// shell they open. `on_ec2` is only checked when neither is found, since both run on EC2.
+fn detect_cloud_environment(env: &Env, on_ec2: impl FnOnce() -> bool) -> Option<CloudEnvironment> {
+    if env.get("AWS_EXECUTION_ENV").is_ok_and(|v| v == "CloudShell") {
+        Some(CloudEnvironment::CloudShell)
+    } else if env.get_os("C9_USER").is_some() || env.get_os("C9_PROJECT").is_some() {
+        Some(CloudEnvironment::Cloud9)
+    } else if on_ec2() {
+        Some(CloudEnvironment::Ec2)
+    } else {
+        None
+    }
+}
+
+/// Test if the machine is an EC2 instance, from what the hypervisor reports without asking the
+/// instance metadata service, which may not be reachable.
+fn on_ec2() -> bool {
+    cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            let read = |path: &str| std::fs::read_to_string(path).unwrap_or_default().to_ascii_lowercase();
+            // Nitro instances set the instance id as the asset tag, Xen ones prefix the uuid
+            read("/sys/devices/virtual/dmi/id/board_asset_tag").starts_with("i-")
+                || read("/sys/hypervisor/uuid").starts_with("ec2")
+        } else {
+            false
+        }
+    }
+}
+
+/// The AWS environment the CLI runs in, if any.
+pub fn cloud_environment() -> Option<CloudEnvironment> {
+    static CLOUD_ENVIRONMENT: OnceLock<Option<CloudEnvironment>> = OnceLock::new();
+    *CLOUD_ENVIRONMENT.get_or_init(|| detect_cloud_environment(&Env::new(), on_ec2))
+}
+
+pub fn in_cloudshell() -> bool {
+    cloud_environment() == Some(CloudEnvironment::CloudShell)
 }
 
 pub fn in_codespaces() -> bool {
@@ -188,3 +251,22 @@ pub fn in_ci() -> bool {
     static IN_CI: OnceLock<bool> = OnceLock::new();
     *IN_CI.get_or_init(|| std::env::var_os("CI").is_some() || std::env::var_os("Q_CI").is_some())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_cloud_environment() {
+        let env = Env::from_slice(&[("AWS_EXECUTION_ENV", "CloudShell")]);
+        assert_eq!(
+            detect_cloud_environment(&env, || true),
+            Some(CloudEnvironment::CloudShell)
+        );
+        let env = Env::from_slice(&[("C9_USER", "ec2-user")]);
+        assert_eq!(detect_cloud_environment(&env, || true), Some(CloudEnvironment::Cloud9));
+        let env = Env::from_slice(&[("AWS_EXECUTION_ENV", "AWS_ECS_FARGATE")]);
+        assert_eq!(detect_cloud_environment(&env, || true), Some(CloudEnvironment::Ec2));
+        assert_eq!(detect_cloud_environment(&env, || false), None);
+    }
+}
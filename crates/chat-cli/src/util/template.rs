@@ -0,0 +1,50 @@
+//! Substitution of `{name}` variables in user configurable strings, e.g. the chat prompt set with
+//! `chat.promptTemplate`.
+
+/// Replaces every `{name}` in `template` with the value of the variable `name`. Placeholders of
+/// unknown variables are kept as they are, and `{{` and `}}` produce literal braces.
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut res = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            res.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let Some(end) = rest
+            .find('}')
+            .filter(|&end| rest.starts_with('{') && !rest[1..end].contains('{'))
+        else {
+            res.push_str(&rest[..1]);
+            rest = &rest[1..];
+            continue;
+        };
+        let name = &rest[1..end];
+        match vars.iter().find(|(var, _)| *var == name) {
+            Some((_, value)) => res.push_str(value),
+            None => res.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    res.push_str(rest);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let vars = [("agent", "dev"), ("model_short", "claude-4-sonnet")];
+        assert_eq!(render("{agent}:{model_short} ❯ ", &vars), "dev:claude-4-sonnet ❯ ");
+        assert_eq!(render("{unknown} {agent}", &vars), "{unknown} dev");
+        assert_eq!(render("{{agent}} }{", &vars), "{agent} }{");
+        assert_eq!(render("unterminated {agent", &vars), "unterminated {agent");
+        assert_eq!(render("{a {agent}", &vars), "{a dev");
+        assert_eq!(render("", &vars), "");
+    }
+}
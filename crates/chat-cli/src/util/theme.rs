@@ -0,0 +1,196 @@
+//! Colors of the chat UI by semantic role, selected with `chat.theme`.
+
+use std::sync::OnceLock;
+
+use crossterm::style::Color;
+
+use crate::os::Env;
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// The colors used for each semantic role of the UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Plain text of assistant responses
+    pub assistant_text: Color,
+    /// Markdown headings
+    pub heading: Color,
+    /// Inline code and code blocks
+    pub code: Color,
+    /// Block quotes
+    pub quote: Color,
+    /// Link text
+    pub link_text: Color,
+    /// Link targets
+    pub link_url: Color,
+    /// Tool names and the header of tool blocks
+    pub tool: Color,
+    /// Labels, e.g. the purpose of a command or the markers of citations
+    pub label: Color,
+    /// Informational messages
+    pub info: Color,
+    /// Hints, status lines and other de-emphasized text
+    pub secondary: Color,
+    pub success: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub diff_add: Color,
+    pub diff_remove: Color,
+    /// Background of the gutter and of the line for added lines in truecolor diffs
+    pub diff_add_bg: (Color, Color),
+    /// Background of the gutter and of the line for removed lines in truecolor diffs
+    pub diff_remove_bg: (Color, Color),
+}
+
+const fn rgb(hex: u32) -> Color {
+    Color::Rgb {
+        r: (hex >> 16) as u8,
+        g: (hex >> 8) as u8,
+        b: hex as u8,
+    }
+}
+
+impl Theme {
+    pub const DARK: Self = Self {
+        assistant_text: Color::Reset,
+        heading: Color::Magenta,
+        code: Color::Green,
+        quote: Color::DarkGrey,
+        link_text: Color::Blue,
+        link_url: Color::DarkGrey,
+        tool: Color::Magenta,
+        label: Color::Blue,
+        info: Color::Cyan,
+        secondary: Color::DarkGrey,
+        success: Color::Green,
+        warning: Color::Yellow,
+        error: Color::Red,
+        diff_add: Color::Green,
+        diff_remove: Color::Red,
+        diff_add_bg: (rgb(0x28432b), rgb(0x18261e)),
+        diff_remove_bg: (rgb(0x4f2828), rgb(0x24191c)),
+    };
+    pub const LIGHT: Self = Self {
+        assistant_text: Color::Reset,
+        heading: Color::DarkMagenta,
+        code: Color::DarkGreen,
+        quote: Color::DarkGrey,
+        link_text: Color::DarkBlue,
+        link_url: Color::DarkGrey,
+        tool: Color::DarkMagenta,
+        label: Color::DarkBlue,
+        info: Color::DarkCyan,
+        secondary: Color::DarkGrey,
+        success: Color::DarkGreen,
+        warning: Color::DarkYellow,
+        error: Color::DarkRed,
+        diff_add: Color::DarkGreen,
+        diff_remove: Color::DarkRed,
+        diff_add_bg: (rgb(0xb4e2b4), rgb(0xdcf5dc)),
+        diff_remove_bg: (rgb(0xf0b4b4), rgb(0xfae1e1)),
+    };
+    pub const SOLARIZED_DARK: Self = Self {
+        assistant_text: rgb(0x839496),
+        heading: rgb(0xd33682),
+        code: rgb(0x859900),
+        quote: rgb(0x586e75),
+        link_text: rgb(0x268bd2),
+        link_url: rgb(0x586e75),
+        tool: rgb(0x6c71c4),
+        label: rgb(0x268bd2),
+        info: rgb(0x2aa198),
+        secondary: rgb(0x586e75),
+        success: rgb(0x859900),
+        warning: rgb(0xb58900),
+        error: rgb(0xdc322f),
+        diff_add: rgb(0x859900),
+        diff_remove: rgb(0xdc322f),
+        diff_add_bg: (rgb(0x1c4236), rgb(0x0b3631)),
+        diff_remove_bg: (rgb(0x4a2a2e), rgb(0x2b2b30)),
+    };
+    pub const SOLARIZED_LIGHT: Self = Self {
+        assistant_text: rgb(0x657b83),
+        heading: rgb(0xd33682),
+        code: rgb(0x859900),
+        quote: rgb(0x93a1a1),
+        link_text: rgb(0x268bd2),
+        link_url: rgb(0x93a1a1),
+        tool: rgb(0x6c71c4),
+        label: rgb(0x268bd2),
+        info: rgb(0x2aa198),
+        secondary: rgb(0x93a1a1),
+        success: rgb(0x859900),
+        warning: rgb(0xb58900),
+        error: rgb(0xdc322f),
+        diff_add: rgb(0x859900),
+        diff_remove: rgb(0xdc322f),
+        diff_add_bg: (rgb(0xdde6b8), rgb(0xf0efcf)),
+        diff_remove_bg: (rgb(0xf5c9bd), rgb(0xfbe3d6)),
+    };
+
+    /// The theme named by `chat.theme`: `dark`, `light`, `solarized-dark`, `solarized-light`, or
+    /// `auto` and `solarized` to pick the dark or light variant from the terminal background.
+    pub fn from_name(name: Option<&str>, light_background: bool) -> Result<Self, String> {
+        Ok(match (name.unwrap_or("auto"), light_background) {
+            ("dark", _) | ("auto", false) => Self::DARK,
+            ("light", _) | ("auto", true) => Self::LIGHT,
+            ("solarized-dark", _) | ("solarized", false) => Self::SOLARIZED_DARK,
+            ("solarized-light", _) | ("solarized", true) => Self::SOLARIZED_LIGHT,
+            (other, _) => {
+                return Err(format!(
+                    "unknown theme '{other}', expected one of auto, dark, light, solarized, solarized-dark, solarized-light"
+                ));
+            },
+        })
+    }
+}
+
+/// Whether the terminal has a light background according to `COLORFGBG`, which terminals such as
+/// rxvt, Konsole and iTerm2 set to `<fg>;<bg>` using the 16 ANSI color indices.
+pub fn light_background(env: &Env) -> bool {
+    env.get("COLORFGBG")
+        .ok()
+        .and_then(|value| value.rsplit(';').next().and_then(|bg| bg.parse::<u8>().ok()))
+        .is_some_and(|bg| bg == 7 || bg >= 9)
+}
+
+/// Sets the theme used for the rest of the process. Only the first call has an effect.
+pub fn init(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// The current theme, [Theme::DARK] unless [init] selected another one.
+pub fn theme() -> &'static Theme {
+    THEME.get().unwrap_or(&Theme::DARK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Theme::from_name(None, false).unwrap(), Theme::DARK);
+        assert_eq!(Theme::from_name(None, true).unwrap(), Theme::LIGHT);
+        assert_eq!(Theme::from_name(Some("dark"), true).unwrap(), Theme::DARK);
+        assert_eq!(
+            Theme::from_name(Some("solarized"), true).unwrap(),
+            Theme::SOLARIZED_LIGHT
+        );
+        assert_eq!(
+            Theme::from_name(Some("solarized-dark"), true).unwrap(),
+            Theme::SOLARIZED_DARK
+        );
+        assert!(Theme::from_name(Some("neon"), false).is_err());
+    }
+
+    #[test]
+    fn test_light_background() {
+        let env = |value: &str| Env::from_slice(&[("COLORFGBG", value)]);
+        assert!(!light_background(&Env::from_slice(&[])));
+        assert!(!light_background(&env("15;0")));
+        assert!(light_background(&env("0;15")));
+        assert!(light_background(&env("0;default;7")));
+        assert!(!light_background(&env("garbage")));
+    }
+}
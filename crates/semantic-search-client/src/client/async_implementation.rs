@@ -33,6 +33,7 @@ use crate::error::{
 use crate::types::{
     ContextId,
     DataPoint,
+    FileFilter,
     IndexingJob,
     KnowledgeContext,
     OperationHandle,
@@ -171,6 +172,19 @@ impl AsyncSemanticSearchClient {
         name: &str,
         description: &str,
         persistent: bool,
+    ) -> Result<(Uuid, CancellationToken)> {
+        self.add_context_from_path_excluding(path, name, description, persistent, None)
+            .await
+    }
+
+    /// Add a context from a path, leaving out the files `exclude` excludes (async, cancellable)
+    pub async fn add_context_from_path_excluding(
+        &self,
+        path: impl AsRef<Path>,
+        name: &str,
+        description: &str,
+        persistent: bool,
+        exclude: Option<FileFilter>,
     ) -> Result<(Uuid, CancellationToken)> {
         let path = path.as_ref();
         let canonical_path = path.canonicalize().map_err(|_e| {
@@ -202,6 +216,7 @@ impl AsyncSemanticSearchClient {
             name: name.to_string(),
             description: description.to_string(),
             persistent,
+            exclude,
         };
 
         self.job_tx
@@ -728,8 +743,9 @@ impl BackgroundWorker {
                     name,
                     description,
                     persistent,
+                    exclude,
                 } => {
-                    self.process_add_directory(id, path, name, description, persistent, cancel)
+                    self.process_add_directory(id, path, name, description, persistent, exclude, cancel)
                         .await;
                 },
                 IndexingJob::Clear { id, cancel } => {
@@ -741,6 +757,7 @@ impl BackgroundWorker {
         tracing::info!("Background worker stopped");
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn process_add_directory(
         &self,
         operation_id: Uuid,
@@ -748,6 +765,7 @@ impl BackgroundWorker {
         name: String,
         description: String,
         persistent: bool,
+        exclude: Option<FileFilter>,
         cancel_token: CancellationToken,
     ) {
         tracing::info!("Processing AddDirectory job: {} -> {}", name, path.display());
@@ -793,7 +811,7 @@ impl BackgroundWorker {
 
         // Perform actual indexing
         let result = self
-            .perform_indexing(operation_id, path, name, description, persistent, cancel_token)
+            .perform_indexing(operation_id, path, name, description, persistent, exclude, cancel_token)
             .await;
 
         match result {
@@ -808,6 +826,7 @@ impl BackgroundWorker {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn perform_indexing(
         &self,
         operation_id: Uuid,
@@ -815,6 +834,7 @@ impl BackgroundWorker {
         name: String,
         description: String,
         persistent: bool,
+        exclude: Option<FileFilter>,
         cancel_token: CancellationToken,
     ) -> std::result::Result<String, String> {
         if !path.exists() {
@@ -887,7 +907,7 @@ impl BackgroundWorker {
 
         // Process files with cancellation checks
         let items = self
-            .process_directory_files(&path, file_count, operation_id, &cancel_token_clone)
+            .process_directory_files(&path, exclude.as_ref(), file_count, operation_id, &cancel_token_clone)
             .await?;
 
         // Check cancellation before creating semantic context
@@ -1270,6 +1290,7 @@ impl BackgroundWorker {
     async fn process_directory_files(
         &self,
         dir_path: &Path,
+        exclude: Option<&FileFilter>,
         file_count: usize,
         operation_id: Uuid,
         cancel_token: &CancellationToken,
@@ -1304,6 +1325,10 @@ impl BackgroundWorker {
                 continue;
             }
 
+            if exclude.is_some_and(|exclude| exclude.excludes(path)) {
+                continue;
+            }
+
             // Process the file
             match process_file(path) {
                 Ok(mut file_items) => items.append(&mut file_items),
@@ -30,6 +30,7 @@ pub use error::{
 };
 pub use types::{
     DataPoint,
+    FileFilter,
     FileType,
     KnowledgeContext,
     OperationStatus,
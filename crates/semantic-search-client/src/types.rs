@@ -1,5 +1,9 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{
+    Path,
+    PathBuf,
+};
 use std::sync::{
     Arc,
     Mutex,
@@ -292,6 +296,28 @@ impl ProgressInfo {
     }
 }
 
+/// Decides which files of a directory are left out when it is indexed
+#[derive(Clone)]
+pub struct FileFilter(Arc<dyn Fn(&Path) -> bool + Send + Sync>);
+
+impl FileFilter {
+    /// Creates a filter that leaves out the files for which `exclude` returns true
+    pub fn new(exclude: impl Fn(&Path) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(exclude))
+    }
+
+    /// Whether the file at `path` is left out
+    pub fn excludes(&self, path: &Path) -> bool {
+        (self.0)(path)
+    }
+}
+
+impl fmt::Debug for FileFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FileFilter")
+    }
+}
+
 /// Background indexing job (internal implementation detail)
 #[derive(Debug)]
 pub(crate) enum IndexingJob {
@@ -302,6 +328,7 @@ pub(crate) enum IndexingJob {
         name: String,
         description: String,
         persistent: bool,
+        exclude: Option<FileFilter>,
     },
     Clear {
         id: Uuid,